@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use openfhe_development_rs::fuzzing;
+
+fuzz_target!(|data: &[u8]| {
+    let mut json = serde_json::Deserializer::from_slice(data);
+    fuzzing::ciphertext(&mut json);
+});
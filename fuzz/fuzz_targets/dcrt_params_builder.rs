@@ -0,0 +1,55 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use crypto_bigint::{Odd, U64};
+use libfuzzer_sys::fuzz_target;
+use openfhe_development_rs::core::lattice::params::DcrtElementParamsBuilder;
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    ciphertext_order: u16,
+    modulus: Option<u64>,
+    depth: Option<u8>,
+    bits: Option<u8>,
+    moduli: Option<Vec<u64>>,
+    roots_of_unity: Option<Vec<u64>>,
+    big_moduli: Option<Vec<u64>>,
+    big_roots_of_unity: Option<Vec<u64>>,
+}
+
+fn odd(values: Vec<u64>) -> Vec<Odd<U64>> {
+    values
+        .into_iter()
+        .filter_map(|v| Option::from(Odd::new(U64::from_u64(v))))
+        .collect()
+}
+
+fn uint(values: Vec<u64>) -> Vec<U64> {
+    values.into_iter().map(U64::from_u64).collect()
+}
+
+fuzz_target!(|input: Input| {
+    let mut builder = DcrtElementParamsBuilder::new(input.ciphertext_order as usize);
+    if let Some(modulus) = input.modulus.and_then(|m| Option::from(Odd::new(U64::from_u64(m)))) {
+        builder = builder.modulus(modulus);
+    }
+    if let Some(depth) = input.depth {
+        builder = builder.depth(depth as usize);
+    }
+    if let Some(bits) = input.bits {
+        builder = builder.bits(bits as usize);
+    }
+    if let Some(moduli) = input.moduli {
+        builder = builder.moduli(odd(moduli));
+    }
+    if let Some(roots) = input.roots_of_unity {
+        builder = builder.roots_of_unity(uint(roots));
+    }
+    if let Some(big_moduli) = input.big_moduli {
+        builder = builder.big_moduli(odd(big_moduli));
+    }
+    if let Some(roots) = input.big_roots_of_unity {
+        builder = builder.big_roots_of_unity(uint(roots));
+    }
+    let _ = builder.build();
+});
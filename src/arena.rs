@@ -0,0 +1,227 @@
+//! Capped contexts for memory constrained deployments
+//!
+//! A [`CryptoContext`](crate::pke::CryptoContext) built with
+//! [`with_arena`](crate::pke::CryptoContext::with_arena) owns a fixed number
+//! of slots for plaintexts and for ciphertexts. Every operation producing one
+//! takes its slot up front in a single atomic check-and-increment and fails
+//! with [`Error::ArenaExhausted`] when none is left, which lets enclave
+//! deployments bound their memory use up front. Dropping a value frees its
+//! slot.
+//!
+//! Plain clones of a value hold no slot, use
+//! [`Ciphertext::try_clone`](crate::ciphertext::Ciphertext::try_clone) for a
+//! copy that counts against the cap.
+
+use crate::error::{Error, Result};
+use derive_more::Display;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// What an arena slot holds
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Display)]
+pub enum ArenaKind {
+    /// A [`PlaintextEncodings`](crate::encoding::PlaintextEncodings)
+    Plaintext,
+    /// A [`Ciphertext`](crate::ciphertext::Ciphertext)
+    Ciphertext,
+}
+
+/// The caps of a context, how many values of each kind may be alive at once
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ArenaConfig {
+    /// Plaintexts from encoding, decryption and slot masks
+    pub plaintexts: usize,
+    /// Ciphertexts from encryption and evaluation
+    pub ciphertexts: usize,
+}
+
+/// The slots of a capped context, shared by its clones and the values they
+/// produce
+#[derive(Debug)]
+pub struct Arena {
+    config: ArenaConfig,
+    plaintexts: AtomicUsize,
+    ciphertexts: AtomicUsize,
+}
+
+impl PartialEq for Arena {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for Arena {}
+
+impl Arena {
+    /// A fresh arena with every slot free
+    pub fn new(config: ArenaConfig) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            plaintexts: AtomicUsize::new(0),
+            ciphertexts: AtomicUsize::new(0),
+        })
+    }
+
+    /// The caps
+    pub fn config(&self) -> ArenaConfig {
+        self.config
+    }
+
+    /// The number of values of `kind` alive
+    pub fn live(&self, kind: ArenaKind) -> usize {
+        self.counter(kind).load(Ordering::Acquire)
+    }
+
+    /// The cap on values of `kind`
+    pub fn capacity(&self, kind: ArenaKind) -> usize {
+        match kind {
+            ArenaKind::Plaintext => self.config.plaintexts,
+            ArenaKind::Ciphertext => self.config.ciphertexts,
+        }
+    }
+
+    /// Take a slot for a new value of `kind`, or fail if all are in use
+    pub(crate) fn lease(self: &Arc<Self>, kind: ArenaKind) -> Result<ArenaLease> {
+        let capacity = self.capacity(kind);
+        self.counter(kind)
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |live| {
+                (live < capacity).then_some(live + 1)
+            })
+            .map_err(|_| Error::ArenaExhausted { kind, capacity })?;
+        Ok(ArenaLease {
+            slot: Some((self.clone(), kind)),
+        })
+    }
+
+    fn counter(&self, kind: ArenaKind) -> &AtomicUsize {
+        match kind {
+            ArenaKind::Plaintext => &self.plaintexts,
+            ArenaKind::Ciphertext => &self.ciphertexts,
+        }
+    }
+}
+
+/// The slot of a value in an [`Arena`], released when the value is dropped.
+///
+/// Values of uncapped contexts, deserialized values and plain clones hold
+/// none.
+#[derive(Debug, Default)]
+pub(crate) struct ArenaLease {
+    slot: Option<(Arc<Arena>, ArenaKind)>,
+}
+
+impl Drop for ArenaLease {
+    fn drop(&mut self) {
+        if let Some((arena, kind)) = &self.slot {
+            arena.counter(*kind).fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pke::test_support::bfv_context;
+    use std::sync::Barrier;
+    use std::thread;
+
+    #[test]
+    fn capped_contexts_fail_instead_of_growing() {
+        let bfv = bfv_context(0).with_arena(ArenaConfig {
+            plaintexts: 1,
+            ciphertexts: 2,
+        });
+        let cc = bfv.crypto_context();
+        let arena = cc.arena().expect("a capped context");
+        let keys = bfv.key_gen().expect("keys");
+        let plaintext = bfv.encode(&[1, 2, 3]).expect("encode");
+        assert!(matches!(
+            bfv.encode(&[4]),
+            Err(Error::ArenaExhausted {
+                kind: ArenaKind::Plaintext,
+                capacity: 1
+            })
+        ));
+
+        let a = bfv.encrypt(keys.public_key(), &plaintext).expect("encrypt");
+        let b = bfv.encrypt(keys.public_key(), &plaintext).expect("encrypt");
+        assert_eq!(arena.live(ArenaKind::Ciphertext), 2);
+        assert!(matches!(
+            bfv.encrypt(keys.public_key(), &plaintext),
+            Err(Error::ArenaExhausted {
+                kind: ArenaKind::Ciphertext,
+                capacity: 2
+            })
+        ));
+        assert!(matches!(
+            cc.eval_add(&a, &b),
+            Err(Error::ArenaExhausted { .. })
+        ));
+        // A decryption needs a plaintext slot too
+        assert!(matches!(
+            bfv.decrypt(keys.private_key(), &a),
+            Err(Error::ArenaExhausted { .. })
+        ));
+
+        drop(b);
+        drop(plaintext);
+        let sum = cc.eval_add(&a, &a).expect("room for the sum");
+        assert_eq!(arena.live(ArenaKind::Ciphertext), 2);
+        let crate::encoding::PlaintextEncodings::Coefficient(decrypted) =
+            bfv.decrypt(keys.private_key(), &sum).expect("decrypt")
+        else {
+            panic!("a coefficient plaintext");
+        };
+        let mut expected = vec![0; 2048];
+        expected[..3].copy_from_slice(&[2, 4, 6]);
+        assert_eq!(decrypted.coefficients(), expected);
+        assert_eq!(arena.live(ArenaKind::Plaintext), 1);
+    }
+
+    #[test]
+    fn concurrent_copies_stop_at_the_cap() {
+        const THREADS: usize = 8;
+        let bfv = bfv_context(0).with_arena(ArenaConfig {
+            plaintexts: 1,
+            ciphertexts: 3,
+        });
+        let arena = bfv.crypto_context().arena().expect("a capped context");
+        let keys = bfv.key_gen().expect("keys");
+        let plaintext = bfv.encode(&[1, 2, 3]).expect("encode");
+        let ciphertext = bfv.encrypt(keys.public_key(), &plaintext).expect("encrypt");
+        // Plain clones hold no slot
+        let untracked = ciphertext.clone();
+        assert_eq!(arena.live(ArenaKind::Ciphertext), 1);
+
+        let barrier = Barrier::new(THREADS);
+        let copies = thread::scope(|s| {
+            let handles = (0..THREADS)
+                .map(|_| {
+                    s.spawn(|| {
+                        barrier.wait();
+                        (0..2).map(|_| ciphertext.try_clone()).collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("thread"))
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(copies.iter().filter(|c| c.is_ok()).count(), 2);
+        assert!(copies.iter().filter(|c| c.is_err()).all(|c| matches!(
+            c,
+            Err(Error::ArenaExhausted {
+                kind: ArenaKind::Ciphertext,
+                capacity: 3
+            })
+        )));
+        assert_eq!(arena.live(ArenaKind::Ciphertext), 3);
+
+        drop(copies);
+        drop(untracked);
+        assert_eq!(arena.live(ArenaKind::Ciphertext), 1);
+        drop(ciphertext);
+        assert_eq!(arena.live(ArenaKind::Ciphertext), 0);
+    }
+}
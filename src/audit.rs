@@ -0,0 +1,89 @@
+//! Opt-in provenance traces for ciphertexts
+//!
+//! With the `audit` feature every ciphertext carries an [`AuditTrail`] of the
+//! operations that produced it and fingerprints of their operands. A
+//! downstream user reporting a wrong result can attach the trail, which
+//! reveals the circuit shape but nothing about the encrypted values. Without
+//! the feature the trail does not exist and recording compiles to nothing.
+
+use blake2::Blake2b;
+use blake2::digest::Digest;
+use blake2::digest::consts::U16;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+/// A short hash identifying ciphertext contents
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Fingerprint(pub [u8; 16]);
+
+impl Fingerprint {
+    /// Hash the encoded elements of a ciphertext together with its level
+    pub fn of_chunks(chunks: impl IntoIterator<Item = impl AsRef<[u8]>>, level: usize) -> Self {
+        let mut hasher = Blake2b::<U16>::new();
+        hasher.update((level as u64).to_le_bytes());
+        for chunk in chunks {
+            hasher.update(chunk);
+        }
+        Self(hasher.finalize().into())
+    }
+}
+
+impl Display for Fingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
+/// One operation in an [`AuditTrail`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// The name of the operation
+    pub op: String,
+    /// The operands other than the ciphertext itself
+    pub operands: Vec<Fingerprint>,
+    /// The ciphertext after the operation
+    pub result: Fingerprint,
+}
+
+/// The operations applied to a ciphertext in order
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditTrail {
+    entries: Vec<AuditEntry>,
+}
+
+impl AuditTrail {
+    /// The recorded operations, oldest first
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+
+    /// Whether nothing was recorded
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Append an operation
+    pub fn record(&mut self, op: &str, operands: Vec<Fingerprint>, result: Fingerprint) {
+        self.entries.push(AuditEntry {
+            op: op.to_string(),
+            operands,
+            result,
+        });
+    }
+}
+
+impl Display for AuditTrail {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, entry) in self.entries.iter().enumerate() {
+            write!(f, "{i}: {}(", entry.op)?;
+            for (j, operand) in entry.operands.iter().enumerate() {
+                if j > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{operand}")?;
+            }
+            writeln!(f, ") -> {}", entry.result)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+//! A fixed micro-benchmark suite for deploy time checks
+//!
+//! [`self_benchmark`] times the ring operations that dominate homomorphic
+//! evaluation. Comparing the report against a known good host catches debug
+//! builds, missing CPU features or an overloaded machine before the node
+//! starts serving.
+
+use crate::constants::SecurityLevel;
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::core::lattice::params::DcrtElementParamsBuilder;
+use crate::core::lattice::poly::Poly;
+use crate::core::math::{CpuLevel, DiscreteGaussian, cpu_level};
+use crate::error::Result;
+use crate::pke::{CcParams, CkksParamsGen};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+const NTT_ORDER: usize = 4096;
+const MULT_DEPTH: usize = 5;
+const TOWER_BITS: usize = 50;
+const ITERATIONS: u32 = 16;
+const ERROR_STD_DEV: f64 = 3.2;
+
+/// The timing of one benchmark in the suite
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BenchmarkEntry {
+    /// A stable name to match entries between reports
+    pub name: String,
+    /// The number of timed runs
+    pub iterations: u32,
+    /// The fastest run
+    pub min: Duration,
+    /// The mean over all runs
+    pub mean: Duration,
+}
+
+/// The result of [`self_benchmark`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    /// The instruction set the kernels ran with
+    pub cpu_level: CpuLevel,
+    /// Whether the library was built with debug assertions
+    pub debug_build: bool,
+    /// One entry per benchmark
+    pub entries: Vec<BenchmarkEntry>,
+}
+
+/// Run the suite:
+///
+/// - `ntt-4096`: a polynomial product through forward and inverse NTTs of length 4096
+/// - `dcrt-mult-depth-5`: five chained CKKS squarings with relinearization
+///   and rescaling over 50 bit towers
+/// - `rlwe-keygen`: sampling a secret and error and computing `-a * s + e`
+pub fn self_benchmark() -> Result<BenchmarkReport> {
+    let mut rng = rand::rng();
+    let mut small =
+        |len: usize| -> Vec<i64> { (0..len).map(|_| rng.random_range(-8..=8)).collect() };
+
+    let single = DcrtElementParamsBuilder::new(NTT_ORDER)
+        .depth(1)
+        .bits(crate::constants::MAX_MODULUS_SIZE)
+        .build()?[0];
+    let rns = DcrtElementParamsBuilder::new(NTT_ORDER)
+        .depth(MULT_DEPTH + 1)
+        .bits(TOWER_BITS)
+        .build()?;
+
+    let mut entries = Vec::with_capacity(3);
+
+    let a = Poly::from_signed(single, &small(NTT_ORDER));
+    let b = Poly::from_signed(single, &small(NTT_ORDER));
+    entries.push(time("ntt-4096", || {
+        std::hint::black_box(&a * &b);
+        Ok(())
+    })?);
+
+    let ckks = CkksParamsGen::new()
+        .cc_params(CcParams {
+            security_level: SecurityLevel::HeStdNotSet,
+            ..Default::default()
+        })
+        .multiplicative_depth(MULT_DEPTH)
+        .scaling_mod_size(TOWER_BITS)
+        .ring_dimension(NTT_ORDER / 2)
+        .build()?;
+    let (cc, params) = (ckks.crypto_context(), ckks.crypto_parameters());
+    let key_pair = ckks.key_gen()?;
+    let relin_key = ckks.eval_mult_key_gen(key_pair.private_key())?;
+    let values = small(ckks.slots())
+        .into_iter()
+        .map(|v| v as f64 / 8.0)
+        .collect::<Vec<_>>();
+    let x = ckks.encrypt(key_pair.public_key(), &ckks.encode(&values)?)?;
+    entries.push(time("dcrt-mult-depth-5", || {
+        let mut acc = x.clone();
+        for _ in 0..MULT_DEPTH {
+            acc = cc.mod_reduce(&cc.eval_mult(params, &acc, &acc, &relin_key)?)?;
+        }
+        std::hint::black_box(acc);
+        Ok(())
+    })?);
+
+    let mut gaussian = DiscreteGaussian::new(ERROR_STD_DEV).expect("a valid standard deviation");
+    gaussian.set_std_dev(ERROR_STD_DEV);
+    let public = DcrtPoly::from_signed(rns.clone(), &small(NTT_ORDER));
+    let mut s = public.clone();
+    let mut b = public.clone();
+    entries.push(time("rlwe-keygen", || {
+        s.fill_discrete_gaussian(&mut gaussian);
+        b.fill_discrete_gaussian(&mut gaussian);
+        let mut a_s = public.clone();
+        a_s *= &s;
+        b -= &a_s;
+        std::hint::black_box(&b);
+        Ok(())
+    })?);
+
+    Ok(BenchmarkReport {
+        cpu_level: cpu_level(),
+        debug_build: cfg!(debug_assertions),
+        entries,
+    })
+}
+
+fn time(name: &str, mut f: impl FnMut() -> Result<()>) -> Result<BenchmarkEntry> {
+    // Warm caches and the scratch pools before timing
+    f()?;
+    let mut min = Duration::MAX;
+    let mut total = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        f()?;
+        let elapsed = start.elapsed();
+        min = min.min(elapsed);
+        total += elapsed;
+    }
+    Ok(BenchmarkEntry {
+        name: name.to_string(),
+        iterations: ITERATIONS,
+        min,
+        mean: total / ITERATIONS,
+    })
+}
@@ -0,0 +1,258 @@
+//! Checkpoint and restore of long running evaluations
+//!
+//! A [`Checkpoint`] captures everything needed to resume a computation on
+//! another worker: the live intermediate ciphertexts, the operation counters
+//! and, when sampling is deterministic, the exact position of the RNG stream.
+//! It is plain serde data so any format can carry it between machines.
+
+use crate::error::{Error, Result};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The checkpoint format written by this version of the library
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// The operations evaluated by every crypto context of the process
+static COUNTERS: AtomicCounters = AtomicCounters {
+    additions: AtomicU64::new(0),
+    multiplications: AtomicU64::new(0),
+    rotations: AtomicU64::new(0),
+    key_switches: AtomicU64::new(0),
+    rescales: AtomicU64::new(0),
+    bootstraps: AtomicU64::new(0),
+};
+
+struct AtomicCounters {
+    additions: AtomicU64,
+    multiplications: AtomicU64,
+    rotations: AtomicU64,
+    key_switches: AtomicU64,
+    rescales: AtomicU64,
+    bootstraps: AtomicU64,
+}
+
+/// Homomorphic operation counters used for cost accounting
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EvalCounters {
+    /// Additions and subtractions
+    pub additions: u64,
+    /// Ciphertext multiplications
+    pub multiplications: u64,
+    /// Rotations and other automorphisms
+    pub rotations: u64,
+    /// Key switches, including relinearizations
+    pub key_switches: u64,
+    /// Rescales and modulus reductions
+    pub rescales: u64,
+    /// Bootstraps
+    pub bootstraps: u64,
+}
+
+impl EvalCounters {
+    /// The operations evaluated so far by every crypto context of the
+    /// process, including those a bootstrap or a polynomial is made of
+    pub fn current() -> Self {
+        Self {
+            additions: COUNTERS.additions.load(Ordering::Relaxed),
+            multiplications: COUNTERS.multiplications.load(Ordering::Relaxed),
+            rotations: COUNTERS.rotations.load(Ordering::Relaxed),
+            key_switches: COUNTERS.key_switches.load(Ordering::Relaxed),
+            rescales: COUNTERS.rescales.load(Ordering::Relaxed),
+            bootstraps: COUNTERS.bootstraps.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The operations counted after `earlier`, e.g. the cost of one job
+    /// between two [`EvalCounters::current`] readings
+    pub fn since(&self, earlier: &Self) -> Self {
+        Self {
+            additions: self.additions.saturating_sub(earlier.additions),
+            multiplications: self.multiplications.saturating_sub(earlier.multiplications),
+            rotations: self.rotations.saturating_sub(earlier.rotations),
+            key_switches: self.key_switches.saturating_sub(earlier.key_switches),
+            rescales: self.rescales.saturating_sub(earlier.rescales),
+            bootstraps: self.bootstraps.saturating_sub(earlier.bootstraps),
+        }
+    }
+
+    /// Count the ciphertext operation `op`, as named in the audit trail
+    pub(crate) fn count(op: &str) {
+        let counters: &[&AtomicU64] = match op {
+            "eval_add" | "eval_sub" | "eval_negate" | "eval_add_plaintext"
+            | "eval_sub_plaintext" | "add_const" => &[&COUNTERS.additions],
+            "eval_mult" | "eval_square" => &[&COUNTERS.multiplications, &COUNTERS.key_switches],
+            "eval_mult_no_relin" | "eval_mult_plaintext" | "mult_const" => {
+                &[&COUNTERS.multiplications]
+            }
+            "eval_automorphism" | "eval_fast_rotation" => {
+                &[&COUNTERS.rotations, &COUNTERS.key_switches]
+            }
+            "key_switch" | "relinearize" => &[&COUNTERS.key_switches],
+            "rescale" | "compress" => &[&COUNTERS.rescales],
+            "eval_bootstrap" => &[&COUNTERS.bootstraps],
+            _ => &[],
+        };
+        for counter in counters {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The total number of counted operations
+    pub fn total(&self) -> u64 {
+        self.additions
+            + self.multiplications
+            + self.rotations
+            + self.key_switches
+            + self.rescales
+            + self.bootstraps
+    }
+}
+
+/// The position of a deterministic ChaCha20 stream
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RngState {
+    seed: [u8; 32],
+    stream: u64,
+    word_pos: u128,
+}
+
+impl RngState {
+    /// Capture the current position of `rng`
+    pub fn capture(rng: &ChaCha20Rng) -> Self {
+        Self {
+            seed: rng.get_seed(),
+            stream: rng.get_stream(),
+            word_pos: rng.get_word_pos(),
+        }
+    }
+
+    /// An RNG that continues exactly where the captured one stopped
+    pub fn restore(&self) -> ChaCha20Rng {
+        let mut rng = ChaCha20Rng::from_seed(self.seed);
+        rng.set_stream(self.stream);
+        rng.set_word_pos(self.word_pos);
+        rng
+    }
+}
+
+/// A snapshot of an in progress evaluation over ciphertexts of type `C`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint<C> {
+    version: u32,
+    step: u64,
+    ciphertexts: BTreeMap<String, C>,
+    counters: EvalCounters,
+    rng: Option<RngState>,
+}
+
+impl<C> Default for Checkpoint<C> {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl<C> Checkpoint<C> {
+    /// An empty checkpoint taken after `step` steps of the computation
+    pub fn new(step: u64) -> Self {
+        Self {
+            version: CHECKPOINT_VERSION,
+            step,
+            ciphertexts: BTreeMap::new(),
+            counters: EvalCounters::default(),
+            rng: None,
+        }
+    }
+
+    /// Check a deserialized checkpoint can be resumed by this library version
+    pub fn verify(&self) -> Result<()> {
+        if self.version != CHECKPOINT_VERSION {
+            return Err(Error::CheckpointVersion(self.version));
+        }
+        Ok(())
+    }
+
+    /// The step the computation should resume from
+    pub fn step(&self) -> u64 {
+        self.step
+    }
+
+    /// Store an intermediate ciphertext under `name`, returning any previous one
+    pub fn insert<S: Into<String>>(&mut self, name: S, ciphertext: C) -> Option<C> {
+        self.ciphertexts.insert(name.into(), ciphertext)
+    }
+
+    /// The intermediate ciphertext stored under `name`
+    pub fn get(&self, name: &str) -> Option<&C> {
+        self.ciphertexts.get(name)
+    }
+
+    /// Remove and return the intermediate ciphertext stored under `name`
+    pub fn take(&mut self, name: &str) -> Option<C> {
+        self.ciphertexts.remove(name)
+    }
+
+    /// The names of all stored ciphertexts
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.ciphertexts.keys().map(String::as_str)
+    }
+
+    /// The operation counters at the time of the snapshot
+    pub fn counters(&self) -> &EvalCounters {
+        &self.counters
+    }
+
+    /// Set the operation counters
+    pub fn set_counters(&mut self, counters: EvalCounters) {
+        self.counters = counters;
+    }
+
+    /// Record the deterministic RNG so sampling resumes on the same stream
+    pub fn set_rng(&mut self, rng: &ChaCha20Rng) {
+        self.rng = Some(RngState::capture(rng));
+    }
+
+    /// The recorded RNG, `None` when the computation was not deterministic
+    pub fn restore_rng(&self) -> Option<ChaCha20Rng> {
+        self.rng.as_ref().map(RngState::restore)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pke::test_support::ckks_params;
+
+    #[test]
+    fn counts_evaluations() {
+        let ckks = ckks_params()
+            .multiplicative_depth(1)
+            .build()
+            .expect("parameters");
+        let key_pair = ckks.key_gen().expect("keys");
+        let relin_key = ckks
+            .eval_mult_key_gen(key_pair.private_key())
+            .expect("relinearization key");
+        let plaintext = ckks.encode(&[1.0, 2.0]).expect("encode");
+        let c = ckks
+            .encrypt(key_pair.public_key(), &plaintext)
+            .expect("encrypt");
+
+        let before = EvalCounters::current();
+        let sum = ckks.eval_add(&c, &c).expect("add");
+        let product = ckks.eval_mult(&sum, &c, &relin_key).expect("mult");
+        ckks.crypto_context().mod_reduce(&product).expect("rescale");
+        let counted = EvalCounters::current().since(&before);
+        // Other tests evaluate concurrently, so only lower bounds hold
+        assert!(counted.additions >= 1);
+        assert!(counted.multiplications >= 1);
+        assert!(counted.key_switches >= 1);
+        assert!(counted.rescales >= 1);
+
+        let mut checkpoint = Checkpoint::<()>::new(1);
+        checkpoint.set_counters(counted);
+        assert_eq!(checkpoint.counters().total(), counted.total());
+    }
+}
@@ -1,19 +1,50 @@
+//! Ciphertexts and their context free verification
+//!
+//! [`verify_ciphertext`] checks the structure of a serialized ciphertext
+//! against the shape a deployment expects, so gateways can reject malformed
+//! or oversized inputs before a crypto context is loaded.
+
+use crate::ActingPrimitive;
+use crate::arena::{ArenaKind, ArenaLease};
+#[cfg(feature = "audit")]
+use crate::audit::{AuditTrail, Fingerprint};
+use crate::checkpoint::EvalCounters;
 use crate::constants::PlaintextEncodingsType;
+use crate::core::lattice::dcrt_poly::{DcrtPoly, DcrtPolySeed};
+use crate::core::lattice::params::DcrtElementParams;
 use crate::crypto_object::CryptoObject;
+use crate::error::{Error, Result};
 use crate::pke::CryptoContext;
-use crypto_bigint::U64;
-use serde::{Deserialize, Serialize};
+use crate::serdes::{BoundedSeq, FieldIndex};
+use num::BigUint;
+use serde::de::{DeserializeSeed, Error as DError, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// An encryption under a crypto context, decrypting as `c0 + c1 s + ...`
+/// for the polynomials `[c0, c1, ...]` in `elements`
+///
+/// Clones hold no slot in the arena of a capped context, see
+/// [`Ciphertext::try_clone`].
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Ciphertext {
     crypto_context: CryptoContext,
-    elements: Vec<U64>,
+    elements: Vec<DcrtPoly>,
+    element_params: DcrtElementParams,
     noise_scale_degree: usize,
     encoding_type: PlaintextEncodingsType,
     scaling_factor: f64,
     scaling_factor_int: usize,
     level: usize,
     hops_level: usize,
+    #[serde(default)]
+    slots: usize,
+    #[serde(default)]
+    key_tag: String,
+    #[cfg(feature = "audit")]
+    #[serde(default)]
+    audit: AuditTrail,
+    #[serde(skip)]
+    _lease: ArenaLease,
 }
 
 impl Default for Ciphertext {
@@ -21,20 +52,570 @@ impl Default for Ciphertext {
         Self {
             crypto_context: CryptoContext::default(),
             elements: Vec::new(),
+            element_params: DcrtElementParams::default(),
             noise_scale_degree: 1,
             encoding_type: PlaintextEncodingsType::default(),
             scaling_factor: 1.0,
             scaling_factor_int: 1,
             level: 0,
             hops_level: 0,
+            slots: 0,
+            key_tag: String::new(),
+            #[cfg(feature = "audit")]
+            audit: AuditTrail::default(),
+            _lease: ArenaLease::default(),
+        }
+    }
+}
+
+impl Clone for Ciphertext {
+    fn clone(&self) -> Self {
+        Self {
+            crypto_context: self.crypto_context.clone(),
+            elements: self.elements.clone(),
+            element_params: self.element_params.clone(),
+            noise_scale_degree: self.noise_scale_degree,
+            encoding_type: self.encoding_type,
+            scaling_factor: self.scaling_factor,
+            scaling_factor_int: self.scaling_factor_int,
+            level: self.level,
+            hops_level: self.hops_level,
+            slots: self.slots,
+            key_tag: self.key_tag.clone(),
+            #[cfg(feature = "audit")]
+            audit: self.audit.clone(),
+            _lease: ArenaLease::default(),
         }
     }
 }
 
 impl CryptoObject for Ciphertext {
     fn get_crypto_context(&self) -> &CryptoContext {
-        unimplemented!()
+        &self.crypto_context
+    }
+}
+
+impl Ciphertext {
+    /// A fresh ciphertext of `elements`, which must share their parameters
+    /// and format
+    pub fn new(
+        crypto_context: CryptoContext,
+        elements: Vec<DcrtPoly>,
+        encoding_type: PlaintextEncodingsType,
+        scaling_factor: f64,
+    ) -> Result<Self> {
+        let Some(first) = elements.first() else {
+            return Err(Error::CiphertextFormat(
+                "a ciphertext needs at least one polynomial".to_string(),
+            ));
+        };
+        if elements
+            .iter()
+            .any(|e| e.params() != first.params() || e.format() != first.format())
+        {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        Ok(Self {
+            _lease: crypto_context.lease(ArenaKind::Ciphertext)?,
+            crypto_context,
+            element_params: first.params().clone(),
+            elements,
+            encoding_type,
+            scaling_factor,
+            ..Self::default()
+        })
+    }
+
+    /// A copy taking its own slot in the arena of a capped context, failing
+    /// with [`Error::ArenaExhausted`] when none is left
+    pub fn try_clone(&self) -> Result<Self> {
+        let lease = self.crypto_context.lease(ArenaKind::Ciphertext)?;
+        Ok(Self {
+            _lease: lease,
+            ..self.clone()
+        })
+    }
+
+    /// The polynomials `[c0, c1, ...]`
+    pub fn elements(&self) -> &[DcrtPoly] {
+        &self.elements
+    }
+
+    /// The plaintext encoding
+    pub fn encoding_type(&self) -> PlaintextEncodingsType {
+        self.encoding_type
+    }
+
+    /// The CKKS scaling factor, one for the other schemes
+    pub fn scaling_factor(&self) -> f64 {
+        self.scaling_factor
+    }
+
+    /// The parameters of the towers remaining at the current level
+    pub fn element_params(&self) -> &DcrtElementParams {
+        &self.element_params
+    }
+
+    /// The number of towers consumed from the modulus chain
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    /// The number of plaintexts multiplied into the message, one for a
+    /// fresh encryption
+    pub fn noise_scale_degree(&self) -> usize {
+        self.noise_scale_degree
+    }
+
+    pub(crate) fn set_noise_scale_degree(&mut self, noise_scale_degree: usize) {
+        self.noise_scale_degree = noise_scale_degree;
+    }
+
+    /// The number of proxy re-encryptions the ciphertext went through
+    pub fn hops_level(&self) -> usize {
+        self.hops_level
+    }
+
+    pub(crate) fn set_hops_level(&mut self, hops_level: usize) {
+        self.hops_level = hops_level;
+    }
+
+    /// The CKKS slots the message fills, zero for the slots of its crypto
+    /// context
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    pub(crate) fn set_slots(&mut self, slots: usize) {
+        self.slots = slots;
+    }
+
+    /// The tag of the key pair the ciphertext was encrypted under, which
+    /// selects its evaluation keys
+    pub fn key_tag(&self) -> &str {
+        &self.key_tag
+    }
+
+    pub(crate) fn set_key_tag(&mut self, key_tag: &str) {
+        self.key_tag = key_tag.to_string();
+    }
+
+    /// Move the ciphertext `levels` down the modulus chain.
+    ///
+    /// The element parameters are shrunk to the remaining towers so a
+    /// serialized low level ciphertext only describes the moduli it uses.
+    pub fn drop_levels(&mut self, levels: usize) {
+        let remaining = self.element_params.params().len().saturating_sub(levels);
+        self.element_params = self.element_params.shrink(remaining);
+        self.level += levels;
+        self.record("drop_levels", &[]);
+    }
+
+    /// Replace the polynomials by the result `elements` of `op` applied to
+    /// `operands`, scaled by `scaling_factor`, counting the towers they
+    /// dropped as consumed levels and `op` in [`EvalCounters::current`]
+    pub(crate) fn replace_elements(
+        &mut self,
+        op: &str,
+        elements: Vec<DcrtPoly>,
+        scaling_factor: f64,
+        operands: &[&Ciphertext],
+    ) {
+        let towers = elements.first().map_or(0, |e| e.towers().len());
+        self.level += self.element_params.params().len().saturating_sub(towers);
+        if let Some(first) = elements.first() {
+            self.element_params = first.params().clone();
+        }
+        self.elements = elements;
+        self.scaling_factor = scaling_factor;
+        EvalCounters::count(op);
+        self.record(op, operands);
+    }
+
+    /// Check the structural invariants and describe the ciphertext.
+    ///
+    /// The towers must share one ring dimension, the composite modulus must
+    /// be their product, there must be at least two polynomials over those
+    /// towers and every residue must be reduced modulo its tower.
+    pub fn metadata(&self) -> Result<CiphertextMetadata> {
+        let invalid = |msg: String| Err(Error::CiphertextFormat(msg));
+        let towers = self.element_params.params();
+        let Some(first) = towers.front() else {
+            return invalid("the ciphertext has no towers".to_string());
+        };
+        let ring_dimension = first.ring_dimension;
+        if ring_dimension == 0 || !ring_dimension.is_power_of_two() {
+            return invalid(format!(
+                "ring dimension {ring_dimension} is not a power of two"
+            ));
+        }
+        if let Some(p) = towers.iter().find(|p| {
+            p.ring_dimension != ring_dimension || p.cyclotomic_order != first.cyclotomic_order
+        }) {
+            return invalid(format!(
+                "a tower has ring dimension {} but the first has {ring_dimension}",
+                p.ring_dimension
+            ));
+        }
+        let product = towers
+            .iter()
+            .map(|p| BigUint::from(p.ciphertext_modulus.get().to_primitive()))
+            .product::<BigUint>();
+        if &product != self.element_params.ciphertext_composite_modulus() {
+            return invalid("the composite modulus is not the product of the towers".to_string());
+        }
+
+        let polys = self.elements.len();
+        if polys < 2 {
+            return invalid(format!("{polys} polynomials are fewer than two"));
+        }
+        if self
+            .elements
+            .iter()
+            .any(|e| e.params() != &self.element_params)
+        {
+            return invalid("a polynomial has different towers".to_string());
+        }
+        let tower_len = self.elements[0].towers().first().map_or(0, |t| t.len());
+        let misshapen = self.elements.iter().any(|e| {
+            e.towers().len() != towers.len() || e.towers().iter().any(|t| t.len() != tower_len)
+        });
+        if tower_len < ring_dimension || misshapen {
+            return invalid("the polynomials do not have one value per coefficient".to_string());
+        }
+        let unreduced = self
+            .elements
+            .iter()
+            .flat_map(|e| e.towers().iter().zip(towers))
+            .any(|(tower, p)| {
+                tower
+                    .values()
+                    .iter()
+                    .any(|r| r >= p.ciphertext_modulus.as_ref())
+            });
+        if unreduced {
+            return invalid("a residue is not reduced modulo its tower".to_string());
+        }
+        if self.noise_scale_degree == 0 {
+            return invalid("the noise scale degree is zero".to_string());
+        }
+        if !(self.scaling_factor.is_finite() && self.scaling_factor > 0.0) {
+            return invalid(format!(
+                "scaling factor {} is not positive",
+                self.scaling_factor
+            ));
+        }
+
+        Ok(CiphertextMetadata {
+            ring_dimension,
+            towers: towers.len(),
+            polys,
+            level: self.level,
+            noise_scale_degree: self.noise_scale_degree,
+            encoding_type: self.encoding_type,
+            scaling_factor: self.scaling_factor,
+        })
+    }
+
+    /// The operations that produced this ciphertext
+    #[cfg(feature = "audit")]
+    pub fn audit_trail(&self) -> &AuditTrail {
+        &self.audit
+    }
+
+    /// A hash of the ciphertext contents and level
+    #[cfg(feature = "audit")]
+    pub fn fingerprint(&self) -> Fingerprint {
+        let residues = self
+            .elements
+            .iter()
+            .flat_map(|e| e.towers().iter().flat_map(|t| t.values()));
+        Fingerprint::of_chunks(residues.map(|r| r.to_le_bytes()), self.level)
+    }
+
+    /// Append `op` applied with `operands` to the audit trail
+    #[cfg(feature = "audit")]
+    pub(crate) fn record(&mut self, op: &str, operands: &[&Ciphertext]) {
+        let operands = operands.iter().map(|c| c.fingerprint()).collect();
+        let result = self.fingerprint();
+        self.audit.record(op, operands, result);
+    }
+
+    #[cfg(not(feature = "audit"))]
+    #[inline(always)]
+    pub(crate) fn record(&mut self, _op: &str, _operands: &[&Ciphertext]) {}
+}
+
+/// The shape a deployment expects of incoming ciphertexts
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CiphertextShape {
+    /// The ring dimension of every tower
+    pub ring_dimension: usize,
+    /// The number of towers of a fresh ciphertext at level zero
+    pub towers: usize,
+    /// The largest accepted number of polynomials, two for relinearized ciphertexts
+    pub max_polys: usize,
+}
+
+/// The structure of a verified ciphertext
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CiphertextMetadata {
+    /// The ring dimension of every tower
+    pub ring_dimension: usize,
+    /// The number of towers left
+    pub towers: usize,
+    /// The number of polynomials
+    pub polys: usize,
+    /// The number of towers consumed
+    pub level: usize,
+    /// The noise scale degree
+    pub noise_scale_degree: usize,
+    /// The plaintext encoding
+    pub encoding_type: PlaintextEncodingsType,
+    /// The CKKS scaling factor
+    pub scaling_factor: f64,
+}
+
+impl CiphertextMetadata {
+    /// Check the metadata against what the deployment expects.
+    ///
+    /// The remaining towers plus the consumed levels must add up to the
+    /// towers of a fresh ciphertext.
+    pub fn check_shape(&self, shape: &CiphertextShape) -> Result<()> {
+        if self.ring_dimension != shape.ring_dimension {
+            return Err(Error::CiphertextFormat(format!(
+                "ring dimension {} where {} is expected",
+                self.ring_dimension, shape.ring_dimension
+            )));
+        }
+        if self.towers.checked_add(self.level) != Some(shape.towers) {
+            return Err(Error::CiphertextFormat(format!(
+                "{} towers at level {} where {} towers at level zero are expected",
+                self.towers, self.level, shape.towers
+            )));
+        }
+        if self.polys > shape.max_polys {
+            return Err(Error::CiphertextFormat(format!(
+                "{} polynomials where at most {} are accepted",
+                self.polys, shape.max_polys
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Deserialize a ciphertext and check it against `shape` without a crypto
+/// context, see [`Ciphertext::metadata`] for the checked invariants.
+///
+/// The polynomial count, the tower counts and ring dimensions and the
+/// length of every tower are checked against `shape` as they are read, so
+/// oversized inputs are rejected before their residues are decoded.
+pub fn verify_ciphertext<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    shape: &CiphertextShape,
+) -> Result<CiphertextMetadata> {
+    let ciphertext = CiphertextSeed { shape: *shape }
+        .deserialize(deserializer)
+        .map_err(|e| Error::CiphertextFormat(e.to_string()))?;
+    let metadata = ciphertext.metadata()?;
+    metadata.check_shape(shape)?;
+    Ok(metadata)
+}
+
+/// Deserializes a [`Ciphertext`] no larger than `shape`
+#[derive(Copy, Clone, Debug)]
+struct CiphertextSeed {
+    shape: CiphertextShape,
+}
+
+const CIPHERTEXT_FIELDS: &[&str] = &[
+    "crypto_context",
+    "elements",
+    "element_params",
+    "noise_scale_degree",
+    "encoding_type",
+    "scaling_factor",
+    "scaling_factor_int",
+    "level",
+    "hops_level",
+    "slots",
+    "key_tag",
+    #[cfg(feature = "audit")]
+    "audit",
+];
+
+impl CiphertextSeed {
+    fn elements(&self) -> BoundedSeq<DcrtPolySeed> {
+        BoundedSeq {
+            seed: DcrtPolySeed {
+                ring_dimension: self.shape.ring_dimension,
+                max_towers: self.shape.towers,
+            },
+            max_len: self.shape.max_polys,
+            what: "polynomials",
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for CiphertextSeed {
+    type Value = Ciphertext;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> std::result::Result<Ciphertext, D::Error> {
+        d.deserialize_struct("Ciphertext", CIPHERTEXT_FIELDS, self)
+    }
+}
+
+impl<'de> Visitor<'de> for CiphertextSeed {
+    type Value = Ciphertext;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a ciphertext of shape {:?}", self.shape)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Ciphertext, A::Error> {
+        let missing = |i| DError::invalid_length(i, &self);
+        Ok(Ciphertext {
+            crypto_context: seq.next_element()?.ok_or_else(|| missing(0))?,
+            elements: seq
+                .next_element_seed(self.elements())?
+                .ok_or_else(|| missing(1))?,
+            element_params: seq.next_element()?.ok_or_else(|| missing(2))?,
+            noise_scale_degree: seq.next_element()?.ok_or_else(|| missing(3))?,
+            encoding_type: seq.next_element()?.ok_or_else(|| missing(4))?,
+            scaling_factor: seq.next_element()?.ok_or_else(|| missing(5))?,
+            scaling_factor_int: seq.next_element()?.ok_or_else(|| missing(6))?,
+            level: seq.next_element()?.ok_or_else(|| missing(7))?,
+            hops_level: seq.next_element()?.ok_or_else(|| missing(8))?,
+            slots: seq.next_element()?.unwrap_or_default(),
+            key_tag: seq.next_element()?.unwrap_or_default(),
+            #[cfg(feature = "audit")]
+            audit: seq.next_element()?.unwrap_or_default(),
+            _lease: ArenaLease::default(),
+        })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<Ciphertext, A::Error> {
+        let mut crypto_context = None;
+        let mut elements = None;
+        let mut element_params = None;
+        let mut noise_scale_degree = None;
+        let mut encoding_type = None;
+        let mut scaling_factor = None;
+        let mut scaling_factor_int = None;
+        let mut level = None;
+        let mut hops_level = None;
+        let mut slots = None;
+        let mut key_tag = None;
+        #[cfg(feature = "audit")]
+        let mut audit = None;
+        while let Some(field) = map.next_key_seed(FieldIndex(CIPHERTEXT_FIELDS))? {
+            let duplicate = match field {
+                Some(0) => crypto_context.replace(map.next_value()?).is_some(),
+                Some(1) => elements
+                    .replace(map.next_value_seed(self.elements())?)
+                    .is_some(),
+                Some(2) => element_params.replace(map.next_value()?).is_some(),
+                Some(3) => noise_scale_degree.replace(map.next_value()?).is_some(),
+                Some(4) => encoding_type.replace(map.next_value()?).is_some(),
+                Some(5) => scaling_factor.replace(map.next_value()?).is_some(),
+                Some(6) => scaling_factor_int.replace(map.next_value()?).is_some(),
+                Some(7) => level.replace(map.next_value()?).is_some(),
+                Some(8) => hops_level.replace(map.next_value()?).is_some(),
+                Some(9) => slots.replace(map.next_value()?).is_some(),
+                Some(10) => key_tag.replace(map.next_value()?).is_some(),
+                #[cfg(feature = "audit")]
+                Some(11) => audit.replace(map.next_value()?).is_some(),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                    false
+                }
+            };
+            if duplicate {
+                return Err(DError::custom("duplicate field"));
+            }
+        }
+        let field = |i: usize| DError::missing_field(CIPHERTEXT_FIELDS[i]);
+        Ok(Ciphertext {
+            crypto_context: crypto_context.ok_or_else(|| field(0))?,
+            elements: elements.ok_or_else(|| field(1))?,
+            element_params: element_params.ok_or_else(|| field(2))?,
+            noise_scale_degree: noise_scale_degree.ok_or_else(|| field(3))?,
+            encoding_type: encoding_type.ok_or_else(|| field(4))?,
+            scaling_factor: scaling_factor.ok_or_else(|| field(5))?,
+            scaling_factor_int: scaling_factor_int.ok_or_else(|| field(6))?,
+            level: level.ok_or_else(|| field(7))?,
+            hops_level: hops_level.ok_or_else(|| field(8))?,
+            slots: slots.unwrap_or_default(),
+            key_tag: key_tag.unwrap_or_default(),
+            #[cfg(feature = "audit")]
+            audit: audit.unwrap_or_default(),
+            _lease: ArenaLease::default(),
+        })
     }
 }
 
-impl Ciphertext {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pke::test_support::bfv_context;
+    use bincode::Options;
+
+    fn ciphertext() -> Ciphertext {
+        let bfv = bfv_context(0);
+        let key_pair = bfv.key_gen().expect("keys");
+        let plaintext = bfv.encode(&[1, 2, 3]).expect("encode");
+        bfv.encrypt(key_pair.public_key(), &plaintext)
+            .expect("encrypt")
+    }
+
+    fn verify(ciphertext: &Ciphertext, shape: &CiphertextShape) -> Result<CiphertextMetadata> {
+        let options = bincode::options();
+        let bytes = options.serialize(ciphertext).expect("serialize");
+        verify_ciphertext(
+            &mut bincode::Deserializer::from_slice(&bytes, options),
+            shape,
+        )
+    }
+
+    #[test]
+    fn verify_checks_the_shape_while_reading() {
+        let ciphertext = ciphertext();
+        let towers = ciphertext.element_params().params().len();
+        let shape = CiphertextShape {
+            ring_dimension: 2048,
+            towers,
+            max_polys: 2,
+        };
+        let metadata = verify(&ciphertext, &shape).expect("valid ciphertext");
+        assert_eq!((metadata.towers, metadata.polys), (towers, 2));
+
+        for (shape, reason) in [
+            (
+                CiphertextShape {
+                    ring_dimension: 1024,
+                    ..shape
+                },
+                "ring dimension 2048",
+            ),
+            (
+                CiphertextShape {
+                    towers: towers - 1,
+                    ..shape
+                },
+                "towers",
+            ),
+            (
+                CiphertextShape {
+                    max_polys: 1,
+                    ..shape
+                },
+                "polynomials",
+            ),
+        ] {
+            match verify(&ciphertext, &shape) {
+                Err(Error::CiphertextFormat(msg)) => assert!(msg.contains(reason), "{msg}"),
+                other => panic!("{shape:?} accepted: {other:?}"),
+            }
+        }
+    }
+}
@@ -11,10 +11,20 @@ pub const MAX_LOG_STEP: usize = 60;
 pub const MAX_DISCRETE_GAUSSIAN_LEVELS: usize = 4;
 /// Most common value for levels/towers to drop
 pub const BASE_NUM_LEVELS_TO_DROP: usize = 1;
-/// Noise Flooding distribution parameter for distributed decryption in threshold FHE
+/// Noise Flooding distribution parameter for distributed decryption in threshold FHE,
+/// the flooding standard deviation for the default statistical security
 pub const MP_SD: usize = 1048576;
-/// Noise Flooding distribution parameter for fixed 20 bits noise multi-hop PRE
+/// Noise Flooding distribution parameter for fixed 20 bits noise multi-hop PRE,
+/// the flooding standard deviation for the default statistical security
 pub const PRE_SD: usize = 1048576;
+/// The default statistical security in bits of noise flooding
+pub const DEFAULT_STATISTICAL_SECURITY: u32 = 40;
+/// The default number of decryptions an adversary is assumed to observe
+pub const DEFAULT_NUM_ADVERSARIAL_QUERIES: u64 = 1;
+/// The number of nonzero entries of a sparse ternary secret
+pub const SPARSE_HAMMING_WEIGHT: usize = 192;
+/// The default standard deviation of fresh errors
+pub const DEFAULT_STD_DEV: f64 = 3.19;
 /// Num of additional moduli in NOISE_FLOODING_MULTIPARTY mode
 pub const NUM_MODULI_MULTIPARTY: usize = 2;
 /// Modulus size for additional moduli in NOISE_FLOODING_MULTIPARTY mode
@@ -92,6 +102,20 @@ impl From<usize> for ScalingTechnique {
 
 serde_str_or_u8!(ScalingTechnique);
 
+impl ScalingTechnique {
+    /// The largest noise scale degree a CKKS product may reach before its
+    /// operands are rescaled automatically, none for the techniques leaving
+    /// rescaling to the caller
+    pub fn max_noise_scale_degree(self) -> Option<usize> {
+        match self {
+            ScalingTechnique::FixedAuto
+            | ScalingTechnique::FlexibleAuto
+            | ScalingTechnique::FlexibleAutoExt => Some(2),
+            _ => None,
+        }
+    }
+}
+
 /// Proxy Pre-Encryption Mode
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Ord, PartialOrd, Hash, Display, FromStr)]
 #[repr(usize)]
@@ -184,6 +208,23 @@ pub enum DecryptionNoiseMode {
 hex_enum_usize!(DecryptionNoiseMode);
 try_serde_str_or_u8!(DecryptionNoiseMode);
 
+/// How the encoding transforms evaluate sines and cosines
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, Ord, PartialOrd, Hash, Display, FromStr, TryFrom,
+)]
+#[try_from(repr)]
+#[repr(usize)]
+pub enum FloatingPointMode {
+    /// The platform `f64` functions, fastest but may differ between targets
+    #[default]
+    Native = 0,
+    /// Integer fixed point, bit identical on every target
+    Deterministic,
+}
+
+hex_enum_usize!(FloatingPointMode);
+try_serde_str_or_u8!(FloatingPointMode);
+
 /// Key switch technique
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Ord, PartialOrd, Hash, Display, FromStr)]
 #[repr(usize)]
@@ -299,6 +340,13 @@ pub enum CompressionLevel {
 hex_enum_usize!(CompressionLevel);
 try_serde_str_or_u8!(CompressionLevel);
 
+impl CompressionLevel {
+    /// The towers a ciphertext keeps when compressed to this level
+    pub fn towers(self) -> usize {
+        self as usize
+    }
+}
+
 /// RLWE key generation modes
 #[derive(
     Copy, Clone, Debug, Default, PartialEq, Eq, Ord, PartialOrd, Hash, Display, FromStr, TryFrom,
@@ -352,34 +400,58 @@ pub enum BaseSamplerType {
 hex_enum_usize!(BaseSamplerType);
 try_serde_str_or_u8!(BaseSamplerType);
 
+/// The secret key distribution used by the security tables
 #[derive(
     Copy, Clone, Debug, Default, PartialEq, Eq, Ord, PartialOrd, Hash, Display, FromStr, TryFrom,
 )]
 #[try_from(repr)]
 #[repr(usize)]
 pub enum DistributionType {
+    /// Uniform over the ciphertext modulus
     #[default]
     Uniform = 0,
+    /// Same as the error distribution
     Error,
+    /// Uniform ternary
     Ternary,
 }
 hex_enum_usize!(DistributionType);
 try_serde_str_or_u8!(DistributionType);
 
 impl DistributionType {
+    /// The number of distribution types
     pub const NUM_VALUES: usize = 3;
 }
 
+impl From<SecretKeyDistribution> for DistributionType {
+    fn from(value: SecretKeyDistribution) -> Self {
+        match value {
+            SecretKeyDistribution::Gaussian => DistributionType::Error,
+            SecretKeyDistribution::UniformTernary | SecretKeyDistribution::SparseTernary => {
+                DistributionType::Ternary
+            }
+        }
+    }
+}
+
+/// The HomomorphicEncryption.org security levels
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Ord, PartialOrd, Hash, Display, FromStr)]
 #[repr(usize)]
 pub enum SecurityLevel {
+    /// No security level, parameters are not checked
     #[default]
     HeStdNotSet = 0,
+    /// 128 bits of classical security
     HeStd128Classic,
+    /// 192 bits of classical security
     HeStd192Classic,
+    /// 256 bits of classical security
     HeStd256Classic,
+    /// 128 bits of quantum security
     HeStd128Quantum,
+    /// 192 bits of quantum security
     HeStd192Quantum,
+    /// 256 bits of quantum security
     HeStd256Quantum,
 }
 
@@ -402,5 +474,6 @@ impl From<usize> for SecurityLevel {
 serde_str_or_u8!(SecurityLevel);
 
 impl SecurityLevel {
+    /// The number of security levels
     pub const NUM_VALUES: usize = 7;
 }
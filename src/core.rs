@@ -1,3 +1,5 @@
+//! Core lattice and math primitives
+
 pub mod lattice;
 pub mod math;
-pub mod utils;
+pub(crate) mod utils;
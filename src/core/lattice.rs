@@ -1,4 +1,10 @@
-mod dcrt_poly;
-pub mod element;
+//! Ideal lattice elements and their parameters
+
+pub(crate) mod dcrt_poly;
+pub(crate) mod element;
+pub mod matrix;
 pub mod params;
-pub mod poly;
+pub(crate) mod poly;
+
+pub use dcrt_poly::DcrtPoly;
+pub use element::Element;
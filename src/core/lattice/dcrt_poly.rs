@@ -1,11 +1,804 @@
-use crate::constants::PolynomialRingFormat;
-use crate::core::lattice::params::DcrtElementParams;
-use crate::core::lattice::poly::Poly;
-use serde::{Deserialize, Serialize};
+use crate::ActingPrimitive;
+use crate::constants::{DEFAULT_STD_DEV, PolynomialRingFormat};
+use crate::core::lattice::element::Element;
+use crate::core::lattice::params::{DcrtElementParams, DcrtElementParamsBuilder};
+use crate::core::lattice::poly::{Poly, PolySeed, poly_ops_variants};
+use crate::core::math::{DiscreteGaussian, TernaryUniform, scratch};
+use crate::error::Result;
+use crate::serdes::{BoundedSeq, FieldIndex};
+use crypto_bigint::{Odd, U64};
+use num::{BigInt, BigUint, Integer, Signed, ToPrimitive};
+use rand::CryptoRng;
+use serde::de::{DeserializeSeed, Error as DError, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::convert::Infallible;
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Index, Mul, MulAssign, Neg, Rem, RemAssign, Sub, SubAssign,
+};
 
+/// A polynomial of the ring modulo a product of word sized primes, stored
+/// as one [`Poly`] per prime
 #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct DcrtPoly {
     params: DcrtElementParams,
     format: PolynomialRingFormat,
     values: Vec<Poly>,
 }
+
+/// Deserializes a [`DcrtPoly`] of at most `max_towers` towers of ring
+/// dimension `ring_dimension`, rejecting larger ones before their residues
+/// are decoded
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct DcrtPolySeed {
+    pub(crate) ring_dimension: usize,
+    pub(crate) max_towers: usize,
+}
+
+const DCRT_POLY_FIELDS: &[&str] = &["params", "format", "values"];
+
+impl DcrtPolySeed {
+    fn check<E: DError>(&self, params: &DcrtElementParams) -> std::result::Result<(), E> {
+        let towers = params.params();
+        if towers.len() > self.max_towers {
+            return Err(E::custom(format!(
+                "{} towers where at most {} are expected",
+                towers.len(),
+                self.max_towers
+            )));
+        }
+        if let Some(p) = towers
+            .iter()
+            .find(|p| p.ring_dimension != self.ring_dimension)
+        {
+            return Err(E::custom(format!(
+                "ring dimension {} where {} is expected",
+                p.ring_dimension, self.ring_dimension
+            )));
+        }
+        Ok(())
+    }
+
+    fn values(&self) -> BoundedSeq<PolySeed> {
+        BoundedSeq {
+            seed: PolySeed {
+                ring_dimension: self.ring_dimension,
+            },
+            max_len: self.max_towers,
+            what: "towers",
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for DcrtPolySeed {
+    type Value = DcrtPoly;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> std::result::Result<DcrtPoly, D::Error> {
+        d.deserialize_struct("DcrtPoly", DCRT_POLY_FIELDS, self)
+    }
+}
+
+impl<'de> Visitor<'de> for DcrtPolySeed {
+    type Value = DcrtPoly;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "a polynomial of at most {} towers of ring dimension {}",
+            self.max_towers, self.ring_dimension
+        )
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<DcrtPoly, A::Error> {
+        let missing = |i| DError::invalid_length(i, &self);
+        let params = seq.next_element()?.ok_or_else(|| missing(0))?;
+        self.check(&params)?;
+        let format = seq.next_element()?.ok_or_else(|| missing(1))?;
+        let values = seq
+            .next_element_seed(self.values())?
+            .ok_or_else(|| missing(2))?;
+        Ok(DcrtPoly {
+            params,
+            format,
+            values,
+        })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<DcrtPoly, A::Error> {
+        let mut params = None;
+        let mut format = None;
+        let mut values = None;
+        while let Some(field) = map.next_key_seed(FieldIndex(DCRT_POLY_FIELDS))? {
+            let duplicate = match field {
+                Some(0) => {
+                    let p = map.next_value()?;
+                    self.check(&p)?;
+                    params.replace(p).is_some()
+                }
+                Some(1) => format.replace(map.next_value()?).is_some(),
+                Some(2) => values
+                    .replace(map.next_value_seed(self.values())?)
+                    .is_some(),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                    false
+                }
+            };
+            if duplicate {
+                return Err(DError::custom("duplicate field"));
+            }
+        }
+        let field = |i: usize| DError::missing_field(DCRT_POLY_FIELDS[i]);
+        Ok(DcrtPoly {
+            params: params.ok_or_else(|| field(0))?,
+            format: format.ok_or_else(|| field(1))?,
+            values: values.ok_or_else(|| field(2))?,
+        })
+    }
+}
+
+impl Neg for &DcrtPoly {
+    type Output = DcrtPoly;
+
+    fn neg(self) -> Self::Output {
+        let mut result = self.clone();
+        result.values.iter_mut().for_each(|t| *t = -&*t);
+        result
+    }
+}
+
+impl AddAssign<&DcrtPoly> for DcrtPoly {
+    fn add_assign(&mut self, rhs: &Self) {
+        self.zip_towers(rhs, |l, r| *l += r);
+    }
+}
+
+impl SubAssign<&DcrtPoly> for DcrtPoly {
+    fn sub_assign(&mut self, rhs: &Self) {
+        self.zip_towers(rhs, |l, r| *l -= r);
+    }
+}
+
+impl MulAssign<&DcrtPoly> for DcrtPoly {
+    fn mul_assign(&mut self, rhs: &Self) {
+        self.zip_towers(rhs, |l, r| *l *= r);
+    }
+}
+
+impl MulAssign<&U64> for DcrtPoly {
+    fn mul_assign(&mut self, rhs: &U64) {
+        self.values.iter_mut().for_each(|t| *t *= rhs);
+    }
+}
+
+impl Neg for DcrtPoly {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        -&self
+    }
+}
+
+poly_ops_variants!(Add, add, +, AddAssign, add_assign, +=, LHS = DcrtPoly, RHS = U64, Output = DcrtPoly);
+poly_ops_variants!(Sub, sub, -, SubAssign, sub_assign, -=, LHS = DcrtPoly, RHS = U64, Output = DcrtPoly);
+poly_ops_variants!(Mul, mul, *, MulAssign, mul_assign, *=, LHS = DcrtPoly, RHS = U64, Output = DcrtPoly);
+poly_ops_variants!(Div, div, /, DivAssign, div_assign, /=, LHS = DcrtPoly, RHS = U64, Output = DcrtPoly);
+poly_ops_variants!(Rem, rem, %, RemAssign, rem_assign, %=, LHS = DcrtPoly, RHS = U64, Output = DcrtPoly);
+
+poly_ops_variants!(Add, add, +, AddAssign, add_assign, +=, LHS = DcrtPoly, RHS = DcrtPoly, Output = DcrtPoly);
+poly_ops_variants!(Sub, sub, -, SubAssign, sub_assign, -=, LHS = DcrtPoly, RHS = DcrtPoly, Output = DcrtPoly);
+poly_ops_variants!(Mul, mul, *, MulAssign, mul_assign, *=, LHS = DcrtPoly, RHS = DcrtPoly, Output = DcrtPoly);
+
+impl AddAssign<&U64> for DcrtPoly {
+    fn add_assign(&mut self, rhs: &U64) {
+        self.values.iter_mut().for_each(|t| *t += rhs);
+    }
+}
+
+impl SubAssign<&U64> for DcrtPoly {
+    fn sub_assign(&mut self, rhs: &U64) {
+        self.values.iter_mut().for_each(|t| *t -= rhs);
+    }
+}
+
+/// Multiplies by the inverse of `rhs` modulo every tower, like [`Poly`]
+impl DivAssign<&U64> for DcrtPoly {
+    fn div_assign(&mut self, rhs: &U64) {
+        self.values.iter_mut().for_each(|t| *t /= rhs);
+    }
+}
+
+/// Reduces the coefficients, taken in `[0, Q)` for the composite modulus
+/// `Q`, modulo `rhs`
+impl RemAssign<&U64> for DcrtPoly {
+    fn rem_assign(&mut self, rhs: &U64) {
+        let q = BigInt::from(self.params.ciphertext_composite_modulus().clone());
+        let m = BigInt::from(rhs.to_primitive());
+        *self = self.map_interpolated(|c| c.mod_floor(&q).mod_floor(&m));
+    }
+}
+
+/// The residue of a single tower polynomial, see [`DcrtPoly::towers`] for
+/// the others
+impl Index<usize> for DcrtPoly {
+    type Output = U64;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.single_tower()[index]
+    }
+}
+
+impl DcrtPoly {
+    /// The zero polynomial in evaluation format
+    pub fn zero(params: DcrtElementParams) -> Self {
+        let towers = params.params().iter().map(|p| Poly::zero(*p)).collect();
+        Self {
+            params,
+            format: PolynomialRingFormat::Evaluation,
+            values: towers,
+        }
+    }
+
+    /// Embed small signed coefficients in every tower
+    pub fn from_signed(params: DcrtElementParams, values: &[i64]) -> Self {
+        let towers = params
+            .params()
+            .iter()
+            .map(|p| Poly::from_signed(*p, values))
+            .collect();
+        Self {
+            params,
+            format: PolynomialRingFormat::Coefficient,
+            values: towers,
+        }
+    }
+
+    /// Reduce arbitrary size signed coefficients into every tower
+    pub fn from_big_signed(params: DcrtElementParams, values: &[BigInt]) -> Self {
+        let zeros = vec![0; values.len()];
+        let towers = params
+            .params()
+            .iter()
+            .map(|p| {
+                let q = BigInt::from(p.ciphertext_modulus.get().to_primitive());
+                let residues = values
+                    .iter()
+                    .map(|v| {
+                        v.mod_floor(&q)
+                            .to_u64()
+                            .expect("a residue is below a 64 bit modulus")
+                    })
+                    .collect::<Vec<_>>();
+                let mut tower = Poly::from_signed(*p, &zeros);
+                tower.set_values(&residues);
+                tower
+            })
+            .collect();
+        Self {
+            params,
+            format: PolynomialRingFormat::Coefficient,
+            values: towers,
+        }
+    }
+
+    /// The polynomial with the given coefficient residues, one vector per
+    /// tower, each reduced modulo its tower
+    pub fn from_residues(params: DcrtElementParams, residues: &[Vec<u64>]) -> Self {
+        assert_eq!(
+            params.params().len(),
+            residues.len(),
+            "one vector per tower"
+        );
+        let towers = params
+            .params()
+            .iter()
+            .zip(residues)
+            .map(|(p, r)| {
+                let mut tower = Poly::from_signed(*p, &vec![0; r.len()]);
+                tower.set_values(r);
+                tower
+            })
+            .collect();
+        Self {
+            params,
+            format: PolynomialRingFormat::Coefficient,
+            values: towers,
+        }
+    }
+
+    /// The polynomial with the given residues in evaluation format, one
+    /// vector per tower ordered as [`Poly::switch_format`] produces them
+    pub fn from_evaluations(params: DcrtElementParams, residues: &[Vec<u64>]) -> Self {
+        assert_eq!(
+            params.params().len(),
+            residues.len(),
+            "one vector per tower"
+        );
+        let mut result = Self::zero(params);
+        for (tower, r) in result.values.iter_mut().zip(residues) {
+            tower.set_values(r);
+        }
+        result
+    }
+
+    /// The residues of every tower in the current format, see
+    /// [`DcrtPoly::from_residues`]
+    pub fn residues(&self) -> Vec<Vec<u64>> {
+        self.values
+            .iter()
+            .map(|t| t.values().iter().map(|v| v.to_primitive()).collect())
+            .collect()
+    }
+
+    /// Overwrite every tower with the same small signed coefficients
+    pub fn fill_signed(&mut self, values: &[i64]) {
+        self.format = PolynomialRingFormat::Coefficient;
+        for tower in self.values.iter_mut() {
+            tower.fill_signed(values);
+        }
+    }
+
+    /// Draw one pass of Gaussian coefficients and write them into every tower.
+    ///
+    /// One coefficient is drawn per power below the ring dimension, the
+    /// ring is `Z_q[x]/(x^n + 1)`. The tower buffers and the sample
+    /// buffer are reused, so sampling the error of an encryption allocates
+    /// nothing once the pools are warm.
+    pub fn fill_discrete_gaussian(&mut self, discrete_gaussian: &mut DiscreteGaussian) {
+        let mut samples = scratch::take_samples(self.sample_len());
+        discrete_gaussian.fill_i64(&mut samples);
+        self.fill_signed(&samples);
+        scratch::give_samples(samples);
+    }
+
+    /// [`DcrtPoly::fill_discrete_gaussian`] drawing randomness from `rng`
+    pub fn fill_discrete_gaussian_with_rng(
+        &mut self,
+        discrete_gaussian: &DiscreteGaussian,
+        rng: impl CryptoRng,
+    ) {
+        let mut samples = scratch::take_samples(self.sample_len());
+        discrete_gaussian.fill_i64_with_rng(rng, &mut samples);
+        self.fill_signed(&samples);
+        scratch::give_samples(samples);
+    }
+
+    /// Draw one ternary secret and write it into every tower, sized like
+    /// [`DcrtPoly::fill_discrete_gaussian`]
+    pub fn fill_ternary_with_rng(
+        &mut self,
+        ternary: &TernaryUniform,
+        rng: impl CryptoRng,
+    ) -> Result<()> {
+        let mut samples = scratch::take_samples(self.sample_len());
+        let filled = ternary.fill_i64_with_rng(rng, &mut samples);
+        if filled.is_ok() {
+            self.fill_signed(&samples);
+        }
+        scratch::give_samples(samples);
+        filled
+    }
+
+    /// Convert every tower between coefficient and evaluation format
+    pub fn switch_format(&mut self) {
+        self.values.iter_mut().for_each(Poly::switch_format);
+        self.format = match self.format {
+            PolynomialRingFormat::Coefficient => PolynomialRingFormat::Evaluation,
+            PolynomialRingFormat::Evaluation => PolynomialRingFormat::Coefficient,
+        };
+    }
+
+    /// Keep both representations of every tower, see [`Poly::set_format_cache`]
+    pub fn set_format_cache(&mut self, enabled: bool) {
+        self.values
+            .iter_mut()
+            .for_each(|t| t.set_format_cache(enabled));
+    }
+
+    /// Overwrite every tower with uniform residues, keeping the format
+    pub fn fill_uniform_with_rng(&mut self, mut rng: impl CryptoRng) {
+        self.values
+            .iter_mut()
+            .for_each(|t| t.fill_uniform_with_rng(&mut rng));
+    }
+
+    /// Replace every coefficient `c` of tower `i` by `f(i, c) mod q_i`,
+    /// keeping the format, see [`Poly::map_coefficients`]
+    pub fn map_coefficients(&mut self, mut f: impl FnMut(usize, u64) -> u64) {
+        let Ok(()) = self.try_map_coefficients(|i, c| Ok::<_, Infallible>(f(i, c)));
+    }
+
+    /// [`DcrtPoly::map_coefficients`] with a fallible `f`, leaving every
+    /// tower unchanged when it fails for any coefficient
+    pub fn try_map_coefficients<E>(
+        &mut self,
+        mut f: impl FnMut(usize, u64) -> std::result::Result<u64, E>,
+    ) -> std::result::Result<(), E> {
+        let mut towers = self.values.clone();
+        for (i, tower) in towers.iter_mut().enumerate() {
+            tower.try_map(|c| f(i, c))?;
+        }
+        self.values = towers;
+        Ok(())
+    }
+
+    fn sample_len(&self) -> usize {
+        self.params.params().front().map_or(0, |p| p.ring_dimension)
+    }
+
+    /// The parameters of the towers
+    pub fn params(&self) -> &DcrtElementParams {
+        &self.params
+    }
+
+    /// Whether the towers hold coefficients or evaluations
+    pub fn format(&self) -> PolynomialRingFormat {
+        self.format
+    }
+
+    /// The residues modulo each tower modulus
+    pub fn towers(&self) -> &[Poly] {
+        &self.values
+    }
+
+    /// The polynomial modulo the first `towers` towers only, see
+    /// [`DcrtElementParams::shrink`]
+    pub fn shrink(&self, towers: usize) -> Self {
+        Self {
+            params: self.params.shrink(towers),
+            format: self.format,
+            values: self.values.iter().take(towers).cloned().collect(),
+        }
+    }
+
+    /// The image under the automorphism `X -> X^index` for an `index` coprime
+    /// to the length of the towers, see [`Poly::automorphism`]
+    pub fn automorphism(&self, index: usize) -> Self {
+        Self {
+            params: self.params.clone(),
+            format: self.format,
+            values: self.values.iter().map(|t| t.automorphism(index)).collect(),
+        }
+    }
+
+    /// CRT interpolate the towers to centered coefficients modulo the composite modulus
+    pub fn to_signed(&self) -> Vec<BigInt> {
+        let q = self.params.ciphertext_composite_modulus();
+        let half_q = q >> 1u32;
+        // x = sum r_i * (Q / q_i) * ((Q / q_i)^-1 mod q_i) mod Q
+        let basis = self
+            .values
+            .iter()
+            .map(|t| {
+                let qi = BigUint::from(t.modulus().get().to_primitive());
+                let q_hat = q / &qi;
+                let q_hat_inv = (&q_hat % &qi).modpow(&(&qi - 2u32), &qi);
+                q_hat * q_hat_inv
+            })
+            .collect::<Vec<_>>();
+
+        (0..self.values.first().map_or(0, Poly::len))
+            .map(|i| {
+                let x = self
+                    .values
+                    .iter()
+                    .zip(&basis)
+                    .map(|(t, b)| b * t.values()[i].to_primitive())
+                    .sum::<BigUint>()
+                    % q;
+                if x > half_q {
+                    BigInt::from(x) - BigInt::from(q.clone())
+                } else {
+                    BigInt::from(x)
+                }
+            })
+            .collect()
+    }
+
+    fn single_tower(&self) -> &Poly {
+        assert_eq!(self.values.len(), 1, "word sized views need a single tower");
+        &self.values[0]
+    }
+
+    fn map_towers(&self, f: impl FnMut(&Poly) -> Poly) -> Self {
+        Self {
+            params: self.params.clone(),
+            format: self.format,
+            values: self.values.iter().map(f).collect(),
+        }
+    }
+
+    /// The CRT interpolated, centered coefficients, see [`DcrtPoly::to_signed`]
+    fn coefficients(&self) -> Vec<BigInt> {
+        match self.format {
+            PolynomialRingFormat::Coefficient => self.to_signed(),
+            PolynomialRingFormat::Evaluation => {
+                let mut coefficients = self.clone();
+                coefficients.switch_format();
+                coefficients.to_signed()
+            }
+        }
+    }
+
+    /// Replace every centered coefficient `c` by `f(c)`, keeping the format
+    fn map_interpolated(&self, f: impl FnMut(BigInt) -> BigInt) -> Self {
+        let values = self.coefficients().into_iter().map(f).collect::<Vec<_>>();
+        let mut result = Self::from_big_signed(self.params.clone(), &values);
+        result.set_format(self.format);
+        result
+    }
+
+    /// The number of `base_bits` digits of the composite modulus
+    fn windows(&self, base_bits: usize) -> usize {
+        (self.params.ciphertext_composite_modulus().bits() as usize).div_ceil(base_bits)
+    }
+
+    fn zip_towers(&mut self, rhs: &Self, f: impl Fn(&mut Poly, &Poly)) {
+        assert_eq!(self.params, rhs.params);
+        assert_eq!(self.format, rhs.format);
+        self.values
+            .iter_mut()
+            .zip(&rhs.values)
+            .for_each(|(l, r)| f(l, r));
+    }
+}
+
+/// The word sized views, [`Element::values`], [`Element::modulus`],
+/// indexing and [`Element::switch_modulus`], only exist for a single tower
+/// and panic otherwise. Everything taking the coefficients modulo the
+/// composite modulus interpolates the towers, see [`DcrtPoly::to_signed`].
+impl Element for DcrtPoly {
+    fn clone_empty(&self) -> Self {
+        let mut empty = self.clone();
+        empty.values.iter_mut().for_each(Poly::set_zero);
+        empty
+    }
+
+    fn clone_parameters(&self) -> Self {
+        Self {
+            params: self.params.clone(),
+            format: self.format,
+            values: Vec::new(),
+        }
+    }
+
+    fn clone_with_noise(&self) -> Self {
+        let mut discrete_gaussian =
+            DiscreteGaussian::new(DEFAULT_STD_DEV).expect("the default deviation is valid");
+        discrete_gaussian.set_std_dev(DEFAULT_STD_DEV);
+        let mut noise = self.clone_empty();
+        noise.fill_discrete_gaussian(&mut discrete_gaussian);
+        noise.set_format(self.format);
+        noise
+    }
+
+    fn format(&self) -> PolynomialRingFormat {
+        self.format
+    }
+
+    fn len(&self) -> usize {
+        self.values.first().map_or(0, Poly::len)
+    }
+
+    fn modulus(&self) -> Odd<U64> {
+        *self.single_tower().modulus()
+    }
+
+    fn values(&self) -> &[U64] {
+        self.single_tower().values()
+    }
+
+    fn cyclotomic_order(&self) -> U64 {
+        U64::from_u64(
+            self.params
+                .params()
+                .front()
+                .map_or(0, |p| p.cyclotomic_order) as u64,
+        )
+    }
+
+    fn add_assign_one(&mut self) {
+        self.map_coefficients(|_, c| c + 1);
+    }
+
+    fn automorphism_transform(&self, i: usize) -> Self {
+        self.map_towers(|t| t.automorphism_transform(i))
+    }
+
+    fn automorphism_transform_precompute(&self, i: usize, vec: &[usize]) -> Self {
+        self.map_towers(|t| t.automorphism_transform_precompute(i, vec))
+    }
+
+    fn transpose(&self) -> Self {
+        self.map_towers(Poly::transpose)
+    }
+
+    fn base_decompose(&self, base_bits: usize, eval_mode_answer: bool) -> Vec<Self> {
+        let q = BigInt::from(self.params.ciphertext_composite_modulus().clone());
+        let mask = (BigInt::from(1u8) << base_bits) - 1u8;
+        let coefficients = self
+            .coefficients()
+            .into_iter()
+            .map(|c| c.mod_floor(&q))
+            .collect::<Vec<_>>();
+        (0..self.windows(base_bits))
+            .map(|i| {
+                let digits = coefficients
+                    .iter()
+                    .map(|c| (c >> (i * base_bits)) & &mask)
+                    .collect::<Vec<_>>();
+                let mut digit = Self::from_big_signed(self.params.clone(), &digits);
+                if eval_mode_answer {
+                    digit.switch_format();
+                }
+                digit
+            })
+            .collect()
+    }
+
+    fn inverse(&self) -> Option<Self> {
+        let values = self
+            .values
+            .iter()
+            .map(Poly::inverse)
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            params: self.params.clone(),
+            format: self.format,
+            values,
+        })
+    }
+
+    fn norm(&self) -> f64 {
+        self.coefficients()
+            .iter()
+            .map(BigInt::abs)
+            .max()
+            .and_then(|c| c.to_f64())
+            .unwrap_or_default()
+    }
+
+    fn make_sparse(&mut self, w_factor: usize) {
+        self.values.iter_mut().for_each(|t| t.make_sparse(w_factor));
+    }
+
+    fn mod_2(&self) -> Self {
+        let two = BigInt::from(2u8);
+        self.map_interpolated(|c| c.mod_floor(&two))
+    }
+
+    fn multiply_and_round(&self, p: U64, q: U64) -> Self {
+        let p = BigInt::from(p.to_primitive());
+        let q = BigInt::from(q.to_primitive());
+        // round(c p / q) = floor((2 c p + q) / 2 q)
+        self.map_interpolated(|c| (2u8 * c * &p + &q).div_floor(&(2u8 * &q)))
+    }
+
+    fn powers_of_base(&self, base_bits: usize) -> Vec<Self> {
+        (0..self.windows(base_bits))
+            .map(|i| {
+                let shift = BigUint::from(i * base_bits);
+                let mut power = self.clone();
+                for tower in power.values.iter_mut() {
+                    let q = BigUint::from(tower.modulus().get().to_primitive());
+                    let scale = BigUint::from(2u8)
+                        .modpow(&shift, &q)
+                        .to_u64()
+                        .expect("a residue is below a 64 bit modulus");
+                    *tower *= &U64::from_u64(scale);
+                }
+                power
+            })
+            .collect()
+    }
+
+    fn switch_modulus(
+        &mut self,
+        modulus: Odd<U64>,
+        root_of_unity: U64,
+        modulus_arb: Odd<U64>,
+        root_of_unity_arb: U64,
+    ) {
+        let order = self.single_tower().cyclotomic_order();
+        self.params = DcrtElementParamsBuilder::new(order)
+            .moduli(vec![modulus])
+            .roots_of_unity(vec![root_of_unity])
+            .big_moduli(vec![modulus_arb])
+            .big_roots_of_unity(vec![root_of_unity_arb])
+            .build()
+            .expect("one tower of each");
+        self.values[0].switch_modulus(modulus, root_of_unity, modulus_arb, root_of_unity_arb);
+    }
+
+    fn switch_format(&mut self) {
+        DcrtPoly::switch_format(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::lattice::params::DcrtElementParamsBuilder;
+
+    #[test]
+    fn towers_map_with_their_index() {
+        let params = DcrtElementParamsBuilder::new(16)
+            .depth(2)
+            .bits(30)
+            .build()
+            .expect("parameters");
+        assert!(params.params().len() > 1);
+        let signed = [3, -1, 4, 1, -5, 9, 2, -6];
+        let mut poly = DcrtPoly::from_signed(params, &signed);
+        poly.switch_format();
+        let before = poly.clone();
+
+        let result = poly.try_map_coefficients(|i, c| if i == 1 { Err(i) } else { Ok(c + 1) });
+        assert_eq!(result, Err(1));
+        assert_eq!(poly, before);
+
+        poly.map_coefficients(|i, c| c + i as u64);
+        assert_eq!(poly.format(), PolynomialRingFormat::Evaluation);
+        poly.switch_format();
+        for (i, tower) in poly.towers().iter().enumerate() {
+            assert_eq!(tower.to_signed(), signed.map(|x| x + i as i64));
+        }
+    }
+
+    #[test]
+    fn digits_against_powers_of_the_base_multiply() {
+        let params = DcrtElementParamsBuilder::new(16)
+            .depth(2)
+            .bits(30)
+            .build()
+            .expect("parameters");
+        let mut a = DcrtPoly::zero(params.clone());
+        a.fill_uniform_with_rng(rand::rng());
+        let mut b = DcrtPoly::from_signed(params.clone(), &[2, 0, -1, 0, 0, 7, 0, 1]);
+        b.switch_format();
+
+        let digits = a.base_decompose(10, true);
+        let powers = b.powers_of_base(10);
+        assert_eq!(digits.len(), 6);
+        assert_eq!(powers.len(), 6);
+        let sum = digits
+            .iter()
+            .zip(&powers)
+            .map(|(d, p)| d * p)
+            .reduce(|l, r| l + r)
+            .expect("six digits");
+        assert_eq!(sum, &a * &b);
+        assert!(digits.iter().all(|d| d.norm() < 1024.0));
+    }
+
+    #[test]
+    fn interpolated_coefficients_round_and_reduce() {
+        let params = DcrtElementParamsBuilder::new(16)
+            .depth(2)
+            .bits(30)
+            .build()
+            .expect("parameters");
+        let mut x = DcrtPoly::from_signed(params, &[7, -7, 10, -10, 0, 1, -1, 25]);
+        x.switch_format();
+        let signed = |e: &DcrtPoly| {
+            e.coefficients()
+                .iter()
+                .map(|c| c.to_i64())
+                .collect::<Vec<_>>()
+        };
+
+        let rounded = x.multiply_and_round(U64::from_u64(1), U64::from_u64(4));
+        assert_eq!(rounded.format(), PolynomialRingFormat::Evaluation);
+        assert_eq!(
+            signed(&rounded),
+            [2, -2, 3, -2, 0, 0, 0, 6].map(Some).to_vec()
+        );
+        assert_eq!(
+            signed(&x.mod_2()),
+            [1, 1, 0, 0, 0, 1, 1, 1].map(Some).to_vec()
+        );
+        assert_eq!(x.norm(), 25.0);
+        assert_eq!(x.clone_empty().norm(), 0.0);
+        assert!(x.clone_parameters().is_empty());
+        assert_eq!(x.len(), 8);
+    }
+}
@@ -0,0 +1,509 @@
+//! Matrices of ring elements
+//!
+//! Trapdoor sampling and multiparty protocols work on vectors and matrices
+//! of polynomials. [`Matrix`] stores [`Element`]s row major. Like
+//! [`DcrtPoly`], the arithmetic operators panic when the dimensions do not
+//! match.
+
+use crate::constants::PolynomialRingFormat;
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::core::lattice::element::Element;
+use crate::core::lattice::params::DcrtElementParams;
+use crate::core::math::DiscreteGaussian;
+use crate::error::{Error, Result};
+use rand::CryptoRng;
+use serde::{Deserialize, Serialize};
+use std::ops::{Add, AddAssign, Index, IndexMut, Mul, Neg, Sub, SubAssign};
+
+/// A `rows × cols` matrix of ring elements
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "MatrixData<E>")]
+pub struct Matrix<E: Element> {
+    rows: usize,
+    cols: usize,
+    data: Vec<E>,
+}
+
+/// The serialized form of [`Matrix`], checked to hold `rows × cols`
+/// entries when deserialized
+#[derive(Deserialize)]
+struct MatrixData<E> {
+    rows: usize,
+    cols: usize,
+    data: Vec<E>,
+}
+
+impl<E: Element> TryFrom<MatrixData<E>> for Matrix<E> {
+    type Error = Error;
+
+    fn try_from(value: MatrixData<E>) -> Result<Self> {
+        if value.rows.checked_mul(value.cols) != Some(value.data.len()) {
+            return Err(Error::MatrixDimensions(format!(
+                "{} entries in a {} × {} matrix",
+                value.data.len(),
+                value.rows,
+                value.cols
+            )));
+        }
+        Ok(Self {
+            rows: value.rows,
+            cols: value.cols,
+            data: value.data,
+        })
+    }
+}
+
+impl<E: Element> Matrix<E> {
+    /// The matrix whose entry `(i, j)` is `f(i, j)`
+    pub fn from_fn(rows: usize, cols: usize, mut f: impl FnMut(usize, usize) -> E) -> Self {
+        let data = (0..rows)
+            .flat_map(|i| (0..cols).map(move |j| (i, j)))
+            .map(|(i, j)| f(i, j))
+            .collect();
+        Self { rows, cols, data }
+    }
+
+    /// The matrix with the given rows, which must all have the same length
+    pub fn from_rows(rows: Vec<Vec<E>>) -> Result<Self> {
+        let cols = rows.first().map_or(0, Vec::len);
+        if let Some(row) = rows.iter().find(|r| r.len() != cols) {
+            return Err(Error::MatrixDimensions(format!(
+                "a row of {} entries in a matrix of {cols} columns",
+                row.len()
+            )));
+        }
+        Ok(Self {
+            rows: rows.len(),
+            cols,
+            data: rows.into_iter().flatten().collect(),
+        })
+    }
+
+    /// A column vector
+    pub fn column(entries: Vec<E>) -> Self {
+        Self {
+            rows: entries.len(),
+            cols: 1,
+            data: entries,
+        }
+    }
+
+    /// The number of rows
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The number of columns
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// The entry in row `i` and column `j`
+    pub fn get(&self, i: usize, j: usize) -> Option<&E> {
+        (i < self.rows && j < self.cols).then(|| &self.data[i * self.cols + j])
+    }
+
+    /// The mutable entry in row `i` and column `j`
+    pub fn get_mut(&mut self, i: usize, j: usize) -> Option<&mut E> {
+        (i < self.rows && j < self.cols).then(|| &mut self.data[i * self.cols + j])
+    }
+
+    /// The entries of row `i`
+    pub fn row(&self, i: usize) -> &[E] {
+        &self.data[i * self.cols..(i + 1) * self.cols]
+    }
+
+    /// The entries in row major order
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        self.data.iter()
+    }
+
+    /// The entries in row major order, mutably
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut E> {
+        self.data.iter_mut()
+    }
+
+    /// The entries in row major order
+    pub fn into_vec(self) -> Vec<E> {
+        self.data
+    }
+
+    /// Apply `f` to every entry
+    pub fn map<F: Element>(&self, f: impl FnMut(&E) -> F) -> Matrix<F> {
+        Matrix {
+            rows: self.rows,
+            cols: self.cols,
+            data: self.data.iter().map(f).collect(),
+        }
+    }
+
+    /// The matrix with every entry equal to `value`
+    pub fn filled(rows: usize, cols: usize, value: E) -> Self {
+        Self {
+            rows,
+            cols,
+            data: vec![value; rows * cols],
+        }
+    }
+
+    /// The transpose, entry `(i, j)` moves to `(j, i)`
+    pub fn transpose(&self) -> Self {
+        Self::from_fn(self.cols, self.rows, |i, j| self[(j, i)].clone())
+    }
+
+    /// `self` on top of `below`
+    pub fn vstack(&self, below: &Self) -> Self {
+        assert_eq!(self.cols, below.cols, "matrices differ in columns");
+        Self {
+            rows: self.rows + below.rows,
+            cols: self.cols,
+            data: self.data.iter().chain(&below.data).cloned().collect(),
+        }
+    }
+
+    /// `self` left of `right`
+    pub fn hstack(&self, right: &Self) -> Self {
+        assert_eq!(self.rows, right.rows, "matrices differ in rows");
+        Self::from_fn(self.rows, self.cols + right.cols, |i, j| {
+            if j < self.cols {
+                self[(i, j)].clone()
+            } else {
+                right[(i, j - self.cols)].clone()
+            }
+        })
+    }
+
+    /// Multiply every entry by `scalar`
+    pub fn scale(&self, scalar: &E) -> Self {
+        self.map(|e| {
+            let mut e = e.clone();
+            e *= scalar;
+            e
+        })
+    }
+}
+
+impl Matrix<DcrtPoly> {
+    /// The zero matrix over `params` in evaluation format
+    pub fn zero(params: &DcrtElementParams, rows: usize, cols: usize) -> Self {
+        Self::filled(rows, cols, DcrtPoly::zero(params.clone()))
+    }
+
+    /// Independent discrete Gaussian entries, in evaluation format
+    pub fn gaussian(
+        params: &DcrtElementParams,
+        rows: usize,
+        cols: usize,
+        discrete_gaussian: &mut DiscreteGaussian,
+    ) -> Self {
+        Self::from_fn(rows, cols, |_, _| {
+            let mut e = DcrtPoly::zero(params.clone());
+            e.fill_discrete_gaussian(discrete_gaussian);
+            e.switch_format();
+            e
+        })
+    }
+
+    /// [`Matrix::gaussian`] drawing randomness from `rng`
+    pub fn gaussian_with_rng(
+        params: &DcrtElementParams,
+        rows: usize,
+        cols: usize,
+        discrete_gaussian: &DiscreteGaussian,
+        rng: &mut impl CryptoRng,
+    ) -> Self {
+        Self::from_fn(rows, cols, |_, _| {
+            let mut e = DcrtPoly::zero(params.clone());
+            e.fill_discrete_gaussian_with_rng(discrete_gaussian, &mut *rng);
+            e.switch_format();
+            e
+        })
+    }
+
+    /// Independent uniform entries, in evaluation format
+    pub fn uniform(params: &DcrtElementParams, rows: usize, cols: usize) -> Self {
+        Self::uniform_with_rng(params, rows, cols, &mut rand::rng())
+    }
+
+    /// [`Matrix::uniform`] drawing randomness from `rng`
+    pub fn uniform_with_rng(
+        params: &DcrtElementParams,
+        rows: usize,
+        cols: usize,
+        rng: &mut impl CryptoRng,
+    ) -> Self {
+        Self::from_fn(rows, cols, |_, _| {
+            let mut e = DcrtPoly::zero(params.clone());
+            e.fill_uniform_with_rng(&mut *rng);
+            e
+        })
+    }
+
+    /// Convert every entry to `format`
+    pub fn set_format(&mut self, format: PolynomialRingFormat) {
+        self.data
+            .iter_mut()
+            .filter(|e| e.format() != format)
+            .for_each(DcrtPoly::switch_format);
+    }
+}
+
+impl<E: Element> Index<(usize, usize)> for Matrix<E> {
+    type Output = E;
+
+    fn index(&self, (i, j): (usize, usize)) -> &E {
+        assert!(i < self.rows && j < self.cols, "index out of bounds");
+        &self.data[i * self.cols + j]
+    }
+}
+
+impl<E: Element> IndexMut<(usize, usize)> for Matrix<E> {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut E {
+        assert!(i < self.rows && j < self.cols, "index out of bounds");
+        &mut self.data[i * self.cols + j]
+    }
+}
+
+impl<E: Element> AddAssign<&Matrix<E>> for Matrix<E> {
+    fn add_assign(&mut self, rhs: &Matrix<E>) {
+        self.zip_entries(rhs, |l, r| *l += r);
+    }
+}
+
+impl<E: Element> SubAssign<&Matrix<E>> for Matrix<E> {
+    fn sub_assign(&mut self, rhs: &Matrix<E>) {
+        self.zip_entries(rhs, |l, r| *l -= r);
+    }
+}
+
+impl<E: Element> Add for &Matrix<E> {
+    type Output = Matrix<E>;
+
+    fn add(self, rhs: Self) -> Matrix<E> {
+        let mut sum = self.clone();
+        sum += rhs;
+        sum
+    }
+}
+
+impl<E: Element> Sub for &Matrix<E> {
+    type Output = Matrix<E>;
+
+    fn sub(self, rhs: Self) -> Matrix<E> {
+        let mut difference = self.clone();
+        difference -= rhs;
+        difference
+    }
+}
+
+impl<E: Element> Neg for &Matrix<E>
+where
+    for<'a> &'a E: Neg<Output = E>,
+{
+    type Output = Matrix<E>;
+
+    fn neg(self) -> Matrix<E> {
+        self.map(|e| -e)
+    }
+}
+
+impl<E: Element> Mul for &Matrix<E> {
+    type Output = Matrix<E>;
+
+    /// The matrix product, the inner dimension must not be zero since
+    /// entries have no generic zero
+    fn mul(self, rhs: Self) -> Matrix<E> {
+        assert_eq!(self.cols, rhs.rows, "matrix dimensions do not match");
+        assert!(self.cols > 0, "inner dimension is zero");
+        Matrix::from_fn(self.rows, rhs.cols, |i, j| {
+            let mut sum = self[(i, 0)].clone();
+            sum *= &rhs[(0, j)];
+            for k in 1..self.cols {
+                let mut term = self[(i, k)].clone();
+                term *= &rhs[(k, j)];
+                sum += &term;
+            }
+            sum
+        })
+    }
+}
+
+impl<E: Element> Matrix<E> {
+    fn zip_entries(&mut self, rhs: &Self, f: impl Fn(&mut E, &E)) {
+        assert_eq!(
+            (self.rows, self.cols),
+            (rhs.rows, rhs.cols),
+            "matrix dimensions do not match"
+        );
+        self.data
+            .iter_mut()
+            .zip(&rhs.data)
+            .for_each(|(l, r)| f(l, r));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::lattice::params::DcrtElementParamsBuilder;
+    use num::{BigInt, ToPrimitive};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    /// Ring dimension 8
+    const N: usize = 8;
+
+    fn params() -> DcrtElementParams {
+        DcrtElementParamsBuilder::new(2 * N)
+            .depth(2)
+            .bits(30)
+            .build()
+            .expect("valid params")
+    }
+
+    fn poly(coefficients: &[i64]) -> DcrtPoly {
+        let mut values = coefficients.to_vec();
+        values.resize(N, 0);
+        DcrtPoly::from_signed(params(), &values)
+    }
+
+    fn negacyclic(a: &[i64], b: &[i64]) -> Vec<i64> {
+        let mut product = vec![0; N];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                let sign = if i + j < N { 1 } else { -1 };
+                product[(i + j) % N] += sign * x * y;
+            }
+        }
+        product
+    }
+
+    fn signed(e: &DcrtPoly) -> Vec<i64> {
+        let mut e = e.clone();
+        e.set_format(PolynomialRingFormat::Coefficient);
+        e.to_signed()
+            .iter()
+            .map(|c| i64::try_from(c).expect("small coefficient"))
+            .collect()
+    }
+
+    fn entries() -> [Vec<i64>; 4] {
+        [
+            vec![1, 2, 0, 0, 0, 0, 0, 3],
+            vec![-4, 0, 5, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 6, 7],
+            vec![2, -1, 0, 0, 0, 0, 0, 0],
+        ]
+    }
+
+    fn matrix(entries: &[Vec<i64>]) -> Matrix<DcrtPoly> {
+        Matrix::from_fn(2, 2, |i, j| poly(&entries[2 * i + j]))
+    }
+
+    #[test]
+    fn sums_and_products_follow_the_entries() {
+        let a = entries();
+        let b = [a[3].clone(), a[0].clone(), a[2].clone(), a[1].clone()];
+        let (ma, mb) = (matrix(&a), matrix(&b));
+
+        let sum = &ma + &mb;
+        let difference = &ma - &mb;
+        let product = &ma * &mb;
+        for i in 0..2 {
+            for j in 0..2 {
+                let add = a[2 * i + j].iter().zip(&b[2 * i + j]).map(|(x, y)| x + y);
+                assert_eq!(signed(&sum[(i, j)]), add.collect::<Vec<_>>());
+                let sub = a[2 * i + j].iter().zip(&b[2 * i + j]).map(|(x, y)| x - y);
+                assert_eq!(signed(&difference[(i, j)]), sub.collect::<Vec<_>>());
+
+                let expected = (0..2)
+                    .map(|k| negacyclic(&a[2 * i + k], &b[2 * k + j]))
+                    .reduce(|l, r| l.iter().zip(&r).map(|(x, y)| x + y).collect())
+                    .expect("two terms");
+                assert_eq!(signed(&product[(i, j)]), expected);
+            }
+        }
+        assert_eq!(signed(&(-&ma)[(1, 0)]), vec![0, 0, 0, 0, 0, 0, -6, -7]);
+        assert_eq!(
+            signed(&ma.scale(&poly(&[0, 1]))[(0, 0)]),
+            negacyclic(&a[0], &[0, 1])
+        );
+    }
+
+    #[test]
+    fn transposes_and_stacks_move_entries() {
+        let a = matrix(&entries());
+        let t = a.transpose();
+        assert_eq!(t[(0, 1)], a[(1, 0)]);
+        assert_eq!(t[(1, 0)], a[(0, 1)]);
+        assert_eq!(t.transpose(), a);
+        // (AB)^T = B^T A^T
+        assert_eq!((&a * &t).transpose(), &t.transpose() * &t);
+
+        let column = Matrix::column(vec![poly(&[1]), poly(&[2])]);
+        let wide = a.hstack(&column);
+        assert_eq!((wide.rows(), wide.cols()), (2, 3));
+        assert_eq!(
+            wide.row(1),
+            &[a[(1, 0)].clone(), a[(1, 1)].clone(), poly(&[2])]
+        );
+        let tall = a.vstack(&column.transpose());
+        assert_eq!((tall.rows(), tall.cols()), (3, 2));
+        assert_eq!(tall.row(2), &[poly(&[1]), poly(&[2])]);
+        assert_eq!(tall.get(3, 0), None);
+        assert!(Matrix::from_rows(vec![vec![poly(&[1])], vec![]]).is_err());
+    }
+
+    #[test]
+    fn random_constructors_are_seeded_and_in_evaluation_format() {
+        let mut discrete_gaussian = DiscreteGaussian::new(3.19).expect("valid deviation");
+        discrete_gaussian.set_std_dev(3.19);
+        let gaussian = |seed| {
+            Matrix::gaussian_with_rng(
+                &params(),
+                2,
+                3,
+                &discrete_gaussian,
+                &mut ChaCha20Rng::seed_from_u64(seed),
+            )
+        };
+        let g = gaussian(1);
+        assert_eq!(g, gaussian(1));
+        assert_ne!(g, gaussian(2));
+        assert!(
+            g.iter()
+                .all(|e| e.format() == PolynomialRingFormat::Evaluation)
+        );
+        assert!(g.iter().all(|e| e.norm() < 40.0));
+        assert!(g.iter().any(|e| e.norm() > 0.0));
+
+        let uniform =
+            |seed| Matrix::uniform_with_rng(&params(), 3, 2, &mut ChaCha20Rng::seed_from_u64(seed));
+        let u = uniform(1);
+        assert_eq!(u, uniform(1));
+        assert!(
+            u.iter()
+                .all(|e| e.format() == PolynomialRingFormat::Evaluation)
+        );
+        // A uniform entry is below Q / 256 with negligible probability
+        let q = BigInt::from(params().ciphertext_composite_modulus().clone());
+        let bound = (q >> 8u32).to_f64().expect("finite");
+        assert!(u.iter().all(|e| e.norm() > bound));
+        assert_ne!(u[(0, 0)], u[(0, 1)]);
+    }
+
+    #[test]
+    fn deserialized_matrices_hold_rows_times_cols_entries() {
+        let a = matrix(&entries());
+        let json = serde_json::to_string(&a).expect("serialize");
+        let back: Matrix<DcrtPoly> = serde_json::from_str(&json).expect("deserialize");
+        assert_eq!(back, a);
+
+        let mut value = serde_json::to_value(&a).expect("serialize");
+        value["rows"] = 3.into();
+        assert!(serde_json::from_value::<Matrix<DcrtPoly>>(value).is_err());
+        let mut value = serde_json::to_value(&a).expect("serialize");
+        value["cols"] = usize::MAX.into();
+        assert!(serde_json::from_value::<Matrix<DcrtPoly>>(value).is_err());
+    }
+}
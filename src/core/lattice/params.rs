@@ -1,11 +1,21 @@
+//! Element parameters and the HomomorphicEncryption.org security tables
+//!
+//! [`LatticeParams`] exposes the standard tables mapping a secret distribution,
+//! security level and ring dimension to the largest secure ciphertext modulus,
+//! so parameter planning can be done without building a context.
+
+use crate::ActingPrimitive;
 use crate::constants::{DistributionType, MAX_MODULUS_SIZE, SecurityLevel};
-use crate::core::utils::{get_totient, previous_prime, root_of_unity};
+use crate::core::utils::{
+    first_prime, get_totient, is_ntt_friendly, is_primitive_root_of_unity, previous_prime,
+    root_of_unity,
+};
 use crate::error::Error;
 use crypto_bigint::{Odd, U64};
 use derive_more::Display;
+use num::BigUint;
 use serde::{Deserialize, Serialize};
-use std::cell::{LazyCell, OnceCell};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::ops::{Index, IndexMut};
 use std::sync::LazyLock;
 use subtle::CtOption;
@@ -37,6 +47,7 @@ pub struct ElementParams {
 }
 
 impl ElementParams {
+    /// Create parameters for the cyclotomic `order` with a random prime modulus of `bits` bits
     pub fn with_modulus_bits(order: usize, bits: usize) -> Self {
         if bits > MAX_MODULUS_SIZE {
             panic!(
@@ -52,11 +63,13 @@ impl ElementParams {
         )
     }
 
+    /// Create parameters for the cyclotomic `order` and compute the root of unity for the modulus
     pub fn with_modulus(order: usize, ciphertext_modulus: Odd<U64>) -> Self {
         let root_of_unity = root_of_unity(order, ciphertext_modulus);
         Self::with_ciphertext_root_of_unity(order, ciphertext_modulus, root_of_unity)
     }
 
+    /// Create parameters with an already known root of unity for the modulus
     pub fn with_ciphertext_root_of_unity(
         order: usize,
         ciphertext_modulus: Odd<U64>,
@@ -71,6 +84,7 @@ impl ElementParams {
         )
     }
 
+    /// Create parameters with all moduli and roots of unity specified
     pub fn with_big_ciphertext_params(
         cyclotomic_order: usize,
         ciphertext_modulus: Odd<U64>,
@@ -89,19 +103,31 @@ impl ElementParams {
     }
 }
 
+/// Builder for [`DcrtElementParams`]
+///
+/// Only certain combinations of fields are valid, see [`DcrtElementParamsBuilder::build`].
 #[derive(Debug, Clone)]
 pub struct DcrtElementParamsBuilder {
+    /// The cyclotomic order shared by all towers
     pub ciphertext_order: usize,
+    /// Generate towers until their product covers this modulus
     pub modulus: Option<Odd<U64>>,
+    /// Generate this many towers
     pub depth: Option<usize>,
+    /// The bit size of each generated tower, used with `depth`
     pub bits: Option<usize>,
+    /// Use these moduli for the towers
     pub moduli: Option<Vec<Odd<U64>>>,
+    /// The roots of unity for `moduli`
     pub roots_of_unity: Option<Vec<U64>>,
+    /// The big moduli for arbitrary cyclotomics
     pub big_moduli: Option<Vec<Odd<U64>>>,
+    /// The roots of unity for `big_moduli`
     pub big_roots_of_unity: Option<Vec<U64>>,
 }
 
 impl DcrtElementParamsBuilder {
+    /// Create a new builder for the cyclotomic order `ciphertext_order`
     pub fn new(ciphertext_order: usize) -> Self {
         Self {
             ciphertext_order,
@@ -115,7 +141,20 @@ impl DcrtElementParamsBuilder {
         }
     }
 
+    /// Build the parameters.
+    ///
+    /// The accepted combinations are
+    /// - `modulus`
+    /// - `depth` with optional `bits`
+    /// - `moduli`
+    /// - `moduli` and `roots_of_unity`
+    /// - `moduli`, `roots_of_unity`, `big_moduli` and `big_roots_of_unity`
+    ///
+    /// Any other combination returns [`Error::DcrtElementParamsMismatch`].
     pub fn build(self) -> crate::error::Result<DcrtElementParams> {
+        if self.ciphertext_order < 2 || !self.ciphertext_order.is_power_of_two() {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
         match (
             self.modulus,
             self.depth,
@@ -126,20 +165,23 @@ impl DcrtElementParamsBuilder {
             self.big_roots_of_unity,
         ) {
             (Some(modulus), None, None, None, None, None, None) => {
-                let mut q: U64 = crypto_primes::generate_prime(MAX_MODULUS_SIZE as u32);
+                let mut q = first_prime(MAX_MODULUS_SIZE, self.ciphertext_order)
+                    .ok_or(Error::DcrtElementParamsMismatch)?;
                 let mut params = VecDeque::with_capacity(32);
                 params.push_back(ElementParams::with_modulus(
                     self.ciphertext_order,
                     CtOption::from(q.to_odd()).expect("modulus is not odd"),
                 ));
-                let mut composite_modulus = q;
+                let mut composite_modulus = BigUint::from(q.to_primitive());
+                let modulus = BigUint::from(modulus.get().to_primitive());
                 while composite_modulus < modulus {
-                    q = previous_prime(q, self.ciphertext_order);
+                    q = previous_prime(q, self.ciphertext_order)
+                        .ok_or(Error::DcrtElementParamsMismatch)?;
                     params.push_back(ElementParams::with_modulus(
                         self.ciphertext_order,
                         CtOption::from(q.to_odd()).expect("modulus is not odd"),
                     ));
-                    composite_modulus *= q;
+                    composite_modulus *= q.to_primitive();
                 }
                 Ok(DcrtElementParams {
                     params,
@@ -148,23 +190,25 @@ impl DcrtElementParamsBuilder {
             }
             (None, Some(depth), bits, None, None, None, None) => {
                 let bits = bits.unwrap_or(MAX_MODULUS_SIZE);
-                if bits > MAX_MODULUS_SIZE {
+                if depth == 0 || bits > MAX_MODULUS_SIZE {
                     return Err(Error::DcrtElementParamsMismatch);
                 }
-                let mut q: U64 = crypto_primes::generate_prime(MAX_MODULUS_SIZE as u32);
+                let mut q = first_prime(bits, self.ciphertext_order)
+                    .ok_or(Error::DcrtElementParamsMismatch)?;
                 let mut params = VecDeque::with_capacity(depth);
                 params.push_back(ElementParams::with_modulus(
                     self.ciphertext_order,
                     CtOption::from(q.to_odd()).expect("modulus is not odd"),
                 ));
-                let mut composite_modulus = q;
+                let mut composite_modulus = BigUint::from(q.to_primitive());
                 for _ in 1..depth {
-                    q = previous_prime(q, self.ciphertext_order);
+                    q = previous_prime(q, self.ciphertext_order)
+                        .ok_or(Error::DcrtElementParamsMismatch)?;
                     params.push_back(ElementParams::with_modulus(
                         self.ciphertext_order,
                         CtOption::from(q.to_odd()).expect("modulus is not odd"),
                     ));
-                    composite_modulus *= q;
+                    composite_modulus *= q.to_primitive();
                 }
                 Ok(DcrtElementParams {
                     params,
@@ -173,10 +217,17 @@ impl DcrtElementParamsBuilder {
             }
             (None, None, None, Some(moduli), None, None, None) => {
                 let mut params = VecDeque::with_capacity(moduli.len());
-                let mut composite_modulus = U64::ONE;
+                let mut composite_modulus = BigUint::from(1u8);
                 for modulus in moduli.iter() {
+                    let q = modulus.get().to_primitive();
+                    if !is_ntt_friendly(q, self.ciphertext_order) {
+                        return Err(Error::NotNttFriendlyModulus {
+                            modulus: q,
+                            cyclotomic_order: self.ciphertext_order,
+                        });
+                    }
                     params.push_back(ElementParams::with_modulus(self.ciphertext_order, *modulus));
-                    composite_modulus *= modulus.get();
+                    composite_modulus *= modulus.get().to_primitive();
                 }
                 Ok(DcrtElementParams {
                     params,
@@ -188,14 +239,14 @@ impl DcrtElementParamsBuilder {
                     return Err(Error::DcrtElementParamsMismatch);
                 }
                 let mut params = VecDeque::with_capacity(moduli.len());
-                let mut composite_modulus = U64::ONE;
+                let mut composite_modulus = BigUint::from(1u8);
                 for (modulus, root_of_unity) in moduli.iter().zip(roots_of_unity.iter()) {
                     params.push_back(ElementParams::with_ciphertext_root_of_unity(
                         self.ciphertext_order,
                         *modulus,
                         *root_of_unity,
                     ));
-                    composite_modulus *= modulus.get();
+                    composite_modulus *= modulus.get().to_primitive();
                 }
                 Ok(DcrtElementParams {
                     params,
@@ -219,7 +270,7 @@ impl DcrtElementParamsBuilder {
                 }
 
                 let mut params = VecDeque::with_capacity(moduli.len());
-                let mut composite_modulus = U64::ONE;
+                let mut composite_modulus = BigUint::from(1u8);
                 for ((modulus, root_of_unity), (big_modulus, big_root_of_unity)) in moduli
                     .iter()
                     .zip(roots_of_unity.iter())
@@ -232,7 +283,7 @@ impl DcrtElementParamsBuilder {
                         *big_modulus,
                         *big_root_of_unity,
                     ));
-                    composite_modulus *= modulus.get();
+                    composite_modulus *= modulus.get().to_primitive();
                 }
                 Ok(DcrtElementParams {
                     params,
@@ -243,42 +294,77 @@ impl DcrtElementParamsBuilder {
         }
     }
 
+    /// Set the modulus the towers must cover
     pub fn modulus(mut self, modulus: Odd<U64>) -> Self {
         self.modulus = Some(modulus);
         self
     }
 
+    /// Set the number of towers
     pub fn depth(mut self, depth: usize) -> Self {
         self.depth = Some(depth);
         self
     }
 
+    /// Set the bit size of the generated towers
     pub fn bits(mut self, bits: usize) -> Self {
         self.bits = Some(bits);
         self
     }
 
+    /// Set the tower moduli
     pub fn moduli(mut self, moduli: Vec<Odd<U64>>) -> Self {
         self.moduli = Some(moduli);
         self
     }
 
+    /// Set the roots of unity for the tower moduli
     pub fn roots_of_unity(mut self, roots_of_unity: Vec<U64>) -> Self {
         self.roots_of_unity = Some(roots_of_unity);
         self
     }
 
+    /// Set the big tower moduli
     pub fn big_moduli(mut self, big_moduli: Vec<Odd<U64>>) -> Self {
         self.big_moduli = Some(big_moduli);
         self
     }
 
+    /// Set the roots of unity for the big tower moduli
     pub fn big_roots_of_unity(mut self, big_roots_of_unity: Vec<U64>) -> Self {
         self.big_roots_of_unity = Some(big_roots_of_unity);
         self
     }
 }
 
+/// Check moduli from another library, see [`DcrtElementParams::from_external_moduli`]
+fn external_moduli(cyclotomic_order: usize, moduli: &[u64]) -> crate::error::Result<Vec<Odd<U64>>> {
+    let mut seen = HashSet::with_capacity(moduli.len());
+    let mut odd_moduli = Vec::with_capacity(moduli.len());
+    for &modulus in moduli {
+        if u64::BITS - modulus.leading_zeros() > MAX_MODULUS_SIZE as u32
+            || !is_ntt_friendly(modulus, cyclotomic_order)
+        {
+            return Err(Error::NotNttFriendlyModulus {
+                modulus,
+                cyclotomic_order,
+            });
+        }
+        if !seen.insert(modulus) {
+            return Err(Error::DuplicateModulus(modulus));
+        }
+        let odd = Option::<Odd<U64>>::from(Odd::new(U64::from_u64(modulus))).ok_or(
+            Error::NotNttFriendlyModulus {
+                modulus,
+                cyclotomic_order,
+            },
+        )?;
+        odd_moduli.push(odd);
+    }
+    Ok(odd_moduli)
+}
+
+/// Parameters for a double-CRT element, one [`ElementParams`] per tower
 #[derive(Debug, Clone, Default, Eq, PartialEq, Display, Serialize, Deserialize)]
 #[display(
     "DcrtElementParams {{ params: [{:?}], ciphertext_composite_modulus: {} }}",
@@ -287,7 +373,7 @@ impl DcrtElementParamsBuilder {
 )]
 pub struct DcrtElementParams {
     params: VecDeque<ElementParams>,
-    ciphertext_composite_modulus: U64,
+    ciphertext_composite_modulus: BigUint,
 }
 
 impl Index<usize> for DcrtElementParams {
@@ -305,50 +391,400 @@ impl IndexMut<usize> for DcrtElementParams {
 }
 
 impl DcrtElementParams {
+    /// Import tower moduli generated by another library such as SEAL or OpenFHE.
+    ///
+    /// Every modulus must be a distinct prime with `q = 1 mod cyclotomic_order`
+    /// and at most [`MAX_MODULUS_SIZE`] bits. The roots of unity are recomputed
+    /// locally instead of being trusted from the source.
+    pub fn from_external_moduli(
+        cyclotomic_order: usize,
+        moduli: &[u64],
+    ) -> crate::error::Result<Self> {
+        DcrtElementParamsBuilder::new(cyclotomic_order)
+            .moduli(external_moduli(cyclotomic_order, moduli)?)
+            .build()
+    }
+
+    /// Import tower moduli together with the roots of unity the source used.
+    ///
+    /// Values in evaluation form only match the source when the NTT uses the
+    /// same roots, so instead of recomputing them each root is checked to be a
+    /// primitive `cyclotomic_order`-th root of unity modulo its tower. The
+    /// moduli are checked as in [`DcrtElementParams::from_external_moduli`].
+    pub fn from_external_moduli_with_roots(
+        cyclotomic_order: usize,
+        moduli: &[u64],
+        roots_of_unity: &[u64],
+    ) -> crate::error::Result<Self> {
+        if moduli.len() != roots_of_unity.len() {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        let odd_moduli = external_moduli(cyclotomic_order, moduli)?;
+        let roots_of_unity = roots_of_unity
+            .iter()
+            .zip(&odd_moduli)
+            .map(|(&root, modulus)| {
+                let root_of_unity = U64::from_u64(root);
+                if is_primitive_root_of_unity(root_of_unity, cyclotomic_order, *modulus) {
+                    Ok(root_of_unity)
+                } else {
+                    Err(Error::InvalidRootOfUnity {
+                        root,
+                        modulus: modulus.get().to_primitive(),
+                        cyclotomic_order,
+                    })
+                }
+            })
+            .collect::<crate::error::Result<Vec<_>>>()?;
+        DcrtElementParamsBuilder::new(cyclotomic_order)
+            .moduli(odd_moduli)
+            .roots_of_unity(roots_of_unity)
+            .build()
+    }
+
+    /// The parameters of each tower
     pub fn params(&self) -> &VecDeque<ElementParams> {
         &self.params
     }
 
+    /// The product of all tower moduli
+    pub fn ciphertext_composite_modulus(&self) -> &BigUint {
+        &self.ciphertext_composite_modulus
+    }
+
+    /// Remove the first tower
     pub fn pop_front(&mut self) {
         if let Some(elem) = self.params.pop_front() {
-            self.ciphertext_composite_modulus /=
-                CtOption::from(elem.ciphertext_modulus.to_nz()).expect("Invalid modulus");
+            self.ciphertext_composite_modulus /= elem.ciphertext_modulus.get().to_primitive();
         }
     }
 
+    /// Remove the last tower
     pub fn pop_back(&mut self) {
         if let Some(elem) = self.params.pop_back() {
-            self.ciphertext_composite_modulus /=
-                CtOption::from(elem.ciphertext_modulus.to_nz()).expect("Invalid modulus");
+            self.ciphertext_composite_modulus /= elem.ciphertext_modulus.get().to_primitive();
+        }
+    }
+
+    /// Insert `count` new towers of `bits` bits directly after the first tower.
+    ///
+    /// Rescaling drops towers from the back, so the new towers are kept until
+    /// the last level. Their moduli differ from every existing tower.
+    pub fn insert_towers(&mut self, count: usize, bits: usize) -> crate::error::Result<()> {
+        let order = self
+            .params
+            .front()
+            .ok_or(Error::DcrtElementParamsMismatch)?
+            .cyclotomic_order;
+        let existing = self
+            .params
+            .iter()
+            .map(|p| p.ciphertext_modulus.get())
+            .collect::<HashSet<_>>();
+        let mut q = first_prime(bits, order).ok_or(Error::DcrtElementParamsMismatch)?;
+        for i in 0..count {
+            while existing.contains(&q) {
+                q = previous_prime(q, order).ok_or(Error::DcrtElementParamsMismatch)?;
+            }
+            let modulus = CtOption::from(q.to_odd()).expect("modulus is not odd");
+            self.params
+                .insert(1 + i, ElementParams::with_modulus(order, modulus));
+            self.ciphertext_composite_modulus *= q.to_primitive();
+            if i + 1 < count {
+                q = previous_prime(q, order).ok_or(Error::DcrtElementParamsMismatch)?;
+            }
         }
+        Ok(())
+    }
+
+    /// Whether the ring dimension and composite modulus are secure at
+    /// `security_level` in the HomomorphicEncryption.org tables.
+    ///
+    /// Every set of parameters meets [`SecurityLevel::HeStdNotSet`].
+    pub fn meets_security_level(
+        &self,
+        distribution_type: DistributionType,
+        security_level: SecurityLevel,
+    ) -> bool {
+        if security_level == SecurityLevel::HeStdNotSet {
+            return true;
+        }
+        let ring_dimension = self.params.front().map_or(0, |p| p.ring_dimension);
+        let log_q = self.ciphertext_composite_modulus.bits() as usize;
+        // Past the tables a larger ring is at least as secure as the largest entry
+        let max_log_q = match LatticeParams::largest(distribution_type, security_level) {
+            Some(largest) if ring_dimension > largest.ring_dimension => largest.max_log_q,
+            _ => LatticeParams::find_max_q(distribution_type, security_level, ring_dimension),
+        };
+        max_log_q >= log_q
+    }
+
+    /// The strongest security level of the family of `family`, classical or
+    /// quantum, that the parameters meet, or [`SecurityLevel::HeStdNotSet`]
+    pub fn effective_security_level(
+        &self,
+        distribution_type: DistributionType,
+        family: SecurityLevel,
+    ) -> SecurityLevel {
+        let levels = match family {
+            SecurityLevel::HeStd128Quantum
+            | SecurityLevel::HeStd192Quantum
+            | SecurityLevel::HeStd256Quantum => [
+                SecurityLevel::HeStd256Quantum,
+                SecurityLevel::HeStd192Quantum,
+                SecurityLevel::HeStd128Quantum,
+            ],
+            _ => [
+                SecurityLevel::HeStd256Classic,
+                SecurityLevel::HeStd192Classic,
+                SecurityLevel::HeStd128Classic,
+            ],
+        };
+        levels
+            .into_iter()
+            .find(|&l| self.meets_security_level(distribution_type, l))
+            .unwrap_or(SecurityLevel::HeStdNotSet)
+    }
+
+    /// Parameters for a partially consumed modulus chain that only keeps the
+    /// first `levels_remaining` towers.
+    ///
+    /// Towers are dropped from the back as rescaling consumes them, so the
+    /// result describes a ciphertext at a lower level without carrying the
+    /// full original chain.
+    pub fn shrink(&self, levels_remaining: usize) -> Self {
+        let mut result = self.clone();
+        while result.params.len() > levels_remaining {
+            result.pop_back();
+        }
+        result
+    }
+}
+
+/// [`DcrtElementParams`] whose mutations re-check the security tables.
+///
+/// Dropping, inserting or replacing towers changes the composite modulus
+/// and with it the security of the parameters. Every mutation here is
+/// applied to a copy first and only kept when the result still meets the
+/// required level, returning the new effective level, see
+/// [`DcrtElementParams::effective_security_level`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedGuardedDcrtElementParams")]
+pub struct GuardedDcrtElementParams {
+    params: DcrtElementParams,
+    distribution_type: DistributionType,
+    required: SecurityLevel,
+}
+
+/// The serialized form of [`GuardedDcrtElementParams`], checked by
+/// [`GuardedDcrtElementParams::new`] when deserialized
+#[derive(Deserialize)]
+struct UncheckedGuardedDcrtElementParams {
+    params: DcrtElementParams,
+    distribution_type: DistributionType,
+    required: SecurityLevel,
+}
+
+impl TryFrom<UncheckedGuardedDcrtElementParams> for GuardedDcrtElementParams {
+    type Error = Error;
+
+    fn try_from(value: UncheckedGuardedDcrtElementParams) -> crate::error::Result<Self> {
+        Self::new(value.params, value.distribution_type, value.required)
     }
 }
 
+impl GuardedDcrtElementParams {
+    /// Guard `params` so they keep meeting `required` for secrets of `distribution_type`
+    pub fn new(
+        params: DcrtElementParams,
+        distribution_type: DistributionType,
+        required: SecurityLevel,
+    ) -> crate::error::Result<Self> {
+        let guarded = Self {
+            params,
+            distribution_type,
+            required,
+        };
+        guarded.check(&guarded.params)?;
+        Ok(guarded)
+    }
+
+    /// The guarded parameters
+    pub fn params(&self) -> &DcrtElementParams {
+        &self.params
+    }
+
+    /// The guarded parameters, dropping the guard
+    pub fn into_inner(self) -> DcrtElementParams {
+        self.params
+    }
+
+    /// The level the parameters must keep meeting
+    pub fn required_security_level(&self) -> SecurityLevel {
+        self.required
+    }
+
+    /// The strongest level of the required family the parameters meet
+    pub fn security_level(&self) -> SecurityLevel {
+        self.params
+            .effective_security_level(self.distribution_type, self.required)
+    }
+
+    /// [`DcrtElementParams::pop_front`], checked
+    pub fn pop_front(&mut self) -> crate::error::Result<SecurityLevel> {
+        self.mutate(DcrtElementParams::pop_front)
+    }
+
+    /// [`DcrtElementParams::pop_back`], checked
+    pub fn pop_back(&mut self) -> crate::error::Result<SecurityLevel> {
+        self.mutate(DcrtElementParams::pop_back)
+    }
+
+    /// [`DcrtElementParams::shrink`], checked
+    pub fn shrink(&mut self, levels_remaining: usize) -> crate::error::Result<SecurityLevel> {
+        self.mutate(|p| *p = p.shrink(levels_remaining))
+    }
+
+    /// [`DcrtElementParams::insert_towers`], checked
+    pub fn insert_towers(
+        &mut self,
+        count: usize,
+        bits: usize,
+    ) -> crate::error::Result<SecurityLevel> {
+        let mut params = self.params.clone();
+        params.insert_towers(count, bits)?;
+        self.replace(params)
+    }
+
+    /// Replace the modulus of tower `index` by `modulus`, checked
+    pub fn switch_modulus(
+        &mut self,
+        index: usize,
+        modulus: Odd<U64>,
+    ) -> crate::error::Result<SecurityLevel> {
+        let old = self
+            .params
+            .params
+            .get(index)
+            .ok_or(Error::DcrtElementParamsMismatch)?;
+        let value = modulus.get().to_primitive();
+        if !is_ntt_friendly(value, old.cyclotomic_order) {
+            return Err(Error::NotNttFriendlyModulus {
+                modulus: value,
+                cyclotomic_order: old.cyclotomic_order,
+            });
+        }
+        if self
+            .params
+            .params
+            .iter()
+            .enumerate()
+            .any(|(i, p)| i != index && p.ciphertext_modulus == modulus)
+        {
+            return Err(Error::DuplicateModulus(value));
+        }
+        let mut params = self.params.clone();
+        params.ciphertext_composite_modulus /= old.ciphertext_modulus.get().to_primitive();
+        params.ciphertext_composite_modulus *= value;
+        params.params[index] = ElementParams::with_modulus(old.cyclotomic_order, modulus);
+        self.replace(params)
+    }
+
+    fn mutate(
+        &mut self,
+        f: impl FnOnce(&mut DcrtElementParams),
+    ) -> crate::error::Result<SecurityLevel> {
+        let mut params = self.params.clone();
+        f(&mut params);
+        self.replace(params)
+    }
+
+    fn replace(&mut self, params: DcrtElementParams) -> crate::error::Result<SecurityLevel> {
+        self.check(&params)?;
+        self.params = params;
+        Ok(self.security_level())
+    }
+
+    fn check(&self, params: &DcrtElementParams) -> crate::error::Result<()> {
+        if params.meets_security_level(self.distribution_type, self.required) {
+            return Ok(());
+        }
+        Err(Error::SecurityDowngrade(format!(
+            "a {} bit modulus in ring dimension {} is below {}, it only meets {}",
+            params.ciphertext_composite_modulus.bits(),
+            params.params.front().map_or(0, |p| p.ring_dimension),
+            self.required,
+            params.effective_security_level(self.distribution_type, self.required)
+        )))
+    }
+}
+
+/// An entry of the HomomorphicEncryption.org security tables
+///
+/// Each entry states the largest ciphertext modulus, in bits, for which a ring
+/// dimension reaches a security level given the secret key distribution.
 #[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Display, Serialize, Deserialize)]
 #[display(
     "LatticeParams {{ distribution_type: {distribution_type}, ring_dimension: {ring_dimension}, min_security_level: {min_security_level}, max_log_q: {max_log_q} }}"
 )]
 pub struct LatticeParams {
+    /// The distribution of the secret key
     pub distribution_type: DistributionType,
+    /// The ring dimension
     pub ring_dimension: usize,
+    /// The security level reached
     pub min_security_level: SecurityLevel,
+    /// The largest ciphertext modulus in bits
     pub max_log_q: usize,
 }
 
 impl LatticeParams {
-    pub fn find_max_q(
+    /// All entries of the security tables
+    pub fn table() -> &'static [LatticeParams] {
+        LATTICE_PARAMS.as_slice()
+    }
+
+    /// Find the table entry for the `ring_dimension`
+    pub fn find(
         distribution_type: DistributionType,
         security_level: SecurityLevel,
         ring_dimension: usize,
-    ) -> usize {
+    ) -> Option<&'static LatticeParams> {
+        let dist = distribution_type as usize;
+        let sec = security_level as usize;
+        BY_RING[dist][sec].get(&ring_dimension).copied()
+    }
+
+    /// The table entry with the largest ring dimension
+    pub fn largest(
+        distribution_type: DistributionType,
+        security_level: SecurityLevel,
+    ) -> Option<&'static LatticeParams> {
         let dist = distribution_type as usize;
         let sec = security_level as usize;
         BY_RING[dist][sec]
-            .get(&ring_dimension)
+            .values()
+            .max_by_key(|l| l.ring_dimension)
+            .copied()
+    }
+
+    /// Find the largest ciphertext modulus in bits for the `ring_dimension`.
+    ///
+    /// Returns 0 if the ring dimension is not in the tables
+    pub fn find_max_q(
+        distribution_type: DistributionType,
+        security_level: SecurityLevel,
+        ring_dimension: usize,
+    ) -> usize {
+        Self::find(distribution_type, security_level, ring_dimension)
             .map(|l| l.max_log_q)
             .unwrap_or_default()
     }
 
+    /// Find the ring dimension needed for a ciphertext modulus of `current_log_q` bits.
+    ///
+    /// If the modulus is larger than any in the tables, twice the largest
+    /// ring dimension is returned.
     pub fn find_ring_dimension(
         distribution_type: DistributionType,
         security_level: SecurityLevel,
@@ -367,6 +803,24 @@ impl LatticeParams {
         }
         n * 2
     }
+
+    /// The smallest ring dimension in the tables that is secure for a
+    /// ciphertext modulus of `log_q` bits.
+    ///
+    /// Returns [`None`] if no entry supports the modulus or the security level
+    /// is [`SecurityLevel::HeStdNotSet`].
+    pub fn min_ring_dimension(
+        distribution_type: DistributionType,
+        security_level: SecurityLevel,
+        log_q: usize,
+    ) -> Option<usize> {
+        let dist = distribution_type as usize;
+        let sec = security_level as usize;
+        BY_LOG_Q[dist][sec]
+            .range(log_q..)
+            .next()
+            .map(|(_, lattice)| lattice.ring_dimension)
+    }
 }
 
 static BY_RING: LazyLock<
@@ -410,36 +864,36 @@ static BY_RING: LazyLock<
 });
 
 static BY_LOG_Q: LazyLock<
-    [[HashMap<usize, &'static LatticeParams>; SecurityLevel::NUM_VALUES];
+    [[BTreeMap<usize, &'static LatticeParams>; SecurityLevel::NUM_VALUES];
         DistributionType::NUM_VALUES],
 > = LazyLock::new(|| {
     let mut map = [
         [
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
         ],
         [
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
         ],
         [
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
-            HashMap::with_capacity(LATTICE_PARAMS.len()),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            BTreeMap::new(),
         ],
     ];
     for p in LATTICE_PARAMS.iter() {
@@ -1244,3 +1698,304 @@ static LATTICE_PARAMS: LazyLock<Vec<LatticeParams>> = LazyLock::new(|| {
         },
     ]
 });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ring dimension 2048
+    const ORDER: usize = 4096;
+
+    #[test]
+    fn external_moduli_are_imported_with_local_roots() {
+        let params = DcrtElementParams::from_external_moduli(ORDER, &[12289, 40961])
+            .expect("NTT friendly primes");
+        assert_eq!(params.params().len(), 2);
+        assert_eq!(
+            params.ciphertext_composite_modulus(),
+            &BigUint::from(12289u64 * 40961)
+        );
+        for p in params.params() {
+            assert!(is_primitive_root_of_unity(
+                p.root_of_unity,
+                ORDER,
+                p.ciphertext_modulus
+            ));
+        }
+
+        let roots = params
+            .params()
+            .iter()
+            .map(|p| p.root_of_unity.to_primitive())
+            .collect::<Vec<_>>();
+        let with_roots =
+            DcrtElementParams::from_external_moduli_with_roots(ORDER, &[12289, 40961], &roots)
+                .expect("valid roots");
+        assert_eq!(with_roots, params);
+    }
+
+    #[test]
+    fn external_moduli_reject_composites() {
+        // 4097 = 17 * 241 is 1 mod 4096
+        assert!(matches!(
+            DcrtElementParams::from_external_moduli(ORDER, &[12289, 4097]),
+            Err(Error::NotNttFriendlyModulus {
+                modulus: 4097,
+                cyclotomic_order: ORDER
+            })
+        ));
+    }
+
+    #[test]
+    fn external_moduli_reject_primes_without_the_order() {
+        // 65539 is prime but 2 mod 4096
+        assert!(matches!(
+            DcrtElementParams::from_external_moduli(ORDER, &[65539]),
+            Err(Error::NotNttFriendlyModulus {
+                modulus: 65539,
+                cyclotomic_order: ORDER
+            })
+        ));
+    }
+
+    #[test]
+    fn external_moduli_reject_duplicates() {
+        assert!(matches!(
+            DcrtElementParams::from_external_moduli(ORDER, &[12289, 40961, 12289]),
+            Err(Error::DuplicateModulus(12289))
+        ));
+    }
+
+    #[test]
+    fn external_moduli_reject_wrong_roots() {
+        let q = Odd::new(U64::from_u64(12289)).expect("odd modulus");
+        let root = root_of_unity(ORDER, q).to_primitive();
+        // A root of half the order, one of order two and one that is no root
+        let half_order = root * root % 12289;
+        for wrong in [half_order, 12288, 1, 3] {
+            assert!(
+                matches!(
+                    DcrtElementParams::from_external_moduli_with_roots(
+                        ORDER,
+                        &[12289],
+                        &[wrong]
+                    ),
+                    Err(Error::InvalidRootOfUnity {
+                        root,
+                        modulus: 12289,
+                        cyclotomic_order: ORDER
+                    }) if root == wrong
+                ),
+                "{wrong}"
+            );
+        }
+        assert!(matches!(
+            DcrtElementParams::from_external_moduli_with_roots(ORDER, &[12289], &[root, root]),
+            Err(Error::DcrtElementParamsMismatch)
+        ));
+    }
+
+    /// Ring dimension 4096, where uniform secrets allow 111, 77 and 60 bits
+    /// at the 128, 192 and 256 bit classical levels
+    const GUARDED_ORDER: usize = 8192;
+
+    fn guard(
+        depth: usize,
+        required: SecurityLevel,
+    ) -> crate::error::Result<GuardedDcrtElementParams> {
+        let params = DcrtElementParamsBuilder::new(GUARDED_ORDER)
+            .depth(depth)
+            .bits(20)
+            .build()
+            .expect("valid params");
+        GuardedDcrtElementParams::new(params, DistributionType::Uniform, required)
+    }
+
+    #[test]
+    fn guarded_towers_are_dropped_and_inserted_while_secure() {
+        let mut guarded = guard(4, SecurityLevel::HeStd128Classic).expect("80 bits are secure");
+        assert_eq!(guarded.security_level(), SecurityLevel::HeStd128Classic);
+
+        assert_eq!(
+            guarded.pop_back().expect("60 bits"),
+            SecurityLevel::HeStd256Classic
+        );
+        assert_eq!(guarded.params().params().len(), 3);
+        assert_eq!(
+            guarded.insert_towers(1, 20).expect("80 bits"),
+            SecurityLevel::HeStd128Classic
+        );
+        assert_eq!(
+            guarded.pop_front().expect("60 bits"),
+            SecurityLevel::HeStd256Classic
+        );
+        assert_eq!(
+            guarded.shrink(2).expect("40 bits"),
+            SecurityLevel::HeStd256Classic
+        );
+
+        let before = guarded.clone();
+        assert!(matches!(
+            guarded.insert_towers(5, 20),
+            Err(Error::SecurityDowngrade(_))
+        ));
+        assert_eq!(guarded, before);
+        assert!(matches!(
+            guard(6, SecurityLevel::HeStd128Classic),
+            Err(Error::SecurityDowngrade(_))
+        ));
+    }
+
+    #[test]
+    fn guarded_moduli_are_switched_while_secure() {
+        let mut guarded = guard(3, SecurityLevel::HeStd192Classic).expect("60 bits are secure");
+        let moduli = guarded
+            .params()
+            .params()
+            .iter()
+            .map(|p| p.ciphertext_modulus)
+            .collect::<Vec<_>>();
+        let below = previous_prime(moduli[2].get(), GUARDED_ORDER).expect("smaller prime");
+        let below = Odd::new(below).expect("odd prime");
+        assert_eq!(
+            guarded.switch_modulus(2, below).expect("still 60 bits"),
+            SecurityLevel::HeStd256Classic
+        );
+        assert_eq!(guarded.params().params()[2].ciphertext_modulus, below);
+
+        let before = guarded.clone();
+        let composite = Odd::new(U64::from_u64(4097)).expect("odd");
+        assert!(matches!(
+            guarded.switch_modulus(0, composite),
+            Err(Error::NotNttFriendlyModulus { modulus: 4097, .. })
+        ));
+        assert!(matches!(
+            guarded.switch_modulus(0, below),
+            Err(Error::DuplicateModulus(_))
+        ));
+        assert!(matches!(
+            guarded.switch_modulus(3, below),
+            Err(Error::DcrtElementParamsMismatch)
+        ));
+        // 40 + 2 * 20 bits is past the 77 bits of the 192 bit level
+        let large = first_prime(40, GUARDED_ORDER).expect("40 bit prime");
+        let large = Odd::new(large).expect("odd prime");
+        assert!(matches!(
+            guarded.switch_modulus(0, large),
+            Err(Error::SecurityDowngrade(_))
+        ));
+        assert_eq!(guarded, before);
+    }
+
+    #[test]
+    fn guarded_params_are_checked_when_deserialized() {
+        let guarded = guard(4, SecurityLevel::HeStd128Classic).expect("80 bits are secure");
+        let json = serde_json::to_string(&guarded).expect("serialize");
+        let back: GuardedDcrtElementParams = serde_json::from_str(&json).expect("deserialize");
+        assert_eq!(back, guarded);
+
+        let mut value = serde_json::to_value(&guarded).expect("serialize");
+        value["required"] =
+            serde_json::to_value(SecurityLevel::HeStd256Classic).expect("serialize");
+        assert!(serde_json::from_value::<GuardedDcrtElementParams>(value).is_err());
+    }
+
+    #[test]
+    fn rings_past_the_tables_meet_the_largest_entry() {
+        let largest =
+            LatticeParams::largest(DistributionType::Ternary, SecurityLevel::HeStd128Classic)
+                .expect("table entry");
+        assert_eq!(largest.ring_dimension, 131072);
+        let params = DcrtElementParamsBuilder::new(4 * largest.ring_dimension)
+            .depth(1)
+            .bits(30)
+            .build()
+            .expect("valid params");
+        assert!(
+            params.meets_security_level(DistributionType::Ternary, SecurityLevel::HeStd128Classic)
+        );
+        assert_eq!(
+            params.effective_security_level(
+                DistributionType::Ternary,
+                SecurityLevel::HeStd128Quantum
+            ),
+            SecurityLevel::HeStd256Quantum
+        );
+    }
+
+    #[test]
+    fn tables_give_the_standard_moduli() {
+        let ternary = LatticeParams::find(
+            DistributionType::Ternary,
+            SecurityLevel::HeStd128Classic,
+            8192,
+        )
+        .expect("table entry");
+        assert_eq!(ternary.max_log_q, 218);
+        assert_eq!(ternary.distribution_type, DistributionType::Ternary);
+        assert_eq!(ternary.min_security_level, SecurityLevel::HeStd128Classic);
+        for (dist, sec, ring_dimension, max_log_q) in [
+            (
+                DistributionType::Ternary,
+                SecurityLevel::HeStd128Classic,
+                131072,
+                3523,
+            ),
+            (
+                DistributionType::Uniform,
+                SecurityLevel::HeStd192Classic,
+                ORDER,
+                77,
+            ),
+            (
+                DistributionType::Error,
+                SecurityLevel::HeStd256Classic,
+                8192,
+                120,
+            ),
+            (
+                DistributionType::Ternary,
+                SecurityLevel::HeStd128Classic,
+                5000,
+                0,
+            ),
+        ] {
+            assert_eq!(
+                LatticeParams::find_max_q(dist, sec, ring_dimension),
+                max_log_q,
+                "{dist} {sec} {ring_dimension}"
+            );
+        }
+    }
+
+    #[test]
+    fn ring_dimensions_at_the_table_boundaries() {
+        let dist = DistributionType::Ternary;
+        let sec = SecurityLevel::HeStd128Classic;
+        for (log_q, ring_dimension) in [
+            (1, 1024),
+            (27, 1024),
+            (28, 2048),
+            (218, 8192),
+            (219, 16384),
+            (3523, 131072),
+        ] {
+            assert_eq!(
+                LatticeParams::min_ring_dimension(dist, sec, log_q),
+                Some(ring_dimension),
+                "{log_q}"
+            );
+            assert_eq!(
+                LatticeParams::find_ring_dimension(dist, sec, log_q),
+                ring_dimension,
+                "{log_q}"
+            );
+        }
+        assert_eq!(LatticeParams::min_ring_dimension(dist, sec, 3524), None);
+        assert_eq!(LatticeParams::find_ring_dimension(dist, sec, 3524), 262144);
+        assert_eq!(
+            LatticeParams::min_ring_dimension(dist, SecurityLevel::HeStdNotSet, 1),
+            None
+        );
+    }
+}
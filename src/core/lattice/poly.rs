@@ -1,18 +1,22 @@
 use crate::constants::PolynomialRingFormat;
 use crate::core::lattice::params::ElementParams;
-use crate::serdes::monty_params;
+use crate::serdes::{FieldIndex, monty_params};
+use std::convert::Infallible;
 use std::marker::PhantomData;
 
 use crate::ActingPrimitive;
-use crate::core::math::{DiscreteGaussian, VecMod, VecModStd};
-use crate::core::utils::reverse_bits;
+use crate::core::math::{
+    BoundedVecMod, DiscreteGaussian, VecMod, VecModStd, cached_monty_params, scratch,
+};
+use crate::core::utils::{cyclotomic_polynomial, get_coprimes, reverse_bits};
 use crypto_bigint::modular::{MontyParams, Retrieve};
-use crypto_bigint::{Monty, NonZero, Odd, U64, modular::MontyForm};
+use crypto_bigint::{Monty, Odd, U64, U128, modular::MontyForm};
 use num::Integer;
+use rand::CryptoRng;
 use rand::distr::Distribution;
-use rand::{Rng, RngCore};
 use rand_distr::Normal;
-use serde::{Deserialize, Serialize};
+use serde::de::{DeserializeSeed, Error as DError, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::ops::{
     Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Rem, RemAssign, Sub,
     SubAssign,
@@ -63,7 +67,9 @@ macro_rules! poly_ops_variants {
     };
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub(crate) use poly_ops_variants;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Poly {
     format: PolynomialRingFormat,
     params: ElementParams,
@@ -72,6 +78,148 @@ pub struct Poly {
     monty_params_ciphertext_modulus: MontyParams<{ U64::LIMBS }>,
     #[serde(with = "monty_params")]
     monty_params_big_ciphertext_modulus: MontyParams<{ U64::LIMBS }>,
+    #[serde(skip)]
+    cache: FormatCache,
+}
+
+/// The values of a [`Poly`] in the format it is not in, see [`Poly::set_format_cache`]
+#[derive(Debug, Clone, Default)]
+struct FormatCache {
+    enabled: bool,
+    other: Option<VecModStd>,
+}
+
+impl PartialEq for Poly {
+    fn eq(&self, other: &Self) -> bool {
+        self.format == other.format
+            && self.params == other.params
+            && self.values == other.values
+            && self.monty_params_ciphertext_modulus == other.monty_params_ciphertext_modulus
+            && self.monty_params_big_ciphertext_modulus == other.monty_params_big_ciphertext_modulus
+    }
+}
+
+impl Eq for Poly {}
+
+/// Deserializes a [`Poly`] of ring dimension `ring_dimension`, rejecting
+/// other parameters and longer value vectors before they are decoded
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct PolySeed {
+    pub(crate) ring_dimension: usize,
+}
+
+const POLY_FIELDS: &[&str] = &[
+    "format",
+    "params",
+    "values",
+    "monty_params_ciphertext_modulus",
+    "monty_params_big_ciphertext_modulus",
+];
+
+/// The Montgomery parameters of a modulus, see [`monty_params`]
+struct SerdeMontyParams(MontyParams<{ U64::LIMBS }>);
+
+impl<'de> Deserialize<'de> for SerdeMontyParams {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        monty_params::deserialize::<D, { U64::LIMBS }, { U128::LIMBS }>(d).map(Self)
+    }
+}
+
+impl PolySeed {
+    fn check<E: DError>(&self, params: &ElementParams) -> Result<(), E> {
+        if params.ring_dimension != self.ring_dimension {
+            return Err(E::custom(format!(
+                "ring dimension {} where {} is expected",
+                params.ring_dimension, self.ring_dimension
+            )));
+        }
+        Ok(())
+    }
+
+    fn values(&self) -> BoundedVecMod<{ U64::LIMBS }, { U128::LIMBS }> {
+        BoundedVecMod::new(self.ring_dimension)
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for PolySeed {
+    type Value = Poly;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Poly, D::Error> {
+        d.deserialize_struct("Poly", POLY_FIELDS, self)
+    }
+}
+
+impl<'de> Visitor<'de> for PolySeed {
+    type Value = Poly;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a polynomial of ring dimension {}", self.ring_dimension)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Poly, A::Error> {
+        let missing = |i| DError::invalid_length(i, &self);
+        let format = seq.next_element()?.ok_or_else(|| missing(0))?;
+        let params: ElementParams = seq.next_element()?.ok_or_else(|| missing(1))?;
+        self.check(&params)?;
+        let values = seq
+            .next_element_seed(self.values())?
+            .ok_or_else(|| missing(2))?;
+        let SerdeMontyParams(monty_params_ciphertext_modulus) =
+            seq.next_element()?.ok_or_else(|| missing(3))?;
+        let SerdeMontyParams(monty_params_big_ciphertext_modulus) =
+            seq.next_element()?.ok_or_else(|| missing(4))?;
+        Ok(Poly {
+            format,
+            params,
+            values,
+            monty_params_ciphertext_modulus,
+            monty_params_big_ciphertext_modulus,
+            cache: FormatCache::default(),
+        })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Poly, A::Error> {
+        let mut format = None;
+        let mut params = None;
+        let mut values = None;
+        let mut monty = None;
+        let mut big_monty = None;
+        while let Some(field) = map.next_key_seed(FieldIndex(POLY_FIELDS))? {
+            let duplicate = match field {
+                Some(0) => format.replace(map.next_value()?).is_some(),
+                Some(1) => {
+                    let p = map.next_value()?;
+                    self.check(&p)?;
+                    params.replace(p).is_some()
+                }
+                Some(2) => values
+                    .replace(map.next_value_seed(self.values())?)
+                    .is_some(),
+                Some(3) => monty
+                    .replace(map.next_value::<SerdeMontyParams>()?.0)
+                    .is_some(),
+                Some(4) => big_monty
+                    .replace(map.next_value::<SerdeMontyParams>()?.0)
+                    .is_some(),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                    false
+                }
+            };
+            if duplicate {
+                return Err(DError::custom("duplicate field"));
+            }
+        }
+        let field = |i: usize| DError::missing_field(POLY_FIELDS[i]);
+        Ok(Poly {
+            format: format.ok_or_else(|| field(0))?,
+            params: params.ok_or_else(|| field(1))?,
+            values: values.ok_or_else(|| field(2))?,
+            monty_params_ciphertext_modulus: monty.ok_or_else(|| field(3))?,
+            monty_params_big_ciphertext_modulus: big_monty.ok_or_else(|| field(4))?,
+            cache: FormatCache::default(),
+        })
+    }
 }
 
 impl Index<usize> for Poly {
@@ -84,7 +232,7 @@ impl Index<usize> for Poly {
 
 impl IndexMut<usize> for Poly {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        &mut self.values[index]
+        &mut self.values_mut()[index]
     }
 }
 
@@ -101,8 +249,9 @@ impl Neg for &Poly {
 
     fn neg(self) -> Self::Output {
         let mut result = self.clone();
-        for i in result.values.iter_mut() {
-            let ii = MontyForm::new(i, result.monty_params_ciphertext_modulus);
+        let params = result.monty_params_ciphertext_modulus;
+        for i in result.values_mut().iter_mut() {
+            let ii = MontyForm::new(i, params);
             *i = ii.neg().retrieve();
         }
         result
@@ -124,6 +273,7 @@ poly_ops_variants!(Mul, mul, *, MulAssign, mul_assign, *=, LHS = Poly, RHS = Pol
 impl AddAssign<&U64> for Poly {
     fn add_assign(&mut self, rhs: &U64) {
         let r = MontyForm::new(rhs, self.monty_params_ciphertext_modulus);
+        self.invalidate_format_cache();
         match self.format {
             PolynomialRingFormat::Coefficient => {
                 let e = MontyForm::new(&self.values[0], self.monty_params_ciphertext_modulus);
@@ -141,13 +291,13 @@ impl AddAssign<&U64> for Poly {
 
 impl SubAssign<&U64> for Poly {
     fn sub_assign(&mut self, rhs: &U64) {
-        self.values -= rhs;
+        *self.values_mut() -= rhs;
     }
 }
 
 impl MulAssign<&U64> for Poly {
     fn mul_assign(&mut self, rhs: &U64) {
-        self.values *= rhs;
+        *self.values_mut() *= rhs;
     }
 }
 
@@ -160,7 +310,7 @@ impl MulAssign<&(U64, U64)> for Poly {
 
 impl RemAssign<&Odd<U64>> for Poly {
     fn rem_assign(&mut self, rhs: &Odd<U64>) {
-        self.values %= rhs;
+        *self.values_mut() %= rhs;
     }
 }
 
@@ -168,8 +318,9 @@ impl DivAssign<&U64> for Poly {
     fn div_assign(&mut self, rhs: &U64) {
         let r = MontyForm::new(rhs, self.monty_params_ciphertext_modulus);
         let r_inv = CtOption::from(r.inv()).expect("r is not zero");
-        for e in self.values.iter_mut() {
-            let i = MontyForm::new(e, self.monty_params_ciphertext_modulus);
+        let params = self.monty_params_ciphertext_modulus;
+        for e in self.values_mut().iter_mut() {
+            let i = MontyForm::new(e, params);
             *e = (i * r_inv).retrieve();
         }
     }
@@ -180,7 +331,7 @@ impl AddAssign<&Poly> for Poly {
         assert_eq!(self.params, rhs.params);
         assert_eq!(self.format, rhs.format);
 
-        self.values += &rhs.values;
+        *self.values_mut() += &rhs.values;
     }
 }
 
@@ -189,14 +340,22 @@ impl SubAssign<&Poly> for Poly {
         assert_eq!(self.params, rhs.params);
         assert_eq!(self.format, rhs.format);
 
-        self.values -= &rhs.values;
+        *self.values_mut() -= &rhs.values;
     }
 }
 
 impl MulAssign<&Poly> for Poly {
     fn mul_assign(&mut self, rhs: &Self) {
-        let res = self.ntt() * rhs.ntt();
-        *self = res.inv();
+        assert_eq!(self.params, rhs.params);
+        assert_eq!(self.format, rhs.format);
+
+        match self.format {
+            PolynomialRingFormat::Evaluation => *self.values_mut() *= &rhs.values,
+            PolynomialRingFormat::Coefficient => {
+                let res = self.ntt() * rhs.ntt();
+                *self.values_mut() = res.inv().values;
+            }
+        }
     }
 }
 
@@ -211,13 +370,43 @@ impl Poly {
             params,
             values: discrete_gaussian
                 .gen_vec_mod(params.ring_dimension, &params.ciphertext_modulus),
-            monty_params_ciphertext_modulus: MontyParams::new(params.ciphertext_modulus),
-            monty_params_big_ciphertext_modulus: MontyParams::new(params.big_ciphertext_modulus),
+            monty_params_ciphertext_modulus: cached_monty_params(params.ciphertext_modulus),
+            monty_params_big_ciphertext_modulus: cached_monty_params(params.big_ciphertext_modulus),
+            cache: FormatCache::default(),
         };
         // res.set_format(format);
         res
     }
 
+    /// Map small signed coefficients into `[0, q)`
+    pub fn from_signed(params: ElementParams, values: &[i64]) -> Self {
+        Self {
+            format: PolynomialRingFormat::Coefficient,
+            params,
+            values: VecMod::from_signed(values, params.ciphertext_modulus),
+            monty_params_ciphertext_modulus: cached_monty_params(params.ciphertext_modulus),
+            monty_params_big_ciphertext_modulus: cached_monty_params(params.big_ciphertext_modulus),
+            cache: FormatCache::default(),
+        }
+    }
+
+    /// Overwrite the coefficients with small signed values in place
+    pub fn fill_signed(&mut self, values: &[i64]) {
+        self.format = PolynomialRingFormat::Coefficient;
+        self.values_mut().fill_signed(values);
+    }
+
+    /// Overwrite every value with one uniform modulo the modulus, keeping the format
+    pub fn fill_uniform_with_rng(&mut self, rng: impl CryptoRng) {
+        let len = self.values.len();
+        *self.values_mut() = VecMod::random(rng, len, self.params.ciphertext_modulus);
+    }
+
+    /// The centered representatives of the coefficients
+    pub fn to_signed(&self) -> Vec<i64> {
+        self.values.to_signed()
+    }
+
     pub fn format(&self) -> PolynomialRingFormat {
         self.format
     }
@@ -240,7 +429,7 @@ impl Poly {
 
     pub fn set_values(&mut self, values: &[u64]) {
         let m = self.params.ciphertext_modulus.get().to_primitive();
-        for (v, &val) in self.values.iter_mut().zip(values) {
+        for (v, &val) in self.values_mut().iter_mut().zip(values) {
             *v = U64::from_u64(val % m);
         }
     }
@@ -249,56 +438,155 @@ impl Poly {
         self.params.cyclotomic_order
     }
 
+    /// The image under the automorphism `X -> X^index` for an `index`
+    /// coprime to the cyclotomic order, in the current format.
+    ///
+    /// Coefficient `i` moves to `i index mod 2n`, negated past `n` since
+    /// `x^n = -1`. Evaluation `j` is at `psi^(2j + 1)`, so it is taken from
+    /// the evaluation at `psi^((2j + 1) index)` without leaving the
+    /// evaluation format.
+    pub fn automorphism(&self, index: usize) -> Self {
+        self.automorphism_transform(index)
+    }
+
+    /// Replace every coefficient `c` in `[0, q)` by `f(c) mod q`.
+    ///
+    /// A polynomial in evaluation format is transformed to coefficients for
+    /// `f` and back, so the format and the cached representation stay
+    /// consistent with the new values.
+    pub fn map_coefficients(&mut self, mut f: impl FnMut(u64) -> u64) {
+        let Ok(()) = self.try_map(|c| Ok::<_, Infallible>(f(c)));
+    }
+
+    /// [`Poly::map_coefficients`] with a fallible `f`, leaving the
+    /// polynomial unchanged when it fails for any coefficient
+    pub fn try_map<E>(&mut self, mut f: impl FnMut(u64) -> Result<u64, E>) -> Result<(), E> {
+        let evaluation = self.format == PolynomialRingFormat::Evaluation;
+        if evaluation {
+            self.switch_format();
+        }
+        let q = self.params.ciphertext_modulus.get().to_primitive();
+        let mapped = self
+            .values
+            .iter()
+            .map(|c| f(c.to_primitive()).map(|c| U64::from_u64(c % q)))
+            .collect::<Result<Vec<_>, E>>();
+        if let Ok(mapped) = &mapped {
+            self.values_mut()
+                .iter_mut()
+                .zip(mapped)
+                .for_each(|(v, c)| *v = *c);
+        }
+        if evaluation {
+            self.switch_format();
+        }
+        mapped.map(|_| ())
+    }
+
     /// Permutes coefficients in a polynomial. Moves the ith index to the
-    /// first one, it only supports odd indices.
+    /// first one, `k` must be coprime to the cyclotomic order.
+    ///
+    /// Rings of any other order hold `phi(m)` values, see
+    /// [`Poly::automorphism_transform_arbitrary`].
     ///
     /// # Arguments
     /// `k`: the element at `k` to perform the automorphism transform with
     /// `return`: the result of the automorphism transform
     pub fn automorphism_transform(&self, k: usize) -> Self {
-        assert_eq!(k & 1, 1, "k must be odd");
-
-        assert!(
-            self.params.cyclotomic_order.is_power_of_two(),
-            "Automorphism transform is only supported for power of two cyclotomic rings"
+        assert_eq!(
+            k.gcd(&self.params.cyclotomic_order),
+            1,
+            "k must be coprime to the cyclotomic order"
         );
+        if !self.params.cyclotomic_order.is_power_of_two() {
+            return self.automorphism_transform_arbitrary(k);
+        }
 
-        let log_m = (usize::BITS - self.params.cyclotomic_order.leading_zeros()) as usize;
-        let log_n = log_m - 1;
+        let log_n = self.params.ring_dimension.trailing_zeros() as usize;
         let mask = (1 << log_n) - 1;
 
+        let mut result = self.clone();
+        result.invalidate_format_cache();
         match self.format {
             PolynomialRingFormat::Evaluation => {
                 let mut jk = k;
-
-                let mut result = self.clone();
-
-                for j in 1..self.params.ring_dimension {
-                    let jrev = reverse_bits(j, log_n);
-                    let idxrev = reverse_bits((jk >> 1) & mask, log_n);
-                    result.values[jrev] = self.values[idxrev];
+                for j in 0..self.params.ring_dimension {
+                    result.values[j] = self.values[(jk >> 1) & mask];
                     jk += 2 * k;
                 }
-                result
             }
             PolynomialRingFormat::Coefficient => {
-                let mut result = self.clone();
-                let mut jk = 0;
+                let mut jk = k;
                 let modulus = self.params.ciphertext_modulus.get();
 
                 for j in 1..self.params.ring_dimension {
                     let idx = jk & mask;
-                    result.values[idx] = if ((jk >> log_n) & 0x1) == 1 {
-                        modulus - self.values[j]
-                    } else {
-                        self.values[j]
-                    };
+                    result.values[idx] =
+                        if ((jk >> log_n) & 0x1) == 1 && self.values[j] != U64::ZERO {
+                            modulus - self.values[j]
+                        } else {
+                            self.values[j]
+                        };
 
                     jk += k;
                 }
-                result
             }
         }
+        result
+    }
+
+    /// [`Poly::automorphism_transform`] for a cyclotomic order `m` that is not
+    /// a power of two, on the `phi(m)` values of the ring.
+    ///
+    /// In evaluation format value `i` is the evaluation at `ζ^t_i`, where
+    /// `t_i` is the `i`-th integer coprime to `m`, and `X -> X^k` moves the
+    /// evaluation at `ζ^(t k mod m)` there. In coefficient format `x^j` goes
+    /// to `x^(j k mod m)` and the result is reduced modulo the cyclotomic
+    /// polynomial.
+    fn automorphism_transform_arbitrary(&self, k: usize) -> Self {
+        let m = self.params.cyclotomic_order;
+        let n = self.params.ring_dimension;
+        assert_eq!(self.values.len(), n, "expected one value per totient");
+
+        let mut result = self.clone();
+        result.invalidate_format_cache();
+        match self.format {
+            PolynomialRingFormat::Evaluation => {
+                let totients = get_coprimes(m);
+                let mut position = vec![0; m];
+                totients
+                    .iter()
+                    .enumerate()
+                    .for_each(|(i, &t)| position[t] = i);
+                for (i, &t) in totients.iter().enumerate() {
+                    result.values[i] = self.values[position[t * k % m]];
+                }
+            }
+            PolynomialRingFormat::Coefficient => {
+                let q = self.params.ciphertext_modulus.get().to_primitive() as u128;
+                let mut spread = vec![0u128; m];
+                for (j, v) in self.values.iter().enumerate() {
+                    let idx = j * k % m;
+                    spread[idx] = (spread[idx] + v.to_primitive() as u128) % q;
+                }
+                // Φ_m is monic of degree n, so x^i = x^i - x^(i - n) Φ_m
+                let phi = cyclotomic_polynomial(m)
+                    .iter()
+                    .map(|&c| (c as i128).rem_euclid(q as i128) as u128)
+                    .collect::<Vec<_>>();
+                for i in (n..m).rev() {
+                    let c = spread[i];
+                    for (l, &p) in phi.iter().enumerate() {
+                        let target = &mut spread[i - n + l];
+                        *target = (*target + q - c * p % q) % q;
+                    }
+                }
+                for (i, &c) in spread[..n].iter().enumerate() {
+                    result.values[i] = U64::from_u64(c as u64);
+                }
+            }
+        }
+        result
     }
 
     pub fn automorphism_transform_precompute(&self, k: usize, vec: &[usize]) -> Self {
@@ -314,6 +602,7 @@ impl Poly {
         );
 
         let mut result = self.clone();
+        result.invalidate_format_cache();
         for (j, &idxrev) in vec.iter().enumerate() {
             result.values[j] = self.values[idxrev];
         }
@@ -339,7 +628,7 @@ impl Poly {
         for i in 0..windows {
             x_digit.format = x.format;
             let t = x.get_digit_at_index_for_base(i + 1, 1 << base_bits);
-            x_digit.values.iter_mut().for_each(|x| *x = t);
+            x_digit.values_mut().iter_mut().for_each(|x| *x = t);
             if eval_mode_answer {
                 x_digit.switch_format();
             }
@@ -356,6 +645,7 @@ impl Poly {
             values,
             monty_params_ciphertext_modulus: self.monty_params_ciphertext_modulus,
             monty_params_big_ciphertext_modulus: self.monty_params_big_ciphertext_modulus,
+            cache: FormatCache::default(),
         })
     }
 
@@ -378,9 +668,10 @@ impl Poly {
     }
 
     pub fn make_sparse(&mut self, w_factor: usize) {
-        for i in 0..self.values.len() {
+        let values = self.values_mut();
+        for i in 0..values.len() {
             if i % w_factor != 0 {
-                self.values[i] = U64::ZERO;
+                values[i] = U64::ZERO;
             }
         }
     }
@@ -392,6 +683,7 @@ impl Poly {
             values: self.values.rem_mod_2(),
             monty_params_ciphertext_modulus: self.monty_params_ciphertext_modulus,
             monty_params_big_ciphertext_modulus: self.monty_params_big_ciphertext_modulus,
+            cache: FormatCache::default(),
         }
     }
 
@@ -425,17 +717,63 @@ impl Poly {
         self.params.root_of_unity = root_of_unity;
         self.params.big_ciphertext_modulus = modulus_arb;
         self.params.big_root_of_unity = root_of_unity_arb;
-        self.monty_params_ciphertext_modulus = MontyParams::new(modulus);
-        self.monty_params_big_ciphertext_modulus = MontyParams::new(modulus_arb);
+        self.monty_params_ciphertext_modulus = cached_monty_params(modulus);
+        self.monty_params_big_ciphertext_modulus = cached_monty_params(modulus_arb);
+        self.values_mut().mod_switch(modulus);
+    }
 
-        let m: NonZero<U64> = CtOption::from(modulus.to_nz()).expect("modulus is zero");
-        for i in self.values.iter_mut() {
-            *i %= m;
+    /// Convert between coefficient and evaluation format.
+    ///
+    /// With [`Poly::set_format_cache`] enabled the representation being left
+    /// is kept, so switching back costs no transform until the next mutation.
+    pub fn switch_format(&mut self) {
+        let (switched, format) = match self.format {
+            PolynomialRingFormat::Coefficient => (
+                self.cache
+                    .other
+                    .take()
+                    .unwrap_or_else(|| self.ntt().to_vec_mod()),
+                PolynomialRingFormat::Evaluation,
+            ),
+            PolynomialRingFormat::Evaluation => (
+                self.cache
+                    .other
+                    .take()
+                    .unwrap_or_else(|| self.as_ntt().inv().values),
+                PolynomialRingFormat::Coefficient,
+            ),
+        };
+        let previous = std::mem::replace(&mut self.values, switched);
+        if self.cache.enabled {
+            self.cache.other = Some(previous);
         }
+        self.format = format;
     }
 
-    pub fn switch_format(&mut self) {
-        todo!()
+    /// Keep both representations across [`Poly::switch_format`] calls.
+    ///
+    /// Doubles the memory of the polynomial while a second representation
+    /// is held. Disabling drops it.
+    pub fn set_format_cache(&mut self, enabled: bool) {
+        self.cache.enabled = enabled;
+        if !enabled {
+            self.cache.other = None;
+        }
+    }
+
+    /// Whether the representation in the other format is held
+    pub fn has_cached_format(&self) -> bool {
+        self.cache.other.is_some()
+    }
+
+    fn invalidate_format_cache(&mut self) {
+        self.cache.other = None;
+    }
+
+    /// Mutable access to the values, dropping the other representation
+    fn values_mut(&mut self) -> &mut VecModStd {
+        self.invalidate_format_cache();
+        &mut self.values
     }
 
     pub fn zero(params: ElementParams) -> Self {
@@ -443,17 +781,21 @@ impl Poly {
             format: PolynomialRingFormat::default(),
             params,
             values: VecMod::with_value_uint(
-                params.cyclotomic_order,
+                params.ring_dimension,
                 U64::ZERO,
                 params.ciphertext_modulus,
             ),
-            monty_params_ciphertext_modulus: MontyParams::new(params.ciphertext_modulus),
-            monty_params_big_ciphertext_modulus: MontyParams::new(params.big_ciphertext_modulus),
+            monty_params_ciphertext_modulus: cached_monty_params(params.ciphertext_modulus),
+            monty_params_big_ciphertext_modulus: cached_monty_params(params.big_ciphertext_modulus),
+            cache: FormatCache::default(),
         }
     }
 
     pub fn set_zero(&mut self) {
-        self.values.values.iter_mut().for_each(|d| *d = U64::ZERO);
+        self.values_mut()
+            .values
+            .iter_mut()
+            .for_each(|d| *d = U64::ZERO);
     }
 
     pub fn max(params: ElementParams) -> Self {
@@ -461,61 +803,29 @@ impl Poly {
             format: PolynomialRingFormat::default(),
             params,
             values: VecMod::with_value_uint(
-                params.cyclotomic_order,
+                params.ring_dimension,
                 params.ciphertext_modulus.get() - U64::ONE,
                 params.ciphertext_modulus,
             ),
-            monty_params_ciphertext_modulus: MontyParams::new(params.ciphertext_modulus),
-            monty_params_big_ciphertext_modulus: MontyParams::new(params.big_ciphertext_modulus),
+            monty_params_ciphertext_modulus: cached_monty_params(params.ciphertext_modulus),
+            monty_params_big_ciphertext_modulus: cached_monty_params(params.big_ciphertext_modulus),
+            cache: FormatCache::default(),
         }
     }
 
     pub fn set_max(&mut self) {
         let m = self.params.ciphertext_modulus.get() - U64::ONE;
-        self.values.values.iter_mut().for_each(|d| *d = m);
+        self.values_mut().values.iter_mut().for_each(|d| *d = m);
     }
 
-    fn ntt(&self) -> NttPoly {
-        let mut values = self
-            .values
-            .iter()
-            .map(|v| MontyForm::<{ U64::LIMBS }>::new(v, self.monty_params_ciphertext_modulus))
-            .collect::<Vec<_>>();
-
-        bit_reverse_permutation(&mut values);
-
-        // Compute NTT
-        let mut m = U64::ONE;
-        let root_of_unity = MontyForm::new(
-            &self.params.root_of_unity,
-            self.monty_params_ciphertext_modulus,
+    /// The values in Montgomery form, without any transform
+    fn as_ntt(&self) -> NttPoly {
+        let mut values = scratch::take_monty(self.values.len());
+        values.extend(
+            self.values
+                .iter()
+                .map(|v| MontyForm::<{ U64::LIMBS }>::new(v, self.monty_params_ciphertext_modulus)),
         );
-        let order = self.params.cyclotomic_order;
-        let cyclotomic_order = U64::from_u64(order as u64);
-        while m < cyclotomic_order {
-            let half_m: u64 = m.to_primitive();
-            let half_m = half_m as usize;
-            m <<= 1;
-
-            let divisor = CtOption::from(m.to_nz()).expect("m is not zero");
-            let exponent = cyclotomic_order / divisor;
-
-            let omega_m = root_of_unity.pow(&exponent);
-            let step: u64 = m.to_primitive();
-            let step = step as usize;
-
-            for k in (0..order).step_by(step) {
-                let mut omega = MontyForm::one(self.monty_params_ciphertext_modulus);
-
-                for j in 0..half_m {
-                    let t = omega * values[k + j + half_m];
-                    values[k + j + half_m] = values[k + j] - t;
-                    values[k + j] += t;
-                    omega *= omega_m;
-                }
-            }
-        }
-
         NttPoly {
             format: self.format,
             params: self.params,
@@ -525,6 +835,30 @@ impl Poly {
         }
     }
 
+    /// The evaluations at the odd powers `psi^(2j + 1)` of the primitive
+    /// `2n`-th root of unity `psi`, in natural order.
+    ///
+    /// Twisting coefficient `i` by `psi^i` turns the cyclic transform of
+    /// length `n` into a negacyclic one, so products are reduced modulo
+    /// `x^n + 1`.
+    fn ntt(&self) -> NttPoly {
+        let mut result = self.as_ntt();
+        let values = &mut result.values;
+
+        let psi = MontyForm::new(
+            &self.params.root_of_unity,
+            self.monty_params_ciphertext_modulus,
+        );
+        let mut twist = MontyForm::one(self.monty_params_ciphertext_modulus);
+        for v in values.iter_mut() {
+            *v *= twist;
+            twist *= psi;
+        }
+        cyclic_ntt(values, psi, self.params.cyclotomic_order);
+
+        result
+    }
+
     fn get_digit_at_index_for_base(&self, index: usize, base: u64) -> U64 {
         let digit_length = base.ilog2() as usize;
         let mut digit = 0;
@@ -546,13 +880,40 @@ fn bit_reverse_permutation(values: &mut [MontyForm<{ U64::LIMBS }>]) {
     let bits = n.trailing_zeros() as usize;
 
     for i in 0..n {
-        let rev = i.reverse_bits();
+        let rev = reverse_bits(i, bits);
         if i < rev {
             values.swap(i, rev);
         }
     }
 }
 
+/// The cyclic transform of `values` in place with `omega = root^(order / len)`,
+/// where `root` is a primitive `order`-th root of unity
+fn cyclic_ntt(
+    values: &mut [MontyForm<{ U64::LIMBS }>],
+    root: MontyForm<{ U64::LIMBS }>,
+    order: usize,
+) {
+    bit_reverse_permutation(values);
+
+    let len = values.len();
+    let mut m = 1;
+    while m < len {
+        let half_m = m;
+        m <<= 1;
+        let omega_m = root.pow(&U64::from_u64((order / m) as u64));
+        for k in (0..len).step_by(m) {
+            let mut omega = MontyForm::one(*root.params());
+            for j in 0..half_m {
+                let t = omega * values[k + j + half_m];
+                values[k + j + half_m] = values[k + j] - t;
+                values[k + j] += t;
+                omega *= omega_m;
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 struct NttPoly {
     format: PolynomialRingFormat,
@@ -573,55 +934,40 @@ impl MulAssign<&NttPoly> for NttPoly {
 }
 
 impl NttPoly {
+    fn to_vec_mod(&self) -> VecModStd {
+        VecMod {
+            values: self.values.iter().map(|v| v.retrieve()).collect(),
+            params: self.monty_params_ciphertext_modulus,
+            _marker: PhantomData,
+        }
+    }
+
     pub fn inv(&self) -> Poly {
-        let root_of_unity = MontyForm::<{ U64::LIMBS }>::new(
+        let psi = MontyForm::<{ U64::LIMBS }>::new(
             &self.params.root_of_unity,
             self.monty_params_ciphertext_modulus,
         );
-        let inv_root: MontyForm<{ U64::LIMBS }> =
-            CtOption::from(root_of_unity.inv()).expect("root of unity is not zero");
-
-        let mut values = self.values.clone();
-
-        bit_reverse_permutation(&mut values);
-
-        let mut m = U64::ONE;
-        let order = self.params.cyclotomic_order;
-        let cyclotomic_order = U64::from_u64(order as u64);
-        while m < cyclotomic_order {
-            let half_m: u64 = m.to_primitive();
-            let half_m = half_m as usize;
-            m <<= 1;
+        let inv_psi: MontyForm<{ U64::LIMBS }> =
+            CtOption::from(psi.inv()).expect("root of unity is not zero");
 
-            let divisor = CtOption::from(m.to_nz()).expect("m is not zero");
-            let exponent = cyclotomic_order / divisor;
+        let mut values = scratch::take_monty(self.values.len());
+        values.extend_from_slice(&self.values);
 
-            let omega_m = inv_root.pow(&exponent);
-            let step: u64 = m.to_primitive();
-            let step = step as usize;
-
-            for k in (0..order).step_by(step) {
-                let mut omega = MontyForm::one(self.monty_params_ciphertext_modulus);
-
-                for j in 0..half_m {
-                    let t = omega * values[k + j + half_m];
-                    values[k + j + half_m] = values[k + j] - t;
-                    values[k + j] += t;
-                    omega *= omega_m;
-                }
-            }
-        }
+        cyclic_ntt(&mut values, inv_psi, self.params.cyclotomic_order);
 
+        // Scale by n^-1 and undo the twist by psi^i
         let n = MontyForm::<{ U64::LIMBS }>::new(
-            &cyclotomic_order,
+            &U64::from_u64(values.len() as u64),
             self.monty_params_ciphertext_modulus,
         );
-        let n_inv = CtOption::from(n.inv()).expect("n is not zero");
+        let mut untwist: MontyForm<{ U64::LIMBS }> =
+            CtOption::from(n.inv()).expect("n is not zero");
         for i in values.iter_mut() {
-            *i *= n_inv;
+            *i *= untwist;
+            untwist *= inv_psi;
         }
 
-        Poly {
+        let result = Poly {
             format: self.format,
             params: self.params,
             values: VecMod {
@@ -631,6 +977,126 @@ impl NttPoly {
             },
             monty_params_ciphertext_modulus: self.monty_params_ciphertext_modulus,
             monty_params_big_ciphertext_modulus: self.monty_params_big_ciphertext_modulus,
+            cache: FormatCache::default(),
+        };
+        scratch::give_monty(values);
+        result
+    }
+}
+
+impl Drop for NttPoly {
+    fn drop(&mut self) {
+        scratch::give_monty(std::mem::take(&mut self.values));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::lattice::params::DcrtElementParamsBuilder;
+    use rand::Rng;
+
+    fn params() -> ElementParams {
+        DcrtElementParamsBuilder::new(16)
+            .depth(1)
+            .bits(30)
+            .build()
+            .expect("parameters")[0]
+    }
+
+    #[test]
+    fn products_wrap_negacyclically() {
+        let params = params();
+        let n = params.ring_dimension;
+        let mut top = vec![0; n];
+        top[n - 1] = 1;
+        let mut x = vec![0; n];
+        x[1] = 1;
+        let mut product = Poly::from_signed(params, &top);
+        product *= &Poly::from_signed(params, &x);
+        assert_eq!(product.len(), n);
+        let mut minus_one = vec![0; n];
+        minus_one[0] = -1;
+        assert_eq!(product.to_signed(), minus_one);
+
+        let mut rng = rand::rng();
+        let [a, b] = [(); 2].map(|_| {
+            (0..n)
+                .map(|_| rng.random_range(-50..=50))
+                .collect::<Vec<i64>>()
+        });
+        let mut expected = vec![0; n];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                let sign = if i + j < n { 1 } else { -1 };
+                expected[(i + j) % n] += sign * x * y;
+            }
         }
+        let mut product = Poly::from_signed(params, &a);
+        product *= &Poly::from_signed(params, &b);
+        assert_eq!(product.to_signed(), expected);
+    }
+
+    #[test]
+    fn automorphisms_commute_with_the_transform() {
+        let params = params();
+        let n = params.ring_dimension;
+        let mut rng = rand::rng();
+        let values = (0..n)
+            .map(|_| rng.random_range(-50..=50))
+            .collect::<Vec<i64>>();
+        let poly = Poly::from_signed(params, &values);
+        for k in (1..params.cyclotomic_order).step_by(2) {
+            let mut expected = vec![0; n];
+            for (i, &v) in values.iter().enumerate() {
+                let j = i * k % (2 * n);
+                if j < n {
+                    expected[j] = v;
+                } else {
+                    expected[j - n] = -v;
+                }
+            }
+            assert_eq!(poly.automorphism(k).to_signed(), expected, "k = {k}");
+
+            let mut evaluated = poly.clone();
+            evaluated.switch_format();
+            let mut permuted = evaluated.automorphism(k);
+            permuted.switch_format();
+            assert_eq!(permuted.to_signed(), expected, "k = {k}");
+        }
+    }
+
+    #[test]
+    fn mapped_coefficients_keep_the_format_and_are_reduced() {
+        let params = params();
+        let q = params.ciphertext_modulus.get().to_primitive();
+        let signed = [3, -1, 4, 1, -5, 9, 2, -6];
+        let mut poly = Poly::from_signed(params, &signed);
+        poly.switch_format();
+        poly.map_coefficients(|c| c + q + 1);
+        assert_eq!(poly.format(), PolynomialRingFormat::Evaluation);
+        poly.switch_format();
+        assert_eq!(poly.to_signed(), signed.map(|x| x + 1));
+
+        poly.map_coefficients(|_| u64::MAX);
+        assert!(
+            poly.values()
+                .iter()
+                .all(|v| v.to_primitive() == u64::MAX % q)
+        );
+    }
+
+    #[test]
+    fn failed_maps_leave_the_polynomial_unchanged() {
+        let mut poly = Poly::from_signed(params(), &[3, -1, 4, 1, -5, 9, 2, -6]);
+        poly.switch_format();
+        let before = poly.clone();
+        let mut seen = 0;
+        let result = poly.try_map(|c| {
+            seen += 1;
+            if seen == 5 { Err("fifth") } else { Ok(c + 1) }
+        });
+        assert_eq!((result, seen), (Err("fifth"), 5));
+        assert_eq!(poly, before);
     }
 }
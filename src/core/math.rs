@@ -1,17 +1,41 @@
+//! Math primitives used by the lattice layer
+
+mod barrett;
 mod base_sampler;
 mod bitgenerator;
 mod chebyshev;
+mod cpu;
 mod dftransform;
 mod discretegaussian;
 mod discretegaussiangeneric;
 mod discreteuniform;
+mod karney;
+mod monty_cache;
 mod sampler_combiner;
+mod sampler_pool;
+pub mod scratch;
+mod ternary_uniform;
 mod transform;
+mod uniform_int;
 mod vec_mod;
 
-pub(crate) use base_sampler::*;
-pub(crate) use bitgenerator::*;
-pub(crate) use discretegaussian::*;
-pub(crate) use discretegaussiangeneric::*;
+pub(crate) use barrett::*;
+pub use base_sampler::{BaseSampler, Sampler};
+pub use bitgenerator::BitGenerator;
+pub(crate) use chebyshev::eval_chebyshev_coefficients;
+pub(crate) use cpu::dispatch;
+pub use cpu::{CpuLevel, cpu_level, detected_cpu_level, set_cpu_level_limit};
+pub(crate) use dftransform::{fft_special, fft_special_inv};
+pub use dftransform::{floating_point_mode, set_floating_point_mode};
+pub use discretegaussian::{
+    DiscreteGaussian, GAUSSIAN_BATCH_CHUNK, GAUSSIAN_PARALLEL_THRESHOLD, KARNEY_THRESHOLD,
+};
+pub use discretegaussiangeneric::{DiscreteGaussianGeneric, DiscreteGaussianGenericBuilder};
+pub use discreteuniform::DiscreteUniform;
+pub use karney::KarneySampler;
+pub(crate) use monty_cache::*;
 pub(crate) use sampler_combiner::*;
+pub use sampler_pool::SamplerPool;
+pub use ternary_uniform::TernaryUniform;
+pub use uniform_int::UniformInt;
 pub(crate) use vec_mod::*;
@@ -0,0 +1,164 @@
+#[cfg(feature = "simd")]
+use wide::u64x8;
+
+/// The number of `u64` lanes of a [`u64x8`] processed per SIMD instruction
+#[cfg(feature = "simd")]
+pub const SIMD_LANES: usize = 8;
+
+/// Barrett reduction for moduli of at most [`Barrett64::MAX_BITS`] bits.
+///
+/// Products are formed in a `u128` and reduced with two multiplications and
+/// shifts instead of a division. The final corrections are branch free.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Barrett64 {
+    modulus: u64,
+    mu: u64,
+    bits: u32,
+}
+
+impl Barrett64 {
+    /// The largest modulus size supported, leaving room for lazy sums below `2^64`
+    pub const MAX_BITS: u32 = 62;
+
+    /// Precompute `floor(2^(2k) / q)` for a `k` bit modulus `q`
+    pub fn new(modulus: u64) -> Option<Self> {
+        let bits = u64::BITS - modulus.leading_zeros();
+        if modulus < 2 || bits > Self::MAX_BITS {
+            return None;
+        }
+        let mu = ((1u128 << (2 * bits)) / modulus as u128) as u64;
+        Some(Self { modulus, mu, bits })
+    }
+
+    /// The modulus `q`
+    #[inline]
+    pub fn modulus(&self) -> u64 {
+        self.modulus
+    }
+
+    /// Reduce `x < q^2`
+    #[inline]
+    pub fn reduce(&self, x: u128) -> u64 {
+        let q_hat = (((x >> (self.bits - 1)) * self.mu as u128) >> (self.bits + 1)) as u64;
+        // HAC 14.42, the estimate is off by at most two multiples of q
+        let r = (x as u64).wrapping_sub(q_hat.wrapping_mul(self.modulus));
+        self.reduce_once(self.reduce_once(r))
+    }
+
+    /// Reduce an arbitrary `x`
+    #[inline]
+    pub fn reduce_word(&self, x: u64) -> u64 {
+        x % self.modulus
+    }
+
+    /// `a * b mod q` for `a, b < q`
+    #[inline]
+    pub fn mul_mod(&self, a: u64, b: u64) -> u64 {
+        self.reduce(a as u128 * b as u128)
+    }
+
+    /// `a + b mod q` for `a, b < q`
+    #[inline]
+    pub fn add_mod(&self, a: u64, b: u64) -> u64 {
+        self.reduce_once(a + b)
+    }
+
+    /// `a - b mod q` for `a, b < q`
+    #[inline]
+    pub fn sub_mod(&self, a: u64, b: u64) -> u64 {
+        self.add_back(a.wrapping_sub(b))
+    }
+
+    /// Map `r < 2q` to `[0, q)`
+    #[inline]
+    fn reduce_once(&self, r: u64) -> u64 {
+        self.add_back(r.wrapping_sub(self.modulus))
+    }
+
+    /// Add `q` back if `t` wrapped below zero
+    #[inline]
+    fn add_back(&self, t: u64) -> u64 {
+        t.wrapping_add(self.modulus & 0u64.wrapping_sub(t >> 63))
+    }
+
+    /// Lane-wise [`Barrett64::add_mod`]
+    #[cfg(feature = "simd")]
+    #[inline]
+    pub fn add_mod_simd(&self, a: u64x8, b: u64x8) -> u64x8 {
+        self.add_back_simd(a + b - u64x8::splat(self.modulus))
+    }
+
+    /// Lane-wise [`Barrett64::sub_mod`]
+    #[cfg(feature = "simd")]
+    #[inline]
+    pub fn sub_mod_simd(&self, a: u64x8, b: u64x8) -> u64x8 {
+        self.add_back_simd(a - b)
+    }
+
+    #[cfg(feature = "simd")]
+    #[inline]
+    fn add_back_simd(&self, t: u64x8) -> u64x8 {
+        t + (u64x8::splat(self.modulus) & (u64x8::splat(0) - (t >> 63)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto_bigint::{NonZero, U64};
+    use rand::Rng;
+
+    /// `a * b mod q` through crypto-bigint, which needs an odd `q`
+    fn reference(a: u64, b: u64, q: u64) -> u64 {
+        let q = NonZero::new(U64::from_u64(q)).expect("nonzero modulus");
+        U64::from_u64(a).mul_mod(&U64::from_u64(b), &q).as_words()[0]
+    }
+
+    #[test]
+    fn matches_crypto_bigint_up_to_max_bits() {
+        let mut rng = rand::rng();
+        // 2 and 3 bits, a 61 bit prime and odd moduli up to the largest 62 bit one
+        for q in [
+            3,
+            5,
+            (1 << 61) - 1,
+            (1 << 61) + 3,
+            (1 << 62) - 57,
+            (1 << 62) - 1,
+        ] {
+            let b = Barrett64::new(q).expect("at most 62 bits");
+            let edges = [0, 1, q / 2, q - 1];
+            let randoms = (0..1000).map(|_| rng.random_range(0..q));
+            let values = edges.into_iter().chain(randoms).collect::<Vec<_>>();
+            for pair in values.windows(2) {
+                let (x, y) = (pair[0], pair[1]);
+                assert_eq!(b.mul_mod(x, y), reference(x, y, q), "{x} * {y} mod {q}");
+                assert_eq!(
+                    b.add_mod(x, y),
+                    ((x as u128 + y as u128) % q as u128) as u64
+                );
+                assert_eq!(
+                    b.sub_mod(x, y),
+                    ((x as u128 + q as u128 - y as u128) % q as u128) as u64
+                );
+            }
+            for x in edges {
+                for y in edges {
+                    assert_eq!(b.mul_mod(x, y), reference(x, y, q), "{x} * {y} mod {q}");
+                }
+            }
+            assert_eq!(
+                b.reduce((q as u128 - 1) * (q as u128 - 1)),
+                reference(q - 1, q - 1, q)
+            );
+        }
+    }
+
+    #[test]
+    fn wider_moduli_have_no_fast_path() {
+        assert!(Barrett64::new(0).is_none());
+        assert!(Barrett64::new(1).is_none());
+        assert!(Barrett64::new((1 << 62) + 1).is_none());
+        assert!(Barrett64::new(u64::MAX).is_none());
+    }
+}
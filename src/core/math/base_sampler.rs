@@ -1,16 +1,18 @@
 use super::BitGenerator;
 use crate::constants::BaseSamplerType;
 use crate::core::utils::find_in_vector;
-use rand::distr::Open01;
-use rand::rngs::StdRng;
-use rand::{Rng, SeedableRng};
 use std::f64::consts::E;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
 
+/// A source of discrete Gaussian integers
 pub trait Sampler {
+    /// Draw one integer
     fn random_i64(&mut self) -> i64;
+    /// Draw one uniform bit
     fn random_bit(&mut self) -> u16;
 }
 
+/// A discrete Gaussian sampler for a fixed mean and standard deviation
 #[derive(Debug)]
 pub struct BaseSampler {
     b_a: f64,
@@ -18,8 +20,10 @@ pub struct BaseSampler {
     std_dev: f64,
     bit_generator: BitGenerator,
     base_sampler_type: BaseSamplerType,
+    constant_time: bool,
     fin: usize,
     ddg_tree: Vec<Vec<i16>>,
+    prob_matrix: Vec<u64>,
     hamming_weights: Vec<u32>,
     matrix_size: usize,
     first_non_zero: isize,
@@ -38,6 +42,7 @@ impl Sampler for BaseSampler {
 }
 
 impl BaseSampler {
+    /// Precompute the tables for `mean` and `std_dev` using `base_sampler_type`
     pub fn new(
         mean: f64,
         std_dev: f64,
@@ -55,8 +60,10 @@ impl BaseSampler {
             std_dev,
             bit_generator: bg,
             base_sampler_type,
+            constant_time: false,
             fin: (std_dev * (-2.0 * ACC.ln()).sqrt()).ceil() as usize,
             ddg_tree: vec![],
+            prob_matrix: vec![],
             hamming_weights: vec![],
             matrix_size: 0,
             first_non_zero: -1,
@@ -72,13 +79,30 @@ impl BaseSampler {
         sampler
     }
 
+    /// Walk the Knuth-Yao tree without branching on the random bits.
+    ///
+    /// Every sample reads all 64 levels and every row of the probability
+    /// matrix, trading speed for timing that is independent of the output.
+    /// Has no effect on the Peikert sampler.
+    pub fn set_constant_time(&mut self, constant_time: bool) {
+        self.constant_time = constant_time;
+    }
+
+    /// Whether Knuth-Yao sampling runs in constant time
+    pub fn is_constant_time(&self) -> bool {
+        self.constant_time
+    }
+
+    /// Draw one integer
     pub fn random_i64(&mut self) -> i64 {
         match self.base_sampler_type {
+            BaseSamplerType::KnuthYao if self.constant_time => self.gen_i64_knuth_yao_ct(),
             BaseSamplerType::KnuthYao => self.gen_i64_knuth_yao(),
             BaseSamplerType::Peikert => self.gen_i64_peikert(),
         }
     }
 
+    /// Draw one uniform bit
     pub fn random_bit(&mut self) -> u16 {
         self.bit_generator.generate()
     }
@@ -120,9 +144,38 @@ impl BaseSampler {
         ans - (self.fin as i64) + (self.mean as i64)
     }
 
+    fn gen_i64_knuth_yao_ct(&mut self) -> i64 {
+        let reject = (self.matrix_size - 1) as u64;
+        loop {
+            let mut distance = 0i64;
+            let mut done = Choice::from(0);
+            let mut sample = 0u64;
+
+            for column in 0..64 {
+                let bit = i64::from(self.bit_generator.generate());
+                let next = distance.wrapping_mul(2).wrapping_add(bit);
+                distance = i64::conditional_select(&next, &distance, done);
+                for (row, p) in self.prob_matrix.iter().enumerate() {
+                    let next = distance.wrapping_sub(((p >> (63 - column)) & 1) as i64);
+                    distance = i64::conditional_select(&next, &distance, done);
+                    let hit = distance.ct_eq(&-1) & !done;
+                    sample = u64::conditional_select(&sample, &(row as u64), hit);
+                    done |= hit;
+                }
+            }
+
+            // Retrying only reveals that the walk fell off the tree, which
+            // is independent of the value eventually returned
+            if bool::from(done & !sample.ct_eq(&reject)) {
+                return sample as i64 - (self.fin as i64) + (self.mean as i64);
+            }
+        }
+    }
+
     fn gen_i64_peikert(&mut self) -> i64 {
-        let seed = StdRng::from_os_rng().sample(Open01);
-        let ans = find_in_vector(&self.values, seed) as i64;
+        let seed = self.bit_generator.gen_open01();
+        // `find_in_vector` is one based, the table starts at `-fin`
+        let ans = find_in_vector(&self.values, seed) as i64 - 1;
 
         ans - (self.fin as i64) + (self.mean as i64)
     }
@@ -189,41 +242,41 @@ impl BaseSampler {
             }
             let mut e_node_count = 0;
             for j in 0..self.matrix_size {
-                if e_node_count == self.hamming_weights[i] {
-                    if (prob_matrix[j] >> (63 - i)) & 1 == 1 {
-                        self.ddg_tree[(node_count as u32 + e_node_count) as usize]
-                            [i - self.first_non_zero as usize] = j as i16;
-                        e_node_count += 1;
-                    }
+                if e_node_count != self.hamming_weights[i] && (prob_matrix[j] >> (63 - i)) & 1 == 1
+                {
+                    self.ddg_tree[(node_count as u32 + e_node_count) as usize]
+                        [i - self.first_non_zero as usize] = j as i16;
+                    e_node_count += 1;
                 }
             }
         }
     }
 
     fn gen_prob_matrix(&mut self, mean: f64, std_dev: f64) {
-        self.matrix_size = 2 * self.fin + 1;
+        self.matrix_size = 2 * self.fin + 2;
         self.hamming_weights.resize(64, 0);
         self.std_dev = std_dev;
-        let mut prob_matrix = Vec::<u64>::with_capacity(self.matrix_size);
-        let mut probs = Vec::<f64>::with_capacity(self.matrix_size);
-        let mut s = 0.0;
-        let mut error = 1.0;
-
-        for i in -1 * self.fin as isize..=self.fin as isize {
-            let prob = E.powf(-(i as f64 - mean).powi(2) / (2.0 * std_dev * std_dev));
-            s += prob;
-            probs[i as usize + self.fin] = prob;
-        }
-        prob_matrix[self.matrix_size - 1] = (error * 2.0f64.powi(64)) as u64;
-        for i in 0..self.matrix_size {
-            let tmp = probs[i] * (1.0 / s);
-            error -= tmp;
-            prob_matrix[i] = (tmp * 2.0f64.powi(64)) as u64;
+        let probs = (-(self.fin as isize)..=self.fin as isize)
+            .map(|i| E.powf(-(i as f64 - mean).powi(2) / (2.0 * std_dev * std_dev)))
+            .collect::<Vec<_>>();
+        let s = probs.iter().sum::<f64>();
+        let mut prob_matrix = probs
+            .iter()
+            .map(|p| (p / s * 2.0f64.powi(64)) as u64)
+            .collect::<Vec<_>>();
+        // The truncation error fills the last row, which the samplers reject
+        let error = prob_matrix
+            .iter()
+            .fold(0u64, |acc, p| acc.wrapping_add(*p))
+            .wrapping_neg();
+        prob_matrix.push(error);
+        for p in &prob_matrix {
             for j in 0..64 {
-                self.hamming_weights[j] += ((prob_matrix[i] >> (63 - j)) & 1) as u32;
+                self.hamming_weights[j] += ((p >> (63 - j)) & 1) as u32;
             }
         }
         self.gen_ddg_tree(&prob_matrix);
+        self.prob_matrix = prob_matrix;
     }
 
     fn initialize(&mut self, mean: f64) {
@@ -249,3 +302,98 @@ impl BaseSampler {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STD_DEV: f64 = 3.2;
+    const SAMPLES: usize = 100_000;
+
+    fn sampler(mean: f64, base_sampler_type: BaseSamplerType) -> BaseSampler {
+        let bit_generator = BitGenerator::from_seed([7; 32]);
+        BaseSampler::new(mean, STD_DEV, bit_generator, base_sampler_type)
+    }
+
+    fn samples(sampler: &mut BaseSampler) -> Vec<i64> {
+        (0..SAMPLES).map(|_| sampler.random_i64()).collect()
+    }
+
+    /// The sample mean and standard deviation
+    fn moments(samples: &[i64]) -> (f64, f64) {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<i64>() as f64 / n;
+        let variance = samples
+            .iter()
+            .map(|x| (*x as f64 - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1.0);
+        (mean, variance.sqrt())
+    }
+
+    #[test]
+    fn samples_match_the_distribution() {
+        for base_sampler_type in [BaseSamplerType::KnuthYao, BaseSamplerType::Peikert] {
+            for mean in [0.0, 5.0, -3.0] {
+                let samples = samples(&mut sampler(mean, base_sampler_type));
+                let (sample_mean, sample_std_dev) = moments(&samples);
+                // The standard error of the mean is 0.01
+                assert!(
+                    (sample_mean - mean).abs() < 0.05,
+                    "{base_sampler_type:?} mean {sample_mean} for {mean}"
+                );
+                assert!(
+                    (sample_std_dev - STD_DEV).abs() < 0.05,
+                    "{base_sampler_type:?} standard deviation {sample_std_dev}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn peikert_samples_stay_in_the_table() {
+        let mut sampler = sampler(0.0, BaseSamplerType::Peikert);
+        let fin = sampler.fin as i64;
+        let samples = samples(&mut sampler);
+        assert!(samples.iter().all(|x| (-fin..=fin).contains(x)));
+        // Without the one based correction every sample was one too large
+        let zeros = samples.iter().filter(|x| **x == 0).count();
+        let ones = samples.iter().filter(|x| **x == 1).count();
+        assert!(zeros > ones, "{zeros} zeros and {ones} ones");
+    }
+
+    #[test]
+    fn constant_time_walk_matches_the_branching_walk() {
+        let histogram = |constant_time| {
+            let mut sampler = sampler(0.0, BaseSamplerType::KnuthYao);
+            sampler.set_constant_time(constant_time);
+            assert_eq!(sampler.is_constant_time(), constant_time);
+            let fin = sampler.fin as i64;
+            let mut histogram = vec![0u32; 2 * sampler.fin + 1];
+            for x in samples(&mut sampler) {
+                assert!((-fin..=fin).contains(&x), "{x} out of range");
+                histogram[(x + fin) as usize] += 1;
+            }
+            histogram
+        };
+        let branching = histogram(false);
+        let constant_time = histogram(true);
+
+        // Two sample chi-squared statistic over the bins both walks reach
+        let mut bins = 0;
+        let chi_squared = branching
+            .iter()
+            .zip(&constant_time)
+            .filter(|(a, b)| *a + *b > 0)
+            .map(|(&a, &b)| {
+                bins += 1;
+                (f64::from(a) - f64::from(b)).powi(2) / f64::from(a + b)
+            })
+            .sum::<f64>();
+        // Roughly the 99.9% quantile for `bins - 1` degrees of freedom
+        assert!(
+            chi_squared < 2.0 * f64::from(bins),
+            "{chi_squared} over {bins} bins"
+        );
+    }
+}
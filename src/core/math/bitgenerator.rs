@@ -1,78 +1,113 @@
-use crate::core::math::VecMod;
-use crypto_bigint::modular::{MontyParams, SafeGcdInverter};
-use crypto_bigint::{Concat, Odd, PrecomputeInverter, Split, Uint};
-use rand::distr::Bernoulli;
-use rand::rngs::StdRng;
-use rand::{Rng, SeedableRng};
-use std::marker::PhantomData;
-
-#[derive(Debug)]
+use rand::{CryptoRng, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::fmt::{self, Debug, Formatter};
+
+/// Words drawn from the CSPRNG per refill
+const BLOCK_WORDS: usize = 64;
+const BLOCK_BITS: usize = BLOCK_WORDS * u64::BITS as usize;
+
+/// Uniform bits from an internal ChaCha20 stream, buffered in blocks of
+/// [`BLOCK_WORDS`] words so single bits cost a shift rather than an RNG call
+#[allow(missing_copy_implementations)] // A copy would hand out the same bits twice
 pub struct BitGenerator {
-    sequence: u32,
-    counter: u32,
+    rng: ChaCha20Rng,
+    block: [u64; BLOCK_WORDS],
+    position: usize,
+}
+
+impl Debug for BitGenerator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // The buffered bits are future output and stay out of logs
+        f.debug_struct("BitGenerator")
+            .field("buffered_bits", &(BLOCK_BITS - self.position))
+            .finish_non_exhaustive()
+    }
 }
 
 impl Default for BitGenerator {
     fn default() -> Self {
-        BitGenerator {
-            sequence: StdRng::from_os_rng().random::<u32>(),
-            counter: 0,
-        }
+        Self::with_stream(ChaCha20Rng::from_os_rng())
     }
 }
 
 impl BitGenerator {
-    pub fn generate(&mut self) -> u16 {
-        if self.counter == 0 {
-            self.sequence = StdRng::from_os_rng().random::<u32>();
-            self.counter = 32;
-        }
-        self.counter -= 1;
-        ((self.sequence >> self.counter) & 1) as u16
+    /// A generator whose bits are fully determined by `seed`
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        Self::with_stream(ChaCha20Rng::from_seed(seed))
     }
 
-    pub fn gen_uint<const LIMBS: usize, const WIDE_LIMBS: usize, const UNSAT_LIMBS: usize>(
-        &self,
-    ) -> Uint<LIMBS>
-    where
-        Uint<LIMBS>: Concat<Output = Uint<WIDE_LIMBS>>,
-        Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
-        Odd<Uint<LIMBS>>: PrecomputeInverter<Inverter = SafeGcdInverter<LIMBS, UNSAT_LIMBS>>,
-    {
-        let b = Bernoulli::new(0.5).unwrap();
-        let s = StdRng::from_os_rng().sample(b);
-        if s {
-            Uint::<LIMBS>::ONE
-        } else {
-            Uint::<LIMBS>::ZERO
-        }
+    /// A generator seeded from `rng`, e.g. an application supplied DRBG
+    pub fn from_rng(rng: &mut impl CryptoRng) -> Self {
+        Self::with_stream(ChaCha20Rng::from_rng(rng))
     }
 
-    pub fn gen_vec_mod<const LIMBS: usize, const WIDE_LIMBS: usize, const UNSAT_LIMBS: usize>(
-        &self,
-        length: usize,
-        modulus: &Odd<Uint<LIMBS>>,
-    ) -> VecMod<LIMBS, WIDE_LIMBS>
-    where
-        Uint<LIMBS>: Concat<Output = Uint<WIDE_LIMBS>>,
-        Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
-        Odd<Uint<LIMBS>>: PrecomputeInverter<Inverter = SafeGcdInverter<LIMBS, UNSAT_LIMBS>>,
-    {
-        let b = Bernoulli::new(0.5).unwrap();
-        let mut rng = StdRng::from_os_rng();
-        let mut values = Vec::<Uint<LIMBS>>::with_capacity(length);
-
-        for _ in 0..length {
-            if rng.sample(b) {
-                values.push(Uint::<LIMBS>::ONE);
-            } else {
-                values.push(Uint::<LIMBS>::ZERO);
-            }
+    fn with_stream(rng: ChaCha20Rng) -> Self {
+        Self {
+            rng,
+            block: [0; BLOCK_WORDS],
+            position: BLOCK_BITS,
         }
-        VecMod {
-            values,
-            params: MontyParams::new(*modulus),
-            _marker: PhantomData,
+    }
+
+    /// The next uniform bit
+    pub fn generate(&mut self) -> u16 {
+        if self.position == BLOCK_BITS {
+            self.rng.fill(&mut self.block[..]);
+            self.position = 0;
         }
+        let word = self.block[self.position / u64::BITS as usize];
+        let bit = (word >> (self.position % u64::BITS as usize)) & 1;
+        self.position += 1;
+        bit as u16
+    }
+
+    /// A uniform `f64` in the open interval `(0, 1)` from 53 bits
+    pub(crate) fn gen_open01(&mut self) -> f64 {
+        let bits =
+            (0..f64::MANTISSA_DIGITS).fold(0u64, |acc, _| (acc << 1) | self.generate() as u64);
+        (bits as f64 + 0.5) / (1u64 << f64::MANTISSA_DIGITS) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(generator: &mut BitGenerator, count: usize) -> Vec<u16> {
+        (0..count).map(|_| generator.generate()).collect()
+    }
+
+    #[test]
+    fn equal_seeds_give_equal_bits_across_refills() {
+        let mut a = BitGenerator::from_seed([3; 32]);
+        let mut b = BitGenerator::from_seed([3; 32]);
+        // Three blocks, starting mid block after the first draw
+        let first = bits(&mut a, 3 * BLOCK_BITS + 17);
+        assert_eq!(first, bits(&mut b, 3 * BLOCK_BITS + 17));
+        assert_eq!(bits(&mut a, BLOCK_BITS), bits(&mut b, BLOCK_BITS));
+        assert!(first.iter().all(|&bit| bit < 2));
+
+        let ones = first.iter().filter(|&&bit| bit == 1).count();
+        // Four standard deviations of the binomial count
+        assert!(ones.abs_diff(first.len() / 2) < 4 * 64, "{ones} ones");
+
+        let mut other = BitGenerator::from_seed([4; 32]);
+        assert_ne!(bits(&mut other, BLOCK_BITS), first[..BLOCK_BITS]);
+    }
+
+    #[test]
+    fn refills_continue_the_stream() {
+        let mut generator = BitGenerator::from_seed([9; 32]);
+        let drawn = bits(&mut generator, 2 * BLOCK_BITS);
+
+        // The same bits, read straight from the ChaCha20 words
+        let mut rng = ChaCha20Rng::from_seed([9; 32]);
+        let mut words = [0u64; 2 * BLOCK_WORDS];
+        rng.fill(&mut words[..BLOCK_WORDS]);
+        rng.fill(&mut words[BLOCK_WORDS..]);
+        let expected = (0..2 * BLOCK_BITS)
+            .map(|i| ((words[i / 64] >> (i % 64)) & 1) as u16)
+            .collect::<Vec<_>>();
+        assert_eq!(drawn, expected);
     }
 }
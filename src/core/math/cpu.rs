@@ -0,0 +1,94 @@
+//! Runtime selection of the instruction set used by the word kernels
+//!
+//! Kernels are written once as plain loops and compiled again inside
+//! functions carrying `#[target_feature]`, so LLVM vectorizes each copy for
+//! its instruction set. The best copy the CPU supports is picked at runtime,
+//! letting one binary run well on every host of a mixed fleet.
+
+use derive_more::{Display, FromStr, TryFrom};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static DETECTED: OnceLock<CpuLevel> = OnceLock::new();
+static LIMIT: AtomicUsize = AtomicUsize::new(CpuLevel::Avx512 as usize);
+
+/// An instruction set tier the kernels are compiled for
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, Ord, PartialOrd, Hash, Display, FromStr, TryFrom,
+)]
+#[try_from(repr)]
+#[repr(usize)]
+pub enum CpuLevel {
+    /// Baseline instructions of the target
+    #[default]
+    Scalar = 0,
+    /// x86 AVX2
+    Avx2,
+    /// x86 AVX-512 foundation
+    Avx512,
+}
+
+hex_enum_usize!(CpuLevel);
+try_serde_str_or_u8!(CpuLevel);
+
+/// The best level supported by the running CPU
+pub fn detected_cpu_level() -> CpuLevel {
+    *DETECTED.get_or_init(detect)
+}
+
+/// The level kernels run at, the detected level capped by [`set_cpu_level_limit`]
+pub fn cpu_level() -> CpuLevel {
+    let limit = match LIMIT.load(Ordering::Relaxed) {
+        0 => CpuLevel::Scalar,
+        1 => CpuLevel::Avx2,
+        _ => CpuLevel::Avx512,
+    };
+    detected_cpu_level().min(limit)
+}
+
+/// Never run kernels above `limit`, e.g. to compare tiers on one host
+pub fn set_cpu_level_limit(limit: CpuLevel) {
+    LIMIT.store(limit as usize, Ordering::Relaxed);
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn detect() -> CpuLevel {
+    if is_x86_feature_detected!("avx512f") {
+        CpuLevel::Avx512
+    } else if is_x86_feature_detected!("avx2") {
+        CpuLevel::Avx2
+    } else {
+        CpuLevel::Scalar
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+fn detect() -> CpuLevel {
+    CpuLevel::Scalar
+}
+
+/// Run `kernel` compiled for the current [`cpu_level`]
+#[inline(always)]
+pub(crate) fn dispatch<R>(kernel: impl FnOnce() -> R) -> R {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    match cpu_level() {
+        // SAFETY: the CPU reported support for the enabled features
+        CpuLevel::Avx512 => return unsafe { run_avx512(kernel) },
+        // SAFETY: as above
+        CpuLevel::Avx2 => return unsafe { run_avx2(kernel) },
+        CpuLevel::Scalar => {}
+    }
+    kernel()
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+fn run_avx2<R>(kernel: impl FnOnce() -> R) -> R {
+    kernel()
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx512f")]
+fn run_avx512<R>(kernel: impl FnOnce() -> R) -> R {
+    kernel()
+}
@@ -1,117 +1,183 @@
+use crate::constants::FloatingPointMode;
 use crate::error::Result;
 use num::complex::Complex;
-use std::{
-    collections::HashMap,
-    sync::{Arc, OnceLock, RwLock},
-};
-
-static ROOT_OF_UNITY_TABLE: OnceLock<Arc<RwLock<Vec<Complex<f64>>>>> = OnceLock::new();
-static PRECOMPUTED_VALUES_TABLE: OnceLock<Arc<RwLock<HashMap<usize, PrecomputedValues>>>> =
-    OnceLock::new();
-
-pub fn reset() -> Result<()> {
-    ROOT_OF_UNITY_TABLE
-        .get_or_init(Default::default)
-        .write()?
-        .clear();
-    Ok(())
-}
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+/// The tables of [`fft_special`] by cyclotomic order and the mode their
+/// roots were computed in, so a mode switch never hands out stale roots
+static PRECOMPUTED_VALUES_TABLE: RwLock<
+    BTreeMap<(usize, FloatingPointMode), Arc<PrecomputedValues>>,
+> = RwLock::new(BTreeMap::new());
+static FLOATING_POINT_MODE: RwLock<FloatingPointMode> = RwLock::new(FloatingPointMode::Native);
+
+/// `floor(pi / 4 * 2^64)`
+const QUARTER_PI_Q64: u128 = 0xC90F_DAA2_2168_C234;
+const ONE_Q64: u128 = 1 << 64;
 
-pub fn initialize(m: usize, nh: usize) -> Result<()> {
-    let mut table = PRECOMPUTED_VALUES_TABLE
-        .get_or_init(Default::default)
-        .write()?;
-    table.entry(m).or_insert(PrecomputedValues::new(m, nh));
+/// Select how roots of unity are computed and drop every cached table
+pub fn set_floating_point_mode(mode: FloatingPointMode) -> Result<()> {
+    *FLOATING_POINT_MODE.write()? = mode;
+    PRECOMPUTED_VALUES_TABLE.write()?.clear();
     Ok(())
 }
 
-pub fn precompute_table(s: usize) -> Result<()> {
-    reset()?;
+/// How roots of unity are currently computed
+pub fn floating_point_mode() -> Result<FloatingPointMode> {
+    Ok(*FLOATING_POINT_MODE.read()?)
+}
 
-    let mut table = ROOT_OF_UNITY_TABLE.get_or_init(Default::default).write()?;
-    for j in 0..s {
-        let theta = -2.0 * std::f64::consts::PI * (j as f64) / (s as f64);
-        table.push(Complex::from_polar(1.0, theta));
+/// `e^(2 pi i k / m)` computed according to `mode`
+fn root_of_unity(mode: FloatingPointMode, k: usize, m: usize) -> Complex<f64> {
+    match mode {
+        FloatingPointMode::Native => {
+            let angle = 2.0 * std::f64::consts::PI * (k as f64) / (m as f64);
+            Complex::new(f64::cos(angle), f64::sin(angle))
+        }
+        FloatingPointMode::Deterministic => {
+            let (cos, sin) = fixed_cos_sin(k, m);
+            Complex::new(cos, sin)
+        }
     }
-    Ok(())
 }
 
-pub fn fft_forward_transform(a: &[Complex<f64>]) -> Result<Vec<Complex<f64>>> {
-    static TABLES: OnceLock<RwLock<Tables>> = OnceLock::new();
-
-    let m = a.len();
-    let mut b = a.to_vec();
-    let l = m.ilog2() as usize;
-
-    {
-        let mut tables = TABLES.get_or_init(Default::default).write()?;
-
-        if m != tables.cached_m[l] {
-            let half_m = m / 2;
-            tables.cached_m[l] = m;
-            tables.sin_table[l].resize(half_m, 0.0);
-            tables.cos_table[l].resize(half_m, 0.0);
-            for i in 0..half_m {
-                let angle = 2.0 * std::f64::consts::PI * (i as f64) / (m as f64);
-                tables.cos_table[l][i] = f64::cos(angle);
-                tables.sin_table[l][i] = f64::sin(angle);
-            }
+/// Cosine and sine of `2 pi k / m` using only integer arithmetic.
+///
+/// The angle is reduced exactly to an octant, evaluated with Q64 Taylor
+/// series on `[0, pi/4]` and rounded to `f64` once, so every target gets
+/// the same bits.
+fn fixed_cos_sin(k: usize, m: usize) -> (f64, f64) {
+    let m = m as u128;
+    let eighths = 8 * (k as u128 % m);
+    let octant = eighths / m;
+    let f = ((eighths % m) << 64) / m;
+    // Odd octants are measured back from the next multiple of pi/4
+    let f = if octant & 1 == 1 { ONE_Q64 - f } else { f };
+    let (c, s) = taylor_cos_sin((f * QUARTER_PI_Q64) >> 64);
+
+    let (cos, sin) = match octant {
+        0 => (c, s),
+        1 => (s, c),
+        2 => (-s, c),
+        3 => (-c, s),
+        4 => (-c, -s),
+        5 => (-s, -c),
+        6 => (s, -c),
+        _ => (c, -s),
+    };
+    (cos as f64 / ONE_Q64 as f64, sin as f64 / ONE_Q64 as f64)
+}
+
+/// Q64 cosine and sine of `x <= pi/4`, itself in Q64
+fn taylor_cos_sin(x: u128) -> (i128, i128) {
+    let x2 = (x * x) >> 64;
+    let series = |mut term: u128, mut n: u128| {
+        let mut sum = 0i128;
+        let mut positive = true;
+        while term != 0 {
+            sum += if positive {
+                term as i128
+            } else {
+                -(term as i128)
+            };
+            term = ((term * x2) >> 64) / ((n + 1) * (n + 2));
+            n += 2;
+            positive = !positive;
         }
+        sum
+    };
+    (series(ONE_Q64, 0), series(x, 1))
+}
+
+/// The tables of the `m`-th cyclotomic ring in the current
+/// [`floating_point_mode`], computing them on first use
+fn precomputed(m: usize) -> Result<Arc<PrecomputedValues>> {
+    let key = (m, floating_point_mode()?);
+    if let Some(precomputed) = PRECOMPUTED_VALUES_TABLE.read()?.get(&key) {
+        return Ok(precomputed.clone());
     }
+    let precomputed = Arc::new(PrecomputedValues::new(key.1, m, m / 4));
+    Ok(PRECOMPUTED_VALUES_TABLE
+        .write()?
+        .entry(key)
+        .or_insert(precomputed)
+        .clone())
+}
 
-    // bit-reversed addressing permutation
-    for i in 0..m {
-        let j = i.reverse_bits() >> (64 - l);
-        if i < j {
-            b.swap(i, j);
+/// Evaluate a CKKS plaintext at its slots in place, OpenFHE's `FFTSpecial`.
+///
+/// `values` holds the coefficients of the plaintext polynomial of the
+/// `m`-th cyclotomic ring gathered into complex numbers, the real parts
+/// from the first half and the imaginary parts from the second half, with
+/// a power of two of at most `m / 4` slots.
+pub fn fft_special(values: &mut [Complex<f64>], m: usize) -> Result<()> {
+    let slots = values.len();
+    let precomputed = precomputed(m)?;
+    bit_reverse(values);
+    let mut len = 2;
+    while len <= slots {
+        let (half, quarter) = (len / 2, len * 4);
+        let gap = m / quarter;
+        for i in (0..slots).step_by(len) {
+            for j in 0..half {
+                let index = (precomputed.rotation_group_indices[j] % quarter) * gap;
+                let u = values[i + j];
+                let v = values[i + j + half] * precomputed.ksi_powers[index];
+                values[i + j] = u + v;
+                values[i + j + half] = u - v;
+            }
         }
+        len *= 2;
     }
+    Ok(())
+}
 
-    // Cooley-Tukey decimation-in-time radix-2 FFT
-    let table = TABLES.get_or_init(Default::default).read()?;
-    let mut size = 2;
-    while size <= m {
-        let half_size = size / 2;
-        let table_step = m / size;
-
-        for i in (0..m).step_by(size) {
-            let mut k = 0;
-            for j in i..(i + half_size) {
-                let tpre = b[j + half_size].re * table.cos_table[l][k]
-                    + b[j + half_size].im * table.sin_table[l][k];
-                let tpim = -b[j + half_size].re * table.sin_table[l][k]
-                    + b[j + half_size].im * table.cos_table[l][k];
-
-                b[j + half_size].re = b[j].re - tpre;
-                b[j + half_size].im = b[j].im - tpim;
-                b[j].re += tpre;
-                b[j].im += tpim;
-
-                k += table_step;
+/// The inverse of [`fft_special`], OpenFHE's `FFTSpecialInv`
+pub fn fft_special_inv(values: &mut [Complex<f64>], m: usize) -> Result<()> {
+    let slots = values.len();
+    let precomputed = precomputed(m)?;
+    let mut len = slots;
+    while len > 1 {
+        let (half, quarter) = (len / 2, len * 4);
+        let gap = m / quarter;
+        for i in (0..slots).step_by(len) {
+            for j in 0..half {
+                let index = (quarter - precomputed.rotation_group_indices[j] % quarter) * gap;
+                let u = values[i + j] + values[i + j + half];
+                let v = (values[i + j] - values[i + j + half]) * precomputed.ksi_powers[index];
+                values[i + j] = u;
+                values[i + j + half] = v;
             }
         }
+        len /= 2;
+    }
+    bit_reverse(values);
+    let slots = slots as f64;
+    values.iter_mut().for_each(|v| *v /= slots);
+    Ok(())
+}
 
-        if size == m {
-            break;
+fn bit_reverse(values: &mut [Complex<f64>]) {
+    let bits = values.len().trailing_zeros();
+    if bits == 0 {
+        return;
+    }
+    for i in 0..values.len() {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            values.swap(i, j);
         }
-
-        size *= 2;
     }
-
-    Ok(b)
 }
 
-/// Keep values precomputed for every cyclotomic order value
-pub struct PrecomputedValues {
-    // cyclotomic order
-    m: usize,
-    nh: usize,
+/// The rotation group and roots of unity of one cyclotomic order
+struct PrecomputedValues {
     rotation_group_indices: Vec<usize>,
     ksi_powers: Vec<Complex<f64>>,
 }
 
 impl PrecomputedValues {
-    pub fn new(m: usize, nh: usize) -> Self {
+    fn new(mode: FloatingPointMode, m: usize, nh: usize) -> Self {
         let mut rotation_group_indices = Vec::with_capacity(nh);
         let mut five_powers = 1;
         for _ in 0..nh {
@@ -121,38 +187,108 @@ impl PrecomputedValues {
 
         let mut ksi_powers = Vec::with_capacity(m + 1);
         for i in 0..m {
-            let angle = 2.0 * std::f64::consts::PI * (i as f64) / (m as f64);
-            ksi_powers.push(Complex::new(f64::cos(angle), f64::sin(angle)));
+            ksi_powers.push(root_of_unity(mode, i, m));
         }
         let first = ksi_powers[0];
         ksi_powers.push(first);
         Self {
-            m,
-            nh,
             rotation_group_indices,
             ksi_powers,
         }
     }
 }
 
-struct Tables {
-    logm_max: usize,
-    cached_m: Vec<usize>,
-    cos_table: Vec<Vec<f64>>,
-    sin_table: Vec<Vec<f64>>,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::{CkksPlaintext, PlaintextEncodings, PlaintextParams};
+    use std::sync::Mutex;
 
-impl Default for Tables {
-    fn default() -> Self {
-        Self {
-            logm_max: Self::LOGM_MAX,
-            cached_m: vec![0; Self::LOGM_MAX + 1],
-            cos_table: vec![Vec::new(); Self::LOGM_MAX + 1],
-            sin_table: vec![Vec::new(); Self::LOGM_MAX + 1],
+    /// Held by the tests that switch the global mode
+    static MODE: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn fixed_roots_match_known_values() {
+        let half_sqrt2 = std::f64::consts::FRAC_1_SQRT_2;
+        for (k, m, expected) in [
+            (0, 8, (1.0, 0.0)),
+            (1, 8, (half_sqrt2, half_sqrt2)),
+            (2, 8, (0.0, 1.0)),
+            (3, 8, (-half_sqrt2, half_sqrt2)),
+            (4, 8, (-1.0, 0.0)),
+            (6, 8, (0.0, -1.0)),
+            (7, 8, (half_sqrt2, -half_sqrt2)),
+            (9, 8, (half_sqrt2, half_sqrt2)),
+            (1, 12, (0.75f64.sqrt(), 0.5)),
+            (1, 6, (0.5, 0.75f64.sqrt())),
+        ] {
+            let (cos, sin) = fixed_cos_sin(k, m);
+            assert!(
+                (cos - expected.0).abs() <= f64::EPSILON
+                    && (sin - expected.1).abs() <= f64::EPSILON,
+                "2 pi {k} / {m}: ({cos}, {sin}) != {expected:?}"
+            );
+        }
+        for m in [16, 1 << 12, 1 << 17] {
+            for k in (0..m).step_by(m / 16 + 3) {
+                let (cos, sin) = fixed_cos_sin(k, m);
+                let angle = 2.0 * std::f64::consts::PI * k as f64 / m as f64;
+                assert!((cos - angle.cos()).abs() < 1e-15, "cos 2 pi {k} / {m}");
+                assert!((sin - angle.sin()).abs() < 1e-15, "sin 2 pi {k} / {m}");
+            }
         }
     }
-}
 
-impl Tables {
-    pub const LOGM_MAX: usize = 18; // 2^18 = 262144
+    fn encode(values: &[f64]) -> Result<Vec<i64>> {
+        let params = PlaintextParams {
+            scaling_factor: 2f64.powi(40),
+            slots: 4,
+            ..Default::default()
+        };
+        let encoded = PlaintextEncodings::Ckks(CkksPlaintext::new(values, params, 16)?);
+        Ok(encoded.encoded_value().to_signed())
+    }
+
+    #[test]
+    fn deterministic_encodings_are_fixed() {
+        let _mode = MODE.lock().expect("mode lock");
+        set_floating_point_mode(FloatingPointMode::Deterministic).expect("mode");
+        let encoded = encode(&[0.1, -std::f64::consts::PI, 1.0 / 3.0, 2.5]);
+        set_floating_point_mode(FloatingPointMode::Native).expect("mode");
+        assert_eq!(
+            encoded.expect("encode"),
+            [
+                -57245886054,
+                534190068940,
+                208931248622,
+                -1457250046088,
+                0,
+                1457250046088,
+                -208931248622,
+                -534190068940
+            ]
+        );
+    }
+
+    #[test]
+    fn mode_switches_do_not_disturb_encodings() {
+        let _mode = MODE.lock().expect("mode lock");
+        let expected = encode(&[1.0, 2.0, 3.0, 4.0]).expect("encode");
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 0..200 {
+                    let mode = [FloatingPointMode::Native, FloatingPointMode::Deterministic][i % 2];
+                    set_floating_point_mode(mode).expect("mode");
+                }
+                set_floating_point_mode(FloatingPointMode::Native).expect("mode");
+            });
+            for _ in 0..2 {
+                scope.spawn(|| {
+                    for _ in 0..200 {
+                        assert_eq!(encode(&[1.0, 2.0, 3.0, 4.0]).expect("encode"), expected);
+                    }
+                });
+            }
+        });
+    }
 }
@@ -1,21 +1,30 @@
-use crate::core::math::VecMod;
+use crate::core::math::{KarneySampler, UniformInt, VecMod, uint_from_i64};
 use crate::core::utils::find_in_vector;
-use crypto_bigint::modular::{MontyParams, SafeGcdInverter};
+use crate::parallel::{ParallelConfig, global_config};
+use crypto_bigint::modular::SafeGcdInverter;
 use crypto_bigint::{Concat, Odd, PrecomputeInverter, Split, Uint};
 use rand::distr::Open01;
 use rand::prelude::*;
-use rand_distr::{Distribution, Normal, StandardNormal};
+use rand_chacha::ChaCha20Rng;
+use rand_distr::{Distribution, Normal};
 use std::f64::consts::E;
-use std::marker::PhantomData;
 
+/// Standard deviations from which Karney's sampler replaces the inversion table
 pub const KARNEY_THRESHOLD: f64 = 300.0;
+/// Samples drawn from each independent stream of a batch
+pub const GAUSSIAN_BATCH_CHUNK: usize = 4096;
+/// Batches of at least this many samples are split over worker threads
+pub const GAUSSIAN_PARALLEL_THRESHOLD: usize = 16384;
 
+/// Discrete Gaussian sampler over the integers, using a Peikert inversion
+/// table for small deviations and Karney's method for large ones
 #[derive(Debug)]
 pub struct DiscreteGaussian {
     normal: Normal<f64>,
     rng: StdRng,
     values: Vec<f64>,
-    peikert: bool,
+    /// Used instead of the Peikert table from [`KARNEY_THRESHOLD`] on
+    karney: Option<KarneySampler>,
 }
 
 impl Default for DiscreteGaussian {
@@ -24,7 +33,7 @@ impl Default for DiscreteGaussian {
             normal: Normal::new(0.0, 1.0).unwrap(),
             rng: StdRng::from_os_rng(),
             values: Vec::new(),
-            peikert: false,
+            karney: None,
         }
     }
 }
@@ -39,6 +48,13 @@ impl DiscreteGaussian {
         E.powf(-(x as f64 - mean).powi(2) / (2.0 * sigma * sigma))
     }
 
+    /// The candidates `mean ± log2(n) * std_dev` of the rejection samplers
+    fn candidate_range(mean: f64, std_dev: f64, ring_dimension: usize) -> UniformInt {
+        let t = ring_dimension.ilog2() as f64 * std_dev;
+        UniformInt::new((mean - t).floor() as i64, (mean + t).ceil() as i64)
+            .expect("floor(mean - t) <= ceil(mean + t)")
+    }
+
     /// Optimized version where sigmaFactor = -1/(2*sigma^2)
     pub fn unnormalized_gaussian_probability_density_function_optimized(
         mean: f64,
@@ -48,148 +64,87 @@ impl DiscreteGaussian {
         E.powf(sigma_factor * (x as f64 - mean).powi(2))
     }
 
-    fn algorithm_b<R: Rng>(rng: &mut R, k: i32, x: f64) -> bool {
-        let mut y = x as f32;
-        let m = (2 * k + 2) as f32;
-        let kk = k as f32;
-        let xx = x as f32;
-        let mut n = 0;
-
-        loop {
-            let z: f32 = StandardNormal.sample(rng);
-            if z > y {
-                break;
-            } else if z < y {
-                let r: f32 = StandardNormal.sample(rng);
-                let r_temp: f32 = (2.0 * kk + xx) / m;
-                if r > r_temp {
-                    break;
-                } else if r < r_temp {
-                    y = z;
-                } else {
-                    return Self::algorithm_b_double(rng, k, x);
-                }
-            } else {
-                return Self::algorithm_b_double(rng, k, x);
-            }
-
-            n += 1;
-        }
-        n & 1 == 0
+    /// A sampler seeded from the OS; call [`DiscreteGaussian::set_std_dev`]
+    /// before sampling to build the inversion table
+    pub fn new(std_dev: f64) -> Result<Self, rand_distr::NormalError> {
+        Self::with_rng(std_dev, StdRng::from_os_rng())
     }
 
-    fn algorithm_b_double<R: Rng>(rng: &mut R, k: i32, x: f64) -> bool {
-        let mut y = x;
-        let kk = k as f64;
-        let mut n = 0;
-        let m = (2 * k + 2) as f64;
-
-        loop {
-            let z: f64 = StandardNormal.sample(rng);
-            if !(z < y) {
-                break;
-            }
-            let r: f64 = StandardNormal.sample(rng);
-            if !(r < (2.0 * kk + x) / m) {
-                break;
-            }
-
-            y = z;
-            n += 1;
-        }
-
-        n & 1 == 0
+    /// Seed the internal generator from `rng` so sampling is reproducible
+    pub fn with_rng(
+        std_dev: f64,
+        mut rng: impl CryptoRng,
+    ) -> Result<Self, rand_distr::NormalError> {
+        let normal = Normal::new(0.0, std_dev)?;
+        Ok(Self {
+            normal,
+            rng: StdRng::from_rng(&mut rng),
+            values: Vec::new(),
+            karney: None,
+        })
     }
 
-    fn algorithm_g<R: Rng>(rng: &mut R) -> i32 {
-        let mut n = 0;
-        while Self::algorithm_h(rng) {
-            n += 1;
-        }
-        n
-    }
-
-    fn algorithm_h<R: Rng>(rng: &mut R) -> bool {
-        let mut h_a: f32 = StandardNormal.sample(rng);
-        let mut h_b;
-
-        if h_a > 0.5 {
-            true
-        } else if h_a < 0.5 {
-            loop {
-                h_b = StandardNormal.sample(rng);
-                if h_b > h_a {
-                    return false;
-                } else if h_b < h_a {
-                    h_a = StandardNormal.sample(rng);
-                } else {
-                    return Self::algorithm_h_double(rng);
-                }
-                if h_a > h_b {
-                    return true;
-                } else if h_a == h_b {
-                    return Self::algorithm_h_double(rng);
-                }
-            }
-        } else {
-            // Equal
-            Self::algorithm_h_double(rng)
-        }
+    /// One sample from the internal generator
+    pub fn gen_i32(&mut self) -> i32 {
+        Self::sample_from(
+            &self.values,
+            self.normal.mean(),
+            self.karney.as_ref(),
+            &mut self.rng,
+        ) as i32
     }
 
-    fn algorithm_h_double<R: Rng>(rng: &mut R) -> bool {
-        let mut h_a: f64 = StandardNormal.sample(rng);
-        let mut h_b;
+    /// [`DiscreteGaussian::gen_i32`] drawing randomness from `rng`
+    pub fn gen_i32_with_rng(&self, mut rng: impl CryptoRng) -> i32 {
+        self.sample(&mut rng) as i32
+    }
 
-        if !(h_a < 0.5) {
-            return true;
-        }
-        loop {
-            h_b = StandardNormal.sample(rng);
-            if !(h_b < h_a) {
-                return false;
-            } else {
-                h_a = StandardNormal.sample(rng);
-            }
-            if !(h_a < h_b) {
-                return true;
-            }
-        }
+    /// One sample from the Peikert table or the Karney sampler
+    fn sample(&self, rng: &mut impl CryptoRng) -> i64 {
+        Self::sample_from(&self.values, self.normal.mean(), self.karney.as_ref(), rng)
     }
 
-    fn algorithm_p<R: Rng>(rng: &mut R, mut n: i32) -> bool {
-        while n != 0 && Self::algorithm_h(rng) {
-            n -= 1;
+    fn sample_from(
+        values: &[f64],
+        mean: f64,
+        karney: Option<&KarneySampler>,
+        rng: &mut impl CryptoRng,
+    ) -> i64 {
+        match karney {
+            None => Self::peikert_sample(values, mean, rng) as i64,
+            Some(karney) => karney.sample_with_rng(rng),
         }
-        n < 0
     }
 
-    pub fn new(std_dev: f64) -> Result<Self, rand_distr::NormalError> {
-        let normal = Normal::new(0.0, std_dev)?;
-        Ok(Self {
-            normal,
-            rng: StdRng::from_os_rng(),
-            values: Vec::new(),
-            peikert: false,
-        })
+    fn peikert_sample<R: Rng>(values: &[f64], mean: f64, rng: &mut R) -> isize {
+        Self::peikert_from_uniform(values, mean, rng.sample(Open01))
     }
 
-    pub fn gen_i32(&mut self) -> i32 {
-        let seed: f64 = self.rng.sample(Open01);
+    /// The Peikert inversion of a uniform `seed` in `(0, 1)`
+    fn peikert_from_uniform(values: &[f64], mean: f64, seed: f64) -> isize {
         let seed = seed - 0.5;
-        let tmp = seed.abs() - self.normal.mean() / 2.0;
+        let tmp = seed.abs() - mean / 2.0;
         if tmp <= 0.0 {
             return 0;
         }
-        (find_in_vector(&self.values, tmp) * (if seed > 0.0 { 1 } else { -1 })) as i32
+        find_in_vector(values, tmp) * (if seed > 0.0 { 1 } else { -1 })
     }
 
+    /// One rejection sample around `mean`, for deviations set per call
     pub fn gen_i32_with_params(mean: f64, std_dev: f64, ring_dimension: usize) -> i32 {
+        Self::gen_i32_with_params_and_rng(mean, std_dev, ring_dimension, StdRng::from_os_rng())
+    }
+
+    /// [`DiscreteGaussian::gen_i32_with_params`] drawing randomness from `rng`
+    pub fn gen_i32_with_params_and_rng(
+        mean: f64,
+        std_dev: f64,
+        ring_dimension: usize,
+        mut rng: impl CryptoRng,
+    ) -> i32 {
         const LIMIT: usize = 10_000;
 
-        let mut rng = StdRng::from_os_rng();
-        let t = ring_dimension.ilog2() as f64 * std_dev;
-        let uniform_int = Normal::<f64>::new((mean - t).floor(), (mean + t).ceil()).unwrap();
+        let uniform_int = Self::candidate_range(mean, std_dev, ring_dimension);
         let sigma_factor = 1.0 / (-2.0 * std_dev * std_dev);
         let mut count = 0;
         let mut x = 0;
@@ -197,7 +152,7 @@ impl DiscreteGaussian {
 
         while !success {
             x = uniform_int.sample(&mut rng) as i32;
-            let dice: f64 = rng.sample(StandardNormal);
+            let dice: f64 = rng.random();
             success = dice
                 <= Self::unnormalized_gaussian_probability_density_function_optimized(
                     mean,
@@ -213,70 +168,88 @@ impl DiscreteGaussian {
         x
     }
 
+    /// One sample of Karney's sampler around `mean`, see [`KarneySampler`]
     pub fn gen_i32_karney(mean: f64, std_dev: f64) -> i32 {
-        let uniform_j = Normal::<f64>::new(0.0, std_dev.ceil() - 1.0).expect("");
-        let mut rng = StdRng::from_os_rng();
-
-        loop {
-            let k = Self::algorithm_g(&mut rng);
-
-            if !Self::algorithm_p(&mut rng, k) {
-                continue;
-            }
-
-            let s: f64 = rng.sample(StandardNormal);
-            let mut s = s as i64;
-            if s == 0 {
-                s = -1;
-            }
-
-            let di0 = std_dev * (k as f64) + (s as f64) * mean;
-            let i0 = di0.ceil() as i64;
-            let x0 = (i0 as f64 - di0) / std_dev;
-            let j = uniform_j.sample(&mut rng) as i64;
+        Self::gen_i32_karney_with_rng(mean, std_dev, StdRng::from_os_rng())
+    }
 
-            let x = x0 + j as f64 / std_dev;
+    /// [`DiscreteGaussian::gen_i32_karney`] drawing randomness from `rng`
+    pub fn gen_i32_karney_with_rng(mean: f64, std_dev: f64, mut rng: impl CryptoRng) -> i32 {
+        KarneySampler::with_rng(mean, std_dev, &mut rng)
+            .expect("a finite mean and positive standard deviation")
+            .sample_with_rng(&mut rng) as i32
+    }
 
-            if !(x < 1.0) || (x == 0.0 && s < 0 && k == 0) {
-                continue;
-            }
+    /// `length` samples from the internal generator
+    pub fn gen_i64_vec(&mut self, length: usize) -> Vec<i64> {
+        let mut values = vec![0; length];
+        self.fill_i64(&mut values);
+        values
+    }
 
-            let mut h = k + 1;
-            while h != 0 && Self::algorithm_b(&mut rng, k, x) {
-                h -= 1;
-            }
+    /// [`DiscreteGaussian::gen_i64_vec`] drawing randomness from `rng`
+    pub fn gen_i64_vec_with_rng(&self, rng: impl CryptoRng, length: usize) -> Vec<i64> {
+        let mut values = vec![0; length];
+        self.fill_i64_with_rng(rng, &mut values);
+        values
+    }
 
-            if !(h < 0) {
-                continue;
-            }
+    /// Overwrite every entry of `out` with a fresh sample, batched as in
+    /// [`DiscreteGaussian::fill_i64_batch_with_rng`] with the global
+    /// parallel configuration
+    pub fn fill_i64(&mut self, out: &mut [i64]) {
+        let mut rng = StdRng::from_rng(&mut self.rng);
+        self.fill_i64_batch_with_rng(&mut rng, out, global_config());
+    }
 
-            return (s * (i0 + j)) as i32;
+    /// Overwrite `out` in chunks of [`GAUSSIAN_BATCH_CHUNK`] samples.
+    ///
+    /// Every chunk draws from its own ChaCha20 stream seeded from `rng`, so
+    /// the uniform input of a whole chunk is generated in one call and the
+    /// result does not depend on the number of threads. Batches of at least
+    /// [`GAUSSIAN_PARALLEL_THRESHOLD`] samples spread the chunks over the
+    /// threads of `config`.
+    pub fn fill_i64_batch_with_rng(
+        &self,
+        mut rng: impl CryptoRng,
+        out: &mut [i64],
+        config: ParallelConfig,
+    ) {
+        let len = out.len();
+        let mut chunks = out
+            .chunks_mut(GAUSSIAN_BATCH_CHUNK)
+            .map(|chunk| (ChaCha20Rng::from_rng(&mut rng), chunk))
+            .collect::<Vec<_>>();
+        let fill = |(rng, chunk): &mut (ChaCha20Rng, &mut [i64])| self.fill_chunk(rng, chunk);
+        if len >= GAUSSIAN_PARALLEL_THRESHOLD {
+            config.for_each_mut(&mut chunks, fill);
+        } else {
+            chunks.iter_mut().for_each(fill);
         }
     }
 
-    pub fn gen_i64_vec(&mut self, length: usize) -> Vec<i64> {
-        let mut result = Vec::with_capacity(length);
-        if !self.peikert {
-            for _ in 0..length {
-                result.push(Self::gen_i32_karney(0.0, self.normal.mean()) as i64);
-            }
-            return result;
+    fn fill_chunk(&self, rng: &mut ChaCha20Rng, out: &mut [i64]) {
+        if let Some(karney) = &self.karney {
+            karney.fill_i64_with_rng(rng, out);
+            return;
         }
-
-        for _ in 0..length {
-            let seed: f64 = self.rng.sample(Open01);
-            let seed = seed - 0.5;
-            let tmp = seed.abs() - self.normal.mean() / 2.0;
-            let mut val = 0;
-            if tmp > 0.0 {
-                val = find_in_vector(&self.values, tmp) * if seed > 0.0 { 1 } else { -1 };
-            }
-            result.push(val as i64);
+        // One call fills the uniform bits of the chunk, written in place
+        // as u64 and converted to a sample in (0, 1) with 53 bits each
+        rng.fill(&mut *out);
+        for v in out.iter_mut() {
+            let seed = ((*v as u64 >> 11) as f64 + 0.5) * (1.0 / (1u64 << 53) as f64);
+            *v = Self::peikert_from_uniform(&self.values, self.normal.mean(), seed) as i64;
         }
+    }
 
-        result
+    /// [`DiscreteGaussian::fill_i64`] drawing randomness from `rng`
+    pub fn fill_i64_with_rng(&self, mut rng: impl CryptoRng, out: &mut [i64]) {
+        for v in out.iter_mut() {
+            *v = self.sample(&mut rng);
+        }
     }
 
+    /// One sample reduced into `[0, modulus)`
     pub fn gen_uint<const LIMBS: usize, const WIDE_LIMBS: usize, const UNSAT_LIMBS: usize>(
         &mut self,
         modulus: &Odd<Uint<LIMBS>>,
@@ -286,19 +259,34 @@ impl DiscreteGaussian {
         Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
         Odd<Uint<LIMBS>>: PrecomputeInverter<Inverter = SafeGcdInverter<LIMBS, UNSAT_LIMBS>>,
     {
-        let seed: f64 = self.rng.sample(Open01);
-        let seed = seed - 0.5;
-        let tmp = seed.abs() - self.normal.mean() / 2.0;
-        if tmp <= 0.0 {
-            return Uint::ZERO;
-        }
-        let val = find_in_vector(&self.values, tmp) * (if seed > 0.0 { 1 } else { -1 });
-        if val < 0 {
-            return **modulus - Uint::from(val.abs() as u64);
-        }
-        Uint::from(val as u64)
+        let val = Self::sample_from(
+            &self.values,
+            self.normal.mean(),
+            self.karney.as_ref(),
+            &mut self.rng,
+        );
+        uint_from_i64(val, modulus)
     }
 
+    /// [`DiscreteGaussian::gen_uint`] drawing randomness from `rng`
+    pub fn gen_uint_with_rng<
+        const LIMBS: usize,
+        const WIDE_LIMBS: usize,
+        const UNSAT_LIMBS: usize,
+    >(
+        &self,
+        mut rng: impl CryptoRng,
+        modulus: &Odd<Uint<LIMBS>>,
+    ) -> Uint<LIMBS>
+    where
+        Uint<LIMBS>: Concat<Output = Uint<WIDE_LIMBS>>,
+        Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
+        Odd<Uint<LIMBS>>: PrecomputeInverter<Inverter = SafeGcdInverter<LIMBS, UNSAT_LIMBS>>,
+    {
+        uint_from_i64(self.sample(&mut rng), modulus)
+    }
+
+    /// [`DiscreteGaussian::gen_i32_with_params`] reduced into `[0, modulus)`
     pub fn gen_uint_with_params<
         const LIMBS: usize,
         const WIDE_LIMBS: usize,
@@ -314,25 +302,44 @@ impl DiscreteGaussian {
         Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
         Odd<Uint<LIMBS>>: PrecomputeInverter<Inverter = SafeGcdInverter<LIMBS, UNSAT_LIMBS>>,
     {
-        let mut rng = StdRng::from_os_rng();
-        let t = ring_dimension.ilog2() as f64 * std_dev;
-        let uniform_int = Normal::<f64>::new((mean - t).floor(), (mean + t).ceil()).unwrap();
-
-        let mut x = rng.sample(uniform_int) as i32;
-        let mut not_finished = rng.sample::<f64, _>(Open01)
-            > Self::unnormalized_gaussian_probability_density_function(mean, std_dev, x);
-        while !not_finished {
-            x = rng.sample(uniform_int) as i32;
-            not_finished = rng.sample::<f64, _>(Open01)
-                > Self::unnormalized_gaussian_probability_density_function(mean, std_dev, x);
-        }
+        Self::gen_uint_with_params_and_rng(
+            mean,
+            std_dev,
+            ring_dimension,
+            modulus,
+            StdRng::from_os_rng(),
+        )
+    }
+
+    /// [`DiscreteGaussian::gen_uint_with_params`] drawing randomness from `rng`
+    pub fn gen_uint_with_params_and_rng<
+        const LIMBS: usize,
+        const WIDE_LIMBS: usize,
+        const UNSAT_LIMBS: usize,
+    >(
+        mean: f64,
+        std_dev: f64,
+        ring_dimension: usize,
+        modulus: &Odd<Uint<LIMBS>>,
+        mut rng: impl CryptoRng,
+    ) -> Uint<LIMBS>
+    where
+        Uint<LIMBS>: Concat<Output = Uint<WIDE_LIMBS>>,
+        Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
+        Odd<Uint<LIMBS>>: PrecomputeInverter<Inverter = SafeGcdInverter<LIMBS, UNSAT_LIMBS>>,
+    {
+        let uniform_int = Self::candidate_range(mean, std_dev, ring_dimension);
 
-        if x < 0 {
-            return **modulus - Uint::from(x.abs() as u64);
+        loop {
+            let x = uniform_int.sample(&mut rng) as i32;
+            let dice: f64 = rng.random();
+            if dice <= Self::unnormalized_gaussian_probability_density_function(mean, std_dev, x) {
+                return uint_from_i64(x as i64, modulus);
+            }
         }
-        Uint::from(x as u64)
     }
 
+    /// `length` samples reduced into `[0, modulus)`
     pub fn gen_vec_mod<const LIMBS: usize, const WIDE_LIMBS: usize, const UNSAT_LIMBS: usize>(
         &mut self,
         length: usize,
@@ -343,27 +350,35 @@ impl DiscreteGaussian {
         Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
         Odd<Uint<LIMBS>>: PrecomputeInverter<Inverter = SafeGcdInverter<LIMBS, UNSAT_LIMBS>>,
     {
-        VecMod {
-            values: self
-                .gen_i64_vec(length)
-                .into_iter()
-                .map(|i| {
-                    if i < 0 {
-                        **modulus - Uint::from(i.abs() as u64)
-                    } else {
-                        Uint::from(i as u64)
-                    }
-                })
-                .collect(),
-            params: MontyParams::new(*modulus),
-            _marker: PhantomData,
-        }
+        VecMod::from_signed(&self.gen_i64_vec(length), *modulus)
     }
 
+    /// [`DiscreteGaussian::gen_vec_mod`] drawing randomness from `rng`
+    pub fn gen_vec_mod_with_rng<
+        const LIMBS: usize,
+        const WIDE_LIMBS: usize,
+        const UNSAT_LIMBS: usize,
+    >(
+        &self,
+        rng: impl CryptoRng,
+        length: usize,
+        modulus: &Odd<Uint<LIMBS>>,
+    ) -> VecMod<LIMBS, WIDE_LIMBS>
+    where
+        Uint<LIMBS>: Concat<Output = Uint<WIDE_LIMBS>>,
+        Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
+        Odd<Uint<LIMBS>>: PrecomputeInverter<Inverter = SafeGcdInverter<LIMBS, UNSAT_LIMBS>>,
+    {
+        VecMod::from_signed(&self.gen_i64_vec_with_rng(rng, length), *modulus)
+    }
+
+    /// The standard deviation
     pub fn get_std_dev(&self) -> f64 {
         self.normal.std_dev()
     }
 
+    /// Set the standard deviation and rebuild the inversion table, or the
+    /// Karney sampler from [`KARNEY_THRESHOLD`] on
     pub fn set_std_dev(&mut self, std_dev: f64) {
         if std_dev.log2() > 59.0 {
             panic!("Standard deviation too large");
@@ -371,8 +386,17 @@ impl DiscreteGaussian {
 
         self.normal =
             Normal::new(self.normal.mean(), std_dev).expect("Failed to create normal distribution");
-        self.peikert = std_dev < KARNEY_THRESHOLD;
-        self.initialize();
+        self.karney = (std_dev >= KARNEY_THRESHOLD).then(|| {
+            KarneySampler::with_rng(0.0, std_dev, &mut self.rng)
+                .expect("the standard deviation is positive")
+        });
+        // The Karney sampler needs no table, which would not fit in memory
+        // for flooding sized deviations
+        if self.karney.is_some() {
+            self.values.clear();
+        } else {
+            self.initialize();
+        }
     }
 
     fn initialize(&mut self) {
@@ -400,3 +424,28 @@ impl DiscreteGaussian {
             Normal::new(mean, self.get_std_dev()).expect("Failed to create normal distribution");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeded(std_dev: f64, seed: u64) -> DiscreteGaussian {
+        let mut dg = DiscreteGaussian::with_rng(std_dev, ChaCha20Rng::seed_from_u64(seed))
+            .expect("valid standard deviation");
+        dg.set_std_dev(std_dev);
+        dg
+    }
+
+    #[test]
+    fn equal_seeds_give_equal_samples() {
+        // Below and above the Karney threshold
+        for std_dev in [3.19, 512.0] {
+            let mut a = seeded(std_dev, 5);
+            let mut b = seeded(std_dev, 5);
+            let samples = a.gen_i64_vec(1024);
+            assert_eq!(samples, b.gen_i64_vec(1024));
+            assert_eq!(a.gen_i32(), b.gen_i32());
+            assert_ne!(samples, seeded(std_dev, 6).gen_i64_vec(1024));
+        }
+    }
+}
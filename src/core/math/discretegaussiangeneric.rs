@@ -1,15 +1,21 @@
-use crate::core::math::{BaseSampler, Sampler, SamplerCombiner};
-use std::mem::MaybeUninit;
+use crate::constants::BaseSamplerType;
+use crate::core::math::{BaseSampler, BitGenerator, Sampler, SamplerCombiner};
+use crate::error::{Error, Result};
+use rand::CryptoRng;
+use std::cmp;
+use std::fmt::{self, Debug, Formatter};
 use std::sync::{Arc, Mutex};
-use std::{cmp, ptr};
 
 const PRECISION: usize = 53;
 const BERNOULLI_FLIPS: usize = 23;
+const COMBINER_LEVELS: usize = 3;
+const MAX_LOG_BASE: usize = 16;
 
+/// Samples discrete Gaussians of any mean and standard deviation from a
+/// fixed set of base samplers
 pub struct DiscreteGaussianGeneric {
     pub(crate) base_samplers: Vec<Arc<Mutex<BaseSampler>>>,
     pub(crate) wide_sampler: Arc<Mutex<dyn Sampler>>,
-    pub(crate) combiners: [Arc<Mutex<dyn Sampler>>; 4],
     pub(crate) wide_variance: f64,
     pub(crate) sampler_variance: f64,
     pub(crate) x: f64,
@@ -20,7 +26,24 @@ pub struct DiscreteGaussianGeneric {
     pub(crate) mask: u64,
 }
 
+impl Debug for DiscreteGaussianGeneric {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiscreteGaussianGeneric")
+            .field("base_samplers", &self.base_samplers.len())
+            .field("wide_variance", &self.wide_variance)
+            .field("sampler_variance", &self.sampler_variance)
+            .field("log_base", &self.log_base)
+            .finish()
+    }
+}
+
 impl DiscreteGaussianGeneric {
+    /// Start building a sampler with `2^log_base` base samplers of deviation `std_dev`
+    pub fn builder(std_dev: f64, log_base: usize) -> DiscreteGaussianGenericBuilder {
+        DiscreteGaussianGenericBuilder::new(std_dev, log_base)
+    }
+
+    /// Combine `samplers`, one per center `i / 2^log_base`, with smoothing parameter `n`
     pub fn new(
         samplers: &[Arc<Mutex<BaseSampler>>],
         std_dev: f64,
@@ -33,15 +56,9 @@ impl DiscreteGaussianGeneric {
         let mut wide_variance = base_variance;
         let mut x1;
         let mut x2;
-        let mut combiners = [
-            MaybeUninit::<Arc<Mutex<dyn Sampler>>>::uninit(),
-            MaybeUninit::<Arc<Mutex<dyn Sampler>>>::uninit(),
-            MaybeUninit::<Arc<Mutex<dyn Sampler>>>::uninit(),
-            MaybeUninit::<Arc<Mutex<dyn Sampler>>>::uninit(),
-        ];
         let mut wide_sampler: Arc<Mutex<dyn Sampler>> = base_samplers[0].clone();
         let t = 2.0 * n * n;
-        for i in 1..4 {
+        for _ in 0..COMBINER_LEVELS {
             x1 = (wide_variance / t).sqrt().floor() as i64;
             x2 = cmp::max(x1 - 1, 1);
             wide_sampler = Arc::new(Mutex::new(SamplerCombiner {
@@ -49,7 +66,6 @@ impl DiscreteGaussianGeneric {
                 x1,
                 x2,
             }));
-            combiners[i - 1].write(wide_sampler.clone());
             wide_variance *= (x1 * x1 + x2 * x2) as f64;
         }
         let k = (((PRECISION - BERNOULLI_FLIPS) as f64) / (log_base as f64)).ceil() as usize;
@@ -65,32 +81,33 @@ impl DiscreteGaussianGeneric {
         Self {
             base_samplers,
             wide_sampler,
-            combiners: unsafe {
-                [
-                    combiners[0].assume_init_read(),
-                    combiners[1].assume_init_read(),
-                    combiners[2].assume_init_read(),
-                    combiners[3].assume_init_read(),
-                ]
-            },
             wide_variance,
             sampler_variance,
             x: 0.0,
             c: 0.0,
             ci: 0.0,
-            k: 0,
+            k,
             log_base,
             mask,
         }
     }
 
+    /// Draw from the first base sampler
     pub fn random_i64(&mut self) -> i64 {
-        self.base_samplers[0].lock().unwrap().random_i64()
+        self.base_samplers[0]
+            .lock()
+            .expect("base sampler lock poisoned")
+            .random_i64()
     }
 
+    /// Draw with any `mean` and a `std_dev` above the deviation of the base samplers
     pub fn random_i64_with_params(&mut self, mean: f64, std_dev: f64) -> i64 {
         let variance = std_dev * std_dev;
-        self.x = self.wide_sampler.lock().unwrap().random_i64() as f64;
+        self.x = self
+            .wide_sampler
+            .lock()
+            .expect("wide sampler lock poisoned")
+            .random_i64() as f64;
         self.c = mean + self.x * ((variance - self.sampler_variance) / self.wide_variance).sqrt();
         self.ci = self.c.floor();
         self.c -= self.ci;
@@ -104,7 +121,10 @@ impl DiscreteGaussianGeneric {
         let mut bit;
 
         for i in (0..BERNOULLI_FLIPS).rev() {
-            bit = self.base_samplers[0].lock().unwrap().random_bit();
+            bit = self.base_samplers[0]
+                .lock()
+                .expect("base sampler lock poisoned")
+                .random_bit();
             let t = extract_bit(c, i);
             if bit > t {
                 return self.sample_c(base_c);
@@ -121,13 +141,12 @@ impl DiscreteGaussianGeneric {
         let mask = self.mask as i64;
         for _ in 0..self.k {
             let index = (mask & c) as usize;
-            let mut sample = self.base_samplers[index].lock().unwrap().random_i64();
-            if c < 0 {
-                sample -= 1;
-            }
-            for _ in 0..self.log_base {
-                c >>= 1;
-            }
+            let sample = self.base_samplers[index]
+                .lock()
+                .expect("base sampler lock poisoned")
+                .random_i64();
+            // An arithmetic shift already floors negative centers
+            c >>= self.log_base;
             c += sample;
         }
         c
@@ -137,3 +156,171 @@ impl DiscreteGaussianGeneric {
 fn extract_bit(number: i64, n: usize) -> u16 {
     ((number >> n) & 1) as u16
 }
+
+/// Builder for [`DiscreteGaussianGeneric`]
+#[derive(Debug, Clone, Copy)]
+pub struct DiscreteGaussianGenericBuilder {
+    /// The standard deviation of every base sampler
+    pub std_dev: f64,
+    /// Use `2^log_base` base samplers, one per center `i / 2^log_base`
+    pub log_base: usize,
+    /// The smoothing parameter used to size the wide sampler chain
+    pub smoothing: f64,
+    /// The algorithm of the base samplers
+    pub base_sampler_type: BaseSamplerType,
+}
+
+impl DiscreteGaussianGenericBuilder {
+    /// Create a new builder with Peikert base samplers and smoothing parameter `6`
+    pub fn new(std_dev: f64, log_base: usize) -> Self {
+        Self {
+            std_dev,
+            log_base,
+            smoothing: 6.0,
+            base_sampler_type: BaseSamplerType::Peikert,
+        }
+    }
+
+    /// Set the smoothing parameter
+    pub fn smoothing(mut self, smoothing: f64) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Set the algorithm of the base samplers
+    pub fn base_sampler_type(mut self, base_sampler_type: BaseSamplerType) -> Self {
+        self.base_sampler_type = base_sampler_type;
+        self
+    }
+
+    /// Create the base samplers, wide sampler chain and combiners
+    pub fn build(self) -> Result<DiscreteGaussianGeneric> {
+        self.build_with_rng(&mut rand::rng())
+    }
+
+    /// [`DiscreteGaussianGenericBuilder::build`] seeding every base sampler from `rng`
+    pub fn build_with_rng(self, rng: &mut impl CryptoRng) -> Result<DiscreteGaussianGeneric> {
+        if !(self.std_dev.is_finite() && self.std_dev > 0.0) {
+            return Err(Error::SamplerParams(format!(
+                "standard deviation {} is not positive",
+                self.std_dev
+            )));
+        }
+        if self.log_base == 0 || self.log_base > MAX_LOG_BASE {
+            return Err(Error::SamplerParams(format!(
+                "log base {} is not between 1 and {MAX_LOG_BASE}",
+                self.log_base
+            )));
+        }
+        if !(self.smoothing.is_finite() && self.smoothing > 0.0) {
+            return Err(Error::SamplerParams(format!(
+                "smoothing parameter {} is not positive",
+                self.smoothing
+            )));
+        }
+        let centers = 1usize << self.log_base;
+        let samplers = (0..centers)
+            .map(|i| {
+                Arc::new(Mutex::new(BaseSampler::new(
+                    i as f64 / centers as f64,
+                    self.std_dev,
+                    BitGenerator::from_rng(rng),
+                    self.base_sampler_type,
+                )))
+            })
+            .collect::<Vec<_>>();
+        Ok(DiscreteGaussianGeneric::new(
+            &samplers,
+            self.std_dev,
+            self.log_base,
+            self.smoothing,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    const STD_DEV: f64 = 3.2;
+    const SAMPLES: usize = 20_000;
+
+    fn sampler(log_base: usize) -> DiscreteGaussianGeneric {
+        DiscreteGaussianGeneric::builder(STD_DEV, log_base)
+            .build_with_rng(&mut ChaCha20Rng::seed_from_u64(7))
+            .expect("valid parameters")
+    }
+
+    /// The sample mean and standard deviation
+    fn moments(samples: impl Iterator<Item = i64>) -> (f64, f64) {
+        let samples = samples.collect::<Vec<_>>();
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<i64>() as f64 / n;
+        let variance = samples
+            .iter()
+            .map(|x| (*x as f64 - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1.0);
+        (mean, variance.sqrt())
+    }
+
+    #[test]
+    fn builder_rejects_invalid_parameters() {
+        let build = |builder: DiscreteGaussianGenericBuilder| {
+            matches!(builder.build(), Err(Error::SamplerParams(_)))
+        };
+        for std_dev in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(
+                build(DiscreteGaussianGeneric::builder(std_dev, 2)),
+                "{std_dev}"
+            );
+        }
+        for log_base in [0, MAX_LOG_BASE + 1] {
+            assert!(
+                build(DiscreteGaussianGeneric::builder(STD_DEV, log_base)),
+                "{log_base}"
+            );
+        }
+        for smoothing in [0.0, f64::NAN] {
+            assert!(build(
+                DiscreteGaussianGeneric::builder(STD_DEV, 2).smoothing(smoothing)
+            ));
+        }
+    }
+
+    #[test]
+    fn base_samplers_cover_every_center() {
+        let sampler = sampler(2);
+        assert_eq!(sampler.base_samplers.len(), 4);
+        assert_eq!(sampler.mask, 3);
+        for (i, base) in sampler.base_samplers.iter().enumerate() {
+            let mut base = base.lock().expect("base sampler lock poisoned");
+            let (mean, _) = moments((0..SAMPLES).map(|_| base.random_i64()));
+            // The standard error of the mean is about 0.023
+            assert!(
+                (mean - i as f64 / 4.0).abs() < 0.1,
+                "center {i} mean {mean}"
+            );
+        }
+    }
+
+    #[test]
+    fn samples_match_any_mean_and_deviation() {
+        let mut sampler = sampler(4);
+        for (mean, std_dev) in [(2.7, 30.0), (-11.3, 12.5)] {
+            let (sample_mean, sample_std_dev) =
+                moments((0..SAMPLES).map(|_| sampler.random_i64_with_params(mean, std_dev)));
+            // The standard error of the mean is below 0.22
+            assert!(
+                (sample_mean - mean).abs() < 1.0,
+                "mean {sample_mean} for {mean}"
+            );
+            assert!(
+                (sample_std_dev - std_dev).abs() < 0.05 * std_dev,
+                "standard deviation {sample_std_dev} for {std_dev}"
+            );
+        }
+    }
+}
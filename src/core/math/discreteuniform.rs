@@ -2,9 +2,10 @@ use crate::core::math::VecMod;
 use crypto_bigint::modular::SafeGcdInverter;
 use crypto_bigint::{Concat, Odd, PrecomputeInverter, RandomMod, Split, Uint};
 use rand::rngs::StdRng;
-use rand::{Rng, SeedableRng};
+use rand::{CryptoRng, SeedableRng};
 
-#[derive(Debug, Copy, Clone)]
+/// Uniform residues modulo an odd modulus from an internal generator
+#[derive(Debug, Clone)]
 pub struct DiscreteUniform<const LIMBS: usize, const WIDE_LIMBS: usize, const UNSAT_LIMBS: usize>
 where
     Uint<LIMBS>: Concat<Output = Uint<WIDE_LIMBS>>,
@@ -12,6 +13,7 @@ where
     Odd<Uint<LIMBS>>: PrecomputeInverter<Inverter = SafeGcdInverter<LIMBS, UNSAT_LIMBS>>,
 {
     pub(crate) modulus: Odd<Uint<LIMBS>>,
+    rng: StdRng,
 }
 
 impl<const LIMBS: usize, const WIDE_LIMBS: usize, const UNSAT_LIMBS: usize>
@@ -21,28 +23,55 @@ where
     Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
     Odd<Uint<LIMBS>>: PrecomputeInverter<Inverter = SafeGcdInverter<LIMBS, UNSAT_LIMBS>>,
 {
+    /// Sample modulo `modulus`, seeding the generator from the OS once
     pub fn new(modulus: Odd<Uint<LIMBS>>) -> Self {
-        Self { modulus }
+        Self::with_rng(modulus, StdRng::from_os_rng())
     }
 
+    /// Seed the internal generator from `rng` so sampling is reproducible
+    pub fn with_rng(modulus: Odd<Uint<LIMBS>>, mut rng: impl CryptoRng) -> Self {
+        Self {
+            modulus,
+            rng: StdRng::from_rng(&mut rng),
+        }
+    }
+
+    /// Sample modulo `modulus` from now on
     pub fn set_modulus(&mut self, modulus: Odd<Uint<LIMBS>>) {
         self.modulus = modulus;
     }
 
-    pub fn gen_uint(&self) -> Uint<LIMBS> {
-        Uint::<LIMBS>::random_mod(&mut StdRng::from_os_rng(), self.modulus.as_nz_ref())
+    /// One uniform residue
+    pub fn gen_uint(&mut self) -> Uint<LIMBS> {
+        Uint::<LIMBS>::random_mod(&mut self.rng, self.modulus.as_nz_ref())
+    }
+
+    /// [`DiscreteUniform::gen_uint`] drawing randomness from `rng`
+    pub fn gen_uint_with_rng(&self, mut rng: impl CryptoRng) -> Uint<LIMBS> {
+        Uint::<LIMBS>::random_mod(&mut rng, self.modulus.as_nz_ref())
     }
 
-    pub fn gen_vec_mod(&self, length: usize) -> VecMod<LIMBS, WIDE_LIMBS> {
-        VecMod::<LIMBS, WIDE_LIMBS>::random(StdRng::from_os_rng(), length, self.modulus)
+    /// `length` uniform residues
+    pub fn gen_vec_mod(&mut self, length: usize) -> VecMod<LIMBS, WIDE_LIMBS> {
+        VecMod::<LIMBS, WIDE_LIMBS>::random(&mut self.rng, length, self.modulus)
+    }
+
+    /// [`DiscreteUniform::gen_vec_mod`] drawing randomness from `rng`
+    pub fn gen_vec_mod_with_rng(
+        &self,
+        rng: impl CryptoRng,
+        length: usize,
+    ) -> VecMod<LIMBS, WIDE_LIMBS> {
+        VecMod::<LIMBS, WIDE_LIMBS>::random(rng, length, self.modulus)
     }
 
+    /// `length` uniform residues modulo `modulus`, which is kept for later calls
     pub fn gen_vec_mod_with_modulus(
         &mut self,
         length: usize,
         modulus: &Odd<Uint<LIMBS>>,
     ) -> VecMod<LIMBS, WIDE_LIMBS> {
         self.modulus = *modulus;
-        VecMod::<LIMBS, WIDE_LIMBS>::random(StdRng::from_os_rng(), length, self.modulus)
+        self.gen_vec_mod(length)
     }
 }
@@ -0,0 +1,295 @@
+use crate::core::math::{Sampler, UniformInt};
+use crate::error::{Error, Result};
+use rand::prelude::*;
+
+/// Karney's exact sampler for discrete Gaussians of any mean and standard
+/// deviation, see "Sampling exactly from the normal distribution" (2016).
+///
+/// Needs no tables, so it suits deviations too large for the Peikert and
+/// Knuth-Yao samplers and centers that change on every draw.
+#[derive(Debug, Clone)]
+pub struct KarneySampler {
+    mean: f64,
+    std_dev: f64,
+    uniform_j: UniformInt,
+    rng: StdRng,
+}
+
+impl Sampler for KarneySampler {
+    fn random_i64(&mut self) -> i64 {
+        self.sample()
+    }
+
+    fn random_bit(&mut self) -> u16 {
+        self.rng.random::<bool>() as u16
+    }
+}
+
+impl KarneySampler {
+    /// Sample around `mean` with `std_dev`, seeding the generator from the OS once
+    pub fn new(mean: f64, std_dev: f64) -> Result<Self> {
+        Self::with_rng(mean, std_dev, StdRng::from_os_rng())
+    }
+
+    /// Seed the internal generator from `rng` so sampling is reproducible
+    pub fn with_rng(mean: f64, std_dev: f64, mut rng: impl CryptoRng) -> Result<Self> {
+        Ok(Self {
+            mean,
+            std_dev,
+            uniform_j: Self::uniform_j(mean, std_dev)?,
+            rng: StdRng::from_rng(&mut rng),
+        })
+    }
+
+    /// The center of the distribution
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// The standard deviation
+    pub fn std_dev(&self) -> f64 {
+        self.std_dev
+    }
+
+    /// Sample around `mean` with `std_dev` from now on
+    pub fn set_params(&mut self, mean: f64, std_dev: f64) -> Result<()> {
+        self.uniform_j = Self::uniform_j(mean, std_dev)?;
+        self.mean = mean;
+        self.std_dev = std_dev;
+        Ok(())
+    }
+
+    /// One sample
+    pub fn sample(&mut self) -> i64 {
+        draw(self.mean, self.std_dev, &self.uniform_j, &mut self.rng)
+    }
+
+    /// [`KarneySampler::sample`] drawing randomness from `rng`
+    pub fn sample_with_rng(&self, rng: &mut impl CryptoRng) -> i64 {
+        draw(self.mean, self.std_dev, &self.uniform_j, rng)
+    }
+
+    /// Overwrite every entry of `out` with a fresh sample
+    pub fn fill_i64(&mut self, out: &mut [i64]) {
+        out.iter_mut().for_each(|v| *v = self.sample());
+    }
+
+    /// [`KarneySampler::fill_i64`] drawing randomness from `rng`
+    pub fn fill_i64_with_rng(&self, mut rng: impl CryptoRng, out: &mut [i64]) {
+        out.iter_mut()
+            .for_each(|v| *v = self.sample_with_rng(&mut rng));
+    }
+
+    /// The offsets `j` in `[0, ceil(std_dev))` added to the scaled candidate
+    fn uniform_j(mean: f64, std_dev: f64) -> Result<UniformInt> {
+        if !(mean.is_finite() && std_dev.is_finite() && std_dev > 0.0) {
+            return Err(Error::SamplerParams(format!(
+                "mean {mean} and standard deviation {std_dev} must be finite, the deviation positive"
+            )));
+        }
+        UniformInt::new(0, std_dev.ceil() as i64 - 1)
+    }
+}
+
+/// Karney's algorithm D
+fn draw<R: Rng + ?Sized>(mean: f64, std_dev: f64, uniform_j: &UniformInt, rng: &mut R) -> i64 {
+    loop {
+        // k with probability exp(-k / 2) (1 - exp(-1 / 2)), kept with
+        // probability exp(-k (k - 1) / 2)
+        let k = algorithm_g(rng);
+        if !algorithm_p(rng, k * (k - 1)) {
+            continue;
+        }
+
+        let s: i64 = if rng.random() { 1 } else { -1 };
+        let di0 = std_dev * k as f64 + s as f64 * mean;
+        let i0 = di0.ceil() as i64;
+        let x0 = (i0 as f64 - di0) / std_dev;
+        let j = uniform_j.sample(rng);
+        let x = x0 + j as f64 / std_dev;
+        if x >= 1.0 || (x == 0.0 && s < 0 && k == 0) {
+            continue;
+        }
+
+        // Accept with probability exp(-x (2k + x) / 2), k + 1 times
+        let mut h = k + 1;
+        while h > 0 && algorithm_b(rng, k, x) {
+            h -= 1;
+        }
+        if h == 0 {
+            return s * (i0 + j);
+        }
+    }
+}
+
+/// Whether `n` Bernoulli trials of probability `exp(-1/2)` all succeed
+fn algorithm_p<R: Rng + ?Sized>(rng: &mut R, n: i32) -> bool {
+    (0..n).all(|_| algorithm_h(rng))
+}
+
+/// The number of successes of [`algorithm_h`] before the first failure
+fn algorithm_g<R: Rng + ?Sized>(rng: &mut R) -> i32 {
+    let mut n = 0;
+    while algorithm_h(rng) {
+        n += 1;
+    }
+    n
+}
+
+/// True with probability `exp(-1/2)`, in single precision unless two draws tie
+fn algorithm_h<R: Rng + ?Sized>(rng: &mut R) -> bool {
+    let mut h_a: f32 = rng.random();
+    if h_a > 0.5 {
+        return true;
+    }
+    if h_a == 0.5 {
+        return algorithm_h_double(rng);
+    }
+    loop {
+        let h_b: f32 = rng.random();
+        if h_b > h_a {
+            return false;
+        }
+        if h_b == h_a {
+            return algorithm_h_double(rng);
+        }
+        h_a = rng.random();
+        if h_a > h_b {
+            return true;
+        }
+        if h_a == h_b {
+            return algorithm_h_double(rng);
+        }
+    }
+}
+
+fn algorithm_h_double<R: Rng + ?Sized>(rng: &mut R) -> bool {
+    let mut h_a: f64 = rng.random();
+    if h_a >= 0.5 {
+        return true;
+    }
+    loop {
+        let h_b: f64 = rng.random();
+        if h_b >= h_a {
+            return false;
+        }
+        h_a = rng.random();
+        if h_a >= h_b {
+            return true;
+        }
+    }
+}
+
+/// True with probability `exp(-x (2k + x) / (2k + 2))`, in single precision
+/// unless two draws tie
+fn algorithm_b<R: Rng + ?Sized>(rng: &mut R, k: i32, x: f64) -> bool {
+    let mut y = x as f32;
+    let m = (2 * k + 2) as f32;
+    let r_bound = (2.0 * k as f32 + x as f32) / m;
+    let mut n = 0;
+    loop {
+        let z: f32 = rng.random();
+        if z > y {
+            break;
+        }
+        if z == y {
+            return algorithm_b_double(rng, k, x);
+        }
+        let r: f32 = rng.random();
+        if r > r_bound {
+            break;
+        }
+        if r == r_bound {
+            return algorithm_b_double(rng, k, x);
+        }
+        y = z;
+        n += 1;
+    }
+    n % 2 == 0
+}
+
+fn algorithm_b_double<R: Rng + ?Sized>(rng: &mut R, k: i32, x: f64) -> bool {
+    let mut y = x;
+    let m = (2 * k + 2) as f64;
+    let mut n = 0;
+    loop {
+        let z: f64 = rng.random();
+        if z >= y {
+            break;
+        }
+        let r: f64 = rng.random();
+        if r >= (2.0 * k as f64 + x) / m {
+            break;
+        }
+        y = z;
+        n += 1;
+    }
+    n % 2 == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::ChaCha20Rng;
+
+    const SAMPLES: usize = 50_000;
+
+    /// The sample mean and standard deviation
+    fn moments(samples: &[i64]) -> (f64, f64) {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<i64>() as f64 / n;
+        let variance = samples
+            .iter()
+            .map(|x| (*x as f64 - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1.0);
+        (mean, variance.sqrt())
+    }
+
+    #[test]
+    fn samples_match_fractional_means_and_deviations() {
+        for (mean, std_dev) in [(3.7, 2.3), (-15.25, 42.6)] {
+            let sampler = KarneySampler::with_rng(mean, std_dev, ChaCha20Rng::seed_from_u64(11))
+                .expect("valid parameters");
+            let mut samples = vec![0; SAMPLES];
+            sampler.fill_i64_with_rng(ChaCha20Rng::seed_from_u64(13), &mut samples);
+            let (sample_mean, sample_std_dev) = moments(&samples);
+            // Four standard errors of the mean
+            let tolerance = 4.0 * std_dev / (SAMPLES as f64).sqrt();
+            assert!(
+                (sample_mean - mean).abs() < tolerance,
+                "mean {sample_mean} for {mean}"
+            );
+            assert!(
+                (sample_std_dev - std_dev).abs() < 0.02 * std_dev,
+                "standard deviation {sample_std_dev} for {std_dev}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_parameters_are_rejected() {
+        for (mean, std_dev) in [
+            (0.0, 0.0),
+            (0.0, -1.5),
+            (0.0, f64::NAN),
+            (0.0, f64::INFINITY),
+            (f64::NAN, 1.0),
+            (f64::NEG_INFINITY, 1.0),
+        ] {
+            assert!(
+                matches!(
+                    KarneySampler::new(mean, std_dev),
+                    Err(Error::SamplerParams(_))
+                ),
+                "{mean} {std_dev}"
+            );
+        }
+
+        let mut sampler = KarneySampler::new(1.5, 2.5).expect("valid parameters");
+        assert!(sampler.set_params(1.5, 0.0).is_err());
+        assert_eq!((sampler.mean(), sampler.std_dev()), (1.5, 2.5));
+        sampler.set_params(-0.5, 4.0).expect("valid parameters");
+        assert_eq!((sampler.mean(), sampler.std_dev()), (-0.5, 4.0));
+    }
+}
@@ -0,0 +1,37 @@
+use crypto_bigint::modular::MontyParams;
+use crypto_bigint::{Odd, Uint, Word};
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+/// Moduli beyond this are computed without being cached so untrusted input
+/// can't grow the cache without bound
+const MAX_CACHED_MODULI: usize = 4096;
+
+type Cache = RwLock<HashMap<Vec<Word>, Box<dyn Any + Send + Sync>>>;
+
+static CACHE: OnceLock<Cache> = OnceLock::new();
+
+/// The Montgomery parameters for `modulus`, computed once per modulus and shared
+/// by every [`VecMod`](super::VecMod) and polynomial built over it
+pub fn cached_monty_params<const LIMBS: usize>(modulus: Odd<Uint<LIMBS>>) -> MontyParams<LIMBS> {
+    let cache = CACHE.get_or_init(Default::default);
+    // The key length distinguishes widths, the downcast guards against a mismatch
+    let key = modulus.as_ref().as_words();
+    if let Ok(map) = cache.read()
+        && let Some(params) = map
+            .get(key.as_slice())
+            .and_then(|p| p.downcast_ref::<MontyParams<LIMBS>>())
+    {
+        return *params;
+    }
+
+    // Moduli are public, so the variable time setup leaks nothing
+    let params = MontyParams::new_vartime(modulus);
+    if let Ok(mut map) = cache.write()
+        && map.len() < MAX_CACHED_MODULI
+    {
+        map.insert(key.to_vec(), Box::new(params));
+    }
+    params
+}
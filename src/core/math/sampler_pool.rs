@@ -0,0 +1,138 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+
+static NEXT_POOL_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static INSTANCES: RefCell<HashMap<u64, Instance>> = RefCell::new(HashMap::new());
+}
+
+/// A thread's sampler and the pool it belongs to
+struct Instance {
+    owner: Weak<dyn Any + Send + Sync>,
+    sampler: Box<dyn Any>,
+}
+
+struct Shared<S> {
+    id: u64,
+    factory: Box<dyn Fn() -> S + Send + Sync>,
+}
+
+impl<S> Drop for Shared<S> {
+    fn drop(&mut self) {
+        // Other threads drop theirs on their next first use of any pool, or
+        // when they exit. Thread local storage may already be gone here.
+        let _ = INSTANCES.try_with(|m| m.borrow_mut().remove(&self.id));
+    }
+}
+
+/// Hands every thread its own sampler so parallel sampling never shares a lock.
+///
+/// Instances are built lazily by the factory the first time a thread uses the
+/// pool and live in thread local storage until the thread exits or the last
+/// clone of the pool is dropped.
+pub struct SamplerPool<S: 'static> {
+    shared: Arc<Shared<S>>,
+}
+
+impl<S: 'static> Clone for SamplerPool<S> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<S: 'static> Debug for SamplerPool<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SamplerPool")
+            .field("id", &self.shared.id)
+            .finish()
+    }
+}
+
+impl<S: 'static> SamplerPool<S> {
+    /// Create a pool whose per-thread instances come from `factory`
+    pub fn new(factory: impl Fn() -> S + Send + Sync + 'static) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                id: NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed),
+                factory: Box::new(factory),
+            }),
+        }
+    }
+
+    /// Run `f` with the calling thread's sampler
+    pub fn with<R>(&self, f: impl FnOnce(&mut S) -> R) -> R {
+        let id = self.shared.id;
+        // Taken out of the map while in use so `f` may use other pools
+        let mut sampler = match INSTANCES
+            .with(|m| m.borrow_mut().remove(&id))
+            .and_then(|instance| instance.sampler.downcast::<S>().ok())
+        {
+            Some(sampler) => sampler,
+            None => {
+                INSTANCES.with(|m| {
+                    m.borrow_mut()
+                        .retain(|_, instance| instance.owner.strong_count() > 0)
+                });
+                Box::new((self.shared.factory)())
+            }
+        };
+        let result = f(&mut sampler);
+        let owner = Arc::downgrade(&self.shared);
+        INSTANCES.with(|m| m.borrow_mut().insert(id, Instance { owner, sampler }));
+        result
+    }
+
+    /// Drop the calling thread's sampler, the next use builds a fresh one
+    pub fn reset_current_thread(&self) {
+        INSTANCES.with(|m| m.borrow_mut().remove(&self.shared.id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn one_sampler_per_thread() {
+        let built = Arc::new(AtomicUsize::new(0));
+        let counter = built.clone();
+        let pool = SamplerPool::new(move || counter.fetch_add(1, Ordering::Relaxed));
+        assert_eq!(pool.with(|s| *s), 0);
+        assert_eq!(pool.with(|s| *s), 0);
+        let other = pool.clone();
+        let from_thread = std::thread::spawn(move || other.with(|s| *s))
+            .join()
+            .expect("thread");
+        assert_eq!(from_thread, 1);
+        assert_eq!(built.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn dropping_the_pool_drops_the_sampler() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let counter = dropped.clone();
+        let pool = SamplerPool::new(move || Counted(counter.clone()));
+        pool.with(|_| ());
+        let clone = pool.clone();
+        drop(pool);
+        assert_eq!(dropped.load(Ordering::Relaxed), 0);
+        drop(clone);
+        assert_eq!(dropped.load(Ordering::Relaxed), 1);
+    }
+}
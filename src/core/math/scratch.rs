@@ -0,0 +1,156 @@
+//! Thread local scratch buffers for transform temporaries
+//!
+//! Every NTT needs a working vector as large as the ring dimension, and so do
+//! the residues key switching decomposes into digits. Instead of allocating
+//! one per call, buffers are taken from a per-thread pool and handed back once
+//! the operation is done. Sample and residue buffers may hold errors, secrets
+//! or digits of them, so they are zeroized over their whole capacity before
+//! they are pooled.
+
+use crypto_bigint::U64;
+use crypto_bigint::modular::MontyForm;
+use std::cell::RefCell;
+use std::thread::LocalKey;
+use zeroize::Zeroize;
+
+/// The maximum number of buffers kept in the pool of each thread
+pub const MAX_POOLED_BUFFERS: usize = 16;
+
+type MontyBuffer = Vec<MontyForm<{ U64::LIMBS }>>;
+
+thread_local! {
+    static MONTY_BUFFERS: RefCell<Vec<MontyBuffer>> = const { RefCell::new(Vec::new()) };
+    static SAMPLE_BUFFERS: RefCell<Vec<Vec<i64>>> = const { RefCell::new(Vec::new()) };
+    static RESIDUE_BUFFERS: RefCell<Vec<Vec<u64>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Pre-allocate `buffers` scratch buffers for `ring_dimension` on the current thread
+/// so the first transforms don't pay for the allocations.
+///
+/// At most [`MAX_POOLED_BUFFERS`] are kept.
+pub fn prewarm(ring_dimension: usize, buffers: usize) {
+    MONTY_BUFFERS.with_borrow_mut(|pool| {
+        for buffer in pool.iter_mut() {
+            buffer.reserve(ring_dimension);
+        }
+        let buffers = buffers.min(MAX_POOLED_BUFFERS);
+        while pool.len() < buffers {
+            pool.push(Vec::with_capacity(ring_dimension));
+        }
+    });
+}
+
+/// The number of buffers pooled on the current thread
+pub fn pooled() -> usize {
+    MONTY_BUFFERS.with_borrow(|pool| pool.len())
+}
+
+/// Release all buffers pooled on the current thread
+pub fn clear() {
+    MONTY_BUFFERS.with_borrow_mut(|pool| pool.clear());
+    SAMPLE_BUFFERS.with_borrow_mut(|pool| pool.clear());
+    RESIDUE_BUFFERS.with_borrow_mut(|pool| pool.clear());
+}
+
+/// Take an empty buffer with room for at least `len` elements
+pub(crate) fn take_monty(len: usize) -> MontyBuffer {
+    let mut buffer =
+        MONTY_BUFFERS.with_borrow_mut(|pool| match pool.iter().position(|b| b.capacity() >= len) {
+            Some(i) => pool.swap_remove(i),
+            None => pool.pop().unwrap_or_default(),
+        });
+    buffer.reserve(len);
+    buffer
+}
+
+/// Return a buffer to the pool of the current thread
+pub(crate) fn give_monty(mut buffer: MontyBuffer) {
+    buffer.clear();
+    // The pool may already be gone if this runs during thread teardown
+    let _ = MONTY_BUFFERS.try_with(|pool| {
+        let mut pool = pool.borrow_mut();
+        if pool.len() < MAX_POOLED_BUFFERS {
+            pool.push(buffer);
+        }
+    });
+}
+
+/// Take a zeroed buffer of `len` signed samples
+pub(crate) fn take_samples(len: usize) -> Vec<i64> {
+    take_zeroed(&SAMPLE_BUFFERS, len)
+}
+
+/// Zeroize a sample buffer and return it to the pool of the current thread
+pub(crate) fn give_samples(buffer: Vec<i64>) {
+    give_zeroized(&SAMPLE_BUFFERS, buffer);
+}
+
+/// Take a zeroed buffer of `len` residues
+pub(crate) fn take_residues(len: usize) -> Vec<u64> {
+    take_zeroed(&RESIDUE_BUFFERS, len)
+}
+
+/// Zeroize a residue buffer and return it to the pool of the current thread
+pub(crate) fn give_residues(buffer: Vec<u64>) {
+    give_zeroized(&RESIDUE_BUFFERS, buffer);
+}
+
+fn take_zeroed<T: Copy + Default>(
+    pool: &'static LocalKey<RefCell<Vec<Vec<T>>>>,
+    len: usize,
+) -> Vec<T> {
+    let mut buffer = pool.with_borrow_mut(|pool| pool.pop().unwrap_or_default());
+    buffer.resize(len, T::default());
+    buffer
+}
+
+fn give_zeroized<T: Zeroize>(pool: &'static LocalKey<RefCell<Vec<Vec<T>>>>, mut buffer: Vec<T>) {
+    // Clears the buffer and zeroes its spare capacity too, a shorter
+    // buffer may have held longer contents before
+    buffer.zeroize();
+    // The pool may already be gone if this runs during thread teardown
+    let _ = pool.try_with(|pool| {
+        let mut pool = pool.borrow_mut();
+        if pool.len() < MAX_POOLED_BUFFERS {
+            pool.push(buffer);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The pooled buffers of `pool`, spare capacity included
+    fn pooled_memory<T: Copy>(pool: &'static LocalKey<RefCell<Vec<Vec<T>>>>) -> Vec<Vec<T>> {
+        pool.with_borrow(|pool| {
+            pool.iter()
+                .map(|b| {
+                    // SAFETY: zeroize wrote every element of the capacity
+                    unsafe { std::slice::from_raw_parts(b.as_ptr(), b.capacity()) }.to_vec()
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn pooled_buffers_are_zeroized_over_their_capacity() {
+        clear();
+        let mut samples = take_samples(64);
+        samples.fill(-7);
+        samples.truncate(3);
+        give_samples(samples);
+        let mut residues = take_residues(64);
+        residues.fill(7);
+        give_residues(residues);
+
+        let samples = pooled_memory(&SAMPLE_BUFFERS);
+        assert_eq!(samples.len(), 1);
+        assert!(samples[0].len() >= 64 && samples[0].iter().all(|&v| v == 0));
+        let residues = pooled_memory(&RESIDUE_BUFFERS);
+        assert_eq!(residues.len(), 1);
+        assert!(residues[0].len() >= 64 && residues[0].iter().all(|&v| v == 0));
+        assert_eq!(take_samples(8), vec![0; 8]);
+        clear();
+    }
+}
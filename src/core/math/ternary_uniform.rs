@@ -0,0 +1,154 @@
+use crate::constants::{SPARSE_HAMMING_WEIGHT, SecretKeyDistribution};
+use crate::core::math::VecMod;
+use crate::error::{Error, Result};
+use crypto_bigint::modular::SafeGcdInverter;
+use crypto_bigint::{Concat, Odd, PrecomputeInverter, Split, Uint};
+use rand::seq::SliceRandom;
+use rand::{CryptoRng, Rng};
+
+/// Samples vectors over `{-1, 0, 1}`, the secrets of ternary key generation.
+///
+/// Uniform sampling draws every entry independently with probability `1/3`
+/// each. Sparse sampling sets exactly `hamming_weight` random entries to `±1`
+/// and leaves the rest zero, as OpenFHE does for sparse secrets.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TernaryUniform {
+    hamming_weight: Option<usize>,
+}
+
+impl TernaryUniform {
+    /// Every entry uniform over `{-1, 0, 1}`
+    pub fn uniform() -> Self {
+        Self::default()
+    }
+
+    /// Exactly `hamming_weight` nonzero entries with random signs
+    pub fn sparse(hamming_weight: usize) -> Self {
+        Self {
+            hamming_weight: Some(hamming_weight),
+        }
+    }
+
+    /// The sampler for secrets of `distribution`, `None` for Gaussian secrets.
+    ///
+    /// Sparse secrets have [`SPARSE_HAMMING_WEIGHT`] nonzero entries.
+    pub fn for_secret_key_distribution(distribution: SecretKeyDistribution) -> Option<Self> {
+        match distribution {
+            SecretKeyDistribution::Gaussian => None,
+            SecretKeyDistribution::UniformTernary => Some(Self::uniform()),
+            SecretKeyDistribution::SparseTernary => Some(Self::sparse(SPARSE_HAMMING_WEIGHT)),
+        }
+    }
+
+    /// The number of nonzero entries, `None` when uniform
+    pub fn hamming_weight(&self) -> Option<usize> {
+        self.hamming_weight
+    }
+
+    /// A vector of `length` entries
+    pub fn gen_i64_vec(&self, length: usize) -> Result<Vec<i64>> {
+        self.gen_i64_vec_with_rng(rand::rng(), length)
+    }
+
+    /// [`TernaryUniform::gen_i64_vec`] drawing randomness from `rng`
+    pub fn gen_i64_vec_with_rng(&self, rng: impl CryptoRng, length: usize) -> Result<Vec<i64>> {
+        let mut values = vec![0; length];
+        self.fill_i64_with_rng(rng, &mut values)?;
+        Ok(values)
+    }
+
+    /// Overwrite every entry of `out` with a fresh sample
+    pub fn fill_i64_with_rng(&self, mut rng: impl CryptoRng, out: &mut [i64]) -> Result<()> {
+        match self.hamming_weight {
+            None => out.iter_mut().for_each(|v| *v = rng.random_range(-1..=1)),
+            Some(weight) => {
+                if weight > out.len() {
+                    return Err(Error::SamplerParams(format!(
+                        "hamming weight {weight} exceeds the length {}",
+                        out.len()
+                    )));
+                }
+                // Fisher-Yates over the whole buffer places the `weight`
+                // signs uniformly without allocating a list of positions
+                let (nonzero, zero) = out.split_at_mut(weight);
+                nonzero
+                    .iter_mut()
+                    .for_each(|v| *v = if rng.random() { 1 } else { -1 });
+                zero.fill(0);
+                out.shuffle(&mut rng);
+            }
+        }
+        Ok(())
+    }
+
+    /// A vector of `length` entries mapped into `[0, modulus)`
+    pub fn gen_vec_mod<const LIMBS: usize, const WIDE_LIMBS: usize, const UNSAT_LIMBS: usize>(
+        &self,
+        length: usize,
+        modulus: Odd<Uint<LIMBS>>,
+    ) -> Result<VecMod<LIMBS, WIDE_LIMBS>>
+    where
+        Uint<LIMBS>: Concat<Output = Uint<WIDE_LIMBS>>,
+        Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
+        Odd<Uint<LIMBS>>: PrecomputeInverter<Inverter = SafeGcdInverter<LIMBS, UNSAT_LIMBS>>,
+    {
+        let values = self.gen_i64_vec(length)?;
+        Ok(VecMod::from_signed(&values, modulus))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    const LENGTH: usize = 1024;
+
+    #[test]
+    fn sparse_samples_have_exactly_the_weight() {
+        let mut rng = ChaCha20Rng::seed_from_u64(5);
+        for weight in [0, 1, 64, LENGTH - 1, LENGTH] {
+            // Stale values must not survive in the zero remainder
+            let mut out = vec![7; LENGTH];
+            TernaryUniform::sparse(weight)
+                .fill_i64_with_rng(&mut rng, &mut out)
+                .expect("weight fits");
+            let nonzero = out.iter().filter(|&&v| v != 0).count();
+            assert_eq!(nonzero, weight);
+            assert!(out.iter().all(|v| [-1, 0, 1].contains(v)), "{weight}");
+            assert_eq!(out.iter().filter(|&&v| v == 0).count(), LENGTH - weight);
+        }
+
+        let out = TernaryUniform::sparse(512)
+            .gen_i64_vec_with_rng(&mut rng, LENGTH)
+            .expect("weight fits");
+        let ones = out.iter().filter(|&&v| v == 1).count();
+        // Both signs occur, and the weight is not packed at the front
+        assert!((192..=320).contains(&ones), "{ones} ones");
+        assert!(out[..512].contains(&0));
+    }
+
+    #[test]
+    fn sparse_weights_beyond_the_length_are_rejected() {
+        let mut out = vec![7; 16];
+        assert!(matches!(
+            TernaryUniform::sparse(17).fill_i64_with_rng(ChaCha20Rng::seed_from_u64(5), &mut out),
+            Err(Error::SamplerParams(_))
+        ));
+        assert_eq!(out, [7; 16]);
+    }
+
+    #[test]
+    fn uniform_samples_cover_the_three_values() {
+        let out = TernaryUniform::uniform()
+            .gen_i64_vec_with_rng(ChaCha20Rng::seed_from_u64(5), 3 * LENGTH)
+            .expect("uniform");
+        for value in [-1, 0, 1] {
+            let count = out.iter().filter(|&&v| v == value).count();
+            // About six standard deviations around 1024
+            assert!((870..=1180).contains(&count), "{count} of {value}");
+        }
+        assert_eq!(TernaryUniform::uniform().hamming_weight(), None);
+    }
+}
@@ -0,0 +1,36 @@
+use crate::error::{Error, Result};
+use rand::Rng;
+use rand::distr::{Distribution, Uniform};
+
+/// Uniform integers in an inclusive range, e.g. the candidates of a rejection sampler
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UniformInt {
+    low: i64,
+    high: i64,
+    inner: Uniform<i64>,
+}
+
+impl UniformInt {
+    /// Sample from `low..=high`
+    pub fn new(low: i64, high: i64) -> Result<Self> {
+        let inner = Uniform::new_inclusive(low, high)
+            .map_err(|e| Error::SamplerParams(format!("range {low}..={high}: {e}")))?;
+        Ok(Self { low, high, inner })
+    }
+
+    /// The smallest value that can be drawn
+    pub fn low(&self) -> i64 {
+        self.low
+    }
+
+    /// The largest value that can be drawn
+    pub fn high(&self) -> i64 {
+        self.high
+    }
+}
+
+impl Distribution<i64> for UniformInt {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> i64 {
+        self.inner.sample(rng)
+    }
+}
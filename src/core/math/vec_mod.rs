@@ -1,11 +1,16 @@
+#[cfg(feature = "simd")]
+use crate::core::math::SIMD_LANES;
+use crate::core::math::{Barrett64, cached_monty_params, dispatch};
+use crate::serdes::{BoundedSeq, compact_uints};
 use crypto_bigint::modular::{MontyForm, MontyParams, SafeGcdInverter};
 use crypto_bigint::*;
 use rand::CryptoRng;
 use serde::{
     Deserialize, Deserializer, Serialize, Serializer,
-    de::{Error as DError, MapAccess, SeqAccess, Visitor},
+    de::{DeserializeSeed, Error as DError, MapAccess, SeqAccess, Visitor},
     ser::SerializeStruct,
 };
+use std::borrow::Borrow;
 use std::fmt::Formatter;
 use std::marker::PhantomData;
 use std::ops::{
@@ -32,8 +37,9 @@ macro_rules! ops_impl {
         {
             type Output = $output;
 
-            fn $func(self, rhs: $rhs) -> Self::Output {
-                &self $op &rhs
+            fn $func(mut self, rhs: $rhs) -> Self::Output {
+                self $op_assign &rhs;
+                self
             }
         }
 
@@ -43,8 +49,9 @@ macro_rules! ops_impl {
         {
             type Output = $output;
 
-            fn $func(self, rhs: &$rhs) -> Self::Output {
-                &self $op rhs
+            fn $func(mut self, rhs: &$rhs) -> Self::Output {
+                self $op_assign rhs;
+                self
             }
         }
 
@@ -145,6 +152,20 @@ where
     }
 }
 
+impl<const LIMBS: usize, const WIDE_LIMBS: usize> VecMod<LIMBS, WIDE_LIMBS>
+where
+    Uint<LIMBS>: Concat<Output = Uint<WIDE_LIMBS>>,
+    Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
+{
+    /// The `u64` fast path, available for single word moduli such as [`VecModStd`]
+    fn barrett(&self) -> Option<Barrett64> {
+        if LIMBS != 1 || Word::BITS != u64::BITS {
+            return None;
+        }
+        Barrett64::new(word(self.params.modulus()))
+    }
+}
+
 ops_impl!(
     Add,
     add,
@@ -164,6 +185,17 @@ where
     Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
 {
     fn add_assign(&mut self, rhs: &Uint<LIMBS>) {
+        if let Some(b) = self.barrett() {
+            let rhs = b.reduce_word(word(rhs));
+            zip_words(
+                &mut self.values,
+                std::iter::repeat(&Uint::from_u64(rhs)),
+                #[cfg(feature = "simd")]
+                |x, y| b.add_mod_simd(x, y),
+                |x, y| b.add_mod(x, y),
+            );
+            return;
+        }
         let m = self.params.modulus().get();
         self.values
             .iter_mut()
@@ -191,6 +223,16 @@ where
 {
     fn add_assign(&mut self, rhs: &VecMod<LIMBS, WIDE_LIMBS>) {
         assert_eq!(self.params, rhs.params);
+        if let Some(b) = self.barrett() {
+            zip_words(
+                &mut self.values,
+                rhs.values.iter(),
+                #[cfg(feature = "simd")]
+                |x, y| b.add_mod_simd(x, y),
+                |x, y| b.add_mod(x, y),
+            );
+            return;
+        }
         let m = self.params.modulus().get();
         self.values
             .iter_mut()
@@ -220,6 +262,17 @@ where
     Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
 {
     fn sub_assign(&mut self, rhs: &Uint<LIMBS>) {
+        if let Some(b) = self.barrett() {
+            let rhs = b.reduce_word(word(rhs));
+            zip_words(
+                &mut self.values,
+                std::iter::repeat(&Uint::from_u64(rhs)),
+                #[cfg(feature = "simd")]
+                |x, y| b.sub_mod_simd(x, y),
+                |x, y| b.sub_mod(x, y),
+            );
+            return;
+        }
         let m = self.params.modulus().get();
         self.values
             .iter_mut()
@@ -247,6 +300,16 @@ where
 {
     fn sub_assign(&mut self, rhs: &VecMod<LIMBS, WIDE_LIMBS>) {
         assert_eq!(self.params, rhs.params);
+        if let Some(b) = self.barrett() {
+            zip_words(
+                &mut self.values,
+                rhs.values.iter(),
+                #[cfg(feature = "simd")]
+                |x, y| b.sub_mod_simd(x, y),
+                |x, y| b.sub_mod(x, y),
+            );
+            return;
+        }
         let m = self.params.modulus().get();
         self.values
             .iter_mut()
@@ -276,10 +339,19 @@ where
     Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
 {
     fn mul_assign(&mut self, rhs: &Uint<LIMBS>) {
-        let m = self.params.modulus().as_nz_ref();
+        if let Some(b) = self.barrett() {
+            let rhs = b.reduce_word(word(rhs));
+            dispatch(|| {
+                self.values
+                    .iter_mut()
+                    .for_each(|it| *it = Uint::from_u64(b.mul_mod(word(it), rhs)))
+            });
+            return;
+        }
+        let rhs = MontyForm::new(rhs, self.params);
         self.values
             .iter_mut()
-            .for_each(|it| *it = it.mul_mod(rhs, m));
+            .for_each(|it| *it = mul_by_monty(it, &rhs));
     }
 }
 
@@ -303,16 +375,80 @@ where
 {
     fn mul_assign(&mut self, rhs: &VecMod<LIMBS, WIDE_LIMBS>) {
         assert_eq!(self.params, rhs.params);
-        let m = self.params.modulus().as_nz_ref();
+        if let Some(b) = self.barrett() {
+            dispatch(|| {
+                self.values
+                    .iter_mut()
+                    .zip(rhs.values.iter())
+                    .for_each(|(it, rhs)| *it = Uint::from_u64(b.mul_mod(word(it), word(rhs))))
+            });
+            return;
+        }
+        // Converting `rhs` is one Montgomery multiplication, two of them
+        // still beat the wide division behind `Uint::mul_mod`
+        self.values
+            .iter_mut()
+            .zip(rhs.values.iter())
+            .for_each(|(it, rhs)| *it = mul_by_monty(it, &MontyForm::new(rhs, self.params)));
+    }
+}
+
+impl<const LIMBS: usize, const WIDE_LIMBS: usize> MulAssign<&MontyVecMod<LIMBS>>
+    for VecMod<LIMBS, WIDE_LIMBS>
+where
+    Uint<LIMBS>: Concat<Output = Uint<WIDE_LIMBS>>,
+    Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
+{
+    fn mul_assign(&mut self, rhs: &MontyVecMod<LIMBS>) {
+        assert_eq!(self.params, rhs.params);
+        let params = self.params;
         self.values
             .iter_mut()
             .zip(rhs.values.iter())
             .for_each(|(it, rhs)| {
-                *it = it.mul_mod(rhs, m);
+                *it = mul_by_monty(it, &MontyForm::from_montgomery(*rhs, params))
             });
     }
 }
 
+/// A vector mod `q` held in Montgomery form, each value standing for `x * R mod q`.
+///
+/// Multiplying a [`VecMod`] by it costs one Montgomery multiplication per
+/// element, convert with [`VecMod::to_montgomery`] once when the same operand
+/// multiplies several vectors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MontyVecMod<const LIMBS: usize> {
+    values: Vec<Uint<LIMBS>>,
+    params: MontyParams<LIMBS>,
+}
+
+impl<const LIMBS: usize> MontyVecMod<LIMBS> {
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Leave Montgomery form
+    pub fn retrieve<const WIDE_LIMBS: usize>(&self) -> VecMod<LIMBS, WIDE_LIMBS>
+    where
+        Uint<LIMBS>: Concat<Output = Uint<WIDE_LIMBS>>,
+        Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
+    {
+        VecMod {
+            values: self
+                .values
+                .iter()
+                .map(|it| MontyForm::from_montgomery(*it, self.params).retrieve())
+                .collect(),
+            params: self.params,
+            _marker: PhantomData,
+        }
+    }
+}
+
 impl<const LIMBS: usize, const WIDE_LIMBS: usize, const UNSAT_LIMBS: usize>
     Div<NonZero<Uint<LIMBS>>> for VecMod<LIMBS, WIDE_LIMBS>
 where
@@ -415,30 +551,12 @@ where
     Uint<LIMBS>: Concat<Output = Uint<WIDE_LIMBS>>,
     Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>>,
 {
-    fn rem_assign(&mut self, modulus: &Odd<Uint<LIMBS>>) {
-        let new_modulus = modulus.get();
-        let old_modulus = self.params.modulus().get();
-        let half_q = old_modulus >> 1;
-
-        if new_modulus > old_modulus {
-            let diff = new_modulus - old_modulus;
-            self.values.iter_mut().for_each(|x| {
-                if *x > half_q {
-                    *x += diff;
-                }
-            });
-        } else {
-            let nz_modulus = modulus.as_nz_ref();
-            let diff = new_modulus - old_modulus.rem(nz_modulus);
-            self.values.iter_mut().for_each(|x| {
-                if *x > half_q {
-                    *x += diff;
-                }
-                if *x >= new_modulus {
-                    *x = x.rem(nz_modulus);
-                }
-            });
-        }
+    /// Replace every value with its remainder modulo `rhs`, keeping the modulus.
+    ///
+    /// Use [`VecMod::mod_switch`] to move the values to a new modulus.
+    fn rem_assign(&mut self, rhs: &Odd<Uint<LIMBS>>) {
+        let nz_modulus = rhs.as_nz_ref();
+        self.values.iter_mut().for_each(|x| *x = x.rem(nz_modulus));
     }
 }
 
@@ -451,6 +569,12 @@ where
     where
         S: Serializer,
     {
+        if !s.is_human_readable() {
+            return s.serialize_bytes(&compact_uints::encode(
+                &self.params.modulus().get(),
+                &self.values,
+            ));
+        }
         let mut state = s.serialize_struct("VecMod", 2)?;
         state.serialize_field("modulus", self.params.modulus())?;
         state.serialize_field("values", &self.values)?;
@@ -468,125 +592,178 @@ where
     where
         D: Deserializer<'de>,
     {
-        struct VecModVisitor<'de, const LIMBS: usize, const WIDE_LIMBS: usize>
-        where
-            Uint<LIMBS>: Concat<Output = Uint<WIDE_LIMBS>> + Deserialize<'de>,
-            Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>> + Deserialize<'de>,
-        {
-            _marker: PhantomData<&'de [(); WIDE_LIMBS]>,
-        }
+        BoundedVecMod::new(usize::MAX).deserialize(d)
+    }
+}
 
-        enum Field {
-            Values,
-            Modulus,
-        }
-        const FIELDS: &[&str] = &["values", "modulus"];
+/// Deserializes a [`VecMod`] of at most `max_len` values, rejecting longer
+/// inputs before their values are decoded
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct BoundedVecMod<const LIMBS: usize, const WIDE_LIMBS: usize> {
+    max_len: usize,
+    _marker: PhantomData<[(); WIDE_LIMBS]>,
+}
 
-        impl<'de> Deserialize<'de> for Field {
-            fn deserialize<D>(d: D) -> Result<Self, D::Error>
-            where
-                D: Deserializer<'de>,
-            {
-                struct FieldVisitor;
+impl<const LIMBS: usize, const WIDE_LIMBS: usize> BoundedVecMod<LIMBS, WIDE_LIMBS> {
+    pub(crate) fn new(max_len: usize) -> Self {
+        Self {
+            max_len,
+            _marker: PhantomData,
+        }
+    }
 
-                impl<'de> Visitor<'de> for FieldVisitor {
-                    type Value = Field;
+    fn values_seed(&self) -> BoundedSeq<PhantomData<Uint<LIMBS>>> {
+        BoundedSeq {
+            seed: PhantomData,
+            max_len: self.max_len,
+            what: "values",
+        }
+    }
+}
 
-                    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
-                        write!(f, "`values` or `modulus`")
-                    }
+enum VecModField {
+    Values,
+    Modulus,
+}
 
-                    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
-                    where
-                        E: DError,
-                    {
-                        match v {
-                            "values" => Ok(Field::Values),
-                            "modulus" => Ok(Field::Modulus),
-                            _ => Err(DError::unknown_field(v, FIELDS)),
-                        }
-                    }
-                }
+const VEC_MOD_FIELDS: &[&str] = &["values", "modulus"];
 
-                d.deserialize_identifier(FieldVisitor)
-            }
-        }
+impl<'de> Deserialize<'de> for VecModField {
+    fn deserialize<D>(d: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct FieldVisitor;
 
-        impl<'de, const LIMBS: usize, const WIDE_LIMBS: usize> Visitor<'de>
-            for VecModVisitor<'de, LIMBS, WIDE_LIMBS>
-        where
-            Uint<LIMBS>: Concat<Output = Uint<WIDE_LIMBS>> + Deserialize<'de>,
-            Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>> + Deserialize<'de>,
-        {
-            type Value = VecMod<LIMBS, WIDE_LIMBS>;
+        impl<'de> Visitor<'de> for FieldVisitor {
+            type Value = VecModField;
 
             fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
-                write!(f, "a modulus and values")
+                write!(f, "`values` or `modulus`")
             }
 
-            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
             where
-                A: SeqAccess<'de>,
+                E: DError,
             {
-                let modulus: Odd<Uint<LIMBS>> = seq
-                    .next_element()?
-                    .ok_or_else(|| DError::invalid_length(0, &self))?;
-                let values: Vec<Uint<LIMBS>> = seq
-                    .next_element()?
-                    .ok_or_else(|| DError::invalid_length(1, &self))?;
-
-                Ok(VecMod {
-                    values,
-                    params: MontyParams::new(modulus),
-                    _marker: PhantomData,
-                })
+                match v {
+                    "values" => Ok(VecModField::Values),
+                    "modulus" => Ok(VecModField::Modulus),
+                    _ => Err(DError::unknown_field(v, VEC_MOD_FIELDS)),
+                }
             }
+        }
 
-            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
-            where
-                A: MapAccess<'de>,
-            {
-                let mut values = None;
-                let mut modulus = None;
-
-                while let Some(key) = map.next_key()? {
-                    match key {
-                        Field::Values => {
-                            if values.is_some() {
-                                return Err(DError::duplicate_field("values"));
-                            }
-
-                            values = Some(map.next_value()?);
-                        }
-                        Field::Modulus => {
-                            if modulus.is_some() {
-                                return Err(DError::duplicate_field("modulus"));
-                            }
-
-                            modulus = Some(map.next_value()?);
-                        }
+        d.deserialize_identifier(FieldVisitor)
+    }
+}
+
+fn check_reduced<E: DError, const LIMBS: usize>(
+    values: &[Uint<LIMBS>],
+    modulus: &Odd<Uint<LIMBS>>,
+) -> Result<(), E> {
+    let modulus = modulus.get();
+    if values.iter().any(|v| *v >= modulus) {
+        return Err(DError::custom("values must be reduced by the modulus"));
+    }
+    Ok(())
+}
+
+impl<'de, const LIMBS: usize, const WIDE_LIMBS: usize> DeserializeSeed<'de>
+    for BoundedVecMod<LIMBS, WIDE_LIMBS>
+where
+    Uint<LIMBS>: Concat<Output = Uint<WIDE_LIMBS>> + Deserialize<'de>,
+    Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>> + Deserialize<'de>,
+{
+    type Value = VecMod<LIMBS, WIDE_LIMBS>;
+
+    fn deserialize<D>(self, d: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if !d.is_human_readable() {
+            let max_len = compact_uints::encoded_len::<LIMBS>(self.max_len);
+            let bytes = d.deserialize_bytes(compact_uints::BytesVisitor { max_len })?;
+            let (modulus, values) = compact_uints::decode::<D::Error, LIMBS>(&bytes)?;
+            let modulus = Option::<Odd<Uint<LIMBS>>>::from(CtOption::from(modulus.to_odd()))
+                .ok_or_else(|| DError::custom("modulus is not odd"))?;
+            check_reduced(&values, &modulus)?;
+            return Ok(VecMod {
+                values,
+                params: cached_monty_params(modulus),
+                _marker: PhantomData,
+            });
+        }
+
+        d.deserialize_struct("VecMod", VEC_MOD_FIELDS, self)
+    }
+}
+
+impl<'de, const LIMBS: usize, const WIDE_LIMBS: usize> Visitor<'de>
+    for BoundedVecMod<LIMBS, WIDE_LIMBS>
+where
+    Uint<LIMBS>: Concat<Output = Uint<WIDE_LIMBS>> + Deserialize<'de>,
+    Uint<WIDE_LIMBS>: Split<Output = Uint<LIMBS>> + Deserialize<'de>,
+{
+    type Value = VecMod<LIMBS, WIDE_LIMBS>;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "a modulus and at most {} values", self.max_len)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let modulus: Odd<Uint<LIMBS>> = seq
+            .next_element()?
+            .ok_or_else(|| DError::invalid_length(0, &self))?;
+        let values = seq
+            .next_element_seed(self.values_seed())?
+            .ok_or_else(|| DError::invalid_length(1, &self))?;
+        check_reduced(&values, &modulus)?;
+
+        Ok(VecMod {
+            values,
+            params: cached_monty_params(modulus),
+            _marker: PhantomData,
+        })
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut values = None;
+        let mut modulus = None;
+
+        while let Some(key) = map.next_key()? {
+            match key {
+                VecModField::Values => {
+                    if values.is_some() {
+                        return Err(DError::duplicate_field("values"));
                     }
+
+                    values = Some(map.next_value_seed(self.values_seed())?);
                 }
+                VecModField::Modulus => {
+                    if modulus.is_some() {
+                        return Err(DError::duplicate_field("modulus"));
+                    }
 
-                let modulus: Odd<Uint<LIMBS>> =
-                    modulus.ok_or_else(|| DError::missing_field("modulus"))?;
-                let values: Vec<Uint<LIMBS>> =
-                    values.ok_or_else(|| DError::missing_field("values"))?;
-                Ok(VecMod {
-                    values,
-                    params: MontyParams::new(modulus),
-                    _marker: PhantomData,
-                })
+                    modulus = Some(map.next_value()?);
+                }
             }
         }
 
-        d.deserialize_struct(
-            "VecMod",
-            FIELDS,
-            VecModVisitor::<LIMBS, WIDE_LIMBS> {
-                _marker: PhantomData,
-            },
-        )
+        let modulus: Odd<Uint<LIMBS>> = modulus.ok_or_else(|| DError::missing_field("modulus"))?;
+        let values = values.ok_or_else(|| DError::missing_field("values"))?;
+        check_reduced(&values, &modulus)?;
+        Ok(VecMod {
+            values,
+            params: cached_monty_params(modulus),
+            _marker: PhantomData,
+        })
     }
 }
 
@@ -600,7 +777,7 @@ where
     pub fn with_value_uint(len: usize, value: Uint<LIMBS>, modulus: Odd<Uint<LIMBS>>) -> Self {
         let nz_modulus = modulus.as_nz_ref();
         let values = vec![value.rem(nz_modulus); len];
-        let params = MontyParams::new(modulus);
+        let params = cached_monty_params(modulus);
         Self {
             values,
             params,
@@ -614,7 +791,21 @@ where
             .iter()
             .map(|x| Uint::from_u64(*x as u64).rem(nz_modulus))
             .collect::<Vec<_>>();
-        let params = MontyParams::new(modulus);
+        let params = cached_monty_params(modulus);
+        Self {
+            values,
+            params,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Map signed values to `[0, q)`, negative `x` becoming `q - |x|`
+    pub fn from_signed(values: &[i64], modulus: Odd<Uint<LIMBS>>) -> Self {
+        let values = values
+            .iter()
+            .map(|x| uint_from_i64(*x, &modulus))
+            .collect::<Vec<_>>();
+        let params = cached_monty_params(modulus);
         Self {
             values,
             params,
@@ -622,6 +813,38 @@ where
         }
     }
 
+    /// Replace the values with small signed integers mapped into `[0, q)`,
+    /// reusing the existing allocation
+    pub fn fill_signed(&mut self, values: &[i64]) {
+        let modulus = *self.params.modulus();
+        self.values.clear();
+        self.values
+            .extend(values.iter().map(|x| uint_from_i64(*x, &modulus)));
+    }
+
+    /// The centered representation in `(-q/2, q/2]`.
+    ///
+    /// # Panics
+    ///
+    /// If a centered value does not fit in an `i64`.
+    pub fn to_signed(&self) -> Vec<i64> {
+        let modulus = self.params.modulus().get();
+        let half_q = modulus >> 1;
+        self.values
+            .iter()
+            .map(|x| {
+                let (magnitude, negative) = if *x > half_q {
+                    (modulus.wrapping_sub(x), true)
+                } else {
+                    (*x, false)
+                };
+                assert!(magnitude.bits() < i64::BITS, "value does not fit in an i64");
+                let magnitude = magnitude.as_words()[0] as i64;
+                if negative { -magnitude } else { magnitude }
+            })
+            .collect()
+    }
+
     pub fn inverse(&self) -> Option<Self> {
         let mut result = self.clone();
         if result.values.iter().any(|i| i.is_zero().into()) {
@@ -634,6 +857,100 @@ where
         Some(result)
     }
 
+    /// Add `rhs` elementwise in place without first collecting it into a [`VecMod`].
+    ///
+    /// The items of `rhs` are reduced modulo `q` first.
+    ///
+    /// # Panics
+    ///
+    /// If `rhs` does not yield exactly one item per value.
+    pub fn add_assign_iter<I>(&mut self, rhs: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Uint<LIMBS>>,
+    {
+        let m = self.params.modulus();
+        for_each_reduced(&mut self.values, rhs, m, |it, rhs| {
+            *it = it.add_mod(rhs, &m.get())
+        });
+    }
+
+    /// Subtract `rhs` elementwise in place without first collecting it into a [`VecMod`].
+    ///
+    /// The items of `rhs` are reduced modulo `q` first.
+    ///
+    /// # Panics
+    ///
+    /// If `rhs` does not yield exactly one item per value.
+    pub fn sub_assign_iter<I>(&mut self, rhs: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Uint<LIMBS>>,
+    {
+        let m = self.params.modulus();
+        for_each_reduced(&mut self.values, rhs, m, |it, rhs| {
+            *it = it.sub_mod(rhs, &m.get())
+        });
+    }
+
+    /// Multiply by `rhs` elementwise in place without first collecting it into a [`VecMod`].
+    ///
+    /// # Panics
+    ///
+    /// If `rhs` does not yield exactly one item per value.
+    pub fn mul_assign_iter<I>(&mut self, rhs: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Uint<LIMBS>>,
+    {
+        let params = self.params;
+        // `MontyForm::new` reduces the items itself
+        for_each_reduced(&mut self.values, rhs, params.modulus(), |it, rhs| {
+            *it = mul_by_monty(it, &MontyForm::new(rhs, params))
+        });
+    }
+
+    /// Convert to Montgomery form for repeated elementwise products
+    pub fn to_montgomery(&self) -> MontyVecMod<LIMBS> {
+        MontyVecMod {
+            values: self
+                .values
+                .iter()
+                .map(|it| MontyForm::new(it, self.params).to_montgomery())
+                .collect(),
+            params: self.params,
+        }
+    }
+
+    /// Compute the inner product `Σ self[i] * other[i] mod q`.
+    ///
+    /// Products are accumulated in `WIDE_LIMBS` and only reduced once the
+    /// accumulator could overflow, instead of after every multiplication.
+    pub fn dot(&self, other: &Self) -> Uint<LIMBS> {
+        assert_eq!(self.params, other.params);
+        assert_eq!(self.values.len(), other.values.len());
+
+        let m = self.params.modulus().as_nz_ref();
+        // acc < q after a reduction and each product is < 2^(2 * bits(q)),
+        // so 2^headroom - 1 products can be added before the next one
+        let headroom = Uint::<WIDE_LIMBS>::BITS - 2 * m.bits();
+        let batch = if headroom >= usize::BITS - 1 {
+            usize::MAX
+        } else {
+            ((1usize << headroom) - 1).max(1)
+        };
+
+        let mut acc = Uint::<WIDE_LIMBS>::ZERO;
+        for (i, (a, b)) in self.values.iter().zip(other.values.iter()).enumerate() {
+            if i > 0 && i % batch == 0 {
+                acc = Uint::rem_wide_vartime(acc.split(), m).concat(&Uint::ZERO);
+            }
+            let (lo, hi) = a.split_mul(b);
+            acc = acc.wrapping_add(&lo.concat(&hi));
+        }
+        Uint::rem_wide_vartime(acc.split(), m)
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &Uint<LIMBS>> {
         self.values.iter()
     }
@@ -671,20 +988,124 @@ where
         result
     }
 
+    /// Raise every element to the same public `exponent`.
+    ///
+    /// The exponent length is computed once and shared by all elements so
+    /// short exponents don't pay for a full width exponentiation.
     pub fn pow_assign(&mut self, exponent: &Uint<LIMBS>) {
+        let exponent_bits = exponent.bits_vartime();
         self.values.iter_mut().for_each(|it| {
             let t = MontyForm::new(it, self.params);
-            *it = t.pow(exponent).retrieve();
+            *it = t.pow_bounded_exp(exponent, exponent_bits).retrieve();
         })
     }
 
+    /// Compute `base^e mod q` for every `e` in `exponents`.
+    ///
+    /// A table of `base^(d * 16^j)` for every nibble position `j` is built once,
+    /// after which each exponent costs one multiplication per nonzero nibble
+    /// and no squarings.
+    pub fn pow_fixed_base(
+        base: &Uint<LIMBS>,
+        exponents: &[Uint<LIMBS>],
+        modulus: Odd<Uint<LIMBS>>,
+    ) -> Self {
+        const WINDOW: u32 = 4;
+        let params = cached_monty_params(modulus);
+        let bits = exponents
+            .iter()
+            .map(|e| e.bits_vartime())
+            .max()
+            .unwrap_or(0);
+        let windows = bits.div_ceil(WINDOW) as usize;
+
+        let one = MontyForm::one(params);
+        let mut table = Vec::with_capacity(windows);
+        let mut power = MontyForm::new(base, params);
+        for _ in 0..windows {
+            let mut row = Vec::with_capacity(1 << WINDOW);
+            row.push(one);
+            for d in 1..1 << WINDOW {
+                row.push(row[d - 1] * power);
+            }
+            power = row[(1 << WINDOW) - 1] * power;
+            table.push(row);
+        }
+
+        let values = exponents
+            .iter()
+            .map(|e| {
+                let words = e.as_words();
+                table
+                    .iter()
+                    .enumerate()
+                    .fold(one, |acc, (j, row)| {
+                        let shift = j as u32 * WINDOW;
+                        let word = words[(shift / Word::BITS) as usize];
+                        let digit = (word >> (shift % Word::BITS)) as usize & ((1 << WINDOW) - 1);
+                        if digit == 0 { acc } else { acc * row[digit] }
+                    })
+                    .retrieve()
+            })
+            .collect();
+        Self {
+            values,
+            params,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The consecutive powers `base^0, base^1, ..., base^(len - 1) mod q`,
+    /// e.g. a table of roots of unity
+    pub fn powers(base: &Uint<LIMBS>, len: usize, modulus: Odd<Uint<LIMBS>>) -> Self {
+        let params = cached_monty_params(modulus);
+        let base = MontyForm::new(base, params);
+        let mut values = Vec::with_capacity(len);
+        let mut power = MontyForm::one(params);
+        for _ in 0..len {
+            values.push(power.retrieve());
+            power *= base;
+        }
+        Self {
+            values,
+            params,
+            _marker: PhantomData,
+        }
+    }
+
     pub fn modulus(&self) -> &Odd<Uint<LIMBS>> {
         self.params.modulus()
     }
 
-    pub fn switch_modulus(&mut self, modulus: Odd<Uint<LIMBS>>) {
-        *self %= &modulus;
-        self.params = MontyParams::new(modulus);
+    /// Switch to `modulus`, keeping the centered representative of every value.
+    ///
+    /// Values above `q/2` are treated as negative, so a small negative `x mod q`
+    /// becomes `x mod q'` instead of a large positive residue.
+    pub fn mod_switch(&mut self, modulus: Odd<Uint<LIMBS>>) {
+        let new_modulus = modulus.get();
+        let old_modulus = self.params.modulus().get();
+        let half_q = old_modulus >> 1;
+
+        if new_modulus > old_modulus {
+            let diff = new_modulus - old_modulus;
+            self.values.iter_mut().for_each(|x| {
+                if *x > half_q {
+                    *x += diff;
+                }
+            });
+        } else {
+            let nz_modulus = modulus.as_nz_ref();
+            let diff = new_modulus - old_modulus.rem(nz_modulus);
+            self.values.iter_mut().for_each(|x| {
+                if *x > half_q {
+                    *x += diff;
+                }
+                if *x >= new_modulus {
+                    *x = x.rem(nz_modulus);
+                }
+            });
+        }
+        self.params = cached_monty_params(modulus);
     }
 
     pub fn random(mut rng: impl CryptoRng, length: usize, modulus: Odd<Uint<LIMBS>>) -> Self {
@@ -694,7 +1115,7 @@ where
             let value = Uint::<LIMBS>::random_mod(&mut rng, nz_modulus);
             values.push(value);
         }
-        let params = MontyParams::new(modulus);
+        let params = cached_monty_params(modulus);
         Self {
             values,
             params,
@@ -702,3 +1123,274 @@ where
         }
     }
 }
+
+/// Map a signed value to `[0, q)`, negative `x` becoming `q - |x|`
+pub(crate) fn uint_from_i64<const LIMBS: usize>(
+    value: i64,
+    modulus: &Odd<Uint<LIMBS>>,
+) -> Uint<LIMBS> {
+    let nz_modulus = modulus.as_nz_ref();
+    let magnitude = Uint::from_u64(value.unsigned_abs()).rem(nz_modulus);
+    if value < 0 && !bool::from(magnitude.is_zero()) {
+        modulus.get().wrapping_sub(&magnitude)
+    } else {
+        magnitude
+    }
+}
+
+/// Call `f` on every value with the matching item of `rhs` reduced modulo `modulus`.
+///
+/// Panics unless `rhs` has exactly as many items as `values`.
+fn for_each_reduced<const LIMBS: usize, I>(
+    values: &mut [Uint<LIMBS>],
+    rhs: I,
+    modulus: &Odd<Uint<LIMBS>>,
+    mut f: impl FnMut(&mut Uint<LIMBS>, &Uint<LIMBS>),
+) where
+    I: IntoIterator,
+    I::Item: Borrow<Uint<LIMBS>>,
+{
+    let nz_modulus = modulus.as_nz_ref();
+    let mut rhs = rhs.into_iter();
+    for value in values.iter_mut() {
+        let item = rhs.next().expect("rhs to have as many values as self");
+        f(value, &item.borrow().rem(nz_modulus));
+    }
+    assert!(rhs.next().is_none(), "rhs to have as many values as self");
+}
+
+/// `a * b mod q` for a reduced `a` and `b` already in Montgomery form.
+///
+/// Reading `a` as a Montgomery representation stands for `a / R`, so one
+/// Montgomery multiplication by `b * R` lands on `a * b` without converting
+/// `a` in or the product out. Converting `b` costs another Montgomery
+/// multiplication, [`MontyVecMod`] pays it once per operand.
+#[inline]
+fn mul_by_monty<const LIMBS: usize>(a: &Uint<LIMBS>, b: &MontyForm<LIMBS>) -> Uint<LIMBS> {
+    (MontyForm::from_montgomery(*a, *b.params()) * b).to_montgomery()
+}
+
+/// The least significant word of `x` as a `u64`
+#[inline]
+#[allow(clippy::useless_conversion)] // `Word` is `u32` on 32-bit targets
+fn word<const LIMBS: usize>(x: &Uint<LIMBS>) -> u64 {
+    u64::from(x.as_words()[0])
+}
+
+/// Combine the single word `values` with `rhs` in place.
+///
+/// With the `simd` feature whole chunks go through `lanes` and only the tail
+/// falls back to `scalar`, otherwise `scalar` is compiled for the instruction
+/// set picked by [`dispatch`].
+#[inline]
+fn zip_words<'a, const LIMBS: usize>(
+    values: &mut [Uint<LIMBS>],
+    rhs: impl IntoIterator<Item = &'a Uint<LIMBS>>,
+    #[cfg(feature = "simd")] lanes: impl Fn(wide::u64x8, wide::u64x8) -> wide::u64x8,
+    scalar: impl Fn(u64, u64) -> u64,
+) {
+    let rhs = rhs.into_iter();
+    #[cfg(feature = "simd")]
+    let (values, rhs) = {
+        let mut rhs = rhs;
+        let mut chunks = values.chunks_exact_mut(SIMD_LANES);
+        for chunk in &mut chunks {
+            let mut a = [0u64; SIMD_LANES];
+            let mut b = [0u64; SIMD_LANES];
+            let mut filled = 0;
+            for (x, y) in chunk.iter().zip(&mut rhs) {
+                a[filled] = word(x);
+                b[filled] = word(y);
+                filled += 1;
+            }
+            if filled < SIMD_LANES {
+                // `rhs` ran out, finish the overlap and leave the rest untouched
+                chunk[..filled]
+                    .iter_mut()
+                    .zip(a.iter().zip(&b))
+                    .for_each(|(x, (a, b))| *x = Uint::from_u64(scalar(*a, *b)));
+                return;
+            }
+            let out = lanes(a.into(), b.into()).to_array();
+            chunk
+                .iter_mut()
+                .zip(out)
+                .for_each(|(x, o)| *x = Uint::from_u64(o));
+        }
+        (chunks.into_remainder(), rhs)
+    };
+    dispatch(|| {
+        values
+            .iter_mut()
+            .zip(rhs)
+            .for_each(|(x, y)| *x = Uint::from_u64(scalar(word(x), word(y))))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type VecMod256 = VecMod<{ U256::LIMBS }, { U512::LIMBS }>;
+
+    #[test]
+    fn montgomery_products_match_mul_mod() {
+        // 2^255 - 19 spans four limbs, so none of this takes the Barrett path
+        let modulus = Odd::new(U256::MAX.shr_vartime(1).wrapping_sub(&U256::from_u8(18)))
+            .expect("odd modulus");
+        let mut rng = rand::rng();
+        let a = VecMod256::random(&mut rng, 64, modulus);
+        let b = VecMod256::random(&mut rng, 64, modulus);
+        let expected = a
+            .iter()
+            .zip(b.iter())
+            .map(|(a, b)| a.mul_mod(b, modulus.as_nz_ref()))
+            .collect::<Vec<_>>();
+
+        assert_eq!((&a * &b).values, expected);
+
+        let mut c = a.clone();
+        c.mul_assign_iter(b.iter());
+        assert_eq!(c.values, expected);
+
+        let monty = b.to_montgomery();
+        let mut c = a.clone();
+        c *= &monty;
+        assert_eq!(c.values, expected);
+        assert_eq!(monty.retrieve::<{ U512::LIMBS }>(), b);
+    }
+
+    #[test]
+    fn iterator_ops_reduce_their_items() {
+        let q = U64::from_u64((1 << 61) - 1);
+        let modulus = Odd::new(q).expect("odd modulus");
+        let mut rng = rand::rng();
+        let a = VecModStd::random(&mut rng, 16, modulus);
+        let b = VecModStd::random(&mut rng, 16, modulus);
+        // The residues of `b` offset by q or 3q, still below 2^64
+        let unreduced = b
+            .iter()
+            .enumerate()
+            .map(|(i, x)| x.wrapping_add(&q.wrapping_mul(&U64::from_u64(1 + 2 * (i as u64 % 2)))))
+            .collect::<Vec<_>>();
+        assert!(unreduced.iter().all(|x| x >= &q));
+
+        let mut c = a.clone();
+        c.add_assign_iter(&unreduced);
+        assert_eq!(c, &a + &b);
+        let mut c = a.clone();
+        c.sub_assign_iter(&unreduced);
+        assert_eq!(c, &a - &b);
+        let mut c = a.clone();
+        c.mul_assign_iter(&unreduced);
+        assert_eq!(c, &a * &b);
+    }
+
+    #[test]
+    #[should_panic(expected = "rhs to have as many values as self")]
+    fn iterator_ops_reject_a_short_rhs() {
+        let modulus = Odd::new(U64::from_u64(97)).expect("odd modulus");
+        let mut a = VecModStd::from_signed(&[1, 2, 3], modulus);
+        a.add_assign_iter([U64::ONE, U64::ONE]);
+    }
+
+    #[test]
+    #[should_panic(expected = "rhs to have as many values as self")]
+    fn iterator_ops_reject_a_long_rhs() {
+        let modulus = Odd::new(U64::from_u64(97)).expect("odd modulus");
+        let mut a = VecModStd::from_signed(&[1, 2], modulus);
+        a.sub_assign_iter([U64::ONE, U64::ONE, U64::ONE]);
+    }
+
+    /// Elementwise ops of single word vectors mod `q` against crypto-bigint
+    fn check_single_word(q: u64) {
+        let modulus = Odd::new(U64::from_u64(q)).expect("odd modulus");
+        let m = modulus.as_nz_ref();
+        let mut rng = rand::rng();
+        let mut a = VecModStd::random(&mut rng, 67, modulus);
+        let mut b = VecModStd::random(&mut rng, 67, modulus);
+        // The extremes of the range in the first slots
+        a[0] = U64::ZERO;
+        a[1] = modulus.get().wrapping_sub(&U64::ONE);
+        b[1] = modulus.get().wrapping_sub(&U64::ONE);
+        let scalar = modulus.get().wrapping_sub(&U64::ONE);
+        let zipped = |f: fn(&U64, &U64, &NonZero<U64>) -> U64| {
+            a.iter()
+                .zip(b.iter())
+                .map(|(x, y)| f(x, y, m))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!((&a * &b).values, zipped(|x, y, m| x.mul_mod(y, m)), "{q}");
+        assert_eq!((&a + &b).values, zipped(|x, y, m| x.add_mod(y, m)), "{q}");
+        assert_eq!((&a - &b).values, zipped(|x, y, m| x.sub_mod(y, m)), "{q}");
+        let scaled = a.iter().map(|x| x.mul_mod(&scalar, m)).collect::<Vec<_>>();
+        assert_eq!((&a * scalar).values, scaled, "{q}");
+    }
+
+    #[test]
+    fn barrett_fast_path_matches_mul_mod() {
+        assert!(
+            VecModStd::from_signed(&[1], Odd::new(U64::from_u64(3)).expect("odd"))
+                .barrett()
+                .is_some()
+        );
+        // 2 bits, a 61 bit prime and the largest odd 62 bit modulus
+        for q in [3, (1 << 61) - 1, (1 << 62) - 1] {
+            check_single_word(q);
+        }
+    }
+
+    #[test]
+    fn wider_single_word_moduli_fall_back() {
+        for q in [(1 << 62) + 1, (1 << 63) - 25, u64::MAX] {
+            let modulus = Odd::new(U64::from_u64(q)).expect("odd modulus");
+            assert!(VecModStd::from_signed(&[1], modulus).barrett().is_none());
+            check_single_word(q);
+        }
+    }
+
+    #[test]
+    fn zip_words_matches_scalar_on_partial_chunks() {
+        let q = (1u64 << 61) - 1;
+        let b = Barrett64::new(q).expect("a 61 bit modulus");
+        let modulus = Odd::new(U64::from_u64(q)).expect("odd modulus");
+        let mut rng = rand::rng();
+        for len in [0usize, 1, 7, 8, 9, 15, 16, 17, 23, 67] {
+            // A shorter right hand side runs out inside a chunk
+            for rhs_len in [len, len.saturating_sub(3)] {
+                let a = VecModStd::random(&mut rng, len, modulus);
+                let rhs = VecModStd::random(&mut rng, rhs_len, modulus);
+                let scalar = |f: fn(&Barrett64, u64, u64) -> u64| {
+                    a.iter()
+                        .enumerate()
+                        .map(|(i, x)| match rhs.values.get(i) {
+                            Some(y) => U64::from_u64(f(&b, word(x), word(y))),
+                            None => *x,
+                        })
+                        .collect::<Vec<_>>()
+                };
+
+                let mut sum = a.values.clone();
+                zip_words(
+                    &mut sum,
+                    rhs.iter(),
+                    #[cfg(feature = "simd")]
+                    |x, y| b.add_mod_simd(x, y),
+                    |x, y| b.add_mod(x, y),
+                );
+                assert_eq!(sum, scalar(Barrett64::add_mod), "{len} + {rhs_len}");
+
+                let mut difference = a.values.clone();
+                zip_words(
+                    &mut difference,
+                    rhs.iter(),
+                    #[cfg(feature = "simd")]
+                    |x, y| b.sub_mod_simd(x, y),
+                    |x, y| b.sub_mod(x, y),
+                );
+                assert_eq!(difference, scalar(Barrett64::sub_mod), "{len} - {rhs_len}");
+            }
+        }
+    }
+}
@@ -7,10 +7,11 @@ use subtle::Choice;
 
 /// Compute phi of `n` which is the number of integers `m` coprime to `n` such that `1 <= m < n`
 pub fn get_totient(n: usize) -> usize {
-    let factors = prime_factorization::Factorization::<u64>::run(n as u64);
+    let mut factors = prime_factorization::Factorization::<u64>::run(n as u64).factors;
+    factors.dedup();
     let mut prime_prod = 1;
     let mut numerator = 1;
-    for &r in &factors.factors {
+    for &r in &factors {
         numerator *= r - 1;
         prime_prod *= r;
     }
@@ -28,7 +29,7 @@ pub fn root_of_unity(order: usize, modulus: Odd<U64>) -> U64 {
     if !factors.is_prime {
         panic!("`modulus` must be a prime number");
     }
-    if (modu - 1) % order as u64 == 0 {
+    if !(modu - 1).is_multiple_of(order as u64) {
         panic!(
             "Please provide a prime modulus(q) and a cyclotomic number(m) satisfying the condition (q-1)/m is an integer. prime modulus({}) and modulus({}) do not satisfy this condition",
             modu, order
@@ -68,6 +69,56 @@ pub fn root_of_unity(order: usize, modulus: Odd<U64>) -> U64 {
     min_ru.retrieve()
 }
 
+/// The coefficients of the `m`-th cyclotomic polynomial, lowest degree first.
+///
+/// Computed as the product of `(x^d - 1)^mu(m / d)` over the divisors `d` of
+/// `m`, multiplying before dividing so every division is exact.
+pub fn cyclotomic_polynomial(m: usize) -> Vec<i64> {
+    let divisors = (1..=m).filter(|d| m.is_multiple_of(*d));
+    let (mut numerator, mut denominator) = (Vec::new(), Vec::new());
+    for d in divisors {
+        match mobius(m / d) {
+            1 => numerator.push(d),
+            -1 => denominator.push(d),
+            _ => {}
+        }
+    }
+    let mut phi = vec![1i64];
+    for d in numerator {
+        // multiply by x^d - 1
+        let mut product = vec![0; phi.len() + d];
+        for (i, &c) in phi.iter().enumerate() {
+            product[i + d] += c;
+            product[i] -= c;
+        }
+        phi = product;
+    }
+    for d in denominator {
+        // divide by x^d - 1, the quotient q satisfies q_i = q_{i + d} - p_{i + d}
+        let degree = phi.len() - 1 - d;
+        let mut quotient = vec![0; degree + 1];
+        for i in (0..=degree).rev() {
+            quotient[i] = phi[i + d] + quotient.get(i + d).copied().unwrap_or(0);
+        }
+        phi = quotient;
+    }
+    phi
+}
+
+/// The Möbius function
+fn mobius(n: usize) -> i32 {
+    let factors = prime_factorization::Factorization::<u64>::run(n as u64).factors;
+    let mut distinct = factors.clone();
+    distinct.dedup();
+    if distinct.len() != factors.len() {
+        0
+    } else if factors.len().is_multiple_of(2) {
+        1
+    } else {
+        -1
+    }
+}
+
 pub fn get_coprimes(n: usize) -> Vec<usize> {
     let mut coprimes = Vec::new();
     let mut i = 1;
@@ -90,16 +141,32 @@ pub fn is_generator(generator: U64, modulus: Odd<U64>) -> bool {
     let generator = MontyForm::<{ U64::LIMBS }>::new(&generator, params);
 
     let prime_factors = prime_factorization::Factorization::run(qm1_u64);
-    let mut cnt = 0;
-    for r in &prime_factors.factors {
+    prime_factors.factors.iter().all(|r| {
         let r = NonZero::<U64>::new_unwrap(U64::from_u64(*r));
         let exponent = qm1 / r;
-        cnt += 1;
-        if generator.pow(&exponent) == one {
-            break;
-        }
+        generator.pow(&exponent) != one
+    })
+}
+
+/// Check that `root` is a primitive root of unity of the power of two
+/// `order` modulo `modulus`, i.e. `root^(order / 2) = -1`
+pub fn is_primitive_root_of_unity(root: U64, order: usize, modulus: Odd<U64>) -> bool {
+    if order < 2 || !order.is_power_of_two() || root >= modulus.get() {
+        return false;
     }
-    cnt == prime_factors.factors.len()
+    let params = MontyForm::<{ U64::LIMBS }>::new_params_vartime(modulus);
+    let root = MontyForm::<{ U64::LIMBS }>::new(&root, params);
+    let minus_one = -MontyForm::<{ U64::LIMBS }>::one(params);
+    root.pow(&U64::from_u64(order as u64 / 2)) == minus_one
+}
+
+/// Check that `modulus` is a prime supporting a negacyclic NTT for the
+/// power of two `cyclotomic_order`, i.e. `modulus = 1 mod cyclotomic_order`
+pub fn is_ntt_friendly(modulus: u64, cyclotomic_order: usize) -> bool {
+    cyclotomic_order.is_power_of_two()
+        && modulus > 2
+        && (modulus - 1).is_multiple_of(cyclotomic_order as u64)
+        && prime_factorization::Factorization::<u64>::run(modulus).is_prime
 }
 
 /// Find a generator for a given prime modulus
@@ -126,14 +193,33 @@ pub fn next_prime(starting_number: U64, cyclotomic_order: usize) -> U64 {
     n
 }
 
-pub fn previous_prime(starting_number: U64, cyclotomic_order: usize) -> U64 {
-    let cyclotomic_order = U64::from_u64(cyclotomic_order as u64);
+/// The largest prime below `2^bits` with `q = 1 mod cyclotomic_order`,
+/// `None` if there is no such prime
+pub fn first_prime(bits: usize, cyclotomic_order: usize) -> Option<U64> {
+    let order = cyclotomic_order as u64;
+    let bound = 1u64.checked_shl(bits as u32)?;
+    let start = (bound - 1) / order * order + 1;
+    let start = if start >= bound {
+        start.checked_sub(order)?
+    } else {
+        start
+    };
+    prime_at_or_below(start, order)
+}
+
+/// The next prime below `starting_number` in the same residue class modulo
+/// `cyclotomic_order`, `None` if there is no such prime
+pub fn previous_prime(starting_number: U64, cyclotomic_order: usize) -> Option<U64> {
+    let order = cyclotomic_order as u64;
+    prime_at_or_below(starting_number.to_primitive().checked_sub(order)?, order)
+}
+
+fn prime_at_or_below(mut n: u64, step: u64) -> Option<U64> {
     let mut rng = rand_chacha::ChaCha8Rng::from_os_rng();
-    let mut n = starting_number - cyclotomic_order;
-    while !crypto_primes::is_prime_with_rng(&mut rng, &n) {
-        n -= cyclotomic_order;
+    while !crypto_primes::is_prime_with_rng(&mut rng, &U64::from_u64(n)) {
+        n = n.checked_sub(step).filter(|n| *n > 1)?;
     }
-    n
+    Some(U64::from_u64(n))
 }
 
 pub fn reverse_bits(n: usize, bits: usize) -> usize {
@@ -147,18 +233,7 @@ pub fn reverse_bits(n: usize, bits: usize) -> usize {
 }
 
 pub fn find_in_vector(values: &[f64], search: f64) -> isize {
-    let mut low = 0;
-    let mut high = values.len() - 1;
-
-    while low <= high {
-        let mid = low + (high - low) / 2;
-        if values[mid] < search {
-            low = mid + 1;
-        } else {
-            high = mid;
-        }
-    }
-
+    let low = values.partition_point(|v| *v < search);
     if low < values.len() {
         return (low + 1) as isize;
     }
@@ -0,0 +1,175 @@
+//! Differential checks between the single-CRT and RNS polynomial paths
+//!
+//! Every operation is applied to a [`Poly`] over one large prime and to a
+//! [`DcrtPoly`] over a chain of smaller towers. While the exact integer results
+//! stay below half of both moduli the centered coefficients must be identical,
+//! so any difference points at a bug in the RNS code.
+
+use crate::constants::MAX_MODULUS_SIZE;
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::core::lattice::params::{DcrtElementParams, DcrtElementParamsBuilder, ElementParams};
+use crate::core::lattice::poly::Poly;
+use crate::error::Result;
+use crypto_bigint::U64;
+use num::BigInt;
+
+/// The parameters for both sides of a differential check
+#[derive(Debug, Clone)]
+pub struct DifferentialHarness {
+    single: ElementParams,
+    rns: DcrtElementParams,
+}
+
+impl DifferentialHarness {
+    /// Compare a [`MAX_MODULUS_SIZE`] bit prime against `towers` towers of `bits` bits
+    pub fn new(cyclotomic_order: usize, towers: usize, bits: usize) -> Result<Self> {
+        let single = DcrtElementParamsBuilder::new(cyclotomic_order)
+            .depth(1)
+            .bits(MAX_MODULUS_SIZE)
+            .build()?[0];
+        let rns = DcrtElementParamsBuilder::new(cyclotomic_order)
+            .depth(towers)
+            .bits(bits)
+            .build()?;
+        Ok(Self { single, rns })
+    }
+
+    /// Encode small signed coefficients on both sides
+    pub fn encode(&self, values: &[i64]) -> DifferentialPoly {
+        DifferentialPoly {
+            single: Poly::from_signed(self.single, values),
+            rns: DcrtPoly::from_signed(self.rns.clone(), values),
+        }
+    }
+}
+
+/// A polynomial kept in both representations
+#[derive(Debug, Clone)]
+pub struct DifferentialPoly {
+    single: Poly,
+    rns: DcrtPoly,
+}
+
+impl DifferentialPoly {
+    /// Add `rhs` on both sides
+    pub fn add(&mut self, rhs: &Self) -> &mut Self {
+        self.single += &rhs.single;
+        self.rns += &rhs.rns;
+        self
+    }
+
+    /// Subtract `rhs` on both sides
+    pub fn sub(&mut self, rhs: &Self) -> &mut Self {
+        self.single -= &rhs.single;
+        self.rns -= &rhs.rns;
+        self
+    }
+
+    /// Multiply by `rhs` on both sides
+    pub fn mul(&mut self, rhs: &Self) -> &mut Self {
+        self.single *= &rhs.single;
+        self.rns *= &rhs.rns;
+        self
+    }
+
+    /// Multiply by a scalar on both sides
+    pub fn scale(&mut self, scalar: u64) -> &mut Self {
+        let scalar = U64::from_u64(scalar);
+        self.single *= &scalar;
+        self.rns *= &scalar;
+        self
+    }
+
+    /// Negate both sides
+    pub fn neg(&mut self) -> &mut Self {
+        self.single = -&self.single;
+        self.rns = -&self.rns;
+        self
+    }
+
+    /// The first coefficient where the two sides disagree, with both values
+    pub fn mismatch(&self) -> Option<(usize, BigInt, BigInt)> {
+        self.single
+            .to_signed()
+            .into_iter()
+            .map(BigInt::from)
+            .zip(self.rns.to_signed())
+            .enumerate()
+            .find(|(_, (single, rns))| single != rns)
+            .map(|(i, (single, rns))| (i, single, rns))
+    }
+
+    /// Panic if the two sides disagree
+    pub fn assert_agree(&self) {
+        if let Some((i, single, rns)) = self.mismatch() {
+            panic!("coefficient {i} differs: single-CRT {single}, RNS {rns}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    const DELTA: i64 = 1 << 20;
+
+    fn ternary(rng: &mut impl Rng, n: usize) -> Vec<i64> {
+        (0..n).map(|_| rng.random_range(-1..=1)).collect()
+    }
+
+    /// A toy RLWE encryption `(Δ m + e - a s, a)` with a small `a`, so every
+    /// intermediate stays far below both moduli
+    fn encrypt(
+        harness: &DifferentialHarness,
+        secret: &DifferentialPoly,
+        message: &[i64],
+        rng: &mut impl Rng,
+    ) -> [DifferentialPoly; 2] {
+        let n = message.len();
+        let a = harness.encode(&ternary(rng, n));
+        let mut b = harness.encode(&message.iter().map(|m| m * DELTA).collect::<Vec<_>>());
+        b.add(&harness.encode(&ternary(rng, n)));
+        let mut mask = a.clone();
+        b.sub(mask.mul(secret));
+        [b, a]
+    }
+
+    fn decrypt(secret: &DifferentialPoly, [b, a]: &[DifferentialPoly; 2]) -> Vec<i64> {
+        let mut noisy = a.clone();
+        noisy.mul(secret).add(b);
+        noisy.assert_agree();
+        noisy
+            .single
+            .to_signed()
+            .into_iter()
+            .map(|x| (x as f64 / DELTA as f64).round() as i64)
+            .collect()
+    }
+
+    #[test]
+    fn encrypt_evaluate_decrypt() {
+        let order = 16;
+        let harness = DifferentialHarness::new(order, 3, 30).expect("parameters");
+        let mut rng = rand::rng();
+        let secret = harness.encode(&ternary(&mut rng, order));
+        let x = (0..order)
+            .map(|_| rng.random_range(-4..=4))
+            .collect::<Vec<_>>();
+        let y = (0..order)
+            .map(|_| rng.random_range(-4..=4))
+            .collect::<Vec<_>>();
+        let [mut b, mut a] = encrypt(&harness, &secret, &x, &mut rng);
+        let [yb, ya] = encrypt(&harness, &secret, &y, &mut rng);
+
+        // 3 (x - y) + y, negated
+        b.sub(&yb).scale(3).add(&yb).neg();
+        a.sub(&ya).scale(3).add(&ya).neg();
+        let expected = x
+            .iter()
+            .zip(&y)
+            .map(|(x, y)| -(3 * (x - y) + y))
+            .collect::<Vec<_>>();
+        assert_eq!(decrypt(&secret, &[b, a]), expected);
+    }
+}
@@ -10,18 +10,7 @@ use serde::{Deserialize, Serialize};
 
 /// Parameters for encoding
 #[derive(
-    Copy,
-    Clone,
-    Debug,
-    Default,
-    PartialEq,
-    Eq,
-    Ord,
-    PartialOrd,
-    Hash,
-    Display,
-    Deserialize,
-    Serialize,
+    Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Display, Deserialize, Serialize,
 )]
 #[display(
     "Params {{ batch_size: {}, plaintext_generator: {}, plaintext_modulus: {}, plaintext_root_of_unity: {}, plaintext_big_modulus: {}, plaintext_big_root_of_unity: {} }}",
@@ -47,6 +36,21 @@ pub struct EncodingParams {
     pub plaintext_big_root_of_unity: U64,
 }
 
+impl Default for EncodingParams {
+    /// Unset moduli are one, which unlike zero deserializes as an odd modulus
+    fn default() -> Self {
+        let one = Odd::new(U64::ONE).expect("One is odd");
+        Self {
+            batch_size: 0,
+            plaintext_generator: U64::ZERO,
+            plaintext_modulus: one,
+            plaintext_root_of_unity: U64::ZERO,
+            plaintext_big_modulus: one,
+            plaintext_big_root_of_unity: U64::ZERO,
+        }
+    }
+}
+
 /// Plaintext Polynomial Type
 #[derive(
     Copy, Clone, Debug, Default, PartialEq, Eq, Ord, PartialOrd, Hash, Display, TryFrom, FromStr,
@@ -1,9 +1,24 @@
 use crate::ActingPrimitive;
+use crate::arena::ArenaLease;
+use crate::constants::PlaintextEncodingsType;
+use crate::core::lattice::params::ElementParams;
 use crate::core::lattice::poly::Poly;
+use crate::core::math::{fft_special, fft_special_inv};
+use crate::core::utils::is_ntt_friendly;
 use crate::encoding::EncodingParams;
+use crate::error::{Error, Result};
+use crypto_bigint::U64;
 use derive_more::Display;
+use num::complex::Complex;
+use num::{BigInt, Integer, ToPrimitive};
 use serde::{Deserialize, Serialize};
 
+/// The modulus CKKS plaintext coefficients are stored under, `2^63 - 1`
+const CKKS_PLAINTEXT_MODULUS: u64 = (1 << 63) - 1;
+
+/// The bound on the scaled coefficients of an encoded CKKS plaintext
+const CKKS_MAX_COEFFICIENT: f64 = (1u64 << 62) as f64;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum PlaintextEncodings {
     Packed(PackedPlaintext),
@@ -22,6 +37,37 @@ impl PlaintextEncodings {
         }
     }
 
+    /// The encoding of the plaintext
+    pub fn encoding_type(&self) -> PlaintextEncodingsType {
+        match self {
+            PlaintextEncodings::Packed(_) => PlaintextEncodingsType::Packed,
+            PlaintextEncodings::Coefficient(_) => PlaintextEncodingsType::CoefficientPacked,
+            PlaintextEncodings::String(_) => PlaintextEncodingsType::String,
+            PlaintextEncodings::Ckks(_) => PlaintextEncodingsType::CkksPacked,
+        }
+    }
+
+    /// Hold `lease` until the plaintext is dropped
+    pub(crate) fn with_lease(mut self, lease: ArenaLease) -> Self {
+        match &mut self {
+            PlaintextEncodings::Packed(p) => p.lease = lease,
+            PlaintextEncodings::Coefficient(p) => p.lease = lease,
+            PlaintextEncodings::String(p) => p.lease = lease,
+            PlaintextEncodings::Ckks(p) => p.lease = lease,
+        }
+        self
+    }
+
+    /// The encoded polynomial, modulo the plaintext modulus except for CKKS
+    pub(crate) fn encoded_value(&self) -> &Poly {
+        match self {
+            PlaintextEncodings::Packed(p) => &p.encoded_value,
+            PlaintextEncodings::Coefficient(p) => &p.encoded_value,
+            PlaintextEncodings::String(p) => &p.encoded_value,
+            PlaintextEncodings::Ckks(p) => &p.encoded_value,
+        }
+    }
+
     pub fn lower_bound(&self) -> isize {
         let modulus = self
             .params()
@@ -71,29 +117,381 @@ impl Default for PlaintextParams {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Clones of plaintexts hold no slot in the arena of a capped context
+macro_rules! untracked_clone {
+    ($($plaintext:ident { $($field:ident),* }),*) => {
+        $(impl Clone for $plaintext {
+            fn clone(&self) -> Self {
+                Self {
+                    $($field: self.$field.clone(),)*
+                    lease: ArenaLease::default(),
+                }
+            }
+        })*
+    };
+}
+
+untracked_clone!(
+    PackedPlaintext {
+        value,
+        encoded_value,
+        encoding_params
+    },
+    CoefficientPlaintext {
+        value,
+        encoded_value,
+        encoding_params
+    },
+    StringPlaintext {
+        value,
+        encoded_value,
+        encoding_params
+    },
+    CkksPlaintext {
+        value,
+        encoded_value,
+        encoding_params,
+        log_error
+    }
+);
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct PackedPlaintext {
     value: Vec<u64>,
     encoded_value: Poly,
     encoding_params: PlaintextParams,
+    #[serde(skip)]
+    lease: ArenaLease,
 }
-#[derive(Debug, Clone, Deserialize, Serialize)]
+impl PackedPlaintext {
+    /// Encode `values` into the slots of a polynomial of the
+    /// `cyclotomic_order`-th ring modulo the plaintext modulus `t` of
+    /// `encoding_params`, a prime with `t = 1 mod cyclotomic_order`.
+    ///
+    /// Slot `i < n / 2` holds the evaluation at `zeta^(5^i)` and slot
+    /// `n / 2 + i` the one at `zeta^(-5^i)` for a primitive
+    /// `cyclotomic_order`-th root of unity `zeta` modulo `t`, so the
+    /// automorphism `X -> X^(5^k)` rotates both rows of `n / 2` slots left by
+    /// `k`. Unset slots are zero.
+    pub fn new(
+        values: &[i64],
+        encoding_params: PlaintextParams,
+        cyclotomic_order: usize,
+    ) -> Result<Self> {
+        let params = Self::ring(&encoding_params, cyclotomic_order)?;
+        if values.len() > params.ring_dimension {
+            return Err(Error::CcParams(format!(
+                "cannot pack {} values in ring dimension {}",
+                values.len(),
+                params.ring_dimension
+            )));
+        }
+        let t = params.ciphertext_modulus.get().to_primitive();
+        let mut evaluations = vec![0; params.ring_dimension];
+        for (&v, index) in values.iter().zip(Self::slot_indices(cyclotomic_order)) {
+            evaluations[index] = v.rem_euclid(t as i64) as u64;
+        }
+        let mut encoded_value = Poly::zero(params);
+        encoded_value.set_values(&evaluations);
+        encoded_value.switch_format();
+        let mut value = values
+            .iter()
+            .map(|v| v.rem_euclid(t as i64) as u64)
+            .collect::<Vec<_>>();
+        value.resize(params.ring_dimension, 0);
+        Ok(Self {
+            value,
+            encoded_value,
+            encoding_params,
+            lease: ArenaLease::default(),
+        })
+    }
+
+    /// Read the slots of the polynomial with `coefficients` modulo `t`, see
+    /// [`PackedPlaintext::new`]
+    pub fn decode(
+        coefficients: &[i64],
+        encoding_params: PlaintextParams,
+        cyclotomic_order: usize,
+    ) -> Result<Self> {
+        let params = Self::ring(&encoding_params, cyclotomic_order)?;
+        if coefficients.len() > params.ring_dimension {
+            return Err(Error::CcParams(format!(
+                "cannot decode {} coefficients in ring dimension {}",
+                coefficients.len(),
+                params.ring_dimension
+            )));
+        }
+        let mut encoded_value = Poly::from_signed(params, coefficients);
+        encoded_value.switch_format();
+        let evaluations = encoded_value.values();
+        let value = Self::slot_indices(cyclotomic_order)
+            .map(|index| evaluations[index].to_primitive())
+            .collect();
+        encoded_value.switch_format();
+        Ok(Self {
+            value,
+            encoded_value,
+            encoding_params,
+            lease: ArenaLease::default(),
+        })
+    }
+
+    /// The slots centered around zero
+    pub fn values(&self) -> Vec<i64> {
+        let t = self.encoded_value.modulus().get().to_primitive();
+        self.value
+            .iter()
+            .map(|&v| {
+                if v > t / 2 {
+                    v as i64 - t as i64
+                } else {
+                    v as i64
+                }
+            })
+            .collect()
+    }
+
+    /// The ring modulo `t` whose evaluation format holds the slots
+    fn ring(encoding_params: &PlaintextParams, cyclotomic_order: usize) -> Result<ElementParams> {
+        let t = encoding_params.encoding_params.plaintext_modulus;
+        if cyclotomic_order < 4 || !is_ntt_friendly(t.get().to_primitive(), cyclotomic_order) {
+            return Err(Error::CcParams(format!(
+                "cannot pack slots modulo {} in the {cyclotomic_order}-th cyclotomic ring",
+                t.get().to_primitive()
+            )));
+        }
+        Ok(ElementParams::with_modulus(cyclotomic_order, t))
+    }
+
+    /// The index in evaluation format of every slot, the evaluation at
+    /// `zeta^(2 j + 1)` being at index `j`
+    fn slot_indices(cyclotomic_order: usize) -> impl Iterator<Item = usize> {
+        let m = cyclotomic_order;
+        let rows = std::iter::successors(Some(1), move |e| Some(e * 5 % m)).take(m / 4);
+        rows.clone()
+            .map(|e| (e - 1) / 2)
+            .chain(rows.map(move |e| (m - e - 1) / 2))
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct CoefficientPlaintext {
     value: Vec<u64>,
     encoded_value: Poly,
     encoding_params: PlaintextParams,
+    #[serde(skip)]
+    lease: ArenaLease,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+impl CoefficientPlaintext {
+    /// Encode `values` as the coefficients of a polynomial of the
+    /// `cyclotomic_order`-th ring modulo the plaintext modulus of `encoding_params`
+    pub fn new(
+        values: &[i64],
+        encoding_params: PlaintextParams,
+        cyclotomic_order: usize,
+    ) -> Result<Self> {
+        let t = encoding_params.encoding_params.plaintext_modulus;
+        let params = ElementParams::with_ciphertext_root_of_unity(cyclotomic_order, t, U64::ZERO);
+        if values.len() > params.ring_dimension || t.get() <= U64::ONE {
+            return Err(Error::CcParams(format!(
+                "cannot encode {} coefficients modulo {} in ring dimension {}",
+                values.len(),
+                t.get().to_primitive(),
+                params.ring_dimension
+            )));
+        }
+        let encoded_value = Poly::from_signed(params, values);
+        Ok(Self {
+            value: encoded_value
+                .values()
+                .iter()
+                .map(|v| v.to_primitive())
+                .collect(),
+            encoded_value,
+            encoding_params,
+            lease: ArenaLease::default(),
+        })
+    }
+
+    /// The coefficients centered around zero
+    pub fn coefficients(&self) -> Vec<i64> {
+        self.encoded_value.to_signed()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct StringPlaintext {
     value: String,
     encoded_value: Poly,
     encoding_params: PlaintextParams,
+    #[serde(skip)]
+    lease: ArenaLease,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// A vector of real numbers packed into the slots of the canonical
+/// embedding and scaled by the scaling factor of its parameters
+#[derive(Debug, Deserialize, Serialize)]
 pub struct CkksPlaintext {
     value: Vec<f64>,
     encoded_value: Poly,
     encoding_params: PlaintextParams,
+    #[serde(default)]
+    log_error: Option<f64>,
+    #[serde(skip)]
+    lease: ArenaLease,
+}
+
+impl CkksPlaintext {
+    /// Encode `values` into the slots of a polynomial of the
+    /// `cyclotomic_order`-th ring, rounding its coefficients after scaling
+    /// them by `encoding_params.scaling_factor`.
+    ///
+    /// `encoding_params.slots` is a power of two of at most a quarter of
+    /// the cyclotomic order, zero for exactly that many. Fewer slots repeat
+    /// the values so the polynomial lives in a subring.
+    pub fn new(
+        values: &[f64],
+        encoding_params: PlaintextParams,
+        cyclotomic_order: usize,
+    ) -> Result<Self> {
+        let slot_values = values
+            .iter()
+            .map(|&v| Complex::new(v, 0.0))
+            .collect::<Vec<_>>();
+        Self::from_complex(&slot_values, encoding_params, cyclotomic_order)
+    }
+
+    /// Encode complex `values` like [`CkksPlaintext::new`], keeping their
+    /// real parts as the values
+    pub(crate) fn from_complex(
+        values: &[Complex<f64>],
+        encoding_params: PlaintextParams,
+        cyclotomic_order: usize,
+    ) -> Result<Self> {
+        let slots = Self::slots(&encoding_params, cyclotomic_order)?;
+        if values.len() > slots || values.iter().any(|v| !v.is_finite()) {
+            return Err(Error::CcParams(format!(
+                "cannot encode {} finite values into {slots} slots",
+                values.len()
+            )));
+        }
+        let mut slot_values = values.to_vec();
+        slot_values.resize(slots, Complex::ZERO);
+        fft_special_inv(&mut slot_values, cyclotomic_order)?;
+
+        let half = cyclotomic_order / 4;
+        let gap = half / slots;
+        let scale = encoding_params.scaling_factor;
+        let mut coefficients = vec![0; 2 * half];
+        for (i, v) in slot_values.iter().enumerate() {
+            for (index, part) in [(i * gap, v.re), (i * gap + half, v.im)] {
+                let scaled = (part * scale).round();
+                if scaled.abs() >= CKKS_MAX_COEFFICIENT {
+                    return Err(Error::CcParams(format!(
+                        "values scaled by {scale} overflow the plaintext coefficients"
+                    )));
+                }
+                coefficients[index] = scaled as i64;
+            }
+        }
+        Ok(Self {
+            value: values.iter().map(|v| v.re).collect(),
+            encoded_value: Poly::from_signed(Self::ring(cyclotomic_order), &coefficients),
+            encoding_params,
+            log_error: None,
+            lease: ArenaLease::default(),
+        })
+    }
+
+    /// Decode the decrypted centered `coefficients` of a ciphertext scaled
+    /// by `encoding_params.scaling_factor`, see [`CkksPlaintext::new`].
+    pub(crate) fn decode(
+        coefficients: &[BigInt],
+        encoding_params: PlaintextParams,
+        cyclotomic_order: usize,
+    ) -> Result<Self> {
+        let slots = Self::slots(&encoding_params, cyclotomic_order)?;
+        let n = cyclotomic_order / 2;
+        if coefficients.len() != n {
+            return Err(Error::DecryptionFailure);
+        }
+        let scale = encoding_params.scaling_factor;
+        let scaled = coefficients
+            .iter()
+            .map(|c| c.to_f64().map(|c| c / scale))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::DecryptionFailure)?;
+
+        let half = n / 2;
+        let gap = half / slots;
+        let mut slot_values = (0..slots)
+            .map(|i| Complex::new(scaled[i * gap], scaled[i * gap + half]))
+            .collect::<Vec<_>>();
+        fft_special(&mut slot_values, cyclotomic_order)?;
+
+        let modulus = BigInt::from(CKKS_PLAINTEXT_MODULUS);
+        let residues = coefficients
+            .iter()
+            .map(|c| {
+                c.mod_floor(&modulus)
+                    .to_u64()
+                    .expect("a residue is below a 64 bit modulus")
+            })
+            .collect::<Vec<_>>();
+        let mut encoded_value = Poly::from_signed(Self::ring(cyclotomic_order), &vec![0; n]);
+        encoded_value.set_values(&residues);
+        // Real messages leave only noise in the imaginary parts, the sum of
+        // `slots` coefficient pairs each
+        let variance =
+            slot_values.iter().map(|v| v.im * v.im).sum::<f64>() / slots as f64 / slots as f64;
+        let bound = (12.0 * variance).sqrt() * scale;
+        Ok(Self {
+            value: slot_values.iter().map(|v| v.re).collect(),
+            encoded_value,
+            encoding_params,
+            log_error: Some(bound.log2().max(0.0)),
+            lease: ArenaLease::default(),
+        })
+    }
+
+    /// The encoded or decoded values, one per slot when decoded
+    pub fn values(&self) -> &[f64] {
+        &self.value
+    }
+
+    /// The bits of the noise in the coefficients of a decoded plaintext,
+    /// `log2(sqrt(12) sigma)` for the standard deviation `sigma` the
+    /// imaginary parts of the slots imply, as OpenFHE estimates it. Only
+    /// meaningful for real messages, `None` for encoded plaintexts.
+    pub fn log_error(&self) -> Option<f64> {
+        self.log_error
+    }
+
+    fn slots(encoding_params: &PlaintextParams, cyclotomic_order: usize) -> Result<usize> {
+        let max = cyclotomic_order / 4;
+        let slots = match encoding_params.slots {
+            0 => max,
+            slots => slots,
+        };
+        if !cyclotomic_order.is_power_of_two()
+            || max == 0
+            || !slots.is_power_of_two()
+            || slots > max
+        {
+            return Err(Error::CcParams(format!(
+                "cannot pack {slots} slots in the {cyclotomic_order}-th cyclotomic ring"
+            )));
+        }
+        Ok(slots)
+    }
+
+    /// The coefficients of CKKS plaintexts are stored modulo
+    /// [`CKKS_PLAINTEXT_MODULUS`]
+    fn ring(cyclotomic_order: usize) -> ElementParams {
+        let modulus = Option::from(U64::from_u64(CKKS_PLAINTEXT_MODULUS).to_odd())
+            .expect("the modulus is odd");
+        ElementParams::with_ciphertext_root_of_unity(cyclotomic_order, modulus, U64::ZERO)
+    }
 }
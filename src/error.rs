@@ -1,5 +1,6 @@
 //! Error handling by this library
 
+use crate::arena::ArenaKind;
 use thiserror::Error;
 
 /// Error type for the library
@@ -14,6 +15,89 @@ pub enum Error {
     /// Error when creating a Dcrt element params
     #[error("Incorrect parameters set for the DCRT element.")]
     DcrtElementParamsMismatch,
+    /// Error when a modulus cannot be used for an NTT of the cyclotomic order
+    #[error(
+        "Modulus `{modulus}` is not an NTT friendly prime for cyclotomic order `{cyclotomic_order}`"
+    )]
+    NotNttFriendlyModulus {
+        /// The rejected modulus
+        modulus: u64,
+        /// The cyclotomic order
+        cyclotomic_order: usize,
+    },
+    /// Error when the same modulus appears more than once in an RNS basis
+    #[error("Modulus `{0}` appears more than once in the RNS basis")]
+    DuplicateModulus(u64),
+    /// Error when a root of unity does not have the cyclotomic order modulo its tower
+    #[error(
+        "`{root}` is not a primitive root of unity of order `{cyclotomic_order}` modulo `{modulus}`"
+    )]
+    InvalidRootOfUnity {
+        /// The rejected root
+        root: u64,
+        /// The modulus of the tower
+        modulus: u64,
+        /// The cyclotomic order
+        cyclotomic_order: usize,
+    },
+    /// Error when a SEAL serialized object cannot be imported
+    #[error("Invalid SEAL data: `{0}`")]
+    SealFormat(String),
+    /// Error when a transciphering key does not match the cipher
+    #[error("Transciphering key has `{actual}` bits but the cipher expects `{expected}`")]
+    TranscipherKeyLength {
+        /// The key size of the cipher
+        expected: usize,
+        /// The key size given
+        actual: usize,
+    },
+    /// Error when reading or writing a file
+    #[error("I/O error: `{0}`")]
+    Io(#[from] std::io::Error),
+    /// Error when an evaluation key file is malformed
+    #[error("Invalid evaluation key file: `{0}`")]
+    EvalKeyFile(String),
+    /// Error when a checkpoint was written by an incompatible format version
+    #[error("Unsupported checkpoint version `{0}`")]
+    CheckpointVersion(u32),
+    /// Error when a bootstrapping configuration cannot be estimated
+    #[error("Invalid bootstrapping parameters: `{0}`")]
+    BootstrapParams(String),
+    /// Error when a sampler cannot be built from the given parameters
+    #[error("Invalid sampler parameters: `{0}`")]
+    SamplerParams(String),
+    /// Error when crypto context parameters are inconsistent
+    #[error("Invalid crypto context parameters: `{0}`")]
+    CcParams(String),
+    /// Error when a ciphertext cannot be moved to another context
+    #[error("Re-encryption failed: `{0}`")]
+    Reencrypt(String),
+    /// Error when a ciphertext violates its structural invariants
+    #[error("Invalid ciphertext: `{0}`")]
+    CiphertextFormat(String),
+    /// Error when a ciphertext does not decrypt, deliberately without detail
+    #[error("Decryption failed")]
+    DecryptionFailure,
+    /// Error when a ciphertext cannot be blinded or unblinded
+    #[error("Blinding failed: `{0}`")]
+    Blinding(String),
+    /// Error when matrix dimensions are inconsistent
+    #[error("Invalid matrix dimensions: `{0}`")]
+    MatrixDimensions(String),
+    /// Error when ciphertexts cannot be aggregated
+    #[error("Aggregation failed: `{0}`")]
+    Aggregation(String),
+    /// Error when a parameter change drops below the guarded security level
+    #[error("Security downgrade: `{0}`")]
+    SecurityDowngrade(String),
+    /// Error when a capped context has no room for another value
+    #[error("Arena exhausted, all `{capacity}` {kind} slots are in use")]
+    ArenaExhausted {
+        /// What ran out of slots
+        kind: ArenaKind,
+        /// The cap of the context
+        capacity: usize,
+    },
 }
 
 impl<T> From<std::sync::PoisonError<T>> for Error {
@@ -0,0 +1,327 @@
+//! Slot-wise building blocks for encrypted programs
+//!
+//! The helpers are written against [`SlotEvaluator`] so the same logic runs
+//! on plain vectors, which is handy for checking expected results, and on
+//! packed ciphertexts of any scheme that provides the basic arithmetic.
+
+use crate::core::math::eval_chebyshev_coefficients;
+use crate::error::Result;
+
+/// Slot-wise arithmetic over some representation of a packed vector
+pub trait SlotEvaluator {
+    /// The representation of a packed vector, e.g. a ciphertext
+    type Ciphertext: Clone;
+    /// A public packed vector
+    type Plaintext;
+
+    /// `a + b`
+    fn add(&self, a: &Self::Ciphertext, b: &Self::Ciphertext) -> Result<Self::Ciphertext>;
+    /// `a - b`
+    fn sub(&self, a: &Self::Ciphertext, b: &Self::Ciphertext) -> Result<Self::Ciphertext>;
+    /// `a * b`, consumes one multiplicative level
+    fn mul(&self, a: &Self::Ciphertext, b: &Self::Ciphertext) -> Result<Self::Ciphertext>;
+    /// `a * b` where `b` is public
+    fn mul_plain(&self, a: &Self::Ciphertext, b: &Self::Plaintext) -> Result<Self::Ciphertext>;
+    /// `a + c` in every slot
+    fn add_const(&self, a: &Self::Ciphertext, c: f64) -> Result<Self::Ciphertext>;
+    /// `a * c` in every slot, consumes one level unless `c` is an integer
+    fn mul_const(&self, a: &Self::Ciphertext, c: f64) -> Result<Self::Ciphertext>;
+}
+
+/// Evaluates on plain `f64` vectors, slot `i` of the result uses slot `i` of every operand
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PlainSlotEvaluator;
+
+impl PlainSlotEvaluator {
+    fn zip(a: &[f64], b: &[f64], f: impl Fn(f64, f64) -> f64) -> Vec<f64> {
+        debug_assert_eq!(a.len(), b.len());
+        a.iter().zip(b).map(|(&a, &b)| f(a, b)).collect()
+    }
+}
+
+impl SlotEvaluator for PlainSlotEvaluator {
+    type Ciphertext = Vec<f64>;
+    type Plaintext = Vec<f64>;
+
+    fn add(&self, a: &Vec<f64>, b: &Vec<f64>) -> Result<Vec<f64>> {
+        Ok(Self::zip(a, b, |a, b| a + b))
+    }
+
+    fn sub(&self, a: &Vec<f64>, b: &Vec<f64>) -> Result<Vec<f64>> {
+        Ok(Self::zip(a, b, |a, b| a - b))
+    }
+
+    fn mul(&self, a: &Vec<f64>, b: &Vec<f64>) -> Result<Vec<f64>> {
+        Ok(Self::zip(a, b, |a, b| a * b))
+    }
+
+    fn mul_plain(&self, a: &Vec<f64>, b: &Vec<f64>) -> Result<Vec<f64>> {
+        Ok(Self::zip(a, b, |a, b| a * b))
+    }
+
+    fn add_const(&self, a: &Vec<f64>, c: f64) -> Result<Vec<f64>> {
+        Ok(a.iter().map(|a| a + c).collect())
+    }
+
+    fn mul_const(&self, a: &Vec<f64>, c: f64) -> Result<Vec<f64>> {
+        Ok(a.iter().map(|a| a * c).collect())
+    }
+}
+
+/// `cond * a + (1 - cond) * b` slot-wise with one multiplication.
+///
+/// Slots of `cond` are expected to hold `0` or `1`, other values interpolate
+/// between `b` and `a`. Computed as `b + cond * (a - b)`.
+pub fn eval_mux<E: SlotEvaluator>(
+    eval: &E,
+    cond: &E::Ciphertext,
+    a: &E::Ciphertext,
+    b: &E::Ciphertext,
+) -> Result<E::Ciphertext> {
+    let diff = eval.sub(a, b)?;
+    eval.add(b, &eval.mul(cond, &diff)?)
+}
+
+/// [`eval_mux`] with a public condition, e.g. a fixed selection mask
+pub fn eval_mux_plain<E: SlotEvaluator>(
+    eval: &E,
+    cond: &E::Plaintext,
+    a: &E::Ciphertext,
+    b: &E::Ciphertext,
+) -> Result<E::Ciphertext> {
+    let diff = eval.sub(a, b)?;
+    eval.add(b, &eval.mul_plain(&diff, cond)?)
+}
+
+/// Evaluate `coeffs[0] / 2 + sum(coeffs[k] * T_k(y))` where `y` maps `[a, b]` onto `[-1, 1]`.
+///
+/// Every `T_k` is built from two halves, so degree `d` needs `ceil(log2(d))`
+/// levels plus one for the weighted sum and one for the change of interval
+/// unless `[a, b]` is `[-1, 1]`. The `d - 1` products make it best suited to
+/// the small and medium degrees used by activation functions.
+pub fn eval_chebyshev_series<E: SlotEvaluator>(
+    eval: &E,
+    x: &E::Ciphertext,
+    coeffs: &[f64],
+    a: f64,
+    b: f64,
+) -> Result<E::Ciphertext> {
+    let y = if a == -1.0 && b == 1.0 {
+        x.clone()
+    } else {
+        let scaled = eval.mul_const(x, 2.0 / (b - a))?;
+        eval.add_const(&scaled, -(a + b) / (b - a))?
+    };
+    let constant = coeffs.first().map_or(0.0, |c| c / 2.0);
+    let degree = coeffs.len().saturating_sub(1);
+    if degree == 0 {
+        return eval.add_const(&eval.mul_const(&y, 0.0)?, constant);
+    }
+
+    // T_{m+n} = 2 T_m T_n - T_{m-n} with m = ceil(k / 2), n = floor(k / 2)
+    let mut t: Vec<E::Ciphertext> = Vec::with_capacity(degree);
+    t.push(y);
+    for k in 2..=degree {
+        let (m, n) = (k.div_ceil(2), k / 2);
+        let product = eval.mul(&t[m - 1], &t[n - 1])?;
+        let doubled = eval.add(&product, &product)?;
+        t.push(if m == n {
+            eval.add_const(&doubled, -1.0)?
+        } else {
+            eval.sub(&doubled, &t[0])?
+        });
+    }
+
+    let mut sum = eval.mul_const(&t[0], coeffs[1])?;
+    for (tk, &c) in t.iter().zip(&coeffs[1..]).skip(1) {
+        sum = eval.add(&sum, &eval.mul_const(tk, c)?)?;
+    }
+    eval.add_const(&sum, constant)
+}
+
+/// How [`eval_abs`] and [`eval_relu`] approximate `|x|` on `[-bound, bound]`.
+///
+/// Measured errors relative to `bound`, over the whole range and for
+/// `|x| >= bound / 20`:
+///
+/// | approximation                 | depth | max error | away from 0 |
+/// |-------------------------------|-------|-----------|-------------|
+/// | `Chebyshev { degree: 15 }`    | 6     | 6.3e-2    | 2.2e-2      |
+/// | `Chebyshev { degree: 31 }`    | 7     | 3.1e-2    | 4.9e-3      |
+/// | `Chebyshev { degree: 63 }`    | 8     | 1.6e-2    | 2.2e-3      |
+/// | `Chebyshev { degree: 119 }`   | 9     | 8.3e-3    | 4.3e-4      |
+/// | `Composite { iterations: 1 }` | 14    | 1.3e-2    | 8.9e-3      |
+/// | `Composite { iterations: 2 }` | 18    | 2.8e-3    | 6.2e-8      |
+/// | `Composite { iterations: 3 }` | 22    | 6.3e-4    | 6.5e-8      |
+/// | `Composite { iterations: 4 }` | 26    | 1.4e-4    | 6.6e-8      |
+///
+/// A single interpolant is shallow but spreads its error over the range.
+/// Composites cost more levels and push nearly all of the error into a
+/// shrinking neighbourhood of zero, where `|x|` itself is small.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AbsApproximation {
+    /// One Chebyshev interpolant of `degree`
+    Chebyshev {
+        /// The degree of the interpolant
+        degree: usize,
+    },
+    /// `iterations` steep sign rounds and two finishing rounds, then `x * sign(x)`
+    Composite {
+        /// The number of steep sign rounds
+        iterations: usize,
+    },
+}
+
+impl Default for AbsApproximation {
+    fn default() -> Self {
+        Self::Chebyshev { degree: 63 }
+    }
+}
+
+impl AbsApproximation {
+    /// The multiplicative depth consumed, counting scalar products as a level
+    pub fn depth(&self) -> usize {
+        match *self {
+            Self::Chebyshev { degree } => degree.max(1).next_power_of_two().ilog2() as usize + 2,
+            Self::Composite { iterations } => 4 * (iterations + FINISH_ROUNDS) + 2,
+        }
+    }
+}
+
+/// Approximate `|x|` for slots in `[-bound, bound]`
+pub fn eval_abs<E: SlotEvaluator>(
+    eval: &E,
+    x: &E::Ciphertext,
+    bound: f64,
+    approximation: AbsApproximation,
+) -> Result<E::Ciphertext> {
+    match approximation {
+        AbsApproximation::Chebyshev { degree } => {
+            let coeffs = eval_chebyshev_coefficients(f64::abs, -bound, bound, degree);
+            eval_chebyshev_series(eval, x, &coeffs, -bound, bound)
+        }
+        AbsApproximation::Composite { iterations } => {
+            let mut sign = eval.mul_const(x, 1.0 / bound)?;
+            for _ in 0..iterations {
+                sign = sign_round(eval, &sign, &STEEP_SIGN)?;
+            }
+            for _ in 0..FINISH_ROUNDS {
+                sign = sign_round(eval, &sign, &FINISH_SIGN)?;
+            }
+            eval.mul(x, &sign)
+        }
+    }
+}
+
+/// Approximate `max(x, 0)` for slots in `[-bound, bound]` as `(x + |x|) / 2`,
+/// which takes one level more than [`AbsApproximation::depth`]
+pub fn eval_relu<E: SlotEvaluator>(
+    eval: &E,
+    x: &E::Ciphertext,
+    bound: f64,
+    approximation: AbsApproximation,
+) -> Result<E::Ciphertext> {
+    let abs = eval_abs(eval, x, bound, approximation)?;
+    eval.mul_const(&eval.add(x, &abs)?, 0.5)
+}
+
+/// Odd degree 7 sign polynomials, the coefficients of `y, y^3, y^5, y^7`.
+///
+/// The steep one moves small inputs away from zero quickly, the finishing
+/// one converges to `±1` from there.
+const STEEP_SIGN: [f64; 4] = [
+    4589.0 / 1024.0,
+    -16577.0 / 1024.0,
+    25614.0 / 1024.0,
+    -12860.0 / 1024.0,
+];
+const FINISH_SIGN: [f64; 4] = [35.0 / 16.0, -35.0 / 16.0, 21.0 / 16.0, -5.0 / 16.0];
+const FINISH_ROUNDS: usize = 2;
+
+fn sign_round<E: SlotEvaluator>(
+    eval: &E,
+    y: &E::Ciphertext,
+    coeffs: &[f64; 4],
+) -> Result<E::Ciphertext> {
+    let y2 = eval.mul(y, y)?;
+    let y3 = eval.mul(&y2, y)?;
+    let y4 = eval.mul(&y2, &y2)?;
+    let y5 = eval.mul(&y4, y)?;
+    let y7 = eval.mul(&y4, &y3)?;
+    let mut sum = eval.mul_const(y, coeffs[0])?;
+    for (power, &c) in [y3, y5, y7].iter().zip(&coeffs[1..]) {
+        sum = eval.add(&sum, &eval.mul_const(power, c)?)?;
+    }
+    Ok(sum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ciphertext::Ciphertext;
+    use crate::encoding::PlaintextEncodings;
+    use crate::pke::test_support::ckks_context;
+    use crate::pke::{CkksRns, KeyPair};
+
+    fn ckks(depth: usize) -> (CkksRns, KeyPair) {
+        let ckks = ckks_context(depth);
+        let key_pair = ckks.key_gen().expect("keys");
+        ckks.eval_mult_key_gen(key_pair.private_key())
+            .expect("relinearization key");
+        (ckks, key_pair)
+    }
+
+    fn encrypt(ckks: &CkksRns, key_pair: &KeyPair, values: &[f64]) -> Ciphertext {
+        let plaintext = ckks.encode(values).expect("encode");
+        ckks.encrypt(key_pair.public_key(), &plaintext)
+            .expect("encrypt")
+    }
+
+    fn assert_decrypts_to(ckks: &CkksRns, key_pair: &KeyPair, c: &Ciphertext, expected: &[f64]) {
+        let PlaintextEncodings::Ckks(plaintext) =
+            ckks.decrypt(key_pair.private_key(), c).expect("decrypt")
+        else {
+            panic!("not a CKKS plaintext");
+        };
+        for (x, y) in plaintext.values().iter().zip(expected) {
+            assert!((x - y).abs() < 1e-4, "{x} != {y}");
+        }
+    }
+
+    #[test]
+    fn mux_on_ciphertexts() {
+        let (ckks, key_pair) = ckks(1);
+        let cond = [1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0];
+        let a = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
+        let b = [-1.0; 8];
+        let expected = eval_mux(
+            &PlainSlotEvaluator,
+            &cond.to_vec(),
+            &a.to_vec(),
+            &b.to_vec(),
+        )
+        .expect("plain mux");
+        let (ca, cb) = (encrypt(&ckks, &key_pair, &a), encrypt(&ckks, &key_pair, &b));
+
+        let muxed = eval_mux(&ckks, &encrypt(&ckks, &key_pair, &cond), &ca, &cb).expect("mux");
+        assert_decrypts_to(&ckks, &key_pair, &muxed, &expected);
+        let mask = ckks.encode(&cond).expect("encode");
+        let muxed = eval_mux_plain(&ckks, &mask, &ca, &cb).expect("mux");
+        assert_decrypts_to(&ckks, &key_pair, &muxed, &expected);
+    }
+
+    #[test]
+    fn relu_on_ciphertexts() {
+        let approximation = AbsApproximation::Chebyshev { degree: 15 };
+        let (ckks, key_pair) = ckks(approximation.depth() + 1);
+        let x = [-0.9, -0.5, -0.1, 0.0, 0.05, 0.3, 0.7, 1.0];
+        let expected =
+            eval_relu(&PlainSlotEvaluator, &x.to_vec(), 1.0, approximation).expect("plain relu");
+
+        let relu =
+            eval_relu(&ckks, &encrypt(&ckks, &key_pair, &x), 1.0, approximation).expect("relu");
+        assert_decrypts_to(&ckks, &key_pair, &relu, &expected);
+        let too_deep = AbsApproximation::Composite { iterations: 1 };
+        assert!(eval_relu(&ckks, &encrypt(&ckks, &key_pair, &x), 1.0, too_deep).is_err());
+    }
+}
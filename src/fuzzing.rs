@@ -0,0 +1,37 @@
+//! Entry points for the cargo-fuzz targets in `fuzz/`
+//!
+//! Only compiled with `--cfg fuzzing`. Each function deserializes untrusted
+//! input with the caller's deserializer and then exercises the result, so any
+//! panic reachable from bytes on the wire shows up as a fuzzer crash.
+
+use crate::ciphertext::Ciphertext;
+use crate::core::lattice::poly::Poly;
+use crate::core::math::VecModStd;
+use serde::{Deserialize, Deserializer};
+
+/// Deserialize a [`VecModStd`] and run arithmetic on it
+pub fn vec_mod<'de, D: Deserializer<'de>>(d: D) {
+    if let Ok(v) = VecModStd::deserialize(d) {
+        let sum = &v + &v;
+        let product = &sum * &v;
+        let _ = &product - &v;
+        let _ = v.to_signed();
+        let _ = v.dot(&product);
+    }
+}
+
+/// Deserialize a [`Poly`] and run arithmetic on it
+pub fn poly<'de, D: Deserializer<'de>>(d: D) {
+    if let Ok(p) = Poly::deserialize(d) {
+        let _ = p.norm();
+        let _ = p.inverse();
+    }
+}
+
+/// Deserialize a [`Ciphertext`] and inspect it
+pub fn ciphertext<'de, D: Deserializer<'de>>(d: D) {
+    if let Ok(mut c) = Ciphertext::deserialize(d) {
+        let _ = c.level();
+        c.drop_levels(1);
+    }
+}
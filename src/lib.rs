@@ -18,15 +18,30 @@
 #[macro_use]
 mod macros;
 
-mod ciphertext;
+pub mod arena;
+#[cfg(feature = "audit")]
+pub mod audit;
+pub mod benchmark;
+pub mod checkpoint;
+pub mod ciphertext;
 pub mod constants;
 pub mod context;
-mod core;
+pub mod core;
 mod crypto_object;
+#[cfg(any(test, feature = "differential"))]
+pub mod differential;
 pub mod encoding;
 pub mod error;
+pub mod evaluator;
+#[cfg(fuzzing)]
+#[doc(hidden)]
+pub mod fuzzing;
+pub mod parallel;
 pub mod pke;
+#[cfg(feature = "seal")]
+pub mod seal;
 mod serdes;
+pub mod transcipher;
 
 trait ActingPrimitive {
     type Primitive;
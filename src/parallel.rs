@@ -0,0 +1,150 @@
+//! Configuration of the parallel backend
+//!
+//! Work is split over scoped threads owned by the call instead of a global
+//! pool, so the library never competes with pools the host process manages.
+//! A [`CryptoContext`](crate::pke::CryptoContext) can override the global
+//! configuration for the operations it runs.
+
+use derive_more::{Display, FromStr};
+use std::num::NonZeroUsize;
+use std::sync::RwLock;
+
+static GLOBAL_CONFIG: RwLock<ParallelConfig> = RwLock::new(ParallelConfig::new());
+
+/// How worker threads are pinned to CPUs
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Ord, PartialOrd, Hash, Display, FromStr)]
+#[repr(usize)]
+pub enum PinningPolicy {
+    /// Let the OS schedule the workers
+    #[default]
+    None = 0,
+    /// Pin worker `i` to CPU `i`
+    Compact,
+    /// Spread the workers evenly over all CPUs
+    Scatter,
+}
+
+hex_enum_usize!(PinningPolicy);
+
+/// Parallel backend configuration
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ParallelConfig {
+    threads: usize,
+    pinning: PinningPolicy,
+}
+
+impl Default for ParallelConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParallelConfig {
+    /// Use all available CPUs without pinning
+    pub const fn new() -> Self {
+        Self {
+            threads: 0,
+            pinning: PinningPolicy::None,
+        }
+    }
+
+    /// Run everything on the calling thread
+    pub const fn sequential() -> Self {
+        Self {
+            threads: 1,
+            pinning: PinningPolicy::None,
+        }
+    }
+
+    /// Set the number of worker threads, `0` uses all available CPUs
+    pub const fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Set the pinning policy
+    pub const fn pinning(mut self, pinning: PinningPolicy) -> Self {
+        self.pinning = pinning;
+        self
+    }
+
+    /// The pinning policy
+    pub fn pinning_policy(&self) -> PinningPolicy {
+        self.pinning
+    }
+
+    /// The number of worker threads after resolving `0` to the available CPUs
+    pub fn num_threads(&self) -> usize {
+        match self.threads {
+            0 => available_cpus(),
+            n => n,
+        }
+    }
+
+    /// Apply `f` to every item, splitting the slice over the worker threads
+    pub fn for_each_mut<T, F>(&self, items: &mut [T], f: F)
+    where
+        T: Send,
+        F: Fn(&mut T) + Sync,
+    {
+        let threads = self.num_threads().min(items.len());
+        if threads <= 1 {
+            items.iter_mut().for_each(f);
+            return;
+        }
+
+        let chunk = items.len().div_ceil(threads);
+        let f = &f;
+        let pinning = self.pinning;
+        std::thread::scope(|s| {
+            for (worker, part) in items.chunks_mut(chunk).enumerate() {
+                s.spawn(move || {
+                    pin_current_thread(pinning, worker, threads);
+                    part.iter_mut().for_each(f);
+                });
+            }
+        });
+    }
+}
+
+/// Replace the configuration used when a context has no override
+pub fn set_global_config(config: ParallelConfig) {
+    match GLOBAL_CONFIG.write() {
+        Ok(mut c) => *c = config,
+        Err(e) => *e.into_inner() = config,
+    }
+}
+
+/// The configuration used when a context has no override
+pub fn global_config() -> ParallelConfig {
+    match GLOBAL_CONFIG.read() {
+        Ok(c) => *c,
+        Err(e) => **e.get_ref(),
+    }
+}
+
+fn available_cpus() -> usize {
+    std::thread::available_parallelism()
+        .map(NonZeroUsize::get)
+        .unwrap_or(1)
+}
+
+#[cfg(target_os = "linux")]
+fn pin_current_thread(pinning: PinningPolicy, worker: usize, workers: usize) {
+    let cpus = available_cpus();
+    let cpu = match pinning {
+        PinningPolicy::None => return,
+        PinningPolicy::Compact => worker % cpus,
+        PinningPolicy::Scatter => (worker * (cpus / workers).max(1)) % cpus,
+    };
+    // SAFETY: `set` is a plain bitmask that outlives the call and pid 0 is the calling thread
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        // Pinning is best effort, e.g. the CPU may be outside the process cgroup
+        let _ = libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &set);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_current_thread(_pinning: PinningPolicy, _worker: usize, _workers: usize) {}
@@ -1,7 +1,33 @@
+mod aggregation;
+mod blinding;
+mod cc_params;
 mod crypto_context;
+mod decryption;
+mod encryption_pool;
 mod key;
+mod key_switch;
+mod multiparty;
+mod polynomial;
+mod pre;
+#[cfg(feature = "trusted-reencrypt")]
+mod reencrypt;
+mod scaling;
 mod scheme;
 mod schemebase;
+#[cfg(test)]
+pub(crate) mod test_support;
 
+pub use aggregation::*;
+pub use blinding::*;
+pub use cc_params::*;
 pub use crypto_context::*;
+pub use decryption::*;
+pub use encryption_pool::*;
+pub use key::*;
+pub use key_switch::FastRotationPrecompute;
+pub use polynomial::{ApproximationPrecision, SignApproximation};
+#[cfg(feature = "trusted-reencrypt")]
+pub use reencrypt::*;
+pub use scaling::*;
 pub use scheme::*;
+pub use schemebase::*;
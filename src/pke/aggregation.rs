@@ -0,0 +1,199 @@
+//! Aggregate-then-decrypt pipelines
+//!
+//! The most common use of additive homomorphism is summing many encrypted
+//! contributions, e.g. votes or telemetry, and decrypting only the total.
+//! [`Aggregator`] does that in one call: it sums ciphertexts with
+//! [`CryptoContext::eval_add_many`], optionally compresses the sum to fewer
+//! towers and wraps it in an [`AggregatePayload`] ready to be sent to the
+//! parties holding shares of the secret key.
+
+use crate::ciphertext::Ciphertext;
+use crate::constants::PolynomialRingFormat;
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::error::{Error, Result};
+use crate::pke::CryptoContext;
+use num::{BigInt, Integer};
+use serde::{Deserialize, Serialize};
+
+/// Drop all but the first `towers` towers of a ciphertext whose plaintext is
+/// scaled by the ciphertext modulus, as in BFV.
+///
+/// Every coefficient is multiplied by `Q' / Q` and rounded, so the result
+/// decrypts under the smaller modulus `Q'` with the noise scaled down too.
+/// Smaller ciphertexts are cheaper to ship and to decrypt jointly.
+pub fn compress(ciphertext: &[DcrtPoly], towers: usize) -> Result<Vec<DcrtPoly>> {
+    let params = checked(ciphertext)?[0].params();
+    if towers == 0 || towers > params.params().len() {
+        return Err(Error::Aggregation(format!(
+            "cannot compress {} towers to {towers}",
+            params.params().len()
+        )));
+    }
+    let target = params.shrink(towers);
+    let dropped =
+        BigInt::from(params.ciphertext_composite_modulus() / target.ciphertext_composite_modulus());
+    Ok(ciphertext
+        .iter()
+        .map(|poly| {
+            let mut poly = poly.clone();
+            let format = poly.format();
+            if format == PolynomialRingFormat::Evaluation {
+                poly.switch_format();
+            }
+            let rounded = poly
+                .to_signed()
+                .iter()
+                // floor((2 c + P) / 2 P) rounds half up
+                .map(|c| ((c << 1u32) + &dropped).div_floor(&(&dropped << 1u32)))
+                .collect::<Vec<_>>();
+            let mut poly = DcrtPoly::from_big_signed(target.clone(), &rounded);
+            if format == PolynomialRingFormat::Evaluation {
+                poly.switch_format();
+            }
+            poly
+        })
+        .collect())
+}
+
+/// An aggregated ciphertext ready for threshold decryption
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AggregatePayload {
+    ciphertext: Ciphertext,
+    count: usize,
+}
+
+impl AggregatePayload {
+    /// The aggregated ciphertext
+    pub fn ciphertext(&self) -> &Ciphertext {
+        &self.ciphertext
+    }
+
+    /// The number of ciphertexts that were summed
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The aggregated ciphertext, dropping the metadata
+    pub fn into_ciphertext(self) -> Ciphertext {
+        self.ciphertext
+    }
+}
+
+/// Sums ciphertexts into an [`AggregatePayload`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Aggregator {
+    compress_to: Option<usize>,
+    max_count: Option<usize>,
+}
+
+impl Aggregator {
+    /// An aggregator that neither compresses nor limits the input
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compress the sum to its first `towers` towers, see
+    /// [`CryptoContext::compress`]
+    pub fn compress_to(mut self, towers: usize) -> Self {
+        self.compress_to = Some(towers);
+        self
+    }
+
+    /// Fail when more than `max_count` ciphertexts arrive, e.g. to keep the
+    /// sum of bounded contributions below the plaintext modulus
+    pub fn max_count(mut self, max_count: usize) -> Self {
+        self.max_count = Some(max_count);
+        self
+    }
+
+    /// Sum `ciphertexts` of `crypto_context` and compress the result if
+    /// configured
+    pub fn aggregate(
+        &self,
+        crypto_context: &CryptoContext,
+        ciphertexts: impl IntoIterator<Item = Ciphertext>,
+    ) -> Result<AggregatePayload> {
+        let max_count = self.max_count.unwrap_or(usize::MAX);
+        let ciphertexts = ciphertexts
+            .into_iter()
+            .take(max_count.saturating_add(1))
+            .collect::<Vec<_>>();
+        if ciphertexts.is_empty() {
+            return Err(Error::Aggregation(
+                "there are no ciphertexts to add".to_string(),
+            ));
+        }
+        if ciphertexts.len() > max_count {
+            return Err(Error::Aggregation(format!(
+                "more than {max_count} ciphertexts were received"
+            )));
+        }
+        let sum = crypto_context.eval_add_many(&ciphertexts)?;
+        let ciphertext = match self.compress_to {
+            Some(towers) => crypto_context.compress(&sum, towers)?,
+            None => sum,
+        };
+        Ok(AggregatePayload {
+            ciphertext,
+            count: ciphertexts.len(),
+        })
+    }
+}
+
+fn checked(ciphertext: &[DcrtPoly]) -> Result<&[DcrtPoly]> {
+    let Some(first) = ciphertext.first() else {
+        return Err(Error::Aggregation(
+            "a ciphertext has no polynomials".to_string(),
+        ));
+    };
+    if ciphertext
+        .iter()
+        .any(|c| c.params() != first.params() || c.format() != first.format())
+    {
+        return Err(Error::DcrtElementParamsMismatch);
+    }
+    Ok(ciphertext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::PlaintextEncodings;
+    use crate::pke::test_support::bfv_context;
+
+    #[test]
+    fn aggregate_then_decrypt() {
+        let bfv = bfv_context(2);
+        let key_pair = bfv.key_gen().expect("keys");
+        let votes = [[1, 0, 2], [0, 1, 1], [3, 0, 0], [1, 1, 1]];
+        let ciphertexts = votes
+            .iter()
+            .map(|v| {
+                let plaintext = bfv.encode(v).expect("encode");
+                bfv.encrypt(key_pair.public_key(), &plaintext)
+                    .expect("encrypt")
+            })
+            .collect::<Vec<_>>();
+        let cc = bfv.crypto_context();
+        let towers = ciphertexts[0].elements()[0].params().params().len();
+
+        let payload = Aggregator::new()
+            .compress_to(towers - 1)
+            .max_count(votes.len())
+            .aggregate(cc, ciphertexts.clone())
+            .expect("aggregate");
+        assert_eq!(payload.count(), votes.len());
+        assert_eq!(
+            payload.ciphertext().elements()[0].params().params().len(),
+            towers - 1
+        );
+        match bfv.decrypt(key_pair.private_key(), payload.ciphertext()) {
+            Ok(PlaintextEncodings::Coefficient(p)) => assert_eq!(p.coefficients()[..3], [5, 2, 4]),
+            other => panic!("unexpected decryption {other:?}"),
+        }
+
+        let too_many = Aggregator::new().max_count(votes.len() - 1);
+        assert!(too_many.aggregate(cc, ciphertexts).is_err());
+        assert!(Aggregator::new().aggregate(cc, []).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! Additive blinding of ciphertexts with party-local masks
+//!
+//! Before a party hands a ciphertext to an aggregator it adds a uniformly
+//! random polynomial `r` to `c0`. The blinded ciphertext decrypts to
+//! `m + e + r`, which is uniform and reveals nothing about the party's
+//! partial result even to someone holding the secret key. Blinded
+//! ciphertexts can still be added homomorphically; the masks are removed
+//! either from the aggregate with [`unblind_all`] before decryption, or
+//! while decrypting with [`CryptoContext::decrypt_blinded`].
+//!
+//! A [`BlindingMask`] is a 32 byte seed expanded to the shape of the
+//! ciphertext it blinds, so storing it between blinding and unmasking is
+//! cheap. A mask must blind only one ciphertext, reusing it reveals the
+//! difference of the two plaintexts.
+
+use crate::arena::ArenaKind;
+use crate::ciphertext::Ciphertext;
+use crate::constants::PolynomialRingFormat;
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::crypto_object::CryptoObject;
+use crate::encoding::PlaintextEncodings;
+use crate::error::{Error, Result};
+use crate::pke::decryption::phase;
+use crate::pke::encryption_pool::in_format_of;
+use crate::pke::{CryptoContext, PrivateKey};
+use rand::{CryptoRng, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug, Formatter};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// The seed of a party-local mask, wiped when dropped
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct BlindingMask {
+    seed: [u8; BlindingMask::BYTES],
+}
+
+impl Debug for BlindingMask {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlindingMask").finish_non_exhaustive()
+    }
+}
+
+impl BlindingMask {
+    /// The length of [`BlindingMask::to_bytes`]
+    pub const BYTES: usize = 32;
+
+    /// A fresh mask
+    pub fn generate() -> Self {
+        Self::generate_with_rng(&mut rand::rng())
+    }
+
+    /// [`BlindingMask::generate`] drawing randomness from `rng`
+    pub fn generate_with_rng(rng: &mut impl CryptoRng) -> Self {
+        Self { seed: rng.random() }
+    }
+
+    /// The seed, to be kept as secret as the mask itself
+    pub fn to_bytes(&self) -> [u8; Self::BYTES] {
+        self.seed
+    }
+
+    /// Restore a mask written with [`BlindingMask::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let seed = bytes.try_into().map_err(|_| {
+            Error::Blinding(format!(
+                "a mask is {} bytes, found {}",
+                Self::BYTES,
+                bytes.len()
+            ))
+        })?;
+        Ok(Self { seed })
+    }
+
+    /// The mask expanded to the parameters and format of `like`.
+    ///
+    /// The expansion is done in coefficient format, so it is the same
+    /// polynomial whichever format `like` is in.
+    pub(crate) fn expand(&self, like: &DcrtPoly) -> DcrtPoly {
+        let mut mask = like.clone();
+        if mask.format() == PolynomialRingFormat::Evaluation {
+            mask.switch_format();
+        }
+        mask.fill_uniform_with_rng(ChaCha20Rng::from_seed(self.seed));
+        in_format_of(mask, like)
+    }
+
+    /// Add the mask to `c0` of `ciphertext`
+    pub fn blind(&self, ciphertext: &mut Ciphertext) -> Result<()> {
+        self.apply("blind", ciphertext, |c0, mask| *c0 += mask)
+    }
+
+    /// Remove the mask from a ciphertext blinded with [`BlindingMask::blind`],
+    /// possibly after it was added to other ciphertexts
+    pub fn unblind(&self, ciphertext: &mut Ciphertext) -> Result<()> {
+        self.apply("unblind", ciphertext, |c0, mask| *c0 -= mask)
+    }
+
+    /// Remove the mask from the raw decryption `c0 + c1 s + ...` of a blinded
+    /// ciphertext, before it is decoded
+    pub(crate) fn unblind_decrypted(&self, decrypted: &mut DcrtPoly) {
+        *decrypted -= &self.expand(decrypted);
+    }
+
+    fn apply(
+        &self,
+        op: &str,
+        ciphertext: &mut Ciphertext,
+        f: impl FnOnce(&mut DcrtPoly, &DcrtPoly),
+    ) -> Result<()> {
+        let mut elements = ciphertext.elements().to_vec();
+        let c0 = elements
+            .first_mut()
+            .ok_or_else(|| Error::Blinding("the ciphertext has no polynomials".to_string()))?;
+        let mask = self.expand(c0);
+        f(c0, &mask);
+        let scaling_factor = ciphertext.scaling_factor();
+        ciphertext.replace_elements(op, elements, scaling_factor, &[]);
+        Ok(())
+    }
+}
+
+/// Remove every mask in `masks` from the aggregate of the ciphertexts they blinded
+pub fn unblind_all<'a>(
+    masks: impl IntoIterator<Item = &'a BlindingMask>,
+    ciphertext: &mut Ciphertext,
+) -> Result<()> {
+    masks
+        .into_iter()
+        .try_for_each(|mask| mask.unblind(ciphertext))
+}
+
+impl CryptoContext {
+    /// Decrypt the aggregate of ciphertexts blinded with `masks`, removing
+    /// the masks from the raw decryption before it is decoded so the
+    /// ciphertext itself stays blinded, see [`CryptoContext::decrypt`]
+    pub fn decrypt_blinded<'a>(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+        masks: impl IntoIterator<Item = &'a BlindingMask>,
+    ) -> Result<PlaintextEncodings> {
+        if private_key.get_crypto_context() != self || ciphertext.get_crypto_context() != self {
+            return Err(Error::CcParams(
+                "the key or the ciphertext belongs to another crypto context".to_string(),
+            ));
+        }
+        let lease = self.lease(ArenaKind::Plaintext)?;
+        let towers = ciphertext.element_params().params().len();
+        let mut decrypted = phase(ciphertext.elements(), &private_key.secret().shrink(towers))?;
+        for mask in masks {
+            mask.unblind_decrypted(&mut decrypted);
+        }
+        self.decode_phase(ciphertext, &decrypted, lease)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pke::test_support::{RING_DIMENSION, bfv_context};
+
+    #[test]
+    fn aggregates_unblind_to_the_plain_sum() {
+        let bfv = bfv_context(0);
+        let cc = bfv.crypto_context();
+        let keys = bfv.key_gen().expect("keys");
+        let messages = (0..3)
+            .map(|party| {
+                (0..RING_DIMENSION as i64)
+                    .map(|i| (i * 7 + party * 11) % 200 - 100)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let masks = (0..3).map(|_| BlindingMask::generate()).collect::<Vec<_>>();
+        let blinded = messages
+            .iter()
+            .zip(&masks)
+            .map(|(message, mask)| {
+                let plaintext = bfv.encode(message).expect("encode");
+                let mut ciphertext = bfv.encrypt(keys.public_key(), &plaintext).expect("encrypt");
+                mask.blind(&mut ciphertext).expect("blind");
+                ciphertext
+            })
+            .collect::<Vec<_>>();
+        let aggregate = blinded[1..].iter().fold(blinded[0].clone(), |sum, c| {
+            cc.eval_add(&sum, c).expect("add")
+        });
+        let sum = (0..RING_DIMENSION)
+            .map(|i| messages.iter().map(|m| m[i]).sum::<i64>())
+            .collect::<Vec<_>>();
+        let coefficients = |decrypted: Result<PlaintextEncodings>| match decrypted {
+            Ok(PlaintextEncodings::Coefficient(plaintext)) => Some(plaintext.coefficients()),
+            _ => None,
+        };
+
+        // The blinded aggregate does not decrypt to the sum
+        assert_ne!(
+            coefficients(bfv.decrypt(keys.private_key(), &aggregate)),
+            Some(sum.clone())
+        );
+        assert_eq!(
+            coefficients(cc.decrypt_blinded(keys.private_key(), &aggregate, &masks)),
+            Some(sum.clone())
+        );
+
+        let mut unblinded = aggregate.clone();
+        unblind_all(&masks, &mut unblinded).expect("unblind");
+        assert_eq!(
+            coefficients(bfv.decrypt(keys.private_key(), &unblinded)),
+            Some(sum.clone())
+        );
+
+        // Every mask is needed
+        assert_ne!(
+            coefficients(cc.decrypt_blinded(keys.private_key(), &aggregate, &masks[..2])),
+            Some(sum)
+        );
+    }
+
+    #[test]
+    fn masks_round_trip_through_their_bytes() {
+        let mask = BlindingMask::generate();
+        let bytes = mask.to_bytes();
+        assert_eq!(BlindingMask::from_bytes(&bytes).expect("mask"), mask);
+        for len in [0, BlindingMask::BYTES - 1, BlindingMask::BYTES + 1] {
+            let bytes = vec![7; len];
+            match BlindingMask::from_bytes(&bytes) {
+                Err(Error::Blinding(message)) => {
+                    assert!(message.contains(&format!("found {len}")), "{message}")
+                }
+                other => panic!("{len} bytes accepted: {other:?}"),
+            }
+        }
+    }
+}
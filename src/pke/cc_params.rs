@@ -0,0 +1,152 @@
+use crate::constants::{
+    DEFAULT_NUM_ADVERSARIAL_QUERIES, DEFAULT_STATISTICAL_SECURITY, DEFAULT_STD_DEV,
+    DecryptionNoiseMode, ExecutionMode, MAX_MODULUS_SIZE, MultipartyMode, ProxyPreEncryptionMode,
+    SecretKeyDistribution, SecurityLevel,
+};
+use crate::error::{Error, Result};
+use crate::pke::RLWECryptoParameters;
+use serde::{Deserialize, Serialize};
+
+/// User facing parameters for creating a crypto context.
+///
+/// The flooding standard deviation used by threshold decryption and
+/// re-encryption is derived from `statistical_security` and
+/// `num_adversarial_queries` instead of being fixed. The defaults reproduce
+/// [`MP_SD`](crate::constants::MP_SD) and [`PRE_SD`](crate::constants::PRE_SD).
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CcParams {
+    /// The standard deviation of fresh errors
+    pub std_dev: f64,
+    /// The distribution of the secret key
+    pub secret_key_distribution: SecretKeyDistribution,
+    /// The security level of the lattice parameters
+    pub security_level: SecurityLevel,
+    /// The multiparty mode
+    pub multiparty_mode: MultipartyMode,
+    /// The proxy re-encryption mode
+    pub proxy_pre_encryption_mode: ProxyPreEncryptionMode,
+    /// The number of threshold parties sharing a joint key, see
+    /// [`CryptoContext::multiparty_key_gen`](crate::pke::CryptoContext::multiparty_key_gen)
+    pub threshold_parties: usize,
+    /// The number of proxy re-encryptions a ciphertext may go through, zero
+    /// to disable re-encryption. BFV parameters leave room for the noise of
+    /// every hop.
+    pub max_hops: usize,
+    /// Bits of statistical security provided by noise flooding
+    pub statistical_security: u32,
+    /// The number of decryptions an adversary is assumed to observe
+    pub num_adversarial_queries: u64,
+    /// The highest power of the secret relinearization keys are generated for
+    pub max_relinearization_secret_key_power: usize,
+    /// The bits of BV key switching digits, zero for one digit per tower as
+    /// in OpenFHE. Smaller digits add less noise but multiply the size and
+    /// the cost of the keys by the bits of a tower over the digit size.
+    pub digit_size: usize,
+    /// Whether CKKS decryption estimates the noise or evaluates
+    pub execution_mode: ExecutionMode,
+    /// Whether CKKS decryption floods the noise
+    pub decryption_noise_mode: DecryptionNoiseMode,
+    /// The [`NoiseEstimate::log_error`] of the computation measured in
+    /// [`ExecutionMode::NoiseEstimation`], required to flood the noise in
+    /// [`ExecutionMode::Evaluation`]
+    ///
+    /// [`NoiseEstimate::log_error`]: crate::pke::NoiseEstimate::log_error
+    pub noise_estimate: f64,
+}
+
+impl Default for CcParams {
+    fn default() -> Self {
+        Self {
+            std_dev: DEFAULT_STD_DEV,
+            secret_key_distribution: SecretKeyDistribution::default(),
+            security_level: SecurityLevel::default(),
+            multiparty_mode: MultipartyMode::default(),
+            proxy_pre_encryption_mode: ProxyPreEncryptionMode::default(),
+            threshold_parties: 1,
+            max_hops: 0,
+            statistical_security: DEFAULT_STATISTICAL_SECURITY,
+            num_adversarial_queries: DEFAULT_NUM_ADVERSARIAL_QUERIES,
+            max_relinearization_secret_key_power: 2,
+            digit_size: 0,
+            execution_mode: ExecutionMode::default(),
+            decryption_noise_mode: DecryptionNoiseMode::default(),
+            noise_estimate: 0.0,
+        }
+    }
+}
+
+impl CcParams {
+    /// Check that the parameters can be used to build a context
+    pub fn validate(&self) -> Result<()> {
+        if !(self.std_dev.is_finite() && self.std_dev > 0.0) {
+            return Err(Error::CcParams(format!(
+                "standard deviation {} is not positive",
+                self.std_dev
+            )));
+        }
+        if self.statistical_security == 0 {
+            return Err(Error::CcParams(
+                "statistical security must be at least one bit".to_string(),
+            ));
+        }
+        if self.num_adversarial_queries == 0 {
+            return Err(Error::CcParams(
+                "the number of adversarial queries must be at least one".to_string(),
+            ));
+        }
+        if self.threshold_parties == 0 {
+            return Err(Error::CcParams(
+                "there must be at least one threshold party".to_string(),
+            ));
+        }
+        if self.digit_size > MAX_MODULUS_SIZE {
+            return Err(Error::CcParams(format!(
+                "digits of {} bits are larger than a tower",
+                self.digit_size
+            )));
+        }
+        if self.decryption_noise_mode == DecryptionNoiseMode::NoiseFlooding
+            && self.execution_mode == ExecutionMode::Evaluation
+            && !(self.noise_estimate.is_finite() && self.noise_estimate > 0.0)
+        {
+            return Err(Error::CcParams(format!(
+                "noise flooding decryption needs the positive noise estimate of a run in noise \
+                 estimation mode, got {}",
+                self.noise_estimate
+            )));
+        }
+        Ok(())
+    }
+
+    /// The standard deviation of flooding noise for unit sized noise to hide,
+    /// shared by threshold decryption and multi-hop re-encryption.
+    ///
+    /// The statistical distance grows with the square root of the number of
+    /// observed decryptions, so the result is
+    /// `sqrt(num_adversarial_queries) * 2^(statistical_security / 2)`.
+    pub fn flooding_std_dev(&self) -> f64 {
+        (self.num_adversarial_queries as f64).sqrt()
+            * 2f64.powf(self.statistical_security as f64 / 2.0)
+    }
+
+    /// The RLWE parameters described by these settings
+    pub fn rlwe_crypto_parameters(&self) -> Result<RLWECryptoParameters> {
+        self.validate()?;
+        Ok(RLWECryptoParameters {
+            discrete_gaussian_std_dev: self.std_dev,
+            discrete_gaussian_std_dev_with_flooding: self.flooding_std_dev(),
+            secret_key_distribution: self.secret_key_distribution,
+            security_level: self.security_level,
+            proxy_pre_encryption_mode: self.proxy_pre_encryption_mode,
+            multiparty_mode: self.multiparty_mode,
+            threshold_parties: self.threshold_parties,
+            max_hops: self.max_hops,
+            max_relinearization_secret_key_power: self.max_relinearization_secret_key_power,
+            digit_size: self.digit_size,
+            execution_mode: self.execution_mode,
+            decryption_noise_mode: self.decryption_noise_mode,
+            noise_estimate: self.noise_estimate,
+            ..RLWECryptoParameters::default()
+        })
+    }
+}
@@ -1,7 +1,33 @@
+use crate::ActingPrimitive;
+use crate::arena::{Arena, ArenaConfig, ArenaKind, ArenaLease};
+use crate::ciphertext::Ciphertext;
+use crate::constants::{
+    BASE_NUM_LEVELS_TO_DROP, DecryptionNoiseMode, EncryptionTechnique, ExecutionMode,
+    PolynomialRingFormat,
+};
+use crate::core::lattice::dcrt_poly::DcrtPoly;
 use crate::core::lattice::params::ElementParams;
-use crate::encoding::{EncodingParams, PlaintextEncodings};
+use crate::core::math::{DiscreteGaussian, TernaryUniform};
+use crate::crypto_object::CryptoObject;
+use crate::encoding::{
+    CkksPlaintext, CoefficientPlaintext, EncodingParams, PackedPlaintext, PlaintextEncodings,
+    PlaintextParams,
+};
+use crate::error::{Error, Result};
+use crate::parallel::ParallelConfig;
+use crate::pke::decryption::phase;
+use crate::pke::encryption_pool::{in_format_of, with_gaussian};
+use crate::pke::{
+    CryptoParametersRns, EvalKey, FastRotationPrecompute, KeyPair, KeyPlan, PrivateKey, PublicKey,
+    RnsDecoderStd, UsageTrackedKeys, ZeroEncryption, automorphism_index, compress, key_switch,
+    level_reduce, tensor_product, tensor_square, tower_count,
+};
 use crypto_bigint::{Odd, U64};
-use std::sync::RwLock;
+use num::{BigInt, Integer, ToPrimitive, Zero};
+use rand::CryptoRng;
+use std::collections::{BTreeMap, VecDeque};
+use std::ops::Range;
+use std::sync::{Arc, RwLock};
 
 use crate::constants::PlaintextEncodingsType;
 use derive_more::Display;
@@ -9,16 +35,1938 @@ use serde::{Deserialize, Serialize};
 
 pub(crate) static ALL_CRYPTO_CONTEXTS: RwLock<Vec<CryptoContext>> = RwLock::new(Vec::new());
 
-#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Display, Deserialize, Serialize)]
+/// The relinearization keys by key tag, the key for `s^i` at index `i - 2`
+static EVAL_MULT_KEYS: RwLock<BTreeMap<String, Vec<EvalKey>>> = RwLock::new(BTreeMap::new());
+
+/// The automorphism keys by key tag and automorphism index, remembering
+/// which ones were applied
+static EVAL_AUTOMORPHISM_KEYS: RwLock<BTreeMap<String, UsageTrackedKeys<EvalKey>>> =
+    RwLock::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Display, Deserialize, Serialize)]
 #[display(
     "CryptoContext{{ element_params: {element_params}, encoding_params: {encoding_params} }}"
 )]
 pub struct CryptoContext {
     pub element_params: ElementParams,
     pub encoding_params: EncodingParams,
+    #[serde(skip)]
+    parallel_config: Option<ParallelConfig>,
+    #[serde(skip)]
+    arena: Option<Arc<Arena>>,
 }
 
 impl CryptoContext {
+    /// Run this context's operations with `config` instead of the global configuration
+    pub fn with_parallel_config(mut self, config: ParallelConfig) -> Self {
+        self.parallel_config = Some(config);
+        self
+    }
+
+    /// The parallel configuration used by this context
+    pub fn parallel_config(&self) -> ParallelConfig {
+        self.parallel_config
+            .unwrap_or_else(crate::parallel::global_config)
+    }
+
+    /// Cap the plaintexts and ciphertexts this context keeps alive, see
+    /// [`crate::arena`]
+    pub fn with_arena(mut self, config: ArenaConfig) -> Self {
+        self.arena = Some(Arena::new(config));
+        self
+    }
+
+    /// The slots of a capped context
+    pub fn arena(&self) -> Option<&Arena> {
+        self.arena.as_deref()
+    }
+
+    /// A slot for a new value of `kind`, empty for uncapped contexts
+    pub(crate) fn lease(&self, kind: ArenaKind) -> Result<ArenaLease> {
+        self.arena
+            .as_ref()
+            .map_or(Ok(ArenaLease::default()), |a| a.lease(kind))
+    }
+
+    /// Generate a secret key following the distribution of `params` and its
+    /// public key `(-a s + e, a)`, both in evaluation format and tagged with
+    /// a fresh random key tag
+    pub fn key_gen(&self, params: &CryptoParametersRns) -> Result<KeyPair> {
+        self.key_gen_with_rng(params, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::key_gen`] drawing randomness from `rng`
+    pub fn key_gen_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        rng: &mut impl CryptoRng,
+    ) -> Result<KeyPair> {
+        let rlwe = &params.rlwe_crypto_parameters;
+        let secret = self.secret_key_gen(params, rng)?;
+        let ZeroEncryption { c0, c1 } =
+            ZeroEncryption::with_secret_key_and_rng(&secret, rlwe.discrete_gaussian_std_dev, rng)?;
+        let key_tag = format!("{:016x}{:016x}", rng.next_u64(), rng.next_u64());
+        KeyPair::new(
+            PublicKey::new(self.clone(), [c0, c1])?.with_key_tag(&key_tag),
+            PrivateKey::new(self.clone(), secret, rlwe.secret_key_distribution)
+                .with_key_tag(key_tag),
+        )
+    }
+
+    /// A secret following the distribution of `params`, in evaluation format
+    pub(crate) fn secret_key_gen(
+        &self,
+        params: &CryptoParametersRns,
+        rng: &mut impl CryptoRng,
+    ) -> Result<DcrtPoly> {
+        let rlwe = &params.rlwe_crypto_parameters;
+        let towers = &params.dcrt_element_params;
+        let order = towers.params().front().map(|p| p.cyclotomic_order);
+        if order != Some(self.element_params.cyclotomic_order) {
+            return Err(Error::CcParams(format!(
+                "the towers do not match the cyclotomic order {} of the context",
+                self.element_params.cyclotomic_order
+            )));
+        }
+
+        let mut secret = DcrtPoly::zero(towers.clone());
+        match TernaryUniform::for_secret_key_distribution(rlwe.secret_key_distribution) {
+            Some(ternary) => secret.fill_ternary_with_rng(&ternary, &mut *rng)?,
+            None => with_gaussian(rlwe.discrete_gaussian_std_dev, |gaussian| {
+                secret.fill_discrete_gaussian_with_rng(gaussian, &mut *rng)
+            })?,
+        }
+        secret.switch_format();
+        Ok(secret)
+    }
+
+    /// Encrypt `plaintext` under `public_key` with the standard technique.
+    ///
+    /// Integer encodings are scaled by `floor(Q / t)` as in BFV, CKKS
+    /// plaintexts are already scaled and are added as they are.
+    pub fn encrypt(
+        &self,
+        params: &CryptoParametersRns,
+        public_key: &PublicKey,
+        plaintext: &PlaintextEncodings,
+    ) -> Result<Ciphertext> {
+        self.encrypt_with_rng(params, public_key, plaintext, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::encrypt`] drawing randomness from `rng`
+    pub fn encrypt_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        public_key: &PublicKey,
+        plaintext: &PlaintextEncodings,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.check_encryption(params, public_key.get_crypto_context())?;
+        let rlwe = &params.rlwe_crypto_parameters;
+        let zero = ZeroEncryption::with_public_key_and_rng(
+            public_key.elements(),
+            rlwe.discrete_gaussian_std_dev,
+            rlwe.secret_key_distribution,
+            rng,
+        )?;
+        let mut ciphertext = self.add_plaintext(zero, plaintext)?;
+        ciphertext.set_key_tag(public_key.key_tag());
+        Ok(ciphertext)
+    }
+
+    /// Encrypt `plaintext` under the secret of `private_key`, see
+    /// [`CryptoContext::encrypt`]
+    pub fn encrypt_with_secret(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        plaintext: &PlaintextEncodings,
+    ) -> Result<Ciphertext> {
+        self.encrypt_with_secret_and_rng(params, private_key, plaintext, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::encrypt_with_secret`] drawing randomness from `rng`
+    pub fn encrypt_with_secret_and_rng(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        plaintext: &PlaintextEncodings,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.check_encryption(params, private_key.get_crypto_context())?;
+        let zero = ZeroEncryption::with_secret_key_and_rng(
+            private_key.secret(),
+            params.rlwe_crypto_parameters.discrete_gaussian_std_dev,
+            rng,
+        )?;
+        let mut ciphertext = self.add_plaintext(zero, plaintext)?;
+        ciphertext.set_key_tag(private_key.key_tag());
+        Ok(ciphertext)
+    }
+
+    /// `ciphertext` plus a fresh encryption of zero under `public_key`, which
+    /// decrypts to the same message but cannot be linked to `ciphertext`
+    /// without the secret key.
+    ///
+    /// The noise of the result still contains the noise of `ciphertext`, so
+    /// the holder of the secret key may tell them apart, see
+    /// [`CryptoContext::flood_noise`] to hide it. Ciphertexts after an
+    /// unrelinearized product have to be relinearized first. Compressed
+    /// ciphertexts get the encryption of zero at their towers.
+    pub fn rerandomize(
+        &self,
+        params: &CryptoParametersRns,
+        public_key: &PublicKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        self.rerandomize_with_rng(params, public_key, ciphertext, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::rerandomize`] drawing randomness from `rng`
+    pub fn rerandomize_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        public_key: &PublicKey,
+        ciphertext: &Ciphertext,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        let std_dev = params.rlwe_crypto_parameters.discrete_gaussian_std_dev;
+        self.add_zero_encryption(params, public_key, ciphertext, std_dev, rng)
+    }
+
+    /// `ciphertext` plus an encryption of zero under `public_key` with
+    /// errors of standard deviation `std_dev`
+    pub(crate) fn add_zero_encryption(
+        &self,
+        params: &CryptoParametersRns,
+        public_key: &PublicKey,
+        ciphertext: &Ciphertext,
+        std_dev: f64,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.check_encryption(params, public_key.get_crypto_context())?;
+        self.check_operand(ciphertext)?;
+        if public_key.key_tag() != ciphertext.key_tag() {
+            return Err(Error::CcParams(format!(
+                "the public key of key tag `{}` does not encrypt under key tag `{}`",
+                public_key.key_tag(),
+                ciphertext.key_tag()
+            )));
+        }
+        let [c0, c1] = ciphertext.elements() else {
+            return Err(Error::CiphertextFormat(format!(
+                "cannot rerandomize a ciphertext of {} polynomials, relinearize it first",
+                ciphertext.elements().len()
+            )));
+        };
+        let ZeroEncryption { c0: z0, c1: z1 } = ZeroEncryption::with_public_key_and_rng(
+            public_key.elements(),
+            std_dev,
+            params.rlwe_crypto_parameters.secret_key_distribution,
+            rng,
+        )?;
+        let towers = tower_count(ciphertext);
+        let mut elements = [c0.clone(), c1.clone()];
+        for (element, zero) in elements.iter_mut().zip([z0, z1]) {
+            *element += &in_format_of(zero.shrink(towers), element);
+        }
+        let mut result = ciphertext.try_clone()?;
+        result.replace_elements(
+            "rerandomize",
+            elements.to_vec(),
+            ciphertext.scaling_factor(),
+            &[ciphertext],
+        );
+        Ok(result)
+    }
+
+    /// [`CryptoContext::rerandomize`] `ciphertext` and flood its noise, so
+    /// its decryptor learns nothing about the circuit that computed it
+    /// beyond the message.
+    ///
+    /// `noise_bits` bounds the bits of the noise of `ciphertext` in its
+    /// coefficients, the flooding noise is Gaussian with `2^noise_bits` times
+    /// the standard deviation of [`CcParams::flooding_std_dev`], whose
+    /// statistical security and number of queries set the statistical
+    /// distance. Fails if the flooding noise would not stay below the
+    /// decryption bound, for BFV `Q / 2t` at the towers of `ciphertext`.
+    pub fn flood_noise(
+        &self,
+        params: &CryptoParametersRns,
+        public_key: &PublicKey,
+        ciphertext: &Ciphertext,
+        noise_bits: f64,
+    ) -> Result<Ciphertext> {
+        self.flood_noise_with_rng(params, public_key, ciphertext, noise_bits, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::flood_noise`] drawing randomness from `rng`
+    pub fn flood_noise_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        public_key: &PublicKey,
+        ciphertext: &Ciphertext,
+        noise_bits: f64,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        let std_dev = 2f64.powf(noise_bits)
+            * params
+                .rlwe_crypto_parameters
+                .discrete_gaussian_std_dev_with_flooding;
+        let q = ciphertext
+            .element_params()
+            .ciphertext_composite_modulus()
+            .to_f64()
+            .unwrap_or(f64::INFINITY);
+        let bound = match ciphertext.encoding_type() {
+            PlaintextEncodingsType::CkksPacked => q / 2.0,
+            _ => q / (2 * self.encoding_params.plaintext_modulus.get().to_primitive()) as f64,
+        };
+        // Samples stay within 12 standard deviations but with probability
+        // about 2^-100
+        if !(std_dev.is_finite() && 12.0 * std_dev < bound) {
+            return Err(Error::CcParams(format!(
+                "flooding {noise_bits} bits of noise exceeds the decryption bound of {:.1} bits",
+                bound.log2()
+            )));
+        }
+        let mut result = self.rerandomize_with_rng(params, public_key, ciphertext, rng)?;
+        let mut elements = result.elements().to_vec();
+        let mut noise = elements[0].clone();
+        with_gaussian(std_dev, |gaussian| {
+            noise.fill_discrete_gaussian_with_rng(gaussian, &mut *rng)
+        })?;
+        let noise = in_format_of(noise, &elements[0]);
+        elements[0] += &noise;
+        let scaling_factor = result.scaling_factor();
+        result.replace_elements("flood_noise", elements, scaling_factor, &[ciphertext]);
+        Ok(result)
+    }
+
+    /// Decrypt `ciphertext` with `private_key` and decode it modulo the
+    /// plaintext modulus of the context.
+    ///
+    /// Integer plaintexts are decoded as encrypted by
+    /// [`CryptoContext::encrypt`], i.e. scaled by `floor(Q / t)`. When the
+    /// noise has grown past the decryption bound this fails with
+    /// [`Error::DecryptionFailure`] instead of returning wrong values, see
+    /// [`RnsDecoder`]. CKKS ciphertexts decode into approximate values
+    /// divided by their scaling factor.
+    pub fn decrypt(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<PlaintextEncodings> {
+        if private_key.get_crypto_context() != self || ciphertext.get_crypto_context() != self {
+            return Err(Error::CcParams(
+                "the key or the ciphertext belongs to another crypto context".to_string(),
+            ));
+        }
+        let encoding_type = ciphertext.encoding_type();
+        if encoding_type == PlaintextEncodingsType::CkksPacked {
+            return self.decrypt_ckks(private_key, ciphertext, None);
+        }
+        if !matches!(
+            encoding_type,
+            PlaintextEncodingsType::CoefficientPacked | PlaintextEncodingsType::Packed
+        ) {
+            return Err(Error::CcParams(format!(
+                "decrypting {encoding_type} plaintexts is not supported yet"
+            )));
+        }
+        let lease = self.lease(ArenaKind::Plaintext)?;
+        // Compressed ciphertexts decrypt under the towers they kept
+        let towers = ciphertext.element_params().params().len();
+        let decrypted = phase(ciphertext.elements(), &private_key.secret().shrink(towers))?;
+        self.decode_phase(ciphertext, &decrypted, lease)
+    }
+
+    /// The plaintext of `ciphertext` from its decrypted `c0 + c1 s + ...`,
+    /// holding the plaintext slot `lease`
+    pub(crate) fn decode_phase(
+        &self,
+        ciphertext: &Ciphertext,
+        decrypted: &DcrtPoly,
+        lease: ArenaLease,
+    ) -> Result<PlaintextEncodings> {
+        let encoding_type = ciphertext.encoding_type();
+        if encoding_type == PlaintextEncodingsType::CkksPacked {
+            let mut decrypted = decrypted.clone();
+            if decrypted.format() == PolynomialRingFormat::Evaluation {
+                decrypted.switch_format();
+            }
+            let params = PlaintextParams {
+                scaling_factor: ciphertext.scaling_factor(),
+                slots: self.encoding_params.batch_size,
+                encoding_params: self.encoding_params,
+                ..Default::default()
+            };
+            return Ok(PlaintextEncodings::Ckks(CkksPlaintext::decode(
+                &decrypted.to_signed(),
+                params,
+                self.element_params.cyclotomic_order,
+            )?)
+            .with_lease(lease));
+        }
+        if !matches!(
+            encoding_type,
+            PlaintextEncodingsType::CoefficientPacked | PlaintextEncodingsType::Packed
+        ) {
+            return Err(Error::CcParams(format!(
+                "decrypting {encoding_type} plaintexts is not supported yet"
+            )));
+        }
+        let t = self.encoding_params.plaintext_modulus.get().to_primitive();
+        let decoder = RnsDecoderStd::bfv(ciphertext.element_params(), t)?;
+        let mut values = decoder.decode(decrypted)?;
+        values.truncate(self.element_params.ring_dimension);
+        let params = PlaintextParams {
+            scaling_factor: ciphertext.scaling_factor(),
+            encoding_params: self.encoding_params,
+            ..Default::default()
+        };
+        let order = self.element_params.cyclotomic_order;
+        let plaintext = if encoding_type == PlaintextEncodingsType::Packed {
+            PlaintextEncodings::Packed(PackedPlaintext::decode(
+                &values,
+                PlaintextParams {
+                    slots: self.element_params.ring_dimension,
+                    ..params
+                },
+                order,
+            )?)
+        } else {
+            PlaintextEncodings::Coefficient(CoefficientPlaintext::new(&values, params, order)?)
+        };
+        Ok(plaintext.with_lease(lease))
+    }
+
+    /// The invariant noise budget of a BFV ciphertext in bits, as SEAL
+    /// reports it: `log2(Q) - log2(|t (c0 + c1 s) mod Q|) - 1`, the bits the
+    /// noise can still grow by before decryption fails, zero once it has.
+    ///
+    /// Unlike [`CryptoContext::decrypt`] this reveals the size of the noise
+    /// and does not run in constant time, it is meant for choosing
+    /// parameters and debugging.
+    pub fn noise_budget_bits(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<f64> {
+        if private_key.get_crypto_context() != self || ciphertext.get_crypto_context() != self {
+            return Err(Error::CcParams(
+                "the key or the ciphertext belongs to another crypto context".to_string(),
+            ));
+        }
+        if ciphertext.encoding_type() == PlaintextEncodingsType::CkksPacked {
+            return Err(Error::CcParams(
+                "CKKS ciphertexts have no noise budget, their noise is part of the message"
+                    .to_string(),
+            ));
+        }
+        let towers = ciphertext.element_params().params().len();
+        let mut decrypted = phase(ciphertext.elements(), &private_key.secret().shrink(towers))?;
+        if decrypted.format() == PolynomialRingFormat::Evaluation {
+            decrypted.switch_format();
+        }
+        let q = BigInt::from(
+            ciphertext
+                .element_params()
+                .ciphertext_composite_modulus()
+                .clone(),
+        );
+        let half_q = &q >> 1u32;
+        let t = BigInt::from(self.encoding_params.plaintext_modulus.get().to_primitive());
+        let norm = decrypted
+            .to_signed()
+            .iter()
+            .map(|x| {
+                let v = (&t * x).mod_floor(&q);
+                if v > half_q { &q - v } else { v }
+            })
+            .max()
+            .unwrap_or_default();
+        if norm.is_zero() {
+            return Ok(log2(&q) - 1.0);
+        }
+        Ok((log2(&q) - log2(&norm) - 1.0).max(0.0))
+    }
+
+    /// [`CryptoContext::decrypt`] following the decryption noise mode of
+    /// `params` for CKKS ciphertexts.
+    ///
+    /// With [`DecryptionNoiseMode::NoiseFlooding`] in
+    /// [`ExecutionMode::Evaluation`] the decrypted coefficients get Gaussian
+    /// noise of standard deviation `2^noise_estimate` times the flooding
+    /// standard deviation of [`CcParams::flooding_std_dev`] before decoding,
+    /// so decryptions reveal nothing about the secret key beyond the
+    /// statistical security, see Li and Micciancio, "On the Security of
+    /// Homomorphic Encryption on Approximate Numbers" (EUROCRYPT 2021). The
+    /// noise estimate comes from [`CryptoContext::estimate_noise`] on the
+    /// same computation in [`ExecutionMode::NoiseEstimation`], which does
+    /// not decrypt CKKS ciphertexts.
+    pub fn decrypt_with_params(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<PlaintextEncodings> {
+        self.decrypt_with_params_with_rng(params, private_key, ciphertext, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::decrypt_with_params`] drawing the flooding noise from
+    /// `rng`
+    pub fn decrypt_with_params_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+        rng: &mut impl CryptoRng,
+    ) -> Result<PlaintextEncodings> {
+        let rlwe = &params.rlwe_crypto_parameters;
+        if ciphertext.encoding_type() != PlaintextEncodingsType::CkksPacked {
+            return self.decrypt(private_key, ciphertext);
+        }
+        if rlwe.execution_mode == ExecutionMode::NoiseEstimation {
+            return Err(Error::CcParams(
+                "parameters in noise estimation mode only estimate the noise of CKKS ciphertexts"
+                    .to_string(),
+            ));
+        }
+        if rlwe.decryption_noise_mode == DecryptionNoiseMode::FixedNoise {
+            return self.decrypt(private_key, ciphertext);
+        }
+        if private_key.get_crypto_context() != self || ciphertext.get_crypto_context() != self {
+            return Err(Error::CcParams(
+                "the key or the ciphertext belongs to another crypto context".to_string(),
+            ));
+        }
+        if !(rlwe.noise_estimate.is_finite() && rlwe.noise_estimate > 0.0) {
+            return Err(Error::CcParams(format!(
+                "noise flooding decryption needs the positive noise estimate of a run in noise \
+                 estimation mode, got {}",
+                rlwe.noise_estimate
+            )));
+        }
+        with_gaussian(
+            2f64.powf(rlwe.noise_estimate) * rlwe.discrete_gaussian_std_dev_with_flooding,
+            |flooding| self.decrypt_ckks(private_key, ciphertext, Some((flooding, rng))),
+        )?
+    }
+
+    /// Decode `c0 + c1 s + ...` into [`EncodingParams::batch_size`] slots,
+    /// all of them when it is zero, dividing by the scaling factor of the
+    /// ciphertext, after adding the `flooding` noise if any
+    pub(crate) fn decrypt_ckks(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+        flooding: Option<(&DiscreteGaussian, &mut dyn CryptoRng)>,
+    ) -> Result<PlaintextEncodings> {
+        let lease = self.lease(ArenaKind::Plaintext)?;
+        let towers = ciphertext.element_params().params().len();
+        let mut decrypted = phase(ciphertext.elements(), &private_key.secret().shrink(towers))?;
+        if decrypted.format() == PolynomialRingFormat::Evaluation {
+            decrypted.switch_format();
+        }
+        if let Some((gaussian, rng)) = flooding {
+            let mut noise = decrypted.clone();
+            noise.fill_discrete_gaussian_with_rng(gaussian, rng);
+            decrypted += &in_format_of(noise, &decrypted);
+        }
+        self.decode_phase(ciphertext, &decrypted, lease)
+    }
+
+    /// The sum of two ciphertexts at the same level.
+    ///
+    /// A ciphertext with more polynomials, e.g. before relinearization, adds
+    /// its extra polynomials to the sum. CKKS ciphertexts must have the same
+    /// scaling factor, see [`align_scales`](crate::pke::align_scales).
+    pub fn eval_add(&self, a: &Ciphertext, b: &Ciphertext) -> Result<Ciphertext> {
+        self.check_operands(a, b)?;
+        let mut elements = a.elements().to_vec();
+        for (i, e) in b.elements().iter().enumerate() {
+            match elements.get_mut(i) {
+                Some(sum) => *sum += e,
+                None => elements.push(e.clone()),
+            }
+        }
+        let mut result = a.try_clone()?;
+        result.set_noise_scale_degree(a.noise_scale_degree().max(b.noise_scale_degree()));
+        result.replace_elements("eval_add", elements, a.scaling_factor(), &[a, b]);
+        Ok(result)
+    }
+
+    /// The difference `a - b` of two ciphertexts at the same level, see
+    /// [`CryptoContext::eval_add`]
+    pub fn eval_sub(&self, a: &Ciphertext, b: &Ciphertext) -> Result<Ciphertext> {
+        self.check_operands(a, b)?;
+        let mut elements = a.elements().to_vec();
+        for (i, e) in b.elements().iter().enumerate() {
+            match elements.get_mut(i) {
+                Some(difference) => *difference -= e,
+                None => elements.push(-e),
+            }
+        }
+        let mut result = a.try_clone()?;
+        result.set_noise_scale_degree(a.noise_scale_degree().max(b.noise_scale_degree()));
+        result.replace_elements("eval_sub", elements, a.scaling_factor(), &[a, b]);
+        Ok(result)
+    }
+
+    /// The negation of `a`
+    pub fn eval_negate(&self, a: &Ciphertext) -> Result<Ciphertext> {
+        self.check_operand(a)?;
+        let elements = a.elements().iter().map(|e| -e).collect();
+        let mut result = a.try_clone()?;
+        result.replace_elements("eval_negate", elements, a.scaling_factor(), &[a]);
+        Ok(result)
+    }
+
+    /// The sum of a ciphertext and a plaintext, scaled as in
+    /// [`CryptoContext::encrypt`] for the level of the ciphertext
+    pub fn eval_add_plaintext(
+        &self,
+        a: &Ciphertext,
+        plaintext: &PlaintextEncodings,
+    ) -> Result<Ciphertext> {
+        let scaled = self.check_plaintext_operand(a, plaintext)?;
+        let mut elements = a.elements().to_vec();
+        elements[0] += &scaled;
+        let mut result = a.try_clone()?;
+        result.replace_elements("eval_add_plaintext", elements, a.scaling_factor(), &[a]);
+        Ok(result)
+    }
+
+    /// The difference of a ciphertext and a plaintext, see
+    /// [`CryptoContext::eval_add_plaintext`]
+    pub fn eval_sub_plaintext(
+        &self,
+        a: &Ciphertext,
+        plaintext: &PlaintextEncodings,
+    ) -> Result<Ciphertext> {
+        let scaled = self.check_plaintext_operand(a, plaintext)?;
+        let mut elements = a.elements().to_vec();
+        elements[0] -= &scaled;
+        let mut result = a.try_clone()?;
+        result.replace_elements("eval_sub_plaintext", elements, a.scaling_factor(), &[a]);
+        Ok(result)
+    }
+
+    /// The product of a ciphertext and a plaintext, whose coefficients are
+    /// multiplied in unscaled. CKKS products carry the product of the
+    /// scaling factors.
+    pub fn eval_mult_plaintext(
+        &self,
+        a: &Ciphertext,
+        plaintext: &PlaintextEncodings,
+    ) -> Result<Ciphertext> {
+        self.check_plaintext_multiplicand(a, plaintext)?;
+        let mut elements = a.elements().to_vec();
+        let mut factor = self.lifted_plaintext(plaintext, &BigInt::from(1), &elements[0])?;
+        if factor.format() == PolynomialRingFormat::Coefficient {
+            factor.switch_format();
+        }
+        for e in &mut elements {
+            let mut product = in_format_of(e.clone(), &factor);
+            product *= &factor;
+            *e = in_format_of(product, e);
+        }
+        let mut result = a.try_clone()?;
+        if a.encoding_type() == PlaintextEncodingsType::CkksPacked {
+            result.set_noise_scale_degree(a.noise_scale_degree() + 1);
+        }
+        result.replace_elements(
+            "eval_mult_plaintext",
+            elements,
+            product_scaling_factor(a, plaintext.params().scaling_factor),
+            &[a],
+        );
+        Ok(result)
+    }
+
+    /// Generate the relinearization key of `private_key`, switching `s^2`
+    /// back to `s` with the key switching technique of `params`, and store it
+    /// under the key tag of `private_key`
+    pub fn eval_mult_key_gen(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+    ) -> Result<EvalKey> {
+        self.eval_mult_key_gen_with_rng(params, private_key, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::eval_mult_key_gen`] drawing randomness from `rng`
+    pub fn eval_mult_key_gen_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<EvalKey> {
+        let mut keys = self.power_keys(params, private_key, 2, rng)?;
+        let key = keys.pop().expect("one key per power");
+        Self::insert_eval_mult_keys(vec![key.clone()])?;
+        Ok(key)
+    }
+
+    /// Generate the relinearization keys of `private_key` switching `s^2` up
+    /// to `s^k` back to `s` for `k` the
+    /// `max_relinearization_secret_key_power` of `params`, and store them
+    /// under the key tag of `private_key`
+    pub fn eval_mult_keys_gen(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+    ) -> Result<Vec<EvalKey>> {
+        self.eval_mult_keys_gen_with_rng(params, private_key, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::eval_mult_keys_gen`] drawing randomness from `rng`
+    pub fn eval_mult_keys_gen_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Vec<EvalKey>> {
+        let max_power = params
+            .rlwe_crypto_parameters
+            .max_relinearization_secret_key_power
+            .max(2);
+        let keys = self.power_keys(params, private_key, max_power, rng)?;
+        Self::insert_eval_mult_keys(keys.clone())?;
+        Ok(keys)
+    }
+
+    /// Store relinearization keys, e.g. deserialized ones, under their key
+    /// tag, the key for `s^i` at index `i - 2`
+    pub fn insert_eval_mult_keys(keys: Vec<EvalKey>) -> Result<()> {
+        let Some(first) = keys.first() else {
+            return Ok(());
+        };
+        if keys.iter().any(|k| k.key_tag() != first.key_tag()) {
+            return Err(Error::CcParams(
+                "relinearization keys of different key tags".to_string(),
+            ));
+        }
+        EVAL_MULT_KEYS
+            .write()?
+            .insert(first.key_tag().to_string(), keys);
+        Ok(())
+    }
+
+    /// The stored relinearization keys of `key_tag`
+    pub fn get_eval_mult_keys(key_tag: &str) -> Result<Option<Vec<EvalKey>>> {
+        Ok(EVAL_MULT_KEYS.read()?.get(key_tag).cloned())
+    }
+
+    /// Drop every stored relinearization key
+    pub fn clear_eval_mult_keys() -> Result<()> {
+        EVAL_MULT_KEYS.write()?.clear();
+        Ok(())
+    }
+
+    /// Generate the keys rotating the slots by each of `indices`, negative
+    /// indices rotating right, and store them under the key tag of
+    /// `private_key`, see [`CryptoContext::eval_automorphism_key_gen`]
+    pub fn eval_at_index_key_gen(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        indices: &[i32],
+    ) -> Result<BTreeMap<u64, EvalKey>> {
+        self.eval_at_index_key_gen_with_rng(params, private_key, indices, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::eval_at_index_key_gen`] drawing randomness from `rng`
+    pub fn eval_at_index_key_gen_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        indices: &[i32],
+        rng: &mut impl CryptoRng,
+    ) -> Result<BTreeMap<u64, EvalKey>> {
+        let order = self.element_params.cyclotomic_order;
+        let indices = indices
+            .iter()
+            .map(|&i| automorphism_index(i64::from(i), order))
+            .filter(|i| !matches!(i, Ok(1)))
+            .collect::<Result<Vec<_>>>()?;
+        self.eval_automorphism_key_gen_with_rng(params, private_key, &indices, rng)
+    }
+
+    /// Generate the keys [`CryptoContext::eval_sum`] needs over every slot,
+    /// the rotations by each power of two below the number of slots
+    pub fn eval_sum_key_gen(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+    ) -> Result<BTreeMap<u64, EvalKey>> {
+        self.eval_sum_key_gen_with_rng(params, private_key, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::eval_sum_key_gen`] drawing randomness from `rng`
+    pub fn eval_sum_key_gen_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<BTreeMap<u64, EvalKey>> {
+        let plan = KeyPlan::new(self.element_params.cyclotomic_order)?.sum(self.slots());
+        let indices = plan.indices().iter().copied().collect::<Vec<_>>();
+        self.eval_automorphism_key_gen_with_rng(params, private_key, &indices, rng)
+    }
+
+    /// Generate the key of the automorphism `X -> X^(m - 1)` conjugating the
+    /// slots of CKKS ciphertexts and store it under the key tag of
+    /// `private_key`
+    pub fn eval_conjugate_key_gen(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+    ) -> Result<EvalKey> {
+        self.eval_conjugate_key_gen_with_rng(params, private_key, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::eval_conjugate_key_gen`] drawing randomness from `rng`
+    pub fn eval_conjugate_key_gen_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<EvalKey> {
+        let index = self.conjugation_index();
+        let mut keys =
+            self.eval_automorphism_key_gen_with_rng(params, private_key, &[index], rng)?;
+        Ok(keys
+            .remove(&index)
+            .expect("the key of every requested index is generated"))
+    }
+
+    /// Generate the keys of the automorphisms `X -> X^k` for every `k` in
+    /// `indices`, switching the permuted secret back to the secret of
+    /// `private_key`. The keys are returned by automorphism index and merged
+    /// into the keys stored under the key tag of `private_key`.
+    pub fn eval_automorphism_key_gen(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        indices: &[u64],
+    ) -> Result<BTreeMap<u64, EvalKey>> {
+        self.eval_automorphism_key_gen_with_rng(params, private_key, indices, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::eval_automorphism_key_gen`] drawing randomness from
+    /// `rng`
+    pub fn eval_automorphism_key_gen_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        indices: &[u64],
+        rng: &mut impl CryptoRng,
+    ) -> Result<BTreeMap<u64, EvalKey>> {
+        if private_key.get_crypto_context() != self {
+            return Err(Error::CcParams(
+                "the key belongs to another crypto context".to_string(),
+            ));
+        }
+        let secret = private_key.secret();
+        let mut keys = BTreeMap::new();
+        for &index in indices {
+            self.check_automorphism_index(index)?;
+            if keys.contains_key(&index) {
+                continue;
+            }
+            let permuted = secret.automorphism(index as usize);
+            let key = key_switch::key_gen(self.clone(), params, &permuted, secret, &mut *rng)?
+                .with_key_tag(private_key.key_tag());
+            keys.insert(index, key);
+        }
+        Self::insert_eval_automorphism_keys(keys.clone())?;
+        Ok(keys)
+    }
+
+    /// Merge automorphism keys, e.g. deserialized ones, into the keys stored
+    /// under their key tag
+    pub fn insert_eval_automorphism_keys(keys: BTreeMap<u64, EvalKey>) -> Result<()> {
+        let Some(first) = keys.values().next() else {
+            return Ok(());
+        };
+        let key_tag = first.key_tag().to_string();
+        if keys.values().any(|k| k.key_tag() != key_tag) {
+            return Err(Error::CcParams(
+                "automorphism keys of different key tags".to_string(),
+            ));
+        }
+        let context = first.get_crypto_context().clone();
+        let mut stored = EVAL_AUTOMORPHISM_KEYS.write()?;
+        let stored = stored.entry(key_tag).or_default();
+        // Keys of another context under the same tag are replaced, not merged
+        stored.retain(|_, k| k.get_crypto_context() == &context);
+        stored.extend(keys);
+        Ok(())
+    }
+
+    /// The stored automorphism keys of `key_tag` by automorphism index
+    pub fn get_eval_automorphism_keys(key_tag: &str) -> Result<Option<BTreeMap<u64, EvalKey>>> {
+        Ok(EVAL_AUTOMORPHISM_KEYS
+            .read()?
+            .get(key_tag)
+            .map(UsageTrackedKeys::to_map))
+    }
+
+    /// The automorphism indices of the keys stored under `key_tag` that no
+    /// rotation, summation or other automorphism has applied since they
+    /// were stored, in ascending order
+    pub fn unused_automorphism_keys(key_tag: &str) -> Result<Vec<u64>> {
+        Ok(EVAL_AUTOMORPHISM_KEYS
+            .read()?
+            .get(key_tag)
+            .map_or_else(Vec::new, UsageTrackedKeys::unused))
+    }
+
+    /// Drop the keys stored under `key_tag` that `plan` does not need,
+    /// returning the removed automorphism indices in ascending order
+    pub fn prune_automorphism_keys(key_tag: &str, plan: &KeyPlan) -> Result<Vec<u64>> {
+        let mut stored = EVAL_AUTOMORPHISM_KEYS.write()?;
+        let Some(keys) = stored.get_mut(key_tag) else {
+            return Ok(Vec::new());
+        };
+        if keys.to_map().values().any(|k| {
+            k.get_crypto_context().element_params.cyclotomic_order != plan.cyclotomic_order()
+        }) {
+            return Err(Error::CcParams(format!(
+                "the keys of key tag `{key_tag}` are not of cyclotomic order {}",
+                plan.cyclotomic_order()
+            )));
+        }
+        Ok(keys.prune(plan))
+    }
+
+    /// Mark the stored key of `key` for `index` as used
+    fn record_automorphism_key_use(key: &EvalKey, index: u64) -> Result<()> {
+        if let Some(keys) = EVAL_AUTOMORPHISM_KEYS.read()?.get(key.key_tag()) {
+            keys.get(index);
+        }
+        Ok(())
+    }
+
+    /// Drop every stored automorphism key
+    pub fn clear_eval_automorphism_keys() -> Result<()> {
+        EVAL_AUTOMORPHISM_KEYS.write()?.clear();
+        Ok(())
+    }
+
+    /// Apply the automorphism `X -> X^index` to a relinearized ciphertext and
+    /// switch it back to its secret with the key stored for `index` under its
+    /// key tag
+    pub fn eval_automorphism(&self, ciphertext: &Ciphertext, index: u64) -> Result<Ciphertext> {
+        self.check_operand(ciphertext)?;
+        self.check_automorphism_index(index)?;
+        let keys = Self::get_eval_automorphism_keys(ciphertext.key_tag())?.unwrap_or_default();
+        let key = keys.get(&index).ok_or_else(|| {
+            Error::CcParams(format!(
+                "no automorphism key for index {index} and key tag `{}`",
+                ciphertext.key_tag()
+            ))
+        })?;
+        self.automorphism(ciphertext, index, key)
+    }
+
+    /// Rotate the slots of a packed ciphertext left by `index`, right for a
+    /// negative `index`, with the automorphism keys stored under its key tag.
+    ///
+    /// Without a key for the exact rotation the rotation is composed from the
+    /// fewest stored rotations, each adding key switching noise.
+    pub fn eval_at_index(&self, ciphertext: &Ciphertext, index: i32) -> Result<Ciphertext> {
+        self.check_packed(ciphertext)?;
+        let mut keys = Self::get_eval_automorphism_keys(ciphertext.key_tag())?.unwrap_or_default();
+        keys.retain(|_, k| k.get_crypto_context() == self);
+        let steps = self.rotation_steps(&keys, index).ok_or_else(|| {
+            Error::CcParams(format!(
+                "no composition of the automorphism keys of key tag `{}` rotates by {index}",
+                ciphertext.key_tag()
+            ))
+        })?;
+        let mut result = ciphertext.try_clone()?;
+        for index in steps {
+            result = self.automorphism(&result, index, &keys[&index])?;
+        }
+        Ok(result)
+    }
+
+    /// Rotate the slots of a packed ciphertext, see
+    /// [`CryptoContext::eval_at_index`]
+    pub fn eval_rotate(&self, ciphertext: &Ciphertext, index: i32) -> Result<Ciphertext> {
+        self.eval_at_index(ciphertext, index)
+    }
+
+    /// Sum `batch_size` consecutive slots of a packed ciphertext into every
+    /// slot, slot `i` holding the sum of slots `i` to `i + batch_size - 1`
+    /// wrapping around. `batch_size` must be a power of two of at most the
+    /// number of slots, with the keys of [`CryptoContext::eval_sum_key_gen`].
+    pub fn eval_sum(&self, ciphertext: &Ciphertext, batch_size: usize) -> Result<Ciphertext> {
+        self.check_packed(ciphertext)?;
+        if !batch_size.is_power_of_two() || batch_size > self.slots() {
+            return Err(Error::CcParams(format!(
+                "cannot sum {batch_size} of {} slots, a power of two is needed",
+                self.slots()
+            )));
+        }
+        let mut result = ciphertext.try_clone()?;
+        let mut rotation = 1;
+        while rotation < batch_size {
+            let rotated = self.eval_at_index(&result, rotation as i32)?;
+            result = self.eval_add(&result, &rotated)?;
+            rotation *= 2;
+        }
+        Ok(result)
+    }
+
+    /// The complex conjugate of every slot of a CKKS ciphertext, with the key
+    /// of [`CryptoContext::eval_conjugate_key_gen`]
+    pub fn eval_conjugate(&self, ciphertext: &Ciphertext) -> Result<Ciphertext> {
+        if ciphertext.encoding_type() != PlaintextEncodingsType::CkksPacked {
+            return Err(Error::CcParams(format!(
+                "cannot conjugate the slots of a {} ciphertext",
+                ciphertext.encoding_type()
+            )));
+        }
+        self.eval_automorphism(ciphertext, self.conjugation_index())
+    }
+
+    /// A plaintext of `values` in the first slots and zeros elsewhere to
+    /// multiply `like` with, CKKS values scaled by its last tower modulus.
+    /// Masks of BFV packed ciphertexts need integer values.
+    pub fn slot_mask(&self, like: &Ciphertext, values: &[f64]) -> Result<PlaintextEncodings> {
+        match like.encoding_type() {
+            PlaintextEncodingsType::CkksPacked => {}
+            PlaintextEncodingsType::Packed => {
+                let integers = values
+                    .iter()
+                    .map(|&v| {
+                        (v.fract() == 0.0 && v.abs() < i64::MAX as f64)
+                            .then_some(v as i64)
+                            .ok_or_else(|| {
+                                Error::CcParams(format!(
+                                    "the slot mask value {v} of a packed ciphertext is not an integer"
+                                ))
+                            })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let params = PlaintextParams {
+                    slots: self.element_params.ring_dimension,
+                    encoding_params: self.encoding_params,
+                    ..Default::default()
+                };
+                let lease = self.lease(ArenaKind::Plaintext)?;
+                return Ok(PlaintextEncodings::Packed(PackedPlaintext::new(
+                    &integers,
+                    params,
+                    self.element_params.cyclotomic_order,
+                )?)
+                .with_lease(lease));
+            }
+            encoding_type => {
+                return Err(Error::CcParams(format!(
+                    "{encoding_type} ciphertexts have no slots to mask"
+                )));
+            }
+        }
+        let last = like
+            .element_params()
+            .params()
+            .back()
+            .ok_or(Error::DcrtElementParamsMismatch)?;
+        let params = PlaintextParams {
+            scaling_factor: last.ciphertext_modulus.get().to_primitive() as f64,
+            slots: self.slots(),
+            encoding_params: self.encoding_params,
+            ..Default::default()
+        };
+        let lease = self.lease(ArenaKind::Plaintext)?;
+        Ok(PlaintextEncodings::Ckks(CkksPlaintext::new(
+            values,
+            params,
+            self.element_params.cyclotomic_order,
+        )?)
+        .with_lease(lease))
+    }
+
+    /// The mask keeping only `slot`, see [`CryptoContext::slot_mask`]
+    pub fn one_hot_mask(&self, like: &Ciphertext, slot: usize) -> Result<PlaintextEncodings> {
+        self.range_mask(like, slot..slot + 1)
+    }
+
+    /// The mask keeping the slots of `range`, see [`CryptoContext::slot_mask`].
+    /// The slots of BFV masks are in the first row.
+    pub fn range_mask(&self, like: &Ciphertext, range: Range<usize>) -> Result<PlaintextEncodings> {
+        if range.start > range.end || range.end > self.slots() {
+            return Err(Error::CcParams(format!(
+                "slots {range:?} are not among {} slots",
+                self.slots()
+            )));
+        }
+        let mut values = vec![0.0; range.end];
+        values[range].fill(1.0);
+        self.slot_mask(like, &values)
+    }
+
+    /// Merge the first slots of packed ciphertexts into the first slots of
+    /// one ciphertext, the first slot of `ciphertexts[i]` landing in slot `i`.
+    ///
+    /// Every ciphertext is masked to its first slot and rotated right by its
+    /// position, so the keys of the rotations by `-1` to `1 - len` are needed.
+    /// The masks multiply CKKS scaling factors by the last tower modulus.
+    pub fn eval_merge(&self, ciphertexts: &[Ciphertext]) -> Result<Ciphertext> {
+        if ciphertexts.len() > self.slots() {
+            return Err(Error::CcParams(format!(
+                "cannot merge {} ciphertexts into {} slots",
+                ciphertexts.len(),
+                self.slots()
+            )));
+        }
+        let placed = ciphertexts
+            .iter()
+            .enumerate()
+            .map(|(i, ciphertext)| {
+                self.check_packed(ciphertext)?;
+                let mask = self.slot_mask(ciphertext, &[1.0])?;
+                let masked = self.eval_mult_plaintext(ciphertext, &mask)?;
+                self.eval_at_index(&masked, -(i as i32))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.eval_add_many(&placed)
+    }
+
+    /// Copy `slot` of a packed ciphertext into every slot, masking it and
+    /// summing all slots with the keys of [`CryptoContext::eval_sum_key_gen`].
+    /// BFV ciphertexts only have the first row of `n / 2` slots filled.
+    pub fn eval_replicate(&self, ciphertext: &Ciphertext, slot: usize) -> Result<Ciphertext> {
+        self.check_packed(ciphertext)?;
+        let mask = self.one_hot_mask(ciphertext, slot)?;
+        self.eval_sum(&self.eval_mult_plaintext(ciphertext, &mask)?, self.slots())
+    }
+
+    /// Decompose `c1` of a relinearized ciphertext once for the rotations of
+    /// [`CryptoContext::eval_fast_rotation`], with the digits of the
+    /// automorphism keys stored under its key tag
+    pub fn eval_fast_rotation_precompute(
+        &self,
+        ciphertext: &Ciphertext,
+    ) -> Result<FastRotationPrecompute> {
+        self.check_operand(ciphertext)?;
+        let [_, c1] = ciphertext.elements() else {
+            return Err(Error::CcParams(format!(
+                "cannot rotate a ciphertext of {} polynomials, relinearize it first",
+                ciphertext.elements().len()
+            )));
+        };
+        let keys = Self::get_eval_automorphism_keys(ciphertext.key_tag())?.unwrap_or_default();
+        let key = keys
+            .values()
+            .find(|k| k.get_crypto_context() == self)
+            .ok_or_else(|| {
+                Error::CcParams(format!(
+                    "no automorphism keys for key tag `{}`",
+                    ciphertext.key_tag()
+                ))
+            })?;
+        key_switch::precompute(c1, key)
+    }
+
+    /// Rotate the slots of a packed ciphertext like
+    /// [`CryptoContext::eval_at_index`], reusing the decomposition
+    /// `precompute` of the same ciphertext.
+    ///
+    /// Only the digits are permuted, so many rotations of one ciphertext
+    /// cost one decomposition. The exact rotation key must be stored.
+    pub fn eval_fast_rotation(
+        &self,
+        ciphertext: &Ciphertext,
+        index: i32,
+        precompute: &FastRotationPrecompute,
+    ) -> Result<Ciphertext> {
+        self.check_packed(ciphertext)?;
+        let index = automorphism_index(i64::from(index), self.element_params.cyclotomic_order)?;
+        if index == 1 {
+            return ciphertext.try_clone();
+        }
+        let keys = Self::get_eval_automorphism_keys(ciphertext.key_tag())?.unwrap_or_default();
+        let key = keys
+            .get(&index)
+            .filter(|k| k.get_crypto_context() == self)
+            .ok_or_else(|| {
+                Error::CcParams(format!(
+                    "no automorphism key for index {index} and key tag `{}`",
+                    ciphertext.key_tag()
+                ))
+            })?;
+        self.automorphism_precomputed(ciphertext, index, key, precompute)
+    }
+
+    /// [`CryptoContext::automorphism`] of a relinearized ciphertext reusing
+    /// the decomposition `precompute` of its `c1`
+    pub(crate) fn automorphism_precomputed(
+        &self,
+        ciphertext: &Ciphertext,
+        index: u64,
+        key: &EvalKey,
+        precompute: &FastRotationPrecompute,
+    ) -> Result<Ciphertext> {
+        let [c0, _] = ciphertext.elements() else {
+            return Err(Error::CcParams(format!(
+                "cannot rotate a ciphertext of {} polynomials, relinearize it first",
+                ciphertext.elements().len()
+            )));
+        };
+        Self::record_automorphism_key_use(key, index)?;
+        let [k0, k1] = key_switch::switch_precomputed(precompute, index as usize, key)?;
+        let mut c0 = c0.automorphism(index as usize);
+        c0 += &in_format_of(k0, &c0);
+        let c1 = in_format_of(k1, &c0);
+        let mut result = ciphertext.try_clone()?;
+        result.replace_elements(
+            "eval_fast_rotation",
+            vec![c0, c1],
+            ciphertext.scaling_factor(),
+            &[ciphertext],
+        );
+        Ok(result)
+    }
+
+    /// Generate a key switching ciphertexts from the secret of `old_key` to
+    /// the secret of `new_key` with the key switching technique of `params`,
+    /// tagged with the key tag of `new_key`
+    pub fn key_switch_gen(
+        &self,
+        params: &CryptoParametersRns,
+        old_key: &PrivateKey,
+        new_key: &PrivateKey,
+    ) -> Result<EvalKey> {
+        self.key_switch_gen_with_rng(params, old_key, new_key, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::key_switch_gen`] drawing randomness from `rng`
+    pub fn key_switch_gen_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        old_key: &PrivateKey,
+        new_key: &PrivateKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<EvalKey> {
+        if old_key.get_crypto_context() != self || new_key.get_crypto_context() != self {
+            return Err(Error::CcParams(
+                "the key belongs to another crypto context".to_string(),
+            ));
+        }
+        Ok(key_switch::key_gen(
+            self.clone(),
+            params,
+            old_key.secret(),
+            new_key.secret(),
+            rng,
+        )?
+        .with_key_tag(new_key.key_tag()))
+    }
+
+    /// Re-key a relinearized `ciphertext` to the new secret of `key` from
+    /// [`CryptoContext::key_switch_gen`], switching `c1` and adding the
+    /// switched pair to `(c0, 0)`
+    pub fn key_switch(&self, ciphertext: &Ciphertext, key: &EvalKey) -> Result<Ciphertext> {
+        self.check_operand(ciphertext)?;
+        if key.get_crypto_context() != self {
+            return Err(Error::CcParams(
+                "the key belongs to another crypto context".to_string(),
+            ));
+        }
+        let [c0, c1] = ciphertext.elements() else {
+            return Err(Error::CcParams(format!(
+                "cannot switch the key of a ciphertext of {} polynomials, relinearize it first",
+                ciphertext.elements().len()
+            )));
+        };
+        let [k0, k1] = key_switch::switch(c1, key)?;
+        let mut c0 = c0.clone();
+        c0 += &in_format_of(k0, &c0);
+        let c1 = in_format_of(k1, c1);
+        let mut result = ciphertext.try_clone()?;
+        result.replace_elements(
+            "key_switch",
+            vec![c0, c1],
+            ciphertext.scaling_factor(),
+            &[ciphertext],
+        );
+        result.set_key_tag(key.key_tag());
+        Ok(result)
+    }
+
+    /// The product of two ciphertexts at the same level without
+    /// relinearization, with one polynomial per power of the secret.
+    ///
+    /// Integer encodings are multiplied with the BFV multiplication technique
+    /// of `params`, so the message of the product is the product of the
+    /// messages modulo `t`. CKKS operands are first adjusted by
+    /// [`CryptoContext::adjust_for_mult`], which rescales them under the
+    /// automatic scaling techniques.
+    pub fn eval_mult_no_relin(
+        &self,
+        params: &CryptoParametersRns,
+        a: &Ciphertext,
+        b: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        let (a, b) = self.multiplicands(params, a, b)?;
+        let (a, b) = (a.as_ref(), b.as_ref());
+        let elements = self.tensor(params, a, Some(b))?;
+        let mut result = a.try_clone()?;
+        result.set_noise_scale_degree(product_noise_scale_degree(a, b.noise_scale_degree()));
+        result.replace_elements(
+            "eval_mult_no_relin",
+            elements,
+            product_scaling_factor(a, b.scaling_factor()),
+            &[a, b],
+        );
+        check_product_scale(&result)?;
+        Ok(result)
+    }
+
+    /// The product of two ciphertexts relinearized with `relin_key` from
+    /// [`CryptoContext::eval_mult_key_gen`], see
+    /// [`CryptoContext::eval_mult_no_relin`]
+    pub fn eval_mult(
+        &self,
+        params: &CryptoParametersRns,
+        a: &Ciphertext,
+        b: &Ciphertext,
+        relin_key: &EvalKey,
+    ) -> Result<Ciphertext> {
+        let (a, b) = self.multiplicands(params, a, b)?;
+        let (a, b) = (a.as_ref(), b.as_ref());
+        let elements = self.relinearized(
+            self.tensor(params, a, Some(b))?,
+            std::slice::from_ref(relin_key),
+        )?;
+        let mut result = a.try_clone()?;
+        result.set_noise_scale_degree(product_noise_scale_degree(a, b.noise_scale_degree()));
+        result.replace_elements(
+            "eval_mult",
+            elements,
+            product_scaling_factor(a, b.scaling_factor()),
+            &[a, b],
+        );
+        check_product_scale(&result)?;
+        Ok(result)
+    }
+
+    /// The sum of `ciphertexts`, added pairwise in a balanced tree whose
+    /// levels are spread over the worker threads of the context
+    pub fn eval_add_many(&self, ciphertexts: &[Ciphertext]) -> Result<Ciphertext> {
+        self.fold_tree(ciphertexts, |a, b| self.eval_add(a, b))
+    }
+
+    /// The product of `ciphertexts`, multiplied pairwise in a balanced tree
+    /// of depth `ceil(log2(len))` and relinearized with the keys stored
+    /// under their key tag, see [`CryptoContext::eval_mult_no_relin`]
+    pub fn eval_mult_many(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertexts: &[Ciphertext],
+    ) -> Result<Ciphertext> {
+        self.fold_tree(ciphertexts, |a, b| {
+            self.relinearize(&self.eval_mult_no_relin(params, a, b)?)
+        })
+    }
+
+    /// The square of a ciphertext relinearized with the keys stored under
+    /// its key tag. The symmetric cross terms are computed once, so this is
+    /// cheaper than [`CryptoContext::eval_mult`] of the ciphertext with
+    /// itself.
+    pub fn eval_square(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        let keys = Self::stored_eval_mult_keys(ciphertext.key_tag())?;
+        let ciphertext = &self.adjust_for_square(params, ciphertext)?;
+        let elements = self.relinearized(self.tensor(params, ciphertext, None)?, &keys)?;
+        let mut result = ciphertext.try_clone()?;
+        result.set_noise_scale_degree(product_noise_scale_degree(
+            ciphertext,
+            ciphertext.noise_scale_degree(),
+        ));
+        result.replace_elements(
+            "eval_square",
+            elements,
+            product_scaling_factor(ciphertext, ciphertext.scaling_factor()),
+            &[ciphertext],
+        );
+        check_product_scale(&result)?;
+        Ok(result)
+    }
+
+    /// The inner product of the first `batch_size` slots of two packed
+    /// ciphertexts, their relinearized slotwise product summed by
+    /// [`CryptoContext::eval_sum`]. Every slot holds the inner product when
+    /// the other slots are zero, in any case the first one does.
+    ///
+    /// BFV ciphertexts must be packed with
+    /// [`BfvRns::encode_packed`](crate::pke::BfvRns::encode_packed), their
+    /// inner product is modulo `t` and sums within the first row of `n / 2`
+    /// slots.
+    pub fn eval_inner_product(
+        &self,
+        params: &CryptoParametersRns,
+        a: &Ciphertext,
+        b: &Ciphertext,
+        batch_size: usize,
+    ) -> Result<Ciphertext> {
+        self.check_packed(a)?;
+        let product = self.relinearize(&self.eval_mult_no_relin(params, a, b)?)?;
+        self.eval_sum(&product, batch_size)
+    }
+
+    /// The inner product of the first `batch_size` slots of a packed
+    /// ciphertext and a plaintext, see [`CryptoContext::eval_inner_product`]
+    pub fn eval_inner_product_plaintext(
+        &self,
+        a: &Ciphertext,
+        plaintext: &PlaintextEncodings,
+        batch_size: usize,
+    ) -> Result<Ciphertext> {
+        self.check_packed(a)?;
+        self.eval_sum(&self.eval_mult_plaintext(a, plaintext)?, batch_size)
+    }
+
+    /// Switch the polynomials of `s^2` and higher powers back to `s` with the
+    /// relinearization keys stored under the key tag of `ciphertext`,
+    /// leaving two polynomials
+    pub fn relinearize(&self, ciphertext: &Ciphertext) -> Result<Ciphertext> {
+        let keys = Self::stored_eval_mult_keys(ciphertext.key_tag())?;
+        self.relinearize_with_keys(ciphertext, &keys)
+    }
+
+    /// [`CryptoContext::relinearize`] with `relin_keys` for `s^2` onwards
+    /// instead of the stored keys
+    pub fn relinearize_with_keys(
+        &self,
+        ciphertext: &Ciphertext,
+        relin_keys: &[EvalKey],
+    ) -> Result<Ciphertext> {
+        self.check_operand(ciphertext)?;
+        let elements = self.relinearized(ciphertext.elements().to_vec(), relin_keys)?;
+        let mut result = ciphertext.clone();
+        result.replace_elements(
+            "relinearize",
+            elements,
+            ciphertext.scaling_factor(),
+            &[ciphertext],
+        );
+        Ok(result)
+    }
+
+    /// Divide a CKKS ciphertext by its last [`BASE_NUM_LEVELS_TO_DROP`]
+    /// tower moduli and drop them, dividing the scaling factor too
+    pub fn mod_reduce(&self, ciphertext: &Ciphertext) -> Result<Ciphertext> {
+        self.check_leveled(ciphertext, "rescale")?;
+        let mut result = ciphertext.clone();
+        for _ in 0..BASE_NUM_LEVELS_TO_DROP {
+            result = self.ckks_rescale(&result)?;
+        }
+        Ok(result)
+    }
+
+    /// Drop the last `levels` towers of a CKKS ciphertext without scaling,
+    /// which keeps the scaling factor and the message
+    pub fn level_reduce(&self, ciphertext: &Ciphertext, levels: usize) -> Result<Ciphertext> {
+        self.check_leveled(ciphertext, "level reduce")?;
+        let towers = tower_count(ciphertext);
+        if levels >= towers {
+            return Err(Error::CcParams(format!(
+                "cannot drop {levels} levels of a ciphertext with {towers} towers"
+            )));
+        }
+        let mut result = ciphertext.try_clone()?;
+        level_reduce(&mut result, towers - levels);
+        Ok(result)
+    }
+
+    /// Reduce a ciphertext to its first `towers` towers, e.g. the
+    /// [`CompressionLevel::towers`] before a joint decryption.
+    ///
+    /// CKKS ciphertexts are rescaled down to a noise scale degree of one and
+    /// level reduced from there, the other schemes scale every coefficient
+    /// by the ratio of the moduli, see [`compress`].
+    ///
+    /// [`CompressionLevel::towers`]: crate::constants::CompressionLevel::towers
+    pub fn compress(&self, ciphertext: &Ciphertext, towers: usize) -> Result<Ciphertext> {
+        self.check_operand(ciphertext)?;
+        let current = tower_count(ciphertext);
+        if towers == 0 || towers > current {
+            return Err(Error::CcParams(format!(
+                "cannot compress a ciphertext with {current} towers to {towers}"
+            )));
+        }
+        let mut result = ciphertext.try_clone()?;
+        if ciphertext.encoding_type() == PlaintextEncodingsType::CkksPacked {
+            while result.noise_scale_degree() > 1 && tower_count(&result) > towers {
+                result = self.mod_reduce(&result)?;
+            }
+            level_reduce(&mut result, towers);
+        } else {
+            let elements = compress(ciphertext.elements(), towers)?;
+            result.replace_elements(
+                "compress",
+                elements,
+                ciphertext.scaling_factor(),
+                &[ciphertext],
+            );
+        }
+        Ok(result)
+    }
+
+    /// Only CKKS messages are scaled independently of the modulus
+    fn check_leveled(&self, ciphertext: &Ciphertext, op: &str) -> Result<()> {
+        self.check_operand(ciphertext)?;
+        if ciphertext.encoding_type() != PlaintextEncodingsType::CkksPacked {
+            return Err(Error::CcParams(format!(
+                "cannot {op} a {} ciphertext, compress it instead",
+                ciphertext.encoding_type()
+            )));
+        }
+        Ok(())
+    }
+
+    fn stored_eval_mult_keys(key_tag: &str) -> Result<Vec<EvalKey>> {
+        Self::get_eval_mult_keys(key_tag)?.ok_or_else(|| {
+            Error::CcParams(format!("no relinearization keys for key tag `{key_tag}`"))
+        })
+    }
+
+    /// Combine `ciphertexts` with `op` level by level, pairing neighbours
+    fn fold_tree<F>(&self, ciphertexts: &[Ciphertext], op: F) -> Result<Ciphertext>
+    where
+        F: Fn(&Ciphertext, &Ciphertext) -> Result<Ciphertext> + Sync,
+    {
+        if let [ciphertext] = ciphertexts {
+            return ciphertext.try_clone();
+        }
+        let config = self.parallel_config();
+        let mut level = ciphertexts.to_vec();
+        while level.len() > 1 {
+            let mut pairs = Vec::with_capacity(level.len().div_ceil(2));
+            let mut items = level.into_iter();
+            while let Some(a) = items.next() {
+                pairs.push((a, items.next(), None));
+            }
+            config.for_each_mut(&mut pairs, |(a, b, combined)| {
+                *combined = Some(match b {
+                    Some(b) => op(a, b),
+                    None => a.try_clone(),
+                });
+            });
+            level = pairs
+                .into_iter()
+                .map(|(_, _, combined)| combined.expect("every pair is combined"))
+                .collect::<Result<_>>()?;
+        }
+        level
+            .pop()
+            .ok_or_else(|| Error::CcParams("no ciphertexts to combine".to_string()))
+    }
+
+    /// `(ψ(c0), ψ(c1))` for `ψ: X -> X^index` switched back with `key`
+    pub(crate) fn automorphism(
+        &self,
+        ciphertext: &Ciphertext,
+        index: u64,
+        key: &EvalKey,
+    ) -> Result<Ciphertext> {
+        let [c0, c1] = ciphertext.elements() else {
+            return Err(Error::CcParams(format!(
+                "cannot apply an automorphism to a ciphertext of {} polynomials, relinearize it first",
+                ciphertext.elements().len()
+            )));
+        };
+        if key.get_crypto_context() != self {
+            return Err(Error::CcParams(
+                "the key belongs to another crypto context".to_string(),
+            ));
+        }
+        Self::record_automorphism_key_use(key, index)?;
+        let mut c0 = c0.automorphism(index as usize);
+        let [k0, k1] = key_switch::switch(&c1.automorphism(index as usize), key)?;
+        c0 += &in_format_of(k0, &c0);
+        let c1 = in_format_of(k1, &c0);
+        let mut result = ciphertext.try_clone()?;
+        result.replace_elements(
+            "eval_automorphism",
+            vec![c0, c1],
+            ciphertext.scaling_factor(),
+            &[ciphertext],
+        );
+        Ok(result)
+    }
+
+    /// The automorphism indices of the fewest stored rotations adding up to
+    /// `rotation`, modulo the number of slots
+    fn rotation_steps(&self, keys: &BTreeMap<u64, EvalKey>, rotation: i32) -> Option<Vec<u64>> {
+        let order = self.element_params.cyclotomic_order;
+        let group = order / 4;
+        let period = self.slots();
+        if period == 0 {
+            return None;
+        }
+        let target = i64::from(rotation).rem_euclid(period as i64) as usize;
+
+        // The rotation of every stored key, 5 generating the rotations
+        let mut available = BTreeMap::new();
+        let mut index = 1;
+        for r in 0..group {
+            if keys.contains_key(&index) && r % period != 0 {
+                available.entry(r % period).or_insert(index);
+            }
+            index = index * 5 % order as u64;
+        }
+
+        // Breadth first search over the rotations modulo the period
+        let mut previous = vec![None; period];
+        previous[0] = Some((0, 1));
+        let mut queue = VecDeque::from([0]);
+        while let Some(at) = queue.pop_front() {
+            if at == target {
+                let mut steps = Vec::new();
+                let mut at = at;
+                while at != 0 {
+                    let (from, index) = previous[at]?;
+                    steps.push(index);
+                    at = from;
+                }
+                steps.reverse();
+                return Some(steps);
+            }
+            for (&r, &index) in &available {
+                let next = (at + r) % period;
+                if previous[next].is_none() {
+                    previous[next] = Some((at, index));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// The number of slots rotations cycle through, the batch size up to
+    /// the `m / 4` slots the rotation group reaches
+    pub(crate) fn slots(&self) -> usize {
+        let group = self.element_params.cyclotomic_order / 4;
+        match self.encoding_params.batch_size {
+            0 => group,
+            slots => slots.min(group),
+        }
+    }
+
+    /// The automorphism index `m - 1` of complex conjugation
+    pub(crate) fn conjugation_index(&self) -> u64 {
+        self.element_params.cyclotomic_order as u64 - 1
+    }
+
+    /// Only packed encodings have slots to rotate
+    fn check_packed(&self, ciphertext: &Ciphertext) -> Result<()> {
+        self.check_operand(ciphertext)?;
+        if !matches!(
+            ciphertext.encoding_type(),
+            PlaintextEncodingsType::Packed | PlaintextEncodingsType::CkksPacked
+        ) {
+            return Err(Error::CcParams(format!(
+                "cannot rotate the slots of a {} ciphertext",
+                ciphertext.encoding_type()
+            )));
+        }
+        Ok(())
+    }
+
+    /// An automorphism `X -> X^index` must be invertible, i.e. `index` odd
+    /// and below the cyclotomic order
+    fn check_automorphism_index(&self, index: u64) -> Result<()> {
+        let order = self.element_params.cyclotomic_order as u64;
+        if index.is_multiple_of(2) || index >= order {
+            return Err(Error::CcParams(format!(
+                "{index} is not an automorphism index for cyclotomic order {order}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Operations check their operands before taking a slot for the result
+    fn check_operand(&self, a: &Ciphertext) -> Result<()> {
+        if a.get_crypto_context() != self {
+            return Err(Error::CcParams(
+                "the ciphertext belongs to another crypto context".to_string(),
+            ));
+        }
+        a.metadata()?;
+        Ok(())
+    }
+
+    fn check_operands(&self, a: &Ciphertext, b: &Ciphertext) -> Result<()> {
+        self.check_multiplicands(a, b)?;
+        if a.encoding_type() == PlaintextEncodingsType::CkksPacked
+            && a.scaling_factor() != b.scaling_factor()
+        {
+            return Err(Error::CcParams(format!(
+                "scaling factors {} and {} differ",
+                a.scaling_factor(),
+                b.scaling_factor()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Operands of a product may differ in their scaling factors
+    fn check_multiplicands(&self, a: &Ciphertext, b: &Ciphertext) -> Result<()> {
+        self.check_operand(a)?;
+        self.check_operand(b)?;
+        if a.encoding_type() != b.encoding_type() {
+            return Err(Error::CcParams(format!(
+                "cannot combine {} and {} ciphertexts",
+                a.encoding_type(),
+                b.encoding_type()
+            )));
+        }
+        if a.element_params() != b.element_params() {
+            return Err(Error::CcParams(format!(
+                "the ciphertexts are at levels {} and {}",
+                a.level(),
+                b.level()
+            )));
+        }
+        if a.elements()[0].format() != b.elements()[0].format() {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        Ok(())
+    }
+
+    fn check_plaintext_operand(
+        &self,
+        a: &Ciphertext,
+        plaintext: &PlaintextEncodings,
+    ) -> Result<DcrtPoly> {
+        self.check_plaintext_multiplicand(a, plaintext)?;
+        if a.encoding_type() == PlaintextEncodingsType::CkksPacked
+            && a.scaling_factor() != plaintext.params().scaling_factor
+        {
+            return Err(Error::CcParams(format!(
+                "scaling factors {} and {} differ",
+                a.scaling_factor(),
+                plaintext.params().scaling_factor
+            )));
+        }
+        self.scaled_plaintext(plaintext, &a.elements()[0])
+    }
+
+    fn check_plaintext_multiplicand(
+        &self,
+        a: &Ciphertext,
+        plaintext: &PlaintextEncodings,
+    ) -> Result<()> {
+        self.check_operand(a)?;
+        if a.encoding_type() != plaintext.encoding_type() {
+            return Err(Error::CcParams(format!(
+                "cannot combine a {} ciphertext with a {} plaintext",
+                a.encoding_type(),
+                plaintext.encoding_type()
+            )));
+        }
+        Ok(())
+    }
+
+    /// The tensor product of two ciphertexts, or of `a` with itself without
+    /// `b`, for integer encodings scaled down to a ciphertext of the product
+    fn tensor(
+        &self,
+        params: &CryptoParametersRns,
+        a: &Ciphertext,
+        b: Option<&Ciphertext>,
+    ) -> Result<Vec<DcrtPoly>> {
+        self.check_operand(a)?;
+        if let Some(b) = b {
+            self.check_multiplicands(a, b)?;
+        }
+        if a.encoding_type() == PlaintextEncodingsType::CkksPacked {
+            // CKKS multiplies the scaled messages, the scale is tracked
+            let evaluation = |c: &crate::ciphertext::Ciphertext| {
+                let mut elements = c.elements().to_vec();
+                for e in &mut elements {
+                    if e.format() == PolynomialRingFormat::Coefficient {
+                        e.switch_format();
+                    }
+                }
+                elements
+            };
+            let a_eval = evaluation(a);
+            let product = match b {
+                Some(b) => tensor_product(&a_eval, &evaluation(b)),
+                None => tensor_square(&a_eval),
+            };
+            let like = &a.elements()[0];
+            return Ok(product.into_iter().map(|x| in_format_of(x, like)).collect());
+        }
+        let t = self.encoding_params.plaintext_modulus.get().to_primitive();
+        let depth = a
+            .noise_scale_degree()
+            .max(b.unwrap_or(a).noise_scale_degree())
+            - 1;
+        params
+            .bfv_multiplier(a.element_params(), t, depth)?
+            .multiply(a.elements(), b.map(Ciphertext::elements))
+    }
+
+    /// `[c0, c1, c2, ...]` switched to `[c0, c1]` with `relin_keys`, the key
+    /// for `s^i` at index `i - 2`, shorter ciphertexts are returned as they
+    /// are
+    fn relinearized(
+        &self,
+        mut elements: Vec<DcrtPoly>,
+        relin_keys: &[EvalKey],
+    ) -> Result<Vec<DcrtPoly>> {
+        if elements.len() <= 2 {
+            return Ok(elements);
+        }
+        if elements.len() - 2 > relin_keys.len() {
+            return Err(Error::CcParams(format!(
+                "cannot relinearize a ciphertext of {} polynomials with keys up to s^{}",
+                elements.len(),
+                relin_keys.len() + 1
+            )));
+        }
+        let high = elements.split_off(2);
+        for (c, key) in high.iter().zip(relin_keys) {
+            if key.get_crypto_context() != self {
+                return Err(Error::CcParams(
+                    "the key belongs to another crypto context".to_string(),
+                ));
+            }
+            let switched = key_switch::switch(c, key)?;
+            for (c, s) in elements.iter_mut().zip(switched) {
+                *c += &in_format_of(s, c);
+            }
+        }
+        Ok(elements)
+    }
+
+    /// Keys switching `s^2` up to `s^max_power` of `private_key` back to `s`
+    fn power_keys(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        max_power: usize,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Vec<EvalKey>> {
+        if private_key.get_crypto_context() != self {
+            return Err(Error::CcParams(
+                "the key belongs to another crypto context".to_string(),
+            ));
+        }
+        let secret = private_key.secret();
+        let mut power = secret.clone();
+        let mut keys = Vec::with_capacity(max_power - 1);
+        for _ in 2..=max_power {
+            power *= secret;
+            keys.push(
+                key_switch::key_gen(self.clone(), params, &power, secret, &mut *rng)?
+                    .with_key_tag(private_key.key_tag()),
+            );
+        }
+        Ok(keys)
+    }
+
+    fn check_encryption(&self, params: &CryptoParametersRns, key_context: &Self) -> Result<()> {
+        if params.encryption_technique != EncryptionTechnique::Standard {
+            return Err(Error::CcParams(format!(
+                "encryption technique {} is not supported yet",
+                params.encryption_technique
+            )));
+        }
+        if key_context != self {
+            return Err(Error::CcParams(
+                "the key belongs to another crypto context".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn add_plaintext(
+        &self,
+        zero: ZeroEncryption,
+        plaintext: &PlaintextEncodings,
+    ) -> Result<Ciphertext> {
+        let scaled = self.scaled_plaintext(plaintext, &zero.c0)?;
+        let mut ciphertext = Ciphertext::new(
+            self.clone(),
+            zero.encrypt(&scaled).to_vec(),
+            plaintext.encoding_type(),
+            plaintext.params().scaling_factor,
+        )?;
+        if let PlaintextEncodings::Ckks(_) = plaintext {
+            ciphertext.set_slots(plaintext.params().slots);
+        }
+        Ok(ciphertext)
+    }
+
+    /// The plaintext scaled as an encrypted message over the towers and in
+    /// the format of `like`
+    fn scaled_plaintext(
+        &self,
+        plaintext: &PlaintextEncodings,
+        like: &DcrtPoly,
+    ) -> Result<DcrtPoly> {
+        let delta = match plaintext {
+            PlaintextEncodings::Ckks(_) => BigInt::from(1),
+            _ => BigInt::from(
+                like.params().ciphertext_composite_modulus()
+                    / self.encoding_params.plaintext_modulus.get().to_primitive(),
+            ),
+        };
+        self.lifted_plaintext(plaintext, &delta, like)
+    }
+
+    /// The centered coefficients of the plaintext times `factor` over the
+    /// towers and in the format of `like`
+    fn lifted_plaintext(
+        &self,
+        plaintext: &PlaintextEncodings,
+        factor: &BigInt,
+        like: &DcrtPoly,
+    ) -> Result<DcrtPoly> {
+        let encoded = plaintext.encoded_value();
+        let len = like.towers().first().map_or(0, |t| t.len());
+        if encoded.cyclotomic_order() != self.element_params.cyclotomic_order || encoded.len() > len
+        {
+            return Err(Error::CcParams(format!(
+                "a plaintext of cyclotomic order {} does not fit the context",
+                encoded.cyclotomic_order()
+            )));
+        }
+        if !matches!(plaintext, PlaintextEncodings::Ckks(_))
+            && *encoded.modulus() != self.encoding_params.plaintext_modulus
+        {
+            return Err(Error::CcParams(format!(
+                "a plaintext modulo {} does not match the plaintext modulus {} of the context",
+                encoded.modulus().get().to_primitive(),
+                self.encoding_params.plaintext_modulus.get().to_primitive()
+            )));
+        }
+        let mut lifted = encoded
+            .to_signed()
+            .into_iter()
+            .map(|c| c * factor)
+            .collect::<Vec<_>>();
+        lifted.resize(len, BigInt::ZERO);
+        Ok(in_format_of(
+            DcrtPoly::from_big_signed(like.params().clone(), &lifted),
+            like,
+        ))
+    }
+
     pub fn make_plaintext(
         &self,
         encoding: PlaintextEncodingsType,
@@ -29,3 +1977,299 @@ impl CryptoContext {
         todo!()
     }
 }
+
+/// `log2(x)` for a positive `x` beyond the range of `f64`
+fn log2(x: &BigInt) -> f64 {
+    let shift = x.bits().saturating_sub(64);
+    (x >> shift).to_f64().map_or(0.0, f64::log2) + shift as f64
+}
+
+/// CKKS products carry the scaling factors of both operands, the other
+/// schemes count the multiplicative depth
+fn product_noise_scale_degree(a: &Ciphertext, noise_scale_degree: usize) -> usize {
+    match a.encoding_type() {
+        PlaintextEncodingsType::CkksPacked => a.noise_scale_degree() + noise_scale_degree,
+        _ => a.noise_scale_degree().max(noise_scale_degree) + 1,
+    }
+}
+
+/// A CKKS product whose scaling factor reaches the modulus of its towers
+/// no longer decrypts, it has to be rescaled first
+fn check_product_scale(product: &Ciphertext) -> Result<()> {
+    if product.encoding_type() != PlaintextEncodingsType::CkksPacked {
+        return Ok(());
+    }
+    let log_q = product
+        .element_params()
+        .params()
+        .iter()
+        .map(|p| (p.ciphertext_modulus.get().to_primitive() as f64).log2())
+        .sum::<f64>();
+    let log_scale = product.scaling_factor().log2();
+    if log_scale >= log_q {
+        return Err(Error::CcParams(format!(
+            "a scaling factor of 2^{log_scale:.1} at noise scale degree {} reaches the \
+             modulus of 2^{log_q:.1} of the remaining towers",
+            product.noise_scale_degree()
+        )));
+    }
+    Ok(())
+}
+
+/// The scaling factor of the product of `a` and an operand scaled by
+/// `scaling_factor`, only CKKS messages stay scaled by both
+fn product_scaling_factor(a: &Ciphertext, scaling_factor: f64) -> f64 {
+    match a.encoding_type() {
+        PlaintextEncodingsType::CkksPacked => a.scaling_factor() * scaling_factor,
+        _ => a.scaling_factor(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ActingPrimitive;
+    use crate::constants::PolynomialRingFormat;
+    use crate::core::lattice::dcrt_poly::DcrtPoly;
+    use crate::encoding::PlaintextEncodings;
+    use crate::pke::test_support::{RING_DIMENSION, bfv_context, ckks_context};
+    use num::{BigInt, Integer};
+
+    const A: [i64; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+    const B: [i64; 8] = [3, -1, 4, 1, -5, 9, 2, -6];
+
+    /// The first tower of `poly` evaluated at `x = 1`, with its modulus
+    fn at_one(poly: &DcrtPoly) -> (BigInt, BigInt) {
+        let mut poly = poly.clone();
+        if poly.format() == PolynomialRingFormat::Evaluation {
+            poly.switch_format();
+        }
+        let tower = &poly.towers()[0];
+        let q = BigInt::from(tower.modulus().get().to_primitive());
+        let sum = tower
+            .values()
+            .iter()
+            .map(|v| BigInt::from(v.to_primitive()))
+            .sum::<BigInt>();
+        (sum.mod_floor(&q), q)
+    }
+
+    #[test]
+    fn ciphertexts_at_one_reveal_nothing() {
+        let bfv = bfv_context(0);
+        let n = RING_DIMENSION;
+        let key_pair = bfv.key_gen().expect("keys");
+        let ciphertext = bfv
+            .encrypt(
+                key_pair.public_key(),
+                &bfv.encode(&vec![1; n]).expect("encode"),
+            )
+            .expect("encrypt");
+
+        // In Z_q[x]/(x^m - 1) evaluating at one is a ring homomorphism, so
+        // the small s(1) would turn c0(1) + c1(1) s(1) into Δ m(1) plus a
+        // small error for one guess of s(1)
+        let [(c0, q), (c1, _)] = [0, 1].map(|i| at_one(&ciphertext.elements()[i]));
+        let t = BigInt::from(65537);
+        let delta = BigInt::from(
+            ciphertext
+                .element_params()
+                .ciphertext_composite_modulus()
+                .clone(),
+        ) / &t;
+        let expected = delta * n;
+        let bound = BigInt::from(1u64 << 32);
+        for guess in -(n as i64)..=n as i64 {
+            let phase = (&c0 + &c1 * guess - &expected).mod_floor(&q);
+            let distance = phase.clone().min(&q - &phase);
+            assert!(distance > bound, "s(1) = {guess} decrypts m(1)");
+        }
+
+        // The same holds for the public key, b(1) + a(1) s(1) = e(1)
+        let [(b, _), (a, _)] = key_pair.public_key().elements().each_ref().map(at_one);
+        for guess in -(n as i64)..=n as i64 {
+            let error = (&b + &a * guess).mod_floor(&q);
+            let distance = error.clone().min(&q - &error);
+            assert!(distance > bound, "s(1) = {guess} is recoverable");
+        }
+    }
+
+    #[test]
+    fn packed_bfv_slots_rotate_left() {
+        let bfv = bfv_context(1);
+        let cc = bfv.crypto_context();
+        let keys = bfv.key_gen().expect("keys");
+        cc.eval_at_index_key_gen(bfv.crypto_parameters(), keys.private_key(), &[1])
+            .expect("rotation key");
+        let ciphertext = bfv
+            .encrypt(keys.public_key(), &bfv.encode_packed(&A).expect("encode"))
+            .expect("encrypt");
+        let rotated = cc.eval_at_index(&ciphertext, 1).expect("rotate");
+        let PlaintextEncodings::Packed(decrypted) =
+            bfv.decrypt(keys.private_key(), &rotated).expect("decrypt")
+        else {
+            panic!("a packed plaintext");
+        };
+        let values = decrypted.values();
+        assert_eq!(values[..8], [2, 3, 4, 5, 6, 7, 8, 0]);
+        // Each row of n / 2 slots rotates on its own
+        assert_eq!(values[RING_DIMENSION / 2 - 1], 1);
+        assert!(values[RING_DIMENSION / 2..].iter().all(|v| *v == 0));
+    }
+
+    #[test]
+    fn bfv_inner_product() {
+        let bfv = bfv_context(1);
+        let cc = bfv.crypto_context();
+        let params = bfv.crypto_parameters();
+        let keys = bfv.key_gen().expect("keys");
+        cc.eval_mult_key_gen(params, keys.private_key())
+            .expect("relinearization key");
+        cc.eval_at_index_key_gen(params, keys.private_key(), &[1, 2, 4])
+            .expect("rotation keys");
+        let a = bfv.encode_packed(&A).expect("encode");
+        let b = bfv.encode_packed(&B).expect("encode");
+        let ca = bfv.encrypt(keys.public_key(), &a).expect("encrypt");
+        let cb = bfv.encrypt(keys.public_key(), &b).expect("encrypt");
+        let expected = A.iter().zip(B).map(|(a, b)| a * b).sum::<i64>();
+
+        let product = cc
+            .eval_inner_product(params, &ca, &cb, 8)
+            .expect("inner product");
+        let with_plaintext = cc
+            .eval_inner_product_plaintext(&ca, &b, 8)
+            .expect("inner product");
+        for ciphertext in [product, with_plaintext] {
+            let PlaintextEncodings::Packed(decrypted) = bfv
+                .decrypt(keys.private_key(), &ciphertext)
+                .expect("decrypt")
+            else {
+                panic!("a packed plaintext");
+            };
+            assert_eq!(decrypted.values()[0], expected);
+        }
+
+        // Coefficient encodings have no slots
+        let coefficients = bfv
+            .encrypt(keys.public_key(), &bfv.encode(&A).expect("encode"))
+            .expect("encrypt");
+        assert!(matches!(
+            cc.eval_inner_product(params, &coefficients, &coefficients, 8),
+            Err(crate::error::Error::CcParams(_))
+        ));
+    }
+
+    #[test]
+    fn ckks_inner_product() {
+        let ckks = ckks_context(1);
+        let cc = ckks.crypto_context();
+        let params = ckks.crypto_parameters();
+        let keys = ckks.key_gen().expect("keys");
+        cc.eval_mult_key_gen(params, keys.private_key())
+            .expect("relinearization key");
+        cc.eval_sum_key_gen(params, keys.private_key())
+            .expect("rotation keys");
+        let [a, b] = [A, B].map(|v| v.map(|x| x as f64 / 4.0));
+        let pa = ckks.encode(&a).expect("encode");
+        let pb = ckks.encode(&b).expect("encode");
+        let ca = ckks.encrypt(keys.public_key(), &pa).expect("encrypt");
+        let cb = ckks.encrypt(keys.public_key(), &pb).expect("encrypt");
+        let expected = a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>();
+
+        let product = cc
+            .eval_inner_product(params, &ca, &cb, 8)
+            .expect("inner product");
+        let with_plaintext = cc
+            .eval_inner_product_plaintext(&ca, &pb, 8)
+            .expect("inner product");
+        for ciphertext in [product, with_plaintext] {
+            let PlaintextEncodings::Ckks(decrypted) = ckks
+                .decrypt(keys.private_key(), &ciphertext)
+                .expect("decrypt")
+            else {
+                panic!("a CKKS plaintext");
+            };
+            // Eight slots summed, so every slot holds the inner product
+            for x in decrypted.values() {
+                assert!((x - expected).abs() < 1e-3, "{x} != {expected}");
+            }
+        }
+    }
+
+    #[test]
+    fn bfv_masks_and_replication() {
+        let bfv = bfv_context(1);
+        let cc = bfv.crypto_context();
+        let params = bfv.crypto_parameters();
+        let keys = bfv.key_gen().expect("keys");
+        cc.eval_sum_key_gen(params, keys.private_key())
+            .expect("rotation keys");
+        let ciphertext = bfv
+            .encrypt(keys.public_key(), &bfv.encode_packed(&A).expect("encode"))
+            .expect("encrypt");
+        let decrypt = |c: &crate::ciphertext::Ciphertext| {
+            let PlaintextEncodings::Packed(decrypted) =
+                bfv.decrypt(keys.private_key(), c).expect("decrypt")
+            else {
+                panic!("a packed plaintext");
+            };
+            decrypted.values()
+        };
+
+        let mask = cc.range_mask(&ciphertext, 2..5).expect("mask");
+        let masked = cc.eval_mult_plaintext(&ciphertext, &mask).expect("mask");
+        let values = decrypt(&masked);
+        assert_eq!(values[..8], [0, 0, 3, 4, 5, 0, 0, 0]);
+        assert!(values[8..].iter().all(|v| *v == 0));
+
+        let mask = cc.one_hot_mask(&ciphertext, 6).expect("mask");
+        assert_eq!(
+            decrypt(&cc.eval_mult_plaintext(&ciphertext, &mask).expect("mask"))[..8],
+            [0, 0, 0, 0, 0, 0, 7, 0]
+        );
+
+        let replicated = cc.eval_replicate(&ciphertext, 3).expect("replicate");
+        let values = decrypt(&replicated);
+        let (first, second) = values.split_at(RING_DIMENSION / 2);
+        assert!(first.iter().all(|v| *v == 4));
+        assert!(second.iter().all(|v| *v == 0));
+
+        assert!(matches!(
+            cc.slot_mask(&ciphertext, &[0.5]),
+            Err(crate::error::Error::CcParams(_))
+        ));
+    }
+
+    #[test]
+    fn ckks_masks_and_replication() {
+        let ckks = ckks_context(1);
+        let cc = ckks.crypto_context();
+        let params = ckks.crypto_parameters();
+        let keys = ckks.key_gen().expect("keys");
+        cc.eval_sum_key_gen(params, keys.private_key())
+            .expect("rotation keys");
+        let values = A.map(|x| x as f64 / 4.0);
+        let ciphertext = ckks
+            .encrypt(keys.public_key(), &ckks.encode(&values).expect("encode"))
+            .expect("encrypt");
+        let decrypt = |c: &crate::ciphertext::Ciphertext| {
+            let PlaintextEncodings::Ckks(decrypted) =
+                ckks.decrypt(keys.private_key(), c).expect("decrypt")
+            else {
+                panic!("a CKKS plaintext");
+            };
+            decrypted.values().to_vec()
+        };
+
+        let mask = cc.range_mask(&ciphertext, 2..5).expect("mask");
+        let masked = decrypt(&cc.eval_mult_plaintext(&ciphertext, &mask).expect("mask"));
+        for (i, x) in masked.iter().enumerate() {
+            let expected = if (2..5).contains(&i) { values[i] } else { 0.0 };
+            assert!((x - expected).abs() < 1e-3, "slot {i}: {x} != {expected}");
+        }
+
+        let replicated = decrypt(&cc.eval_replicate(&ciphertext, 3).expect("replicate"));
+        for x in replicated {
+            assert!((x - values[3]).abs() < 1e-3, "{x} != {}", values[3]);
+        }
+    }
+}
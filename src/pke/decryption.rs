@@ -0,0 +1,351 @@
+//! Constant time decryption of BFV and BGV ciphertexts
+//!
+//! The CRT interpolation, rounding, noise check and centering run the same
+//! instructions for every coefficient whatever its value. Overflowing noise
+//! or out of range messages are only reported once every coefficient has
+//! been processed, and always as the same [`Error::DecryptionFailure`], so
+//! neither timing nor the error reveals which coefficient failed or why.
+//! Divisions are by the public composite modulus, which the `vartime`
+//! routines of `crypto_bigint` handle in time independent of the dividend.
+
+use crate::ActingPrimitive;
+use crate::constants::PolynomialRingFormat;
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::core::lattice::params::DcrtElementParams;
+use crate::error::{Error, Result};
+use crate::pke::Scheme;
+use crate::pke::encryption_pool::in_format_of;
+use crypto_bigint::{Limb, NonZero, U64, Uint};
+use num::BigUint;
+use subtle::{
+    Choice, ConditionallySelectable, ConstantTimeEq, ConstantTimeGreater, ConstantTimeLess,
+};
+
+/// Decodes decrypted polynomials of up to `64 * LIMBS - 65` bit moduli
+#[derive(Clone, Debug)]
+pub struct RnsDecoder<const LIMBS: usize> {
+    scheme: Scheme,
+    params: DcrtElementParams,
+    plaintext_modulus: u64,
+    /// `(Q / q_i) * ((Q / q_i)^-1 mod q_i)`, interpolating the towers
+    crt_basis: Vec<Uint<LIMBS>>,
+    modulus: NonZero<Uint<LIMBS>>,
+    half_modulus: Uint<LIMBS>,
+    noise_bound: Uint<LIMBS>,
+    max_magnitude: u64,
+}
+
+/// A decoder for moduli of up to 1983 bits
+pub type RnsDecoderStd = RnsDecoder<32>;
+
+impl<const LIMBS: usize> RnsDecoder<LIMBS> {
+    /// Decode BFV, where the message is `round(t * x / Q) mod t`
+    pub fn bfv(params: &DcrtElementParams, plaintext_modulus: u64) -> Result<Self> {
+        Self::new(Scheme::Bfv, params, plaintext_modulus)
+    }
+
+    /// Decode BGV, where the message is `x mod t` for the centered `x`
+    pub fn bgv(params: &DcrtElementParams, plaintext_modulus: u64) -> Result<Self> {
+        Self::new(Scheme::Bgv, params, plaintext_modulus)
+    }
+
+    fn new(scheme: Scheme, params: &DcrtElementParams, plaintext_modulus: u64) -> Result<Self> {
+        let q = params.ciphertext_composite_modulus();
+        let towers = params.params().len();
+        if towers == 0 || plaintext_modulus < 2 {
+            return Err(Error::CcParams(format!(
+                "cannot decode modulo {plaintext_modulus} with {towers} towers"
+            )));
+        }
+        // The unreduced interpolation sums `towers` products of 64 bits
+        // with basis elements below Q
+        let headroom = 64 + usize::BITS - (towers - 1).leading_zeros();
+        if q.bits() + headroom as u64 > 64 * LIMBS as u64 || BigUint::from(plaintext_modulus) >= *q
+        {
+            return Err(Error::CcParams(format!(
+                "a {} bit modulus does not fit the decoder",
+                q.bits()
+            )));
+        }
+        let crt_basis = params
+            .params()
+            .iter()
+            .map(|p| {
+                let qi = BigUint::from(p.ciphertext_modulus.get().to_primitive());
+                let q_hat = q / &qi;
+                let q_hat_inv = (&q_hat % &qi).modpow(&(&qi - 2u32), &qi);
+                to_uint(&(q_hat * q_hat_inv % q))
+            })
+            .collect();
+        let modulus = to_uint(q);
+        let half_modulus = modulus.shr_vartime(1);
+        Ok(Self {
+            scheme,
+            params: params.clone(),
+            plaintext_modulus,
+            crt_basis,
+            modulus: NonZero::new(modulus).expect("the modulus is a product of primes"),
+            half_modulus,
+            noise_bound: half_modulus.shr_vartime(1),
+            max_magnitude: plaintext_modulus / 2,
+        })
+    }
+
+    /// Fail unless the noise leaves at least `bits` bits of budget, one by
+    /// default. With zero only an impossible overflow is reported.
+    pub fn min_noise_budget(mut self, bits: u32) -> Self {
+        self.noise_bound = self.half_modulus.shr_vartime(bits);
+        self
+    }
+
+    /// Fail when a decoded coefficient exceeds `max_magnitude` in absolute
+    /// value, e.g. the range a packed encoding promised
+    pub fn max_magnitude(mut self, max_magnitude: u64) -> Self {
+        self.max_magnitude = max_magnitude;
+        self
+    }
+
+    /// Decrypt `c0 + c1 s + c2 s^2 + ...` and decode it, see [`RnsDecoder::decode`]
+    pub fn decrypt(&self, ciphertext: &[DcrtPoly], secret: &DcrtPoly) -> Result<Vec<i64>> {
+        self.decode(&phase(ciphertext, secret)?)
+    }
+
+    /// The centered message coefficients of a decrypted polynomial.
+    ///
+    /// Every coefficient is processed before failing with
+    /// [`Error::DecryptionFailure`] when the noise budget is too small or a
+    /// message exceeds [`RnsDecoder::max_magnitude`].
+    pub fn decode(&self, decrypted: &DcrtPoly) -> Result<Vec<i64>> {
+        if decrypted.params() != &self.params {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        let mut decrypted = decrypted.clone();
+        if decrypted.format() == PolynomialRingFormat::Evaluation {
+            decrypted.switch_format();
+        }
+        let towers = decrypted.towers();
+        let t = NonZero::<Limb>::new(Limb(self.plaintext_modulus))
+            .expect("the plaintext modulus is at least 2");
+        let t_limb = U64::from_u64(self.plaintext_modulus);
+        let half_t = self.plaintext_modulus / 2;
+        let q_mod_t = self.modulus.rem_limb(t).0;
+
+        let mut failed = Choice::from(0);
+        let mut message = Vec::with_capacity(towers.first().map_or(0, |p| p.len()));
+        for i in 0..message.capacity() {
+            let y = towers
+                .iter()
+                .zip(&self.crt_basis)
+                .fold(Uint::<LIMBS>::ZERO, |acc, (tower, b)| {
+                    acc.wrapping_add(&b.wrapping_mul(&tower.values()[i]))
+                });
+            let x = y.rem_vartime(&self.modulus);
+
+            let (m, noise) = match self.scheme {
+                Scheme::Bfv => {
+                    // t x + Q / 2 = Q m + r, the noise is r - Q / 2
+                    let z = x.wrapping_mul(&t_limb).wrapping_add(&self.half_modulus);
+                    let (m, r) = z.div_rem_vartime(&self.modulus);
+                    let m = m.as_words()[0];
+                    let m = u64::conditional_select(&m, &0, m.ct_eq(&self.plaintext_modulus));
+                    let above = r.ct_gt(&self.half_modulus);
+                    let noise = Uint::conditional_select(
+                        &self.half_modulus.wrapping_sub(&r),
+                        &r.wrapping_sub(&self.half_modulus),
+                        above,
+                    );
+                    (m, noise)
+                }
+                _ => {
+                    // x centered is x or x - Q, whose residue mod t is
+                    // x mod t minus Q mod t
+                    let negative = x.ct_gt(&self.half_modulus);
+                    let x_mod_t = x.rem_limb(t).0;
+                    let shifted = u64::conditional_select(
+                        &x_mod_t.wrapping_sub(q_mod_t),
+                        &x_mod_t
+                            .wrapping_sub(q_mod_t)
+                            .wrapping_add(self.plaintext_modulus),
+                        x_mod_t.ct_lt(&q_mod_t),
+                    );
+                    let m = u64::conditional_select(&x_mod_t, &shifted, negative);
+                    let noise =
+                        Uint::conditional_select(&x, &self.modulus.wrapping_sub(&x), negative);
+                    (m, noise)
+                }
+            };
+            failed |= self.noise_bound.ct_lt(&noise);
+
+            let centered = i64::conditional_select(
+                &(m as i64),
+                &(m as i64 - self.plaintext_modulus as i64),
+                m.ct_gt(&half_t),
+            );
+            failed |= centered.unsigned_abs().ct_gt(&self.max_magnitude);
+            message.push(centered);
+        }
+
+        if bool::from(failed) {
+            return Err(Error::DecryptionFailure);
+        }
+        Ok(message)
+    }
+}
+
+/// `c0 + c1 s + c2 s^2 + ...` in the format of `secret`
+pub(crate) fn phase(ciphertext: &[DcrtPoly], secret: &DcrtPoly) -> Result<DcrtPoly> {
+    let Some((c0, rest)) = ciphertext.split_first() else {
+        return Err(Error::DecryptionFailure);
+    };
+    if ciphertext.iter().any(|c| c.params() != secret.params()) {
+        return Err(Error::DcrtElementParamsMismatch);
+    }
+    let mut decrypted = in_format_of(c0.clone(), secret);
+    let mut power = secret.clone();
+    for c in rest {
+        let mut term = in_format_of(c.clone(), secret);
+        term *= &power;
+        decrypted += &term;
+        power *= secret;
+    }
+    Ok(decrypted)
+}
+
+fn to_uint<const LIMBS: usize>(value: &BigUint) -> Uint<LIMBS> {
+    let mut bytes = value.to_bytes_le();
+    bytes.resize(LIMBS * Limb::BYTES, 0);
+    Uint::from_le_slice(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::lattice::params::DcrtElementParamsBuilder;
+    use crate::pke::test_support::bfv_context;
+    use num::BigInt;
+
+    const T: u64 = 65537;
+
+    fn params() -> DcrtElementParams {
+        DcrtElementParamsBuilder::new(16)
+            .depth(2)
+            .bits(30)
+            .build()
+            .expect("parameters")
+    }
+
+    /// The phase of `message` encoded for `scheme` with `noise` added
+    fn phase_of(
+        scheme: Scheme,
+        params: &DcrtElementParams,
+        message: &[i64],
+        noise: &[BigInt],
+    ) -> DcrtPoly {
+        let q = BigInt::from(params.ciphertext_composite_modulus().clone());
+        let values = message
+            .iter()
+            .zip(noise)
+            .map(|(m, e)| match scheme {
+                Scheme::Bfv => &q * m / T + e,
+                _ => BigInt::from(*m) + e * T,
+            })
+            .collect::<Vec<_>>();
+        DcrtPoly::from_big_signed(params.clone(), &values)
+    }
+
+    fn decoder(scheme: Scheme, params: &DcrtElementParams) -> RnsDecoderStd {
+        match scheme {
+            Scheme::Bfv => RnsDecoderStd::bfv(params, T),
+            _ => RnsDecoderStd::bgv(params, T),
+        }
+        .expect("decoder")
+    }
+
+    fn message(n: usize) -> Vec<i64> {
+        let half_t = (T / 2) as i64;
+        [0, 1, -1, half_t, -half_t, 42, -1000, 7]
+            .into_iter()
+            .cycle()
+            .take(n)
+            .collect()
+    }
+
+    #[test]
+    fn small_noise_decodes() {
+        let params = params();
+        let n = params.params()[0].ring_dimension;
+        let message = message(n);
+        let noise = (0..n as i64)
+            .map(|i| BigInt::from(i % 7 - 3))
+            .collect::<Vec<_>>();
+        for scheme in [Scheme::Bfv, Scheme::Bgv] {
+            let decoder = decoder(scheme, &params);
+            let decrypted = phase_of(scheme, &params, &message, &noise);
+            assert_eq!(decoder.decode(&decrypted).expect("decode"), message);
+            let mut evaluations = decrypted.clone();
+            evaluations.switch_format();
+            assert_eq!(decoder.decode(&evaluations).expect("decode"), message);
+        }
+    }
+
+    #[test]
+    fn every_failure_is_the_same_error() {
+        let params = params();
+        let n = params.params()[0].ring_dimension;
+        let q = BigInt::from(params.ciphertext_composite_modulus().clone());
+        let overflow = &q / (3 * T);
+        for scheme in [Scheme::Bfv, Scheme::Bgv] {
+            let decoder = decoder(scheme, &params);
+            for i in [0, n / 2, n - 1] {
+                let mut noise = vec![BigInt::from(1); n];
+                noise[i] = overflow.clone();
+                let decrypted = phase_of(scheme, &params, &message(n), &noise);
+                assert!(
+                    matches!(decoder.decode(&decrypted), Err(Error::DecryptionFailure)),
+                    "{scheme:?} noise overflow at {i}"
+                );
+
+                let mut message = vec![5; n];
+                message[i] = 101;
+                let decrypted = phase_of(scheme, &params, &message, &vec![BigInt::from(1); n]);
+                let decoder = decoder.clone().max_magnitude(100);
+                assert!(
+                    matches!(decoder.decode(&decrypted), Err(Error::DecryptionFailure)),
+                    "{scheme:?} message out of range at {i}"
+                );
+                message[i] = -100;
+                assert_eq!(
+                    decoder
+                        .decode(&phase_of(
+                            scheme,
+                            &params,
+                            &message,
+                            &vec![BigInt::from(1); n]
+                        ))
+                        .expect("in range"),
+                    message
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn encrypted_messages_decode() {
+        let bfv = bfv_context(0);
+        let keys = bfv.key_gen().expect("keys");
+        let ciphertext = bfv
+            .encrypt(keys.public_key(), &bfv.encode(&[3, -1, 4]).expect("encode"))
+            .expect("encrypt");
+        let decoder = RnsDecoderStd::bfv(ciphertext.element_params(), T).expect("decoder");
+        let message = decoder
+            .decrypt(ciphertext.elements(), keys.private_key().secret())
+            .expect("decrypt");
+        assert_eq!(message[..3], [3, -1, 4]);
+        assert!(message[3..].iter().all(|m| *m == 0));
+
+        assert!(matches!(
+            decoder.decode(&DcrtPoly::zero(params())),
+            Err(Error::DcrtElementParamsMismatch)
+        ));
+    }
+}
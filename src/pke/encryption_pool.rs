@@ -0,0 +1,413 @@
+//! Precomputed encryption randomness
+//!
+//! Everything expensive in an encryption, sampling and the products with the
+//! key, is independent of the message. A [`RandomnessPool`] of
+//! [`ZeroEncryption`]s is filled while the node is idle, or by a background
+//! thread, and an encryption at request time is a single addition. Every
+//! pooled value is handed out exactly once.
+
+use crate::constants::SecretKeyDistribution;
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::core::math::{DiscreteGaussian, SamplerPool, TernaryUniform};
+use crate::error::{Error, Result};
+use rand::CryptoRng;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{self, Debug, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread::JoinHandle;
+
+/// The per-thread error samplers of every standard deviation in use, keyed
+/// by its bits
+static GAUSSIANS: RwLock<BTreeMap<u64, SamplerPool<DiscreteGaussian>>> =
+    RwLock::new(BTreeMap::new());
+
+/// An encryption of zero, decrypting as `c0 + c1 * s`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZeroEncryption {
+    /// The element the message is added to
+    pub c0: DcrtPoly,
+    /// The element multiplied by the secret when decrypting
+    pub c1: DcrtPoly,
+}
+
+impl ZeroEncryption {
+    /// `(-a * s + e, a)` for a uniform `a`, `s` is the secret key
+    pub fn with_secret_key(secret: &DcrtPoly, std_dev: f64) -> Result<Self> {
+        Self::with_secret_key_and_rng(secret, std_dev, &mut rand::rng())
+    }
+
+    /// [`ZeroEncryption::with_secret_key`] drawing randomness from `rng`
+    pub fn with_secret_key_and_rng(
+        secret: &DcrtPoly,
+        std_dev: f64,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Self> {
+        with_gaussian(std_dev, |gaussian| {
+            Self::secret_key_core(secret, gaussian, rng)
+        })
+    }
+
+    /// `(-a * s + e, a)` for the given `a`, e.g. the `a` of another party's
+    /// public key when generating a joint key
+    pub(crate) fn with_secret_key_a_and_rng(
+        secret: &DcrtPoly,
+        a: &DcrtPoly,
+        std_dev: f64,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Self> {
+        if a.params() != secret.params() {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        let a = in_format_of(a.clone(), secret);
+        with_gaussian(std_dev, |gaussian| {
+            Self::masked_core(secret, a, gaussian, rng)
+        })
+    }
+
+    /// `(pk0 * v + e0, pk1 * v + e1)` where `v` follows `secret_key_distribution`
+    pub fn with_public_key(
+        public_key: &[DcrtPoly; 2],
+        std_dev: f64,
+        secret_key_distribution: SecretKeyDistribution,
+    ) -> Result<Self> {
+        Self::with_public_key_and_rng(
+            public_key,
+            std_dev,
+            secret_key_distribution,
+            &mut rand::rng(),
+        )
+    }
+
+    /// [`ZeroEncryption::with_public_key`] drawing randomness from `rng`
+    pub fn with_public_key_and_rng(
+        public_key: &[DcrtPoly; 2],
+        std_dev: f64,
+        secret_key_distribution: SecretKeyDistribution,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Self> {
+        let ternary = TernaryUniform::for_secret_key_distribution(secret_key_distribution);
+        with_gaussian(std_dev, |gaussian| {
+            Self::public_key_core(public_key, gaussian, ternary.as_ref(), rng)
+        })?
+    }
+
+    /// Add an already scaled plaintext, returning `[c0 + m, c1]`
+    pub fn encrypt(mut self, scaled_plaintext: &DcrtPoly) -> [DcrtPoly; 2] {
+        self.c0 += scaled_plaintext;
+        [self.c0, self.c1]
+    }
+
+    fn secret_key_core(
+        secret: &DcrtPoly,
+        gaussian: &DiscreteGaussian,
+        rng: &mut impl CryptoRng,
+    ) -> Self {
+        let mut a = secret.clone();
+        a.fill_uniform_with_rng(&mut *rng);
+        Self::masked_core(secret, a, gaussian, rng)
+    }
+
+    fn masked_core(
+        secret: &DcrtPoly,
+        a: DcrtPoly,
+        gaussian: &DiscreteGaussian,
+        rng: &mut impl CryptoRng,
+    ) -> Self {
+        let mut c0 = error_like(secret, gaussian, rng);
+        let mut a_s = a.clone();
+        a_s *= secret;
+        c0 -= &a_s;
+        Self { c0, c1: a }
+    }
+
+    fn public_key_core(
+        public_key: &[DcrtPoly; 2],
+        gaussian: &DiscreteGaussian,
+        ternary: Option<&TernaryUniform>,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Self> {
+        let [pk0, pk1] = public_key;
+        let mut v = pk0.clone();
+        match ternary {
+            Some(ternary) => v.fill_ternary_with_rng(ternary, &mut *rng)?,
+            None => v.fill_discrete_gaussian_with_rng(gaussian, &mut *rng),
+        }
+        v = in_format_of(v, pk0);
+        let mut c0 = pk0.clone();
+        c0 *= &v;
+        c0 += &error_like(pk0, gaussian, rng);
+        let mut c1 = pk1.clone();
+        c1 *= &v;
+        c1 += &error_like(pk1, gaussian, rng);
+        Ok(Self { c0, c1 })
+    }
+}
+
+pub(super) fn gaussian(std_dev: f64) -> Result<DiscreteGaussian> {
+    if !(std_dev.is_finite() && std_dev > 0.0 && std_dev.log2() <= 59.0) {
+        return Err(Error::SamplerParams(format!(
+            "standard deviation {std_dev} is out of range"
+        )));
+    }
+    let mut gaussian =
+        DiscreteGaussian::new(std_dev).map_err(|e| Error::SamplerParams(e.to_string()))?;
+    gaussian.set_std_dev(std_dev);
+    Ok(gaussian)
+}
+
+/// Run `f` with the calling thread's sampler of `std_dev`, built once per
+/// thread instead of on every key generation or encryption
+pub(super) fn with_gaussian<R>(std_dev: f64, f: impl FnOnce(&DiscreteGaussian) -> R) -> Result<R> {
+    let key = std_dev.to_bits();
+    let pooled = GAUSSIANS.read()?.get(&key).cloned();
+    let pool = match pooled {
+        Some(pool) => pool,
+        None => {
+            gaussian(std_dev)?;
+            let pool = SamplerPool::new(move || {
+                gaussian(std_dev).expect("the standard deviation was checked")
+            });
+            GAUSSIANS.write()?.entry(key).or_insert(pool).clone()
+        }
+    };
+    Ok(pool.with(|gaussian| f(gaussian)))
+}
+
+/// A fresh error in the towers and format of `like`
+fn error_like(like: &DcrtPoly, gaussian: &DiscreteGaussian, rng: &mut impl CryptoRng) -> DcrtPoly {
+    let mut e = like.clone();
+    e.fill_discrete_gaussian_with_rng(gaussian, rng);
+    in_format_of(e, like)
+}
+
+pub(super) fn in_format_of(mut poly: DcrtPoly, like: &DcrtPoly) -> DcrtPoly {
+    if poly.format() != like.format() {
+        poly.switch_format();
+    }
+    poly
+}
+
+struct Shared<T> {
+    queue: Mutex<VecDeque<T>>,
+    below_capacity: Condvar,
+    capacity: usize,
+    stop: AtomicBool,
+    factory: Box<dyn Fn() -> T + Send + Sync>,
+}
+
+/// A bounded queue of single use randomness, see the [module](self) docs.
+///
+/// [`RandomnessPool::take`] falls back to computing a value inline when the
+/// pool is empty, so callers never block on the background thread.
+pub struct RandomnessPool<T: Send + 'static> {
+    shared: Arc<Shared<T>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> Debug for RandomnessPool<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RandomnessPool")
+            .field("len", &self.len())
+            .field("capacity", &self.shared.capacity)
+            .field("background", &self.worker.is_some())
+            .finish()
+    }
+}
+
+impl RandomnessPool<ZeroEncryption> {
+    /// A pool of secret key encryptions of zero under `secret`
+    pub fn with_secret_key(capacity: usize, secret: DcrtPoly, std_dev: f64) -> Result<Self> {
+        let gaussian = gaussian(std_dev)?;
+        Ok(Self::new(capacity, move || {
+            ZeroEncryption::secret_key_core(&secret, &gaussian, &mut rand::rng())
+        }))
+    }
+
+    /// A pool of public key encryptions of zero under `public_key`
+    pub fn with_public_key(
+        capacity: usize,
+        public_key: [DcrtPoly; 2],
+        std_dev: f64,
+        secret_key_distribution: SecretKeyDistribution,
+    ) -> Result<Self> {
+        let gaussian = gaussian(std_dev)?;
+        let ternary = TernaryUniform::for_secret_key_distribution(secret_key_distribution);
+        // The first value checks the parameters so the factory cannot fail later
+        let first = ZeroEncryption::public_key_core(
+            &public_key,
+            &gaussian,
+            ternary.as_ref(),
+            &mut rand::rng(),
+        )?;
+        let pool = Self::new(capacity, move || {
+            ZeroEncryption::public_key_core(
+                &public_key,
+                &gaussian,
+                ternary.as_ref(),
+                &mut rand::rng(),
+            )
+            .expect("the sampler was validated when the pool was created")
+        });
+        pool.push(first);
+        Ok(pool)
+    }
+}
+
+impl<T: Send + 'static> RandomnessPool<T> {
+    /// An empty pool holding up to `capacity` values made by `factory`
+    pub fn new(capacity: usize, factory: impl Fn() -> T + Send + Sync + 'static) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                queue: Mutex::new(VecDeque::with_capacity(capacity)),
+                below_capacity: Condvar::new(),
+                capacity,
+                stop: AtomicBool::new(false),
+                factory: Box::new(factory),
+            }),
+            worker: None,
+        }
+    }
+
+    /// Compute values on the calling thread until the pool is full
+    pub fn fill(&self) {
+        while self.len() < self.shared.capacity {
+            let value = (self.shared.factory)();
+            if !self.push(value) {
+                break;
+            }
+        }
+    }
+
+    /// Keep the pool full from a background thread until the pool is dropped
+    pub fn spawn_refill(&mut self) {
+        if self.worker.is_some() {
+            return;
+        }
+        let shared = self.shared.clone();
+        self.worker = Some(std::thread::spawn(move || {
+            loop {
+                {
+                    let Ok(mut queue) = shared.queue.lock() else {
+                        return;
+                    };
+                    while queue.len() >= shared.capacity && !shared.stop.load(Ordering::Acquire) {
+                        queue = match shared.below_capacity.wait(queue) {
+                            Ok(queue) => queue,
+                            Err(_) => return,
+                        };
+                    }
+                }
+                if shared.stop.load(Ordering::Acquire) {
+                    return;
+                }
+                let value = (shared.factory)();
+                match shared.queue.lock() {
+                    Ok(mut queue) if queue.len() < shared.capacity => queue.push_back(value),
+                    Ok(_) => {}
+                    Err(_) => return,
+                }
+            }
+        }));
+    }
+
+    /// A pooled value, or a freshly computed one when the pool is empty
+    pub fn take(&self) -> T {
+        let pooled = self
+            .shared
+            .queue
+            .lock()
+            .ok()
+            .and_then(|mut queue| queue.pop_front());
+        self.shared.below_capacity.notify_one();
+        pooled.unwrap_or_else(|| (self.shared.factory)())
+    }
+
+    /// The number of values ready to be taken
+    pub fn len(&self) -> usize {
+        self.shared.queue.lock().map_or(0, |queue| queue.len())
+    }
+
+    /// Whether no value is ready
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn push(&self, value: T) -> bool {
+        match self.shared.queue.lock() {
+            Ok(mut queue) if queue.len() < self.shared.capacity => {
+                queue.push_back(value);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<T: Send + 'static> Drop for RandomnessPool<T> {
+    fn drop(&mut self) {
+        {
+            // Under the lock so the worker cannot miss the wakeup between
+            // checking the flag and waiting
+            let _queue = self.shared.queue.lock();
+            self.shared.stop.store(true, Ordering::Release);
+        }
+        self.shared.below_capacity.notify_all();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ActingPrimitive;
+    use crate::core::lattice::params::DcrtElementParamsBuilder;
+    use num::{BigInt, Integer};
+
+    /// The first tower of `poly` evaluated at `x = 1`, with its modulus
+    fn at_one(poly: &DcrtPoly) -> (BigInt, BigInt) {
+        let mut poly = poly.clone();
+        poly.switch_format();
+        let tower = &poly.towers()[0];
+        let q = BigInt::from(tower.modulus().get().to_primitive());
+        let sum = tower
+            .values()
+            .iter()
+            .map(|v| BigInt::from(v.to_primitive()))
+            .sum::<BigInt>();
+        (sum, q)
+    }
+
+    #[test]
+    fn zero_encryptions_at_one_hide_the_secret() {
+        let params = DcrtElementParamsBuilder::new(4096)
+            .depth(2)
+            .build()
+            .expect("parameters");
+        let distribution = SecretKeyDistribution::UniformTernary;
+        let ternary = TernaryUniform::for_secret_key_distribution(distribution).expect("ternary");
+        let mut secret = DcrtPoly::zero(params);
+        secret
+            .fill_ternary_with_rng(&ternary, rand::rng())
+            .expect("secret");
+        let secret_at_one = secret.to_signed().into_iter().sum::<BigInt>();
+        secret.switch_format();
+
+        let key = ZeroEncryption::with_secret_key(&secret, 3.2).expect("key");
+        let public_key = [key.c0.clone(), key.c1.clone()];
+        let zero = ZeroEncryption::with_public_key(&public_key, 3.2, distribution).expect("zero");
+        // Even knowing s(1), c0(1) + c1(1) s(1) is uniform rather than the
+        // small error it is when evaluating at one is a ring homomorphism
+        for ZeroEncryption { c0, c1 } in [key, zero] {
+            let ((c0, q), (c1, _)) = (at_one(&c0), at_one(&c1));
+            let phase = (c0 + c1 * &secret_at_one).mod_floor(&q);
+            let distance = phase.clone().min(&q - &phase);
+            assert!(
+                distance > BigInt::from(1u64 << 32),
+                "the phase at one is small"
+            );
+        }
+    }
+}
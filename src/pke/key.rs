@@ -1 +1,13 @@
 mod evalkey;
+mod keypair;
+mod mapped;
+mod privatekey;
+mod pruning;
+mod publickey;
+
+pub use evalkey::*;
+pub use keypair::*;
+pub use mapped::*;
+pub use privatekey::*;
+pub use pruning::*;
+pub use publickey::*;
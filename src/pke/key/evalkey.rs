@@ -1,12 +1,20 @@
+use crate::constants::KeySwitchTechnique;
+use crate::core::lattice::dcrt_poly::DcrtPoly;
 use crate::crypto_object::CryptoObject;
+use crate::error::{Error, Result};
 use crate::pke::CryptoContext;
-use crypto_bigint::U64;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A key switching key, one encryption under the new secret of the old
+/// secret times each gadget digit, e.g. for relinearization or rotations
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct EvalKey {
     crypto_context: CryptoContext,
-    key: Vec<U64>,
+    technique: KeySwitchTechnique,
+    digit_size: usize,
+    elements: Vec<[DcrtPoly; 2]>,
+    #[serde(default)]
+    key_tag: String,
 }
 
 impl CryptoObject for EvalKey {
@@ -14,3 +22,60 @@ impl CryptoObject for EvalKey {
         &self.crypto_context
     }
 }
+
+impl EvalKey {
+    /// Wrap the encryptions `elements` of the digits of `technique` with
+    /// `digit_size` bit digits, which must share their parameters and format
+    pub fn new(
+        crypto_context: CryptoContext,
+        technique: KeySwitchTechnique,
+        digit_size: usize,
+        elements: Vec<[DcrtPoly; 2]>,
+    ) -> Result<Self> {
+        let Some([first, _]) = elements.first() else {
+            return Err(Error::CcParams(
+                "a key switching key has no digits".to_string(),
+            ));
+        };
+        if elements
+            .iter()
+            .flatten()
+            .any(|e| e.params() != first.params() || e.format() != first.format())
+        {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        Ok(Self {
+            crypto_context,
+            technique,
+            digit_size,
+            elements,
+            key_tag: String::new(),
+        })
+    }
+
+    /// Tag the key with the tag of the secret it switches to
+    pub fn with_key_tag(mut self, key_tag: impl Into<String>) -> Self {
+        self.key_tag = key_tag.into();
+        self
+    }
+
+    /// The tag of the key pair the key belongs to
+    pub fn key_tag(&self) -> &str {
+        &self.key_tag
+    }
+
+    /// The technique the key was generated for
+    pub fn key_switch_technique(&self) -> KeySwitchTechnique {
+        self.technique
+    }
+
+    /// The bits of each digit within a tower, zero for whole towers
+    pub fn digit_size(&self) -> usize {
+        self.digit_size
+    }
+
+    /// One pair per digit
+    pub fn elements(&self) -> &[[DcrtPoly; 2]] {
+        &self.elements
+    }
+}
@@ -0,0 +1,47 @@
+use crate::crypto_object::CryptoObject;
+use crate::error::{Error, Result};
+use crate::pke::{CryptoContext, PrivateKey, PublicKey};
+use serde::{Deserialize, Serialize};
+
+/// A public key and the private key it was generated from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyPair {
+    public_key: PublicKey,
+    private_key: PrivateKey,
+}
+
+impl CryptoObject for KeyPair {
+    fn get_crypto_context(&self) -> &CryptoContext {
+        self.public_key.get_crypto_context()
+    }
+}
+
+impl KeyPair {
+    /// Pair keys of the same context and parameters
+    pub fn new(public_key: PublicKey, private_key: PrivateKey) -> Result<Self> {
+        if public_key.get_crypto_context() != private_key.get_crypto_context()
+            || public_key.elements()[0].params() != private_key.secret().params()
+        {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        Ok(Self {
+            public_key,
+            private_key,
+        })
+    }
+
+    /// The public key
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// The private key
+    pub fn private_key(&self) -> &PrivateKey {
+        &self.private_key
+    }
+
+    /// Split into the public and the private key
+    pub fn into_parts(self) -> (PublicKey, PrivateKey) {
+        (self.public_key, self.private_key)
+    }
+}
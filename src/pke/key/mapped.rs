@@ -0,0 +1,634 @@
+//! Memory mapped evaluation key files
+//!
+//! Bootstrapping servers hold gigabytes of rotation keys but only touch the
+//! ones a circuit needs. The file layout keeps every key's coefficients as
+//! little endian `u64`s at 64 byte aligned offsets, so a mapped file is read
+//! in place and pages are only faulted in when a key is used.
+//!
+//! Layout, all integers little endian:
+//!
+//! | field | size |
+//! |-------|------|
+//! | magic `OFHEEVK\0` | 8 |
+//! | version, reserved | 4 + 4 |
+//! | cyclotomic order, towers, keys | 3 × 8 |
+//! | tower (modulus, root, big modulus, big root) | towers × 32 |
+//! | key table of (id, technique, digit size, polys, offset) | keys × 40 |
+//! | residues, tower major per poly | polys × towers × order / 2 × 8 each |
+//!
+//! Each tower holds one residue per power below the ring dimension, like
+//! [`DcrtPoly::residues`], in coefficient format. [`MappedEvalKeys::eval_key`]
+//! rebuilds the [`EvalKey`] in evaluation format.
+
+use crate::ActingPrimitive;
+use crate::constants::{KeySwitchTechnique, PolynomialRingFormat};
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::core::lattice::params::{DcrtElementParams, DcrtElementParamsBuilder};
+use crate::error::{Error, Result};
+use crate::pke::{CryptoContext, EvalKey, KeyPlan};
+use crypto_bigint::{Odd, U64};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const MAGIC: &[u8; 8] = b"OFHEEVK\0";
+const VERSION: u32 = 3;
+const ALIGNMENT: usize = 64;
+const FIXED_HEADER: usize = 8 + 4 + 4 + 3 * 8;
+const TOWER_ENTRY: usize = 4 * 8;
+const KEY_ENTRY: usize = 5 * 8;
+
+/// A key to store with [`write_eval_keys`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EvalKeyRecord {
+    /// Identifier, e.g. the automorphism index
+    pub id: u64,
+    /// The technique the key was generated for
+    pub technique: KeySwitchTechnique,
+    /// The digit size of the key, see [`EvalKey::digit_size`]
+    pub digit_size: usize,
+    /// Each polynomial as `towers × ring_dimension` coefficient residues,
+    /// tower major
+    pub polys: Vec<Vec<u64>>,
+}
+
+impl EvalKeyRecord {
+    /// The record of `key` under `id`, its digits flattened to `[b, a]` pairs
+    pub fn from_eval_key(id: u64, key: &EvalKey) -> Self {
+        let polys = key
+            .elements()
+            .iter()
+            .flatten()
+            .map(|poly| {
+                let mut poly = poly.clone();
+                if poly.format() == PolynomialRingFormat::Evaluation {
+                    poly.switch_format();
+                }
+                poly.residues().concat()
+            })
+            .collect();
+        Self {
+            id,
+            technique: key.key_switch_technique(),
+            digit_size: key.digit_size(),
+            polys,
+        }
+    }
+}
+
+/// Write `keys`, whose polynomials have the towers of `params`, in the
+/// mappable layout
+pub fn write_eval_keys<W: Write>(
+    mut writer: W,
+    params: &DcrtElementParams,
+    keys: &[EvalKeyRecord],
+) -> Result<()> {
+    let towers = params.params();
+    let order = towers.front().map_or(0, |p| p.cyclotomic_order);
+    let poly_len = towers.front().map_or(0, |p| p.ring_dimension) * towers.len();
+    let table_end = FIXED_HEADER + TOWER_ENTRY * towers.len() + KEY_ENTRY * keys.len();
+
+    let mut header = Vec::with_capacity(table_end);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    for v in [order, towers.len(), keys.len()] {
+        header.extend_from_slice(&(v as u64).to_le_bytes());
+    }
+    for tower in towers {
+        for v in [
+            tower.ciphertext_modulus.get(),
+            tower.root_of_unity,
+            tower.big_ciphertext_modulus.get(),
+            tower.big_root_of_unity,
+        ] {
+            header.extend_from_slice(&v.to_primitive().to_le_bytes());
+        }
+    }
+
+    let mut offset = table_end.next_multiple_of(ALIGNMENT);
+    let mut seen = BTreeSet::new();
+    for key in keys {
+        if !seen.insert(key.id) {
+            return Err(Error::EvalKeyFile(format!("duplicate key id `{}`", key.id)));
+        }
+        if let Some(poly) = key.polys.iter().find(|p| p.len() != poly_len) {
+            return Err(Error::EvalKeyFile(format!(
+                "key `{}` has a polynomial of `{}` residues, expected `{}`",
+                key.id,
+                poly.len(),
+                poly_len
+            )));
+        }
+        for v in [
+            key.id,
+            key.technique as u64,
+            key.digit_size as u64,
+            key.polys.len() as u64,
+            offset as u64,
+        ] {
+            header.extend_from_slice(&v.to_le_bytes());
+        }
+        offset = (offset + 8 * poly_len * key.polys.len()).next_multiple_of(ALIGNMENT);
+    }
+    header.resize(table_end.next_multiple_of(ALIGNMENT), 0);
+    writer.write_all(&header)?;
+
+    let mut written = header.len();
+    for key in keys {
+        for poly in &key.polys {
+            let bytes = poly
+                .iter()
+                .flat_map(|x| x.to_le_bytes())
+                .collect::<Vec<_>>();
+            writer.write_all(&bytes)?;
+            written += bytes.len();
+        }
+        let padded = written.next_multiple_of(ALIGNMENT);
+        writer.write_all(&[0u8; ALIGNMENT][..padded - written])?;
+        written = padded;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+struct KeyEntry {
+    technique: KeySwitchTechnique,
+    digit_size: usize,
+    polys: usize,
+    offset: usize,
+    used: AtomicBool,
+}
+
+/// Evaluation keys read in place from a file in the mappable layout
+#[derive(Debug)]
+pub struct MappedEvalKeys {
+    data: Backing,
+    cyclotomic_order: usize,
+    ring_dimension: usize,
+    params: DcrtElementParams,
+    moduli: Vec<u64>,
+    keys: BTreeMap<u64, KeyEntry>,
+}
+
+impl MappedEvalKeys {
+    /// Map the file at `path`.
+    ///
+    /// On little endian Unix targets nothing beyond the header is read until
+    /// a key is accessed. Elsewhere the file is read into memory.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be written to or truncated while the keys are alive.
+    /// The mapping is private, but pages not yet faulted in still show later
+    /// writes, so the residues handed out could change under their borrows,
+    /// and reading past a truncation raises `SIGBUS`. Use
+    /// [`MappedEvalKeys::read`] for files that may change.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path)?;
+        Self::parse(Backing::map(&file)?)
+    }
+
+    /// Read the file at `path` into memory
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Load keys from an in-memory copy of the file
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::parse(Backing::owned(bytes))
+    }
+
+    /// The cyclotomic order of the key polynomials
+    pub fn cyclotomic_order(&self) -> usize {
+        self.cyclotomic_order
+    }
+
+    /// The ring dimension, the number of residues in each tower
+    pub fn ring_dimension(&self) -> usize {
+        self.ring_dimension
+    }
+
+    /// The tower moduli
+    pub fn moduli(&self) -> &[u64] {
+        &self.moduli
+    }
+
+    /// The parameters of the key polynomials
+    pub fn element_params(&self) -> &DcrtElementParams {
+        &self.params
+    }
+
+    /// The ids of the stored keys in ascending order
+    pub fn key_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.keys.keys().copied()
+    }
+
+    /// The number of polynomials in key `id`
+    pub fn polys(&self, id: u64) -> Option<usize> {
+        self.keys.get(&id).map(|e| e.polys)
+    }
+
+    /// The residues of polynomial `poly` of key `id` in `tower`, without copying.
+    ///
+    /// Marks key `id` as used, see [`MappedEvalKeys::unused_key_ids`].
+    pub fn tower(&self, id: u64, poly: usize, tower: usize) -> Option<&[u64]> {
+        let entry = self.keys.get(&id)?;
+        if poly >= entry.polys || tower >= self.moduli.len() {
+            return None;
+        }
+        entry.used.store(true, Ordering::Relaxed);
+        let n = self.ring_dimension;
+        let start = entry.offset / 8 + (poly * self.moduli.len() + tower) * n;
+        Some(&self.data.words()[start..start + n])
+    }
+
+    /// The ids of keys no residue was read from, in ascending order
+    pub fn unused_key_ids(&self) -> Vec<u64> {
+        self.keys
+            .iter()
+            .filter(|(_, e)| !e.used.load(Ordering::Relaxed))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Key `id` of `crypto_context` in evaluation format, `None` if the file
+    /// has no such key.
+    ///
+    /// Marks key `id` as used. The key has no key tag, set it with
+    /// [`EvalKey::with_key_tag`] before storing it, e.g. with
+    /// [`CryptoContext::insert_eval_automorphism_keys`].
+    pub fn eval_key(&self, id: u64, crypto_context: CryptoContext) -> Result<Option<EvalKey>> {
+        let Some(entry) = self.keys.get(&id) else {
+            return Ok(None);
+        };
+        if entry.polys % 2 != 0 {
+            return Err(Error::EvalKeyFile(format!(
+                "key `{}` has an odd number of polynomials",
+                id
+            )));
+        }
+        let towers = self.moduli.len();
+        let poly = |index: usize| {
+            let residues = (0..towers)
+                .map(|tower| {
+                    self.tower(id, index, tower)
+                        .expect("the entry holds every poly and tower")
+                        .to_vec()
+                })
+                .collect::<Vec<_>>();
+            let mut poly = DcrtPoly::from_residues(self.params.clone(), &residues);
+            poly.switch_format();
+            poly
+        };
+        let elements = (0..entry.polys / 2)
+            .map(|digit| [poly(2 * digit), poly(2 * digit + 1)])
+            .collect();
+        EvalKey::new(crypto_context, entry.technique, entry.digit_size, elements).map(Some)
+    }
+
+    /// Copies of the keys `plan` needs, to write a smaller bundle with
+    /// [`write_eval_keys`]. Reading them does not mark them as used.
+    pub fn planned_records(&self, plan: &KeyPlan) -> Vec<EvalKeyRecord> {
+        let towers = self.moduli.len();
+        let n = self.ring_dimension;
+        let words = self.data.words();
+        self.keys
+            .iter()
+            .filter(|(id, _)| plan.contains(**id))
+            .map(|(id, entry)| EvalKeyRecord {
+                id: *id,
+                technique: entry.technique,
+                digit_size: entry.digit_size,
+                polys: (0..entry.polys)
+                    .map(|poly| {
+                        let start = entry.offset / 8 + poly * towers * n;
+                        words[start..start + towers * n].to_vec()
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Ask the OS to start paging in key `id` ahead of its use
+    pub fn prefetch(&self, id: u64) {
+        if let Some(entry) = self.keys.get(&id) {
+            let len = 8 * entry.polys * self.moduli.len() * self.ring_dimension;
+            self.data.will_need(entry.offset, len);
+        }
+    }
+
+    fn parse(data: Backing) -> Result<Self> {
+        let bytes = data.bytes();
+        let err = |msg: &str| Error::EvalKeyFile(msg.to_string());
+        let read = |at: usize| -> Result<u64> {
+            bytes
+                .get(at..at + 8)
+                .and_then(|b| b.try_into().ok())
+                .map(u64::from_le_bytes)
+                .ok_or_else(|| err("truncated header"))
+        };
+        let read_usize = |at: usize| -> Result<usize> {
+            usize::try_from(read(at)?).map_err(|_| err("header value too large"))
+        };
+
+        if bytes.get(..8) != Some(MAGIC.as_slice()) {
+            return Err(err("bad magic"));
+        }
+        if read(8)? as u32 != VERSION {
+            return Err(err("unsupported version"));
+        }
+        let cyclotomic_order = read_usize(16)?;
+        let towers = read_usize(24)?;
+        let key_count = read_usize(32)?;
+
+        let odd = |v: u64| -> Result<Odd<U64>> {
+            Option::from(Odd::new(U64::from_u64(v))).ok_or_else(|| err("even tower modulus"))
+        };
+        let mut at = FIXED_HEADER;
+        let capacity = towers.min(bytes.len() / TOWER_ENTRY);
+        let mut moduli = Vec::with_capacity(capacity);
+        let mut roots = Vec::with_capacity(capacity);
+        let mut big_moduli = Vec::with_capacity(capacity);
+        let mut big_roots = Vec::with_capacity(capacity);
+        for _ in 0..towers {
+            moduli.push(read(at)?);
+            roots.push(U64::from_u64(read(at + 8)?));
+            big_moduli.push(odd(read(at + 16)?)?);
+            big_roots.push(U64::from_u64(read(at + 24)?));
+            at += TOWER_ENTRY;
+        }
+        let params = DcrtElementParamsBuilder::new(cyclotomic_order)
+            .moduli(moduli.iter().map(|&q| odd(q)).collect::<Result<_>>()?)
+            .roots_of_unity(roots)
+            .big_moduli(big_moduli)
+            .big_roots_of_unity(big_roots)
+            .build()
+            .map_err(|_| err("bad tower parameters"))?;
+
+        let ring_dimension = cyclotomic_order / 2;
+        let poly_bytes = ring_dimension
+            .checked_mul(towers)
+            .and_then(|x| x.checked_mul(8))
+            .ok_or_else(|| err("polynomial size overflows"))?;
+        let mut keys = BTreeMap::new();
+        for _ in 0..key_count {
+            let id = read(at)?;
+            let technique = KeySwitchTechnique::from(read_usize(at + 8)?);
+            let digit_size = read_usize(at + 16)?;
+            let polys = read_usize(at + 24)?;
+            let offset = read_usize(at + 32)?;
+            at += KEY_ENTRY;
+            let end = polys
+                .checked_mul(poly_bytes)
+                .and_then(|len| len.checked_add(offset))
+                .ok_or_else(|| err("key size overflows"))?;
+            if offset % ALIGNMENT != 0 || end > bytes.len() {
+                return Err(Error::EvalKeyFile(format!(
+                    "key `{}` is misaligned or out of bounds",
+                    id
+                )));
+            }
+            let entry = KeyEntry {
+                technique,
+                digit_size,
+                polys,
+                offset,
+                used: AtomicBool::new(false),
+            };
+            if keys.insert(id, entry).is_some() {
+                return Err(Error::EvalKeyFile(format!("duplicate key id `{}`", id)));
+            }
+        }
+
+        Ok(Self {
+            data,
+            cyclotomic_order,
+            ring_dimension,
+            params,
+            moduli,
+            keys,
+        })
+    }
+}
+
+/// The file contents, either mapped or copied with the residues decoded
+#[derive(Debug)]
+enum Backing {
+    #[cfg(all(unix, target_endian = "little"))]
+    Mapped {
+        ptr: *mut libc::c_void,
+        len: usize,
+    },
+    Owned {
+        bytes: Vec<u8>,
+        words: Vec<u64>,
+    },
+}
+
+// SAFETY: the mapping is private and read only and is only unmapped on drop
+#[cfg(all(unix, target_endian = "little"))]
+unsafe impl Send for Backing {}
+// SAFETY: see above, shared access never mutates the mapping
+#[cfg(all(unix, target_endian = "little"))]
+unsafe impl Sync for Backing {}
+
+impl Backing {
+    #[cfg(all(unix, target_endian = "little"))]
+    fn map(file: &File) -> Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| Error::EvalKeyFile("file too large to map".to_string()))?;
+        if len == 0 {
+            return Ok(Self::owned(&[]));
+        }
+        // SAFETY: a fresh private read only mapping of an open file descriptor
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(Self::Mapped { ptr, len })
+    }
+
+    #[cfg(not(all(unix, target_endian = "little")))]
+    fn map(mut file: &File) -> Result<Self> {
+        use std::io::Read;
+
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        Ok(Self::owned(&bytes))
+    }
+
+    fn owned(bytes: &[u8]) -> Self {
+        let words = bytes
+            .chunks(8)
+            .map(|c| {
+                let mut w = [0u8; 8];
+                w[..c.len()].copy_from_slice(c);
+                u64::from_le_bytes(w)
+            })
+            .collect();
+        Self::Owned {
+            bytes: bytes.to_vec(),
+            words,
+        }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match self {
+            #[cfg(all(unix, target_endian = "little"))]
+            // SAFETY: the mapping is `len` readable bytes alive as long as `self`
+            Self::Mapped { ptr, len } => unsafe {
+                std::slice::from_raw_parts(*ptr as *const u8, *len)
+            },
+            Self::Owned { bytes, .. } => bytes,
+        }
+    }
+
+    /// The contents as native `u64`s, residues are stored little endian
+    fn words(&self) -> &[u64] {
+        match self {
+            #[cfg(all(unix, target_endian = "little"))]
+            // SAFETY: mappings are page aligned and only whole words are exposed
+            Self::Mapped { ptr, len } => unsafe {
+                std::slice::from_raw_parts(*ptr as *const u64, *len / 8)
+            },
+            Self::Owned { words, .. } => words,
+        }
+    }
+
+    fn will_need(&self, offset: usize, len: usize) {
+        #[cfg(all(unix, target_endian = "little"))]
+        if let Self::Mapped { ptr, .. } = self {
+            // SAFETY: sysconf has no preconditions
+            let page =
+                usize::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).unwrap_or(4096);
+            let start = offset - offset % page;
+            // SAFETY: advice on a range inside the mapping, failure is harmless
+            unsafe {
+                libc::madvise(
+                    (*ptr as *mut u8).add(start) as *mut libc::c_void,
+                    len + offset - start,
+                    libc::MADV_WILLNEED,
+                );
+            }
+        }
+        let _ = (offset, len);
+    }
+}
+
+impl Drop for Backing {
+    fn drop(&mut self) {
+        #[cfg(all(unix, target_endian = "little"))]
+        if let Self::Mapped { ptr, len } = self {
+            // SAFETY: unmapping the region created in `map`, no borrows outlive `self`
+            unsafe {
+                libc::munmap(*ptr, *len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::PlaintextEncodings;
+    use crate::pke::test_support::ckks_context;
+
+    #[test]
+    fn rotation_key_round_trip() {
+        let ckks = ckks_context(1);
+        let cc = ckks.crypto_context();
+        let key_pair = ckks.key_gen().expect("keys");
+        let keys = cc
+            .eval_at_index_key_gen(ckks.crypto_parameters(), key_pair.private_key(), &[1])
+            .expect("rotation key");
+        let (&index, key) = keys.iter().next().expect("one key");
+        let params = key.elements()[0][0].params().clone();
+
+        let mut file = Vec::new();
+        write_eval_keys(
+            &mut file,
+            &params,
+            &[EvalKeyRecord::from_eval_key(index, key)],
+        )
+        .expect("write");
+        let mapped = MappedEvalKeys::from_bytes(&file).expect("parse");
+        assert_eq!(mapped.element_params(), &params);
+        assert_eq!(mapped.unused_key_ids(), vec![index]);
+        let loaded = mapped
+            .eval_key(index, cc.clone())
+            .expect("load")
+            .expect("stored key")
+            .with_key_tag(key_pair.private_key().key_tag());
+        assert!(mapped.unused_key_ids().is_empty());
+        assert_eq!(&loaded, key);
+        assert!(
+            mapped
+                .eval_key(index + 2, cc.clone())
+                .expect("load")
+                .is_none()
+        );
+
+        CryptoContext::insert_eval_automorphism_keys(BTreeMap::from([(index, loaded)]))
+            .expect("insert");
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
+        let ciphertext = ckks
+            .encrypt(
+                key_pair.public_key(),
+                &ckks.encode(&values).expect("encode"),
+            )
+            .expect("encrypt");
+        let rotated = cc.eval_at_index(&ciphertext, 1).expect("rotate");
+        let PlaintextEncodings::Ckks(plaintext) = ckks
+            .decrypt(key_pair.private_key(), &rotated)
+            .expect("decrypt")
+        else {
+            panic!("not a CKKS plaintext");
+        };
+        for (i, x) in plaintext.values().iter().take(values.len()).enumerate() {
+            let expected = values[(i + 1) % values.len()];
+            assert!((x - expected).abs() < 1e-4, "{x} != {expected}");
+        }
+    }
+
+    #[test]
+    fn open_and_read_files() {
+        let params = DcrtElementParamsBuilder::new(16)
+            .depth(2)
+            .bits(30)
+            .build()
+            .expect("parameters");
+        let records = [EvalKeyRecord {
+            id: 5,
+            technique: KeySwitchTechnique::Hybrid,
+            digit_size: 1,
+            polys: vec![(0..16).collect(), (16..32).collect()],
+        }];
+        let path = std::env::temp_dir().join(format!("evk-{}.bin", std::process::id()));
+        write_eval_keys(File::create(&path).expect("create"), &params, &records).expect("write");
+
+        // SAFETY: the file is private to this test and not modified while mapped
+        let mapped = unsafe { MappedEvalKeys::open(&path) }.expect("open");
+        let read = MappedEvalKeys::read(&path).expect("read");
+        std::fs::remove_file(&path).expect("remove");
+        for keys in [mapped, read] {
+            assert_eq!(keys.element_params(), &params);
+            assert_eq!(keys.tower(5, 1, 1), Some(&records[0].polys[1][8..]));
+            let plan = KeyPlan::new(16).expect("plan").index(5);
+            assert_eq!(keys.planned_records(&plan), records);
+        }
+    }
+}
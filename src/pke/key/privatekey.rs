@@ -0,0 +1,70 @@
+use crate::constants::SecretKeyDistribution;
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::crypto_object::CryptoObject;
+use crate::pke::CryptoContext;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug, Formatter};
+
+/// An RLWE secret key and the distribution it was drawn from
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrivateKey {
+    crypto_context: CryptoContext,
+    secret: DcrtPoly,
+    distribution: SecretKeyDistribution,
+    #[serde(default)]
+    key_tag: String,
+}
+
+impl Debug for PrivateKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrivateKey")
+            .field("crypto_context", &self.crypto_context)
+            .field("params", self.secret.params())
+            .field("distribution", &self.distribution)
+            .field("key_tag", &self.key_tag)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CryptoObject for PrivateKey {
+    fn get_crypto_context(&self) -> &CryptoContext {
+        &self.crypto_context
+    }
+}
+
+impl PrivateKey {
+    /// Wrap `secret` drawn from `distribution`
+    pub fn new(
+        crypto_context: CryptoContext,
+        secret: DcrtPoly,
+        distribution: SecretKeyDistribution,
+    ) -> Self {
+        Self {
+            crypto_context,
+            secret,
+            distribution,
+            key_tag: String::new(),
+        }
+    }
+
+    /// Tag the key, ciphertexts and evaluation keys it produces carry `key_tag`
+    pub fn with_key_tag(mut self, key_tag: impl Into<String>) -> Self {
+        self.key_tag = key_tag.into();
+        self
+    }
+
+    /// The tag shared with the public key of the pair
+    pub fn key_tag(&self) -> &str {
+        &self.key_tag
+    }
+
+    /// The secret polynomial
+    pub fn secret(&self) -> &DcrtPoly {
+        &self.secret
+    }
+
+    /// The distribution the secret was drawn from
+    pub fn distribution(&self) -> SecretKeyDistribution {
+        self.distribution
+    }
+}
@@ -0,0 +1,323 @@
+//! Pruning of rotation and summation key sets
+//!
+//! Key generation usually covers every rotation a circuit might use, but a
+//! deployment only needs the automorphisms its circuit plan performs. A
+//! [`KeyPlan`] lists those automorphism indices so generated key maps can be
+//! cut down before shipping, and [`UsageTrackedKeys`] reports which shipped
+//! keys were never touched at runtime. The keys a [`CryptoContext`] stores
+//! by key tag are tracked that way, see
+//! [`CryptoContext::unused_automorphism_keys`] and
+//! [`CryptoContext::prune_automorphism_keys`].
+//!
+//! [`CryptoContext`]: crate::pke::CryptoContext
+//! [`CryptoContext::unused_automorphism_keys`]: crate::pke::CryptoContext::unused_automorphism_keys
+//! [`CryptoContext::prune_automorphism_keys`]: crate::pke::CryptoContext::prune_automorphism_keys
+
+use crate::error::{Error, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The generator of the rotation group of power of two cyclotomics
+const ROTATION_GENERATOR: u64 = 5;
+
+/// The automorphism index rotating the slots by `rotation`, i.e.
+/// `5^rotation mod cyclotomic_order`.
+///
+/// Negative rotations rotate right. `cyclotomic_order` must be a power of
+/// two of at least 4.
+pub fn automorphism_index(rotation: i64, cyclotomic_order: usize) -> Result<u64> {
+    if cyclotomic_order < 4 || !cyclotomic_order.is_power_of_two() {
+        return Err(Error::CcParams(format!(
+            "cyclotomic order {cyclotomic_order} is not a power of two of at least 4"
+        )));
+    }
+    let m = cyclotomic_order as u64;
+    // 5 generates a subgroup of order m / 4, so rotations wrap around there
+    let mut exponent = rotation.rem_euclid(cyclotomic_order as i64 / 4) as u64;
+    let (mut base, mut index) = (ROTATION_GENERATOR, 1);
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            index = index * base % m;
+        }
+        base = base * base % m;
+        exponent >>= 1;
+    }
+    Ok(index)
+}
+
+/// The automorphism indices a circuit plan needs keys for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyPlan {
+    cyclotomic_order: usize,
+    indices: BTreeSet<u64>,
+}
+
+impl KeyPlan {
+    /// An empty plan for keys of `cyclotomic_order`
+    pub fn new(cyclotomic_order: usize) -> Result<Self> {
+        automorphism_index(0, cyclotomic_order)?;
+        Ok(Self {
+            cyclotomic_order,
+            indices: BTreeSet::new(),
+        })
+    }
+
+    /// Also keep the key rotating by `rotation`, a rotation by zero needs no key
+    pub fn rotation(mut self, rotation: i64) -> Self {
+        let index = automorphism_index(rotation, self.cyclotomic_order)
+            .expect("the cyclotomic order was checked by new");
+        if index != 1 {
+            self.indices.insert(index);
+        }
+        self
+    }
+
+    /// Also keep the keys for every rotation in `rotations`
+    pub fn rotations(self, rotations: impl IntoIterator<Item = i64>) -> Self {
+        rotations.into_iter().fold(self, Self::rotation)
+    }
+
+    /// Also keep the keys EvalSum uses over `batch_size` slots, the rotations
+    /// by every power of two below `batch_size`
+    pub fn sum(self, batch_size: usize) -> Self {
+        let rotations = (0..usize::BITS)
+            .map(|i| 1usize << i)
+            .take_while(|&r| r < batch_size)
+            .map(|r| r as i64);
+        self.rotations(rotations)
+    }
+
+    /// Also keep the key of the complex conjugation used by CKKS
+    pub fn conjugation(mut self) -> Self {
+        self.indices.insert(self.cyclotomic_order as u64 - 1);
+        self
+    }
+
+    /// Also keep the key of the raw automorphism `index`
+    pub fn index(mut self, index: u64) -> Self {
+        self.indices.insert(index);
+        self
+    }
+
+    /// The cyclotomic order the indices are computed for
+    pub fn cyclotomic_order(&self) -> usize {
+        self.cyclotomic_order
+    }
+
+    /// The automorphism indices in ascending order
+    pub fn indices(&self) -> &BTreeSet<u64> {
+        &self.indices
+    }
+
+    /// Whether the plan needs the key for `index`
+    pub fn contains(&self, index: u64) -> bool {
+        self.indices.contains(&index)
+    }
+
+    /// Drop every key of `keys` the plan does not need, returning the
+    /// removed indices in ascending order
+    pub fn prune<K>(&self, keys: &mut BTreeMap<u64, K>) -> Vec<u64> {
+        let removed = keys
+            .keys()
+            .copied()
+            .filter(|index| !self.contains(*index))
+            .collect::<Vec<_>>();
+        keys.retain(|index, _| self.contains(*index));
+        removed
+    }
+
+    /// The indices the plan needs that are not in `available`
+    pub fn missing(&self, available: impl IntoIterator<Item = u64>) -> Vec<u64> {
+        let available = available.into_iter().collect::<BTreeSet<_>>();
+        self.indices.difference(&available).copied().collect()
+    }
+}
+
+/// Keys by automorphism index that remember which ones were looked up
+#[derive(Debug)]
+pub struct UsageTrackedKeys<K> {
+    keys: BTreeMap<u64, (K, AtomicBool)>,
+}
+
+impl<K> Default for UsageTrackedKeys<K> {
+    fn default() -> Self {
+        Self {
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K> FromIterator<(u64, K)> for UsageTrackedKeys<K> {
+    fn from_iter<I: IntoIterator<Item = (u64, K)>>(iter: I) -> Self {
+        Self {
+            keys: iter
+                .into_iter()
+                .map(|(index, key)| (index, (key, AtomicBool::new(false))))
+                .collect(),
+        }
+    }
+}
+
+/// Added keys replace stored ones of the same index and count as unused
+impl<K> Extend<(u64, K)> for UsageTrackedKeys<K> {
+    fn extend<I: IntoIterator<Item = (u64, K)>>(&mut self, iter: I) {
+        self.keys.extend(
+            iter.into_iter()
+                .map(|(index, key)| (index, (key, AtomicBool::new(false)))),
+        );
+    }
+}
+
+impl<K> UsageTrackedKeys<K> {
+    /// Track lookups of `keys`, none of which count as used yet
+    pub fn new(keys: BTreeMap<u64, K>) -> Self {
+        keys.into_iter().collect()
+    }
+
+    /// The key for `index`, marking it as used
+    pub fn get(&self, index: u64) -> Option<&K> {
+        self.keys.get(&index).map(|(key, used)| {
+            used.store(true, Ordering::Relaxed);
+            key
+        })
+    }
+
+    /// The number of stored keys
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether no key is stored
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// The indices looked up at least once, in ascending order
+    pub fn used(&self) -> Vec<u64> {
+        self.indices_where(true)
+    }
+
+    /// The indices never looked up, in ascending order
+    pub fn unused(&self) -> Vec<u64> {
+        self.indices_where(false)
+    }
+
+    /// Forget all lookups so far
+    pub fn reset_usage(&self) {
+        self.keys
+            .values()
+            .for_each(|(_, used)| used.store(false, Ordering::Relaxed));
+    }
+
+    /// The keys without their usage, none of them marked as used
+    pub fn to_map(&self) -> BTreeMap<u64, K>
+    where
+        K: Clone,
+    {
+        self.keys
+            .iter()
+            .map(|(index, (key, _))| (*index, key.clone()))
+            .collect()
+    }
+
+    /// Keep only the keys for which `f` holds
+    pub fn retain(&mut self, mut f: impl FnMut(u64, &K) -> bool) {
+        self.keys.retain(|index, (key, _)| f(*index, key));
+    }
+
+    /// Drop every key `plan` does not need, returning the removed indices in
+    /// ascending order, see [`KeyPlan::prune`]
+    pub fn prune(&mut self, plan: &KeyPlan) -> Vec<u64> {
+        plan.prune(&mut self.keys)
+    }
+
+    /// The keys that were looked up, dropping the rest
+    pub fn into_used(self) -> BTreeMap<u64, K> {
+        self.keys
+            .into_iter()
+            .filter(|(_, (_, used))| used.load(Ordering::Relaxed))
+            .map(|(index, (key, _))| (index, key))
+            .collect()
+    }
+
+    fn indices_where(&self, was_used: bool) -> Vec<u64> {
+        self.keys
+            .iter()
+            .filter(|(_, (_, used))| used.load(Ordering::Relaxed) == was_used)
+            .map(|(index, _)| *index)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pke::CryptoContext;
+    use crate::pke::test_support::bfv_context;
+
+    #[test]
+    fn tracked_keys_remember_lookups() {
+        let mut keys = UsageTrackedKeys::new(BTreeMap::from([(3, 'a'), (5, 'b'), (7, 'c')]));
+        assert_eq!(keys.unused(), [3, 5, 7]);
+        assert_eq!(keys.get(5), Some(&'b'));
+        assert_eq!(keys.get(9), None);
+        assert_eq!((keys.used(), keys.unused()), (vec![5], vec![3, 7]));
+
+        keys.extend([(5, 'd')]);
+        assert_eq!(keys.unused(), [3, 5, 7]);
+        keys.get(7);
+        let plan = KeyPlan::new(16).expect("plan").index(3).index(7);
+        assert_eq!(keys.prune(&plan), [5]);
+        assert_eq!(keys.to_map(), BTreeMap::from([(3, 'a'), (7, 'c')]));
+        assert_eq!(keys.into_used(), BTreeMap::from([(7, 'c')]));
+    }
+
+    #[test]
+    fn context_keys_report_use_and_prune_to_a_plan() {
+        let tag = "pruning::tests";
+        let bfv = bfv_context(1);
+        let cc = bfv.crypto_context();
+        let order = cc.element_params.cyclotomic_order;
+        let index = |rotation| automorphism_index(rotation, order).expect("index");
+        let (public_key, private_key) = bfv.key_gen().expect("keys").into_parts();
+        let (public_key, private_key) =
+            (public_key.with_key_tag(tag), private_key.with_key_tag(tag));
+        cc.eval_at_index_key_gen(bfv.crypto_parameters(), &private_key, &[1, 2, 4])
+            .expect("rotation keys");
+        let sorted = |mut indices: Vec<u64>| {
+            indices.sort();
+            indices
+        };
+        let unused = || CryptoContext::unused_automorphism_keys(tag).expect("unused keys");
+        assert_eq!(unused(), sorted(vec![index(1), index(2), index(4)]));
+
+        let plaintext = bfv.encode_packed(&[1, 2, 3]).expect("encode");
+        let ciphertext = bfv.encrypt(&public_key, &plaintext).expect("encrypt");
+        cc.eval_at_index(&ciphertext, 2).expect("rotate");
+        assert_eq!(unused(), sorted(vec![index(1), index(4)]));
+
+        let plan = KeyPlan::new(order).expect("plan").rotations([2, 4]);
+        assert_eq!(
+            CryptoContext::prune_automorphism_keys(tag, &plan).expect("prune"),
+            [index(1)]
+        );
+        let stored = CryptoContext::get_eval_automorphism_keys(tag)
+            .expect("stored keys")
+            .expect("keys of the tag");
+        assert!(stored.keys().eq(plan.indices()));
+        assert_eq!(unused(), [index(4)]);
+        // Odd rotations cannot be composed from the remaining keys
+        assert!(cc.eval_at_index(&ciphertext, 1).is_err());
+
+        let other_order = KeyPlan::new(2 * order).expect("plan");
+        assert!(matches!(
+            CryptoContext::prune_automorphism_keys(tag, &other_order),
+            Err(Error::CcParams(_))
+        ));
+        assert!(
+            CryptoContext::prune_automorphism_keys("pruning::tests::none", &plan)
+                .expect("prune")
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::crypto_object::CryptoObject;
+use crate::error::{Error, Result};
+use crate::pke::CryptoContext;
+use serde::{Deserialize, Serialize};
+
+/// An RLWE public key `(-a s + e, a)`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicKey {
+    crypto_context: CryptoContext,
+    elements: [DcrtPoly; 2],
+    #[serde(default)]
+    key_tag: String,
+}
+
+impl CryptoObject for PublicKey {
+    fn get_crypto_context(&self) -> &CryptoContext {
+        &self.crypto_context
+    }
+}
+
+impl PublicKey {
+    /// Wrap `elements`, which must share their parameters and format
+    pub fn new(crypto_context: CryptoContext, elements: [DcrtPoly; 2]) -> Result<Self> {
+        let [pk0, pk1] = &elements;
+        if pk0.params() != pk1.params() || pk0.format() != pk1.format() {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        Ok(Self {
+            crypto_context,
+            elements,
+            key_tag: String::new(),
+        })
+    }
+
+    /// Tag the key, ciphertexts encrypted under it carry `key_tag`
+    pub fn with_key_tag(mut self, key_tag: impl Into<String>) -> Self {
+        self.key_tag = key_tag.into();
+        self
+    }
+
+    /// The tag shared with the private key of the pair
+    pub fn key_tag(&self) -> &str {
+        &self.key_tag
+    }
+
+    /// The two elements of the key
+    pub fn elements(&self) -> &[DcrtPoly; 2] {
+        &self.elements
+    }
+
+    /// The two elements of the key, dropping the context
+    pub fn into_elements(self) -> [DcrtPoly; 2] {
+        self.elements
+    }
+}
@@ -0,0 +1,376 @@
+//! Key switching
+//!
+//! A ciphertext element multiplied by one secret when decrypting, e.g. the
+//! `s^2` part of a product, is re-expressed under another secret with a key
+//! switching key. With [`KeySwitchTechnique::Bv`](crate::constants::KeySwitchTechnique::Bv)
+//! the element is decomposed into its RNS towers, and every tower into
+//! `digit_size` bit digits unless `digit_size` is zero. Each digit is small
+//! and multiplies its encryption of `old * gadget` under the new secret, so
+//! the key noise only grows by the digit size. With
+//! [`KeySwitchTechnique::Hybrid`] the towers are grouped into a few large
+//! digits that are extended to an auxiliary modulus `P`, and the key noise
+//! is divided by `P` instead, see [`Hybrid`].
+
+use crate::ActingPrimitive;
+use crate::constants::{
+    KeySwitchTechnique, MAX_MODULUS_SIZE, PolynomialRingFormat, SecretKeyDistribution,
+};
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::core::math::scratch;
+use crate::error::{Error, Result};
+use crate::pke::encryption_pool::in_format_of;
+use crate::pke::{CryptoContext, CryptoParametersRns, EvalKey, Hybrid, ZeroEncryption};
+use rand::CryptoRng;
+
+/// A key of `crypto_context` switching `old` to `new` with the technique
+/// and digits of `params`.
+///
+/// BV keys record the bits of their digits, hybrid keys the towers of their
+/// large digits.
+pub(crate) fn key_gen(
+    crypto_context: CryptoContext,
+    params: &CryptoParametersRns,
+    old: &DcrtPoly,
+    new: &DcrtPoly,
+    rng: &mut impl CryptoRng,
+) -> Result<EvalKey> {
+    let rlwe = &params.rlwe_crypto_parameters;
+    let std_dev = rlwe.discrete_gaussian_std_dev;
+    let (digit_size, elements) = match params.key_switch_technique {
+        KeySwitchTechnique::Bv => (
+            rlwe.digit_size,
+            bv_key_gen(old, new, rlwe.digit_size, std_dev, rng)?,
+        ),
+        KeySwitchTechnique::Hybrid => {
+            let alpha =
+                Hybrid::towers_per_digit(new.params().params().len(), params.num_large_digits);
+            (alpha, Hybrid::key_gen(old, new, alpha, std_dev, rng)?)
+        }
+        technique => {
+            return Err(Error::CcParams(format!(
+                "key switching technique {technique} is not supported"
+            )));
+        }
+    };
+    EvalKey::new(
+        crypto_context,
+        params.key_switch_technique,
+        digit_size,
+        elements,
+    )
+}
+
+/// The pair over the towers of `c` decrypting under the new secret of `key`
+/// like `c` under the old one, in evaluation format
+pub(crate) fn switch(c: &DcrtPoly, key: &EvalKey) -> Result<[DcrtPoly; 2]> {
+    match key.key_switch_technique() {
+        KeySwitchTechnique::Bv => bv_switch(c, key.elements(), key.digit_size()),
+        KeySwitchTechnique::Hybrid => {
+            let Some([k0, _]) = key.elements().first() else {
+                return Err(Error::CcParams(
+                    "a key switching key has no digits".to_string(),
+                ));
+            };
+            Hybrid::new(c.params(), k0.params(), key.digit_size())?.switch(c, key.elements())
+        }
+        technique => Err(Error::CcParams(format!(
+            "key switching technique {technique} is not supported"
+        ))),
+    }
+}
+
+/// The digits of a ciphertext element decomposed once for any number of
+/// automorphisms, see
+/// [`CryptoContext::eval_fast_rotation_precompute`]
+#[derive(Clone, Debug)]
+pub struct FastRotationPrecompute {
+    technique: KeySwitchTechnique,
+    digit_size: usize,
+    /// The constants of hybrid key switching
+    hybrid: Option<Hybrid>,
+    /// In evaluation format, over `P ∪ Q_l` with hybrid key switching
+    digits: Vec<DcrtPoly>,
+}
+
+/// Decompose `c` for switching with keys generated like `key`
+pub(crate) fn precompute(c: &DcrtPoly, key: &EvalKey) -> Result<FastRotationPrecompute> {
+    let Some([k0, _]) = key.elements().first() else {
+        return Err(Error::CcParams(
+            "a key switching key has no digits".to_string(),
+        ));
+    };
+    let (hybrid, digits) = match key.key_switch_technique() {
+        KeySwitchTechnique::Bv => (
+            None,
+            bv_digits(c, key.digit_size())?
+                .into_iter()
+                .map(|d| in_format_of(d, k0))
+                .collect(),
+        ),
+        KeySwitchTechnique::Hybrid => {
+            let hybrid = Hybrid::new(c.params(), k0.params(), key.digit_size())?;
+            let digits = hybrid.mod_up(c)?;
+            (Some(hybrid), digits)
+        }
+        technique => {
+            return Err(Error::CcParams(format!(
+                "key switching technique {technique} is not supported"
+            )));
+        }
+    };
+    Ok(FastRotationPrecompute {
+        technique: key.key_switch_technique(),
+        digit_size: key.digit_size(),
+        hybrid,
+        digits,
+    })
+}
+
+/// [`switch`] of the image of the decomposed element under `X -> X^index`,
+/// permuting the digits instead of decomposing the image
+pub(crate) fn switch_precomputed(
+    precompute: &FastRotationPrecompute,
+    index: usize,
+    key: &EvalKey,
+) -> Result<[DcrtPoly; 2]> {
+    if precompute.technique != key.key_switch_technique()
+        || precompute.digit_size != key.digit_size()
+    {
+        return Err(Error::CcParams(
+            "the precomputation was made for keys of another technique".to_string(),
+        ));
+    }
+    let digits = precompute
+        .digits
+        .iter()
+        .map(|d| d.automorphism(index))
+        .collect::<Vec<_>>();
+    match &precompute.hybrid {
+        Some(hybrid) => hybrid.key_product(&digits, key.elements()),
+        None => bv_key_product(digits, key.elements()),
+    }
+}
+
+/// Encryptions under `new` of `old` times every BV gadget digit, both
+/// secrets in evaluation format
+pub(crate) fn bv_key_gen(
+    old: &DcrtPoly,
+    new: &DcrtPoly,
+    digit_size: usize,
+    std_dev: f64,
+    rng: &mut impl CryptoRng,
+) -> Result<Vec<[DcrtPoly; 2]>> {
+    bv_gadget_key(old, new, digit_size, || {
+        ZeroEncryption::with_secret_key_and_rng(new, std_dev, rng)
+    })
+}
+
+/// [`bv_key_gen`] encrypting under `new_public_key` instead of the new
+/// secret, as proxy re-encryption keys are
+pub(crate) fn bv_public_key_gen(
+    old: &DcrtPoly,
+    new_public_key: &[DcrtPoly; 2],
+    digit_size: usize,
+    std_dev: f64,
+    secret_key_distribution: SecretKeyDistribution,
+    rng: &mut impl CryptoRng,
+) -> Result<Vec<[DcrtPoly; 2]>> {
+    bv_gadget_key(old, &new_public_key[0], digit_size, || {
+        ZeroEncryption::with_public_key_and_rng(
+            new_public_key,
+            std_dev,
+            secret_key_distribution,
+            rng,
+        )
+    })
+}
+
+/// `old` times every BV gadget digit added to the encryptions of zero of
+/// `zero`, which are over the towers and in the format of `like`
+fn bv_gadget_key(
+    old: &DcrtPoly,
+    like: &DcrtPoly,
+    digit_size: usize,
+    mut zero: impl FnMut() -> Result<ZeroEncryption>,
+) -> Result<Vec<[DcrtPoly; 2]>> {
+    if old.params() != like.params() {
+        return Err(Error::DcrtElementParamsMismatch);
+    }
+    let old = in_format_of(old.clone(), like);
+    let towers = old.params().params();
+    let len = old.towers().first().map_or(0, |t| t.len());
+    let mut key = Vec::new();
+    for (i, tower) in towers.iter().enumerate() {
+        let q = tower.ciphertext_modulus.get().to_primitive();
+        for digit in 0..digit_count(q, digit_size)? {
+            // The constant 2^(w digit) modulo q_i and zero in the other towers
+            let mut gadget = vec![vec![0; len]; towers.len()];
+            gadget[i][0] = ((1u128 << (digit_size * digit)) % q as u128) as u64;
+            let mut gadget =
+                in_format_of(DcrtPoly::from_residues(old.params().clone(), &gadget), like);
+            gadget *= &old;
+            key.push(zero()?.encrypt(&gadget));
+        }
+    }
+    Ok(key)
+}
+
+/// The pair decrypting under the new secret of `key` like `c` under the
+/// old one, in the format of the key
+pub(crate) fn bv_switch(
+    c: &DcrtPoly,
+    key: &[[DcrtPoly; 2]],
+    digit_size: usize,
+) -> Result<[DcrtPoly; 2]> {
+    bv_key_product(bv_digits(c, digit_size)?, key)
+}
+
+/// The inner product of the BV `digits` with `key`, in the format of the key
+fn bv_key_product(digits: Vec<DcrtPoly>, key: &[[DcrtPoly; 2]]) -> Result<[DcrtPoly; 2]> {
+    let (Some(params), Some([k0, _])) = (digits.first().map(|d| d.params().clone()), key.first())
+    else {
+        return Err(Error::CcParams(
+            "a key switching key has no digits".to_string(),
+        ));
+    };
+    let mismatch = || {
+        Error::CcParams(format!(
+            "a key of {} digits cannot switch an element of {} digits",
+            key.len(),
+            digits.len()
+        ))
+    };
+    if digits.len() > key.len() {
+        return Err(mismatch());
+    }
+    // The digits of the leading towers come first, so a key over more
+    // towers switches elements at lower levels with its leading digits
+    let towers = params.params().len();
+    let mut switched = [
+        DcrtPoly::zero(params.clone()),
+        DcrtPoly::zero(params.clone()),
+    ]
+    .map(|zero| in_format_of(zero, k0));
+    for (digit, pair) in digits.iter().zip(key) {
+        let digit = in_format_of(digit.clone(), k0);
+        for (sum, k) in switched.iter_mut().zip(pair) {
+            let shrunk;
+            let k = if k.params() == &params {
+                k
+            } else {
+                shrunk = k.shrink(towers);
+                &shrunk
+            };
+            if k.params() != &params {
+                return Err(mismatch());
+            }
+            let mut term = digit.clone();
+            term *= k;
+            *sum += &term;
+        }
+    }
+    Ok(switched)
+}
+
+/// The BV digits of `c` in coefficient format, tower by tower.
+///
+/// Whole towers are lifted centered, so a digit is at most `q_i / 2` in
+/// absolute value. Smaller digits are the unsigned base `2^digit_size`
+/// digits of each residue.
+fn bv_digits(c: &DcrtPoly, digit_size: usize) -> Result<Vec<DcrtPoly>> {
+    let mut coefficients = c.clone();
+    if coefficients.format() == PolynomialRingFormat::Evaluation {
+        coefficients.switch_format();
+    }
+    let moduli = c
+        .params()
+        .params()
+        .iter()
+        .map(|p| p.ciphertext_modulus.get().to_primitive())
+        .collect::<Vec<_>>();
+    let counts = moduli
+        .iter()
+        .map(|&q| digit_count(q, digit_size))
+        .collect::<Result<Vec<_>>>()?;
+    let len = coefficients.towers().first().map_or(0, |t| t.len());
+    // One pooled buffer per tower, refilled for every digit
+    let mut residues = moduli
+        .iter()
+        .map(|_| scratch::take_residues(len))
+        .collect::<Vec<_>>();
+    let mut digits = Vec::new();
+    for ((tower, &q), &count) in coefficients.residues().iter().zip(&moduli).zip(&counts) {
+        for digit in 0..count {
+            for (out, &m) in residues.iter_mut().zip(&moduli) {
+                for (o, &v) in out.iter_mut().zip(tower) {
+                    *o = match digit_size {
+                        0 if v > q / 2 => (m - (q - v) % m) % m,
+                        0 => v % m,
+                        w => ((v >> (w * digit)) & ((1 << w) - 1)) % m,
+                    };
+                }
+            }
+            digits.push(DcrtPoly::from_residues(c.params().clone(), &residues));
+        }
+    }
+    residues.into_iter().for_each(scratch::give_residues);
+    Ok(digits)
+}
+
+/// The digits of a residue modulo `q`, one for a whole tower
+fn digit_count(q: u64, digit_size: usize) -> Result<usize> {
+    if digit_size > MAX_MODULUS_SIZE {
+        return Err(Error::CcParams(format!(
+            "digits of {digit_size} bits are larger than a tower"
+        )));
+    }
+    let bits = (u64::BITS - q.leading_zeros()) as usize;
+    Ok(match digit_size {
+        0 => 1,
+        w => bits.div_ceil(w),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::KeySwitchTechnique;
+    use crate::encoding::PlaintextEncodings;
+    use crate::pke::test_support::{RING_DIMENSION, bfv_params};
+    use rand::Rng;
+
+    #[test]
+    fn switch_then_decrypt_under_the_new_key() {
+        for technique in [KeySwitchTechnique::Bv, KeySwitchTechnique::Hybrid] {
+            let bfv = bfv_params()
+                .key_switch_technique(technique)
+                .multiplicative_depth(1)
+                .build()
+                .expect("parameters");
+            let cc = bfv.crypto_context();
+            let (alice, bob) = (bfv.key_gen().expect("keys"), bfv.key_gen().expect("keys"));
+            let mut rng = rand::rng();
+            let message = (0..RING_DIMENSION)
+                .map(|_| rng.random_range(-32768..=32768))
+                .collect::<Vec<i64>>();
+            let plaintext = bfv.encode(&message).expect("encode");
+            let ciphertext = bfv
+                .encrypt(alice.public_key(), &plaintext)
+                .expect("encrypt");
+            let key = cc
+                .key_switch_gen(
+                    bfv.crypto_parameters(),
+                    alice.private_key(),
+                    bob.private_key(),
+                )
+                .expect("key switching key");
+            let switched = cc.key_switch(&ciphertext, &key).expect("switch");
+            assert_eq!(switched.key_tag(), bob.private_key().key_tag());
+            let PlaintextEncodings::Coefficient(decrypted) =
+                bfv.decrypt(bob.private_key(), &switched).expect("decrypt")
+            else {
+                panic!("a coefficient plaintext");
+            };
+            assert_eq!(decrypted.coefficients(), message, "{technique}");
+        }
+    }
+}
@@ -0,0 +1,342 @@
+//! Threshold FHE with n-of-n joint keys
+//!
+//! Every party holds a share of a joint secret `s = s_1 + ... + s_n` that is
+//! never assembled. The first party generates an ordinary key pair with
+//! [`CryptoContext::key_gen`], every further party adds its share to the
+//! public key of the previous one with [`CryptoContext::multiparty_key_gen`].
+//! Ciphertexts under the last public key only decrypt with the help of all
+//! parties: the first one computes a partial decryption with
+//! [`CryptoContext::multiparty_decrypt_lead`], the others with
+//! [`CryptoContext::multiparty_decrypt_main`], and anyone combines them with
+//! [`CryptoContext::multiparty_decrypt_fusion`]. Partial decryptions are
+//! flooded with noise of [`CcParams::flooding_std_dev`] so they reveal
+//! nothing about the shares, [`MultipartyMode::NoiseFlooding`] adds towers
+//! for it to fit in.
+//!
+//! The joint secret and the joint error grow with the number of parties, so
+//! the parameters should be generated for [`CcParams::threshold_parties`]
+//! parties.
+//!
+//! [`CcParams::threshold_parties`]: crate::pke::CcParams::threshold_parties
+//! [`CcParams::flooding_std_dev`]: crate::pke::CcParams::flooding_std_dev
+
+use crate::arena::ArenaKind;
+use crate::ciphertext::Ciphertext;
+use crate::constants::MultipartyMode;
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::crypto_object::CryptoObject;
+use crate::encoding::PlaintextEncodings;
+use crate::error::{Error, Result};
+use crate::pke::encryption_pool::{in_format_of, with_gaussian};
+use crate::pke::scaling::tower_count;
+use crate::pke::{
+    CryptoContext, CryptoParametersRns, KeyPair, PrivateKey, PublicKey, ZeroEncryption,
+};
+use rand::CryptoRng;
+
+impl CryptoContext {
+    /// The key pair of the next party of a joint key: a fresh secret share
+    /// and `public_key` extended to the sum of the secrets behind it and the
+    /// share. Both keep the tag of `public_key`.
+    pub fn multiparty_key_gen(
+        &self,
+        params: &CryptoParametersRns,
+        public_key: &PublicKey,
+    ) -> Result<KeyPair> {
+        self.multiparty_key_gen_with_rng(params, public_key, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::multiparty_key_gen`] drawing randomness from `rng`
+    pub fn multiparty_key_gen_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        public_key: &PublicKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<KeyPair> {
+        if public_key.get_crypto_context() != self {
+            return Err(Error::CcParams(
+                "the public key belongs to another crypto context".to_string(),
+            ));
+        }
+        let rlwe = &params.rlwe_crypto_parameters;
+        let secret = self.secret_key_gen(params, rng)?;
+        let [b, a] = public_key.elements();
+        let ZeroEncryption { c0, c1 } = ZeroEncryption::with_secret_key_a_and_rng(
+            &secret,
+            a,
+            rlwe.discrete_gaussian_std_dev,
+            rng,
+        )?;
+        let mut joint = c0;
+        joint += &in_format_of(b.clone(), &joint);
+        let key_tag = public_key.key_tag();
+        KeyPair::new(
+            PublicKey::new(self.clone(), [joint, c1])?.with_key_tag(key_tag),
+            PrivateKey::new(self.clone(), secret, rlwe.secret_key_distribution)
+                .with_key_tag(key_tag),
+        )
+    }
+
+    /// The partial decryption `c0 + c1 s_1 + e` of `ciphertext` by the
+    /// lead party holding `private_key`, see
+    /// [`CryptoContext::multiparty_decrypt_fusion`]
+    pub fn multiparty_decrypt_lead(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        self.multiparty_decrypt_lead_with_rng(params, private_key, ciphertext, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::multiparty_decrypt_lead`] drawing randomness from `rng`
+    pub fn multiparty_decrypt_lead_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.partial_decrypt(params, private_key, ciphertext, true, rng)
+    }
+
+    /// The partial decryption `c1 s_i + e` of `ciphertext` by every other
+    /// party holding `private_key`, see
+    /// [`CryptoContext::multiparty_decrypt_fusion`]
+    pub fn multiparty_decrypt_main(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        self.multiparty_decrypt_main_with_rng(params, private_key, ciphertext, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::multiparty_decrypt_main`] drawing randomness from `rng`
+    pub fn multiparty_decrypt_main_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.partial_decrypt(params, private_key, ciphertext, false, rng)
+    }
+
+    /// The plaintext of a ciphertext from the partial decryptions of every
+    /// party, exactly one of them from
+    /// [`CryptoContext::multiparty_decrypt_lead`]. Their sum is the phase
+    /// `c0 + c1 s` under the joint secret plus the flooding noise.
+    pub fn multiparty_decrypt_fusion(
+        &self,
+        partial_decryptions: &[Ciphertext],
+    ) -> Result<PlaintextEncodings> {
+        let Some((lead, rest)) = partial_decryptions.split_first() else {
+            return Err(Error::CcParams(
+                "fusing needs at least one partial decryption".to_string(),
+            ));
+        };
+        let lease = self.lease(ArenaKind::Plaintext)?;
+        let mut decrypted = partial(self, lead)?.clone();
+        for other in rest {
+            if other.key_tag() != lead.key_tag()
+                || other.encoding_type() != lead.encoding_type()
+                || other.scaling_factor() != lead.scaling_factor()
+            {
+                return Err(Error::CcParams(
+                    "the partial decryptions are of different ciphertexts".to_string(),
+                ));
+            }
+            let other = partial(self, other)?;
+            if other.params() != decrypted.params() {
+                return Err(Error::DcrtElementParamsMismatch);
+            }
+            decrypted += &in_format_of(other.clone(), &decrypted);
+        }
+        self.decode_phase(lead, &decrypted, lease)
+    }
+
+    fn partial_decrypt(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+        lead: bool,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        if private_key.get_crypto_context() != self || ciphertext.get_crypto_context() != self {
+            return Err(Error::CcParams(
+                "the key or the ciphertext belongs to another crypto context".to_string(),
+            ));
+        }
+        ciphertext.metadata()?;
+        if private_key.key_tag() != ciphertext.key_tag() {
+            return Err(Error::CcParams(format!(
+                "the key share of key tag `{}` cannot decrypt under key tag `{}`",
+                private_key.key_tag(),
+                ciphertext.key_tag()
+            )));
+        }
+        let rlwe = &params.rlwe_crypto_parameters;
+        if rlwe.multiparty_mode == MultipartyMode::Invalid {
+            return Err(Error::CcParams(
+                "partial decryption needs a multiparty mode".to_string(),
+            ));
+        }
+        let [c0, c1] = ciphertext.elements() else {
+            return Err(Error::CiphertextFormat(format!(
+                "cannot partially decrypt a ciphertext of {} polynomials, relinearize it first",
+                ciphertext.elements().len()
+            )));
+        };
+        let secret = private_key.secret().shrink(tower_count(ciphertext));
+        let mut decrypted = in_format_of(c1.clone(), &secret);
+        decrypted *= &secret;
+        if lead {
+            decrypted += &in_format_of(c0.clone(), &secret);
+        }
+        let mut noise = decrypted.clone();
+        with_gaussian(rlwe.discrete_gaussian_std_dev_with_flooding, |gaussian| {
+            noise.fill_discrete_gaussian_with_rng(gaussian, rng)
+        })?;
+        decrypted += &in_format_of(noise, &decrypted);
+        let mut result = ciphertext.try_clone()?;
+        let op = if lead {
+            "multiparty_decrypt_lead"
+        } else {
+            "multiparty_decrypt_main"
+        };
+        result.replace_elements(
+            op,
+            vec![decrypted],
+            ciphertext.scaling_factor(),
+            &[ciphertext],
+        );
+        Ok(result)
+    }
+}
+
+/// The single polynomial of a partial decryption
+fn partial<'a>(crypto_context: &CryptoContext, partial: &'a Ciphertext) -> Result<&'a DcrtPoly> {
+    if partial.get_crypto_context() != crypto_context {
+        return Err(Error::CcParams(
+            "the partial decryption belongs to another crypto context".to_string(),
+        ));
+    }
+    match partial.elements() {
+        [decrypted] => Ok(decrypted),
+        elements => Err(Error::CiphertextFormat(format!(
+            "a partial decryption has one polynomial, not {}",
+            elements.len()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::MultipartyMode;
+    use crate::encoding::PlaintextEncodings;
+    use crate::pke::test_support::{RING_DIMENSION, bfv_params, cc_params};
+    use crate::pke::{BfvRns, CcParams, KeyPair, PrivateKey};
+    use rand::Rng;
+
+    /// Three parties extending one joint public key in turn
+    fn joint_key_parties(bfv: &BfvRns) -> Vec<KeyPair> {
+        let mut parties = vec![bfv.key_gen().expect("keys")];
+        for _ in 1..3 {
+            let previous = parties.last().expect("a party").public_key();
+            let next = bfv.multiparty_key_gen(previous).expect("joint key");
+            parties.push(next);
+        }
+        parties
+    }
+
+    fn dense_message() -> Vec<i64> {
+        let mut rng = rand::rng();
+        (0..RING_DIMENSION)
+            .map(|_| rng.random_range(-32768..=32768))
+            .collect()
+    }
+
+    #[test]
+    fn joint_key_encrypts_under_the_sum_of_secrets() {
+        let bfv = bfv_params()
+            .cc_params(CcParams {
+                threshold_parties: 3,
+                ..cc_params()
+            })
+            .build()
+            .expect("parameters");
+        let parties = joint_key_parties(&bfv);
+        let joint = parties.last().expect("a party").public_key();
+        let message = dense_message();
+        let plaintext = bfv.encode(&message).expect("encode");
+        let ciphertext = bfv.encrypt(joint, &plaintext).expect("encrypt");
+
+        let mut secret = parties[0].private_key().secret().clone();
+        for party in &parties[1..] {
+            secret += party.private_key().secret();
+        }
+        let sum = PrivateKey::new(
+            bfv.crypto_context().clone(),
+            secret,
+            parties[0].private_key().distribution(),
+        )
+        .with_key_tag(joint.key_tag());
+        let PlaintextEncodings::Coefficient(decrypted) =
+            bfv.decrypt(&sum, &ciphertext).expect("decrypt")
+        else {
+            panic!("a coefficient plaintext");
+        };
+        assert_eq!(decrypted.coefficients(), message);
+        // A single share is not enough
+        let share = bfv.decrypt(parties[0].private_key(), &ciphertext);
+        assert!(!matches!(share, Ok(PlaintextEncodings::Coefficient(p))
+            if p.coefficients() == message));
+    }
+
+    #[test]
+    fn three_parties_decrypt_jointly() {
+        for mode in [MultipartyMode::FixedNoise, MultipartyMode::NoiseFlooding] {
+            let bfv = bfv_params()
+                .cc_params(CcParams {
+                    multiparty_mode: mode,
+                    threshold_parties: 3,
+                    ..cc_params()
+                })
+                .build()
+                .expect("parameters");
+            let params = bfv.crypto_parameters();
+            let cc = bfv.crypto_context();
+            let parties = joint_key_parties(&bfv);
+            let joint = parties.last().expect("a party").public_key();
+            let message = dense_message();
+            let plaintext = bfv.encode(&message).expect("encode");
+            let ciphertext = bfv.encrypt(joint, &plaintext).expect("encrypt");
+
+            let lead = cc
+                .multiparty_decrypt_lead(params, parties[0].private_key(), &ciphertext)
+                .expect("lead");
+            let mut partials = vec![lead];
+            for party in &parties[1..] {
+                partials.push(
+                    cc.multiparty_decrypt_main(params, party.private_key(), &ciphertext)
+                        .expect("main"),
+                );
+            }
+            let PlaintextEncodings::Coefficient(decrypted) =
+                cc.multiparty_decrypt_fusion(&partials).expect("fusion")
+            else {
+                panic!("a coefficient plaintext");
+            };
+            assert_eq!(decrypted.coefficients(), message, "{mode}");
+            // Without every share the phase is noise
+            let partial = cc.multiparty_decrypt_fusion(&partials[..2]);
+            assert!(
+                !matches!(partial, Ok(PlaintextEncodings::Coefficient(p))
+                    if p.coefficients() == message),
+                "{mode}"
+            );
+        }
+    }
+}
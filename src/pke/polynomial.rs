@@ -0,0 +1,805 @@
+//! Polynomial evaluation on CKKS ciphertexts
+//!
+//! The products rescale right away, so every multiplication consumes one
+//! tower. Operands that took different paths are brought to a common level
+//! and scaling factor before they are combined, the way the flexible
+//! scaling techniques do, see [`align_scales`].
+
+use crate::ActingPrimitive;
+use crate::ciphertext::Ciphertext;
+use crate::constants::{PlaintextEncodingsType, ScalingTechnique};
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::core::math::eval_chebyshev_coefficients;
+use crate::error::{Error, Result};
+use crate::evaluator::{PlainSlotEvaluator, eval_chebyshev_series};
+use crate::pke::encryption_pool::in_format_of;
+use crate::pke::{
+    CryptoContext, CryptoParametersRns, align_scales, compress, level_reduce, scaled, tower_count,
+};
+use num::{BigInt, FromPrimitive};
+use std::cmp::Ordering;
+
+/// The relative difference below which two scaling factors are the same
+const SCALE_TOLERANCE: f64 = 1e-12;
+
+/// The largest degree a precision target tries, `2^11 - 1`
+const MAX_TUNED_DEGREE: usize = 2047;
+
+/// The points of the range an interpolant is checked at
+const TUNING_POINTS: usize = 1000;
+
+/// How closely the convenience functions like [`CryptoContext::eval_logistic`]
+/// approximate, as the maximal absolute error of the interpolant over the
+/// range or an explicit degree.
+///
+/// A target picks the smallest degree `2^j - 1` meeting it on the range,
+/// which consumes `j + 2` levels. The CKKS noise of about `2^-40` relative
+/// to the scaling factor comes on top.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ApproximationPrecision {
+    /// An error of at most `1e-3`
+    Low,
+    /// An error of at most `1e-6`
+    #[default]
+    Medium,
+    /// An error of at most `1e-9`
+    High,
+    /// The interpolant of this degree, whatever its error
+    Degree(usize),
+}
+
+impl ApproximationPrecision {
+    /// The degree of the interpolant of `f` on `[a, b]` for this precision
+    pub fn degree<F>(&self, f: F, a: f64, b: f64) -> Result<usize>
+    where
+        F: Fn(f64) -> f64,
+    {
+        let target = match *self {
+            Self::Low => 1e-3,
+            Self::Medium => 1e-6,
+            Self::High => 1e-9,
+            Self::Degree(degree) => return Ok(degree),
+        };
+        let points = (0..=TUNING_POINTS)
+            .map(|i| a + (b - a) * i as f64 / TUNING_POINTS as f64)
+            .collect::<Vec<_>>();
+        let expected = points.iter().map(|&x| f(x)).collect::<Vec<_>>();
+        (2..=MAX_TUNED_DEGREE.ilog2() + 1)
+            .map(|j| (1 << j) - 1)
+            .find(|&degree| {
+                let coeffs = eval_chebyshev_coefficients(&f, a, b, degree);
+                eval_chebyshev_series(&PlainSlotEvaluator, &points, &coeffs, a, b).is_ok_and(
+                    |approximated| {
+                        approximated
+                            .iter()
+                            .zip(&expected)
+                            .all(|(x, y)| (x - y).abs() <= target)
+                    },
+                )
+            })
+            .ok_or_else(|| {
+                Error::CcParams(format!(
+                    "no degree up to {MAX_TUNED_DEGREE} approximates the function within {target} on [{a}, {b}]"
+                ))
+            })
+    }
+}
+
+/// The composite approximation of `sign(x)` behind
+/// [`CryptoContext::eval_sign`], from Cheon, Kim, Kim and Lee, "Efficient
+/// Homomorphic Comparison Methods with Optimal Complexity" (ASIACRYPT 2020).
+///
+/// The odd polynomial `f_n(x) = sum_{i <= n} 4^-i binom(2i, i) x (1 - x^2)^i`
+/// of degree `2n + 1` pushes `[-1, 1]` towards `±1`, and `compositions` of it
+/// separate inputs at least `2^-compositions` or so apart from zero. Each
+/// composition consumes about `ceil(log2(2n + 2)) + 1` levels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SignApproximation {
+    /// The inputs lie in `[-bound, bound]`
+    pub bound: f64,
+    /// The `n` of `f_n`
+    pub order: usize,
+    /// How often `f_n` is applied
+    pub compositions: usize,
+}
+
+impl Default for SignApproximation {
+    fn default() -> Self {
+        Self {
+            bound: 1.0,
+            order: 3,
+            compositions: 4,
+        }
+    }
+}
+
+impl SignApproximation {
+    /// The Chebyshev coefficients of `f_n` on `[-1, 1]`
+    fn coefficients(&self) -> Vec<f64> {
+        let n = self.order;
+        let f = |x: f64| {
+            let mut weight = 1.0;
+            let mut sum = 0.0;
+            for i in 0..=n {
+                sum += weight * x * (1.0 - x * x).powi(i as i32);
+                weight *= (2 * i + 1) as f64 / (2 * i + 2) as f64;
+            }
+            sum
+        };
+        eval_chebyshev_coefficients(f, -1.0, 1.0, 2 * n + 1)
+    }
+}
+
+impl CryptoContext {
+    /// Evaluate `coeffs[0] / 2 + sum(coeffs[k] * T_k(y))` on a CKKS
+    /// ciphertext, where `y` maps `[a, b]` onto `[-1, 1]`, e.g. with the
+    /// coefficients of [`eval_chebyshev_coefficients`].
+    ///
+    /// The Paterson-Stockmeyer variant for the Chebyshev basis computes
+    /// `T_1..T_k` for `k` about `sqrt(degree)` and `T_k, T_2k, T_4k, ...`,
+    /// then divides the series by the giant steps recursively. A degree `d`
+    /// consumes about `ceil(log2(d)) + 2` levels and `O(sqrt(d))`
+    /// products of ciphertexts, relinearized with the stored keys of the
+    /// key tag.
+    ///
+    /// [`eval_chebyshev_coefficients`]: crate::core::math::eval_chebyshev_coefficients
+    pub fn eval_chebyshev_series(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        coeffs: &[f64],
+        a: f64,
+        b: f64,
+    ) -> Result<Ciphertext> {
+        if ciphertext.encoding_type() != PlaintextEncodingsType::CkksPacked {
+            return Err(Error::CcParams(format!(
+                "cannot evaluate a series on a {} ciphertext",
+                ciphertext.encoding_type()
+            )));
+        }
+        if coeffs.is_empty()
+            || a.partial_cmp(&b) != Some(Ordering::Less)
+            || coeffs.iter().any(|c| !c.is_finite())
+        {
+            return Err(Error::CcParams(format!(
+                "cannot evaluate {} coefficients on [{a}, {b}]",
+                coeffs.len()
+            )));
+        }
+        let y = if a == -1.0 && b == 1.0 {
+            ciphertext.clone()
+        } else {
+            let scaled = self.ckks_mult_const(ciphertext, 2.0 / (b - a))?;
+            self.ckks_add_const(&scaled, -(a + b) / (b - a))?
+        };
+        let mut series = coeffs.to_vec();
+        series[0] /= 2.0;
+        let degree = series.len() - 1;
+
+        let k = (((degree + 1) as f64).sqrt().ceil() as usize)
+            .next_power_of_two()
+            .max(2);
+        // T_1..T_k, then T_2k, T_4k, ... up to the degree
+        let mut baby = vec![y];
+        for n in 2..=k.min(degree.max(1)) {
+            let (m, l) = (n.div_ceil(2), n / 2);
+            let product = self.ckks_mult(params, &baby[m - 1], &baby[l - 1])?;
+            let doubled = self.ckks_add(&product, &product)?;
+            baby.push(if m == l {
+                self.ckks_add_const(&doubled, -1.0)?
+            } else {
+                self.ckks_sub(&doubled, &baby[0])?
+            });
+        }
+        let mut giants: Vec<(usize, Ciphertext)> = Vec::new();
+        let mut step = k;
+        while step <= degree {
+            let giant = match giants.last() {
+                None => baby[k - 1].clone(),
+                Some((_, previous)) => {
+                    let square = self.ckks_mult(params, previous, previous)?;
+                    let doubled = self.ckks_add(&square, &square)?;
+                    self.ckks_add_const(&doubled, -1.0)?
+                }
+            };
+            giants.push((step, giant));
+            step *= 2;
+        }
+        self.paterson_stockmeyer(params, &series, &baby, &giants, None)
+    }
+
+    /// Evaluate the Chebyshev interpolant of `f` of `degree` on `[a, b]`,
+    /// see [`CryptoContext::eval_chebyshev_series`]
+    pub fn eval_chebyshev_function<F>(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        f: F,
+        a: f64,
+        b: f64,
+        degree: usize,
+    ) -> Result<Ciphertext>
+    where
+        F: Fn(f64) -> f64,
+    {
+        let coeffs = eval_chebyshev_coefficients(f, a, b, degree);
+        self.eval_chebyshev_series(params, ciphertext, &coeffs, a, b)
+    }
+
+    /// The logistic function `1 / (1 + e^-x)` for slots in `[a, b]`
+    pub fn eval_logistic(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        a: f64,
+        b: f64,
+        precision: ApproximationPrecision,
+    ) -> Result<Ciphertext> {
+        self.eval_tuned(
+            params,
+            ciphertext,
+            |x| 1.0 / (1.0 + (-x).exp()),
+            a,
+            b,
+            precision,
+        )
+    }
+
+    /// `sin(x)` for slots in `[a, b]`
+    pub fn eval_sin(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        a: f64,
+        b: f64,
+        precision: ApproximationPrecision,
+    ) -> Result<Ciphertext> {
+        self.eval_tuned(params, ciphertext, f64::sin, a, b, precision)
+    }
+
+    /// `cos(x)` for slots in `[a, b]`
+    pub fn eval_cos(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        a: f64,
+        b: f64,
+        precision: ApproximationPrecision,
+    ) -> Result<Ciphertext> {
+        self.eval_tuned(params, ciphertext, f64::cos, a, b, precision)
+    }
+
+    /// `e^x` for slots in `[a, b]`
+    pub fn eval_exp(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        a: f64,
+        b: f64,
+        precision: ApproximationPrecision,
+    ) -> Result<Ciphertext> {
+        self.eval_tuned(params, ciphertext, f64::exp, a, b, precision)
+    }
+
+    /// `1 / x` for slots in `[a, b]` by the Chebyshev interpolant of
+    /// `degree`, for a range excluding zero
+    pub fn eval_divide(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        a: f64,
+        b: f64,
+        degree: usize,
+    ) -> Result<Ciphertext> {
+        if a <= 0.0 && b >= 0.0 {
+            return Err(Error::CcParams(format!(
+                "the range [{a}, {b}] of a division includes zero"
+            )));
+        }
+        self.eval_chebyshev_function(params, ciphertext, f64::recip, a, b, degree)
+    }
+
+    /// About `sign(x)` for slots in `[-bound, bound]`, by composing the
+    /// polynomials of `approximation`
+    pub fn eval_sign(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        approximation: SignApproximation,
+    ) -> Result<Ciphertext> {
+        if approximation.bound.partial_cmp(&0.0) != Some(Ordering::Greater)
+            || approximation.compositions == 0
+        {
+            return Err(Error::CcParams(format!(
+                "cannot approximate the sign with {approximation:?}"
+            )));
+        }
+        let coeffs = approximation.coefficients();
+        let bound = approximation.bound;
+        let mut sign = self.eval_chebyshev_series(params, ciphertext, &coeffs, -bound, bound)?;
+        for _ in 1..approximation.compositions {
+            sign = self.eval_chebyshev_series(params, &sign, &coeffs, -1.0, 1.0)?;
+        }
+        Ok(sign)
+    }
+
+    /// About `1` where the slots of `a` exceed those of `b`, `0` where they
+    /// are below and `1/2` where they are equal, for differences in
+    /// `[-bound, bound]`
+    pub fn eval_compare(
+        &self,
+        params: &CryptoParametersRns,
+        a: &Ciphertext,
+        b: &Ciphertext,
+        approximation: SignApproximation,
+    ) -> Result<Ciphertext> {
+        let difference = self.ckks_sub(a, b)?;
+        let sign = self.eval_sign(params, &difference, approximation)?;
+        self.ckks_add_const(&self.ckks_mult_const(&sign, 0.5)?, 0.5)
+    }
+
+    /// The slotwise maximum of `a` and `b`, `(a + b + |a - b|) / 2`, for
+    /// differences in `[-bound, bound]`
+    pub fn eval_max(
+        &self,
+        params: &CryptoParametersRns,
+        a: &Ciphertext,
+        b: &Ciphertext,
+        approximation: SignApproximation,
+    ) -> Result<Ciphertext> {
+        let (sum, distance) = self.sum_and_distance(params, a, b, approximation)?;
+        self.ckks_mult_const(&self.ckks_add(&sum, &distance)?, 0.5)
+    }
+
+    /// The slotwise minimum of `a` and `b`, `(a + b - |a - b|) / 2`, for
+    /// differences in `[-bound, bound]`
+    pub fn eval_min(
+        &self,
+        params: &CryptoParametersRns,
+        a: &Ciphertext,
+        b: &Ciphertext,
+        approximation: SignApproximation,
+    ) -> Result<Ciphertext> {
+        let (sum, distance) = self.sum_and_distance(params, a, b, approximation)?;
+        self.ckks_mult_const(&self.ckks_sub(&sum, &distance)?, 0.5)
+    }
+
+    /// `a + b` and about `|a - b|`
+    fn sum_and_distance(
+        &self,
+        params: &CryptoParametersRns,
+        a: &Ciphertext,
+        b: &Ciphertext,
+        approximation: SignApproximation,
+    ) -> Result<(Ciphertext, Ciphertext)> {
+        let difference = self.ckks_sub(a, b)?;
+        let sign = self.eval_sign(params, &difference, approximation)?;
+        let distance = self.ckks_mult(params, &difference, &sign)?;
+        Ok((self.ckks_add(a, b)?, distance))
+    }
+
+    fn eval_tuned<F>(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        f: F,
+        a: f64,
+        b: f64,
+        precision: ApproximationPrecision,
+    ) -> Result<Ciphertext>
+    where
+        F: Fn(f64) -> f64,
+    {
+        let degree = precision.degree(&f, a, b)?;
+        self.eval_chebyshev_function(params, ciphertext, f, a, b, degree)
+    }
+
+    /// `sum(series[n] * T_n)` for a series of degree below twice the
+    /// largest giant step it reaches, a low degree one at the towers and
+    /// scaling factor of `target` where the baby steps allow
+    fn paterson_stockmeyer(
+        &self,
+        params: &CryptoParametersRns,
+        series: &[f64],
+        baby: &[Ciphertext],
+        giants: &[(usize, Ciphertext)],
+        target: Option<(usize, f64)>,
+    ) -> Result<Ciphertext> {
+        let degree = series.len() - 1;
+        let Some((step, giant)) = giants.iter().rev().find(|(step, _)| *step <= degree) else {
+            return self.linear_combination(series, baby, target);
+        };
+        // T_n = 2 T_K T_(n-K) - T_(2K-n) for K < n < 2K, and T_K = T_K T_0
+        let mut remainder = series[..*step].to_vec();
+        let mut quotient = vec![0.0; degree - step + 1];
+        quotient[0] = series[*step];
+        for n in step + 1..=degree {
+            quotient[n - step] = 2.0 * series[n];
+            remainder[2 * step - n] -= series[n];
+        }
+        let high = self.paterson_stockmeyer(params, &quotient, baby, giants, None)?;
+        let product = self.ckks_mult(params, &high, giant)?;
+        let target = (tower_count(&product), product.scaling_factor());
+        let low = self.paterson_stockmeyer(params, &remainder, baby, giants, Some(target))?;
+        self.ckks_add(&product, &low)
+    }
+
+    /// `series[0] + sum(series[n] * T_n)` with `baby[n - 1]` holding `T_n`,
+    /// every term scaled to the same towers and scaling factor
+    fn linear_combination(
+        &self,
+        series: &[f64],
+        baby: &[Ciphertext],
+        target: Option<(usize, f64)>,
+    ) -> Result<Ciphertext> {
+        let terms = &baby[..(series.len() - 1).max(1)];
+        let deepest = terms
+            .iter()
+            .min_by_key(|t| tower_count(t))
+            .expect("there is at least one term");
+        let (towers, scale) = match target {
+            Some((towers, scale)) if terms.iter().all(|t| tower_count(t) > towers) => {
+                (towers, scale)
+            }
+            _ => (
+                tower_count(deepest).saturating_sub(1),
+                deepest.scaling_factor(),
+            ),
+        };
+        let first = series.get(1).copied().unwrap_or(0.0);
+        let mut sum = self.ckks_mult_const_to(&terms[0], first, towers, scale)?;
+        for (tn, &c) in terms.iter().zip(&series[1..]).skip(1) {
+            if c != 0.0 {
+                let term = self.ckks_mult_const_to(tn, c, towers, scale)?;
+                sum = self.eval_add(&sum, &term)?;
+            }
+        }
+        self.ckks_add_const(&sum, series[0])
+    }
+
+    /// Divide a CKKS ciphertext by its last tower modulus and drop that tower
+    pub(crate) fn ckks_rescale(&self, ciphertext: &Ciphertext) -> Result<Ciphertext> {
+        let towers = tower_count(ciphertext);
+        if towers < 2 {
+            return Err(Error::CcParams(
+                "no level is left to rescale the ciphertext".to_string(),
+            ));
+        }
+        let q = last_modulus(ciphertext)?;
+        let elements = compress(ciphertext.elements(), towers - 1)?;
+        let mut result = ciphertext.try_clone()?;
+        result.set_noise_scale_degree(ciphertext.noise_scale_degree().saturating_sub(1).max(1));
+        result.replace_elements(
+            "rescale",
+            elements,
+            ciphertext.scaling_factor() / q,
+            &[ciphertext],
+        );
+        Ok(result)
+    }
+
+    /// The rescaled and relinearized product of two CKKS ciphertexts at the
+    /// level of the lower one
+    pub(crate) fn ckks_mult(
+        &self,
+        params: &CryptoParametersRns,
+        a: &Ciphertext,
+        b: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        let (mut a, mut b) = (a.clone(), b.clone());
+        let towers = tower_count(&a).min(tower_count(&b));
+        level_reduce(&mut a, towers);
+        level_reduce(&mut b, towers);
+        let product = self.relinearize(&self.eval_mult_no_relin(params, &a, &b)?)?;
+        self.ckks_rescale(&product)
+    }
+
+    pub(crate) fn ckks_add(&self, a: &Ciphertext, b: &Ciphertext) -> Result<Ciphertext> {
+        let (a, b) = self.ckks_align(a, b)?;
+        self.eval_add(&a, &b)
+    }
+
+    pub(crate) fn ckks_sub(&self, a: &Ciphertext, b: &Ciphertext) -> Result<Ciphertext> {
+        let (a, b) = self.ckks_align(a, b)?;
+        self.eval_sub(&a, &b)
+    }
+
+    /// Bring two CKKS ciphertexts to a common level and scaling factor. The
+    /// one with more towers is scaled onto the other on the way down, which
+    /// costs no level, only equal levels fall back to [`align_scales`].
+    pub(crate) fn ckks_align(
+        &self,
+        a: &Ciphertext,
+        b: &Ciphertext,
+    ) -> Result<(Ciphertext, Ciphertext)> {
+        let (mut a, mut b) = (a.clone(), b.clone());
+        let (ta, tb) = (tower_count(&a), tower_count(&b));
+        let same_scale = (a.scaling_factor() - b.scaling_factor()).abs()
+            <= SCALE_TOLERANCE * a.scaling_factor().max(b.scaling_factor());
+        if ta == tb || same_scale {
+            align_scales(ScalingTechnique::FlexibleAuto, &mut a, &mut b)?;
+        } else if ta > tb {
+            a = self.ckks_mult_const_to(&a, 1.0, tb, b.scaling_factor())?;
+        } else {
+            b = self.ckks_mult_const_to(&b, 1.0, ta, a.scaling_factor())?;
+        }
+        Ok((a, b))
+    }
+
+    /// `c` added to every slot
+    pub(crate) fn ckks_add_const(&self, ciphertext: &Ciphertext, c: f64) -> Result<Ciphertext> {
+        let constant = rounded(c * ciphertext.scaling_factor())?;
+        let mut elements = ciphertext.elements().to_vec();
+        let c0 = &elements[0];
+        let len = c0.towers().first().map_or(0, |t| t.len());
+        let mut coefficients = vec![BigInt::ZERO; len];
+        if let Some(first) = coefficients.first_mut() {
+            *first = constant;
+        }
+        let constant = in_format_of(
+            DcrtPoly::from_big_signed(c0.params().clone(), &coefficients),
+            c0,
+        );
+        elements[0] += &constant;
+        let mut result = ciphertext.try_clone()?;
+        result.replace_elements(
+            "add_const",
+            elements,
+            ciphertext.scaling_factor(),
+            &[ciphertext],
+        );
+        Ok(result)
+    }
+
+    /// Every slot times `c`, scaled by the last tower modulus and rescaled,
+    /// which keeps the scaling factor and consumes a level
+    pub(crate) fn ckks_mult_const(&self, ciphertext: &Ciphertext, c: f64) -> Result<Ciphertext> {
+        let towers = tower_count(ciphertext).saturating_sub(1);
+        self.ckks_mult_const_to(ciphertext, c, towers, ciphertext.scaling_factor())
+    }
+
+    /// Every slot times `c` at `towers` towers and the scaling factor
+    /// `scale`, the constant absorbing the change of scale before the
+    /// rescaling by the last of the first `towers + 1` tower moduli
+    pub(crate) fn ckks_mult_const_to(
+        &self,
+        ciphertext: &Ciphertext,
+        c: f64,
+        towers: usize,
+        scale: f64,
+    ) -> Result<Ciphertext> {
+        if towers == 0 || tower_count(ciphertext) <= towers {
+            return Err(Error::CcParams(
+                "no level is left to rescale the ciphertext".to_string(),
+            ));
+        }
+        let mut reduced = ciphertext.try_clone()?;
+        level_reduce(&mut reduced, towers + 1);
+        let q = last_modulus(&reduced)?;
+        let constant = rounded(c * scale * q / ciphertext.scaling_factor())?;
+        let elements = scaled(reduced.elements(), &constant);
+        reduced.replace_elements("mult_const", elements, scale * q, &[ciphertext]);
+        self.ckks_rescale(&reduced)
+    }
+}
+
+pub(crate) fn last_modulus(ciphertext: &Ciphertext) -> Result<f64> {
+    let last = ciphertext
+        .elements()
+        .first()
+        .and_then(|e| e.params().params().back())
+        .ok_or(Error::DcrtElementParamsMismatch)?;
+    Ok(last.ciphertext_modulus.get().to_primitive() as f64)
+}
+
+fn rounded(value: f64) -> Result<BigInt> {
+    BigInt::from_f64(value.round())
+        .ok_or_else(|| Error::CcParams(format!("cannot encode the constant {value}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::PlaintextEncodings;
+    use crate::pke::test_support::ckks_context;
+    use crate::pke::{CkksRns, PrivateKey, PublicKey};
+
+    /// Slots in `[-2, 2]`
+    const VALUES: [f64; 8] = [-2.0, -1.5, -0.75, -0.1, 0.0, 0.4, 1.25, 2.0];
+
+    /// A CKKS scheme whose relinearization key is stored under its own key
+    /// tag, so the tests do not replace each other's keys
+    struct Fixture {
+        ckks: CkksRns,
+        public_key: PublicKey,
+        private_key: PrivateKey,
+    }
+
+    impl Fixture {
+        fn new(depth: usize, tag: &str) -> Self {
+            let ckks = ckks_context(depth);
+            let (public_key, private_key) = ckks.key_gen().expect("keys").into_parts();
+            let (public_key, private_key) =
+                (public_key.with_key_tag(tag), private_key.with_key_tag(tag));
+            ckks.eval_mult_key_gen(&private_key)
+                .expect("relinearization key");
+            Self {
+                ckks,
+                public_key,
+                private_key,
+            }
+        }
+
+        fn encrypt(&self, values: &[f64]) -> Ciphertext {
+            let plaintext = self.ckks.encode(values).expect("encode");
+            self.ckks
+                .encrypt(&self.public_key, &plaintext)
+                .expect("encrypt")
+        }
+
+        fn decrypt(&self, ciphertext: &Ciphertext) -> Vec<f64> {
+            let PlaintextEncodings::Ckks(plaintext) = self
+                .ckks
+                .decrypt(&self.private_key, ciphertext)
+                .expect("decrypt")
+            else {
+                panic!("a CKKS plaintext");
+            };
+            plaintext.values().to_vec()
+        }
+
+        /// Decrypt `ciphertext` and compare it to `f` of the slots of `values`
+        fn assert_close(
+            &self,
+            ciphertext: &Ciphertext,
+            values: &[f64],
+            f: impl Fn(f64) -> f64,
+            bound: f64,
+        ) {
+            let expected = values.iter().map(|&v| f(v)).collect::<Vec<_>>();
+            self.assert_slots(ciphertext, &expected, bound);
+        }
+
+        fn assert_slots(&self, ciphertext: &Ciphertext, expected: &[f64], bound: f64) {
+            let actual = self.decrypt(ciphertext);
+            for (i, (x, y)) in actual.iter().zip(expected).enumerate() {
+                assert!((x - y).abs() < bound, "slot {i}: {x} != {y} within {bound}");
+            }
+        }
+    }
+
+    #[test]
+    fn series_match_their_plain_evaluation() {
+        let fixture = Fixture::new(8, "polynomial::tests::series");
+        let ciphertext = fixture.encrypt(&VALUES);
+        // Degree 11 divides by the giant steps T_4 and T_8
+        let coeffs = [
+            0.5, -0.25, 0.75, 0.125, -0.5, 0.0, 0.3, -0.2, 0.1, 0.05, -0.15, 0.25,
+        ];
+        let (a, b) = (-2.0, 3.0);
+        let series = fixture
+            .ckks
+            .eval_chebyshev_series(&ciphertext, &coeffs, a, b)
+            .expect("series");
+        let plain = |x: f64| {
+            let y = ((2.0 * x - a - b) / (b - a)).acos();
+            coeffs[0] / 2.0
+                + coeffs[1..]
+                    .iter()
+                    .enumerate()
+                    .map(|(k, c)| c * ((k + 1) as f64 * y).cos())
+                    .sum::<f64>()
+        };
+        fixture.assert_close(&series, &VALUES, plain, 1e-4);
+
+        let cubic = fixture
+            .ckks
+            .eval_chebyshev_function(&ciphertext, |x| x * x * x - x, -2.0, 2.0, 3)
+            .expect("function");
+        fixture.assert_close(&cubic, &VALUES, |x| x * x * x - x, 1e-4);
+    }
+
+    #[test]
+    fn functions_match_their_f64_references() {
+        let fixture = Fixture::new(9, "polynomial::tests::functions");
+        let ciphertext = fixture.encrypt(&VALUES);
+        let ckks = &fixture.ckks;
+        let low = ApproximationPrecision::Low;
+        // The interpolation error of at most 1e-3 and the CKKS noise
+        let bound = 2e-3;
+
+        let logistic = ckks
+            .eval_logistic(&ciphertext, -4.0, 4.0, low)
+            .expect("logistic");
+        fixture.assert_close(&logistic, &VALUES, |x| 1.0 / (1.0 + (-x).exp()), bound);
+        let sin = ckks.eval_sin(&ciphertext, -2.0, 2.0, low).expect("sin");
+        fixture.assert_close(&sin, &VALUES, f64::sin, bound);
+        let cos = ckks.eval_cos(&ciphertext, -2.0, 2.0, low).expect("cos");
+        fixture.assert_close(&cos, &VALUES, f64::cos, bound);
+        let exp = ckks.eval_exp(&ciphertext, -2.0, 2.0, low).expect("exp");
+        fixture.assert_close(&exp, &VALUES, f64::exp, bound);
+
+        let positive = VALUES.map(|x| 2.5 + 0.75 * x);
+        let divided = ckks
+            .eval_divide(&fixture.encrypt(&positive), 1.0, 4.0, 15)
+            .expect("divide");
+        fixture.assert_close(&divided, &positive, f64::recip, 1e-4);
+    }
+
+    #[test]
+    fn comparisons_match_their_f64_references() {
+        let fixture = Fixture::new(16, "polynomial::tests::comparisons");
+        // Differences of zero or at least one in absolute value
+        let a = [2.0, -1.0, 0.5, 0.0, 1.0, -0.5, 1.5, -2.0];
+        let b = [1.0, 1.0, 0.5, -1.5, 0.0, -0.5, -0.5, -1.0];
+        let (ca, cb) = (fixture.encrypt(&a), fixture.encrypt(&b));
+        let approximation = SignApproximation {
+            bound: 2.0,
+            order: 3,
+            compositions: 3,
+        };
+        let ckks = &fixture.ckks;
+        let slots = |f: fn(f64, f64) -> f64| std::array::from_fn::<_, 8, _>(|i| f(a[i], b[i]));
+
+        let difference = fixture.encrypt(&slots(|x, y| (x - y) / 2.0));
+        let sign = ckks
+            .eval_sign(&difference, SignApproximation::default())
+            .expect("sign");
+        let signs = slots(|x, y| if x == y { 0.0 } else { (x - y).signum() });
+        fixture.assert_slots(&sign, &signs, 0.05);
+
+        let compare = ckks.eval_compare(&ca, &cb, approximation).expect("compare");
+        fixture.assert_slots(&compare, &signs.map(|s| (s + 1.0) / 2.0), 0.01);
+        let max = ckks.eval_max(&ca, &cb, approximation).expect("max");
+        fixture.assert_slots(&max, &slots(f64::max), 0.01);
+        let min = ckks.eval_min(&ca, &cb, approximation).expect("min");
+        fixture.assert_slots(&min, &slots(f64::min), 0.01);
+    }
+
+    #[test]
+    fn invalid_ranges_and_coefficients_are_rejected() {
+        let fixture = Fixture::new(1, "polynomial::tests::invalid");
+        let ciphertext = fixture.encrypt(&VALUES);
+        let ckks = &fixture.ckks;
+        let rejected = |result: Result<Ciphertext>| matches!(result, Err(Error::CcParams(_)));
+
+        for (a, b) in [(-1.0, 1.0), (0.0, 2.0), (-2.0, 0.0)] {
+            assert!(
+                rejected(ckks.eval_divide(&ciphertext, a, b, 7)),
+                "[{a}, {b}]"
+            );
+        }
+        let never = SignApproximation {
+            compositions: 0,
+            ..Default::default()
+        };
+        assert!(rejected(ckks.eval_sign(&ciphertext, never)));
+        assert!(rejected(ckks.eval_compare(&ciphertext, &ciphertext, never)));
+        let unbounded = SignApproximation {
+            bound: f64::NAN,
+            ..Default::default()
+        };
+        assert!(rejected(ckks.eval_sign(&ciphertext, unbounded)));
+
+        for coeffs in [&[1.0, f64::NAN][..], &[f64::INFINITY], &[]] {
+            assert!(rejected(ckks.eval_chebyshev_series(
+                &ciphertext,
+                coeffs,
+                -1.0,
+                1.0
+            )));
+        }
+        assert!(rejected(ckks.eval_chebyshev_series(
+            &ciphertext,
+            &[1.0, 2.0],
+            1.0,
+            1.0
+        )));
+        assert!(rejected(ckks.eval_chebyshev_series(
+            &ciphertext,
+            &[1.0, 2.0],
+            f64::NAN,
+            1.0
+        )));
+    }
+}
@@ -0,0 +1,274 @@
+//! Proxy re-encryption
+//!
+//! The owner of a secret key generates a re-encryption key towards the
+//! public key of a delegatee with [`CryptoContext::re_key_gen`]. A proxy
+//! holding it turns ciphertexts under the owner's key into ciphertexts
+//! under the delegatee's key with [`CryptoContext::re_encrypt`], learning
+//! nothing about the messages. The key is a BV key switching key whose
+//! digits are encrypted under the delegatee's public key, so the delegatee
+//! never shares a secret.
+//!
+//! Re-encrypted ciphertexts can be re-encrypted again. Every hop adds the
+//! key switching noise, see [`BfvParamsGen::hop_noise_bits`], so the
+//! number of hops a ciphertext goes through is limited by
+//! [`CcParams::max_hops`]. The HRA secure modes of
+//! [`ProxyPreEncryptionMode`] also hide the key switching noise from the
+//! delegatee, see [`CryptoContext::re_encrypt`].
+//!
+//! [`BfvParamsGen::hop_noise_bits`]: crate::pke::BfvParamsGen::hop_noise_bits
+//! [`CcParams::max_hops`]: crate::pke::CcParams::max_hops
+
+use crate::ciphertext::Ciphertext;
+use crate::constants::{
+    KeySwitchTechnique, PlaintextEncodingsType, ProxyPreEncryptionMode, SecretKeyDistribution,
+};
+use crate::crypto_object::CryptoObject;
+use crate::error::{Error, Result};
+use crate::pke::key_switch;
+use crate::pke::scaling::tower_count;
+use crate::pke::{CryptoContext, CryptoParametersRns, EvalKey, PrivateKey, PublicKey};
+use rand::CryptoRng;
+
+impl CryptoContext {
+    /// A key re-encrypting ciphertexts under `old_key` to the secret of
+    /// `new_public_key`, tagged with the tag of the new key. Needs BV key
+    /// switching.
+    pub fn re_key_gen(
+        &self,
+        params: &CryptoParametersRns,
+        old_key: &PrivateKey,
+        new_public_key: &PublicKey,
+    ) -> Result<EvalKey> {
+        self.re_key_gen_with_rng(params, old_key, new_public_key, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::re_key_gen`] drawing randomness from `rng`
+    pub fn re_key_gen_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        old_key: &PrivateKey,
+        new_public_key: &PublicKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<EvalKey> {
+        if old_key.get_crypto_context() != self || new_public_key.get_crypto_context() != self {
+            return Err(Error::CcParams(
+                "the key belongs to another crypto context".to_string(),
+            ));
+        }
+        if params.key_switch_technique != KeySwitchTechnique::Bv {
+            return Err(Error::CcParams(format!(
+                "re-encryption keys need BV key switching, not {}",
+                params.key_switch_technique
+            )));
+        }
+        let rlwe = &params.rlwe_crypto_parameters;
+        let elements = key_switch::bv_public_key_gen(
+            old_key.secret(),
+            new_public_key.elements(),
+            rlwe.digit_size,
+            rlwe.discrete_gaussian_std_dev,
+            rlwe.secret_key_distribution,
+            rng,
+        )?;
+        Ok(EvalKey::new(
+            self.clone(),
+            KeySwitchTechnique::Bv,
+            rlwe.digit_size,
+            elements,
+        )?
+        .with_key_tag(new_public_key.key_tag()))
+    }
+
+    /// `ciphertext` under the new secret of `re_key` from
+    /// [`CryptoContext::re_key_gen`], one hop further. Fails once the
+    /// ciphertext went through the [`CcParams::max_hops`] of `params`.
+    ///
+    /// The HRA secure modes of [`CcParams::proxy_pre_encryption_mode`] hide
+    /// the key switching noise, which would otherwise leak the old secret to
+    /// the holder of the new one, behind an encryption of zero under
+    /// `public_key`, the delegatee's key `re_key` was generated for:
+    /// [`ProxyPreEncryptionMode::FixedNoiseHra`] with errors of
+    /// [`CcParams::flooding_std_dev`], [`ProxyPreEncryptionMode::NoiseFloodingHra`]
+    /// with errors that many times the noise bound of a fresh ciphertext
+    /// after a hop, dropping the last tower to scale them back down. The
+    /// latter needs BFV parameters from [`BfvParamsGen`], whose extra towers
+    /// pay for the dropped ones, and fresh or re-encrypted ciphertexts.
+    ///
+    /// [`CcParams::max_hops`]: crate::pke::CcParams::max_hops
+    /// [`CcParams::proxy_pre_encryption_mode`]: crate::pke::CcParams::proxy_pre_encryption_mode
+    /// [`CcParams::flooding_std_dev`]: crate::pke::CcParams::flooding_std_dev
+    /// [`BfvParamsGen`]: crate::pke::BfvParamsGen
+    pub fn re_encrypt(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        re_key: &EvalKey,
+        public_key: Option<&PublicKey>,
+    ) -> Result<Ciphertext> {
+        self.re_encrypt_with_rng(params, ciphertext, re_key, public_key, &mut rand::rng())
+    }
+
+    /// [`CryptoContext::re_encrypt`] drawing randomness from `rng`
+    pub fn re_encrypt_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        re_key: &EvalKey,
+        public_key: Option<&PublicKey>,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        let rlwe = &params.rlwe_crypto_parameters;
+        if ciphertext.hops_level() >= rlwe.max_hops {
+            return Err(Error::CcParams(format!(
+                "the ciphertext went through {} of at most {} re-encryptions",
+                ciphertext.hops_level(),
+                rlwe.max_hops
+            )));
+        }
+        let mode = rlwe.proxy_pre_encryption_mode;
+        let switched = self.key_switch(ciphertext, re_key)?;
+        let mut result = match mode {
+            ProxyPreEncryptionMode::NotSet | ProxyPreEncryptionMode::IndCpa => switched,
+            ProxyPreEncryptionMode::FixedNoiseHra | ProxyPreEncryptionMode::NoiseFloodingHra => {
+                let public_key = public_key
+                    .filter(|key| key.key_tag() == re_key.key_tag())
+                    .ok_or_else(|| {
+                        Error::CcParams(format!(
+                            "{mode} re-encryption needs the public key the re-encryption key \
+                             was generated for"
+                        ))
+                    })?;
+                if mode == ProxyPreEncryptionMode::FixedNoiseHra {
+                    let std_dev = rlwe.discrete_gaussian_std_dev_with_flooding;
+                    self.add_zero_encryption(params, public_key, &switched, std_dev, rng)?
+                } else {
+                    let towers = tower_count(&switched);
+                    if ciphertext.encoding_type() == PlaintextEncodingsType::CkksPacked
+                        || towers < 2
+                    {
+                        return Err(Error::CcParams(format!(
+                            "{mode} re-encryption needs an integer ciphertext with a tower to \
+                             spare, not a {} ciphertext of {towers} towers",
+                            ciphertext.encoding_type()
+                        )));
+                    }
+                    let std_dev =
+                        rlwe.discrete_gaussian_std_dev_with_flooding * hop_noise(params, towers);
+                    let bound = (std_dev * rlwe.assurance_measure_alpha.sqrt()).log2();
+                    let dropped = params.dcrt_element_params[towers - 1]
+                        .ciphertext_modulus
+                        .bits() as f64;
+                    if bound >= dropped - 1.0 {
+                        return Err(Error::CcParams(format!(
+                            "flooding {bound:.1} bits of noise does not vanish when dropping a \
+                             {dropped} bit tower"
+                        )));
+                    }
+                    let flooded =
+                        self.add_zero_encryption(params, public_key, &switched, std_dev, rng)?;
+                    self.compress(&flooded, towers - 1)?
+                }
+            }
+        };
+        result.set_hops_level(ciphertext.hops_level() + 1);
+        Ok(result)
+    }
+}
+
+/// The bound on the noise of a fresh ciphertext after key switching over
+/// its first `towers` towers, the BV digits of `c1` times the noise of their
+/// encryptions under the delegatee's public key
+fn hop_noise(params: &CryptoParametersRns, towers: usize) -> f64 {
+    let rlwe = &params.rlwe_crypto_parameters;
+    let moduli = params.dcrt_element_params.params().iter().take(towers);
+    let delta = 2.0 * (params.dcrt_element_params[0].ring_dimension as f64).sqrt();
+    let b_err = rlwe.discrete_gaussian_std_dev * rlwe.assurance_measure_alpha.sqrt();
+    let b_key = match rlwe.secret_key_distribution {
+        SecretKeyDistribution::Gaussian => b_err,
+        _ => 1.0,
+    };
+    let fresh = b_err * (1.0 + 2.0 * delta * b_key);
+    let digits = moduli
+        .map(|tower| {
+            let bits = tower.ciphertext_modulus.bits() as usize;
+            match rlwe.digit_size {
+                0 => 2f64.powi(bits as i32 - 1),
+                w => bits.div_ceil(w) as f64 * 2f64.powi(w as i32),
+            }
+        })
+        .sum::<f64>();
+    fresh + digits * delta * fresh
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::ProxyPreEncryptionMode;
+    use crate::encoding::PlaintextEncodings;
+    use crate::pke::CcParams;
+    use crate::pke::test_support::{bfv_params, cc_params};
+    use rand::Rng;
+
+    const RING_DIMENSION: usize = 4096;
+
+    /// Re-encrypt a dense message from party 0 to 1 to 2, then past `max_hops`
+    fn re_encrypt_two_hops(mode: ProxyPreEncryptionMode) {
+        let bfv = bfv_params()
+            .cc_params(CcParams {
+                proxy_pre_encryption_mode: mode,
+                max_hops: 2,
+                // Small digits keep the re-encryption noise below a tower
+                digit_size: 8,
+                ..cc_params()
+            })
+            .ring_dimension(RING_DIMENSION)
+            .build()
+            .expect("parameters");
+        let params = bfv.crypto_parameters();
+        let cc = bfv.crypto_context();
+        let parties = (0..3)
+            .map(|_| bfv.key_gen().expect("keys"))
+            .collect::<Vec<_>>();
+        let mut rng = rand::rng();
+        let message = (0..RING_DIMENSION)
+            .map(|_| rng.random_range(-32768..=32768))
+            .collect::<Vec<i64>>();
+        let plaintext = bfv.encode(&message).expect("encode");
+        let mut ciphertext = bfv
+            .encrypt(parties[0].public_key(), &plaintext)
+            .expect("encrypt");
+        for hop in parties.windows(2) {
+            let (from, to) = (&hop[0], &hop[1]);
+            let re_key = cc
+                .re_key_gen(params, from.private_key(), to.public_key())
+                .expect("re-encryption key");
+            ciphertext = cc
+                .re_encrypt(params, &ciphertext, &re_key, Some(to.public_key()))
+                .expect("re-encrypt");
+            let PlaintextEncodings::Coefficient(decrypted) =
+                bfv.decrypt(to.private_key(), &ciphertext).expect("decrypt")
+            else {
+                panic!("a coefficient plaintext");
+            };
+            assert_eq!(decrypted.coefficients(), message, "{mode}");
+        }
+        let re_key = cc
+            .re_key_gen(params, parties[2].private_key(), parties[0].public_key())
+            .expect("re-encryption key");
+        assert!(
+            cc.re_encrypt(params, &ciphertext, &re_key, Some(parties[0].public_key()))
+                .is_err(),
+            "{mode}"
+        );
+    }
+
+    #[test]
+    fn ind_cpa_stops_after_max_hops() {
+        re_encrypt_two_hops(ProxyPreEncryptionMode::IndCpa);
+    }
+
+    #[test]
+    fn hra_modes_stop_after_max_hops() {
+        re_encrypt_two_hops(ProxyPreEncryptionMode::FixedNoiseHra);
+        re_encrypt_two_hops(ProxyPreEncryptionMode::NoiseFloodingHra);
+    }
+}
@@ -0,0 +1,190 @@
+//! Re-encryption to a different context inside a trusted boundary
+//!
+//! # Trust boundary
+//!
+//! [`TrustedReencryptor`] holds the **source secret key** and decrypts every
+//! ciphertext it moves. It is meant for migrating stored data between
+//! parameter generations on a machine that is already allowed to see the
+//! plaintexts, e.g. inside an enclave or an offline key ceremony. It gives
+//! no protection against whoever runs it. The API is only compiled with the
+//! `trusted-reencrypt` feature so that using it is a deliberate choice.
+//!
+//! The target context only needs a public key, so the source secret never
+//! has to coexist with the target secret.
+
+use crate::constants::{PolynomialRingFormat, SecretKeyDistribution};
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::error::{Error, Result};
+use crate::pke::ZeroEncryption;
+use crate::pke::encryption_pool::{gaussian, in_format_of};
+use num::rational::BigRational;
+use num::{BigInt, BigUint, Integer, Signed};
+use rand::CryptoRng;
+use std::fmt::{self, Debug, Formatter};
+
+/// Decrypts under a source context and encrypts under a target public key,
+/// see the [module](self) docs for the trust assumptions.
+pub struct TrustedReencryptor {
+    source_secret: DcrtPoly,
+    target_public_key: [DcrtPoly; 2],
+    std_dev: f64,
+    secret_key_distribution: SecretKeyDistribution,
+}
+
+impl Debug for TrustedReencryptor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TrustedReencryptor")
+            .field("source_params", self.source_secret.params())
+            .field("target_params", self.target_public_key[0].params())
+            .field("std_dev", &self.std_dev)
+            .field("secret_key_distribution", &self.secret_key_distribution)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TrustedReencryptor {
+    /// Move ciphertexts decryptable with `source_secret` to the context of
+    /// `target_public_key`, encrypting with errors of `std_dev` and an
+    /// ephemeral secret following `secret_key_distribution`
+    pub fn new(
+        source_secret: DcrtPoly,
+        target_public_key: [DcrtPoly; 2],
+        std_dev: f64,
+        secret_key_distribution: SecretKeyDistribution,
+    ) -> Result<Self> {
+        gaussian(std_dev)?;
+        let [pk0, pk1] = &target_public_key;
+        if pk0.params() != pk1.params() || pk0.format() != pk1.format() {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        Ok(Self {
+            source_secret,
+            target_public_key,
+            std_dev,
+            secret_key_distribution,
+        })
+    }
+
+    /// Re-encrypt a ciphertext whose plaintext is scaled by the ciphertext
+    /// modulus, as in BFV.
+    ///
+    /// The decrypted `Δ m + e` is multiplied by `Q' / Q` and rounded, so the
+    /// message keeps its meaning under the target modulus `Q'`.
+    pub fn reencrypt(&self, ciphertext: &[DcrtPoly; 2]) -> Result<[DcrtPoly; 2]> {
+        self.reencrypt_with_rng(ciphertext, &mut rand::rng())
+    }
+
+    /// [`TrustedReencryptor::reencrypt`] drawing randomness from `rng`
+    pub fn reencrypt_with_rng(
+        &self,
+        ciphertext: &[DcrtPoly; 2],
+        rng: &mut impl CryptoRng,
+    ) -> Result<[DcrtPoly; 2]> {
+        let ratio = BigRational::new(
+            BigInt::from(self.target_modulus().clone()),
+            BigInt::from(
+                self.source_secret
+                    .params()
+                    .ciphertext_composite_modulus()
+                    .clone(),
+            ),
+        );
+        let plaintext = self.decrypt(ciphertext)?;
+        self.encrypt(&rescale(&plaintext, &ratio), rng)
+    }
+
+    /// Move a CKKS ciphertext to smaller parameters, changing its scaling
+    /// factor from `source_scale` to `target_scale`.
+    ///
+    /// Fails when the rescaled plaintext does not fit in the target modulus,
+    /// i.e. when the target has too few bits for the message and scale.
+    pub fn downgrade_ckks(
+        &self,
+        ciphertext: &[DcrtPoly; 2],
+        source_scale: f64,
+        target_scale: f64,
+    ) -> Result<[DcrtPoly; 2]> {
+        self.downgrade_ckks_with_rng(ciphertext, source_scale, target_scale, &mut rand::rng())
+    }
+
+    /// [`TrustedReencryptor::downgrade_ckks`] drawing randomness from `rng`
+    pub fn downgrade_ckks_with_rng(
+        &self,
+        ciphertext: &[DcrtPoly; 2],
+        source_scale: f64,
+        target_scale: f64,
+        rng: &mut impl CryptoRng,
+    ) -> Result<[DcrtPoly; 2]> {
+        let ratio = match (
+            BigRational::from_float(target_scale),
+            BigRational::from_float(source_scale),
+        ) {
+            (Some(target), Some(source)) if target.is_positive() && source.is_positive() => {
+                target / source
+            }
+            _ => {
+                return Err(Error::Reencrypt(format!(
+                    "scaling factors {source_scale} and {target_scale} must be positive"
+                )));
+            }
+        };
+        let plaintext = rescale(&self.decrypt(ciphertext)?, &ratio);
+        let half_q = BigInt::from(self.target_modulus().clone()) >> 1u32;
+        if plaintext.iter().any(|c| c.abs() >= half_q) {
+            return Err(Error::Reencrypt(
+                "the rescaled plaintext does not fit in the target modulus".to_string(),
+            ));
+        }
+        self.encrypt(&plaintext, rng)
+    }
+
+    /// The centered coefficients of `c0 + c1 * s`
+    fn decrypt(&self, ciphertext: &[DcrtPoly; 2]) -> Result<Vec<BigInt>> {
+        let [c0, c1] = ciphertext;
+        if c0.params() != self.source_secret.params() || c1.params() != c0.params() {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        let mut plaintext = in_format_of(c1.clone(), &self.source_secret);
+        plaintext *= &self.source_secret;
+        plaintext = in_format_of(plaintext, c0);
+        plaintext += c0;
+        if plaintext.format() == PolynomialRingFormat::Evaluation {
+            plaintext.switch_format();
+        }
+        Ok(plaintext.to_signed())
+    }
+
+    fn encrypt(&self, plaintext: &[BigInt], rng: &mut impl CryptoRng) -> Result<[DcrtPoly; 2]> {
+        let [pk0, _] = &self.target_public_key;
+        let zero = ZeroEncryption::with_public_key_and_rng(
+            &self.target_public_key,
+            self.std_dev,
+            self.secret_key_distribution,
+            rng,
+        )?;
+        let plaintext = in_format_of(
+            DcrtPoly::from_big_signed(pk0.params().clone(), plaintext),
+            pk0,
+        );
+        Ok(zero.encrypt(&plaintext))
+    }
+
+    fn target_modulus(&self) -> &BigUint {
+        self.target_public_key[0]
+            .params()
+            .ciphertext_composite_modulus()
+    }
+}
+
+/// `round(c * ratio)` for every coefficient
+fn rescale(values: &[BigInt], ratio: &BigRational) -> Vec<BigInt> {
+    let (numer, denom) = (ratio.numer(), ratio.denom());
+    values
+        .iter()
+        .map(|c| {
+            // floor((2 c n + d) / 2 d) rounds half up without floating point
+            let twice = (c * numer) << 1u32;
+            (twice + denom).div_floor(&(denom << 1u32))
+        })
+        .collect()
+}
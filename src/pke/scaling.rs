@@ -0,0 +1,295 @@
+//! Scaling factor management for CKKS
+//!
+//! Under [`ScalingTechnique::FlexibleAuto`] every rescaling divides the
+//! scaling factor by the dropped prime, so ciphertexts that took different
+//! paths through a circuit end up with slightly different scaling factors.
+//! Adding them as they are silently mixes two scales. [`align_scales`]
+//! first brings both to the level and the scaling factor of the larger
+//! one, the way OpenFHE adjusts operands before an addition.
+
+use crate::ActingPrimitive;
+use crate::ciphertext::Ciphertext;
+use crate::constants::{PlaintextEncodingsType, ScalingTechnique};
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::crypto_object::CryptoObject;
+use crate::encoding::PlaintextEncodings;
+use crate::error::{Error, Result};
+use crate::pke::encryption_pool::in_format_of;
+use crate::pke::{CryptoContext, CryptoParametersRns, compress};
+use num::{BigInt, FromPrimitive};
+use std::borrow::Cow;
+
+/// The relative difference below which two scaling factors are the same
+const SCALE_TOLERANCE: f64 = 1e-12;
+
+/// Bring two CKKS ciphertexts to a common level and scaling factor so that
+/// their polynomials can be added.
+///
+/// The ciphertext with more towers drops the extra ones. If the scaling
+/// factors still differ, which only [`ScalingTechnique::FlexibleAuto`] and
+/// [`ScalingTechnique::FlexibleAutoExt`] allow, the one with the smaller
+/// factor is multiplied by the integer ratio of the two when there is one.
+/// Otherwise it is multiplied by `round(ratio * q)` for its last prime `q`
+/// and rescaled by `q`, which consumes a level of both ciphertexts and
+/// leaves a relative scale error of at most `1 / (2 ratio q)`.
+pub fn align_scales(
+    technique: ScalingTechnique,
+    a: &mut Ciphertext,
+    b: &mut Ciphertext,
+) -> Result<()> {
+    if a.encoding_type() != PlaintextEncodingsType::CkksPacked
+        || b.encoding_type() != PlaintextEncodingsType::CkksPacked
+    {
+        return Err(Error::CcParams(
+            "only CKKS ciphertexts have scaling factors to align".to_string(),
+        ));
+    }
+    if a.get_crypto_context() != b.get_crypto_context() {
+        return Err(Error::CcParams(
+            "the ciphertexts belong to different crypto contexts".to_string(),
+        ));
+    }
+    let flexible = matches!(
+        technique,
+        ScalingTechnique::FlexibleAuto | ScalingTechnique::FlexibleAutoExt
+    );
+
+    let towers = tower_count(a).min(tower_count(b));
+    if tower_count(a) != tower_count(b) {
+        if technique == ScalingTechnique::FixedManual {
+            return Err(Error::CcParams(format!(
+                "ciphertexts with {} and {} towers must be level reduced manually",
+                tower_count(a),
+                tower_count(b)
+            )));
+        }
+        level_reduce(a, towers);
+        level_reduce(b, towers);
+    }
+
+    let (sa, sb) = (a.scaling_factor(), b.scaling_factor());
+    if (sa - sb).abs() <= SCALE_TOLERANCE * sa.max(sb) {
+        return Ok(());
+    }
+    if !flexible {
+        return Err(Error::CcParams(format!(
+            "scaling factors {sa} and {sb} differ under {technique} scaling"
+        )));
+    }
+    let (low, high) = if sa < sb { (a, b) } else { (b, a) };
+    let target = high.scaling_factor();
+    let ratio = target / low.scaling_factor();
+
+    let rounded = ratio.round();
+    if (ratio - rounded).abs() <= SCALE_TOLERANCE * ratio && rounded < (1u64 << 53) as f64 {
+        let elements = scaled(low.elements(), &BigInt::from(rounded as u64));
+        low.replace_elements("align_scales", elements, target, &[]);
+        return Ok(());
+    }
+
+    if towers < 2 {
+        return Err(Error::CcParams(
+            "aligning the scaling factors needs a level to rescale".to_string(),
+        ));
+    }
+    let q = low.element_params().params()[towers - 1]
+        .ciphertext_modulus
+        .get()
+        .to_primitive() as f64;
+    let constant = BigInt::from_f64((ratio * q).round())
+        .ok_or_else(|| Error::CcParams(format!("cannot scale by the ratio {ratio}")))?;
+    let elements = compress(&scaled(low.elements(), &constant), towers - 1)?;
+    low.replace_elements("align_scales", elements, target, &[]);
+    level_reduce(high, towers - 1);
+    Ok(())
+}
+
+impl CryptoContext {
+    /// Bring two ciphertexts to a common level, noise scale degree and
+    /// scaling factor so that they can be added, the way the automatic
+    /// scaling techniques of `params` do before every addition.
+    ///
+    /// CKKS operands are rescaled down to a noise scale degree of one and
+    /// the one with more towers is scaled onto the other, see
+    /// [`align_scales`] for equal levels. [`ScalingTechnique::FixedManual`]
+    /// leaves that to the caller and only fails on operands that do not
+    /// match. Integer encodings are compressed to the towers they share.
+    pub fn adjust_levels_and_depth(
+        &self,
+        params: &CryptoParametersRns,
+        a: &Ciphertext,
+        b: &Ciphertext,
+    ) -> Result<(Ciphertext, Ciphertext)> {
+        if a.encoding_type() != b.encoding_type() {
+            return Err(Error::CcParams(format!(
+                "cannot combine {} and {} ciphertexts",
+                a.encoding_type(),
+                b.encoding_type()
+            )));
+        }
+        if a.encoding_type() != PlaintextEncodingsType::CkksPacked {
+            let towers = tower_count(a).min(tower_count(b));
+            return Ok((self.compress(a, towers)?, self.compress(b, towers)?));
+        }
+        if params.scaling_technique == ScalingTechnique::FixedManual {
+            let (mut a, mut b) = (a.clone(), b.clone());
+            align_scales(ScalingTechnique::FixedManual, &mut a, &mut b)?;
+            return Ok((a, b));
+        }
+        self.ckks_align(&self.rescaled_fully(a)?, &self.rescaled_fully(b)?)
+    }
+
+    /// Bring two ciphertexts to a common level before they are multiplied.
+    ///
+    /// The automatic CKKS scaling techniques rescale the operand of the
+    /// higher noise scale degree until the product stays within
+    /// [`ScalingTechnique::max_noise_scale_degree`], scaling factors may
+    /// still differ since the product carries both. The other techniques
+    /// leave the operands as they are. Integer encodings are compressed as
+    /// in [`CryptoContext::adjust_levels_and_depth`].
+    pub fn adjust_for_mult(
+        &self,
+        params: &CryptoParametersRns,
+        a: &Ciphertext,
+        b: &Ciphertext,
+    ) -> Result<(Ciphertext, Ciphertext)> {
+        if a.encoding_type() != PlaintextEncodingsType::CkksPacked {
+            return self.adjust_levels_and_depth(params, a, b);
+        }
+        let Some(max) = params.scaling_technique.max_noise_scale_degree() else {
+            return Ok((a.clone(), b.clone()));
+        };
+        let (mut a, mut b) = (a.clone(), b.clone());
+        while a.noise_scale_degree() + b.noise_scale_degree() > max {
+            let higher = if a.noise_scale_degree() >= b.noise_scale_degree() {
+                &mut a
+            } else {
+                &mut b
+            };
+            *higher = self.ckks_rescale(higher)?;
+        }
+        let towers = tower_count(&a).min(tower_count(&b));
+        level_reduce(&mut a, towers);
+        level_reduce(&mut b, towers);
+        Ok((a, b))
+    }
+
+    /// A ciphertext to be squared, rescaled as in
+    /// [`CryptoContext::adjust_for_mult`]
+    pub(crate) fn adjust_for_square(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        let mut result = ciphertext.try_clone()?;
+        if ciphertext.encoding_type() == PlaintextEncodingsType::CkksPacked
+            && let Some(max) = params.scaling_technique.max_noise_scale_degree()
+        {
+            while 2 * result.noise_scale_degree() > max {
+                result = self.ckks_rescale(&result)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// The operands of a product, CKKS ones adjusted by
+    /// [`CryptoContext::adjust_for_mult`] and the others as they are
+    pub(crate) fn multiplicands<'a>(
+        &self,
+        params: &CryptoParametersRns,
+        a: &'a Ciphertext,
+        b: &'a Ciphertext,
+    ) -> Result<(Cow<'a, Ciphertext>, Cow<'a, Ciphertext>)> {
+        if a.encoding_type() != PlaintextEncodingsType::CkksPacked
+            || b.encoding_type() != PlaintextEncodingsType::CkksPacked
+        {
+            return Ok((Cow::Borrowed(a), Cow::Borrowed(b)));
+        }
+        let (a, b) = self.adjust_for_mult(params, a, b)?;
+        Ok((Cow::Owned(a), Cow::Owned(b)))
+    }
+
+    /// Bring a CKKS ciphertext to the scaling factor of `plaintext` so that
+    /// they can be added, rescaling it down to a noise scale degree of one
+    /// first under the automatic scaling techniques.
+    ///
+    /// A scaling factor that still differs is matched by a multiplication
+    /// with one at the scale of the plaintext, which consumes a level. Other
+    /// encodings are returned as they are, their plaintexts are lifted to
+    /// the level of the ciphertext.
+    pub fn adjust_levels_and_depth_plaintext(
+        &self,
+        params: &CryptoParametersRns,
+        a: &Ciphertext,
+        plaintext: &PlaintextEncodings,
+    ) -> Result<Ciphertext> {
+        if a.encoding_type() != PlaintextEncodingsType::CkksPacked
+            || params.scaling_technique == ScalingTechnique::FixedManual
+        {
+            return a.try_clone();
+        }
+        let a = self.rescaled_fully(a)?;
+        let target = plaintext.params().scaling_factor;
+        if (a.scaling_factor() - target).abs() <= SCALE_TOLERANCE * target {
+            return Ok(a);
+        }
+        let towers = tower_count(&a);
+        if towers < 2 {
+            return Err(Error::CcParams(
+                "matching the scaling factor of the plaintext needs a level".to_string(),
+            ));
+        }
+        self.ckks_mult_const_to(&a, 1.0, towers - 1, target)
+    }
+
+    /// A CKKS ciphertext rescaled until its noise scale degree is one or no
+    /// level is left
+    fn rescaled_fully(&self, ciphertext: &Ciphertext) -> Result<Ciphertext> {
+        let mut result = ciphertext.try_clone()?;
+        while result.noise_scale_degree() > 1 && tower_count(&result) > 1 {
+            result = self.ckks_rescale(&result)?;
+        }
+        Ok(result)
+    }
+}
+
+pub(crate) fn tower_count(ciphertext: &Ciphertext) -> usize {
+    ciphertext
+        .elements()
+        .first()
+        .map_or(0, |e| e.towers().len())
+}
+
+/// Drop the towers past the first `towers`, keeping the scaling factor
+pub(crate) fn level_reduce(ciphertext: &mut Ciphertext, towers: usize) {
+    if tower_count(ciphertext) > towers {
+        let elements = ciphertext
+            .elements()
+            .iter()
+            .map(|e| e.shrink(towers))
+            .collect();
+        let scaling_factor = ciphertext.scaling_factor();
+        ciphertext.replace_elements("level_reduce", elements, scaling_factor, &[]);
+    }
+}
+
+/// Every polynomial multiplied by the integer `constant`
+pub(crate) fn scaled(elements: &[DcrtPoly], constant: &BigInt) -> Vec<DcrtPoly> {
+    elements
+        .iter()
+        .map(|e| {
+            let len = e.towers().first().map_or(0, |t| t.len());
+            let mut coefficients = vec![BigInt::ZERO; len];
+            if let Some(c) = coefficients.first_mut() {
+                *c = constant.clone();
+            }
+            let constant = in_format_of(
+                DcrtPoly::from_big_signed(e.params().clone(), &coefficients),
+                e,
+            );
+            let mut e = e.clone();
+            e *= &constant;
+            e
+        })
+        .collect()
+}
@@ -1,8 +1,23 @@
 mod bfvrns;
+mod bootstrap;
+mod bootstrap_estimate;
+mod ckksrns;
+mod eval_mod;
+mod linear_transform;
+mod noise_estimation;
 mod rlwe;
 mod rns;
 mod utils;
 
+pub use bfvrns::*;
+pub use bootstrap_estimate::*;
+pub use ckksrns::*;
+pub use eval_mod::EvalModParams;
+pub use linear_transform::{LinearTransform, LinearTransformParams};
+pub use noise_estimation::NoiseEstimate;
+pub use rlwe::RLWECryptoParameters;
+pub use rns::*;
+
 use derive_more::{Display, FromStr};
 
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Ord, PartialOrd, Hash, Display, FromStr)]
@@ -1 +1,863 @@
+//! The BFV scheme in RNS form
+//!
+//! [`BfvParamsGen`] sizes the ring and the ciphertext towers for a
+//! plaintext modulus and a multiplicative depth, [`BfvRns`] then generates
+//! keys, encrypts with the scaling `Δ = floor(Q / t)` and decrypts with the
+//! scale-and-round `round(t x / Q) mod t` of [`RnsDecoder`](crate::pke::RnsDecoder),
+//! which reports noise overflow instead of returning wrong values. Products
+//! are relinearized with BV or hybrid key switching.
 
+use crate::arena::{ArenaConfig, ArenaKind};
+use crate::ciphertext::Ciphertext;
+use crate::constants::{
+    DistributionType, KeySwitchTechnique, MAX_MODULUS_SIZE, MULTIPARTY_MOD_SIZE, MultipartyMode,
+    MultiplicationTechnique, NUM_MODULI_MULTIPARTY, ProxyPreEncryptionMode, SecretKeyDistribution,
+    SecurityLevel,
+};
+use crate::core::lattice::params::{DcrtElementParamsBuilder, LatticeParams};
+use crate::encoding::{
+    CoefficientPlaintext, EncodingParams, PackedPlaintext, PlaintextEncodings, PlaintextParams,
+};
+use crate::error::{Error, Result};
+use crate::pke::{
+    CcParams, CryptoContext, CryptoParametersRns, EvalKey, Hybrid, KeyPair, PrivateKey, PublicKey,
+};
+use crypto_bigint::U64;
+use rand::CryptoRng;
+
+/// Bound on the error in standard deviations, squared as in OpenFHE
+const ASSURANCE_MEASURE_ALPHA: f64 = 36.0;
+
+/// Generates BFV parameters for a plaintext modulus and multiplicative depth
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BfvParamsGen {
+    /// The user facing settings
+    pub cc_params: CcParams,
+    /// The plaintext modulus `t`, odd and at least three
+    pub plaintext_modulus: u64,
+    /// The number of sequential multiplications to support
+    pub multiplicative_depth: usize,
+    /// A fixed ring dimension instead of the smallest secure one
+    pub ring_dimension: Option<usize>,
+    /// The bit size of each tower
+    pub tower_bits: usize,
+    /// How the tensor product of ciphertexts is scaled back to `Q`
+    pub multiplication_technique: MultiplicationTechnique,
+    /// How relinearization and rotation keys switch secrets
+    pub key_switch_technique: KeySwitchTechnique,
+    /// The digits of hybrid key switching, zero to derive them from the
+    /// number of towers
+    pub num_large_digits: usize,
+}
+
+impl BfvParamsGen {
+    /// Parameters for `plaintext_modulus` at depth zero, with 60 bit towers
+    pub fn new(plaintext_modulus: u64) -> Self {
+        Self {
+            cc_params: CcParams::default(),
+            plaintext_modulus,
+            multiplicative_depth: 0,
+            ring_dimension: None,
+            tower_bits: MAX_MODULUS_SIZE,
+            multiplication_technique: MultiplicationTechnique::default(),
+            key_switch_technique: KeySwitchTechnique::Bv,
+            num_large_digits: 0,
+        }
+    }
+
+    /// Set the user facing settings
+    pub fn cc_params(mut self, cc_params: CcParams) -> Self {
+        self.cc_params = cc_params;
+        self
+    }
+
+    /// Set the multiplicative depth
+    pub fn multiplicative_depth(mut self, multiplicative_depth: usize) -> Self {
+        self.multiplicative_depth = multiplicative_depth;
+        self
+    }
+
+    /// Fix the ring dimension, required when the security level is not set
+    pub fn ring_dimension(mut self, ring_dimension: usize) -> Self {
+        self.ring_dimension = Some(ring_dimension);
+        self
+    }
+
+    /// Set the bit size of each tower
+    pub fn tower_bits(mut self, tower_bits: usize) -> Self {
+        self.tower_bits = tower_bits;
+        self
+    }
+
+    /// Set the multiplication technique
+    pub fn multiplication_technique(mut self, technique: MultiplicationTechnique) -> Self {
+        self.multiplication_technique = technique;
+        self
+    }
+
+    /// Set the key switching technique
+    pub fn key_switch_technique(mut self, technique: KeySwitchTechnique) -> Self {
+        self.key_switch_technique = technique;
+        self
+    }
+
+    /// Set the number of digits of hybrid key switching
+    pub fn num_large_digits(mut self, num_large_digits: usize) -> Self {
+        self.num_large_digits = num_large_digits;
+        self
+    }
+
+    /// The bits of the ciphertext modulus needed to decrypt correctly after
+    /// [`BfvParamsGen::multiplicative_depth`] multiplications in ring
+    /// dimension `n`, followed by up to [`CcParams::max_hops`] proxy
+    /// re-encryptions. Hops of [`ProxyPreEncryptionMode::NoiseFloodingHra`]
+    /// only leave the rounding noise of their dropped tower, which the
+    /// extra towers added in [`BfvParamsGen::build`] pay for. Joint
+    /// decryptions of more than one [`CcParams::threshold_parties`] add the
+    /// flooding noise of every party, which in
+    /// [`MultipartyMode::NoiseFlooding`] the extra towers pay for too.
+    ///
+    /// This is the worst case bound of OpenFHE's BFV parameter generation
+    /// with relinearization noise reduced by hybrid key switching.
+    pub fn log_q(&self, n: usize) -> f64 {
+        let t = self.plaintext_modulus as f64;
+        let mut q = 2f64.powf(self.log_q_before_hops(n));
+        if self.cc_params.proxy_pre_encryption_mode != ProxyPreEncryptionMode::NoiseFloodingHra {
+            let hops = self.cc_params.max_hops as f64;
+            q += 4.0 * t * hops * 2f64.powf(self.hop_noise_bits(n));
+        }
+        let parties = self.cc_params.threshold_parties;
+        if parties > 1 && self.cc_params.multiparty_mode == MultipartyMode::FixedNoise {
+            let flooding = self.cc_params.flooding_std_dev() * ASSURANCE_MEASURE_ALPHA.sqrt();
+            q += 4.0 * t * parties as f64 * flooding;
+        }
+        q.log2()
+    }
+
+    /// The bits of the noise one proxy re-encryption adds in ring dimension
+    /// `n`, the BV digits of `c1` times the noise of their encryptions under
+    /// the public key of the re-encryption key, plus the encryption of zero
+    /// with flooded errors of [`ProxyPreEncryptionMode::FixedNoiseHra`]
+    pub fn hop_noise_bits(&self, n: usize) -> f64 {
+        let noise = self.noise(n);
+        let fixed = match self.cc_params.proxy_pre_encryption_mode {
+            ProxyPreEncryptionMode::FixedNoiseHra => {
+                noise.fresh * self.cc_params.flooding_std_dev() / self.cc_params.std_dev
+            }
+            _ => 0.0,
+        };
+        (self.bv_digit_bound(n) * noise.fresh + fixed).log2()
+    }
+
+    /// The bound on the sum of the BV digits of an element times `delta`,
+    /// for digits of [`CcParams::digit_size`] bits over about the towers the
+    /// products need, plus one
+    fn bv_digit_bound(&self, n: usize) -> f64 {
+        let towers = (self.log_q_of_products(n) / self.tower_bits as f64).ceil() + 1.0;
+        let per_tower = match self.cc_params.digit_size {
+            0 => 2f64.powi(self.tower_bits as i32 - 1),
+            w => self.tower_bits.div_ceil(w) as f64 * 2f64.powi(w as i32),
+        };
+        towers * per_tower * self.noise(n).delta
+    }
+
+    /// The modulus of [`BfvParamsGen::log_q_of_products`] plus the BV
+    /// relinearization noise of every product, hybrid key switching divides
+    /// its noise away
+    fn log_q_before_hops(&self, n: usize) -> f64 {
+        let log_q = self.log_q_of_products(n);
+        if self.multiplicative_depth == 0 || self.key_switch_technique != KeySwitchTechnique::Bv {
+            return log_q;
+        }
+        let t = self.plaintext_modulus as f64;
+        let depth = self.multiplicative_depth as f64;
+        let relinearization = self.bv_digit_bound(n) * self.noise(n).b_err;
+        (2f64.powf(log_q) + 4.0 * t * depth * relinearization).log2()
+    }
+
+    fn log_q_of_products(&self, n: usize) -> f64 {
+        let t = self.plaintext_modulus as f64;
+        let noise = self.noise(n);
+        if self.multiplicative_depth == 0 {
+            return (4.0 * t * noise.fresh).log2();
+        }
+        let depth = self.multiplicative_depth as f64;
+        (4.0 * t).log2()
+            + (depth - 1.0) * noise.c1.log2()
+            + (noise.c1 * noise.fresh + depth * noise.c2).log2()
+    }
+
+    /// The bits of the ciphertext modulus that can be dropped before
+    /// multiplying ciphertexts after [`BfvParamsGen::multiplicative_depth`]
+    /// multiplications, as [`HpsOverQLeveled`](crate::constants::MultiplicationTechnique::HpsOverQLeveled)
+    /// does.
+    ///
+    /// Dropping to `Q_l` and scaling the product back up multiplies the
+    /// rounding noise of the switch and of the product by `Q / Q_l`, which
+    /// is kept at half the noise bound of the product over the full modulus.
+    pub fn droppable_bits(&self, n: usize) -> f64 {
+        let t = self.plaintext_modulus as f64;
+        let noise = self.noise(n);
+        let switch = (1.0 + noise.delta * noise.b_key) / 2.0;
+        let product = Self {
+            multiplicative_depth: self.multiplicative_depth + 1,
+            ..*self
+        };
+        product.log_q_before_hops(n)
+            - (4.0 * t).log2()
+            - (noise.c1 * switch + noise.c2).log2()
+            - 1.0
+    }
+
+    fn noise(&self, n: usize) -> Noise {
+        let std_dev = self.cc_params.std_dev;
+        let t = self.plaintext_modulus as f64;
+        let delta = 2.0 * (n as f64).sqrt();
+        // Joint keys sum the secrets and errors of every threshold party
+        let parties = self.cc_params.threshold_parties.max(1) as f64;
+        let b_err = std_dev * ASSURANCE_MEASURE_ALPHA.sqrt() * parties.sqrt();
+        let b_key = match self.cc_params.secret_key_distribution {
+            SecretKeyDistribution::Gaussian => b_err,
+            _ => parties,
+        };
+        Noise {
+            delta,
+            b_err,
+            b_key,
+            fresh: b_err * (1.0 + 2.0 * delta * b_key),
+            c1: (1.0 + 4.0 / (delta * b_key)) * delta * delta * t * b_key,
+            c2: delta * delta * b_key * (b_key + t * t) + delta * b_err,
+        }
+    }
+
+    /// Pick the ring dimension and towers and build the scheme
+    pub fn build(self) -> Result<BfvRns> {
+        let t = self.plaintext_modulus;
+        let plaintext_modulus = Option::from(U64::from_u64(t).to_odd())
+            .filter(|_| t >= 3)
+            .ok_or_else(|| {
+                Error::CcParams(format!(
+                    "the plaintext modulus {t} must be odd and at least 3"
+                ))
+            })?;
+        if self.tower_bits == 0 || self.tower_bits > MAX_MODULUS_SIZE {
+            return Err(Error::CcParams(format!(
+                "towers of {} bits are not supported",
+                self.tower_bits
+            )));
+        }
+        let mut rlwe = self.cc_params.rlwe_crypto_parameters()?;
+        rlwe.assurance_measure_alpha = ASSURANCE_MEASURE_ALPHA;
+        let distribution = DistributionType::from(rlwe.secret_key_distribution);
+        let security_level = rlwe.security_level;
+        let extra_bits = match rlwe.multiparty_mode {
+            MultipartyMode::NoiseFlooding => NUM_MODULI_MULTIPARTY * MULTIPARTY_MOD_SIZE,
+            _ => 0,
+        } + match rlwe.proxy_pre_encryption_mode {
+            ProxyPreEncryptionMode::NoiseFloodingHra => rlwe.max_hops * MULTIPARTY_MOD_SIZE,
+            _ => 0,
+        };
+
+        let towers_for = |n: usize| (self.log_q(n).ceil() as usize).div_ceil(self.tower_bits);
+        // The keys of hybrid key switching are over `P Q`
+        let aux_bits_for = |n: usize| match self.key_switch_technique {
+            KeySwitchTechnique::Hybrid => {
+                let towers = towers_for(n);
+                Hybrid::aux_bits(
+                    &vec![self.tower_bits; towers],
+                    Hybrid::towers_per_digit(towers, self.num_large_digits),
+                )
+            }
+            _ => 0,
+        };
+        let log_q_for = |n: usize| towers_for(n) * self.tower_bits + extra_bits + aux_bits_for(n);
+        let n = match self.ring_dimension {
+            Some(n) => {
+                if !n.is_power_of_two() || n < 2 {
+                    return Err(Error::CcParams(format!(
+                        "the ring dimension {n} is not a power of two"
+                    )));
+                }
+                if security_level != SecurityLevel::HeStdNotSet
+                    && LatticeParams::find_max_q(distribution, security_level, n) < log_q_for(n)
+                {
+                    return Err(Error::CcParams(format!(
+                        "ring dimension {n} does not reach {security_level} with a {} bit modulus",
+                        log_q_for(n)
+                    )));
+                }
+                n
+            }
+            None => {
+                let secure = |log_q| {
+                    LatticeParams::min_ring_dimension(distribution, security_level, log_q)
+                        .ok_or_else(|| {
+                            Error::CcParams(format!(
+                                "no ring dimension reaches {security_level} with a {log_q} bit modulus, set one explicitly"
+                            ))
+                        })
+                };
+                // The noise grows with the ring dimension, so repeat until
+                // the modulus it needs is secure in the same dimension
+                let mut n = secure(0)?;
+                loop {
+                    let needed = secure(log_q_for(n))?;
+                    if needed <= n {
+                        break n;
+                    }
+                    n = needed;
+                }
+            }
+        };
+
+        let dcrt_element_params = DcrtElementParamsBuilder::new(2 * n)
+            .depth(towers_for(n))
+            .bits(self.tower_bits)
+            .build()?;
+        let mut params = CryptoParametersRns {
+            rlwe_crypto_parameters: rlwe,
+            key_switch_technique: self.key_switch_technique,
+            scaling_technique: Default::default(),
+            encryption_technique: Default::default(),
+            multiplication_technique: self.multiplication_technique,
+            aux_bits: aux_bits_for(n),
+            num_large_digits: self.num_large_digits,
+            extra_bits: 0,
+            dcrt_element_params,
+            bfv_multipliers: Default::default(),
+        };
+        params.add_multiparty_towers()?;
+        params.add_pre_towers()?;
+        params.precompute_bfv_multipliers(t, self.multiplicative_depth, |depth| {
+            Self {
+                multiplicative_depth: depth,
+                ..self
+            }
+            .droppable_bits(n)
+        })?;
+        let mut crypto_context = CryptoContext::default();
+        crypto_context.element_params = params.dcrt_element_params.params()[0];
+        crypto_context.encoding_params = EncodingParams {
+            plaintext_modulus,
+            ..Default::default()
+        };
+        Ok(BfvRns {
+            crypto_context,
+            params,
+        })
+    }
+}
+
+/// The expansion factor, key bound and noise growth terms of the
+/// parameter generation
+struct Noise {
+    delta: f64,
+    b_err: f64,
+    b_key: f64,
+    /// The noise of a fresh encryption
+    fresh: f64,
+    /// The factor a multiplication grows noise by
+    c1: f64,
+    /// The noise a multiplication adds
+    c2: f64,
+}
+
+/// A BFV instance with generated parameters
+#[derive(Clone, Debug, PartialEq)]
+pub struct BfvRns {
+    crypto_context: CryptoContext,
+    params: CryptoParametersRns,
+}
+
+impl BfvRns {
+    /// The crypto context keys and ciphertexts belong to
+    pub fn crypto_context(&self) -> &CryptoContext {
+        &self.crypto_context
+    }
+
+    /// Cap the plaintexts and ciphertexts of the context, see
+    /// [`CryptoContext::with_arena`]
+    pub fn with_arena(mut self, config: ArenaConfig) -> Self {
+        self.crypto_context = self.crypto_context.with_arena(config);
+        self
+    }
+
+    /// The RNS parameters
+    pub fn crypto_parameters(&self) -> &CryptoParametersRns {
+        &self.params
+    }
+
+    /// The plaintext modulus `t`
+    pub fn plaintext_modulus(&self) -> u64 {
+        self.crypto_context
+            .encoding_params
+            .plaintext_modulus
+            .get()
+            .as_words()[0]
+    }
+
+    /// A secret key and its public key
+    pub fn key_gen(&self) -> Result<KeyPair> {
+        self.crypto_context.key_gen(&self.params)
+    }
+
+    /// [`BfvRns::key_gen`] drawing randomness from `rng`
+    pub fn key_gen_with_rng(&self, rng: &mut impl CryptoRng) -> Result<KeyPair> {
+        self.crypto_context.key_gen_with_rng(&self.params, rng)
+    }
+
+    /// Encode `values` as the coefficients of a plaintext modulo `t`
+    pub fn encode(&self, values: &[i64]) -> Result<PlaintextEncodings> {
+        let params = PlaintextParams {
+            encoding_params: self.crypto_context.encoding_params,
+            ..Default::default()
+        };
+        let lease = self.crypto_context.lease(ArenaKind::Plaintext)?;
+        Ok(PlaintextEncodings::Coefficient(CoefficientPlaintext::new(
+            values,
+            params,
+            self.crypto_context.element_params.cyclotomic_order,
+        )?)
+        .with_lease(lease))
+    }
+
+    /// Encode `values` into the slots of a plaintext modulo `t`, which needs
+    /// `t = 1 mod 2n`, see [`PackedPlaintext::new`]
+    pub fn encode_packed(&self, values: &[i64]) -> Result<PlaintextEncodings> {
+        let params = PlaintextParams {
+            slots: self.crypto_context.element_params.ring_dimension,
+            encoding_params: self.crypto_context.encoding_params,
+            ..Default::default()
+        };
+        let lease = self.crypto_context.lease(ArenaKind::Plaintext)?;
+        Ok(PlaintextEncodings::Packed(PackedPlaintext::new(
+            values,
+            params,
+            self.crypto_context.element_params.cyclotomic_order,
+        )?)
+        .with_lease(lease))
+    }
+
+    /// Encrypt `plaintext` under `public_key` as `Δ m + (-a s + e) u + e'`
+    pub fn encrypt(
+        &self,
+        public_key: &PublicKey,
+        plaintext: &PlaintextEncodings,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .encrypt(&self.params, public_key, plaintext)
+    }
+
+    /// [`BfvRns::encrypt`] drawing randomness from `rng`
+    pub fn encrypt_with_rng(
+        &self,
+        public_key: &PublicKey,
+        plaintext: &PlaintextEncodings,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .encrypt_with_rng(&self.params, public_key, plaintext, rng)
+    }
+
+    /// `ciphertext` with fresh randomness, see [`CryptoContext::rerandomize`]
+    pub fn rerandomize(
+        &self,
+        public_key: &PublicKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .rerandomize(&self.params, public_key, ciphertext)
+    }
+
+    /// [`BfvRns::rerandomize`] drawing randomness from `rng`
+    pub fn rerandomize_with_rng(
+        &self,
+        public_key: &PublicKey,
+        ciphertext: &Ciphertext,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .rerandomize_with_rng(&self.params, public_key, ciphertext, rng)
+    }
+
+    /// `ciphertext` with fresh randomness and flooded noise of at most
+    /// `noise_bits` bits, see [`CryptoContext::flood_noise`]
+    pub fn flood_noise(
+        &self,
+        public_key: &PublicKey,
+        ciphertext: &Ciphertext,
+        noise_bits: f64,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .flood_noise(&self.params, public_key, ciphertext, noise_bits)
+    }
+
+    /// [`BfvRns::flood_noise`] drawing randomness from `rng`
+    pub fn flood_noise_with_rng(
+        &self,
+        public_key: &PublicKey,
+        ciphertext: &Ciphertext,
+        noise_bits: f64,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.crypto_context.flood_noise_with_rng(
+            &self.params,
+            public_key,
+            ciphertext,
+            noise_bits,
+            rng,
+        )
+    }
+
+    /// A key re-encrypting from `old_key` to `new_public_key`, see
+    /// [`CryptoContext::re_key_gen`]
+    pub fn re_key_gen(&self, old_key: &PrivateKey, new_public_key: &PublicKey) -> Result<EvalKey> {
+        self.crypto_context
+            .re_key_gen(&self.params, old_key, new_public_key)
+    }
+
+    /// [`BfvRns::re_key_gen`] drawing randomness from `rng`
+    pub fn re_key_gen_with_rng(
+        &self,
+        old_key: &PrivateKey,
+        new_public_key: &PublicKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<EvalKey> {
+        self.crypto_context
+            .re_key_gen_with_rng(&self.params, old_key, new_public_key, rng)
+    }
+
+    /// Re-encrypt `ciphertext` with `re_key`, see [`CryptoContext::re_encrypt`]
+    pub fn re_encrypt(
+        &self,
+        ciphertext: &Ciphertext,
+        re_key: &EvalKey,
+        public_key: Option<&PublicKey>,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .re_encrypt(&self.params, ciphertext, re_key, public_key)
+    }
+
+    /// [`BfvRns::re_encrypt`] drawing randomness from `rng`
+    pub fn re_encrypt_with_rng(
+        &self,
+        ciphertext: &Ciphertext,
+        re_key: &EvalKey,
+        public_key: Option<&PublicKey>,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .re_encrypt_with_rng(&self.params, ciphertext, re_key, public_key, rng)
+    }
+
+    /// The key pair of the next party of the joint key `public_key`, see
+    /// [`CryptoContext::multiparty_key_gen`]
+    pub fn multiparty_key_gen(&self, public_key: &PublicKey) -> Result<KeyPair> {
+        self.crypto_context
+            .multiparty_key_gen(&self.params, public_key)
+    }
+
+    /// [`BfvRns::multiparty_key_gen`] drawing randomness from `rng`
+    pub fn multiparty_key_gen_with_rng(
+        &self,
+        public_key: &PublicKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<KeyPair> {
+        self.crypto_context
+            .multiparty_key_gen_with_rng(&self.params, public_key, rng)
+    }
+
+    /// The partial decryption of `ciphertext` by the lead party, see
+    /// [`CryptoContext::multiparty_decrypt_lead`]
+    pub fn multiparty_decrypt_lead(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .multiparty_decrypt_lead(&self.params, private_key, ciphertext)
+    }
+
+    /// [`BfvRns::multiparty_decrypt_lead`] drawing randomness from `rng`
+    pub fn multiparty_decrypt_lead_with_rng(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.crypto_context.multiparty_decrypt_lead_with_rng(
+            &self.params,
+            private_key,
+            ciphertext,
+            rng,
+        )
+    }
+
+    /// The partial decryption of `ciphertext` by another party, see
+    /// [`CryptoContext::multiparty_decrypt_main`]
+    pub fn multiparty_decrypt_main(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .multiparty_decrypt_main(&self.params, private_key, ciphertext)
+    }
+
+    /// [`BfvRns::multiparty_decrypt_main`] drawing randomness from `rng`
+    pub fn multiparty_decrypt_main_with_rng(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.crypto_context.multiparty_decrypt_main_with_rng(
+            &self.params,
+            private_key,
+            ciphertext,
+            rng,
+        )
+    }
+
+    /// Combine the partial decryptions of every party, see
+    /// [`CryptoContext::multiparty_decrypt_fusion`]
+    pub fn multiparty_decrypt_fusion(
+        &self,
+        partial_decryptions: &[Ciphertext],
+    ) -> Result<PlaintextEncodings> {
+        self.crypto_context
+            .multiparty_decrypt_fusion(partial_decryptions)
+    }
+
+    /// The relinearization key of `private_key`
+    pub fn eval_mult_key_gen(&self, private_key: &PrivateKey) -> Result<EvalKey> {
+        self.crypto_context
+            .eval_mult_key_gen(&self.params, private_key)
+    }
+
+    /// [`BfvRns::eval_mult_key_gen`] drawing randomness from `rng`
+    pub fn eval_mult_key_gen_with_rng(
+        &self,
+        private_key: &PrivateKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<EvalKey> {
+        self.crypto_context
+            .eval_mult_key_gen_with_rng(&self.params, private_key, rng)
+    }
+
+    /// The relinearization keys of `private_key` for `s^2` up to the
+    /// maximal secret key power, see [`CryptoContext::eval_mult_keys_gen`]
+    pub fn eval_mult_keys_gen(&self, private_key: &PrivateKey) -> Result<Vec<EvalKey>> {
+        self.crypto_context
+            .eval_mult_keys_gen(&self.params, private_key)
+    }
+
+    /// [`BfvRns::eval_mult_keys_gen`] drawing randomness from `rng`
+    pub fn eval_mult_keys_gen_with_rng(
+        &self,
+        private_key: &PrivateKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Vec<EvalKey>> {
+        self.crypto_context
+            .eval_mult_keys_gen_with_rng(&self.params, private_key, rng)
+    }
+
+    /// Relinearize `ciphertext` with the stored keys of its key tag, see
+    /// [`CryptoContext::relinearize`]
+    pub fn relinearize(&self, ciphertext: &Ciphertext) -> Result<Ciphertext> {
+        self.crypto_context.relinearize(ciphertext)
+    }
+
+    /// The relinearized product of two ciphertexts, see [`CryptoContext::eval_mult`]
+    pub fn eval_mult(
+        &self,
+        a: &Ciphertext,
+        b: &Ciphertext,
+        relin_key: &EvalKey,
+    ) -> Result<Ciphertext> {
+        self.crypto_context.eval_mult(&self.params, a, b, relin_key)
+    }
+
+    /// The relinearized square of a ciphertext, see
+    /// [`CryptoContext::eval_square`]
+    pub fn eval_square(&self, ciphertext: &Ciphertext) -> Result<Ciphertext> {
+        self.crypto_context.eval_square(&self.params, ciphertext)
+    }
+
+    /// The product of `ciphertexts` in a balanced tree, see
+    /// [`CryptoContext::eval_mult_many`]
+    pub fn eval_mult_many(&self, ciphertexts: &[Ciphertext]) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_mult_many(&self.params, ciphertexts)
+    }
+
+    /// A key switching from the secret of `old_key` to the secret of
+    /// `new_key`, see [`CryptoContext::key_switch_gen`]
+    pub fn key_switch_gen(&self, old_key: &PrivateKey, new_key: &PrivateKey) -> Result<EvalKey> {
+        self.crypto_context
+            .key_switch_gen(&self.params, old_key, new_key)
+    }
+
+    /// [`BfvRns::key_switch_gen`] drawing randomness from `rng`
+    pub fn key_switch_gen_with_rng(
+        &self,
+        old_key: &PrivateKey,
+        new_key: &PrivateKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<EvalKey> {
+        self.crypto_context
+            .key_switch_gen_with_rng(&self.params, old_key, new_key, rng)
+    }
+
+    /// Decrypt and scale down `ciphertext`, failing on noise overflow
+    pub fn decrypt(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<PlaintextEncodings> {
+        self.crypto_context.decrypt(private_key, ciphertext)
+    }
+
+    /// The bits the noise of `ciphertext` can still grow by, see
+    /// [`CryptoContext::noise_budget_bits`]
+    pub fn noise_budget_bits(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<f64> {
+        self.crypto_context
+            .noise_budget_bits(private_key, ciphertext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pke::test_support::{self, RING_DIMENSION, bfv_context};
+    use rand::Rng;
+
+    #[test]
+    fn dense_messages_round_trip() {
+        let bfv = bfv_context(0);
+        let key_pair = bfv.key_gen().expect("keys");
+        let mut rng = rand::rng();
+        let message = (0..RING_DIMENSION)
+            .map(|_| rng.random_range(-32768..=32768))
+            .collect::<Vec<i64>>();
+        let plaintext = bfv.encode(&message).expect("encode");
+        let ciphertexts = [
+            bfv.encrypt(key_pair.public_key(), &plaintext)
+                .expect("encrypt"),
+            bfv.crypto_context()
+                .encrypt_with_secret(bfv.crypto_parameters(), key_pair.private_key(), &plaintext)
+                .expect("encrypt with the secret"),
+        ];
+        for ciphertext in ciphertexts {
+            let PlaintextEncodings::Coefficient(decrypted) = bfv
+                .decrypt(key_pair.private_key(), &ciphertext)
+                .expect("decrypt")
+            else {
+                panic!("a coefficient plaintext");
+            };
+            assert_eq!(decrypted.coefficients(), message);
+        }
+    }
+
+    /// The negacyclic product of two coefficient vectors, centered modulo `t`
+    fn negacyclic(a: &[i64], b: &[i64], t: i64) -> Vec<i64> {
+        let n = a.len();
+        let mut product = vec![0i64; n];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                let term = x * y % t;
+                let k = (i + j) % n;
+                product[k] = if i + j < n {
+                    (product[k] + term) % t
+                } else {
+                    (product[k] - term) % t
+                };
+            }
+        }
+        product
+            .into_iter()
+            .map(|c| {
+                let c = c.rem_euclid(t);
+                if c > t / 2 { c - t } else { c }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn products_match_plain_negacyclic_products() {
+        let t = 65537;
+        let mut rng = rand::rng();
+        let mut message = || {
+            (0..RING_DIMENSION)
+                .map(|_| rng.random_range(-16..=16))
+                .collect::<Vec<i64>>()
+        };
+        let (a, b, c) = (message(), message(), message());
+        let ab = negacyclic(&a, &b, t);
+        let square = negacyclic(&a, &a, t);
+        let abc = negacyclic(&ab, &c, t);
+
+        for multiplication_technique in [
+            MultiplicationTechnique::Behz,
+            MultiplicationTechnique::Hps,
+            MultiplicationTechnique::HpsOverQ,
+            MultiplicationTechnique::HpsOverQLeveled,
+        ] {
+            for key_switch_technique in [KeySwitchTechnique::Bv, KeySwitchTechnique::Hybrid] {
+                let techniques = format!("{multiplication_technique:?} {key_switch_technique:?}");
+                let bfv = test_support::bfv_params()
+                    .multiplicative_depth(2)
+                    .multiplication_technique(multiplication_technique)
+                    .key_switch_technique(key_switch_technique)
+                    .build()
+                    .expect("parameters");
+                // The stored relinearization keys are shared by key tag
+                let tag = format!("bfvrns::tests::{techniques}");
+                let (public_key, private_key) = bfv.key_gen().expect("keys").into_parts();
+                let (public_key, private_key) = (
+                    public_key.with_key_tag(&tag),
+                    private_key.with_key_tag(&tag),
+                );
+                let relin_key = bfv.eval_mult_key_gen(&private_key).expect("relin key");
+                let encrypt = |m: &[i64]| {
+                    bfv.encrypt(&public_key, &bfv.encode(m).expect("encode"))
+                        .expect("encrypt")
+                };
+                let decrypt = |ciphertext: &Ciphertext| {
+                    let PlaintextEncodings::Coefficient(decrypted) =
+                        bfv.decrypt(&private_key, ciphertext).expect("decrypt")
+                    else {
+                        panic!("a coefficient plaintext");
+                    };
+                    decrypted.coefficients()
+                };
+                let (ca, cb, cc) = (encrypt(&a), encrypt(&b), encrypt(&c));
+
+                let product = bfv.eval_mult(&ca, &cb, &relin_key).expect("multiply");
+                assert_eq!(decrypt(&product), ab, "{techniques} product");
+                let squared = bfv.eval_square(&ca).expect("square");
+                assert_eq!(decrypt(&squared), square, "{techniques} square");
+                let many = bfv
+                    .eval_mult_many(&[ca.clone(), cb, cc])
+                    .expect("multiply many");
+                assert_eq!(decrypt(&many), abc, "{techniques} product of three");
+
+                let budget = |ciphertext| {
+                    bfv.noise_budget_bits(&private_key, ciphertext)
+                        .expect("noise budget")
+                };
+                assert!(
+                    budget(&product) < budget(&ca),
+                    "{techniques}: {} bits after multiplying, {} before",
+                    budget(&product),
+                    budget(&ca)
+                );
+                assert!(budget(&many) < budget(&product), "{techniques}");
+            }
+        }
+    }
+}
@@ -0,0 +1,512 @@
+//! CKKS bootstrapping
+//!
+//! Cheon, Han, Kim, Kim and Song, "Bootstrapping for Approximate Homomorphic
+//! Encryption" (EUROCRYPT 2018) with the linear transforms of Chen, Chillotti
+//! and Song (EUROCRYPT 2019), as OpenFHE implements them. A ciphertext at the
+//! first tower `q0` is raised to every tower, where it decrypts to its
+//! message plus `q0 I` for a small integer polynomial `I`. CoeffToSlot moves
+//! the coefficients into the slots, EvalMod removes `q0 I` slotwise with a
+//! scaled sine and SlotToCoeff moves the coefficients back.
+//!
+//! The transforms are the [`LinearTransform`]s of
+//! [`CryptoContext::eval_coeffs_to_slots`] and
+//! [`CryptoContext::eval_slots_to_coeffs`], see [`estimate_bootstrap`] for
+//! the costs.
+//!
+//! [`estimate_bootstrap`]: super::estimate_bootstrap
+
+use super::eval_mod::{EvalModParams, with_scaling_factor};
+use crate::ActingPrimitive;
+use crate::ciphertext::Ciphertext;
+use crate::constants::{PlaintextEncodingsType, PolynomialRingFormat};
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::core::lattice::params::DcrtElementParams;
+use crate::error::{Error, Result};
+use crate::pke::encryption_pool::in_format_of;
+use crate::pke::{
+    CryptoContext, CryptoParametersRns, EvalKey, FheBase, LinearTransform, LinearTransformParams,
+    PrivateKey, automorphism_index, level_reduce, tower_count,
+};
+use rand::CryptoRng;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::f64::consts::PI;
+use std::sync::{Arc, RwLock};
+
+/// Messages are raised at `q0 / 2^7` unless the setup asks otherwise
+const DEFAULT_CORRECTION_FACTOR: usize = 7;
+
+/// The setups of every crypto context, its parameters and number of slots
+static BOOTSTRAP_SETUPS: RwLock<Vec<Arc<BootstrapSetup>>> = RwLock::new(Vec::new());
+
+/// The configuration of [`FheBase::eval_bootstrap_setup`] for one crypto
+/// context, its parameters and number of slots
+#[derive(Debug)]
+struct BootstrapSetup {
+    crypto_context: CryptoContext,
+    params: CryptoParametersRns,
+    slots: usize,
+    level_budget: [usize; 2],
+    dimension1: [usize; 2],
+    correction_factor: usize,
+    /// CoeffToSlot and SlotToCoeff when precomputed
+    transforms: Option<[LinearTransform; 2]>,
+    /// EvalMod with the bound `K` on the coefficients of `I`
+    eval_mod: EvalModParams,
+    /// The Chebyshev series of [`EvalModParams::coefficients`]
+    coefficients: Vec<f64>,
+}
+
+impl BootstrapSetup {
+    fn is_for(&self, cc: &CryptoContext, params: &CryptoParametersRns, slots: usize) -> bool {
+        self.crypto_context == *cc && self.params == *params && self.slots == slots
+    }
+
+    fn transforms(
+        &self,
+        cc: &CryptoContext,
+        params: &CryptoParametersRns,
+    ) -> Result<Cow<'_, [LinearTransform; 2]>> {
+        Ok(match &self.transforms {
+            Some(transforms) => Cow::Borrowed(transforms),
+            None => Cow::Owned(self.precompute(cc, params)?),
+        })
+    }
+
+    /// CoeffToSlot for the raised ciphertext at every tower and SlotToCoeff
+    /// for the towers EvalMod leaves
+    fn precompute(
+        &self,
+        cc: &CryptoContext,
+        params: &CryptoParametersRns,
+    ) -> Result<[LinearTransform; 2]> {
+        let moduli = params.dcrt_element_params.params();
+        let (q0, top) = match (moduli.front(), moduli.back()) {
+            (Some(first), Some(last)) => (
+                first.ciphertext_modulus.get().to_primitive() as f64,
+                last.ciphertext_modulus.get().to_primitive() as f64,
+            ),
+            _ => return Err(Error::DcrtElementParamsMismatch),
+        };
+        let slots = self.slots as f64;
+        let coeffs_to_slots = cc.eval_coeffs_to_slots_precompute(
+            params,
+            &LinearTransformParams {
+                slots: self.slots,
+                level_budget: self.level_budget[0],
+                dimension1: self.dimension1[0],
+                towers: moduli.len(),
+                // The trace over the unused slots divides by their number
+                scaling_factor: q0 * (cc.element_params.cyclotomic_order / 4) as f64 / slots,
+                constant: self.coeffs_to_slots_constant(),
+            },
+        )?;
+        let slots_to_coeffs = cc.eval_slots_to_coeffs_precompute(
+            params,
+            &LinearTransformParams {
+                slots: self.slots,
+                level_budget: self.level_budget[1],
+                dimension1: self.dimension1[1],
+                towers: moduli
+                    .len()
+                    .saturating_sub(coeffs_to_slots.levels() + self.eval_mod.depth()),
+                scaling_factor: top,
+                constant: 2f64.powi(self.correction_factor as i32) / (2.0 * PI),
+            },
+        )?;
+        Ok([coeffs_to_slots, slots_to_coeffs])
+    }
+
+    /// CoeffToSlot divides by `n` to invert the butterflies, by two for the
+    /// real and imaginary parts and by `K` to land in `[-1, 1]`
+    fn coeffs_to_slots_constant(&self) -> f64 {
+        1.0 / (2.0 * self.slots as f64 * self.eval_mod.bound)
+    }
+}
+
+impl FheBase for CryptoContext {
+    /// Prepare bootstrapping ciphertexts of `slots` slots, zero for the slots
+    /// of the context.
+    ///
+    /// CoeffToSlot and SlotToCoeff consume `level_budget[0]` and
+    /// `level_budget[1]` levels, merging the `log2(slots)` butterfly layers
+    /// evenly, with `dimension1` baby steps each, zero or empty for the
+    /// default. Messages are raised at `q0 / 2^correction_factor`, where a
+    /// larger factor makes the sine closer to the identity but leaves less
+    /// precision to the message.
+    fn eval_bootstrap_setup(
+        &self,
+        params: &CryptoParametersRns,
+        level_budget: &[usize],
+        dimension1: &[usize],
+        slots: usize,
+        correction_factor: usize,
+        precompute: bool,
+    ) -> Result<()> {
+        let slots = self.bootstrap_slots(slots)?;
+        let level_budget = <[usize; 2]>::try_from(level_budget).map_err(|_| {
+            Error::BootstrapParams(format!(
+                "{} level budgets given, one for encoding and one for decoding are needed",
+                level_budget.len()
+            ))
+        })?;
+        if level_budget.contains(&0) {
+            return Err(Error::BootstrapParams(
+                "level budgets must be at least one".to_string(),
+            ));
+        }
+        let dimension1 = match dimension1 {
+            [] => [0, 0],
+            _ => <[usize; 2]>::try_from(dimension1).map_err(|_| {
+                Error::BootstrapParams(format!(
+                    "{} baby step dimensions given, one for encoding and one for decoding are needed",
+                    dimension1.len()
+                ))
+            })?,
+        };
+        let eval_mod = EvalModParams::new(params.rlwe_crypto_parameters.secret_key_distribution);
+        let mut setup = BootstrapSetup {
+            crypto_context: self.clone(),
+            params: params.clone(),
+            slots,
+            level_budget,
+            dimension1,
+            correction_factor: match correction_factor {
+                0 => DEFAULT_CORRECTION_FACTOR,
+                factor => factor,
+            },
+            transforms: None,
+            eval_mod,
+            coefficients: eval_mod.coefficients(),
+        };
+        if precompute {
+            setup.transforms = Some(setup.precompute(self, params)?);
+        }
+        let mut setups = BOOTSTRAP_SETUPS.write()?;
+        setups.retain(|s| !s.is_for(self, params, slots));
+        setups.push(Arc::new(setup));
+        Ok(())
+    }
+
+    /// Generate the rotation keys of the linear transforms, of summing the
+    /// unused slots and the conjugation key. Bootstrapping relinearizes with
+    /// the key of [`CryptoContext::eval_mult_key_gen`] too.
+    fn eval_bootstrap_key_gen_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        slots: usize,
+        rng: &mut impl CryptoRng,
+    ) -> Result<BTreeMap<u64, EvalKey>> {
+        let setup = self.bootstrap_setup(params, self.bootstrap_slots(slots)?)?;
+        let order = self.element_params.cyclotomic_order;
+        let rotations = setup
+            .transforms(self, params)?
+            .iter()
+            .flat_map(LinearTransform::rotation_indices)
+            .map(i64::from)
+            .chain(self.trace_rotations(setup.slots))
+            .collect::<BTreeSet<_>>();
+        let mut indices = rotations
+            .into_iter()
+            .map(|r| automorphism_index(r, order))
+            .collect::<Result<BTreeSet<_>>>()?;
+        indices.insert(self.conjugation_index());
+        indices.remove(&1);
+        let indices = indices.into_iter().collect::<Vec<_>>();
+        self.eval_automorphism_key_gen_with_rng(params, private_key, &indices, rng)
+    }
+
+    /// Raise a CKKS ciphertext at any level to the towers of `params` and
+    /// bring it back down by the levels bootstrapping consumes: one to scale
+    /// the message, the level budget, the Chebyshev series and the double
+    /// angles of EvalMod. A ciphertext at its first tower keeps its scaling
+    /// factor instead of the one of the correction factor. Uses the setup for
+    /// the slots of the ciphertext.
+    ///
+    /// More than one of `iterations` bootstraps the error of the previous
+    /// result again, scaled up by `2^precision`, and adds it back scaled
+    /// down, see Bae, Cheon, Cho, Kim and Kim, "META-BTS: Bootstrapping
+    /// Precision Beyond the Limit" (CCS 2022). `precision` is about the bits
+    /// a single bootstrap achieves, the error it scales up has to stay
+    /// within the range of the messages.
+    fn eval_bootstrap(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        iterations: usize,
+        precision: usize,
+    ) -> Result<Ciphertext> {
+        if ciphertext.encoding_type() != PlaintextEncodingsType::CkksPacked {
+            return Err(Error::CcParams(format!(
+                "cannot bootstrap a {} ciphertext",
+                ciphertext.encoding_type()
+            )));
+        }
+        if iterations == 0 || (iterations > 1 && precision == 0) {
+            return Err(Error::BootstrapParams(format!(
+                "cannot bootstrap in {iterations} iterations at {precision} bits of precision"
+            )));
+        }
+        let amplification = 2f64.powi(precision as i32);
+        let mut result = self.bootstrap_once(params, ciphertext, 1.0)?;
+        for _ in 1..iterations {
+            // Doubling scales the raw coefficients up with the slots, so the
+            // error fills the range of the messages
+            let mut error = self.ckks_sub(ciphertext, &result)?;
+            for _ in 0..precision {
+                error = self.eval_add(&error, &error)?;
+            }
+            let correction = self.bootstrap_once(params, &error, amplification)?;
+            result = self.ckks_add(&result, &correction)?;
+        }
+        Ok(result)
+    }
+}
+
+impl CryptoContext {
+    /// One bootstrap of `ciphertext` with the slots divided by `divisor`
+    fn bootstrap_once(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        divisor: f64,
+    ) -> Result<Ciphertext> {
+        let setup = self.bootstrap_setup(params, self.bootstrap_slots(ciphertext.slots())?)?;
+        let transforms = setup.transforms(self, params)?;
+        let [coeffs_to_slots, slots_to_coeffs] = transforms.as_ref();
+        let keys = self.rotation_keys(ciphertext)?;
+        let full = &params.dcrt_element_params;
+        let q0 = full
+            .params()
+            .front()
+            .ok_or(Error::DcrtElementParamsMismatch)?
+            .ciphertext_modulus
+            .get()
+            .to_primitive() as f64;
+        let correction = 2f64.powi(setup.correction_factor as i32);
+        if correction >= q0 {
+            return Err(Error::BootstrapParams(format!(
+                "a correction factor of {} leaves no room below the first tower",
+                setup.correction_factor
+            )));
+        }
+
+        let mut message = ciphertext.clone();
+        if tower_count(&message) > 1 {
+            level_reduce(&mut message, 2);
+            message = self.ckks_mult_const_to(&message, 1.0, 1, q0 / correction)?;
+        }
+        let message_scale = message.scaling_factor();
+        let mut raised = self.mod_raise(&message, full, q0)?;
+
+        // Fewer slots leave the message in a subring, which the trace over
+        // the automorphisms fixing it projects onto
+        let trace = self.trace_rotations(setup.slots);
+        for &rotation in &trace {
+            let rotated = self.rotate(&raised, rotation, &keys)?;
+            raised = self.eval_add(&raised, &rotated)?;
+        }
+        let projected = raised.scaling_factor() * (1 << trace.len()) as f64;
+        let raised = with_scaling_factor("eval_bootstrap", &raised, projected)?;
+
+        let encoded = self.eval_linear_transform(
+            &raised,
+            coeffs_to_slots,
+            setup.coeffs_to_slots_constant(),
+            &keys,
+        )?;
+        let conjugation = self.conjugation_index();
+        let key = keys.get(&conjugation).ok_or_else(|| {
+            Error::CcParams(format!(
+                "no conjugation key for key tag `{}`",
+                ciphertext.key_tag()
+            ))
+        })?;
+        let conjugated = self.automorphism(&encoded, conjugation, key)?;
+        let real = self.eval_add(&encoded, &conjugated)?;
+        let imaginary = self.times_i(&self.eval_sub(&encoded, &conjugated)?, true)?;
+
+        let real =
+            self.eval_mod_normalized(params, &real, &setup.eval_mod, &setup.coefficients, 1.0)?;
+        let imaginary = self.eval_mod_normalized(
+            params,
+            &imaginary,
+            &setup.eval_mod,
+            &setup.coefficients,
+            1.0,
+        )?;
+        let combined = self.ckks_add(&real, &self.times_i(&imaginary, false)?)?;
+        self.eval_linear_transform(
+            &combined,
+            slots_to_coeffs,
+            q0 / (2.0 * PI * message_scale * divisor),
+            &keys,
+        )
+    }
+
+    /// `slots` or the slots of the context for zero, a power of two
+    fn bootstrap_slots(&self, slots: usize) -> Result<usize> {
+        let slots = match slots {
+            0 => self.slots(),
+            slots => slots,
+        };
+        if !slots.is_power_of_two() || slots > self.element_params.cyclotomic_order / 4 {
+            return Err(Error::BootstrapParams(format!(
+                "{slots} slots is not a power of two up to {}",
+                self.element_params.cyclotomic_order / 4
+            )));
+        }
+        Ok(slots)
+    }
+
+    fn bootstrap_setup(
+        &self,
+        params: &CryptoParametersRns,
+        slots: usize,
+    ) -> Result<Arc<BootstrapSetup>> {
+        BOOTSTRAP_SETUPS
+            .read()?
+            .iter()
+            .find(|s| s.is_for(self, params, slots))
+            .cloned()
+            .ok_or_else(|| Error::BootstrapParams(format!("no bootstrap setup for {slots} slots")))
+    }
+
+    /// The rotations by `slots`, `2 slots`, ... up to the `m / 4` slots of
+    /// the ring, whose automorphisms fix the subring of `slots` slots
+    fn trace_rotations(&self, slots: usize) -> Vec<i64> {
+        let all = self.element_params.cyclotomic_order / 4;
+        std::iter::successors(Some(slots), |r| Some(r * 2))
+            .take_while(|&r| r < all)
+            .map(|r| r as i64)
+            .collect()
+    }
+
+    /// The ciphertext at its first tower over the towers of `params`, where
+    /// it decrypts to the message plus `q0 I`, scaled by `q0`
+    fn mod_raise(
+        &self,
+        ciphertext: &Ciphertext,
+        params: &DcrtElementParams,
+        q0: f64,
+    ) -> Result<Ciphertext> {
+        let elements = ciphertext
+            .elements()
+            .iter()
+            .map(|e| {
+                let mut e = e.clone();
+                if e.format() == PolynomialRingFormat::Evaluation {
+                    e.switch_format();
+                }
+                let mut raised = DcrtPoly::from_big_signed(params.clone(), &e.to_signed());
+                raised.switch_format();
+                raised
+            })
+            .collect();
+        let mut raised = Ciphertext::new(
+            self.clone(),
+            elements,
+            PlaintextEncodingsType::CkksPacked,
+            q0,
+        )?;
+        raised.set_key_tag(ciphertext.key_tag());
+        raised.set_slots(ciphertext.slots());
+        raised.set_noise_scale_degree(1);
+        Ok(raised)
+    }
+
+    /// The slots times `i`, or `-i` when `negate`, by multiplying with the
+    /// monomial `±X^(n/2)`
+    fn times_i(&self, ciphertext: &Ciphertext, negate: bool) -> Result<Ciphertext> {
+        let quarter = self.element_params.ring_dimension / 2;
+        let elements = ciphertext
+            .elements()
+            .iter()
+            .map(|e| {
+                let mut coefficients = vec![0; e.towers().first().map_or(0, |t| t.len())];
+                coefficients[quarter] = if negate { -1 } else { 1 };
+                let monomial =
+                    in_format_of(DcrtPoly::from_signed(e.params().clone(), &coefficients), e);
+                let mut e = e.clone();
+                e *= &monomial;
+                e
+            })
+            .collect();
+        let mut result = ciphertext.try_clone()?;
+        result.replace_elements(
+            "eval_bootstrap",
+            elements,
+            ciphertext.scaling_factor(),
+            &[ciphertext],
+        );
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::PlaintextEncodings;
+    use crate::pke::test_support::{RING_DIMENSION, ckks_params};
+    use crate::pke::{BootstrapEstimateParams, estimate_bootstrap, tower_count};
+    use rand::Rng;
+
+    /// Every slot of the ring
+    const SLOTS: usize = RING_DIMENSION / 2;
+
+    #[test]
+    fn bootstrap_round_trip() {
+        let estimate = estimate_bootstrap(&BootstrapEstimateParams {
+            slots: SLOTS,
+            level_budget: [2, 2],
+            levels_after_bootstrap: 2,
+            ..BootstrapEstimateParams::new(RING_DIMENSION)
+        })
+        .expect("estimate");
+        let ckks = ckks_params()
+            .multiplicative_depth(estimate.towers - 1)
+            .scaling_mod_size(50)
+            .first_mod_size(60)
+            .batch_size(SLOTS)
+            .build()
+            .expect("parameters");
+        ckks.eval_bootstrap_setup(&[2, 2], &[], SLOTS, 0, true)
+            .expect("setup");
+        let key_pair = ckks.key_gen().expect("keys");
+        ckks.eval_mult_key_gen(key_pair.private_key())
+            .expect("relinearization key");
+        ckks.eval_bootstrap_key_gen(key_pair.private_key(), SLOTS)
+            .expect("bootstrap keys");
+
+        let mut rng = rand::rng();
+        let values = (0..SLOTS)
+            .map(|_| rng.random_range(-1.0..1.0))
+            .collect::<Vec<f64>>();
+        let plaintext = ckks.encode(&values).expect("encode");
+        let ciphertext = ckks
+            .encrypt(key_pair.public_key(), &plaintext)
+            .expect("encrypt");
+        let cc = ckks.crypto_context();
+        let exhausted = cc
+            .level_reduce(&ciphertext, tower_count(&ciphertext) - 1)
+            .expect("drop to the first tower");
+        let refreshed = ckks.eval_bootstrap(&exhausted, 1, 0).expect("bootstrap");
+        // A ciphertext at its first tower skips the level scaling it down
+        assert!(tower_count(&refreshed) >= estimate.towers - estimate.levels_consumed);
+        let PlaintextEncodings::Ckks(decrypted) = ckks
+            .decrypt(key_pair.private_key(), &refreshed)
+            .expect("decrypt")
+        else {
+            panic!("a CKKS plaintext");
+        };
+        assert_eq!(decrypted.values().len(), SLOTS);
+        let error = decrypted
+            .values()
+            .iter()
+            .zip(values)
+            .map(|(got, want)| (got - want).abs())
+            .fold(0.0, f64::max);
+        // q0 / Δ = 2^10 leaves about 7 bits over every slot of the small ring
+        assert!(error < 1e-2, "error {error}");
+    }
+}
@@ -0,0 +1,199 @@
+//! Up front cost estimates for CKKS bootstrapping
+//!
+//! Bootstrapping keys easily reach hundreds of megabytes. [`estimate_bootstrap`]
+//! mirrors the layer collapsing and baby-step giant-step choices of the
+//! bootstrap setup so the key material and work per bootstrap can be checked
+//! before any key is generated. Rotation keys are counted once per distinct
+//! rotation, the same ones [`CkksRns::eval_bootstrap_key_gen`] generates.
+//!
+//! [`CkksRns::eval_bootstrap_key_gen`]: super::CkksRns::eval_bootstrap_key_gen
+
+use super::EvalModParams;
+use super::linear_transform::transform_steps;
+use super::utils::compute_num_large_digits;
+use crate::constants::SecretKeyDistribution;
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// The bootstrapping configuration to estimate
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BootstrapEstimateParams {
+    /// The ring dimension `N`
+    pub ring_dimension: usize,
+    /// The number of packed slots, a power of two up to `N / 2`
+    pub slots: usize,
+    /// Levels spent on CoeffToSlot and SlotToCoeff
+    pub level_budget: [usize; 2],
+    /// Baby-step dimensions for both transforms, `0` picks the default
+    pub dim1: [usize; 2],
+    /// Levels left for the application after bootstrapping
+    pub levels_after_bootstrap: usize,
+    /// Digits used by hybrid key switching, `0` picks the default
+    pub num_large_digits: usize,
+    /// The distribution of the secret key
+    pub secret_key_distribution: SecretKeyDistribution,
+}
+
+impl BootstrapEstimateParams {
+    /// Fully packed defaults for ring dimension `ring_dimension`
+    pub fn new(ring_dimension: usize) -> Self {
+        Self {
+            ring_dimension,
+            slots: ring_dimension / 2,
+            level_budget: [5, 4],
+            dim1: [0, 0],
+            levels_after_bootstrap: 10,
+            num_large_digits: 0,
+            secret_key_distribution: SecretKeyDistribution::UniformTernary,
+        }
+    }
+}
+
+/// Expected key material and work of one bootstrapping configuration
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BootstrapEstimate {
+    /// Towers of the ciphertext modulus the keys are generated for
+    pub towers: usize,
+    /// Extra towers of the key switching modulus
+    pub aux_towers: usize,
+    /// Digits used by hybrid key switching
+    pub num_large_digits: usize,
+    /// Rotation keys needed by the linear transforms
+    pub rotation_keys: usize,
+    /// All key switching keys, rotations plus conjugation and relinearization
+    pub key_switching_keys: usize,
+    /// The size of one key switching key
+    pub bytes_per_key: usize,
+    /// The size of all key switching keys
+    pub key_bytes: usize,
+    /// Rotations performed by one bootstrap
+    pub rotations: usize,
+    /// Plaintext multiplications performed by one bootstrap
+    pub plaintext_mults: usize,
+    /// Ciphertext multiplications performed by one bootstrap
+    pub ciphertext_mults: usize,
+    /// Levels consumed by one bootstrap
+    pub levels_consumed: usize,
+}
+
+/// Estimate key sizes and operation counts without generating anything
+pub fn estimate_bootstrap(params: &BootstrapEstimateParams) -> Result<BootstrapEstimate> {
+    let n = params.ring_dimension;
+    if n < 2 || !n.is_power_of_two() {
+        return Err(Error::BootstrapParams(format!(
+            "ring dimension {n} is not a power of two"
+        )));
+    }
+    if params.slots == 0 || !params.slots.is_power_of_two() || params.slots > n / 2 {
+        return Err(Error::BootstrapParams(format!(
+            "{} slots is not a power of two up to {}",
+            params.slots,
+            n / 2
+        )));
+    }
+    if params.level_budget.contains(&0) {
+        return Err(Error::BootstrapParams(
+            "level budgets must be at least one".to_string(),
+        ));
+    }
+
+    // Even a single slot needs one level for rescaling
+    let log_slots = params.slots.ilog2().max(1) as usize;
+    let mut rotation_indices = BTreeSet::new();
+    let mut rotations = 0;
+    let mut plaintext_mults = 0;
+    let mut transform_levels = 0;
+    // CoeffToSlot first, then SlotToCoeff
+    for (i, (budget, dim1)) in params.level_budget.into_iter().zip(params.dim1).enumerate() {
+        transform_levels += budget.min(log_slots);
+        let steps = transform_steps(params.slots, budget, dim1, i == 0);
+        rotation_indices.extend(steps.rotation_indices);
+        rotations += steps.rotations;
+        plaintext_mults += steps.diagonals;
+    }
+
+    // Sparse packing folds the unused slots with one rotation per doubling,
+    // by at least the slots so never one of the transforms
+    let sparse_folds = (n / 2 / params.slots).ilog2() as usize;
+    let rotation_keys = rotation_indices.len() + sparse_folds;
+    rotations += sparse_folds;
+
+    let eval_mod = EvalModParams::new(params.secret_key_distribution);
+    let chebyshev_levels = eval_mod.degree.next_power_of_two().ilog2() as usize + 1;
+    let chebyshev_mults = 2 * eval_mod.degree.isqrt() + chebyshev_levels;
+    // One more level brings the message to the correction factor before it
+    // is raised
+    let levels_consumed = 1 + transform_levels + eval_mod.depth();
+
+    let towers = levels_consumed + params.levels_after_bootstrap + 1;
+    let num_large_digits = compute_num_large_digits(params.num_large_digits, towers - 1);
+    let aux_towers = towers.div_ceil(num_large_digits);
+    let bytes_per_key = num_large_digits * 2 * (towers + aux_towers) * n * size_of::<u64>();
+    let key_switching_keys = rotation_keys + 2;
+
+    Ok(BootstrapEstimate {
+        towers,
+        aux_towers,
+        num_large_digits,
+        rotation_keys,
+        key_switching_keys,
+        bytes_per_key,
+        key_bytes: key_switching_keys * bytes_per_key,
+        rotations,
+        plaintext_mults,
+        ciphertext_mults: chebyshev_mults + eval_mod.double_angles,
+        levels_consumed,
+    })
+}
+
+/// FFT layers merged into each level, spread as evenly as the budget allows
+pub(super) fn collapse_layers(log_slots: usize, budget: usize) -> impl Iterator<Item = usize> {
+    let base = log_slots / budget;
+    let extra = log_slots % budget;
+    (0..budget).map(move |level| base + usize::from(level < extra))
+}
+
+/// Diagonals, baby steps and giant steps of a level merging `layers` layers
+pub(super) fn baby_giant_steps(layers: usize, dim1: usize) -> (usize, usize, usize) {
+    let diagonals = (1 << (layers + 1)) - 1;
+    let baby = if dim1 == 0 || dim1 > diagonals {
+        if diagonals > 7 {
+            1 << (layers / 2 + 2)
+        } else {
+            1 << (layers / 2 + 1)
+        }
+    } else {
+        dim1
+    };
+    let giant = (diagonals + 1).div_ceil(baby);
+    (diagonals, baby, giant)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pke::test_support::{RING_DIMENSION, ckks_context};
+
+    #[test]
+    fn rotation_keys_match_key_gen() {
+        let params = BootstrapEstimateParams {
+            slots: 8,
+            level_budget: [2, 2],
+            levels_after_bootstrap: 1,
+            ..BootstrapEstimateParams::new(RING_DIMENSION)
+        };
+        let estimate = estimate_bootstrap(&params).expect("estimate");
+        let ckks = ckks_context(estimate.towers - 1);
+        ckks.eval_bootstrap_setup(&params.level_budget, &[], params.slots, 0, false)
+            .expect("setup");
+        let key_pair = ckks.key_gen().expect("keys");
+        let keys = ckks
+            .eval_bootstrap_key_gen(key_pair.private_key(), params.slots)
+            .expect("bootstrap keys");
+        // The conjugation key comes with the rotations, the relinearization
+        // key from eval_mult_key_gen
+        assert_eq!(estimate.rotation_keys + 1, keys.len());
+        assert_eq!(estimate.key_switching_keys, keys.len() + 1);
+    }
+}
@@ -0,0 +1,1102 @@
+//! The CKKS scheme in RNS form
+//!
+//! [`CkksParamsGen`] picks a first tower bounding the decrypted values and
+//! one tower per multiplicative level close to the scaling factor, and the
+//! smallest secure ring. [`CkksRns`] then packs vectors of reals into the
+//! slots of a [`CkksPlaintext`] scaled by `Δ`, encrypts them without
+//! further scaling and decrypts `c0 + c1 s` divided by the scaling factor
+//! the ciphertext tracks. Results are approximate, the noise ends up in the
+//! low bits of the values.
+
+use crate::ActingPrimitive;
+use crate::arena::{ArenaConfig, ArenaKind};
+use crate::ciphertext::Ciphertext;
+use crate::constants::{
+    DistributionType, KeySwitchTechnique, MAX_MODULUS_SIZE, ScalingTechnique, SecurityLevel,
+};
+use crate::core::lattice::params::{DcrtElementParamsBuilder, LatticeParams};
+use crate::core::utils::{first_prime, previous_prime};
+use crate::encoding::{CkksPlaintext, EncodingParams, PlaintextEncodings, PlaintextParams};
+use crate::error::{Error, Result};
+use crate::evaluator::SlotEvaluator;
+use crate::pke::{
+    ApproximationPrecision, CcParams, CryptoContext, CryptoParametersRns, EvalKey, EvalModParams,
+    FheBase, Hybrid, KeyPair, LinearTransform, LinearTransformParams, NoiseEstimate, PrivateKey,
+    PublicKey, SignApproximation,
+};
+use rand::CryptoRng;
+use std::collections::BTreeMap;
+
+/// Generates CKKS parameters for a scaling factor and multiplicative depth
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CkksParamsGen {
+    /// The user facing settings
+    pub cc_params: CcParams,
+    /// The number of rescaled multiplications to support
+    pub multiplicative_depth: usize,
+    /// The bits of the scaling factor and of the towers rescaling drops
+    pub scaling_mod_size: usize,
+    /// The bits of the first tower, which bounds the decrypted values
+    pub first_mod_size: usize,
+    /// The number of slots, zero for half the ring dimension
+    pub batch_size: usize,
+    /// A fixed ring dimension instead of the smallest secure one
+    pub ring_dimension: Option<usize>,
+    /// How the scaling factor follows rescaling
+    pub scaling_technique: ScalingTechnique,
+    /// How relinearization and rotation keys switch secrets
+    pub key_switch_technique: KeySwitchTechnique,
+    /// The digits of hybrid key switching, zero to derive them from the
+    /// number of towers
+    pub num_large_digits: usize,
+}
+
+impl Default for CkksParamsGen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CkksParamsGen {
+    /// Parameters at depth zero with a 50 bit scaling factor and a 60 bit
+    /// first tower
+    pub fn new() -> Self {
+        Self {
+            cc_params: CcParams::default(),
+            multiplicative_depth: 0,
+            scaling_mod_size: 50,
+            first_mod_size: MAX_MODULUS_SIZE,
+            batch_size: 0,
+            ring_dimension: None,
+            scaling_technique: ScalingTechnique::default(),
+            key_switch_technique: KeySwitchTechnique::Hybrid,
+            num_large_digits: 0,
+        }
+    }
+
+    /// Set the user facing settings
+    pub fn cc_params(mut self, cc_params: CcParams) -> Self {
+        self.cc_params = cc_params;
+        self
+    }
+
+    /// Set the multiplicative depth
+    pub fn multiplicative_depth(mut self, multiplicative_depth: usize) -> Self {
+        self.multiplicative_depth = multiplicative_depth;
+        self
+    }
+
+    /// Set the bits of the scaling factor
+    pub fn scaling_mod_size(mut self, scaling_mod_size: usize) -> Self {
+        self.scaling_mod_size = scaling_mod_size;
+        self
+    }
+
+    /// Set the bits of the first tower
+    pub fn first_mod_size(mut self, first_mod_size: usize) -> Self {
+        self.first_mod_size = first_mod_size;
+        self
+    }
+
+    /// Set the number of slots
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Fix the ring dimension, required when the security level is not set
+    pub fn ring_dimension(mut self, ring_dimension: usize) -> Self {
+        self.ring_dimension = Some(ring_dimension);
+        self
+    }
+
+    /// Set the scaling technique
+    pub fn scaling_technique(mut self, scaling_technique: ScalingTechnique) -> Self {
+        self.scaling_technique = scaling_technique;
+        self
+    }
+
+    /// Set the key switching technique
+    pub fn key_switch_technique(mut self, technique: KeySwitchTechnique) -> Self {
+        self.key_switch_technique = technique;
+        self
+    }
+
+    /// Set the number of digits of hybrid key switching
+    pub fn num_large_digits(mut self, num_large_digits: usize) -> Self {
+        self.num_large_digits = num_large_digits;
+        self
+    }
+
+    /// Pick the ring dimension and towers and build the scheme
+    pub fn build(self) -> Result<CkksRns> {
+        if self.scaling_mod_size < 2
+            || self.scaling_mod_size >= MAX_MODULUS_SIZE
+            || self.first_mod_size <= self.scaling_mod_size
+            || self.first_mod_size > MAX_MODULUS_SIZE
+        {
+            return Err(Error::CcParams(format!(
+                "a {} bit scaling factor needs a larger first tower than {} bits and at most {MAX_MODULUS_SIZE}",
+                self.scaling_mod_size, self.first_mod_size
+            )));
+        }
+        if matches!(
+            self.scaling_technique,
+            ScalingTechnique::NoRescale | ScalingTechnique::Invalid
+        ) {
+            return Err(Error::CcParams(format!(
+                "scaling technique {} is not supported",
+                self.scaling_technique
+            )));
+        }
+        let rlwe = self.cc_params.rlwe_crypto_parameters()?;
+        let distribution = DistributionType::from(rlwe.secret_key_distribution);
+        let security_level = rlwe.security_level;
+        if self.key_switch_technique == KeySwitchTechnique::Bv && rlwe.digit_size == 0 {
+            return Err(Error::CcParams(
+                "BV key switching of CKKS ciphertexts needs digits smaller than a tower, set \
+                 the digit size"
+                    .to_string(),
+            ));
+        }
+        let tower_bits = std::iter::once(self.first_mod_size)
+            .chain(std::iter::repeat_n(
+                self.scaling_mod_size,
+                self.multiplicative_depth,
+            ))
+            .collect::<Vec<_>>();
+        let aux_bits = match self.key_switch_technique {
+            KeySwitchTechnique::Hybrid => Hybrid::aux_bits(
+                &tower_bits,
+                Hybrid::towers_per_digit(tower_bits.len(), self.num_large_digits),
+            ),
+            _ => 0,
+        };
+        // The keys of hybrid key switching are over `P Q`
+        let log_q = tower_bits.iter().sum::<usize>() + aux_bits;
+        let n = match self.ring_dimension {
+            Some(n) => {
+                if !n.is_power_of_two() || n < 4 {
+                    return Err(Error::CcParams(format!(
+                        "the ring dimension {n} is not a power of two of at least 4"
+                    )));
+                }
+                if security_level != SecurityLevel::HeStdNotSet
+                    && LatticeParams::find_max_q(distribution, security_level, n) < log_q
+                {
+                    return Err(Error::CcParams(format!(
+                        "ring dimension {n} does not reach {security_level} with a {log_q} bit modulus"
+                    )));
+                }
+                n
+            }
+            None => LatticeParams::min_ring_dimension(distribution, security_level, log_q)
+                .ok_or_else(|| {
+                    Error::CcParams(format!(
+                        "no ring dimension reaches {security_level} with a {log_q} bit modulus, set one explicitly"
+                    ))
+                })?
+                .max(2 * self.batch_size),
+        };
+        if self.batch_size > n / 2 || (self.batch_size != 0 && !self.batch_size.is_power_of_two()) {
+            return Err(Error::CcParams(format!(
+                "{} slots do not fit ring dimension {n}",
+                self.batch_size
+            )));
+        }
+
+        // The first tower, then one tower per level just below
+        // 2^scaling_mod_size, the largest at the back where rescaling starts
+        let order = 2 * n;
+        let no_prime = || {
+            Error::CcParams(format!(
+                "no {} bit towers for ring dimension {n}",
+                self.scaling_mod_size
+            ))
+        };
+        let mut scaling = Vec::with_capacity(self.multiplicative_depth);
+        let mut q = first_prime(self.scaling_mod_size, order).ok_or_else(no_prime)?;
+        for level in 0..self.multiplicative_depth {
+            if level > 0 {
+                q = previous_prime(q, order).ok_or_else(no_prime)?;
+            }
+            scaling.push(q);
+        }
+        let mut first = first_prime(self.first_mod_size, order).ok_or_else(no_prime)?;
+        while scaling.contains(&first) {
+            first = previous_prime(first, order).ok_or_else(no_prime)?;
+        }
+        let moduli = std::iter::once(first)
+            .chain(scaling.iter().rev().copied())
+            .map(|q| Option::from(q.to_odd()).expect("a prime is odd"))
+            .collect();
+        let dcrt_element_params = DcrtElementParamsBuilder::new(order)
+            .moduli(moduli)
+            .build()?;
+        let scaling_factor = match self.scaling_technique {
+            ScalingTechnique::FlexibleAuto | ScalingTechnique::FlexibleAutoExt => scaling
+                .first()
+                .map_or(2f64.powi(self.scaling_mod_size as i32), |q| {
+                    q.to_primitive() as f64
+                }),
+            _ => 2f64.powi(self.scaling_mod_size as i32),
+        };
+
+        let mut params = CryptoParametersRns {
+            rlwe_crypto_parameters: rlwe,
+            key_switch_technique: self.key_switch_technique,
+            scaling_technique: self.scaling_technique,
+            encryption_technique: Default::default(),
+            multiplication_technique: Default::default(),
+            aux_bits,
+            num_large_digits: self.num_large_digits,
+            extra_bits: 0,
+            dcrt_element_params,
+            bfv_multipliers: Default::default(),
+        };
+        params.add_multiparty_towers()?;
+        let mut crypto_context = CryptoContext::default();
+        crypto_context.element_params = params.dcrt_element_params.params()[0];
+        crypto_context.encoding_params = EncodingParams {
+            batch_size: self.batch_size,
+            ..Default::default()
+        };
+        Ok(CkksRns {
+            crypto_context,
+            params,
+            scaling_factor,
+        })
+    }
+}
+
+/// A CKKS instance with generated parameters
+#[derive(Clone, Debug, PartialEq)]
+pub struct CkksRns {
+    crypto_context: CryptoContext,
+    params: CryptoParametersRns,
+    scaling_factor: f64,
+}
+
+impl CkksRns {
+    /// The crypto context keys and ciphertexts belong to
+    pub fn crypto_context(&self) -> &CryptoContext {
+        &self.crypto_context
+    }
+
+    /// Cap the plaintexts and ciphertexts of the context, see
+    /// [`CryptoContext::with_arena`]
+    pub fn with_arena(mut self, config: ArenaConfig) -> Self {
+        self.crypto_context = self.crypto_context.with_arena(config);
+        self
+    }
+
+    /// The RNS parameters
+    pub fn crypto_parameters(&self) -> &CryptoParametersRns {
+        &self.params
+    }
+
+    /// The scaling factor `Δ` of fresh encryptions
+    pub fn scaling_factor(&self) -> f64 {
+        self.scaling_factor
+    }
+
+    /// The number of values a plaintext holds
+    pub fn slots(&self) -> usize {
+        match self.crypto_context.encoding_params.batch_size {
+            0 => self.crypto_context.element_params.ring_dimension / 2,
+            slots => slots,
+        }
+    }
+
+    /// A secret key and its public key
+    pub fn key_gen(&self) -> Result<KeyPair> {
+        self.crypto_context.key_gen(&self.params)
+    }
+
+    /// [`CkksRns::key_gen`] drawing randomness from `rng`
+    pub fn key_gen_with_rng(&self, rng: &mut impl CryptoRng) -> Result<KeyPair> {
+        self.crypto_context.key_gen_with_rng(&self.params, rng)
+    }
+
+    /// Encode up to [`CkksRns::slots`] `values` scaled by `Δ`
+    pub fn encode(&self, values: &[f64]) -> Result<PlaintextEncodings> {
+        let params = PlaintextParams {
+            scaling_factor: self.scaling_factor,
+            slots: self.slots(),
+            encoding_params: self.crypto_context.encoding_params,
+            ..Default::default()
+        };
+        let lease = self.crypto_context.lease(ArenaKind::Plaintext)?;
+        Ok(PlaintextEncodings::Ckks(CkksPlaintext::new(
+            values,
+            params,
+            self.crypto_context.element_params.cyclotomic_order,
+        )?)
+        .with_lease(lease))
+    }
+
+    /// Encrypt `plaintext` under `public_key` as `m + (-a s + e) u + e'`
+    pub fn encrypt(
+        &self,
+        public_key: &PublicKey,
+        plaintext: &PlaintextEncodings,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .encrypt(&self.params, public_key, plaintext)
+    }
+
+    /// [`CkksRns::encrypt`] drawing randomness from `rng`
+    pub fn encrypt_with_rng(
+        &self,
+        public_key: &PublicKey,
+        plaintext: &PlaintextEncodings,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .encrypt_with_rng(&self.params, public_key, plaintext, rng)
+    }
+
+    /// `ciphertext` with fresh randomness, see [`CryptoContext::rerandomize`]
+    pub fn rerandomize(
+        &self,
+        public_key: &PublicKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .rerandomize(&self.params, public_key, ciphertext)
+    }
+
+    /// [`CkksRns::rerandomize`] drawing randomness from `rng`
+    pub fn rerandomize_with_rng(
+        &self,
+        public_key: &PublicKey,
+        ciphertext: &Ciphertext,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .rerandomize_with_rng(&self.params, public_key, ciphertext, rng)
+    }
+
+    /// `ciphertext` with fresh randomness and flooded noise of at most
+    /// `noise_bits` bits, see [`CryptoContext::flood_noise`]
+    pub fn flood_noise(
+        &self,
+        public_key: &PublicKey,
+        ciphertext: &Ciphertext,
+        noise_bits: f64,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .flood_noise(&self.params, public_key, ciphertext, noise_bits)
+    }
+
+    /// [`CkksRns::flood_noise`] drawing randomness from `rng`
+    pub fn flood_noise_with_rng(
+        &self,
+        public_key: &PublicKey,
+        ciphertext: &Ciphertext,
+        noise_bits: f64,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.crypto_context.flood_noise_with_rng(
+            &self.params,
+            public_key,
+            ciphertext,
+            noise_bits,
+            rng,
+        )
+    }
+
+    /// A key re-encrypting from `old_key` to `new_public_key`, see
+    /// [`CryptoContext::re_key_gen`]
+    pub fn re_key_gen(&self, old_key: &PrivateKey, new_public_key: &PublicKey) -> Result<EvalKey> {
+        self.crypto_context
+            .re_key_gen(&self.params, old_key, new_public_key)
+    }
+
+    /// [`CkksRns::re_key_gen`] drawing randomness from `rng`
+    pub fn re_key_gen_with_rng(
+        &self,
+        old_key: &PrivateKey,
+        new_public_key: &PublicKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<EvalKey> {
+        self.crypto_context
+            .re_key_gen_with_rng(&self.params, old_key, new_public_key, rng)
+    }
+
+    /// Re-encrypt `ciphertext` with `re_key`, see [`CryptoContext::re_encrypt`]
+    pub fn re_encrypt(
+        &self,
+        ciphertext: &Ciphertext,
+        re_key: &EvalKey,
+        public_key: Option<&PublicKey>,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .re_encrypt(&self.params, ciphertext, re_key, public_key)
+    }
+
+    /// [`CkksRns::re_encrypt`] drawing randomness from `rng`
+    pub fn re_encrypt_with_rng(
+        &self,
+        ciphertext: &Ciphertext,
+        re_key: &EvalKey,
+        public_key: Option<&PublicKey>,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .re_encrypt_with_rng(&self.params, ciphertext, re_key, public_key, rng)
+    }
+
+    /// The key pair of the next party of the joint key `public_key`, see
+    /// [`CryptoContext::multiparty_key_gen`]
+    pub fn multiparty_key_gen(&self, public_key: &PublicKey) -> Result<KeyPair> {
+        self.crypto_context
+            .multiparty_key_gen(&self.params, public_key)
+    }
+
+    /// [`CkksRns::multiparty_key_gen`] drawing randomness from `rng`
+    pub fn multiparty_key_gen_with_rng(
+        &self,
+        public_key: &PublicKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<KeyPair> {
+        self.crypto_context
+            .multiparty_key_gen_with_rng(&self.params, public_key, rng)
+    }
+
+    /// The partial decryption of `ciphertext` by the lead party, see
+    /// [`CryptoContext::multiparty_decrypt_lead`]
+    pub fn multiparty_decrypt_lead(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .multiparty_decrypt_lead(&self.params, private_key, ciphertext)
+    }
+
+    /// [`CkksRns::multiparty_decrypt_lead`] drawing randomness from `rng`
+    pub fn multiparty_decrypt_lead_with_rng(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.crypto_context.multiparty_decrypt_lead_with_rng(
+            &self.params,
+            private_key,
+            ciphertext,
+            rng,
+        )
+    }
+
+    /// The partial decryption of `ciphertext` by another party, see
+    /// [`CryptoContext::multiparty_decrypt_main`]
+    pub fn multiparty_decrypt_main(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .multiparty_decrypt_main(&self.params, private_key, ciphertext)
+    }
+
+    /// [`CkksRns::multiparty_decrypt_main`] drawing randomness from `rng`
+    pub fn multiparty_decrypt_main_with_rng(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Ciphertext> {
+        self.crypto_context.multiparty_decrypt_main_with_rng(
+            &self.params,
+            private_key,
+            ciphertext,
+            rng,
+        )
+    }
+
+    /// Combine the partial decryptions of every party, see
+    /// [`CryptoContext::multiparty_decrypt_fusion`]
+    pub fn multiparty_decrypt_fusion(
+        &self,
+        partial_decryptions: &[Ciphertext],
+    ) -> Result<PlaintextEncodings> {
+        self.crypto_context
+            .multiparty_decrypt_fusion(partial_decryptions)
+    }
+
+    /// The relinearization key of `private_key`
+    pub fn eval_mult_key_gen(&self, private_key: &PrivateKey) -> Result<EvalKey> {
+        self.crypto_context
+            .eval_mult_key_gen(&self.params, private_key)
+    }
+
+    /// [`CkksRns::eval_mult_key_gen`] drawing randomness from `rng`
+    pub fn eval_mult_key_gen_with_rng(
+        &self,
+        private_key: &PrivateKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<EvalKey> {
+        self.crypto_context
+            .eval_mult_key_gen_with_rng(&self.params, private_key, rng)
+    }
+
+    /// The sum of two ciphertexts after
+    /// [`CryptoContext::adjust_levels_and_depth`]
+    pub fn eval_add(&self, a: &Ciphertext, b: &Ciphertext) -> Result<Ciphertext> {
+        let (a, b) = self
+            .crypto_context
+            .adjust_levels_and_depth(&self.params, a, b)?;
+        self.crypto_context.eval_add(&a, &b)
+    }
+
+    /// The difference `a - b` of two ciphertexts after
+    /// [`CryptoContext::adjust_levels_and_depth`]
+    pub fn eval_sub(&self, a: &Ciphertext, b: &Ciphertext) -> Result<Ciphertext> {
+        let (a, b) = self
+            .crypto_context
+            .adjust_levels_and_depth(&self.params, a, b)?;
+        self.crypto_context.eval_sub(&a, &b)
+    }
+
+    /// The relinearized product of two ciphertexts, see
+    /// [`CryptoContext::eval_mult`]
+    pub fn eval_mult(
+        &self,
+        a: &Ciphertext,
+        b: &Ciphertext,
+        relin_key: &EvalKey,
+    ) -> Result<Ciphertext> {
+        self.crypto_context.eval_mult(&self.params, a, b, relin_key)
+    }
+
+    /// The sum of a ciphertext and a plaintext after
+    /// [`CryptoContext::adjust_levels_and_depth_plaintext`]
+    pub fn eval_add_plaintext(
+        &self,
+        a: &Ciphertext,
+        plaintext: &PlaintextEncodings,
+    ) -> Result<Ciphertext> {
+        let a =
+            self.crypto_context
+                .adjust_levels_and_depth_plaintext(&self.params, a, plaintext)?;
+        self.crypto_context.eval_add_plaintext(&a, plaintext)
+    }
+
+    /// The difference of a ciphertext and a plaintext after
+    /// [`CryptoContext::adjust_levels_and_depth_plaintext`]
+    pub fn eval_sub_plaintext(
+        &self,
+        a: &Ciphertext,
+        plaintext: &PlaintextEncodings,
+    ) -> Result<Ciphertext> {
+        let a =
+            self.crypto_context
+                .adjust_levels_and_depth_plaintext(&self.params, a, plaintext)?;
+        self.crypto_context.eval_sub_plaintext(&a, plaintext)
+    }
+
+    /// The inner product of the first `batch_size` slots of two ciphertexts,
+    /// see [`CryptoContext::eval_inner_product`]
+    pub fn eval_inner_product(
+        &self,
+        a: &Ciphertext,
+        b: &Ciphertext,
+        batch_size: usize,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_inner_product(&self.params, a, b, batch_size)
+    }
+
+    /// A Chebyshev series on `[a, b]` evaluated on a ciphertext, see
+    /// [`CryptoContext::eval_chebyshev_series`]
+    pub fn eval_chebyshev_series(
+        &self,
+        ciphertext: &Ciphertext,
+        coeffs: &[f64],
+        a: f64,
+        b: f64,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_chebyshev_series(&self.params, ciphertext, coeffs, a, b)
+    }
+
+    /// The Chebyshev interpolant of `f` of `degree` on `[a, b]` evaluated on a
+    /// ciphertext, see [`CryptoContext::eval_chebyshev_function`]
+    pub fn eval_chebyshev_function<F>(
+        &self,
+        ciphertext: &Ciphertext,
+        f: F,
+        a: f64,
+        b: f64,
+        degree: usize,
+    ) -> Result<Ciphertext>
+    where
+        F: Fn(f64) -> f64,
+    {
+        self.crypto_context
+            .eval_chebyshev_function(&self.params, ciphertext, f, a, b, degree)
+    }
+
+    /// `1 / x` for slots in `[a, b]`, see [`CryptoContext::eval_divide`]
+    pub fn eval_divide(
+        &self,
+        ciphertext: &Ciphertext,
+        a: f64,
+        b: f64,
+        degree: usize,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_divide(&self.params, ciphertext, a, b, degree)
+    }
+
+    /// About `sign(x)` of the slots, see [`CryptoContext::eval_sign`]
+    pub fn eval_sign(
+        &self,
+        ciphertext: &Ciphertext,
+        approximation: SignApproximation,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_sign(&self.params, ciphertext, approximation)
+    }
+
+    /// About `1` where `a` exceeds `b` and `0` where it is below, see [`CryptoContext::eval_compare`]
+    pub fn eval_compare(
+        &self,
+        a: &Ciphertext,
+        b: &Ciphertext,
+        approximation: SignApproximation,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_compare(&self.params, a, b, approximation)
+    }
+
+    /// The slotwise maximum, see [`CryptoContext::eval_max`]
+    pub fn eval_max(
+        &self,
+        a: &Ciphertext,
+        b: &Ciphertext,
+        approximation: SignApproximation,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_max(&self.params, a, b, approximation)
+    }
+
+    /// The slotwise minimum, see [`CryptoContext::eval_min`]
+    pub fn eval_min(
+        &self,
+        a: &Ciphertext,
+        b: &Ciphertext,
+        approximation: SignApproximation,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_min(&self.params, a, b, approximation)
+    }
+
+    /// The logistic function of the slots in `[a, b]`, see [`CryptoContext::eval_logistic`]
+    pub fn eval_logistic(
+        &self,
+        ciphertext: &Ciphertext,
+        a: f64,
+        b: f64,
+        precision: ApproximationPrecision,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_logistic(&self.params, ciphertext, a, b, precision)
+    }
+
+    /// The sine of the slots in `[a, b]`, see [`CryptoContext::eval_sin`]
+    pub fn eval_sin(
+        &self,
+        ciphertext: &Ciphertext,
+        a: f64,
+        b: f64,
+        precision: ApproximationPrecision,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_sin(&self.params, ciphertext, a, b, precision)
+    }
+
+    /// The cosine of the slots in `[a, b]`, see [`CryptoContext::eval_cos`]
+    pub fn eval_cos(
+        &self,
+        ciphertext: &Ciphertext,
+        a: f64,
+        b: f64,
+        precision: ApproximationPrecision,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_cos(&self.params, ciphertext, a, b, precision)
+    }
+
+    /// The exponential of the slots in `[a, b]`, see [`CryptoContext::eval_exp`]
+    pub fn eval_exp(
+        &self,
+        ciphertext: &Ciphertext,
+        a: f64,
+        b: f64,
+        precision: ApproximationPrecision,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_exp(&self.params, ciphertext, a, b, precision)
+    }
+
+    /// The keys rotating the slots by each of `indices`, see
+    /// [`CryptoContext::eval_at_index_key_gen`]
+    pub fn eval_at_index_key_gen(
+        &self,
+        private_key: &PrivateKey,
+        indices: &[i32],
+    ) -> Result<BTreeMap<u64, EvalKey>> {
+        self.crypto_context
+            .eval_at_index_key_gen(&self.params, private_key, indices)
+    }
+
+    /// [`CkksRns::eval_at_index_key_gen`] drawing randomness from `rng`
+    pub fn eval_at_index_key_gen_with_rng(
+        &self,
+        private_key: &PrivateKey,
+        indices: &[i32],
+        rng: &mut impl CryptoRng,
+    ) -> Result<BTreeMap<u64, EvalKey>> {
+        self.crypto_context
+            .eval_at_index_key_gen_with_rng(&self.params, private_key, indices, rng)
+    }
+
+    /// The rotation keys summing every slot, see
+    /// [`CryptoContext::eval_sum_key_gen`]
+    pub fn eval_sum_key_gen(&self, private_key: &PrivateKey) -> Result<BTreeMap<u64, EvalKey>> {
+        self.crypto_context
+            .eval_sum_key_gen(&self.params, private_key)
+    }
+
+    /// [`CkksRns::eval_sum_key_gen`] drawing randomness from `rng`
+    pub fn eval_sum_key_gen_with_rng(
+        &self,
+        private_key: &PrivateKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<BTreeMap<u64, EvalKey>> {
+        self.crypto_context
+            .eval_sum_key_gen_with_rng(&self.params, private_key, rng)
+    }
+
+    /// The key conjugating the slots, see
+    /// [`CryptoContext::eval_conjugate_key_gen`]
+    pub fn eval_conjugate_key_gen(&self, private_key: &PrivateKey) -> Result<EvalKey> {
+        self.crypto_context
+            .eval_conjugate_key_gen(&self.params, private_key)
+    }
+
+    /// [`CkksRns::eval_conjugate_key_gen`] drawing randomness from `rng`
+    pub fn eval_conjugate_key_gen_with_rng(
+        &self,
+        private_key: &PrivateKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<EvalKey> {
+        self.crypto_context
+            .eval_conjugate_key_gen_with_rng(&self.params, private_key, rng)
+    }
+
+    /// A key switching from the secret of `old_key` to the secret of
+    /// `new_key`, see [`CryptoContext::key_switch_gen`]
+    pub fn key_switch_gen(&self, old_key: &PrivateKey, new_key: &PrivateKey) -> Result<EvalKey> {
+        self.crypto_context
+            .key_switch_gen(&self.params, old_key, new_key)
+    }
+
+    /// [`CkksRns::key_switch_gen`] drawing randomness from `rng`
+    pub fn key_switch_gen_with_rng(
+        &self,
+        old_key: &PrivateKey,
+        new_key: &PrivateKey,
+        rng: &mut impl CryptoRng,
+    ) -> Result<EvalKey> {
+        self.crypto_context
+            .key_switch_gen_with_rng(&self.params, old_key, new_key, rng)
+    }
+
+    /// Precompute CoeffToSlot, see
+    /// [`CryptoContext::eval_coeffs_to_slots_precompute`]
+    pub fn eval_coeffs_to_slots_precompute(
+        &self,
+        params: &LinearTransformParams,
+    ) -> Result<LinearTransform> {
+        self.crypto_context
+            .eval_coeffs_to_slots_precompute(&self.params, params)
+    }
+
+    /// Precompute SlotToCoeff, see
+    /// [`CryptoContext::eval_slots_to_coeffs_precompute`]
+    pub fn eval_slots_to_coeffs_precompute(
+        &self,
+        params: &LinearTransformParams,
+    ) -> Result<LinearTransform> {
+        self.crypto_context
+            .eval_slots_to_coeffs_precompute(&self.params, params)
+    }
+
+    /// Move the slots to the coefficients, see
+    /// [`CryptoContext::eval_coeffs_to_slots`]
+    pub fn eval_coeffs_to_slots(
+        &self,
+        transform: &LinearTransform,
+        ciphertext: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_coeffs_to_slots(transform, ciphertext)
+    }
+
+    /// Move the coefficients back to the slots, see
+    /// [`CryptoContext::eval_slots_to_coeffs`]
+    pub fn eval_slots_to_coeffs(
+        &self,
+        transform: &LinearTransform,
+        ciphertext: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_slots_to_coeffs(transform, ciphertext)
+    }
+
+    /// About the slots modulo one, see [`CryptoContext::eval_mod`]
+    pub fn eval_mod(
+        &self,
+        ciphertext: &Ciphertext,
+        eval_mod: &EvalModParams,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_mod(&self.params, ciphertext, eval_mod)
+    }
+
+    /// Prepare bootstrapping `slots` slots, see
+    /// [`FheBase::eval_bootstrap_setup`]
+    pub fn eval_bootstrap_setup(
+        &self,
+        level_budget: &[usize],
+        dimension1: &[usize],
+        slots: usize,
+        correction_factor: usize,
+        precompute: bool,
+    ) -> Result<()> {
+        self.crypto_context.eval_bootstrap_setup(
+            &self.params,
+            level_budget,
+            dimension1,
+            slots,
+            correction_factor,
+            precompute,
+        )
+    }
+
+    /// The rotation keys bootstrapping `slots` slots, see
+    /// [`FheBase::eval_bootstrap_key_gen`]
+    pub fn eval_bootstrap_key_gen(
+        &self,
+        private_key: &PrivateKey,
+        slots: usize,
+    ) -> Result<BTreeMap<u64, EvalKey>> {
+        self.crypto_context
+            .eval_bootstrap_key_gen(&self.params, private_key, slots)
+    }
+
+    /// [`CkksRns::eval_bootstrap_key_gen`] drawing randomness from `rng`
+    pub fn eval_bootstrap_key_gen_with_rng(
+        &self,
+        private_key: &PrivateKey,
+        slots: usize,
+        rng: &mut impl CryptoRng,
+    ) -> Result<BTreeMap<u64, EvalKey>> {
+        self.crypto_context
+            .eval_bootstrap_key_gen_with_rng(&self.params, private_key, slots, rng)
+    }
+
+    /// Refresh `ciphertext` to the levels bootstrapping leaves, see
+    /// [`FheBase::eval_bootstrap`]
+    pub fn eval_bootstrap(
+        &self,
+        ciphertext: &Ciphertext,
+        iterations: usize,
+        precision: usize,
+    ) -> Result<Ciphertext> {
+        self.crypto_context
+            .eval_bootstrap(&self.params, ciphertext, iterations, precision)
+    }
+
+    /// Decrypt `ciphertext` into approximate values, one per slot, flooding
+    /// the noise if the parameters ask to, see
+    /// [`CryptoContext::decrypt_with_params`]
+    pub fn decrypt(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<PlaintextEncodings> {
+        self.crypto_context
+            .decrypt_with_params(&self.params, private_key, ciphertext)
+    }
+
+    /// The noise of `ciphertext` without decrypting it, see
+    /// [`CryptoContext::estimate_noise`]
+    pub fn estimate_noise(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<NoiseEstimate> {
+        self.crypto_context
+            .estimate_noise(&self.params, private_key, ciphertext)
+    }
+
+    /// [`CkksRns::decrypt`] drawing the flooding noise from `rng`
+    pub fn decrypt_with_rng(
+        &self,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+        rng: &mut impl CryptoRng,
+    ) -> Result<PlaintextEncodings> {
+        self.crypto_context
+            .decrypt_with_params_with_rng(&self.params, private_key, ciphertext, rng)
+    }
+}
+
+/// The slot-wise helpers of [`crate::evaluator`] on CKKS ciphertexts.
+///
+/// Products of ciphertexts are relinearized with the keys stored under their
+/// key tag, see [`CkksRns::eval_mult_key_gen`]. Every product rescales right
+/// away and consumes one tower, operands at different levels are brought
+/// together before they are added.
+impl SlotEvaluator for CkksRns {
+    type Ciphertext = Ciphertext;
+    type Plaintext = PlaintextEncodings;
+
+    fn add(&self, a: &Ciphertext, b: &Ciphertext) -> Result<Ciphertext> {
+        self.crypto_context.ckks_add(a, b)
+    }
+
+    fn sub(&self, a: &Ciphertext, b: &Ciphertext) -> Result<Ciphertext> {
+        self.crypto_context.ckks_sub(a, b)
+    }
+
+    fn mul(&self, a: &Ciphertext, b: &Ciphertext) -> Result<Ciphertext> {
+        self.crypto_context.ckks_mult(&self.params, a, b)
+    }
+
+    fn mul_plain(&self, a: &Ciphertext, b: &PlaintextEncodings) -> Result<Ciphertext> {
+        let product = self.crypto_context.eval_mult_plaintext(a, b)?;
+        self.crypto_context.ckks_rescale(&product)
+    }
+
+    fn add_const(&self, a: &Ciphertext, c: f64) -> Result<Ciphertext> {
+        self.crypto_context.ckks_add_const(a, c)
+    }
+
+    fn mul_const(&self, a: &Ciphertext, c: f64) -> Result<Ciphertext> {
+        self.crypto_context.ckks_mult_const(a, c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pke::test_support::ckks_context;
+
+    const VALUES: [f64; 8] = [0.5, -1.25, 2.0, 3.75, -0.125, 1.0, -2.5, 0.0];
+
+    /// The decrypted slots of `ciphertext`
+    fn decrypt(ckks: &CkksRns, private_key: &PrivateKey, ciphertext: &Ciphertext) -> Vec<f64> {
+        let PlaintextEncodings::Ckks(plaintext) =
+            ckks.decrypt(private_key, ciphertext).expect("decrypt")
+        else {
+            panic!("a CKKS plaintext");
+        };
+        plaintext.values().to_vec()
+    }
+
+    fn assert_close(actual: &[f64], expected: &[f64], bound: f64) {
+        assert_eq!(actual.len(), expected.len());
+        for (i, (x, y)) in actual.iter().zip(expected).enumerate() {
+            assert!((x - y).abs() < bound, "slot {i}: {x} != {y} within {bound}");
+        }
+    }
+
+    #[test]
+    fn encryptions_decrypt_within_the_noise() {
+        for depth in [0, 2] {
+            let ckks = ckks_context(depth);
+            let keys = ckks.key_gen().expect("keys");
+            let plaintext = ckks.encode(&VALUES).expect("encode");
+            let ciphertext = ckks
+                .encrypt(keys.public_key(), &plaintext)
+                .expect("encrypt");
+            assert_eq!(ciphertext.scaling_factor(), ckks.scaling_factor());
+            assert_eq!(ckks.scaling_factor(), 2f64.powi(40));
+            assert_eq!(ckks.slots(), VALUES.len());
+            assert_close(
+                &decrypt(&ckks, keys.private_key(), &ciphertext),
+                &VALUES,
+                1e-6,
+            );
+
+            let sum = ckks.eval_add(&ciphertext, &ciphertext).expect("add");
+            let doubled = VALUES.map(|x| 2.0 * x);
+            assert_close(&decrypt(&ckks, keys.private_key(), &sum), &doubled, 1e-6);
+        }
+    }
+
+    #[test]
+    fn rotations_move_slots_within_the_noise() {
+        let ckks = ckks_context(1);
+        let keys = ckks.key_gen().expect("keys");
+        ckks.eval_at_index_key_gen(keys.private_key(), &[1, -2, 3])
+            .expect("rotation keys");
+        let ciphertext = ckks
+            .encrypt(keys.public_key(), &ckks.encode(&VALUES).expect("encode"))
+            .expect("encrypt");
+        let cc = ckks.crypto_context();
+        // `5` has no key of its own and is composed from the others
+        for index in [1, -2, 3, 5] {
+            let rotated = cc.eval_at_index(&ciphertext, index).expect("rotate");
+            let n = VALUES.len() as i32;
+            let expected = (0..n)
+                .map(|i| VALUES[(i + index).rem_euclid(n) as usize])
+                .collect::<Vec<_>>();
+            assert_close(
+                &decrypt(&ckks, keys.private_key(), &rotated),
+                &expected,
+                1e-4,
+            );
+        }
+    }
+
+    #[test]
+    fn rescaled_products_stay_within_the_noise() {
+        let ckks = ckks_context(2);
+        let keys = ckks.key_gen().expect("keys");
+        let relin_key = ckks
+            .eval_mult_key_gen(keys.private_key())
+            .expect("relinearization key");
+        let ciphertext = ckks
+            .encrypt(keys.public_key(), &ckks.encode(&VALUES).expect("encode"))
+            .expect("encrypt");
+        let cc = ckks.crypto_context();
+
+        let mut power = ciphertext.clone();
+        let mut expected = VALUES;
+        for level in 1..=2 {
+            // Plain products need both operands at the same level
+            let factor = cc
+                .level_reduce(&ciphertext, power.level())
+                .expect("level reduce");
+            let product = ckks
+                .eval_mult(&power, &factor, &relin_key)
+                .expect("multiply");
+            power = cc.mod_reduce(&product).expect("rescale");
+            assert_eq!(power.level(), level);
+            assert!(
+                (power.scaling_factor() / ckks.scaling_factor() - 1.0).abs() < 1e-3,
+                "scaling factor {} after rescaling",
+                power.scaling_factor()
+            );
+            expected = std::array::from_fn(|i| expected[i] * VALUES[i]);
+            assert_close(&decrypt(&ckks, keys.private_key(), &power), &expected, 1e-3);
+        }
+    }
+}
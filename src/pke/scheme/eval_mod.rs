@@ -0,0 +1,161 @@
+//! Homomorphic modular reduction for CKKS
+//!
+//! Slots `y = t + I` with a small `t` and an integer `I` bounded by `K` reduce
+//! to `t` through `sin(2 pi y) / (2 pi)`, which is close to `t` while `t` is
+//! small against one. The sine comes from a Chebyshev series of a cosine
+//! scaled down by `2^r`, brought back by `r` double angle formulas, so the
+//! series only has to follow a few periods. The arcsine correction extends
+//! the range of `t` the result holds for at the cost of two levels.
+
+use crate::ciphertext::Ciphertext;
+use crate::constants::SecretKeyDistribution;
+use crate::core::math::eval_chebyshev_coefficients;
+use crate::error::{Error, Result};
+use crate::pke::{CryptoContext, CryptoParametersRns};
+use std::f64::consts::PI;
+
+/// The bound on the integer parts for uniform ternary secrets
+const K_UNIFORM: f64 = 512.0;
+
+/// The bound on the integer parts for sparse ternary secrets
+const K_SPARSE: f64 = 28.0;
+
+/// Chebyshev degree and double angle iterations of the modular reduction
+pub(super) const EVAL_MOD_UNIFORM: (usize, usize) = (88, 6);
+pub(super) const EVAL_MOD_SPARSE: (usize, usize) = (44, 3);
+
+/// How [`CryptoContext::eval_mod`] approximates `x mod 1`.
+///
+/// The degree has to follow `K / 2^r` periods of the cosine, a larger bound
+/// needs a larger degree or more double angles. Consumes
+/// [`EvalModParams::depth`] levels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EvalModParams {
+    /// The bound `K` on the integer parts, the inputs lie in `[-K, K]`
+    pub bound: f64,
+    /// The degree of the Chebyshev series of the cosine
+    pub degree: usize,
+    /// The `r` double angle formulas turning the cosine into the sine
+    pub double_angles: usize,
+    /// Correct the sine with `arcsin(z) = z + z^3 / 6 + ...`
+    pub arcsine: bool,
+}
+
+impl Default for EvalModParams {
+    fn default() -> Self {
+        Self::new(SecretKeyDistribution::UniformTernary)
+    }
+}
+
+impl EvalModParams {
+    /// The parameters bootstrapping uses for secrets of `distribution`
+    pub fn new(distribution: SecretKeyDistribution) -> Self {
+        let ((degree, double_angles), bound) = match distribution {
+            SecretKeyDistribution::SparseTernary => (EVAL_MOD_SPARSE, K_SPARSE),
+            _ => (EVAL_MOD_UNIFORM, K_UNIFORM),
+        };
+        Self {
+            bound,
+            degree,
+            double_angles,
+            arcsine: false,
+        }
+    }
+
+    /// The levels the modular reduction consumes on inputs in `[-1, 1]`
+    pub fn depth(&self) -> usize {
+        self.degree.next_power_of_two().ilog2() as usize
+            + 1
+            + self.double_angles
+            + 2 * usize::from(self.arcsine)
+    }
+
+    /// The Chebyshev series of `cos(2 pi (K x - 1/4) / 2^r)` on `[-1, 1]`
+    pub(super) fn coefficients(&self) -> Vec<f64> {
+        let period = 2f64.powi(self.double_angles as i32);
+        let bound = self.bound;
+        eval_chebyshev_coefficients(
+            |x| (2.0 * PI * (bound * x - 0.25) / period).cos(),
+            -1.0,
+            1.0,
+            self.degree,
+        )
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !self.bound.is_finite() || self.bound <= 0.0 || self.degree == 0 {
+            return Err(Error::CcParams(format!(
+                "cannot reduce modulo one with a bound of {} and degree {}",
+                self.bound, self.degree
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl CryptoContext {
+    /// About `x mod 1`, centered around zero, for CKKS slots `x` within
+    /// `1/4` or so of an integer in `[-K, K]`, as `sin(2 pi x) / (2 pi)`
+    /// or its arcsine. Consumes [`EvalModParams::depth`] levels plus one to
+    /// map `[-K, K]` onto `[-1, 1]`, the division by `2 pi` only changes the
+    /// scaling factor.
+    pub fn eval_mod(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        eval_mod: &EvalModParams,
+    ) -> Result<Ciphertext> {
+        eval_mod.validate()?;
+        let coefficients = eval_mod.coefficients();
+        let sine =
+            self.eval_mod_normalized(params, ciphertext, eval_mod, &coefficients, eval_mod.bound)?;
+        with_scaling_factor("eval_mod", &sine, sine.scaling_factor() * 2.0 * PI)
+    }
+
+    /// `2 pi (K x mod 1)` for slots `x` in `[-1, 1]` after dividing them by
+    /// `range`, as `sin(2 pi K x)` from the cosine series of `coefficients`
+    /// and the double angle formula `cos(2 a) = 2 cos(a)^2 - 1`, corrected by
+    /// the arcsine if asked
+    pub(super) fn eval_mod_normalized(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        eval_mod: &EvalModParams,
+        coefficients: &[f64],
+        range: f64,
+    ) -> Result<Ciphertext> {
+        let mut cosine =
+            self.eval_chebyshev_series(params, ciphertext, coefficients, -range, range)?;
+        for _ in 0..eval_mod.double_angles {
+            let square = self.ckks_mult(params, &cosine, &cosine)?;
+            cosine = self.ckks_add_const(&self.eval_add(&square, &square)?, -1.0)?;
+        }
+        if !eval_mod.arcsine {
+            return Ok(cosine);
+        }
+        // z (1 + z^2 / 6), dividing by six through the scaling factor
+        let square = self.ckks_mult(params, &cosine, &cosine)?;
+        let factor = self.ckks_add_const(
+            &with_scaling_factor("eval_mod", &square, square.scaling_factor() * 6.0)?,
+            1.0,
+        )?;
+        self.ckks_mult(params, &cosine, &factor)
+    }
+}
+
+/// The ciphertext reinterpreted at `scaling_factor` as the result of `op`,
+/// multiplying its slots by the ratio of the scaling factors
+pub(super) fn with_scaling_factor(
+    op: &str,
+    ciphertext: &Ciphertext,
+    scaling_factor: f64,
+) -> Result<Ciphertext> {
+    let mut result = ciphertext.try_clone()?;
+    result.replace_elements(
+        op,
+        ciphertext.elements().to_vec(),
+        scaling_factor,
+        &[ciphertext],
+    );
+    Ok(result)
+}
@@ -0,0 +1,600 @@
+//! CoeffToSlot and SlotToCoeff
+//!
+//! The decoding matrix of CKKS is [`fft_special`], `log2(slots)` butterfly
+//! layers with two nonzero diagonals each after a bit reversal. SlotToCoeff
+//! applies the layers without the bit reversal and CoeffToSlot their inverses
+//! in reverse, so the coefficients sit in bit reversed slots in between. The
+//! layers are merged into the levels of a level budget, each level being
+//! evaluated with baby-step giant-step rotations on plaintext diagonals
+//! encoded up front, see [`estimate_bootstrap`] for the costs.
+//!
+//! [`fft_special`]: crate::core::math::fft_special
+//! [`estimate_bootstrap`]: super::estimate_bootstrap
+
+use super::bootstrap_estimate::{baby_giant_steps, collapse_layers};
+use crate::ActingPrimitive;
+use crate::ciphertext::Ciphertext;
+use crate::constants::PlaintextEncodingsType;
+use crate::crypto_object::CryptoObject;
+use crate::encoding::{CkksPlaintext, PlaintextEncodings, PlaintextParams};
+use crate::error::{Error, Result};
+use crate::pke::key_switch;
+use crate::pke::polynomial::last_modulus;
+use crate::pke::{CryptoContext, CryptoParametersRns, EvalKey, automorphism_index, tower_count};
+use num::complex::Complex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::f64::consts::PI;
+
+/// The largest scale a diagonal is encoded with, below the coefficient bound
+/// of [`CkksPlaintext::new`]
+const MAX_DIAGONAL_SCALE: f64 = (1u64 << 60) as f64;
+
+/// A matrix over the slots by its nonzero diagonals, `M[i][i + k]` being
+/// `diagonals[k][i]` with the indices modulo the slots
+type Diagonals = BTreeMap<i64, Vec<Complex<f64>>>;
+
+/// What [`CryptoContext::eval_coeffs_to_slots_precompute`] and
+/// [`CryptoContext::eval_slots_to_coeffs_precompute`] prepare for
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LinearTransformParams {
+    /// The number of slots, a power of two up to a quarter of the cyclotomic
+    /// order
+    pub slots: usize,
+    /// The levels the transform consumes, at least one
+    pub level_budget: usize,
+    /// The baby steps per level, zero for the default
+    pub dimension1: usize,
+    /// The towers of the input ciphertexts
+    pub towers: usize,
+    /// The scaling factor of the input ciphertexts
+    pub scaling_factor: f64,
+    /// The constant the result is multiplied by
+    pub constant: f64,
+}
+
+/// CoeffToSlot or SlotToCoeff with its plaintext diagonals encoded
+#[derive(Clone, Debug)]
+pub struct LinearTransform {
+    coeffs_to_slots: bool,
+    slots: usize,
+    constant: f64,
+    levels: Vec<TransformLevel>,
+}
+
+/// The layers merged into one level, by giant step the baby steps and the
+/// diagonals encoded at `scale` and rotated back by the giant step
+#[derive(Clone, Debug)]
+struct TransformLevel {
+    stride: i64,
+    scale: f64,
+    giant_steps: BTreeMap<i64, Vec<(i64, PlaintextEncodings)>>,
+}
+
+impl LinearTransform {
+    /// The number of slots transformed
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// The levels consumed
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// The rotations to generate keys for with
+    /// [`CryptoContext::eval_at_index_key_gen`]
+    pub fn rotation_indices(&self) -> Vec<i32> {
+        let slots = self.slots as i64;
+        self.levels
+            .iter()
+            .flat_map(|level| {
+                level.giant_steps.iter().flat_map(|(&giant, babies)| {
+                    std::iter::once(giant)
+                        .chain(babies.iter().map(|&(b, _)| b))
+                        .map(|step| (step * level.stride).rem_euclid(slots))
+                })
+            })
+            .filter(|&r| r != 0)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|r| r as i32)
+            .collect()
+    }
+}
+
+impl CryptoContext {
+    /// Precompute CoeffToSlot, the inverse of decoding: the slots of a
+    /// ciphertext become the coefficients `m_j + i m_(j + n / 2)` of its
+    /// message, in bit reversed order and times `params.constant`.
+    pub fn eval_coeffs_to_slots_precompute(
+        &self,
+        crypto_params: &CryptoParametersRns,
+        params: &LinearTransformParams,
+    ) -> Result<LinearTransform> {
+        self.linear_transform_precompute(crypto_params, params, true)
+    }
+
+    /// Precompute SlotToCoeff, decoding: slots holding the coefficients in
+    /// bit reversed order like CoeffToSlot leaves them become the slots of
+    /// the message, times `params.constant`.
+    pub fn eval_slots_to_coeffs_precompute(
+        &self,
+        crypto_params: &CryptoParametersRns,
+        params: &LinearTransformParams,
+    ) -> Result<LinearTransform> {
+        self.linear_transform_precompute(crypto_params, params, false)
+    }
+
+    /// Apply a CoeffToSlot transform with the rotation keys of the key tag
+    /// of `ciphertext`, consuming [`LinearTransform::levels`] levels
+    pub fn eval_coeffs_to_slots(
+        &self,
+        transform: &LinearTransform,
+        ciphertext: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        if !transform.coeffs_to_slots {
+            return Err(Error::CcParams(
+                "a SlotToCoeff transform cannot move coefficients to slots".to_string(),
+            ));
+        }
+        let keys = self.rotation_keys(ciphertext)?;
+        self.eval_linear_transform(ciphertext, transform, transform.constant, &keys)
+    }
+
+    /// Apply a SlotToCoeff transform with the rotation keys of the key tag
+    /// of `ciphertext`, consuming [`LinearTransform::levels`] levels
+    pub fn eval_slots_to_coeffs(
+        &self,
+        transform: &LinearTransform,
+        ciphertext: &Ciphertext,
+    ) -> Result<Ciphertext> {
+        if transform.coeffs_to_slots {
+            return Err(Error::CcParams(
+                "a CoeffToSlot transform cannot move slots to coefficients".to_string(),
+            ));
+        }
+        let keys = self.rotation_keys(ciphertext)?;
+        self.eval_linear_transform(ciphertext, transform, transform.constant, &keys)
+    }
+
+    /// The automorphism keys of the key tag of `ciphertext` for this context
+    pub(crate) fn rotation_keys(&self, ciphertext: &Ciphertext) -> Result<BTreeMap<u64, EvalKey>> {
+        let mut keys = Self::get_eval_automorphism_keys(ciphertext.key_tag())?.unwrap_or_default();
+        keys.retain(|_, k| k.get_crypto_context() == self);
+        Ok(keys)
+    }
+
+    /// Rotate the slots left by `rotation` with the automorphism key of
+    /// exactly that rotation among `keys`
+    pub(crate) fn rotate(
+        &self,
+        ciphertext: &Ciphertext,
+        rotation: i64,
+        keys: &BTreeMap<u64, EvalKey>,
+    ) -> Result<Ciphertext> {
+        match self.rotation_key(ciphertext, rotation, keys)? {
+            Some((index, key)) => self.automorphism(ciphertext, index, key),
+            None => ciphertext.try_clone(),
+        }
+    }
+
+    /// The automorphism index and key among `keys` rotating the slots left
+    /// by `rotation`, none for the identity
+    fn rotation_key<'a>(
+        &self,
+        ciphertext: &Ciphertext,
+        rotation: i64,
+        keys: &'a BTreeMap<u64, EvalKey>,
+    ) -> Result<Option<(u64, &'a EvalKey)>> {
+        let index = automorphism_index(rotation, self.element_params.cyclotomic_order)?;
+        if index == 1 {
+            return Ok(None);
+        }
+        let key = keys.get(&index).ok_or_else(|| {
+            Error::CcParams(format!(
+                "no automorphism key for index {index} and key tag `{}`",
+                ciphertext.key_tag()
+            ))
+        })?;
+        Ok(Some((index, key)))
+    }
+
+    /// Merge the layers and encode the diagonals.
+    ///
+    /// The diagonals of all levels share one scale chosen so a ciphertext
+    /// with the towers and scaling factor of `params` ends up at the
+    /// scaling factor of its last tower, the constant being folded into the
+    /// scaling factors rather than into the diagonals.
+    fn linear_transform_precompute(
+        &self,
+        crypto_params: &CryptoParametersRns,
+        params: &LinearTransformParams,
+        coeffs_to_slots: bool,
+    ) -> Result<LinearTransform> {
+        let slots = params.slots;
+        if !slots.is_power_of_two() || slots > self.element_params.cyclotomic_order / 4 {
+            return Err(Error::CcParams(format!(
+                "{slots} slots is not a power of two up to {}",
+                self.element_params.cyclotomic_order / 4
+            )));
+        }
+        if params.level_budget == 0
+            || !params.constant.is_normal()
+            || !params.scaling_factor.is_normal()
+            || params.scaling_factor.is_sign_negative()
+        {
+            return Err(Error::CcParams(
+                "a linear transform needs a level and a nonzero constant and scaling factor"
+                    .to_string(),
+            ));
+        }
+        let moduli = crypto_params
+            .dcrt_element_params
+            .params()
+            .iter()
+            .take(params.towers)
+            .map(|p| p.ciphertext_modulus.get().to_primitive() as f64)
+            .rev()
+            .collect::<Vec<_>>();
+        let merged = merged_layers(
+            slots,
+            params.level_budget,
+            params.dimension1,
+            coeffs_to_slots,
+        );
+        if moduli.len() <= merged.len() {
+            return Err(Error::CcParams(format!(
+                "{} towers are too few for {} levels of a linear transform",
+                params.towers,
+                merged.len()
+            )));
+        }
+        // Each level multiplies the scaling factor by its scale over the
+        // dropped tower, which the constant brings back to the last tower
+        let log_gain = moduli
+            .iter()
+            .take(merged.len())
+            .map(|q| q.ln())
+            .sum::<f64>()
+            + moduli[0].ln()
+            - params.scaling_factor.ln();
+        let precision = ((params.constant.abs().ln() + log_gain) / merged.len() as f64).exp();
+
+        let levels = merged
+            .into_iter()
+            .map(|(stride, baby_steps, diagonals)| {
+                let largest = diagonals
+                    .values()
+                    .flatten()
+                    .map(|v| v.norm())
+                    .fold(f64::MIN_POSITIVE, f64::max);
+                let scale = precision.min(MAX_DIAGONAL_SCALE / largest);
+                self.encode_level(slots, stride, baby_steps, scale, &diagonals)
+            })
+            .collect::<Result<_>>()?;
+        Ok(LinearTransform {
+            coeffs_to_slots,
+            slots,
+            constant: params.constant,
+            levels,
+        })
+    }
+
+    /// The diagonals grouped into giant steps, each rotated right by its
+    /// giant step, which the rotation of the partial sum undoes
+    fn encode_level(
+        &self,
+        slots: usize,
+        stride: usize,
+        baby_steps: usize,
+        scale: f64,
+        diagonals: &Diagonals,
+    ) -> Result<TransformLevel> {
+        let (n, stride, baby) = (slots as i64, stride as i64, baby_steps.max(1) as i64);
+        let params = PlaintextParams {
+            scaling_factor: scale,
+            slots,
+            encoding_params: self.encoding_params,
+            ..Default::default()
+        };
+        let first = diagonals.keys().next().map_or(0, |&offset| offset / stride);
+        let mut giant_steps = BTreeMap::<i64, Vec<_>>::new();
+        for (&offset, diagonal) in diagonals {
+            let k = offset / stride;
+            let giant = giant_step(first, k, baby);
+            let shift = giant * stride;
+            let values = (0..n)
+                .map(|i| diagonal[(i - shift).rem_euclid(n) as usize])
+                .collect::<Vec<_>>();
+            let plaintext = PlaintextEncodings::Ckks(CkksPlaintext::from_complex(
+                &values,
+                params,
+                self.element_params.cyclotomic_order,
+            )?);
+            giant_steps
+                .entry(giant)
+                .or_default()
+                .push((k - giant, plaintext));
+        }
+        Ok(TransformLevel {
+            stride,
+            scale,
+            giant_steps,
+        })
+    }
+
+    /// `constant` times the transform applied to the slots, one level each,
+    /// ending up at the scaling factor of the last tower of `ciphertext`
+    pub(crate) fn eval_linear_transform(
+        &self,
+        ciphertext: &Ciphertext,
+        transform: &LinearTransform,
+        constant: f64,
+        keys: &BTreeMap<u64, EvalKey>,
+    ) -> Result<Ciphertext> {
+        if ciphertext.encoding_type() != PlaintextEncodingsType::CkksPacked {
+            return Err(Error::CcParams(format!(
+                "cannot transform the slots of a {} ciphertext",
+                ciphertext.encoding_type()
+            )));
+        }
+        if tower_count(ciphertext) <= transform.levels() {
+            return Err(Error::CcParams(format!(
+                "{} towers are too few for {} levels of a linear transform",
+                tower_count(ciphertext),
+                transform.levels()
+            )));
+        }
+        let target = last_modulus(ciphertext)?;
+        let mut result = ciphertext.try_clone()?;
+        let mut remaining = constant;
+        for (i, level) in transform.levels.iter().enumerate() {
+            let (scaling_factor, q) = (result.scaling_factor(), last_modulus(&result)?);
+            result = self.eval_transform_level(&result, level, transform.slots, keys)?;
+            // The share of the constant bringing the scaling factor to target
+            let share = if i + 1 == transform.levels() {
+                remaining
+            } else {
+                scaling_factor * level.scale / (q * target)
+            };
+            remaining /= share;
+            let (elements, rescaled) =
+                (result.elements().to_vec(), result.scaling_factor() / share);
+            result.replace_elements("linear_transform", elements, rescaled, &[ciphertext]);
+        }
+        Ok(result)
+    }
+
+    /// One merged level, rescaled
+    fn eval_transform_level(
+        &self,
+        ciphertext: &Ciphertext,
+        level: &TransformLevel,
+        slots: usize,
+        keys: &BTreeMap<u64, EvalKey>,
+    ) -> Result<Ciphertext> {
+        let n = slots as i64;
+        let steps = level
+            .giant_steps
+            .values()
+            .flatten()
+            .map(|&(b, _)| b)
+            .collect::<BTreeSet<_>>();
+        // The baby steps rotate the same ciphertext, so they share the
+        // decomposition of its `c1`
+        let precompute = match (ciphertext.elements(), keys.values().next()) {
+            ([_, c1], Some(key)) if steps.len() > 1 => Some(key_switch::precompute(c1, key)?),
+            _ => None,
+        };
+        let babies = steps
+            .into_iter()
+            .map(|b| {
+                let rotation = (b * level.stride).rem_euclid(n);
+                let rotated = match (self.rotation_key(ciphertext, rotation, keys)?, &precompute) {
+                    (Some((index, key)), Some(precompute)) => {
+                        self.automorphism_precomputed(ciphertext, index, key, precompute)?
+                    }
+                    _ => self.rotate(ciphertext, rotation, keys)?,
+                };
+                Ok((b, rotated))
+            })
+            .collect::<Result<BTreeMap<_, _>>>()?;
+
+        let mut sum: Option<Ciphertext> = None;
+        for (&giant, steps) in &level.giant_steps {
+            let mut inner: Option<Ciphertext> = None;
+            for (b, plaintext) in steps {
+                let term = self.eval_mult_plaintext(&babies[b], plaintext)?;
+                inner = Some(match inner {
+                    Some(inner) => self.eval_add(&inner, &term)?,
+                    None => term,
+                });
+            }
+            if let Some(inner) = inner {
+                let rotated = self.rotate(&inner, (giant * level.stride).rem_euclid(n), keys)?;
+                sum = Some(match sum {
+                    Some(sum) => self.eval_add(&sum, &rotated)?,
+                    None => rotated,
+                });
+            }
+        }
+        let sum = sum.ok_or_else(|| {
+            Error::CcParams("a linear transform level without diagonals".to_string())
+        })?;
+        self.ckks_rescale(&sum)
+    }
+}
+
+/// The halves of the layers in the order they apply merged into at most
+/// `budget` levels, with the number of layers each level counts for
+fn merged_halves(slots: usize, budget: usize, coeffs_to_slots: bool) -> Vec<(usize, Vec<usize>)> {
+    // CoeffToSlot undoes the widest butterflies first
+    let mut halves = (0..slots.ilog2()).map(|s| 1 << s).collect::<Vec<usize>>();
+    if coeffs_to_slots {
+        halves.reverse();
+    }
+    // Even a single slot needs one level for rescaling
+    let log_slots = halves.len().max(1);
+    let mut layers = halves.into_iter();
+    collapse_layers(log_slots, budget.min(log_slots))
+        .map(|count| (count, layers.by_ref().take(count).collect()))
+        .collect()
+}
+
+/// The layers in the order they apply merged into at most `budget` levels,
+/// each with its stride, baby steps and diagonals
+fn merged_layers(
+    slots: usize,
+    budget: usize,
+    dimension1: usize,
+    coeffs_to_slots: bool,
+) -> Vec<(usize, usize, Diagonals)> {
+    merged_halves(slots, budget, coeffs_to_slots)
+        .into_iter()
+        .map(|(count, halves)| {
+            let identity = Diagonals::from([(0, vec![Complex::ONE; slots])]);
+            let matrix = halves.iter().fold(identity, |matrix, &half| {
+                product(slots, &butterflies(slots, half, coeffs_to_slots), &matrix)
+            });
+            (
+                level_stride(slots, &halves),
+                baby_giant_steps(count, dimension1).1,
+                matrix,
+            )
+        })
+        .collect()
+}
+
+/// The rotations and plaintext multiplications of a transform, read off the
+/// offsets of its diagonals without computing them
+#[derive(Clone, Debug, Default)]
+pub(super) struct TransformSteps {
+    /// The nonzero rotations keys are generated for, as
+    /// [`LinearTransform::rotation_indices`] lists them
+    pub(super) rotation_indices: BTreeSet<i64>,
+    /// Rotations performed by one application
+    pub(super) rotations: usize,
+    /// Plaintext diagonals multiplied by one application
+    pub(super) diagonals: usize,
+}
+
+/// The steps of the transform [`CryptoContext::eval_coeffs_to_slots_precompute`]
+/// or [`CryptoContext::eval_slots_to_coeffs_precompute`] would prepare
+pub(super) fn transform_steps(
+    slots: usize,
+    budget: usize,
+    dimension1: usize,
+    coeffs_to_slots: bool,
+) -> TransformSteps {
+    let n = slots as i64;
+    let mut steps = TransformSteps::default();
+    for (count, halves) in merged_halves(slots, budget, coeffs_to_slots) {
+        // The product of the butterflies has a diagonal for every sum of
+        // their offsets, whatever its values
+        let offsets = halves.iter().fold(BTreeSet::from([0]), |offsets, &half| {
+            let half = half as i64;
+            offsets
+                .iter()
+                .flat_map(|&offset| [offset - half, offset, offset + half])
+                .map(|offset| centered(offset, slots))
+                .collect()
+        });
+        let stride = level_stride(slots, &halves) as i64;
+        let baby = baby_giant_steps(count, dimension1).1.max(1) as i64;
+        let first = offsets.first().map_or(0, |&offset| offset / stride);
+        let (mut giants, mut babies) = (BTreeSet::new(), BTreeSet::new());
+        for &offset in &offsets {
+            let k = offset / stride;
+            let giant = giant_step(first, k, baby);
+            giants.insert(giant);
+            babies.insert(k - giant);
+        }
+        for level_steps in [giants, babies] {
+            let rotations = level_steps
+                .into_iter()
+                .map(|step| (step * stride).rem_euclid(n))
+                .filter(|&r| r != 0)
+                .collect::<Vec<_>>();
+            steps.rotations += rotations.len();
+            steps.rotation_indices.extend(rotations);
+        }
+        steps.diagonals += offsets.len();
+    }
+    steps
+}
+
+/// The smallest butterfly of a level, at most half the slots
+fn level_stride(slots: usize, halves: &[usize]) -> usize {
+    halves
+        .iter()
+        .copied()
+        .fold(slots, usize::min)
+        .min(slots.max(2) / 2)
+        .max(1)
+}
+
+/// The giant step of the diagonal `k` strides from the first one at
+/// `first`, the baby step being the rest
+fn giant_step(first: i64, k: i64, baby: i64) -> i64 {
+    first + (k - first) / baby * baby
+}
+
+/// The butterflies of the layer of [`fft_special`] combining slots `half`
+/// apart, or of its inverse without the division by two
+///
+/// [`fft_special`]: crate::core::math::fft_special
+fn butterflies(slots: usize, half: usize, inverse: bool) -> Diagonals {
+    let len = 2 * half;
+    let quarter = 4 * len;
+    let roots = std::iter::successors(Some(1), |power| Some(power * 5 % quarter))
+        .take(half)
+        .map(|power| Complex::from_polar(1.0, 2.0 * PI * power as f64 / quarter as f64))
+        .collect::<Vec<_>>();
+    let mut diagonals = Diagonals::new();
+    let half = half as i64;
+    for p in 0..slots {
+        let j = p % len;
+        let entries = match (j < len / 2, inverse) {
+            (true, false) => [(0, Complex::ONE), (half, roots[j])],
+            (true, true) => [(0, Complex::ONE), (half, Complex::ONE)],
+            (false, false) => [(-half, Complex::ONE), (0, -roots[j - len / 2])],
+            (false, true) => {
+                let root = roots[j - len / 2].conj();
+                [(-half, root), (0, -root)]
+            }
+        };
+        for (offset, value) in entries {
+            diagonals
+                .entry(centered(offset, slots))
+                .or_insert_with(|| vec![Complex::ZERO; slots])[p] += value;
+        }
+    }
+    diagonals
+}
+
+/// The product `a b` of matrices by their diagonals
+fn product(slots: usize, a: &Diagonals, b: &Diagonals) -> Diagonals {
+    let mut result = Diagonals::new();
+    for (&alpha, x) in a {
+        for (&beta, y) in b {
+            let sum = result
+                .entry(centered(alpha + beta, slots))
+                .or_insert_with(|| vec![Complex::ZERO; slots]);
+            for (i, s) in sum.iter_mut().enumerate() {
+                *s += x[i] * y[(i as i64 + alpha).rem_euclid(slots as i64) as usize];
+            }
+        }
+    }
+    result
+}
+
+/// The offset modulo the slots in `(-slots / 2, slots / 2]`
+fn centered(offset: i64, slots: usize) -> i64 {
+    let slots = slots as i64;
+    let offset = offset.rem_euclid(slots);
+    if 2 * offset > slots {
+        offset - slots
+    } else {
+        offset
+    }
+}
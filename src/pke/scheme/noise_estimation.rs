@@ -0,0 +1,100 @@
+//! Noise estimation for CKKS noise flooding
+//!
+//! A context in [`ExecutionMode::NoiseEstimation`] runs the circuit of the
+//! application and measures the noise of its outputs instead of decrypting
+//! them. The estimate then sets up a context in [`ExecutionMode::Evaluation`]
+//! that floods decryptions with [`DecryptionNoiseMode::NoiseFlooding`], see
+//! [`CryptoContext::decrypt_with_params`].
+
+use crate::ciphertext::Ciphertext;
+use crate::constants::{DecryptionNoiseMode, ExecutionMode, PlaintextEncodingsType};
+use crate::crypto_object::CryptoObject;
+use crate::encoding::PlaintextEncodings;
+use crate::error::{Error, Result};
+use crate::pke::{CcParams, CryptoContext, CryptoParametersRns, PrivateKey};
+
+/// The noise of a CKKS ciphertext measured by
+/// [`CryptoContext::estimate_noise`]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NoiseEstimate {
+    /// The bits of the noise in the coefficients, see
+    /// [`CkksPlaintext::log_error`](crate::encoding::CkksPlaintext::log_error)
+    pub log_error: f64,
+    /// The bits of the scaling factor above the noise of the slots, about
+    /// the coefficient noise grown by the square root of the slots
+    pub precision: f64,
+    /// The slots of the ciphertext
+    pub slots: usize,
+}
+
+impl NoiseEstimate {
+    /// `cc_params` for evaluating the circuit with noise flooding
+    /// decryption hiding this noise
+    pub fn flooding_cc_params(&self, cc_params: CcParams) -> CcParams {
+        CcParams {
+            execution_mode: ExecutionMode::Evaluation,
+            decryption_noise_mode: DecryptionNoiseMode::NoiseFlooding,
+            noise_estimate: self.log_error,
+            ..cc_params
+        }
+    }
+
+    /// The bits of the scaling factor leaving `desired_precision` bits in
+    /// the slots above the flooding noise of `cc_params`, the maximum error
+    /// over many slots is a few bits larger
+    pub fn scaling_mod_size(&self, cc_params: &CcParams, desired_precision: f64) -> usize {
+        (self.log_error
+            + cc_params.flooding_std_dev().log2()
+            + slot_growth(self.slots)
+            + desired_precision)
+            .ceil() as usize
+    }
+}
+
+impl CryptoContext {
+    /// The noise of a CKKS `ciphertext` in a context of
+    /// [`ExecutionMode::NoiseEstimation`], from the imaginary parts of its
+    /// slots. Only the noise is returned, not the decrypted values, so the
+    /// circuit should encrypt real inputs representative of the
+    /// application.
+    pub fn estimate_noise(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        ciphertext: &Ciphertext,
+    ) -> Result<NoiseEstimate> {
+        if params.rlwe_crypto_parameters.execution_mode != ExecutionMode::NoiseEstimation {
+            return Err(Error::CcParams(
+                "estimating the noise needs parameters in noise estimation mode".to_string(),
+            ));
+        }
+        if ciphertext.encoding_type() != PlaintextEncodingsType::CkksPacked {
+            return Err(Error::CcParams(format!(
+                "cannot estimate the noise of a {} ciphertext",
+                ciphertext.encoding_type()
+            )));
+        }
+        if private_key.get_crypto_context() != self || ciphertext.get_crypto_context() != self {
+            return Err(Error::CcParams(
+                "the key or the ciphertext belongs to another crypto context".to_string(),
+            ));
+        }
+        let PlaintextEncodings::Ckks(plaintext) =
+            self.decrypt_ckks(private_key, ciphertext, None)?
+        else {
+            return Err(Error::DecryptionFailure);
+        };
+        let log_error = plaintext.log_error().ok_or(Error::DecryptionFailure)?;
+        let slots = plaintext.values().len();
+        Ok(NoiseEstimate {
+            log_error,
+            precision: ciphertext.scaling_factor().log2() - log_error - slot_growth(slots),
+            slots,
+        })
+    }
+}
+
+/// The bits independent coefficient noise grows by in each of `slots` slots
+fn slot_growth(slots: usize) -> f64 {
+    (slots.max(1) as f64).log2() / 2.0
+}
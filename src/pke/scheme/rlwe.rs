@@ -3,12 +3,14 @@ use derive_more::Display;
 use serde::{Deserialize, Serialize};
 
 use crate::constants::{
-    MultipartyMode, ProxyPreEncryptionMode, SecretKeyDistribution, SecurityLevel,
+    DecryptionNoiseMode, ExecutionMode, MultipartyMode, ProxyPreEncryptionMode,
+    SecretKeyDistribution, SecurityLevel,
 };
 
+/// Scheme independent parameters of the RLWE problem
 #[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, Display)]
 #[display(
-    "RLWECryptoParameters {{ {discrete_gaussian_std_dev}, {discrete_gaussian_std_dev_with_flooding}, {assurance_measure_alpha}, {noise_scale}, {digit_size}, {max_relinearization_secret_key_power}, {secret_key_distribution}, {security_level}, {proxy_pre_encryption_mode}, {multiparty_mode}, {threshold_parties} }}"
+    "RLWECryptoParameters {{ {discrete_gaussian_std_dev}, {discrete_gaussian_std_dev_with_flooding}, {assurance_measure_alpha}, {noise_scale}, {digit_size}, {max_relinearization_secret_key_power}, {secret_key_distribution}, {security_level}, {proxy_pre_encryption_mode}, {multiparty_mode}, {threshold_parties}, {max_hops}, {execution_mode}, {decryption_noise_mode}, {noise_estimate} }}"
 )]
 pub struct RLWECryptoParameters {
     /// discrete gaussian standard deviation
@@ -19,7 +21,7 @@ pub struct RLWECryptoParameters {
     pub assurance_measure_alpha: f64,
     /// noise scale
     pub noise_scale: U64,
-    /// digit size
+    /// The bits of BV key switching digits, zero for one digit per tower
     pub digit_size: usize,
     /// the highest power of secret key for which relinearization key is generated
     pub max_relinearization_secret_key_power: usize,
@@ -34,6 +36,15 @@ pub struct RLWECryptoParameters {
     pub multiparty_mode: MultipartyMode,
     /// The number of threshold parties
     pub threshold_parties: usize,
+    /// The number of proxy re-encryptions a ciphertext may go through
+    pub max_hops: usize,
+    /// Whether CKKS decryption estimates the noise or evaluates
+    pub execution_mode: ExecutionMode,
+    /// Whether CKKS decryption floods the noise
+    pub decryption_noise_mode: DecryptionNoiseMode,
+    /// The bits of CKKS noise flooding hides, from a run in
+    /// [`ExecutionMode::NoiseEstimation`]
+    pub noise_estimate: f64,
 }
 
 impl Default for RLWECryptoParameters {
@@ -43,13 +54,17 @@ impl Default for RLWECryptoParameters {
             discrete_gaussian_std_dev_with_flooding: 0.0,
             assurance_measure_alpha: 0.0,
             noise_scale: Default::default(),
-            digit_size: 1,
+            digit_size: 0,
             max_relinearization_secret_key_power: 2,
             secret_key_distribution: Default::default(),
             security_level: Default::default(),
             proxy_pre_encryption_mode: Default::default(),
             multiparty_mode: Default::default(),
             threshold_parties: 1,
+            max_hops: 0,
+            execution_mode: Default::default(),
+            decryption_noise_mode: Default::default(),
+            noise_estimate: 0.0,
         }
     }
 }
@@ -1 +1,133 @@
+mod behz;
 mod crypto_parameters;
+mod hps;
+mod hybrid;
+
+pub(crate) use behz::Behz;
+pub use crypto_parameters::CryptoParametersRns;
+pub(crate) use hps::{Hps, leveled_towers};
+pub(crate) use hybrid::Hybrid;
+
+use crate::ActingPrimitive;
+use crate::constants::MAX_MODULUS_SIZE;
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::core::lattice::params::{DcrtElementParams, DcrtElementParamsBuilder};
+use crate::core::math::Barrett64;
+use crate::core::utils::{first_prime, previous_prime};
+use crate::error::{Error, Result};
+use num::{BigUint, ToPrimitive};
+use std::collections::HashSet;
+
+/// The tower moduli of `params`
+fn moduli(params: &DcrtElementParams) -> Vec<u64> {
+    params
+        .params()
+        .iter()
+        .map(|p| p.ciphertext_modulus.get().to_primitive())
+        .collect()
+}
+
+/// `count` full size towers for the ring of `q`, distinct from its towers
+fn auxiliary_basis(q: &DcrtElementParams, count: usize) -> Result<DcrtElementParams> {
+    let order = q
+        .params()
+        .front()
+        .ok_or(Error::DcrtElementParamsMismatch)?
+        .cyclotomic_order;
+    let existing = moduli(q).into_iter().collect::<HashSet<_>>();
+    let mut primes = Vec::with_capacity(count);
+    let mut p = first_prime(MAX_MODULUS_SIZE, order).ok_or(Error::DcrtElementParamsMismatch)?;
+    loop {
+        if !existing.contains(&p.to_primitive()) {
+            primes.push(Option::from(p.to_odd()).expect("a prime is odd"));
+            if primes.len() == count {
+                break;
+            }
+        }
+        p = previous_prime(p, order).ok_or(Error::DcrtElementParamsMismatch)?;
+    }
+    DcrtElementParamsBuilder::new(order).moduli(primes).build()
+}
+
+/// The towers of `a` followed by those of `b`
+fn joined(a: &DcrtElementParams, b: &DcrtElementParams) -> Result<DcrtElementParams> {
+    let order = a
+        .params()
+        .front()
+        .ok_or(Error::DcrtElementParamsMismatch)?
+        .cyclotomic_order;
+    let towers = a.params().iter().chain(b.params());
+    // The towers carry their roots of unity already, searching them again
+    // dominates key generation
+    DcrtElementParamsBuilder::new(order)
+        .moduli(towers.clone().map(|p| p.ciphertext_modulus).collect())
+        .roots_of_unity(towers.map(|p| p.root_of_unity).collect())
+        .build()
+}
+
+fn barrett(m: u64) -> Result<Barrett64> {
+    Barrett64::new(m).ok_or_else(|| {
+        Error::CcParams(format!(
+            "a modulus of {m} is too large for RNS multiplication"
+        ))
+    })
+}
+
+/// The tensor product of two ciphertexts in evaluation format, with
+/// `x.len() + y.len() - 1` polynomials
+pub(crate) fn tensor_product(x: &[DcrtPoly], y: &[DcrtPoly]) -> Vec<DcrtPoly> {
+    let mut product = Vec::<DcrtPoly>::with_capacity(x.len() + y.len() - 1);
+    for (i, xi) in x.iter().enumerate() {
+        for (j, yj) in y.iter().enumerate() {
+            let mut term = xi.clone();
+            term *= yj;
+            match product.get_mut(i + j) {
+                Some(sum) => *sum += &term,
+                None => product.push(term),
+            }
+        }
+    }
+    product
+}
+
+/// The tensor product of a ciphertext in evaluation format with itself,
+/// computing each cross term `x_i x_j` once and doubling it
+pub(crate) fn tensor_square(x: &[DcrtPoly]) -> Vec<DcrtPoly> {
+    let mut product = Vec::<DcrtPoly>::with_capacity(2 * x.len() - 1);
+    for (i, xi) in x.iter().enumerate() {
+        for (j, xj) in x.iter().enumerate().skip(i) {
+            let mut term = xi.clone();
+            term *= xj;
+            if j != i {
+                term += &term.clone();
+            }
+            match product.get_mut(i + j) {
+                Some(sum) => *sum += &term,
+                None => product.push(term),
+            }
+        }
+    }
+    product
+}
+
+/// `sum_i |values_i|_m * weights_i mod m`, the fast base conversion of the
+/// already scaled residues `values`
+fn fast_convert(m: &Barrett64, values: &[u64], weights: &[u64]) -> u64 {
+    values.iter().zip(weights).fold(0, |acc, (&v, &w)| {
+        m.add_mod(acc, m.mul_mod(m.reduce_word(v), w))
+    })
+}
+
+fn modulo(value: &BigUint, m: u64) -> u64 {
+    (value % m)
+        .to_u64()
+        .expect("a residue is below a 64 bit modulus")
+}
+
+/// `value^-1 mod p` for a prime `p`
+fn inverse(value: &BigUint, p: u64) -> u64 {
+    modulo(
+        &(value % p).modpow(&BigUint::from(p - 2), &BigUint::from(p)),
+        p,
+    )
+}
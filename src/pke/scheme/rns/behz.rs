@@ -0,0 +1,304 @@
+//! BFV multiplication in RNS with the BEHZ technique
+//!
+//! Bajard, Eynard, Hasan and Zucca, "A Full RNS Variant of FV like Somewhat
+//! Homomorphic Encryption Schemes" (SAC 2016). Both ciphertexts are extended
+//! from `Q` to an auxiliary basis `Bsk = B ∪ {m_sk}` by fast base conversion,
+//! whose overflow of a few multiples of `Q` is removed by a small Montgomery
+//! reduction modulo `m̃`. The tensor product is taken in `Q ∪ Bsk`, scaled
+//! by `t / Q` in `Bsk` and brought back to `Q` exactly with Shenoy and
+//! Kumaresan's conversion. Every step works on 64 bit residues, no
+//! coefficient is ever interpolated.
+
+use super::{
+    auxiliary_basis, barrett, fast_convert, inverse, moduli, modulo, tensor_product, tensor_square,
+};
+use crate::constants::{MAX_MODULUS_SIZE, PolynomialRingFormat};
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::core::lattice::params::DcrtElementParams;
+use crate::core::math::Barrett64;
+use crate::error::{Error, Result};
+use crate::pke::encryption_pool::in_format_of;
+use num::BigUint;
+
+/// The small Montgomery modulus `m̃`
+const M_TILDE: u64 = 1 << 16;
+
+/// The auxiliary basis and constants for multiplying ciphertexts modulo `Q`
+#[derive(Clone, Debug)]
+pub(crate) struct Behz {
+    q: DcrtElementParams,
+    /// `B` followed by `m_sk`
+    bsk: DcrtElementParams,
+    q_moduli: Vec<Barrett64>,
+    bsk_moduli: Vec<Barrett64>,
+    m_tilde: Barrett64,
+    /// `|m̃ (Q / q_i)^-1|_{q_i}`
+    m_tilde_q_hat_inv: Vec<u64>,
+    /// `|t (Q / q_i)^-1|_{q_i}`
+    t_q_hat_inv: Vec<u64>,
+    /// `|Q / q_i|_m` for `m` in `Bsk` followed by `m̃`, indexed by `m`
+    q_hat_mod: Vec<Vec<u64>>,
+    /// `|-Q^-1|_{m̃}`
+    neg_q_inv_mod_m_tilde: u64,
+    /// `|Q|_m` for `m` in `Bsk`
+    q_mod_bsk: Vec<u64>,
+    /// `|Q^-1|_m` for `m` in `Bsk`
+    q_inv_mod_bsk: Vec<u64>,
+    /// `|m̃^-1|_m` for `m` in `Bsk`
+    m_tilde_inv_mod_bsk: Vec<u64>,
+    /// `|t|_m` for `m` in `Bsk`
+    t_mod_bsk: Vec<u64>,
+    /// `|(B / b_j)^-1|_{b_j}`
+    b_hat_inv: Vec<u64>,
+    /// `|B / b_j|_m` for `m` in `Q` followed by `m_sk`, indexed by `m`
+    b_hat_mod: Vec<Vec<u64>>,
+    /// `|B^-1|_{m_sk}`
+    b_inv_mod_msk: u64,
+    /// `|B|_{q_i}`
+    b_mod_q: Vec<u64>,
+}
+
+impl Behz {
+    /// Pick the auxiliary basis for ciphertexts over `q` encrypting messages
+    /// modulo `plaintext_modulus`.
+    ///
+    /// `B` is made of full size towers, distinct from those of `q`, with
+    /// room for `t / Q` times a tensor product of ciphertexts in the ring.
+    pub(crate) fn new(q: &DcrtElementParams, plaintext_modulus: u64) -> Result<Self> {
+        let order = q
+            .params()
+            .front()
+            .ok_or(Error::DcrtElementParamsMismatch)?
+            .cyclotomic_order;
+        let q_primes = moduli(q);
+        let big_q = q.ciphertext_composite_modulus();
+        // The scaled tensor product is below 2 t L Q for L coefficients,
+        // leave a few bits for the sums of the products and the sign
+        let bits = big_q.bits() as usize
+            + (u64::BITS - plaintext_modulus.leading_zeros()) as usize
+            + (usize::BITS - order.leading_zeros()) as usize
+            + 8;
+        let bsk = auxiliary_basis(q, bits.div_ceil(MAX_MODULUS_SIZE - 1) + 1)?;
+        let bsk_primes = moduli(&bsk);
+
+        let t = BigUint::from(plaintext_modulus);
+        let m_tilde = BigUint::from(M_TILDE);
+        let (msk, b_primes) = bsk_primes
+            .split_last()
+            .expect("Bsk has at least two towers");
+        let big_b = b_primes
+            .iter()
+            .map(|&b| BigUint::from(b))
+            .product::<BigUint>();
+
+        let q_hat = q_primes.iter().map(|&qi| big_q / qi).collect::<Vec<_>>();
+        let q_hat_inv = q_primes
+            .iter()
+            .zip(&q_hat)
+            .map(|(&qi, h)| inverse(h, qi))
+            .collect::<Vec<_>>();
+        let b_hat = b_primes.iter().map(|&b| &big_b / b).collect::<Vec<_>>();
+
+        Ok(Self {
+            q: q.clone(),
+            q_moduli: q_primes
+                .iter()
+                .map(|&m| barrett(m))
+                .collect::<Result<_>>()?,
+            bsk_moduli: bsk_primes
+                .iter()
+                .map(|&m| barrett(m))
+                .collect::<Result<_>>()?,
+            m_tilde: barrett(M_TILDE)?,
+            m_tilde_q_hat_inv: q_primes
+                .iter()
+                .zip(&q_hat_inv)
+                .map(|(&qi, &h)| modulo(&(&m_tilde * h), qi))
+                .collect(),
+            t_q_hat_inv: q_primes
+                .iter()
+                .zip(&q_hat_inv)
+                .map(|(&qi, &h)| modulo(&(&t * h), qi))
+                .collect(),
+            q_hat_mod: bsk_primes
+                .iter()
+                .chain([&M_TILDE])
+                .map(|&m| q_hat.iter().map(|h| modulo(h, m)).collect())
+                .collect(),
+            // The units modulo 2^16 have order 2^15
+            neg_q_inv_mod_m_tilde: M_TILDE
+                - modulo(
+                    &(big_q % M_TILDE).modpow(&BigUint::from(M_TILDE / 2 - 1), &m_tilde),
+                    M_TILDE,
+                ),
+            q_mod_bsk: bsk_primes.iter().map(|&m| modulo(big_q, m)).collect(),
+            q_inv_mod_bsk: bsk_primes.iter().map(|&m| inverse(big_q, m)).collect(),
+            m_tilde_inv_mod_bsk: bsk_primes.iter().map(|&m| inverse(&m_tilde, m)).collect(),
+            t_mod_bsk: bsk_primes.iter().map(|&m| modulo(&t, m)).collect(),
+            b_hat_inv: b_primes
+                .iter()
+                .zip(&b_hat)
+                .map(|(&b, h)| inverse(h, b))
+                .collect(),
+            b_hat_mod: q_primes
+                .iter()
+                .chain([msk])
+                .map(|&m| b_hat.iter().map(|h| modulo(h, m)).collect())
+                .collect(),
+            b_inv_mod_msk: inverse(&big_b, *msk),
+            b_mod_q: q_primes.iter().map(|&qi| modulo(&big_b, qi)).collect(),
+            bsk,
+        })
+    }
+
+    /// The tensor product of two ciphertexts scaled by `t / Q`, with
+    /// `a.len() + b.len() - 1` polynomials in the format of `a[0]`
+    pub(crate) fn multiply(&self, a: &[DcrtPoly], b: &[DcrtPoly]) -> Result<Vec<DcrtPoly>> {
+        let (Some(first), false) = (a.first(), b.is_empty()) else {
+            return Err(Error::CiphertextFormat(
+                "a ciphertext has no polynomials".to_string(),
+            ));
+        };
+        if a.iter().chain(b).any(|c| c.params() != &self.q) {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        let (a_q, a_bsk) = self.extend_all(a);
+        let (b_q, b_bsk) = self.extend_all(b);
+        Ok(tensor_product(&a_q, &b_q)
+            .into_iter()
+            .zip(tensor_product(&a_bsk, &b_bsk))
+            .map(|(mut x_q, mut x_bsk)| {
+                x_q.switch_format();
+                x_bsk.switch_format();
+                in_format_of(self.scale_down(&x_q, &x_bsk), first)
+            })
+            .collect())
+    }
+
+    /// The tensor square of a ciphertext scaled by `t / Q`, like
+    /// [`Behz::multiply`] with itself but extending each polynomial once
+    pub(crate) fn square(&self, a: &[DcrtPoly]) -> Result<Vec<DcrtPoly>> {
+        let Some(first) = a.first() else {
+            return Err(Error::CiphertextFormat(
+                "a ciphertext has no polynomials".to_string(),
+            ));
+        };
+        if a.iter().any(|c| c.params() != &self.q) {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        let (a_q, a_bsk) = self.extend_all(a);
+        Ok(tensor_square(&a_q)
+            .into_iter()
+            .zip(tensor_square(&a_bsk))
+            .map(|(mut x_q, mut x_bsk)| {
+                x_q.switch_format();
+                x_bsk.switch_format();
+                in_format_of(self.scale_down(&x_q, &x_bsk), first)
+            })
+            .collect())
+    }
+
+    /// Every polynomial in evaluation format modulo `Q` and modulo `Bsk`
+    fn extend_all(&self, ciphertext: &[DcrtPoly]) -> (Vec<DcrtPoly>, Vec<DcrtPoly>) {
+        ciphertext
+            .iter()
+            .map(|c| {
+                let mut coefficients = c.clone();
+                if coefficients.format() == PolynomialRingFormat::Evaluation {
+                    coefficients.switch_format();
+                }
+                let mut extended = self.extend(&coefficients);
+                extended.switch_format();
+                let mut c = c.clone();
+                if c.format() == PolynomialRingFormat::Coefficient {
+                    c.switch_format();
+                }
+                (c, extended)
+            })
+            .unzip()
+    }
+
+    /// The coefficients `x` of `poly` modulo `Bsk`, off by at most one `Q`.
+    ///
+    /// The fast conversion of `|m̃ x|_Q` is `m̃ x + α Q`, the Montgomery step
+    /// adds the multiple of `Q` making it divisible by `m̃` and divides.
+    fn extend(&self, poly: &DcrtPoly) -> DcrtPoly {
+        let x = poly.residues();
+        let len = x.first().map_or(0, Vec::len);
+        let mut scaled = vec![0; self.q_moduli.len()];
+        let mut out = vec![Vec::with_capacity(len); self.bsk_moduli.len()];
+        let m_tilde_index = self.bsk_moduli.len();
+        for c in 0..len {
+            for ((s, (xi, qi)), w) in scaled
+                .iter_mut()
+                .zip(x.iter().zip(&self.q_moduli))
+                .zip(&self.m_tilde_q_hat_inv)
+            {
+                *s = qi.mul_mod(xi[c], *w);
+            }
+            let y = fast_convert(&self.m_tilde, &scaled, &self.q_hat_mod[m_tilde_index]);
+            let r = self.m_tilde.mul_mod(y, self.neg_q_inv_mod_m_tilde);
+            for (j, m) in self.bsk_moduli.iter().enumerate() {
+                let y = fast_convert(m, &scaled, &self.q_hat_mod[j]);
+                // r centered in [-m̃ / 2, m̃ / 2)
+                let q_r = if r < M_TILDE / 2 {
+                    m.mul_mod(self.q_mod_bsk[j], r)
+                } else {
+                    m.sub_mod(0, m.mul_mod(self.q_mod_bsk[j], M_TILDE - r))
+                };
+                out[j].push(m.mul_mod(m.add_mod(y, q_r), self.m_tilde_inv_mod_bsk[j]));
+            }
+        }
+        DcrtPoly::from_residues(self.bsk.clone(), &out)
+    }
+
+    /// `floor(t x / Q)` modulo `Q` for `x` given modulo `Q` and `Bsk`, both
+    /// in coefficient format, up to the small error of the fast conversion
+    fn scale_down(&self, x_q: &DcrtPoly, x_bsk: &DcrtPoly) -> DcrtPoly {
+        let (x_q, x_bsk) = (x_q.residues(), x_bsk.residues());
+        let len = x_q.first().map_or(0, Vec::len);
+        let (msk, b_moduli) = self
+            .bsk_moduli
+            .split_last()
+            .expect("Bsk has at least two towers");
+        let msk_index = self.q_moduli.len();
+        let mut scaled = vec![0; self.q_moduli.len()];
+        let mut y = vec![0; self.bsk_moduli.len()];
+        let mut scaled_b = vec![0; b_moduli.len()];
+        let mut out = vec![Vec::with_capacity(len); self.q_moduli.len()];
+        for c in 0..len {
+            for (i, qi) in self.q_moduli.iter().enumerate() {
+                scaled[i] = qi.mul_mod(x_q[i][c], self.t_q_hat_inv[i]);
+            }
+            // (t x - |t x|_Q - α Q) / Q in Bsk
+            for (j, m) in self.bsk_moduli.iter().enumerate() {
+                let t_x = m.mul_mod(x_bsk[j][c], self.t_mod_bsk[j]);
+                let low = fast_convert(m, &scaled, &self.q_hat_mod[j]);
+                y[j] = m.mul_mod(m.sub_mod(t_x, low), self.q_inv_mod_bsk[j]);
+            }
+            // Shenoy and Kumaresan, m_sk recovers the multiple of B that
+            // the fast conversion from B added
+            for (j, b) in b_moduli.iter().enumerate() {
+                scaled_b[j] = b.mul_mod(y[j], self.b_hat_inv[j]);
+            }
+            let at_msk = fast_convert(msk, &scaled_b, &self.b_hat_mod[msk_index]);
+            let gamma = msk.mul_mod(msk.sub_mod(at_msk, y[b_moduli.len()]), self.b_inv_mod_msk);
+            let gamma_negative = gamma > msk.modulus() / 2;
+            let gamma = if gamma_negative {
+                msk.modulus() - gamma
+            } else {
+                gamma
+            };
+            for (i, qi) in self.q_moduli.iter().enumerate() {
+                let value = fast_convert(qi, &scaled_b, &self.b_hat_mod[i]);
+                let gamma_b = qi.mul_mod(qi.reduce_word(gamma), self.b_mod_q[i]);
+                out[i].push(if gamma_negative {
+                    qi.add_mod(value, gamma_b)
+                } else {
+                    qi.sub_mod(value, gamma_b)
+                });
+            }
+        }
+        DcrtPoly::from_residues(self.q.clone(), &out)
+    }
+}
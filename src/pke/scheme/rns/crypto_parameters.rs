@@ -1,12 +1,18 @@
 use crate::constants::{
-    EncryptionTechnique, KeySwitchTechnique, MultiplicationTechnique, ScalingTechnique,
+    EncryptionTechnique, KeySwitchTechnique, MULTIPARTY_MOD_SIZE, MultipartyMode,
+    MultiplicationTechnique, NUM_MODULI_MULTIPARTY, ProxyPreEncryptionMode, ScalingTechnique,
 };
 use crate::pke::scheme::rlwe::RLWECryptoParameters;
 
+use super::{Behz, Hps, leveled_towers};
+use crate::core::lattice::dcrt_poly::DcrtPoly;
 use crate::core::lattice::params::DcrtElementParams;
+use crate::error::Result;
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
+/// Parameters shared by the RNS variants of the schemes
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Display)]
 #[display(
     "CryptoParametersRns {{\
@@ -16,16 +22,229 @@ use serde::{Deserialize, Serialize};
  encryption_technique: {encryption_technique}, \
  multiplication_technique: {multiplication_technique}, \
  aux_bits: {aux_bits}, \
+ num_large_digits: {num_large_digits}, \
  extra_bits: {extra_bits},\
  dcrt_element_params: {dcrt_element_params}"
 )]
 pub struct CryptoParametersRns {
+    /// The scheme independent RLWE parameters
     pub rlwe_crypto_parameters: RLWECryptoParameters,
+    /// The key switching technique
     pub key_switch_technique: KeySwitchTechnique,
+    /// The rescaling technique
     pub scaling_technique: ScalingTechnique,
+    /// The public key encryption technique
     pub encryption_technique: EncryptionTechnique,
+    /// The BFV multiplication technique
     pub multiplication_technique: MultiplicationTechnique,
+    /// The bit size of the auxiliary key switching towers
     pub aux_bits: usize,
+    /// The digits of hybrid key switching, zero to derive them from the
+    /// number of towers
+    pub num_large_digits: usize,
+    /// The bit size of the extra tower used by some scaling techniques
     pub extra_bits: usize,
+    /// The towers of the ciphertext modulus
     pub dcrt_element_params: DcrtElementParams,
+    /// The BFV multiplication tables of `dcrt_element_params`
+    #[serde(skip)]
+    pub(crate) bfv_multipliers: BfvMultipliers,
+}
+
+impl CryptoParametersRns {
+    /// Precompute the tables of [`CryptoParametersRns::multiplication_technique`]
+    /// for ciphertexts over all towers encrypting messages modulo
+    /// `plaintext_modulus`. With [`MultiplicationTechnique::HpsOverQLeveled`]
+    /// `droppable_bits` gives the bits of the modulus that can be dropped
+    /// before multiplying ciphertexts of each multiplicative depth up to
+    /// `depth`. Call this once after adding towers.
+    pub(crate) fn precompute_bfv_multipliers(
+        &mut self,
+        plaintext_modulus: u64,
+        depth: usize,
+        droppable_bits: impl Fn(usize) -> f64,
+    ) -> Result<()> {
+        let q = &self.dcrt_element_params;
+        let technique = self.multiplication_technique;
+        let by_depth = match technique {
+            MultiplicationTechnique::HpsOverQLeveled => (0..=depth)
+                .map(|d| {
+                    let towers = leveled_towers(q, droppable_bits(d));
+                    BfvMultiplier::new(q, plaintext_modulus, technique, towers)
+                })
+                .collect::<Result<_>>()?,
+            _ => vec![BfvMultiplier::new(
+                q,
+                plaintext_modulus,
+                technique,
+                q.params().len(),
+            )?],
+        };
+        self.bfv_multipliers = BfvMultipliers {
+            plaintext_modulus,
+            by_depth,
+        };
+        Ok(())
+    }
+
+    /// The BFV multiplication of ciphertexts over `q` of multiplicative
+    /// depth `depth`, precomputed for all towers and built for fewer.
+    /// Deeper ciphertexts than precomputed for and fewer towers multiply
+    /// over all their towers.
+    pub(crate) fn bfv_multiplier(
+        &self,
+        q: &DcrtElementParams,
+        plaintext_modulus: u64,
+        depth: usize,
+    ) -> Result<BfvMultiplier> {
+        let technique = self.multiplication_technique;
+        let precomputed = &self.bfv_multipliers;
+        if q == &self.dcrt_element_params && precomputed.plaintext_modulus == plaintext_modulus {
+            if let Some(multiplier) = precomputed.by_depth.get(depth) {
+                return Ok(multiplier.clone());
+            }
+            if technique != MultiplicationTechnique::HpsOverQLeveled
+                && let Some(multiplier) = precomputed.by_depth.first()
+            {
+                return Ok(multiplier.clone());
+            }
+        }
+        BfvMultiplier::new(q, plaintext_modulus, technique, q.params().len())
+    }
+
+    /// Add the towers required by the multiparty mode to `dcrt_element_params`.
+    ///
+    /// [`MultipartyMode::NoiseFlooding`] gets [`NUM_MODULI_MULTIPARTY`] extra
+    /// towers of [`MULTIPARTY_MOD_SIZE`] bits so the flooding noise added in
+    /// threshold decryption fits below the modulus. Other modes need nothing.
+    /// Call this once while generating the parameters.
+    pub fn add_multiparty_towers(&mut self) -> Result<()> {
+        if self.rlwe_crypto_parameters.multiparty_mode == MultipartyMode::NoiseFlooding {
+            self.dcrt_element_params
+                .insert_towers(NUM_MODULI_MULTIPARTY, MULTIPARTY_MOD_SIZE)?;
+        }
+        Ok(())
+    }
+
+    /// Add the towers required by the proxy re-encryption mode to
+    /// `dcrt_element_params`.
+    ///
+    /// [`ProxyPreEncryptionMode::NoiseFloodingHra`] drops a tower per hop to
+    /// scale its flooding noise away, so it gets one extra tower of
+    /// [`MULTIPARTY_MOD_SIZE`] bits per hop. Other modes need nothing. Call
+    /// this once while generating the parameters.
+    pub fn add_pre_towers(&mut self) -> Result<()> {
+        let rlwe = &self.rlwe_crypto_parameters;
+        if rlwe.proxy_pre_encryption_mode == ProxyPreEncryptionMode::NoiseFloodingHra
+            && rlwe.max_hops > 0
+        {
+            self.dcrt_element_params
+                .insert_towers(rlwe.max_hops, MULTIPARTY_MOD_SIZE)?;
+        }
+        Ok(())
+    }
+}
+
+/// The precomputed [`BfvMultiplier`]s of [`CryptoParametersRns`], one per
+/// multiplicative depth with [`MultiplicationTechnique::HpsOverQLeveled`],
+/// one for any depth otherwise. Derived from the other parameters, so not
+/// serialized nor compared.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BfvMultipliers {
+    plaintext_modulus: u64,
+    by_depth: Vec<BfvMultiplier>,
+}
+
+impl PartialEq for BfvMultipliers {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// The auxiliary basis and constants of one BFV multiplication technique
+#[derive(Clone, Debug)]
+pub(crate) enum BfvMultiplier {
+    Behz(Arc<Behz>),
+    Hps(Arc<Hps>),
+}
+
+impl BfvMultiplier {
+    /// The tables of `technique` for ciphertexts over `q`, multiplying over
+    /// its first `towers` towers
+    fn new(
+        q: &DcrtElementParams,
+        plaintext_modulus: u64,
+        technique: MultiplicationTechnique,
+        towers: usize,
+    ) -> Result<Self> {
+        Ok(match technique {
+            MultiplicationTechnique::Behz => Self::Behz(Arc::new(Behz::new(q, plaintext_modulus)?)),
+            technique => Self::Hps(Arc::new(Hps::new(q, plaintext_modulus, technique, towers)?)),
+        })
+    }
+
+    /// The tensor product of `a` and `b`, or of `a` with itself, scaled down
+    /// by `t / Q`
+    pub(crate) fn multiply(&self, a: &[DcrtPoly], b: Option<&[DcrtPoly]>) -> Result<Vec<DcrtPoly>> {
+        match (self, b) {
+            (Self::Behz(behz), Some(b)) => behz.multiply(a, b),
+            (Self::Behz(behz), None) => behz.square(a),
+            (Self::Hps(hps), Some(b)) => hps.multiply(a, b),
+            (Self::Hps(hps), None) => hps.square(a),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::MultiplicationTechnique;
+    use crate::encoding::PlaintextEncodings;
+    use crate::pke::test_support::bfv_params;
+
+    #[test]
+    fn precomputed_multipliers_square_twice() {
+        for technique in [
+            MultiplicationTechnique::Behz,
+            MultiplicationTechnique::Hps,
+            MultiplicationTechnique::HpsOverQ,
+            MultiplicationTechnique::HpsOverQLeveled,
+        ] {
+            let bfv = bfv_params()
+                .multiplicative_depth(2)
+                .multiplication_technique(technique)
+                .build()
+                .expect("parameters");
+            let params = bfv.crypto_parameters();
+            let expected_tables = match technique {
+                MultiplicationTechnique::HpsOverQLeveled => 3,
+                _ => 1,
+            };
+            assert_eq!(params.bfv_multipliers.by_depth.len(), expected_tables);
+
+            let cc = bfv.crypto_context();
+            let key_pair = bfv.key_gen().expect("keys");
+            let relin_key = bfv
+                .eval_mult_key_gen(key_pair.private_key())
+                .expect("relinearization key");
+            let plaintext = bfv.encode(&[3, 1]).expect("encode");
+            let x = bfv
+                .encrypt(key_pair.public_key(), &plaintext)
+                .expect("encrypt");
+            let x2 = cc.eval_mult(params, &x, &x, &relin_key).expect("square");
+            let x4 = cc
+                .eval_mult(params, &x2, &x2, &relin_key)
+                .expect("square again");
+            let PlaintextEncodings::Coefficient(decrypted) =
+                cc.decrypt(key_pair.private_key(), &x4).expect("decrypt")
+            else {
+                panic!("a coefficient plaintext");
+            };
+            // (3 + x)^4
+            assert_eq!(
+                decrypted.coefficients()[..5],
+                [81, 108, 54, 12, 1],
+                "{technique}"
+            );
+        }
+    }
 }
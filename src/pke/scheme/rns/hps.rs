@@ -0,0 +1,438 @@
+//! BFV multiplication in RNS with the HPS family of techniques
+//!
+//! Halevi, Polyakov and Shoup, "An Improved RNS Variant of the BFV
+//! Homomorphic Encryption Scheme" (CT-RSA 2019). Both ciphertexts are
+//! extended from `Q` to an auxiliary basis `P` exactly, the rounding of the
+//! fast conversion is corrected with a floating point estimate of the
+//! overflow. The tensor product is taken in `Q ∪ P`, scaled by `t / Q` and
+//! rounded in `P`, and converted back to `Q`.
+//!
+//! With `HpsOverQ`, Kim, Polyakov and Zucca, "Revisiting Homomorphic
+//! Encryption Schemes for Finite Fields" (ASIACRYPT 2021), the second
+//! ciphertext is scaled by `P / Q` instead, so the product is scaled by
+//! `t / P` and lands in `Q` directly. `P` then only needs as many bits as
+//! `Q`. The leveled variant first switches both ciphertexts down to the
+//! leading towers `Q_l` the noise leaves room for, multiplies there and
+//! scales the product back up to `Q`.
+
+use super::{
+    auxiliary_basis, barrett, inverse, joined, moduli, modulo, tensor_product, tensor_square,
+};
+use crate::constants::{MAX_MODULUS_SIZE, MultiplicationTechnique, PolynomialRingFormat};
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::core::lattice::params::DcrtElementParams;
+use crate::core::math::Barrett64;
+use crate::error::{Error, Result};
+use crate::pke::encryption_pool::in_format_of;
+use num::{BigUint, Integer, ToPrimitive};
+
+/// The auxiliary basis and constants for multiplying ciphertexts modulo `Q`
+#[derive(Clone, Debug)]
+pub(crate) struct Hps {
+    over_q: bool,
+    /// The towers of the operands
+    q: DcrtElementParams,
+    /// The leading towers of `q` the product is taken over
+    ql: DcrtElementParams,
+    /// `Q_l` followed by `P`
+    qlp: DcrtElementParams,
+    /// `round(Q_l x / Q)`, when towers are dropped
+    drop: Option<ScaleAndRound>,
+    /// `|Q / Q_l|_{q_i}` for the kept towers
+    expand: Vec<u64>,
+    ql_to_p: BasisConversion,
+    p_to_ql: BasisConversion,
+    /// `round(P x / Q_l)` in `P` for `x` modulo `Q_l`, with `HpsOverQ`
+    lift: Option<ScaleAndRound>,
+    /// `round(t x / Q_l)` in `P` with `Hps`, `round(t x / P)` in `Q_l` with
+    /// `HpsOverQ`
+    scale: ScaleAndRound,
+}
+
+impl Hps {
+    /// Pick the auxiliary basis for ciphertexts over `q` encrypting messages
+    /// modulo `plaintext_modulus`, multiplying over the first `towers` towers.
+    ///
+    /// Only [`MultiplicationTechnique::HpsOverQLeveled`] multiplies over
+    /// fewer towers than `q` has.
+    pub(crate) fn new(
+        q: &DcrtElementParams,
+        plaintext_modulus: u64,
+        technique: MultiplicationTechnique,
+        towers: usize,
+    ) -> Result<Self> {
+        let over_q = match technique {
+            MultiplicationTechnique::Hps => false,
+            MultiplicationTechnique::HpsOverQ | MultiplicationTechnique::HpsOverQLeveled => true,
+            technique => {
+                return Err(Error::CcParams(format!(
+                    "{technique} is not an HPS multiplication technique"
+                )));
+            }
+        };
+        let all = q.params().len();
+        if towers == 0
+            || towers > all
+            || (towers < all && technique != MultiplicationTechnique::HpsOverQLeveled)
+        {
+            return Err(Error::CcParams(format!(
+                "cannot multiply over {towers} of {all} towers with {technique}"
+            )));
+        }
+        let order = q
+            .params()
+            .front()
+            .ok_or(Error::DcrtElementParamsMismatch)?
+            .cyclotomic_order;
+        let ql = q.shrink(towers);
+        let ql_bits = ql.ciphertext_composite_modulus().bits() as usize;
+        // Converting the scaled product back from `P` needs room for
+        // `t / Q` times a tensor product, below `2 t L Q` for `L`
+        // coefficients. Rounding the second ciphertext to `P / Q` adds less
+        // noise than the product itself once `P > Q`.
+        let bits = if over_q {
+            ql_bits + 1
+        } else {
+            ql_bits
+                + (u64::BITS - plaintext_modulus.leading_zeros()) as usize
+                + (usize::BITS - order.leading_zeros()) as usize
+                + 8
+        };
+        let p = auxiliary_basis(q, bits.div_ceil(MAX_MODULUS_SIZE - 1))?;
+        let (q_primes, ql_primes, p_primes) = (moduli(q), moduli(&ql), moduli(&p));
+        let t = BigUint::from(plaintext_modulus);
+
+        let big_p = p.ciphertext_composite_modulus();
+        let d = q.ciphertext_composite_modulus() / ql.ciphertext_composite_modulus();
+        Ok(Self {
+            over_q,
+            drop: (towers < all)
+                .then(|| ScaleAndRound::new(&ql_primes, &q_primes[towers..], &BigUint::from(1u32)))
+                .transpose()?,
+            expand: ql_primes.iter().map(|&qi| modulo(&d, qi)).collect(),
+            ql_to_p: BasisConversion::new(&ql_primes, &p_primes)?,
+            p_to_ql: BasisConversion::new(&p_primes, &ql_primes)?,
+            lift: over_q
+                .then(|| ScaleAndRound::new(&p_primes, &ql_primes, big_p))
+                .transpose()?,
+            scale: if over_q {
+                ScaleAndRound::new(&ql_primes, &p_primes, &t)?
+            } else {
+                ScaleAndRound::new(&p_primes, &ql_primes, &t)?
+            },
+            qlp: joined(&ql, &p)?,
+            q: q.clone(),
+            ql,
+        })
+    }
+
+    /// The tensor product of two ciphertexts scaled by `t / Q`, with
+    /// `a.len() + b.len() - 1` polynomials in the format of `a[0]`
+    pub(crate) fn multiply(&self, a: &[DcrtPoly], b: &[DcrtPoly]) -> Result<Vec<DcrtPoly>> {
+        let (Some(first), false) = (a.first(), b.is_empty()) else {
+            return Err(Error::CiphertextFormat(
+                "a ciphertext has no polynomials".to_string(),
+            ));
+        };
+        if a.iter().chain(b).any(|c| c.params() != &self.q) {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        let a = a.iter().map(|c| self.extend(c, false)).collect::<Vec<_>>();
+        let b = b
+            .iter()
+            .map(|c| self.extend(c, self.over_q))
+            .collect::<Vec<_>>();
+        Ok(tensor_product(&a, &b)
+            .into_iter()
+            .map(|mut x| {
+                x.switch_format();
+                in_format_of(self.scale_down(&x), first)
+            })
+            .collect())
+    }
+
+    /// The tensor square of a ciphertext scaled by `t / Q`, like
+    /// [`Hps::multiply`] with itself. Each polynomial is extended once
+    /// unless the technique lifts one operand.
+    pub(crate) fn square(&self, a: &[DcrtPoly]) -> Result<Vec<DcrtPoly>> {
+        if self.over_q {
+            return self.multiply(a, a);
+        }
+        let Some(first) = a.first() else {
+            return Err(Error::CiphertextFormat(
+                "a ciphertext has no polynomials".to_string(),
+            ));
+        };
+        if a.iter().any(|c| c.params() != &self.q) {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        let a = a.iter().map(|c| self.extend(c, false)).collect::<Vec<_>>();
+        Ok(tensor_square(&a)
+            .into_iter()
+            .map(|mut x| {
+                x.switch_format();
+                in_format_of(self.scale_down(&x), first)
+            })
+            .collect())
+    }
+
+    /// The coefficients `x` of `poly` modulo `Q_l ∪ P` in evaluation format,
+    /// first rounded to `Q_l x / Q` when towers are dropped and then to
+    /// `P x / Q_l` when `lift` is set
+    fn extend(&self, poly: &DcrtPoly, lift: bool) -> DcrtPoly {
+        let mut coefficients = poly.clone();
+        if coefficients.format() == PolynomialRingFormat::Evaluation {
+            coefficients.switch_format();
+        }
+        let x = coefficients.residues();
+        let ql_len = self.ql.params().len();
+        let x_ql = match &self.drop {
+            Some(drop) => columns(&x, ql_len, |z, out| {
+                let (keep, dropped) = z.split_at(ql_len);
+                drop.apply(keep, dropped, out)
+            }),
+            None => x,
+        };
+        let (x_ql, x_p) = match (&self.lift, lift) {
+            (Some(scale), true) => {
+                let zeros = vec![0; self.p_to_ql.from.len()];
+                let x_p = columns(&x_ql, zeros.len(), |z, out| scale.apply(&zeros, z, out));
+                let x_ql = self.p_to_ql.convert_all(&x_p);
+                (x_ql, x_p)
+            }
+            _ => {
+                let x_p = self.ql_to_p.convert_all(&x_ql);
+                (x_ql, x_p)
+            }
+        };
+        let mut extended = DcrtPoly::from_residues(self.qlp.clone(), &[x_ql, x_p].concat());
+        extended.switch_format();
+        extended
+    }
+
+    /// The tensor product `x` modulo `Q_l ∪ P` in coefficient format scaled
+    /// to `Q`
+    fn scale_down(&self, x: &DcrtPoly) -> DcrtPoly {
+        let x = x.residues();
+        let ql_len = self.ql.params().len();
+        let y = if self.over_q {
+            columns(&x, ql_len, |z, out| {
+                let (keep, dropped) = z.split_at(ql_len);
+                self.scale.apply(keep, dropped, out)
+            })
+        } else {
+            let y_p = columns(&x, x.len() - ql_len, |z, out| {
+                let (dropped, keep) = z.split_at(ql_len);
+                self.scale.apply(keep, dropped, out)
+            });
+            self.p_to_ql.convert_all(&y_p)
+        };
+        if self.drop.is_none() {
+            return DcrtPoly::from_residues(self.q.clone(), &y);
+        }
+        let len = y.first().map_or(0, Vec::len);
+        let mut out = vec![vec![0; len]; self.q.params().len()];
+        for (((o, yi), qi), &d) in out
+            .iter_mut()
+            .zip(&y)
+            .zip(&self.p_to_ql.to)
+            .zip(&self.expand)
+        {
+            for (o, &v) in o.iter_mut().zip(yi) {
+                *o = qi.mul_mod(v, d);
+            }
+        }
+        DcrtPoly::from_residues(self.q.clone(), &out)
+    }
+}
+
+/// The leading towers of `q` to multiply over when at most `droppable_bits`
+/// bits of the modulus can be dropped, at least one
+pub(crate) fn leveled_towers(q: &DcrtElementParams, droppable_bits: f64) -> usize {
+    let primes = moduli(q);
+    let mut towers = primes.len();
+    let mut dropped = 0.0;
+    while towers > 1 {
+        dropped += (primes[towers - 1] as f64).log2();
+        if dropped > droppable_bits {
+            break;
+        }
+        towers -= 1;
+    }
+    towers
+}
+
+/// Apply `f` to the residues of every coefficient, writing `towers`
+/// residues per coefficient
+fn columns(
+    residues: &[Vec<u64>],
+    towers: usize,
+    mut f: impl FnMut(&[u64], &mut [u64]),
+) -> Vec<Vec<u64>> {
+    let len = residues.first().map_or(0, Vec::len);
+    let mut column = vec![0; residues.len()];
+    let mut value = vec![0; towers];
+    let mut out = vec![Vec::with_capacity(len); towers];
+    for c in 0..len {
+        for (z, r) in column.iter_mut().zip(residues) {
+            *z = r[c];
+        }
+        f(&column, &mut value);
+        for (o, &v) in out.iter_mut().zip(&value) {
+            o.push(v);
+        }
+    }
+    out
+}
+
+/// Exact conversion of centered values between two bases.
+///
+/// The fast conversion `sum_i |x (F / f_i)^-1|_{f_i} F / f_i` overflows by
+/// `v F` for the integer `v` nearest to `sum_i |x (F / f_i)^-1|_{f_i} / f_i`,
+/// which is estimated in floating point and subtracted.
+#[derive(Clone, Debug)]
+pub(super) struct BasisConversion {
+    from: Vec<Barrett64>,
+    to: Vec<Barrett64>,
+    /// `|(F / f_i)^-1|_{f_i}`
+    hat_inv: Vec<u64>,
+    /// `|F / f_i|_{t_j}`, indexed by `j`
+    hat_mod: Vec<Vec<u64>>,
+    /// `|F|_{t_j}`
+    from_mod: Vec<u64>,
+    /// `1 / f_i`
+    reciprocals: Vec<f64>,
+}
+
+impl BasisConversion {
+    pub(super) fn new(from: &[u64], to: &[u64]) -> Result<Self> {
+        let big_f = from.iter().map(|&f| BigUint::from(f)).product::<BigUint>();
+        let hat = from.iter().map(|&f| &big_f / f).collect::<Vec<_>>();
+        Ok(Self {
+            from: from.iter().map(|&m| barrett(m)).collect::<Result<_>>()?,
+            to: to.iter().map(|&m| barrett(m)).collect::<Result<_>>()?,
+            hat_inv: from.iter().zip(&hat).map(|(&f, h)| inverse(h, f)).collect(),
+            hat_mod: to
+                .iter()
+                .map(|&m| hat.iter().map(|h| modulo(h, m)).collect())
+                .collect(),
+            from_mod: to.iter().map(|&m| modulo(&big_f, m)).collect(),
+            reciprocals: from.iter().map(|&f| 1.0 / f as f64).collect(),
+        })
+    }
+
+    /// Convert every coefficient of the tower by tower `residues`
+    pub(super) fn convert_all(&self, residues: &[Vec<u64>]) -> Vec<Vec<u64>> {
+        let mut scaled = vec![0; self.from.len()];
+        columns(residues, self.to.len(), |x, out| {
+            self.convert(x, &mut scaled, out)
+        })
+    }
+
+    /// The residues `out` in the target basis of the centered value with
+    /// residues `x`, using `scaled` as scratch space
+    fn convert(&self, x: &[u64], scaled: &mut [u64], out: &mut [u64]) {
+        let mut overflow = 0.0;
+        for (((s, &xi), fi), (&w, r)) in scaled
+            .iter_mut()
+            .zip(x)
+            .zip(&self.from)
+            .zip(self.hat_inv.iter().zip(&self.reciprocals))
+        {
+            *s = fi.mul_mod(xi, w);
+            overflow += *s as f64 * r;
+        }
+        let overflow = overflow.round() as u64;
+        for (((o, m), weights), &f) in out
+            .iter_mut()
+            .zip(&self.to)
+            .zip(&self.hat_mod)
+            .zip(&self.from_mod)
+        {
+            let sum = scaled.iter().zip(weights).fold(0, |acc, (&s, &w)| {
+                m.add_mod(acc, m.mul_mod(m.reduce_word(s), w))
+            });
+            *o = m.sub_mod(sum, m.mul_mod(m.reduce_word(overflow), f));
+        }
+    }
+}
+
+/// `round(f z / D)` modulo a kept basis `K` for `z` given modulo `K ∪ D`.
+///
+/// With the CRT, `f z / D` is `z_k |f D^-1|_k` modulo each `k` plus
+/// `sum_d z_d (ω_d + θ_d)` for the integer and fractional parts of
+/// `f (KD / d) |(KD / d)^-1|_d / D`. The fractions are kept as 64 bit fixed
+/// point numbers, so the rounding is off by one in rare cases, which only
+/// adds to the noise.
+#[derive(Clone, Debug)]
+struct ScaleAndRound {
+    keep: Vec<Barrett64>,
+    /// `|f D^-1|_k`
+    factor: Vec<u64>,
+    /// `|ω_d|_k`, indexed by `k`
+    integer: Vec<Vec<u64>>,
+    /// `floor(2^64 θ_d)`
+    fraction: Vec<u64>,
+}
+
+impl ScaleAndRound {
+    fn new(keep: &[u64], drop: &[u64], factor: &BigUint) -> Result<Self> {
+        let big_k = keep.iter().map(|&k| BigUint::from(k)).product::<BigUint>();
+        let big_d = drop.iter().map(|&d| BigUint::from(d)).product::<BigUint>();
+        let big_kd = &big_k * &big_d;
+        let (integer, fraction): (Vec<_>, Vec<_>) = drop
+            .iter()
+            .map(|&d| {
+                // f (KD / d) |(KD / d)^-1|_d / D = f K |(KD / d)^-1|_d / d
+                let numerator = factor * &big_k * inverse(&(&big_kd / d), d);
+                let (integer, remainder) = numerator.div_rem(&BigUint::from(d));
+                let fraction = (remainder << 64u32) / d;
+                (
+                    keep.iter()
+                        .map(|&k| modulo(&integer, k))
+                        .collect::<Vec<_>>(),
+                    fraction.to_u64().expect("a fraction is below one"),
+                )
+            })
+            .unzip();
+        Ok(Self {
+            keep: keep.iter().map(|&m| barrett(m)).collect::<Result<_>>()?,
+            factor: keep
+                .iter()
+                .map(|&k| {
+                    let d_inv = inverse(&big_d, k);
+                    modulo(&(factor * d_inv), k)
+                })
+                .collect(),
+            integer: (0..keep.len())
+                .map(|k| integer.iter().map(|w: &Vec<u64>| w[k]).collect())
+                .collect(),
+            fraction,
+        })
+    }
+
+    /// The residues `out` modulo `K` of the rounded scaling of the value
+    /// with residues `keep` modulo `K` and `drop` modulo `D`
+    fn apply(&self, keep: &[u64], drop: &[u64], out: &mut [u64]) {
+        let (mut high, mut low) = (0u128, 0u128);
+        for (&z, &f) in drop.iter().zip(&self.fraction) {
+            let product = z as u128 * f as u128;
+            high += product >> 64;
+            low += product as u64 as u128;
+        }
+        let total = low + (1 << 63);
+        let rounded = high + (total >> 64);
+        for (((o, k), (&z, &f)), weights) in out
+            .iter_mut()
+            .zip(&self.keep)
+            .zip(keep.iter().zip(&self.factor))
+            .zip(&self.integer)
+        {
+            let sum = drop.iter().zip(weights).fold(
+                k.add_mod(k.mul_mod(z, f), (rounded % k.modulus() as u128) as u64),
+                |acc, (&zd, &w)| k.add_mod(acc, k.mul_mod(k.reduce_word(zd), w)),
+            );
+            *o = sum;
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! Hybrid key switching in RNS
+//!
+//! Han and Ki, "Better Bootstrapping for Approximate Homomorphic Encryption"
+//! (CT-RSA 2020). The towers of `Q` are partitioned into large digits `Q_j`
+//! of `alpha` towers each. A digit of the element is extended exactly from
+//! `Q_j` to the other towers and an auxiliary basis `P` at least as large as
+//! any `Q_j`, and multiplies its encryption of `P old` under the new secret
+//! over `P ∪ Q`. The sum is divided by `P` and rounded back to `Q`, which
+//! also divides the key noise, so few large digits suffice.
+
+use super::hps::BasisConversion;
+use super::{auxiliary_basis, barrett, inverse, joined, moduli, modulo};
+use crate::constants::{MAX_MODULUS_SIZE, PolynomialRingFormat};
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::core::lattice::params::DcrtElementParams;
+use crate::core::math::{Barrett64, scratch};
+use crate::error::{Error, Result};
+use crate::pke::ZeroEncryption;
+use crate::pke::encryption_pool::in_format_of;
+use crate::pke::scheme::utils::compute_num_large_digits;
+use num::BigUint;
+use rand::CryptoRng;
+
+/// The constants for switching elements over `Q_l` with keys over `P ∪ Q`
+#[derive(Clone, Debug)]
+pub(crate) struct Hybrid {
+    /// The towers of the element
+    ql: DcrtElementParams,
+    /// `P` followed by `Q_l`
+    pql: DcrtElementParams,
+    p_len: usize,
+    alpha: usize,
+    /// One conversion per digit, to `P` and the other towers of `Q_l`
+    mod_up: Vec<BasisConversion>,
+    p_to_ql: BasisConversion,
+    ql_moduli: Vec<Barrett64>,
+    /// `|P^-1|_{q_i}`
+    p_inv: Vec<u64>,
+}
+
+impl Hybrid {
+    /// The towers per digit when `towers` are split into
+    /// `num_large_digits` digits, derived from the towers for zero
+    pub(crate) fn towers_per_digit(towers: usize, num_large_digits: usize) -> usize {
+        towers
+            .div_ceil(compute_num_large_digits(
+                num_large_digits,
+                towers.saturating_sub(1),
+            ))
+            .max(1)
+    }
+
+    /// The bits of the auxiliary basis `P` for towers of `tower_bits` bits
+    /// in digits of `alpha` towers, which the security level has to cover
+    /// along with `Q`
+    pub(crate) fn aux_bits(tower_bits: &[usize], alpha: usize) -> usize {
+        let bits = tower_bits
+            .chunks(alpha.max(1))
+            .map(|digit| digit.iter().sum::<usize>())
+            .max()
+            .unwrap_or(0);
+        bits.div_ceil(MAX_MODULUS_SIZE - 1).max(1) * MAX_MODULUS_SIZE
+    }
+
+    /// Encryptions under `new` over `P ∪ Q` of `P old` restricted to each
+    /// digit of `alpha` towers, both secrets in evaluation format
+    pub(crate) fn key_gen(
+        old: &DcrtPoly,
+        new: &DcrtPoly,
+        alpha: usize,
+        std_dev: f64,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Vec<[DcrtPoly; 2]>> {
+        if old.params() != new.params() || alpha == 0 {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        let q = new.params();
+        let q_moduli = moduli(q);
+        let p = auxiliary_basis(q, p_towers(&q_moduli, alpha))?;
+        let big_p = p.ciphertext_composite_modulus();
+        let pq = joined(&p, q)?;
+        let p_len = p.params().len();
+
+        let mut secret = new.clone();
+        if secret.format() == PolynomialRingFormat::Evaluation {
+            secret.switch_format();
+        }
+        let mut secret = DcrtPoly::from_big_signed(pq.clone(), &secret.to_signed());
+        secret.switch_format();
+        let mut old = old.clone();
+        if old.format() == PolynomialRingFormat::Evaluation {
+            old.switch_format();
+        }
+        let len = old.towers().first().map_or(0, |t| t.len());
+        let old = in_format_of(
+            DcrtPoly::from_residues(
+                pq.clone(),
+                &[vec![vec![0; len]; p_len], old.residues()].concat(),
+            ),
+            &secret,
+        );
+
+        let mut key = Vec::new();
+        for digit in q_moduli
+            .chunks(alpha)
+            .enumerate()
+            .map(|(j, d)| j * alpha..j * alpha + d.len())
+        {
+            // The constant `P` in the towers of the digit and zero elsewhere
+            let mut gadget = vec![vec![0; len]; p_len + q_moduli.len()];
+            for i in digit {
+                gadget[p_len + i][0] = modulo(big_p, q_moduli[i]);
+            }
+            let mut gadget = in_format_of(DcrtPoly::from_residues(pq.clone(), &gadget), &secret);
+            gadget *= &old;
+            let zero = ZeroEncryption::with_secret_key_and_rng(&secret, std_dev, rng)?;
+            key.push(zero.encrypt(&gadget));
+        }
+        Ok(key)
+    }
+
+    /// The constants for elements over `ql` and keys of `alpha` towers per
+    /// digit over `pq`, whose towers are `P` followed by `Q ⊇ Q_l`
+    pub(crate) fn new(
+        ql: &DcrtElementParams,
+        pq: &DcrtElementParams,
+        alpha: usize,
+    ) -> Result<Self> {
+        let ql_moduli = moduli(ql);
+        let pq_moduli = moduli(pq);
+        let p_len = ql_moduli
+            .first()
+            .and_then(|q0| pq_moduli.iter().position(|m| m == q0))
+            .ok_or(Error::DcrtElementParamsMismatch)?;
+        if alpha == 0 || !pq_moduli[p_len..].starts_with(&ql_moduli) {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        let p_moduli = &pq_moduli[..p_len];
+        let pql = pq.shrink(p_len + ql_moduli.len());
+        let big_p = p_moduli
+            .iter()
+            .map(|&m| BigUint::from(m))
+            .product::<BigUint>();
+
+        let mut mod_up = Vec::new();
+        for start in (0..ql_moduli.len()).step_by(alpha) {
+            let end = (start + alpha).min(ql_moduli.len());
+            let to = p_moduli
+                .iter()
+                .chain(&ql_moduli[..start])
+                .chain(&ql_moduli[end..])
+                .copied()
+                .collect::<Vec<_>>();
+            mod_up.push(BasisConversion::new(&ql_moduli[start..end], &to)?);
+        }
+        Ok(Self {
+            ql: ql.clone(),
+            pql,
+            p_len,
+            alpha,
+            mod_up,
+            p_to_ql: BasisConversion::new(p_moduli, &ql_moduli)?,
+            ql_moduli: ql_moduli
+                .iter()
+                .map(|&m| barrett(m))
+                .collect::<Result<_>>()?,
+            p_inv: ql_moduli.iter().map(|&m| inverse(&big_p, m)).collect(),
+        })
+    }
+
+    /// The pair over `Q_l` decrypting under the new secret of `key` like `c`
+    /// under the old one, in evaluation format
+    pub(crate) fn switch(&self, c: &DcrtPoly, key: &[[DcrtPoly; 2]]) -> Result<[DcrtPoly; 2]> {
+        self.key_product(&self.mod_up(c)?, key)
+    }
+
+    /// The digits of `c` over `Q_l` extended to `P ∪ Q_l`, in evaluation
+    /// format
+    pub(crate) fn mod_up(&self, c: &DcrtPoly) -> Result<Vec<DcrtPoly>> {
+        if c.params() != &self.ql {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        let mut coefficients = c.clone();
+        if coefficients.format() == PolynomialRingFormat::Evaluation {
+            coefficients.switch_format();
+        }
+        let x = coefficients.residues();
+        Ok(self
+            .mod_up
+            .iter()
+            .enumerate()
+            .map(|(j, conversion)| {
+                let start = j * self.alpha;
+                let end = (start + self.alpha).min(x.len());
+                let converted = conversion.convert_all(&x[start..end]);
+                let (p, rest) = converted.split_at(self.p_len);
+                let (before, after) = rest.split_at(start);
+                let residues = [p, before, &x[start..end], after].concat();
+                let mut extended = DcrtPoly::from_residues(self.pql.clone(), &residues);
+                extended.switch_format();
+                extended
+            })
+            .collect())
+    }
+
+    /// The inner product of the extended `digits` with `key`, divided by `P`
+    pub(crate) fn key_product(
+        &self,
+        digits: &[DcrtPoly],
+        key: &[[DcrtPoly; 2]],
+    ) -> Result<[DcrtPoly; 2]> {
+        if digits.len() != self.mod_up.len() || key.len() < digits.len() {
+            return Err(Error::CcParams(format!(
+                "a key of {} digits cannot switch an element of {} digits",
+                key.len(),
+                self.mod_up.len()
+            )));
+        }
+        let mut sums = [(); 2].map(|_| DcrtPoly::zero(self.pql.clone()));
+        for (digit, pair) in digits.iter().zip(key) {
+            if digit.params() != &self.pql {
+                return Err(Error::DcrtElementParamsMismatch);
+            }
+            for (sum, k) in sums.iter_mut().zip(pair) {
+                let k = k.shrink(self.pql.params().len());
+                if k.params() != &self.pql {
+                    return Err(Error::DcrtElementParamsMismatch);
+                }
+                let mut term = in_format_of(digit.clone(), sum);
+                term *= &in_format_of(k, sum);
+                *sum += &term;
+            }
+        }
+        Ok(sums.map(|sum| self.mod_down(sum)))
+    }
+
+    /// `round(x / P)` over `Q_l` for `x` over `P ∪ Q_l` in evaluation format
+    fn mod_down(&self, mut x: DcrtPoly) -> DcrtPoly {
+        x.switch_format();
+        let x = x.residues();
+        let len = x.first().map_or(0, Vec::len);
+        let (x_p, x_ql) = x.split_at(self.p_len);
+        let converted = self.p_to_ql.convert_all(x_p);
+        let mut out = self
+            .ql_moduli
+            .iter()
+            .map(|_| scratch::take_residues(len))
+            .collect::<Vec<_>>();
+        for ((((o, xi), ci), qi), &p_inv) in out
+            .iter_mut()
+            .zip(x_ql)
+            .zip(&converted)
+            .zip(&self.ql_moduli)
+            .zip(&self.p_inv)
+        {
+            for ((o, &v), &c) in o.iter_mut().zip(xi).zip(ci) {
+                *o = qi.mul_mod(qi.sub_mod(v, c), p_inv);
+            }
+        }
+        let mut result = DcrtPoly::from_residues(self.ql.clone(), &out);
+        out.into_iter().for_each(scratch::give_residues);
+        result.switch_format();
+        result
+    }
+}
+
+/// The full size towers of `P`, enough to exceed the product of the
+/// largest digit of `alpha` towers
+fn p_towers(q_moduli: &[u64], alpha: usize) -> usize {
+    let bits = q_moduli
+        .chunks(alpha)
+        .map(|digit| digit.iter().map(|&q| (q as f64).log2()).sum::<f64>())
+        .fold(0.0, f64::max);
+    (bits / (MAX_MODULUS_SIZE - 1) as f64).ceil().max(1.0) as usize
+}
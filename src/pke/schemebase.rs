@@ -1 +1,3 @@
 mod base_fhe;
+
+pub use base_fhe::*;
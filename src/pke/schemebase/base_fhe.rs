@@ -1,6 +1,10 @@
-use crate::pke::crypto_context::CryptoContext;
-use std::collections::HashMap;
+use crate::ciphertext::Ciphertext;
+use crate::error::Result;
+use crate::pke::{CryptoParametersRns, EvalKey, PrivateKey};
+use rand::CryptoRng;
+use std::collections::BTreeMap;
 
+/// Bootstrapping, refreshing ciphertexts that ran out of levels
 pub trait FheBase {
     /// `level_budget` budgets for the amount of levels in encoding and decoding
     /// `dimension1` inner dimension in the base-step giant-step routing
@@ -10,13 +14,45 @@ pub trait FheBase {
     /// If set to 0, use default logic.
     /// `precompute` whether to precompute the plaintexts for encoding and decoding
     fn eval_bootstrap_setup(
-        cc: &CryptoContext,
+        &self,
+        params: &CryptoParametersRns,
         level_budget: &[usize],
         dimension1: &[usize],
         slots: usize,
         correction_factor: usize,
         precompute: bool,
-    );
+    ) -> Result<()>;
 
-    // fn eval_bootstrap_key_gen() -> HashMap<usize, EvalKey<Element>>;
+    /// Generate the automorphism keys bootstrapping `slots` slots needs and
+    /// store them under the key tag of `private_key`
+    fn eval_bootstrap_key_gen(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        slots: usize,
+    ) -> Result<BTreeMap<u64, EvalKey>> {
+        self.eval_bootstrap_key_gen_with_rng(params, private_key, slots, &mut rand::rng())
+    }
+
+    /// [`FheBase::eval_bootstrap_key_gen`] drawing randomness from `rng`
+    fn eval_bootstrap_key_gen_with_rng(
+        &self,
+        params: &CryptoParametersRns,
+        private_key: &PrivateKey,
+        slots: usize,
+        rng: &mut impl CryptoRng,
+    ) -> Result<BTreeMap<u64, EvalKey>>;
+
+    /// Refresh a ciphertext to the highest level bootstrapping leaves
+    /// `iterations` number of bootstrapping passes, more than one corrects
+    /// the error of the previous pass
+    /// `precision` bits of precision of a single pass, used to scale the
+    /// error up between passes
+    fn eval_bootstrap(
+        &self,
+        params: &CryptoParametersRns,
+        ciphertext: &Ciphertext,
+        iterations: usize,
+        precision: usize,
+    ) -> Result<Ciphertext>;
 }
@@ -0,0 +1,51 @@
+//! Fixtures shared by the unit tests
+//!
+//! The schemes run over a fixed ring dimension of 2048 without a security
+//! level, which keeps key generation fast. Tests tweak the returned
+//! builders for anything else they need.
+
+use crate::constants::SecurityLevel;
+use crate::pke::{BfvParamsGen, BfvRns, CcParams, CkksParamsGen, CkksRns};
+
+/// The ring dimension of every fixture
+pub(crate) const RING_DIMENSION: usize = 2048;
+
+/// Settings without a security level, so small rings are accepted
+pub(crate) fn cc_params() -> CcParams {
+    CcParams {
+        security_level: SecurityLevel::HeStdNotSet,
+        ..Default::default()
+    }
+}
+
+/// BFV parameters for `t = 65537` at depth zero
+pub(crate) fn bfv_params() -> BfvParamsGen {
+    BfvParamsGen::new(65537)
+        .cc_params(cc_params())
+        .ring_dimension(RING_DIMENSION)
+}
+
+/// A BFV scheme built from [`bfv_params`] at `depth`
+pub(crate) fn bfv_context(depth: usize) -> BfvRns {
+    bfv_params()
+        .multiplicative_depth(depth)
+        .build()
+        .expect("parameters")
+}
+
+/// CKKS parameters with a 40 bit scaling factor at depth zero
+pub(crate) fn ckks_params() -> CkksParamsGen {
+    CkksParamsGen::new()
+        .cc_params(cc_params())
+        .scaling_mod_size(40)
+        .ring_dimension(RING_DIMENSION)
+}
+
+/// A CKKS scheme built from [`ckks_params`] at `depth` with eight slots
+pub(crate) fn ckks_context(depth: usize) -> CkksRns {
+    ckks_params()
+        .multiplicative_depth(depth)
+        .batch_size(8)
+        .build()
+        .expect("parameters")
+}
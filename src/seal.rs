@@ -0,0 +1,687 @@
+//! Import of Microsoft SEAL parameters and ciphertexts
+//!
+//! Only uncompressed blobs written by `save` with `compr_mode_type::none`
+//! are understood. Seeded ciphertexts must be expanded by SEAL before export.
+
+use crate::ActingPrimitive;
+use crate::ciphertext::Ciphertext;
+use crate::constants::PlaintextEncodingsType;
+use crate::core::lattice::dcrt_poly::DcrtPoly;
+use crate::core::lattice::params::DcrtElementParams;
+use crate::core::utils::{reverse_bits, root_of_unity};
+use crate::error::{Error, Result};
+use crate::pke::CryptoContext;
+use derive_more::{Display, FromStr, TryFrom};
+
+const SEAL_MAGIC: u16 = 0xA15E;
+const SEAL_HEADER_SIZE: usize = 0x10;
+
+/// The scheme stored in SEAL encryption parameters
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, Ord, PartialOrd, Hash, Display, FromStr, TryFrom,
+)]
+#[try_from(repr)]
+#[repr(usize)]
+pub enum SealScheme {
+    /// No scheme set
+    #[default]
+    None = 0,
+    /// Brakerski/Fan-Vercauteren
+    Bfv,
+    /// Cheon-Kim-Kim-Song
+    Ckks,
+    /// Brakerski-Gentry-Vaikuntanathan
+    Bgv,
+}
+
+hex_enum_usize!(SealScheme);
+
+/// The fixed size header SEAL writes in front of every serialized object
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SealHeader {
+    /// SEAL major version that wrote the object
+    pub version_major: u8,
+    /// SEAL minor version that wrote the object
+    pub version_minor: u8,
+    /// Compression mode, `0` means none
+    pub compr_mode: u8,
+    /// Total size of the object including the header
+    pub size: u64,
+}
+
+/// SEAL `EncryptionParameters`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SealEncryptionParameters {
+    /// The scheme
+    pub scheme: SealScheme,
+    /// The ring dimension `n`
+    pub poly_modulus_degree: usize,
+    /// The full coefficient modulus chain including the special prime
+    pub coeff_modulus: Vec<u64>,
+    /// The plaintext modulus, zero for CKKS
+    pub plain_modulus: u64,
+}
+
+impl SealEncryptionParameters {
+    /// Parse the output of `EncryptionParameters::save`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = SealReader::new(bytes);
+        let header = reader.header()?;
+        reader.truncate(&header)?;
+
+        let scheme = SealScheme::try_from(reader.u8()? as usize)?;
+        let poly_modulus_degree = reader.usize()?;
+        let coeff_modulus_size = reader.usize()?;
+        let mut coeff_modulus = Vec::with_capacity(coeff_modulus_size.min(reader.remaining()));
+        for _ in 0..coeff_modulus_size {
+            coeff_modulus.push(reader.modulus()?);
+        }
+        let plain_modulus = reader.modulus()?;
+
+        Ok(Self {
+            scheme,
+            poly_modulus_degree,
+            coeff_modulus,
+            plain_modulus,
+        })
+    }
+
+    /// The moduli a fresh ciphertext is defined over.
+    ///
+    /// SEAL reserves the last modulus as the key switching special prime
+    /// whenever the chain has more than one element.
+    pub fn data_moduli(&self) -> &[u64] {
+        match self.coeff_modulus.len() {
+            0 | 1 => &self.coeff_modulus,
+            n => &self.coeff_modulus[..n - 1],
+        }
+    }
+
+    /// Convert the data level moduli to this crate's tower parameters
+    pub fn to_element_params(&self) -> Result<DcrtElementParams> {
+        match self.scheme {
+            SealScheme::Bfv | SealScheme::Ckks => {}
+            scheme => {
+                return Err(Error::SealFormat(format!(
+                    "scheme `{}` is not supported",
+                    scheme
+                )));
+            }
+        }
+        if !self.poly_modulus_degree.is_power_of_two() {
+            return Err(Error::SealFormat(format!(
+                "poly modulus degree `{}` is not a power of two",
+                self.poly_modulus_degree
+            )));
+        }
+        DcrtElementParams::from_external_moduli(2 * self.poly_modulus_degree, self.data_moduli())
+    }
+}
+
+/// SEAL `Ciphertext` with its RNS data kept as raw residues
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SealCiphertext {
+    /// Hash of the parameters the ciphertext was created under
+    pub parms_id: [u64; 4],
+    /// Whether the residues are in NTT form
+    pub is_ntt_form: bool,
+    /// Number of polynomials
+    pub size: usize,
+    /// The ring dimension `n`
+    pub poly_modulus_degree: usize,
+    /// Number of towers per polynomial
+    pub coeff_modulus_size: usize,
+    /// CKKS scale
+    pub scale: f64,
+    /// BGV correction factor, one for older versions
+    pub correction_factor: u64,
+    /// Residues ordered by polynomial, then tower, then coefficient
+    pub data: Vec<u64>,
+}
+
+impl SealCiphertext {
+    /// Parse the output of `Ciphertext::save`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = SealReader::new(bytes);
+        let header = reader.header()?;
+        reader.truncate(&header)?;
+
+        let mut parms_id = [0u64; 4];
+        for id in parms_id.iter_mut() {
+            *id = reader.u64()?;
+        }
+        let is_ntt_form = reader.u8()? != 0;
+        let size = reader.usize()?;
+        let poly_modulus_degree = reader.usize()?;
+        let coeff_modulus_size = reader.usize()?;
+        let scale = f64::from_bits(reader.u64()?);
+        let correction_factor = if header.version_major >= 4 {
+            reader.u64()?
+        } else {
+            1
+        };
+
+        let expected = size
+            .checked_mul(poly_modulus_degree)
+            .and_then(|x| x.checked_mul(coeff_modulus_size))
+            .ok_or_else(|| Error::SealFormat("ciphertext dimensions overflow".to_string()))?;
+        let data_header = reader.header()?;
+        if data_header.compr_mode != 0 {
+            return Err(Error::SealFormat(format!(
+                "compression mode `{}` is not supported",
+                data_header.compr_mode
+            )));
+        }
+        let len = reader.usize()?;
+        if len != expected {
+            return Err(Error::SealFormat(format!(
+                "expected `{}` residues but found `{}`, seeded ciphertexts are not supported",
+                expected, len
+            )));
+        }
+        if reader.remaining() < len * 8 {
+            return Err(Error::SealFormat(
+                "ciphertext data is truncated".to_string(),
+            ));
+        }
+        let mut data = Vec::with_capacity(len);
+        for _ in 0..len {
+            data.push(reader.u64()?);
+        }
+
+        Ok(Self {
+            parms_id,
+            is_ntt_form,
+            size,
+            poly_modulus_degree,
+            coeff_modulus_size,
+            scale,
+            correction_factor,
+            data,
+        })
+    }
+
+    /// The residues of `polynomial` in `tower`
+    pub fn residues(&self, polynomial: usize, tower: usize) -> &[u64] {
+        let start = (polynomial * self.coeff_modulus_size + tower) * self.poly_modulus_degree;
+        &self.data[start..start + self.poly_modulus_degree]
+    }
+
+    /// Check the ciphertext can be interpreted under `params`.
+    ///
+    /// The towers must match the leading moduli of `params` in order and every
+    /// residue must be reduced.
+    pub fn check_compatible(&self, params: &DcrtElementParams) -> Result<()> {
+        let expected = self
+            .size
+            .checked_mul(self.poly_modulus_degree)
+            .and_then(|x| x.checked_mul(self.coeff_modulus_size));
+        if expected != Some(self.data.len()) {
+            return Err(Error::SealFormat(format!(
+                "the dimensions do not match the `{}` residues",
+                self.data.len()
+            )));
+        }
+        let towers = params.params();
+        if towers.len() < self.coeff_modulus_size {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        for (tower, p) in towers.iter().take(self.coeff_modulus_size).enumerate() {
+            if p.ring_dimension != self.poly_modulus_degree {
+                return Err(Error::DcrtElementParamsMismatch);
+            }
+            let q = p.ciphertext_modulus.get().to_primitive();
+            for polynomial in 0..self.size {
+                if self.residues(polynomial, tower).iter().any(|r| *r >= q) {
+                    return Err(Error::SealFormat(format!(
+                        "residue not reduced modulo `{}`",
+                        q
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Convert to a ciphertext of `crypto_context` over the leading towers
+    /// of `params`, whose first tower must be the one of the context.
+    ///
+    /// SEAL keeps NTT form residues in bit-reversed order of the evaluations
+    /// at `psi^(2i + 1)` for the minimal primitive `2n`-th root of unity
+    /// `psi`. They are reordered into evaluation format, which needs the
+    /// towers of `params` to use the same roots, as the moduli imported by
+    /// [`SealEncryptionParameters::to_element_params`] do.
+    pub fn to_ciphertext(
+        &self,
+        crypto_context: &CryptoContext,
+        params: &DcrtElementParams,
+        scheme: SealScheme,
+    ) -> Result<Ciphertext> {
+        let encoding_type = match scheme {
+            SealScheme::Bfv => PlaintextEncodingsType::CoefficientPacked,
+            SealScheme::Ckks => PlaintextEncodingsType::CkksPacked,
+            scheme => {
+                return Err(Error::SealFormat(format!(
+                    "scheme `{}` is not supported",
+                    scheme
+                )));
+            }
+        };
+        if params.params().front() != Some(&crypto_context.element_params) {
+            return Err(Error::DcrtElementParamsMismatch);
+        }
+        self.check_compatible(params)?;
+        let params = params.shrink(self.coeff_modulus_size);
+        if self.is_ntt_form {
+            for p in params.params() {
+                if p.root_of_unity != root_of_unity(p.cyclotomic_order, p.ciphertext_modulus) {
+                    return Err(Error::SealFormat(format!(
+                        "NTT form residues modulo `{}` need the root of unity SEAL uses",
+                        p.ciphertext_modulus.get().to_primitive()
+                    )));
+                }
+            }
+        }
+
+        let bits = self.poly_modulus_degree.trailing_zeros() as usize;
+        let elements = (0..self.size)
+            .map(|polynomial| {
+                let residues = (0..self.coeff_modulus_size)
+                    .map(|tower| {
+                        let residues = self.residues(polynomial, tower);
+                        if self.is_ntt_form {
+                            (0..residues.len())
+                                .map(|i| residues[reverse_bits(i, bits)])
+                                .collect()
+                        } else {
+                            residues.to_vec()
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                if self.is_ntt_form {
+                    DcrtPoly::from_evaluations(params.clone(), &residues)
+                } else {
+                    DcrtPoly::from_residues(params.clone(), &residues)
+                }
+            })
+            .collect();
+        let scaling_factor = match scheme {
+            SealScheme::Ckks => self.scale,
+            _ => 1.0,
+        };
+        let mut ciphertext = Ciphertext::new(
+            crypto_context.clone(),
+            elements,
+            encoding_type,
+            scaling_factor,
+        )?;
+        if scheme == SealScheme::Ckks {
+            ciphertext.set_slots(crypto_context.encoding_params.batch_size);
+        }
+        Ok(ciphertext)
+    }
+}
+
+struct SealReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> SealReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        if self.bytes.len() < N {
+            return Err(Error::SealFormat("unexpected end of input".to_string()));
+        }
+        let (head, tail) = self.bytes.split_at(N);
+        self.bytes = tail;
+        let mut out = [0u8; N];
+        out.copy_from_slice(head);
+        Ok(out)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn usize(&mut self) -> Result<usize> {
+        let v = self.u64()?;
+        usize::try_from(v).map_err(|_| Error::SealFormat(format!("length `{}` is too large", v)))
+    }
+
+    fn header(&mut self) -> Result<SealHeader> {
+        let magic = u16::from_le_bytes(self.take()?);
+        if magic != SEAL_MAGIC {
+            return Err(Error::SealFormat(format!("bad magic `{:#x}`", magic)));
+        }
+        let header_size = self.u8()? as usize;
+        if header_size != SEAL_HEADER_SIZE {
+            return Err(Error::SealFormat(format!(
+                "bad header size `{}`",
+                header_size
+            )));
+        }
+        let version_major = self.u8()?;
+        let version_minor = self.u8()?;
+        let compr_mode = self.u8()?;
+        let _reserved = self.take::<2>()?;
+        let size = self.u64()?;
+        if version_major < 3 {
+            return Err(Error::SealFormat(format!(
+                "SEAL version `{}.{}` is not supported",
+                version_major, version_minor
+            )));
+        }
+        Ok(SealHeader {
+            version_major,
+            version_minor,
+            compr_mode,
+            size,
+        })
+    }
+
+    /// Restrict the reader to the object described by `header`
+    fn truncate(&mut self, header: &SealHeader) -> Result<()> {
+        if header.compr_mode != 0 {
+            return Err(Error::SealFormat(format!(
+                "compression mode `{}` is not supported",
+                header.compr_mode
+            )));
+        }
+        let body = (header.size as usize)
+            .checked_sub(SEAL_HEADER_SIZE)
+            .filter(|body| *body <= self.bytes.len())
+            .ok_or_else(|| Error::SealFormat(format!("bad object size `{}`", header.size)))?;
+        self.bytes = &self.bytes[..body];
+        Ok(())
+    }
+
+    /// A nested `Modulus` object
+    fn modulus(&mut self) -> Result<u64> {
+        let header = self.header()?;
+        if header.compr_mode != 0 || header.size as usize != SEAL_HEADER_SIZE + 8 {
+            return Err(Error::SealFormat(format!(
+                "bad modulus object of size `{}`",
+                header.size
+            )));
+        }
+        self.u64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::lattice::params::DcrtElementParamsBuilder;
+    use crate::encoding::PlaintextEncodings;
+    use crate::pke::test_support::{bfv_context, ckks_context};
+
+    /// BFV parameters as SEAL 4.1 saves them for `n = 4096` with the default
+    /// coefficient moduli and the batching plain modulus `1032193`
+    const BFV_4096_PARAMS: [u8; 129] = [
+        0x5e, 0xa1, 0x10, 0x04, 0x01, 0x00, 0x00, 0x00, 0x81, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x5e, 0xa1, 0x10, 0x04, 0x01, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x01, 0xe0, 0xfe, 0xff, 0x0f, 0x00, 0x00, 0x00, 0x5e, 0xa1, 0x10,
+        0x04, 0x01, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x40,
+        0xfc, 0xff, 0x0f, 0x00, 0x00, 0x00, 0x5e, 0xa1, 0x10, 0x04, 0x01, 0x00, 0x00, 0x00, 0x18,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0xfe, 0xff, 0x1f, 0x00, 0x00, 0x00,
+        0x5e, 0xa1, 0x10, 0x04, 0x01, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x01, 0xc0, 0x0f, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    /// `body` behind the header SEAL 4.1 writes without compression
+    fn object(body: &[u8]) -> Vec<u8> {
+        let mut bytes = SEAL_MAGIC.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&[SEAL_HEADER_SIZE as u8, 4, 1, 0, 0, 0]);
+        bytes.extend_from_slice(&((SEAL_HEADER_SIZE + body.len()) as u64).to_le_bytes());
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    /// The output of SEAL's `Ciphertext::save`
+    fn ciphertext_bytes(ciphertext: &SealCiphertext) -> Vec<u8> {
+        let mut body = Vec::new();
+        for id in ciphertext.parms_id {
+            body.extend_from_slice(&id.to_le_bytes());
+        }
+        body.push(u8::from(ciphertext.is_ntt_form));
+        for v in [
+            ciphertext.size,
+            ciphertext.poly_modulus_degree,
+            ciphertext.coeff_modulus_size,
+        ] {
+            body.extend_from_slice(&(v as u64).to_le_bytes());
+        }
+        body.extend_from_slice(&ciphertext.scale.to_bits().to_le_bytes());
+        body.extend_from_slice(&ciphertext.correction_factor.to_le_bytes());
+        let mut data = (ciphertext.data.len() as u64).to_le_bytes().to_vec();
+        for v in &ciphertext.data {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        body.extend_from_slice(&object(&data));
+        object(&body)
+    }
+
+    /// `ciphertext` as SEAL holds it, in NTT form for CKKS
+    fn to_seal(ciphertext: &Ciphertext, is_ntt_form: bool) -> SealCiphertext {
+        let mut data = Vec::new();
+        for element in ciphertext.elements() {
+            let mut element = element.clone();
+            if element.format() == crate::constants::PolynomialRingFormat::Evaluation {
+                element.switch_format();
+            }
+            for (residues, p) in element.residues().iter().zip(element.params().params()) {
+                if is_ntt_form {
+                    let q = p.ciphertext_modulus.get().to_primitive();
+                    let psi = root_of_unity(p.cyclotomic_order, p.ciphertext_modulus);
+                    data.extend(seal_ntt(residues, psi.to_primitive(), q));
+                } else {
+                    data.extend_from_slice(residues);
+                }
+            }
+        }
+        let towers = ciphertext.element_params().params();
+        SealCiphertext {
+            parms_id: [1, 2, 3, 4],
+            is_ntt_form,
+            size: ciphertext.elements().len(),
+            poly_modulus_degree: towers[0].ring_dimension,
+            coeff_modulus_size: towers.len(),
+            scale: ciphertext.scaling_factor(),
+            correction_factor: 1,
+            data,
+        }
+    }
+
+    /// The evaluations at `psi^(2 rev(i) + 1)` SEAL stores at index `i`
+    fn seal_ntt(coefficients: &[u64], psi: u64, q: u64) -> Vec<u64> {
+        let mul = |a: u64, b: u64| (u128::from(a) * u128::from(b) % u128::from(q)) as u64;
+        let n = coefficients.len();
+        let bits = n.trailing_zeros() as usize;
+        let psi_squared = mul(psi, psi);
+        let mut odd_powers = Vec::with_capacity(n);
+        let mut x = psi;
+        for _ in 0..n {
+            odd_powers.push(x);
+            x = mul(x, psi_squared);
+        }
+        (0..n)
+            .map(|i| {
+                let x = odd_powers[reverse_bits(i, bits)];
+                coefficients
+                    .iter()
+                    .rev()
+                    .fold(0, |acc, c| (mul(acc, x) + c) % q)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_bfv_parameters() {
+        let params = SealEncryptionParameters::from_bytes(&BFV_4096_PARAMS).expect("parse");
+        assert_eq!(params.scheme, SealScheme::Bfv);
+        assert_eq!(params.poly_modulus_degree, 4096);
+        assert_eq!(
+            params.coeff_modulus,
+            vec![0xffffee001, 0xffffc4001, 0x1ffffe0001]
+        );
+        assert_eq!(params.plain_modulus, 1032193);
+        assert_eq!(params.data_moduli(), &[0xffffee001, 0xffffc4001]);
+
+        let element_params = params.to_element_params().expect("element parameters");
+        assert_eq!(element_params.params().len(), 2);
+        for (p, &q) in element_params.params().iter().zip(params.data_moduli()) {
+            assert_eq!(p.ring_dimension, 4096);
+            assert_eq!(p.ciphertext_modulus.get().to_primitive(), q);
+            assert_eq!(
+                p.root_of_unity,
+                root_of_unity(p.cyclotomic_order, p.ciphertext_modulus)
+            );
+        }
+    }
+
+    #[test]
+    fn truncated_input_is_rejected() {
+        for len in 0..BFV_4096_PARAMS.len() {
+            assert!(
+                matches!(
+                    SealEncryptionParameters::from_bytes(&BFV_4096_PARAMS[..len]),
+                    Err(Error::SealFormat(_))
+                ),
+                "a prefix of {len} bytes"
+            );
+        }
+
+        let ciphertext = SealCiphertext {
+            parms_id: [5, 6, 7, 8],
+            is_ntt_form: false,
+            size: 2,
+            poly_modulus_degree: 8,
+            coeff_modulus_size: 1,
+            scale: 1.0,
+            correction_factor: 1,
+            data: (0..16).collect(),
+        };
+        let bytes = ciphertext_bytes(&ciphertext);
+        assert_eq!(
+            SealCiphertext::from_bytes(&bytes).expect("parse"),
+            ciphertext
+        );
+        for len in 0..bytes.len() {
+            assert!(
+                matches!(
+                    SealCiphertext::from_bytes(&bytes[..len]),
+                    Err(Error::SealFormat(_))
+                ),
+                "a prefix of {len} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn bfv_ciphertexts_decrypt_after_import() {
+        let bfv = bfv_context(0);
+        let cc = bfv.crypto_context();
+        let keys = bfv.key_gen().expect("keys");
+        let plaintext = bfv.encode(&[1, 2, 3]).expect("encode");
+        let ciphertext = bfv.encrypt(keys.public_key(), &plaintext).expect("encrypt");
+
+        let seal = to_seal(&ciphertext, false);
+        let parsed = SealCiphertext::from_bytes(&ciphertext_bytes(&seal)).expect("parse");
+        assert_eq!(parsed, seal);
+        let imported = parsed
+            .to_ciphertext(cc, ciphertext.element_params(), SealScheme::Bfv)
+            .expect("import");
+        let PlaintextEncodings::Coefficient(decrypted) =
+            bfv.decrypt(keys.private_key(), &imported).expect("decrypt")
+        else {
+            panic!("a coefficient plaintext");
+        };
+        assert_eq!(decrypted.coefficients()[..4], [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn ntt_form_ckks_ciphertexts_decrypt_after_import() {
+        let ckks = ckks_context(1);
+        let cc = ckks.crypto_context();
+        let keys = ckks.key_gen().expect("keys");
+        let values = [1.0, -2.0, 3.0, 0.5, 4.0, -1.5, 2.5, 8.0];
+        let ciphertext = ckks
+            .encrypt(keys.public_key(), &ckks.encode(&values).expect("encode"))
+            .expect("encrypt");
+
+        let seal = to_seal(&ciphertext, true);
+        let parsed = SealCiphertext::from_bytes(&ciphertext_bytes(&seal)).expect("parse");
+        let imported = parsed
+            .to_ciphertext(cc, ciphertext.element_params(), SealScheme::Ckks)
+            .expect("import");
+        assert_eq!(imported.scaling_factor(), ciphertext.scaling_factor());
+        let PlaintextEncodings::Ckks(decrypted) = ckks
+            .decrypt(keys.private_key(), &imported)
+            .expect("decrypt")
+        else {
+            panic!("a CKKS plaintext");
+        };
+        for (x, expected) in decrypted.values().iter().zip(values) {
+            assert!((x - expected).abs() < 1e-4, "{x} != {expected}");
+        }
+    }
+
+    #[test]
+    fn mismatched_moduli_are_rejected() {
+        let bfv = bfv_context(0);
+        let cc = bfv.crypto_context();
+        let keys = bfv.key_gen().expect("keys");
+        let plaintext = bfv.encode(&[1, 2, 3]).expect("encode");
+        let ciphertext = bfv.encrypt(keys.public_key(), &plaintext).expect("encrypt");
+        let params = ciphertext.element_params();
+        let seal = to_seal(&ciphertext, false);
+
+        // Towers of another chain
+        let other = DcrtElementParamsBuilder::new(params.params()[0].cyclotomic_order)
+            .depth(params.params().len())
+            .bits(30)
+            .build()
+            .expect("parameters");
+        assert!(matches!(
+            seal.to_ciphertext(cc, &other, SealScheme::Bfv),
+            Err(Error::DcrtElementParamsMismatch)
+        ));
+
+        // More towers than the parameters have
+        let mut extra = seal.clone();
+        extra.coeff_modulus_size += params.params().len();
+        extra.data = extra.data.repeat(2);
+        assert!(matches!(
+            extra.to_ciphertext(cc, params, SealScheme::Bfv),
+            Err(Error::DcrtElementParamsMismatch)
+        ));
+
+        // A residue past its modulus
+        let mut unreduced = seal.clone();
+        unreduced.data[0] = params.params()[0].ciphertext_modulus.get().to_primitive();
+        assert!(matches!(
+            unreduced.to_ciphertext(cc, params, SealScheme::Bfv),
+            Err(Error::SealFormat(_))
+        ));
+
+        // Residues that do not fill the dimensions
+        let mut short = seal;
+        short.data.pop();
+        assert!(matches!(
+            short.to_ciphertext(cc, params, SealScheme::Bfv),
+            Err(Error::SealFormat(_))
+        ));
+    }
+}
@@ -1,7 +1,9 @@
+use serde::de::{self, DeserializeSeed, IgnoredAny, SeqAccess, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 pub mod monty_params {
     use super::*;
+    use crate::core::math::cached_monty_params;
     use crypto_bigint::{Concat, Encoding, Odd, Split, Uint, modular::MontyParams};
     use serde::de::Error;
     use subtle::CtOption;
@@ -26,6 +28,305 @@ pub mod monty_params {
         let modulus = Uint::<LIMBS>::deserialize(deserializer)?;
         let modulus = Option::<Odd<Uint<LIMBS>>>::from(CtOption::from(modulus.to_odd()))
             .ok_or(Error::custom("modulus is not odd"))?;
-        Ok(MontyParams::new(modulus))
+        Ok(cached_monty_params(modulus))
+    }
+}
+
+/// Compact encoding of a modulus and its residues for binary formats.
+///
+/// Layout: the value count as a little endian `u64`, then the modulus and
+/// every value as `LIMBS` little endian words each.
+pub mod compact_uints {
+    use crypto_bigint::{Uint, Word};
+    use serde::de::{Error, SeqAccess, Visitor};
+    use std::fmt::Formatter;
+
+    const WORD_BYTES: usize = size_of::<Word>();
+
+    /// Encode `modulus` followed by `values`
+    pub fn encode<const LIMBS: usize>(modulus: &Uint<LIMBS>, values: &[Uint<LIMBS>]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + (values.len() + 1) * LIMBS * WORD_BYTES);
+        bytes.extend_from_slice(&(values.len() as u64).to_le_bytes());
+        for value in std::iter::once(modulus).chain(values) {
+            for word in value.as_words() {
+                bytes.extend_from_slice(&word.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Decode the modulus and values written by [`encode`]
+    pub fn decode<E: Error, const LIMBS: usize>(
+        bytes: &[u8],
+    ) -> Result<(Uint<LIMBS>, Vec<Uint<LIMBS>>), E> {
+        let uint_bytes = LIMBS * WORD_BYTES;
+        let (count, rest) = bytes
+            .split_first_chunk::<8>()
+            .ok_or_else(|| E::invalid_length(bytes.len(), &"a length prefix"))?;
+        let count = usize::try_from(u64::from_le_bytes(*count))
+            .map_err(|_| E::custom("value count too large"))?;
+        if count.checked_add(1).and_then(|n| n.checked_mul(uint_bytes)) != Some(rest.len()) {
+            return Err(E::invalid_length(
+                rest.len(),
+                &"the modulus and every value",
+            ));
+        }
+
+        let mut uints = rest.chunks_exact(uint_bytes).map(|chunk| {
+            let mut words: [Word; LIMBS] = [0; LIMBS];
+            for (word, b) in words.iter_mut().zip(chunk.chunks_exact(WORD_BYTES)) {
+                let mut le = [0u8; WORD_BYTES];
+                le.copy_from_slice(b);
+                *word = Word::from_le_bytes(le);
+            }
+            Uint::from_words(words)
+        });
+        let modulus = uints.next().ok_or_else(|| E::custom("missing modulus"))?;
+        Ok((modulus, uints.collect()))
+    }
+
+    /// The encoded length of a modulus and `values` values
+    pub fn encoded_len<const LIMBS: usize>(values: usize) -> usize {
+        values
+            .saturating_add(1)
+            .saturating_mul(LIMBS * WORD_BYTES)
+            .saturating_add(8)
+    }
+
+    /// Collects a byte string of at most `max_len` bytes from formats with
+    /// or without native byte support
+    pub struct BytesVisitor {
+        /// The longest accepted byte string
+        pub max_len: usize,
+    }
+
+    impl BytesVisitor {
+        fn check<E: Error>(&self, len: usize) -> Result<(), E> {
+            if len > self.max_len {
+                return Err(E::invalid_length(len, self));
+            }
+            Ok(())
+        }
+    }
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+            write!(f, "a byte string of at most {} bytes", self.max_len)
+        }
+
+        fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            self.check(v.len())?;
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            self.check(v.len())?;
+            Ok(v)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            self.check(seq.size_hint().unwrap_or(0))?;
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(b) = seq.next_element()? {
+                bytes.push(b);
+                self.check(bytes.len())?;
+            }
+            Ok(bytes)
+        }
+    }
+}
+
+/// Deserializes a sequence of at most `max_len` elements with `seed`,
+/// failing on the first element past the limit without building it
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct BoundedSeq<S> {
+    /// The seed of every element
+    pub seed: S,
+    /// The most elements accepted
+    pub max_len: usize,
+    /// What the elements are, for error messages
+    pub what: &'static str,
+}
+
+impl<'de, S: DeserializeSeed<'de> + Clone> DeserializeSeed<'de> for BoundedSeq<S> {
+    type Value = Vec<S::Value>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de> + Clone> Visitor<'de> for BoundedSeq<S> {
+    type Value = Vec<S::Value>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "at most {} {}", self.max_len, self.what)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let hint = seq.size_hint().unwrap_or(0);
+        if hint > self.max_len {
+            return Err(de::Error::invalid_length(hint, &self));
+        }
+        let mut values = Vec::with_capacity(hint.min(4096));
+        while values.len() < self.max_len {
+            match seq.next_element_seed(self.seed.clone())? {
+                Some(value) => values.push(value),
+                None => return Ok(values),
+            }
+        }
+        if seq.next_element::<IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(self.max_len + 1, &self));
+        }
+        Ok(values)
+    }
+}
+
+/// Deserializes a struct field identifier as its index in `fields`, `None`
+/// for fields that are not known and are to be ignored
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct FieldIndex(pub &'static [&'static str]);
+
+impl<'de> DeserializeSeed<'de> for FieldIndex {
+    type Value = Option<usize>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FieldIndex {
+    type Value = Option<usize>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "one of the fields {:?}", self.0)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(usize::try_from(v).ok().filter(|&i| i < self.0.len()))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(self.0.iter().position(|field| *field == v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(self.0.iter().position(|field| field.as_bytes() == v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ciphertext::Ciphertext;
+    use crate::core::lattice::dcrt_poly::DcrtPoly;
+    use crate::core::lattice::params::DcrtElementParamsBuilder;
+    use crate::core::lattice::poly::Poly;
+    use crate::core::math::{BoundedVecMod, VecModStd};
+    use crate::pke::test_support::bfv_context;
+    use bincode::Options;
+    use crypto_bigint::{Odd, U64, U128};
+    use serde::Serialize;
+    use serde::de::{DeserializeOwned, DeserializeSeed};
+
+    const Q: u64 = 97;
+
+    /// `value` after a trip through the compact and the human readable form
+    fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> T {
+        let bytes = bincode::serialize(value).expect("serialize");
+        let json = serde_json::to_vec(value).expect("serialize");
+        assert!(
+            bytes.len() < json.len(),
+            "compact {} bytes, human readable {}",
+            bytes.len(),
+            json.len()
+        );
+        let value: T = bincode::deserialize(&bytes).expect("deserialize");
+        serde_json::from_slice(&serde_json::to_vec(&value).expect("serialize"))
+            .expect("deserialize")
+    }
+
+    fn vec_mod() -> VecModStd {
+        VecModStd::from_signed(&[1, -2, 3, 96], Odd::new(U64::from_u64(Q)).expect("odd"))
+    }
+
+    /// The compact encoding of [`vec_mod`] with `edit` applied to its words:
+    /// the byte length, the value count, the modulus and the values
+    fn edited(edit: impl FnOnce(&mut [u64])) -> Vec<u8> {
+        let bytes = bincode::serialize(&vec_mod()).expect("serialize");
+        let mut words = bytes
+            .chunks_exact(8)
+            .map(|w| u64::from_le_bytes(w.try_into().expect("a word")))
+            .collect::<Vec<_>>();
+        edit(&mut words);
+        words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    fn rejected(bytes: &[u8]) -> String {
+        bincode::deserialize::<VecModStd>(bytes)
+            .expect_err("invalid encoding accepted")
+            .to_string()
+    }
+
+    #[test]
+    fn compact_round_trips() {
+        let values = vec_mod();
+        assert_eq!(round_trip(&values), values);
+
+        let params = DcrtElementParamsBuilder::new(16)
+            .depth(1)
+            .bits(30)
+            .build()
+            .expect("parameters");
+        let signed = (0..8).map(|i| i * 7 - 20).collect::<Vec<i64>>();
+        let poly = Poly::from_signed(params[0], &signed);
+        assert_eq!(round_trip(&poly), poly);
+        let dcrt_poly = DcrtPoly::from_signed(params, &signed);
+        assert_eq!(round_trip(&dcrt_poly), dcrt_poly);
+
+        let bfv = bfv_context(0);
+        let keys = bfv.key_gen().expect("keys");
+        let plaintext = bfv.encode(&[1, 2, 3]).expect("encode");
+        let ciphertext = bfv.encrypt(keys.public_key(), &plaintext).expect("encrypt");
+        let copy: Ciphertext = round_trip(&ciphertext);
+        assert_eq!(copy.elements(), ciphertext.elements());
+        assert_eq!(copy.element_params(), ciphertext.element_params());
+        assert_eq!(copy.encoding_type(), ciphertext.encoding_type());
+    }
+
+    #[test]
+    fn malformed_compact_encodings_are_rejected() {
+        assert!(bincode::deserialize::<VecModStd>(&edited(|_| {})).is_ok());
+
+        // More values than the bytes hold, in the value count or byte length
+        let message = rejected(&edited(|w| w[1] = 1 << 40));
+        assert!(message.contains("the modulus and every value"), "{message}");
+        rejected(&edited(|w| w[1] = u64::MAX));
+        rejected(&edited(|w| w[0] = u64::MAX));
+
+        for modulus in [0, 96] {
+            let message = rejected(&edited(|w| w[2] = modulus));
+            assert!(message.contains("modulus is not odd"), "{message}");
+        }
+
+        for value in [Q, Q + 1, u64::MAX] {
+            let message = rejected(&edited(|w| w[3] = value));
+            assert!(message.contains("reduced by the modulus"), "{message}");
+        }
+    }
+
+    #[test]
+    fn bounded_vec_mods_reject_longer_inputs() {
+        let bytes = bincode::serialize(&vec_mod()).expect("serialize");
+        let options = bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes();
+        let deserialize = |max_len| {
+            BoundedVecMod::<{ U64::LIMBS }, { U128::LIMBS }>::new(max_len)
+                .deserialize(&mut bincode::Deserializer::from_slice(&bytes, options))
+        };
+        assert_eq!(deserialize(4).expect("four values"), vec_mod());
+        assert!(deserialize(3).is_err());
     }
 }
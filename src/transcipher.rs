@@ -0,0 +1,381 @@
+//! Transciphering hooks
+//!
+//! A client encrypts its data under a cheap symmetric stream cipher and sends
+//! the symmetric key once under FHE. The server evaluates the keystream
+//! homomorphically and adds it to the symmetric ciphertext bits, which leaves
+//! FHE encryptions of the data without the client ever sending FHE ciphertexts
+//! per message.
+//!
+//! The ciphers are written against [`BitEvaluator`] so the same round function
+//! runs in the clear on the client with [`PlainBitEvaluator`] and over BFV
+//! ciphertexts on the server with [`BfvBitEvaluator`].
+
+use crate::ciphertext::Ciphertext;
+use crate::error::{Error, Result};
+use crate::pke::{BfvRns, PublicKey};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+/// Boolean circuit operations over some representation of a bit
+pub trait BitEvaluator {
+    /// The representation of a single bit, e.g. a ciphertext
+    type Bit: Clone;
+
+    /// A bit with the public value `bit`, e.g. a fresh encryption
+    fn constant(&self, bit: bool) -> Result<Self::Bit>;
+    /// `a ^ b`
+    fn xor(&self, a: &Self::Bit, b: &Self::Bit) -> Result<Self::Bit>;
+    /// `a ^ b` where `b` is public
+    fn xor_const(&self, a: &Self::Bit, b: bool) -> Result<Self::Bit>;
+    /// `a & b`
+    fn and(&self, a: &Self::Bit, b: &Self::Bit) -> Result<Self::Bit>;
+}
+
+/// Evaluates circuits on plain booleans, used on the client side
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PlainBitEvaluator;
+
+impl BitEvaluator for PlainBitEvaluator {
+    type Bit = bool;
+
+    fn constant(&self, bit: bool) -> Result<bool> {
+        Ok(bit)
+    }
+
+    fn xor(&self, a: &bool, b: &bool) -> Result<bool> {
+        Ok(a ^ b)
+    }
+
+    fn xor_const(&self, a: &bool, b: bool) -> Result<bool> {
+        Ok(a ^ b)
+    }
+
+    fn and(&self, a: &bool, b: &bool) -> Result<bool> {
+        Ok(a & b)
+    }
+}
+
+/// Evaluates circuits on BFV ciphertexts of `0` or `1` in the first
+/// coefficient, used on the server side.
+///
+/// The plaintext modulus of [`BfvRns`] is odd, so `a ^ b` is computed as
+/// `a + b - 2ab` and costs a multiplication like `a & b`. Products are
+/// relinearized with the keys stored under the key tag of their operands,
+/// see [`BfvRns::eval_mult_key_gen`], and constants are encrypted under
+/// `public_key`.
+#[derive(Copy, Clone, Debug)]
+pub struct BfvBitEvaluator<'a> {
+    bfv: &'a BfvRns,
+    public_key: &'a PublicKey,
+}
+
+impl<'a> BfvBitEvaluator<'a> {
+    /// An evaluator for ciphertexts of `bfv` under `public_key`
+    pub fn new(bfv: &'a BfvRns, public_key: &'a PublicKey) -> Self {
+        Self { bfv, public_key }
+    }
+
+    /// Encrypt `bits` one per ciphertext, e.g. the symmetric key
+    pub fn encrypt(&self, bits: &[bool]) -> Result<Vec<Ciphertext>> {
+        bits.iter().map(|&bit| self.constant(bit)).collect()
+    }
+}
+
+impl BitEvaluator for BfvBitEvaluator<'_> {
+    type Bit = Ciphertext;
+
+    fn constant(&self, bit: bool) -> Result<Ciphertext> {
+        let plaintext = self.bfv.encode(&[i64::from(bit)])?;
+        self.bfv.encrypt(self.public_key, &plaintext)
+    }
+
+    fn xor(&self, a: &Ciphertext, b: &Ciphertext) -> Result<Ciphertext> {
+        let cc = self.bfv.crypto_context();
+        let ab = self.and(a, b)?;
+        cc.eval_sub(&cc.eval_add(a, b)?, &cc.eval_add(&ab, &ab)?)
+    }
+
+    fn xor_const(&self, a: &Ciphertext, b: bool) -> Result<Ciphertext> {
+        if !b {
+            return Ok(a.clone());
+        }
+        let cc = self.bfv.crypto_context();
+        cc.eval_add_plaintext(&cc.eval_negate(a)?, &self.bfv.encode(&[1])?)
+    }
+
+    fn and(&self, a: &Ciphertext, b: &Ciphertext) -> Result<Ciphertext> {
+        let cc = self.bfv.crypto_context();
+        cc.relinearize(&cc.eval_mult_no_relin(self.bfv.crypto_parameters(), a, b)?)
+    }
+}
+
+/// A stream cipher whose keystream can be evaluated homomorphically
+pub trait TranscipherCipher {
+    /// Number of key bits
+    fn key_bits(&self) -> usize;
+    /// Number of keystream bits produced per block
+    fn block_bits(&self) -> usize;
+    /// Number of `and` gates along the longest path, i.e. the multiplicative
+    /// depth where `xor` is free
+    fn depth(&self) -> usize;
+    /// Evaluate keystream block `counter` for `nonce` under `key`
+    fn keystream<E: BitEvaluator>(
+        &self,
+        eval: &E,
+        key: &[E::Bit],
+        nonce: u64,
+        counter: u64,
+    ) -> Result<Vec<E::Bit>>;
+}
+
+/// Encrypt `message` bits in the clear with the symmetric cipher
+pub fn symmetric_encrypt<C: TranscipherCipher>(
+    cipher: &C,
+    key: &[bool],
+    nonce: u64,
+    message: &[bool],
+) -> Result<Vec<bool>> {
+    transcipher(cipher, &PlainBitEvaluator, key, nonce, message)
+}
+
+/// Turn symmetric ciphertext bits into `E::Bit`s of the message.
+///
+/// `key` is the symmetric key as seen by `eval`, i.e. encrypted under FHE on
+/// the server. Decryption in the clear is the same call with [`PlainBitEvaluator`].
+pub fn transcipher<C: TranscipherCipher, E: BitEvaluator>(
+    cipher: &C,
+    eval: &E,
+    key: &[E::Bit],
+    nonce: u64,
+    ciphertext: &[bool],
+) -> Result<Vec<E::Bit>> {
+    if key.len() != cipher.key_bits() {
+        return Err(Error::TranscipherKeyLength {
+            expected: cipher.key_bits(),
+            actual: key.len(),
+        });
+    }
+    let mut out = Vec::with_capacity(ciphertext.len());
+    for (counter, chunk) in ciphertext.chunks(cipher.block_bits()).enumerate() {
+        let stream = cipher.keystream(eval, key, nonce, counter as u64)?;
+        for (k, c) in stream.iter().zip(chunk) {
+            out.push(eval.xor_const(k, *c)?);
+        }
+    }
+    Ok(out)
+}
+
+/// Parameters of a [`LowMcLike`] cipher
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LowMcParams {
+    /// State size in bits
+    pub state_bits: usize,
+    /// Key size in bits
+    pub key_bits: usize,
+    /// Number of 3-bit S-boxes per round, the rest of the state passes through
+    pub sboxes: usize,
+    /// Number of rounds
+    pub rounds: usize,
+}
+
+/// A LowMC shaped SPN used in counter mode.
+///
+/// Each round applies a partial layer of LowMC S-boxes, a dense linear layer,
+/// a round constant and a round key. The matrices and constants are expanded
+/// from a public seed with ChaCha20 rather than the Grain LFSR of the LowMC
+/// reference, so this is a hook to plug real instances into, not a vetted
+/// instance itself.
+#[derive(Clone, Debug)]
+pub struct LowMcLike {
+    params: LowMcParams,
+    linear: Vec<Vec<Vec<bool>>>,
+    constants: Vec<Vec<bool>>,
+    key_matrices: Vec<Vec<Vec<bool>>>,
+}
+
+impl LowMcLike {
+    /// Expand the round matrices and constants from `seed`
+    pub fn new(params: LowMcParams, seed: [u8; 32]) -> Self {
+        assert!(3 * params.sboxes <= params.state_bits);
+        let mut rng = ChaCha20Rng::from_seed(seed);
+        let mut matrix = |rows: usize, cols: usize| -> Vec<Vec<bool>> {
+            (0..rows)
+                .map(|_| (0..cols).map(|_| rng.random()).collect())
+                .collect()
+        };
+        let linear = (0..params.rounds)
+            .map(|_| matrix(params.state_bits, params.state_bits))
+            .collect();
+        let key_matrices = (0..=params.rounds)
+            .map(|_| matrix(params.state_bits, params.key_bits))
+            .collect();
+        let constants = (0..params.rounds)
+            .map(|_| (0..params.state_bits).map(|_| rng.random()).collect())
+            .collect();
+        Self {
+            params,
+            linear,
+            constants,
+            key_matrices,
+        }
+    }
+
+    /// The cipher parameters
+    pub fn params(&self) -> &LowMcParams {
+        &self.params
+    }
+
+    /// `m v`, each row summed in a balanced tree so evaluators whose `xor`
+    /// costs a level only spend `ceil(log2(columns))` on it
+    fn mat_vec<E: BitEvaluator>(eval: &E, m: &[Vec<bool>], v: &[E::Bit]) -> Result<Vec<E::Bit>> {
+        m.iter()
+            .map(|row| {
+                let mut terms = row
+                    .iter()
+                    .zip(v)
+                    .filter(|(bit, _)| **bit)
+                    .map(|(_, x)| x.clone())
+                    .collect::<Vec<_>>();
+                while terms.len() > 1 {
+                    terms = terms
+                        .chunks(2)
+                        .map(|pair| match pair {
+                            [a, b] => eval.xor(a, b),
+                            _ => Ok(pair[0].clone()),
+                        })
+                        .collect::<Result<_>>()?;
+                }
+                match terms.pop() {
+                    Some(sum) => Ok(sum),
+                    None => eval.constant(false),
+                }
+            })
+            .collect()
+    }
+
+    fn sbox_layer<E: BitEvaluator>(&self, eval: &E, state: &mut [E::Bit]) -> Result<()> {
+        for s in 0..self.params.sboxes {
+            let (a, b, c) = (&state[3 * s], &state[3 * s + 1], &state[3 * s + 2]);
+            let bc = eval.and(b, c)?;
+            let ca = eval.and(c, a)?;
+            let ab = eval.and(a, b)?;
+            let a_out = eval.xor(a, &bc)?;
+            let a_b = eval.xor(a, b)?;
+            let b_out = eval.xor(&a_b, &ca)?;
+            let c_out = eval.xor(&eval.xor(&a_b, c)?, &ab)?;
+            state[3 * s] = a_out;
+            state[3 * s + 1] = b_out;
+            state[3 * s + 2] = c_out;
+        }
+        Ok(())
+    }
+}
+
+impl TranscipherCipher for LowMcLike {
+    fn key_bits(&self) -> usize {
+        self.params.key_bits
+    }
+
+    fn block_bits(&self) -> usize {
+        self.params.state_bits
+    }
+
+    fn depth(&self) -> usize {
+        self.params.rounds
+    }
+
+    fn keystream<E: BitEvaluator>(
+        &self,
+        eval: &E,
+        key: &[E::Bit],
+        nonce: u64,
+        counter: u64,
+    ) -> Result<Vec<E::Bit>> {
+        let input = nonce as u128 | ((counter as u128) << 64);
+        let mut state = Self::mat_vec(eval, &self.key_matrices[0], key)?;
+        for (i, s) in state.iter_mut().enumerate() {
+            *s = eval.xor_const(s, i < 128 && (input >> i) & 1 == 1)?;
+        }
+
+        for round in 0..self.params.rounds {
+            self.sbox_layer(eval, &mut state)?;
+            state = Self::mat_vec(eval, &self.linear[round], &state)?;
+            let round_key = Self::mat_vec(eval, &self.key_matrices[round + 1], key)?;
+            state = state
+                .iter()
+                .zip(&round_key)
+                .zip(&self.constants[round])
+                .map(|((s, k), c)| eval.xor_const(&eval.xor(s, k)?, *c))
+                .collect::<Result<_>>()?;
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::PlaintextEncodings;
+    use crate::pke::test_support::bfv_context;
+
+    /// Tracks the multiplicative depth of a bit where `xor` costs a level
+    struct DepthBitEvaluator;
+
+    impl BitEvaluator for DepthBitEvaluator {
+        type Bit = usize;
+
+        fn constant(&self, _: bool) -> Result<usize> {
+            Ok(0)
+        }
+
+        fn xor(&self, a: &usize, b: &usize) -> Result<usize> {
+            Ok(a.max(b) + 1)
+        }
+
+        fn xor_const(&self, a: &usize, _: bool) -> Result<usize> {
+            Ok(*a)
+        }
+
+        fn and(&self, a: &usize, b: &usize) -> Result<usize> {
+            Ok(a.max(b) + 1)
+        }
+    }
+
+    #[test]
+    fn transcipher_bfv() {
+        let params = LowMcParams {
+            state_bits: 3,
+            key_bits: 3,
+            sboxes: 1,
+            rounds: 1,
+        };
+        let cipher = LowMcLike::new(params, [3; 32]);
+        let key = [true, false, true];
+        let message = [false, true, true];
+        let nonce = 17;
+        let symmetric = symmetric_encrypt(&cipher, &key, nonce, &message).expect("encrypt");
+        let depth = transcipher(&cipher, &DepthBitEvaluator, &[0; 3], nonce, &symmetric)
+            .expect("depth")
+            .into_iter()
+            .max()
+            .unwrap_or(0);
+
+        let bfv = bfv_context(depth);
+        let key_pair = bfv.key_gen().expect("keys");
+        bfv.eval_mult_key_gen(key_pair.private_key())
+            .expect("relinearization key");
+        let eval = BfvBitEvaluator::new(&bfv, key_pair.public_key());
+        let encrypted_key = eval.encrypt(&key).expect("encrypt key");
+
+        let bits =
+            transcipher(&cipher, &eval, &encrypted_key, nonce, &symmetric).expect("transcipher");
+        let decrypted = bits
+            .iter()
+            .map(|bit| match bfv.decrypt(key_pair.private_key(), bit) {
+                Ok(PlaintextEncodings::Coefficient(p)) => p.coefficients()[0] == 1,
+                other => panic!("unexpected decryption {other:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(decrypted, message);
+    }
+}